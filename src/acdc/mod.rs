@@ -0,0 +1,299 @@
+//! Authentic Chained Data Containers
+//!
+//! Credentials identified by their SAID. Issuance is recorded in
+//! issuer's credential registry (TEL), which is anchored in issuer KEL.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    derivation::self_addressing::SelfAddressing,
+    error::Error,
+    event::{EventMessage, SerializationFormats},
+    prefix::{IdentifierPrefix, Prefix, SelfAddressingPrefix},
+    sad,
+    tel::{
+        event::TelEvent,
+        processor::TelProcessor,
+        state::{ManagerTelState, VcStatus},
+    },
+};
+
+/// Credential
+///
+/// ACDC with issuer, registry and schema SAID. Attributes are kept as
+/// arbitrary JSON value. Only JSON serialization is supported.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Credential {
+    #[serde(rename = "v")]
+    pub version: String,
+
+    #[serde(rename = "d")]
+    pub digest: SelfAddressingPrefix,
+
+    #[serde(rename = "i")]
+    pub issuer: IdentifierPrefix,
+
+    #[serde(rename = "ri")]
+    pub registry_id: IdentifierPrefix,
+
+    #[serde(rename = "s")]
+    pub schema: SelfAddressingPrefix,
+
+    #[serde(rename = "a")]
+    pub attributes: Value,
+}
+
+/// Credential Body
+///
+/// Fields of credential following its SAID, used for SAID computation.
+#[derive(Serialize)]
+struct CredentialBody<'a> {
+    #[serde(rename = "i")]
+    issuer: &'a IdentifierPrefix,
+    #[serde(rename = "ri")]
+    registry_id: &'a IdentifierPrefix,
+    #[serde(rename = "s")]
+    schema: &'a SelfAddressingPrefix,
+    #[serde(rename = "a")]
+    attributes: &'a Value,
+}
+
+fn version_string(size: usize) -> String {
+    format!("ACDC10JSON{:06x}_", size)
+}
+
+impl Credential {
+    pub fn new(
+        issuer: &IdentifierPrefix,
+        registry_id: &IdentifierPrefix,
+        schema: &SelfAddressingPrefix,
+        attributes: Value,
+        derivation: &SelfAddressing,
+    ) -> Result<Self, Error> {
+        let mut credential = Self {
+            version: version_string(0),
+            digest: SelfAddressingPrefix::default(),
+            issuer: issuer.clone(),
+            registry_id: registry_id.clone(),
+            schema: schema.clone(),
+            attributes,
+        };
+        let (version, digest) = sad::compute_versioned_said(
+            version_string,
+            None,
+            &credential.body(),
+            derivation,
+            SerializationFormats::JSON,
+        )?;
+        credential.version = version;
+        credential.digest = digest;
+        Ok(credential)
+    }
+
+    fn body(&self) -> CredentialBody<'_> {
+        CredentialBody {
+            issuer: &self.issuer,
+            registry_id: &self.registry_id,
+            schema: &self.schema,
+            attributes: &self.attributes,
+        }
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Checks if credential SAID matches its content.
+    pub fn verify_said(&self) -> Result<bool, Error> {
+        Ok(self.version == version_string(self.serialize()?.len())
+            && sad::verify_versioned_said(
+                &self.version,
+                None,
+                &self.digest,
+                &self.body(),
+                SerializationFormats::JSON,
+            )?)
+    }
+
+    /// Issuance event
+    ///
+    /// Returns TEL issuance event of the credential, which should be
+    /// anchored in issuer KEL and processed by TEL processor.
+    pub fn issuance_event(
+        &self,
+        registry: &ManagerTelState,
+        derivation: &SelfAddressing,
+    ) -> Result<EventMessage<TelEvent>, Error> {
+        if registry.issuer != self.issuer || registry.prefix != self.registry_id {
+            return Err(Error::SemanticError(
+                "Credential doesn't belong to registry".into(),
+            ));
+        }
+        TelEvent::new_issuance(
+            registry,
+            &self.digest,
+            SerializationFormats::JSON,
+            derivation,
+        )
+    }
+}
+
+/// Verify credential
+///
+/// Checks credential SAID and walks from issuer KEL through the seal of
+/// the TEL issuance event to the credential SAID. Returns current status
+/// of the credential in its registry.
+pub fn verify_credential(tel: &TelProcessor, credential: &Credential) -> Result<VcStatus, Error> {
+    if !credential.verify_said()? {
+        return Err(Error::IncorrectDigest);
+    }
+    let vc_id = IdentifierPrefix::SelfAddressing(credential.digest.clone());
+    let registry = tel
+        .get_management_tel_state(&credential.registry_id)?
+        .ok_or(Error::EventOutOfOrderError)?;
+    if registry.issuer != credential.issuer {
+        return Err(Error::SemanticError(
+            "Credential issuer doesn't match registry issuer".into(),
+        ));
+    }
+    let issuance =
+        tel.get_tel(&vc_id).into_iter().next().ok_or_else(|| {
+            Error::SemanticError(format!("Credential {} not issued", vc_id.to_str()))
+        })?;
+    tel.validate_anchor(&credential.issuer, &issuance)?;
+    let state = tel
+        .get_vc_state(&vc_id)?
+        .ok_or(Error::EventOutOfOrderError)?;
+    if state.registry_id != credential.registry_id {
+        return Err(Error::SemanticError(
+            "Credential issued in other registry".into(),
+        ));
+    }
+    Ok(state.status)
+}
+
+#[test]
+fn test_credential_issuance() -> Result<(), Error> {
+    use crate::{
+        database::sled::SledEventDatabase,
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event::sections::seal::{Seal, SourceSeal},
+        event_message::{
+            event_msg_builder::EventMsgBuilder, signed_event_message::Message, EventTypeTag,
+        },
+        prefix::AttachedSignaturePrefix,
+        processor::EventProcessor,
+        signer::{CryptoBox, KeyManager},
+        tel::{database::SledTelDatabase, event::VerifiableTelEvent, event::NO_BACKERS},
+    };
+    use std::sync::Arc;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let kel_db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let tel_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let tel_db = Arc::new(SledTelDatabase::new(tel_root.path()).unwrap());
+    let kel_processor = EventProcessor::new(Arc::clone(&kel_db));
+    let tel_processor = TelProcessor::new(Arc::clone(&kel_db), tel_db);
+
+    let km = CryptoBox::new()?;
+    let sign = |event: EventMessage<_>| -> Result<Message, Error> {
+        let signature = km.sign(&event.serialize()?)?;
+        Ok(Message::Event(event.sign(
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+            None,
+        )))
+    };
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let issuer = icp.event.get_prefix();
+    kel_processor.process(sign(icp)?)?;
+
+    // Anchors TEL event in issuer KEL and processes it.
+    let anchor_and_process = |tel_event: EventMessage<TelEvent>| -> Result<(), Error> {
+        let state = kel_processor.compute_state(&issuer)?.unwrap();
+        let ixn = EventMsgBuilder::new(EventTypeTag::Ixn)
+            .with_prefix(&issuer)
+            .with_sn(state.sn + 1)
+            .with_previous_event(&state.last_event_digest)
+            .with_seal(vec![Seal::Event(tel_event.to_seal())])
+            .build()?;
+        let seal = SourceSeal::new(state.sn + 1, ixn.get_digest());
+        kel_processor.process(sign(ixn)?)?;
+        tel_processor.process(VerifiableTelEvent::new(tel_event, seal))?;
+        Ok(())
+    };
+
+    let vcp = TelEvent::new_registry_inception(
+        &issuer,
+        vec![NO_BACKERS.into()],
+        0,
+        vec![],
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?;
+    let registry_id = vcp.event.get_prefix();
+    anchor_and_process(vcp)?;
+    let registry = tel_processor
+        .get_management_tel_state(&registry_id)?
+        .unwrap();
+
+    let schema = SelfAddressing::Blake3_256.derive(br#"{"type":"object"}"#);
+    let credential = Credential::new(
+        &issuer,
+        &registry_id,
+        &schema,
+        serde_json::json!({"name": "Alice", "score": 10}),
+        &SelfAddressing::Blake3_256,
+    )?;
+    assert!(credential.version.starts_with("ACDC10JSON"));
+    assert_eq!(
+        usize::from_str_radix(&credential.version[10..16], 16).unwrap(),
+        credential.serialize()?.len()
+    );
+    let parsed: Credential = serde_json::from_slice(&credential.serialize()?)?;
+    assert!(parsed.verify_said()?);
+
+    // Not issued credential can't be verified.
+    assert!(verify_credential(&tel_processor, &credential).is_err());
+
+    anchor_and_process(credential.issuance_event(&registry, &SelfAddressing::Blake3_256)?)?;
+    assert_eq!(
+        verify_credential(&tel_processor, &credential)?,
+        VcStatus::Issued
+    );
+
+    // Tampered attributes break the SAID.
+    let tampered = Credential {
+        attributes: serde_json::json!({"name": "Alice", "score": 100}),
+        ..credential.clone()
+    };
+    assert!(!tampered.verify_said()?);
+    assert!(matches!(
+        verify_credential(&tel_processor, &tampered),
+        Err(Error::IncorrectDigest)
+    ));
+
+    let vc_state = tel_processor
+        .get_vc_state(&IdentifierPrefix::SelfAddressing(credential.digest.clone()))?
+        .unwrap();
+    anchor_and_process(TelEvent::new_revocation(
+        &registry,
+        &vc_state,
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?)?;
+    assert_eq!(
+        verify_credential(&tel_processor, &credential)?,
+        VcStatus::Revoked
+    );
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+//! keriox-cli
+//!
+//! Command line tool for inspecting KELs and creating events, built on
+//! the keri library. KELs and aliases are kept in sled database in
+//! `--db` directory, `./keriox-db` by default. Keys are kept in
+//! encrypted keystore next to it and unlocked with passphrase from
+//! `KERIOX_PASSPHRASE` environment variable.
+
+use std::{
+    convert::TryFrom,
+    env, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+};
+
+use keri::{
+    database::sled::SledEventDatabase,
+    directory::AliasRecord,
+    error::Error,
+    event_message::{signed_event_message::Message, Typeable},
+    event_parsing::{message::signed_event_stream, SignedEventData},
+    keri::Keri,
+    keystore::{KeySeeds, Keystore},
+    prefix::{BasicPrefix, IdentifierPrefix, Prefix},
+    processor::EventProcessor,
+    signer::CryptoBox,
+};
+
+const USAGE: &str = "\
+Usage: keriox-cli [--db <dir>] <command> [<args>]
+
+Commands:
+  incept [--alias <alias>] [--witness <prefix>]...
+                          incept identifier and print its prefix
+  rotate <id>             rotate keys and print rotation event
+  sign <id> [<file>]      sign file or stdin and print signatures
+  kel <id>                print KEL of identifier
+  parse [<file>]          pretty-print CESR stream from file or stdin
+  process [<file>]        process CESR stream into database
+  verify-receipts [<file>]
+                          process CESR stream and check its receipts
+
+<id> is identifier prefix or its alias.
+";
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+fn main() {
+    if let Err(e) = run(env::args().skip(1).collect()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: Vec<String>) -> CliResult<()> {
+    let mut dir = PathBuf::from("keriox-db");
+    let mut args = args.into_iter();
+    let mut rest = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => dir = args.next().ok_or("--db requires directory")?.into(),
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return Ok(());
+            }
+            _ => rest.push(arg),
+        }
+    }
+    let (command, args) = match rest.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Err(USAGE.into()),
+    };
+    match (command, args) {
+        ("incept", args) => incept(&dir, args),
+        ("rotate", [id]) => rotate(&dir, id),
+        ("sign", [id, input @ ..]) if input.len() <= 1 => sign(&dir, id, input.first()),
+        ("kel", [id]) => kel(&dir, id),
+        ("parse", input) if input.len() <= 1 => parse(&read_input(input.first())?),
+        ("process", input) if input.len() <= 1 => {
+            process_stream(&dir, &read_input(input.first())?, false)
+        }
+        ("verify-receipts", input) if input.len() <= 1 => {
+            process_stream(&dir, &read_input(input.first())?, true)
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn incept(dir: &Path, args: &[String]) -> CliResult<()> {
+    let (mut alias, mut witnesses) = (None, vec![]);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--alias", Some(value)) => alias = Some(value.clone()),
+            ("--witness", Some(value)) => witnesses.push(value.parse::<BasicPrefix>()?),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let passphrase = passphrase()?;
+    let db = open_db(dir)?;
+    let key_manager = CryptoBox::new()?;
+    let seeds = KeySeeds::from(&key_manager);
+    let mut controller = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(key_manager)))?;
+    controller.incept(Some(witnesses))?;
+    let prefix = controller.prefix().clone();
+    open_keystore(dir)?.store(&prefix, &seeds, &passphrase)?;
+    if let Some(alias) = alias {
+        db.add_alias(AliasRecord::new(&alias, prefix.clone()))?;
+    }
+    println!("{}", prefix.to_str());
+    Ok(())
+}
+
+fn rotate(dir: &Path, id: &str) -> CliResult<()> {
+    let passphrase = passphrase()?;
+    let db = open_db(dir)?;
+    let prefix = resolve(&db, id)?;
+    let keystore = open_keystore(dir)?;
+    let key_manager = Arc::new(Mutex::new(
+        keystore.unlock(&prefix, &passphrase)?.key_manager()?,
+    ));
+    let mut controller = Keri::open(db, Arc::clone(&key_manager), prefix.clone())?;
+    let rot = controller.rotate()?;
+    let seeds = KeySeeds::from(&*key_manager.lock().map_err(|_| Error::MutexPoisoned)?);
+    keystore.store(&prefix, &seeds, &passphrase)?;
+    write_stream(&SignedEventData::from(&rot).to_cesr()?)
+}
+
+fn sign(dir: &Path, id: &str, input: Option<&String>) -> CliResult<()> {
+    let passphrase = passphrase()?;
+    let db = open_db(dir)?;
+    let prefix = resolve(&db, id)?;
+    let key_manager = open_keystore(dir)?
+        .unlock(&prefix, &passphrase)?
+        .key_manager()?;
+    let controller = Keri::open(db, Arc::new(Mutex::new(key_manager)), prefix)?;
+    for signature in controller.sign(&read_input(input)?)? {
+        println!("{}", signature.to_str());
+    }
+    Ok(())
+}
+
+fn kel(dir: &Path, id: &str) -> CliResult<()> {
+    let db = open_db(dir)?;
+    let prefix = resolve(&db, id)?;
+    let kel = EventProcessor::new(db)
+        .get_kerl(&prefix)?
+        .ok_or(Error::UnknownIdentifier(prefix))?;
+    write_stream(&kel)
+}
+
+fn parse(stream: &[u8]) -> CliResult<()> {
+    for (i, message) in parse_stream(stream)?.iter().enumerate() {
+        println!("--- message {}", i);
+        let event = message.deserialized_event.serialize()?;
+        println!("{}", serde_json::to_string_pretty(&to_json(&event)?)?);
+        for attachment in &message.attachments {
+            println!("{}", attachment.to_cesr()?);
+        }
+    }
+    Ok(())
+}
+
+/// Processes messages of the stream in order and prints outcome of
+/// each, or of receipts only if `receipts_only` is set. Fails if any
+/// reported message was rejected.
+fn process_stream(dir: &Path, stream: &[u8], receipts_only: bool) -> CliResult<()> {
+    let processor = EventProcessor::new(open_db(dir)?);
+    let mut rejected = 0;
+    for message in parse_stream(stream)? {
+        let message = Message::try_from(message)?;
+        let is_receipt = matches!(
+            message,
+            Message::NontransferableRct(_) | Message::TransferableRct(_) | Message::WitnessRct(_)
+        );
+        let description = describe(&message);
+        let result = processor.process(message);
+        if receipts_only && !is_receipt {
+            continue;
+        }
+        match result {
+            Ok(_) => println!("ok       {}", description),
+            Err(e) => {
+                rejected += 1;
+                println!("rejected {}: {}", description, e);
+            }
+        }
+    }
+    match rejected {
+        0 => Ok(()),
+        n => Err(format!("{} message(s) rejected", n).into()),
+    }
+}
+
+fn describe(message: &Message) -> String {
+    let (tag, prefix, sn) = match message {
+        Message::Event(ev) => {
+            let event = &ev.event_message.event;
+            (event.get_type(), event.get_prefix(), event.get_sn())
+        }
+        Message::NontransferableRct(rct) => (
+            rct.body.event.get_type(),
+            rct.body.event.prefix.clone(),
+            rct.body.event.sn,
+        ),
+        Message::TransferableRct(rct) => (
+            rct.body.event.get_type(),
+            rct.body.event.prefix.clone(),
+            rct.body.event.sn,
+        ),
+        Message::WitnessRct(rct) => (
+            rct.body.event.get_type(),
+            rct.body.event.prefix.clone(),
+            rct.body.event.sn,
+        ),
+        #[cfg(feature = "query")]
+        _ => return "message".into(),
+    };
+    format!("{} {} of {}", tag.as_str(), sn, prefix.to_str())
+}
+
+fn parse_stream(stream: &[u8]) -> CliResult<Vec<SignedEventData>> {
+    match signed_event_stream(stream) {
+        Ok((rest, messages)) if rest.is_empty() => Ok(messages),
+        Ok((rest, _)) => {
+            Err(format!("can't parse stream at byte {}", stream.len() - rest.len()).into())
+        }
+        Err(e) => Err(e.to_string().into()),
+    }
+}
+
+/// Decodes event in any serialization format as JSON value.
+fn to_json(event: &[u8]) -> CliResult<serde_json::Value> {
+    serde_json::from_slice(event)
+        .ok()
+        .or_else(|| serde_cbor::from_slice(event).ok())
+        .or_else(|| rmp_serde::from_read_ref(event).ok())
+        .ok_or_else(|| "unknown serialization format".into())
+}
+
+fn resolve(db: &SledEventDatabase, id: &str) -> CliResult<IdentifierPrefix> {
+    match db.resolve_alias(id)? {
+        Some(prefix) => Ok(prefix),
+        None => Ok(id.parse()?),
+    }
+}
+
+fn open_db(dir: &Path) -> CliResult<Arc<SledEventDatabase>> {
+    Ok(Arc::new(SledEventDatabase::new(dir.join("kel").as_path())?))
+}
+
+fn open_keystore(dir: &Path) -> CliResult<Keystore> {
+    Ok(Keystore::new(&dir.join("keys"))?)
+}
+
+fn passphrase() -> CliResult<Vec<u8>> {
+    env::var("KERIOX_PASSPHRASE")
+        .map(String::into_bytes)
+        .map_err(|_| "KERIOX_PASSPHRASE is not set".into())
+}
+
+fn read_input(file: Option<&String>) -> CliResult<Vec<u8>> {
+    match file {
+        Some(file) => Ok(fs::read(file)?),
+        None => {
+            let mut input = vec![];
+            io::stdin().read_to_end(&mut input)?;
+            Ok(input)
+        }
+    }
+}
+
+fn write_stream(stream: &[u8]) -> CliResult<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(stream)?;
+    stdout.write_all(b"\n")?;
+    Ok(())
+}
@@ -0,0 +1,313 @@
+//! In-memory database
+//!
+//! `EventDatabase` kept in memory only, for short-lived processors,
+//! eg. verifying KEL received from the other party, tests, or targets
+//! without filesystem like wasm.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Local};
+
+#[cfg(feature = "query")]
+use crate::query::reply::SignedReply;
+use crate::{
+    database::{Escrowed, EventDatabase, FirstSeen, Subscription},
+    error::Error,
+    event_message::signed_event_message::{
+        SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+        TimestampedSignedEventMessage,
+    },
+    prefix::{IdentifierPrefix, Prefix, SelfAddressingPrefix},
+    processor::audit::AuditRecord,
+};
+
+/// Values kept in insertion order, by qb64 of identifier.
+struct Table<T>(Mutex<HashMap<String, Vec<T>>>);
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+}
+
+impl<T: Clone + 'static> Table<T> {
+    fn push(&self, id: &IdentifierPrefix, value: T) -> Result<(), Error> {
+        let mut table = self.0.lock().map_err(|_| Error::MutexPoisoned)?;
+        table.entry(id.to_str()).or_default().push(value);
+        Ok(())
+    }
+
+    fn get(&self, id: &IdentifierPrefix) -> Option<Box<dyn DoubleEndedIterator<Item = T>>> {
+        let table = self.0.lock().ok()?;
+        table
+            .get(&id.to_str())
+            .map(|values| Box::new(values.clone().into_iter()) as _)
+    }
+
+    fn all(&self) -> Option<Box<dyn DoubleEndedIterator<Item = T>>> {
+        let table = self.0.lock().ok()?;
+        let values: Vec<T> = table.values().flatten().cloned().collect();
+        Some(Box::new(values.into_iter()))
+    }
+
+    fn retain(&self, id: &IdentifierPrefix, f: impl FnMut(&T) -> bool) -> Result<(), Error> {
+        let mut table = self.0.lock().map_err(|_| Error::MutexPoisoned)?;
+        if let Some(values) = table.get_mut(&id.to_str()) {
+            values.retain(f);
+        }
+        Ok(())
+    }
+}
+
+/// Memory Database
+///
+/// Keeps KELs, receipts, escrows and replies in hash maps. Nothing is
+/// persisted, so its content is lost when it's dropped.
+#[derive(Default)]
+pub struct MemoryDatabase {
+    kel: Table<TimestampedSignedEventMessage>,
+    first_seen: Table<FirstSeen>,
+    duplicitous: Table<SignedEventMessage>,
+    receipts_t: Table<SignedTransferableReceipt>,
+    receipts_nt: Table<SignedNontransferableReceipt>,
+    escrowed_receipts_t: Table<Escrowed<SignedTransferableReceipt>>,
+    escrowed_receipts_nt: Table<Escrowed<SignedNontransferableReceipt>>,
+    subscriptions: Table<Subscription>,
+    audit_trail: Mutex<Vec<AuditRecord>>,
+    #[cfg(feature = "query")]
+    accepted_replys: Table<SignedReply>,
+    #[cfg(feature = "query")]
+    escrowed_replys: Table<Escrowed<SignedReply>>,
+}
+
+impl MemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EventDatabase for MemoryDatabase {
+    fn add_kel_finalized_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<(), Error> {
+        self.kel
+            .push(id, TimestampedSignedEventMessage::new_at(event, timestamp))
+    }
+
+    fn get_kel_finalized_events(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = TimestampedSignedEventMessage>>> {
+        self.kel.get(id)
+    }
+
+    fn remove_kel_finalized_event(
+        &self,
+        id: &IdentifierPrefix,
+        event: &SignedEventMessage,
+    ) -> Result<(), Error> {
+        self.kel
+            .retain(id, |stored| &stored.signed_event_message != event)
+    }
+
+    fn get_event_by_digest(
+        &self,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<TimestampedSignedEventMessage> {
+        let kels = self.kel.0.lock().ok()?;
+        kels.values()
+            .flatten()
+            .find(|event| &event.signed_event_message.event_message.get_digest() == digest)
+            .cloned()
+    }
+
+    fn add_first_seen(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+        digest: SelfAddressingPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<FirstSeen, Error> {
+        let record = FirstSeen {
+            ordinal: self.first_seen.get(id).map_or(0, |r| r.count() as u64),
+            sn,
+            digest,
+            timestamp,
+        };
+        self.first_seen.push(id, record.clone())?;
+        Ok(record)
+    }
+
+    fn get_first_seen_records(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = FirstSeen>>> {
+        self.first_seen.get(id)
+    }
+
+    fn add_duplicious_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.duplicitous.push(id, event)
+    }
+
+    fn add_receipt_t(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.receipts_t.push(id, receipt)
+    }
+
+    fn get_receipts_t(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.receipts_t.get(id)
+    }
+
+    fn add_receipt_nt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.receipts_nt.push(id, receipt)
+    }
+
+    fn get_receipts_nt(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.receipts_nt.get(id)
+    }
+
+    fn add_escrow_t_receipt(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_t.push(id, Escrowed::new(receipt))
+    }
+
+    fn get_escrow_t_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.escrowed_receipts_t
+            .get(id)
+            .map(|receipts| Box::new(receipts.map(|escrowed| escrowed.value)) as _)
+    }
+
+    fn remove_escrow_t_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedTransferableReceipt,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_t
+            .retain(id, |escrowed| &escrowed.value != receipt)
+    }
+
+    fn get_all_escrow_t_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedTransferableReceipt>>>> {
+        self.escrowed_receipts_t.all()
+    }
+
+    fn add_escrow_nt_receipt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_nt.push(id, Escrowed::new(receipt))
+    }
+
+    fn get_escrow_nt_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.escrowed_receipts_nt
+            .get(id)
+            .map(|receipts| Box::new(receipts.map(|escrowed| escrowed.value)) as _)
+    }
+
+    fn remove_escrow_nt_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedNontransferableReceipt,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_nt
+            .retain(id, |escrowed| &escrowed.value != receipt)
+    }
+
+    fn get_all_escrow_nt_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedNontransferableReceipt>>>> {
+        self.escrowed_receipts_nt.all()
+    }
+
+    fn update_subscription(&self, subscription: Subscription) -> Result<(), Error> {
+        let id = subscription.prefix.clone();
+        self.subscriptions
+            .retain(&id, |s| s.source != subscription.source)?;
+        self.subscriptions.push(&id, subscription)
+    }
+
+    fn get_subscription(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Option<Subscription> {
+        self.subscriptions.get(id)?.find(|s| &s.source == source)
+    }
+
+    fn add_audit_record(&self, record: AuditRecord) -> Result<(), Error> {
+        self.audit_trail
+            .lock()
+            .map_err(|_| Error::MutexPoisoned)?
+            .push(record);
+        Ok(())
+    }
+
+    fn get_audit_trail(&self) -> Option<Box<dyn DoubleEndedIterator<Item = AuditRecord>>> {
+        let records = self.audit_trail.lock().ok()?.clone();
+        Some(Box::new(records.into_iter()))
+    }
+
+    #[cfg(feature = "query")]
+    fn update_accepted_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.accepted_replys
+            .retain(id, |s| !s.reply.event.is_same_subject(&rpy.reply.event))?;
+        self.accepted_replys.push(id, rpy)
+    }
+
+    #[cfg(feature = "query")]
+    fn get_accepted_replys(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedReply>>> {
+        self.accepted_replys.get(id)
+    }
+
+    #[cfg(feature = "query")]
+    fn add_escrowed_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.escrowed_replys.push(id, Escrowed::new(rpy))
+    }
+
+    #[cfg(feature = "query")]
+    fn get_all_escrowed_replys(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedReply>>>> {
+        self.escrowed_replys.all()
+    }
+
+    #[cfg(feature = "query")]
+    fn remove_escrowed_reply(&self, id: &IdentifierPrefix, rpy: SignedReply) -> Result<(), Error> {
+        self.escrowed_replys
+            .retain(id, |escrowed| escrowed.value != rpy)
+    }
+}
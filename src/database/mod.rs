@@ -1,250 +1,310 @@
+#[cfg(feature = "query")]
+use crate::query::reply::SignedReply;
 use crate::{
-    event_parsing::message::message,
-    prefix::{
-        AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, SelfAddressingPrefix,
-        SelfSigningPrefix,
+    error::Error,
+    event_message::signed_event_message::{
+        SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+        TimestampedSignedEventMessage,
     },
-    state::IdentifierState,
+    prefix::{IdentifierPrefix, SelfAddressingPrefix},
+    processor::audit::AuditRecord,
 };
-#[cfg(feature = "lmdb")]
-pub mod lmdb;
 
+pub mod memory;
+#[cfg(feature = "postgres-db")]
+pub mod postgres;
+#[cfg(feature = "rocks-db")]
+pub mod rocks;
 #[cfg(feature = "sled-db")]
 pub mod sled;
-/// Event Database
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// First Seen
 ///
-/// An Abstract model of state for Key Events,
-/// Signatures and Receipts.
-pub trait EventDatabase {
-    type Error;
-
-    /// Last Event At SN
-    ///
-    /// Returns the raw bytes of the last event inserted
-    /// for a given identifier at a given sequence number
-    /// (rotation events can supercede interaction events
-    /// with the same sn)
-    /// TODO: see if it can return a Result<Option<&'a [u8]>_>
-    fn last_event_at_sn(
-        &self,
-        pref: &IdentifierPrefix,
-        sn: u64,
-    ) -> Result<Option<Vec<u8>>, Self::Error>;
-
-    /// Get State for Prefix
-    ///
-    /// Returns the current State associated with
-    /// the given Prefix
-    fn get_state_for_prefix(
-        &self,
-        pref: &IdentifierPrefix,
-    ) -> Result<Option<IdentifierState>, Self::Error> {
-        // start with empty state
-        let mut state = IdentifierState::default();
-
-        // starting from inception
-        for sn in 0.. {
-            // read the latest raw event
-            let raw = match self.last_event_at_sn(pref, sn)? {
-                Some(r) => r,
-                None => {
-                    if sn == 0 {
-                        // no inception event, no state
-                        return Ok(None);
-                    } else {
-                        // end of KEL, stop looping
-                        break;
-                    }
-                }
-            };
-            // parse event
-            // FIXME, DONT UNWRAP
-            let parsed = message(&raw).unwrap().1;
-            // apply it to the state
-            // TODO avoid .clone()
-            state = match state.clone().apply(&parsed) {
-                Ok(s) => s,
-                // will happen when a recovery has overridden some part of the KEL,
-                // stop processing here
-                Err(_) => break,
-            }
+/// Record of the first accepted version of event of given sn.
+/// Ordinal numbers are monotonically increasing per identifier
+/// and reflect the order in which events were accepted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FirstSeen {
+    #[serde(rename = "fn", with = "crate::hex")]
+    pub ordinal: u64,
+
+    #[serde(rename = "s", with = "crate::hex")]
+    pub sn: u64,
+
+    #[serde(rename = "d")]
+    pub digest: SelfAddressingPrefix,
+
+    #[serde(rename = "dt")]
+    pub timestamp: DateTime<Local>,
+}
+
+/// Escrowed
+///
+/// Escrow entry together with time it was escrowed at, so entries kept
+/// in escrow for too long can be purged. Entries are equal if their
+/// values are, regardless of timestamps.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Escrowed<T> {
+    pub timestamp: DateTime<Local>,
+    pub value: T,
+}
+
+impl<T> Escrowed<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            timestamp: Local::now(),
+            value,
         }
+    }
+}
 
-        Ok(Some(state))
+impl<T: PartialEq> PartialEq for Escrowed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
     }
+}
+
+/// Event Location
+///
+/// Identifier and sn of finalized event, indexed by event digest so
+/// event can be found without scanning KELs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventLocation {
+    pub prefix: IdentifierPrefix,
+    pub sn: u64,
+}
+
+/// Subscription
+///
+/// Identifier watched by the monitoring component, together with
+/// the source it is fetched from and the cursor pointing to the last
+/// event seen from that source, so fetching can be resumed from there.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub prefix: IdentifierPrefix,
+    pub source: IdentifierPrefix,
+    pub last_sn: Option<u64>,
+    pub last_digest: Option<SelfAddressingPrefix>,
+}
+
+impl Subscription {
+    pub fn new(prefix: IdentifierPrefix, source: IdentifierPrefix) -> Self {
+        Self {
+            prefix,
+            source,
+            last_sn: None,
+            last_digest: None,
+        }
+    }
+
+    /// Returns sn of the first event which wasn't seen yet.
+    pub fn next_sn(&self) -> u64 {
+        self.last_sn.map_or(0, |sn| sn + 1)
+    }
+}
+
+/// Event Database
+///
+/// Storage of KELs, receipts and escrows event processor works on.
+/// Sled is the default implementation, other backends or test mocks
+/// only have to implement this trait to be used by `EventProcessor`.
+pub trait EventDatabase {
+    /// Adds event to KEL of identifier, as accepted at `timestamp`.
+    fn add_kel_finalized_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<(), Error>;
+
+    fn get_kel_finalized_events(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = TimestampedSignedEventMessage>>>;
 
-    /// Get KERL for Prefix
-    ///
-    /// Returns the current validated KERL for a given Prefix
-    fn get_kerl(&self, id: &IdentifierPrefix) -> Result<Option<Vec<u8>>, Self::Error>;
+    fn remove_kel_finalized_event(
+        &self,
+        id: &IdentifierPrefix,
+        event: &SignedEventMessage,
+    ) -> Result<(), Error>;
+
+    /// Returns finalized event of given digest, found by the digest
+    /// index maintained together with KELs.
+    fn get_event_by_digest(
+        &self,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<TimestampedSignedEventMessage>;
 
-    /// Get Children of Prefix
-    ///
-    /// Returns the Identifiers delegated to by the
-    /// given Prefix
-    fn get_children_of_prefix(
+    /// Returns sn of the first event of identifier which anchors seal of
+    /// given digest. Scans the KEL unless backend indexes seals.
+    fn get_anchoring_sn(
         &self,
-        _pref: &IdentifierPrefix,
-    ) -> Result<Option<Vec<IdentifierPrefix>>, Self::Error> {
-        todo!()
+        id: &IdentifierPrefix,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<u64> {
+        self.get_kel_finalized_events(id)?
+            .map(|event| event.signed_event_message.event_message.event)
+            .find(|event| {
+                event
+                    .get_event_data()
+                    .seals()
+                    .iter()
+                    .any(|seal| seal.anchored_digest() == Some(digest))
+            })
+            .map(|event| event.get_sn())
     }
 
-    /// Get Parent of Prefix
-    ///
-    /// Returns the delegator for the given Prefix,
-    /// if there is one
-    fn get_parent_of_prefix(
+    /// Adds first seen record for event of given sn and digest, seen at
+    /// `timestamp`. Ordinal of the record follows the last one for
+    /// identifier.
+    fn add_first_seen(
         &self,
-        _pref: &IdentifierPrefix,
-    ) -> Result<Option<IdentifierPrefix>, Self::Error> {
-        todo!()
+        id: &IdentifierPrefix,
+        sn: u64,
+        digest: SelfAddressingPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<FirstSeen, Error>;
+
+    /// Returns first seen records of identifier, in order of ordinals.
+    fn get_first_seen_records(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = FirstSeen>>>;
+
+    /// Returns the earliest first seen record of event at `sn`, ie. of
+    /// the first version of event seen, even if it was superseded by
+    /// recovery rotation later.
+    fn get_first_seen(&self, id: &IdentifierPrefix, sn: u64) -> Option<FirstSeen> {
+        self.get_first_seen_records(id)?
+            .find(|record| record.sn == sn)
+    }
+
+    /// Returns first seen record of event accepted at `sn`. Record of
+    /// recovery rotation supersedes all records of the same or greater
+    /// sn seen before it, so the first record at or below `sn`, counting
+    /// from the latest one, has to be at `sn`.
+    fn get_accepted_first_seen(&self, id: &IdentifierPrefix, sn: u64) -> Option<FirstSeen> {
+        self.get_first_seen_records(id)?
+            .rev()
+            .find(|record| record.sn <= sn)
+            .filter(|record| record.sn == sn)
     }
 
-    /// Log Event
-    ///
-    /// Adds the raw event data to the database and a timestamp
-    fn log_event(
+    fn add_duplicious_event(
         &self,
-        prefix: &IdentifierPrefix,
-        dig: &SelfAddressingPrefix,
-        raw: &[u8],
-        sigs: &[AttachedSignaturePrefix],
-    ) -> Result<(), Self::Error>;
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error>;
 
-    /// Finalise Event
-    ///
-    /// Update associated logs for fully verified event
-    fn finalise_event(
+    fn add_receipt_t(
         &self,
-        prefix: &IdentifierPrefix,
-        sn: u64,
-        dig: &SelfAddressingPrefix,
-    ) -> Result<(), Self::Error>;
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error>;
 
-    /// Escrow Partially Signed Event
-    ///
-    /// Escrows an Event which does not have enough signatures
-    fn escrow_partially_signed_event(
+    fn get_receipts_t(
         &self,
-        pref: &IdentifierPrefix,
-        sn: u64,
-        dig: &SelfAddressingPrefix,
-    ) -> Result<(), Self::Error>;
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>>;
 
-    /// Escrow Out of Order Event
-    ///
-    /// Escrows an Event which has arrived before previous events
-    fn escrow_out_of_order_event(
+    fn add_receipt_nt(
         &self,
-        pref: &IdentifierPrefix,
-        sn: u64,
-        dig: &SelfAddressingPrefix,
-    ) -> Result<(), Self::Error>;
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error>;
 
-    /// Likely Duplicitous Event
-    ///
-    /// Marks an event as being likely duplicitous
-    fn likely_duplicitous_event(
+    fn get_receipts_nt(
         &self,
-        pref: &IdentifierPrefix,
-        sn: u64,
-        dig: &SelfAddressingPrefix,
-    ) -> Result<(), Self::Error>;
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>>;
 
-    /// Duplicitous Event
-    ///
-    /// Marks an event as being known duplicitous
-    fn duplicitous_event(
+    fn add_escrow_t_receipt(
         &self,
-        pref: &IdentifierPrefix,
-        sn: u64,
-        dig: &SelfAddressingPrefix,
-    ) -> Result<(), Self::Error>;
-
-    /// Add Non-Transferrable Receipt
-    ///
-    /// Associates a signature Sig made by Signer with the event referenced by Dig and Pref
-    fn add_nt_receipt_for_event(
-        &self,
-        pref: &IdentifierPrefix,
-        dig: &SelfAddressingPrefix,
-        signer: &BasicPrefix,
-        sig: &SelfSigningPrefix,
-    ) -> Result<(), Self::Error>;
-
-    /// Add Transferrable Receipt
-    ///
-    /// Associates a signature Sig made by Signer with the event referenced by Dig and Pref
-    fn add_t_receipt_for_event(
-        &self,
-        pref: &IdentifierPrefix,
-        dig: &SelfAddressingPrefix,
-        signer: &IdentifierPrefix,
-        sig: &AttachedSignaturePrefix,
-    ) -> Result<(), Self::Error>;
-
-    /// Escrow Non-Transferrable Receipt
-    fn escrow_nt_receipt(
-        &self,
-        pref: &IdentifierPrefix,
-        dig: &SelfAddressingPrefix,
-        signer: &BasicPrefix,
-        sig: &SelfSigningPrefix,
-    ) -> Result<(), Self::Error>;
-
-    /// Escrow Transferrable Receipt
-    fn escrow_t_receipt(
-        &self,
-        pref: &IdentifierPrefix,
-        dig: &SelfAddressingPrefix,
-        signer: &IdentifierPrefix,
-        sig: &AttachedSignaturePrefix,
-    ) -> Result<(), Self::Error>;
-
-    fn has_receipt(
-        &self,
-        pref: &IdentifierPrefix,
-        sn: u64,
-        validator: &IdentifierPrefix,
-    ) -> Result<bool, Self::Error>;
-}
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error>;
 
-// #[cfg(test)]
-// fn test_db<D: EventDatabase>(db: D) -> Result<(), D::Error> {
-//     use crate::{
-//         derivation::self_addressing::SelfAddressing,
-//         event_message::parse::{signed_message, Deserialized},
-//     };
+    fn get_escrow_t_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>>;
 
-//     // NOTE this is not actually a valid kel (sig indexes and prefix are wrong), just has the correct form
-//     let signed = br#"{"v":"KERI10JSON000144_","i":"E005TfcIFvrzhJFxoGqebPHvtanxEwcfJOAYcUtCmhk8","s":"0","t":"icp","kt":"1","k":["D5UzOMC5Knhi5eA-Cr8ASuD8lUcZMcLtAhIZ33W5Z4hs","DEbposribdTgsnCSQgmVN6VKoc4Vpc-hs9rbskXQ2O2M","D46n6npISQETk7eGYnwe5Jq7USmEsckHeJRu2YoTCXhU"],"n":"E2zBfVYkE2uaGR5DmMVBbWsIdBIZVu5Ml6joenraD5Ho","bt":"0","b":[],"c":[],"a":[]}-AADAAKfgMIEsKlrXqUxUyw1Qq7gFrg9mNWcDAkQAXUW6Hppvt4NBdEbU_2Wy7Re0zp5zVvLjjq4hjPE5aXSeIlHh7DgABIkKK8jI0l_tor7EaIM2B65aLn9e6Y3Igwa9OjDqbiyqXdL1yHxga7nhJY80Ct0zXGhm7hgLzgB6d86EqfXWLCQACBPCeqK_WXY64EZ8E91Y2trI6MfZT-f2NmtHCmmKhvt7AmehPcvQSvrcQbogdNEBr749AbVG7glVsV8WitVR2DQ"#;
-//     let deser = match signed_message(signed).unwrap().1 {
-//         Deserialized::Event(e) => e,
-//         _ => panic!(),
-//     };
+    fn remove_escrow_t_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedTransferableReceipt,
+    ) -> Result<(), Error>;
 
-//     let dig = SelfAddressing::Blake3_256.derive(deser.event.raw);
+    /// Escrowed transferable receipts of all identifiers, with time
+    /// they were escrowed at.
+    fn get_all_escrow_t_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedTransferableReceipt>>>>;
 
-//     db.log_event(
-//         &deser.event.event.event.prefix,
-//         &dig,
-//         deser.event.raw,
-//         &deser.signatures,
-//     )?;
+    fn add_escrow_nt_receipt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error>;
 
-//     db.finalise_event(&deser.event.event.event.prefix, 0, &dig)?;
+    fn get_escrow_nt_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>>;
 
-//     let written = db.last_event_at_sn(&deser.event.event.event.prefix, 0)?;
+    fn remove_escrow_nt_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedNontransferableReceipt,
+    ) -> Result<(), Error>;
 
-//     assert_eq!(written, Some(deser.event.raw.to_vec()));
+    /// Escrowed nontransferable receipts of all identifiers, with time
+    /// they were escrowed at.
+    fn get_all_escrow_nt_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedNontransferableReceipt>>>>;
 
-//     let kerl = db.get_kerl(&deser.event.event.event.prefix)?.unwrap();
-//     let deser_2 = match signed_message(&kerl).unwrap().1 {
-//         Deserialized::Event(e) => e,
-//         _ => panic!(),
-//     };
+    /// Adds subscription or replaces the one with the same prefix
+    /// and source, which updates its cursor.
+    fn update_subscription(&self, subscription: Subscription) -> Result<(), Error>;
 
-//     assert_eq!(deser, deser_2);
+    fn get_subscription(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Option<Subscription>;
+
+    /// Appends record to the audit trail. Backends which don't keep
+    /// audit trail drop it.
+    fn add_audit_record(&self, _record: AuditRecord) -> Result<(), Error> {
+        Ok(())
+    }
 
-//     Ok(())
-// }
+    /// Audit records in order they were added.
+    fn get_audit_trail(&self) -> Option<Box<dyn DoubleEndedIterator<Item = AuditRecord>>> {
+        None
+    }
+
+    #[cfg(feature = "query")]
+    fn update_accepted_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error>;
+
+    #[cfg(feature = "query")]
+    fn get_accepted_replys(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedReply>>>;
+
+    #[cfg(feature = "query")]
+    fn add_escrowed_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error>;
+
+    #[cfg(feature = "query")]
+    fn get_all_escrowed_replys(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedReply>>>>;
+
+    #[cfg(feature = "query")]
+    fn remove_escrowed_reply(&self, id: &IdentifierPrefix, rpy: SignedReply) -> Result<(), Error>;
+}
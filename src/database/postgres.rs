@@ -0,0 +1,539 @@
+//! PostgreSQL event database
+//!
+//! Shared durable store for services running on many nodes, eg.
+//! horizontally scaled witnesses or watchers. Every table keeps CBOR
+//! serialized records together with qb64 of identifier they belong to,
+//! and `kels` additionally exposes sn, digest and type of events, so
+//! KELs can be analysed with plain SQL.
+
+use super::{Escrowed, EventDatabase, FirstSeen, Subscription};
+use crate::{
+    error::Error,
+    event_message::{
+        signed_event_message::{
+            SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+            TimestampedSignedEventMessage,
+        },
+        EventTypeTag,
+    },
+    prefix::{IdentifierPrefix, Prefix, SelfAddressingPrefix},
+};
+use chrono::{DateTime, Local};
+use postgres::{Client, Config, NoTls};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+#[cfg(feature = "query")]
+use crate::query::reply::SignedReply;
+
+const TABLES: &[&str] = &[
+    "duplicitous_events",
+    "receipts_nt",
+    "escrowed_receipts_nt",
+    "receipts_t",
+    "escrowed_receipts_t",
+    "first_seen",
+    "subscriptions",
+    "accepted_replies",
+    "escrowed_replies",
+];
+
+/// Table of records kept per identifier, in order of insertion.
+struct PostgresTable<T> {
+    client: Arc<Mutex<Client>>,
+    name: &'static str,
+    marker: PhantomData<T>,
+}
+
+impl<T> PostgresTable<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn new(client: Arc<Mutex<Client>>, name: &'static str) -> Self {
+        Self {
+            client,
+            name,
+            marker: PhantomData,
+        }
+    }
+
+    fn client(&self) -> Result<MutexGuard<'_, Client>, Error> {
+        self.client.lock().map_err(|_| Error::MutexPoisoned)
+    }
+
+    fn push(&self, prefix: &str, value: &T) -> Result<(), Error> {
+        self.client()?.execute(
+            format!("INSERT INTO {} (prefix, data) VALUES ($1, $2)", self.name).as_str(),
+            &[&prefix, &serde_cbor::to_vec(value)?],
+        )?;
+        Ok(())
+    }
+
+    fn entries(&self, prefix: Option<&str>) -> Result<Vec<(i64, T)>, Error> {
+        let rows = match prefix {
+            Some(prefix) => self.client()?.query(
+                format!(
+                    "SELECT id, data FROM {} WHERE prefix = $1 ORDER BY id",
+                    self.name
+                )
+                .as_str(),
+                &[&prefix],
+            )?,
+            None => self.client()?.query(
+                format!("SELECT id, data FROM {} ORDER BY id", self.name).as_str(),
+                &[],
+            )?,
+        };
+        rows.iter()
+            .map(|row| Ok((row.get(0), serde_cbor::from_slice(row.get(1))?)))
+            .collect()
+    }
+
+    fn iter_values(&self, prefix: &str) -> Option<impl DoubleEndedIterator<Item = T>> {
+        let values: Vec<T> = self
+            .entries(Some(prefix))
+            .ok()?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.into_iter())
+        }
+    }
+
+    fn get_all(&self) -> Option<impl DoubleEndedIterator<Item = T>> {
+        let values: Vec<T> = self
+            .entries(None)
+            .ok()?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        Some(values.into_iter())
+    }
+
+    fn remove(&self, prefix: &str, value: &T) -> Result<(), Error>
+    where
+        T: PartialEq,
+    {
+        for (id, element) in self.entries(Some(prefix))? {
+            if &element == value {
+                self.client()?.execute(
+                    format!("DELETE FROM {} WHERE id = $1", self.name).as_str(),
+                    &[&id],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// PostgreSQL Event Database
+///
+/// Event database kept in PostgreSQL, which may be shared by many
+/// processes. Tables are created on connection if they don't exist yet.
+pub struct PostgresEventDatabase {
+    client: Arc<Mutex<Client>>,
+    key_event_logs: PostgresTable<TimestampedSignedEventMessage>,
+    duplicitous_events: PostgresTable<TimestampedSignedEventMessage>,
+    receipts_nt: PostgresTable<SignedNontransferableReceipt>,
+    escrowed_receipts_nt: PostgresTable<Escrowed<SignedNontransferableReceipt>>,
+    receipts_t: PostgresTable<SignedTransferableReceipt>,
+    escrowed_receipts_t: PostgresTable<Escrowed<SignedTransferableReceipt>>,
+    first_seen: PostgresTable<FirstSeen>,
+    subscriptions: PostgresTable<Subscription>,
+
+    #[cfg(feature = "query")]
+    accepted_rpy: PostgresTable<SignedReply>,
+
+    #[cfg(feature = "query")]
+    escrowed_replys: PostgresTable<Escrowed<SignedReply>>,
+}
+
+impl PostgresEventDatabase {
+    /// Connects to database given by connection string, eg.
+    /// `postgresql://keri@localhost/keri`.
+    pub fn new(url: &str) -> Result<Self, Error> {
+        Self::connect(&url.parse()?)
+    }
+
+    pub fn connect(config: &Config) -> Result<Self, Error> {
+        let mut client = config.connect(NoTls)?;
+        client.batch_execute(
+            "CREATE TABLE IF NOT EXISTS kels (
+                id BIGSERIAL PRIMARY KEY,
+                prefix TEXT NOT NULL,
+                sn BIGINT NOT NULL,
+                digest TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                data BYTEA NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS kels_prefix_sn ON kels (prefix, sn);
+            CREATE INDEX IF NOT EXISTS kels_digest ON kels (digest);",
+        )?;
+        for table in TABLES {
+            client.batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    id BIGSERIAL PRIMARY KEY,
+                    prefix TEXT NOT NULL,
+                    data BYTEA NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS {table}_prefix ON {table} (prefix);",
+                table = table
+            ))?;
+        }
+        let client = Arc::new(Mutex::new(client));
+        Ok(Self {
+            key_event_logs: PostgresTable::new(client.clone(), "kels"),
+            duplicitous_events: PostgresTable::new(client.clone(), "duplicitous_events"),
+            receipts_nt: PostgresTable::new(client.clone(), "receipts_nt"),
+            escrowed_receipts_nt: PostgresTable::new(client.clone(), "escrowed_receipts_nt"),
+            receipts_t: PostgresTable::new(client.clone(), "receipts_t"),
+            escrowed_receipts_t: PostgresTable::new(client.clone(), "escrowed_receipts_t"),
+            first_seen: PostgresTable::new(client.clone(), "first_seen"),
+            subscriptions: PostgresTable::new(client.clone(), "subscriptions"),
+            #[cfg(feature = "query")]
+            accepted_rpy: PostgresTable::new(client.clone(), "accepted_replies"),
+            #[cfg(feature = "query")]
+            escrowed_replys: PostgresTable::new(client.clone(), "escrowed_replies"),
+            client,
+        })
+    }
+}
+
+impl EventDatabase for PostgresEventDatabase {
+    fn add_kel_finalized_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<(), Error> {
+        let event_message = &event.event_message;
+        let sn = event_message.event.get_sn() as i64;
+        let digest = event_message.get_digest().to_str();
+        let event_type: EventTypeTag = (&event_message.event.get_event_data()).into();
+        let event_type = serde_json::to_value(event_type)?;
+        let data = serde_cbor::to_vec(&TimestampedSignedEventMessage::new_at(event, timestamp))?;
+        self.client
+            .lock()
+            .map_err(|_| Error::MutexPoisoned)?
+            .execute(
+                "INSERT INTO kels (prefix, sn, digest, event_type, data)
+                VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &id.to_str(),
+                    &sn,
+                    &digest,
+                    &event_type.as_str().unwrap_or_default(),
+                    &data,
+                ],
+            )?;
+        Ok(())
+    }
+
+    fn get_kel_finalized_events(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = TimestampedSignedEventMessage>>> {
+        self.key_event_logs
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn remove_kel_finalized_event(
+        &self,
+        id: &IdentifierPrefix,
+        event: &SignedEventMessage,
+    ) -> Result<(), Error> {
+        self.key_event_logs.remove(&id.to_str(), &event.into())
+    }
+
+    fn get_event_by_digest(
+        &self,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<TimestampedSignedEventMessage> {
+        let row = self
+            .client
+            .lock()
+            .ok()?
+            .query_opt(
+                "SELECT data FROM kels WHERE digest = $1 ORDER BY id LIMIT 1",
+                &[&digest.to_str()],
+            )
+            .ok()??;
+        serde_cbor::from_slice(row.get(0)).ok()
+    }
+
+    fn add_first_seen(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+        digest: SelfAddressingPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<FirstSeen, Error> {
+        let ordinal = self
+            .first_seen
+            .iter_values(&id.to_str())
+            .map(|records| records.count() as u64)
+            .unwrap_or_default();
+        let record = FirstSeen {
+            ordinal,
+            sn,
+            digest,
+            timestamp,
+        };
+        self.first_seen.push(&id.to_str(), &record)?;
+        Ok(record)
+    }
+
+    fn get_first_seen_records(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = FirstSeen>>> {
+        self.first_seen
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_duplicious_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.duplicitous_events
+            .push(&id.to_str(), &TimestampedSignedEventMessage::new(event))
+    }
+
+    fn add_receipt_t(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.receipts_t.push(&id.to_str(), &receipt)
+    }
+
+    fn get_receipts_t(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.receipts_t
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_receipt_nt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.receipts_nt.push(&id.to_str(), &receipt)
+    }
+
+    fn get_receipts_nt(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.receipts_nt
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_escrow_t_receipt(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_t
+            .push(&id.to_str(), &Escrowed::new(receipt))
+    }
+
+    fn get_escrow_t_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.escrowed_receipts_t
+            .iter_values(&id.to_str())
+            .map(|it| {
+                Box::new(it.map(|escrowed| escrowed.value))
+                    as Box<dyn DoubleEndedIterator<Item = _>>
+            })
+    }
+
+    fn remove_escrow_t_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedTransferableReceipt,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_t
+            .remove(&id.to_str(), &Escrowed::new(receipt.clone()))
+    }
+
+    fn get_all_escrow_t_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedTransferableReceipt>>>> {
+        self.escrowed_receipts_t
+            .get_all()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_escrow_nt_receipt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_nt
+            .push(&id.to_str(), &Escrowed::new(receipt))
+    }
+
+    fn get_escrow_nt_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.escrowed_receipts_nt
+            .iter_values(&id.to_str())
+            .map(|it| {
+                Box::new(it.map(|escrowed| escrowed.value))
+                    as Box<dyn DoubleEndedIterator<Item = _>>
+            })
+    }
+
+    fn remove_escrow_nt_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedNontransferableReceipt,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_nt
+            .remove(&id.to_str(), &Escrowed::new(receipt.clone()))
+    }
+
+    fn get_all_escrow_nt_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedNontransferableReceipt>>>> {
+        self.escrowed_receipts_nt
+            .get_all()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn update_subscription(&self, subscription: Subscription) -> Result<(), Error> {
+        let key = subscription.prefix.to_str();
+        if let Some(old) = self.get_subscription(&subscription.prefix, &subscription.source) {
+            self.subscriptions.remove(&key, &old)?;
+        }
+        self.subscriptions.push(&key, &subscription)
+    }
+
+    fn get_subscription(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Option<Subscription> {
+        self.subscriptions
+            .iter_values(&id.to_str())
+            .and_then(|mut subs| subs.find(|s| &s.source == source))
+    }
+
+    #[cfg(feature = "query")]
+    fn update_accepted_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        let key = id.to_str();
+        for old in self.accepted_rpy.iter_values(&key).into_iter().flatten() {
+            if old.reply.event.is_same_subject(&rpy.reply.event) {
+                self.accepted_rpy.remove(&key, &old)?;
+            }
+        }
+        self.accepted_rpy.push(&key, &rpy)
+    }
+
+    #[cfg(feature = "query")]
+    fn get_accepted_replys(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedReply>>> {
+        self.accepted_rpy
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    #[cfg(feature = "query")]
+    fn add_escrowed_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.escrowed_replys.push(&id.to_str(), &Escrowed::new(rpy))
+    }
+
+    #[cfg(feature = "query")]
+    fn get_all_escrowed_replys(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedReply>>>> {
+        self.escrowed_replys
+            .get_all()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    #[cfg(feature = "query")]
+    fn remove_escrowed_reply(&self, id: &IdentifierPrefix, rpy: SignedReply) -> Result<(), Error> {
+        self.escrowed_replys
+            .remove(&id.to_str(), &Escrowed::new(rpy))
+    }
+}
+
+// Needs running PostgreSQL, given by `KERI_POSTGRES_URL` variable.
+#[test]
+#[ignore]
+fn test_postgres_event_database() -> Result<(), Error> {
+    use crate::{
+        event_message::signed_event_message::Message, event_parsing::message::signed_event_stream,
+        processor::EventProcessor,
+    };
+    use std::convert::TryFrom;
+
+    let url = std::env::var("KERI_POSTGRES_URL")
+        .unwrap_or_else(|_| "postgresql://postgres@localhost/postgres".into());
+    let mut config: Config = url.parse()?;
+    // Tables are created in fresh schema, dropped at the end.
+    let schema = format!("keri_test_{}", std::process::id());
+    config
+        .connect(NoTls)?
+        .batch_execute(&format!("CREATE SCHEMA {}", schema))?;
+    config.options(&format!("-c search_path={}", schema));
+    let db = Arc::new(PostgresEventDatabase::connect(&config)?);
+    let event_processor = EventProcessor::new(db.clone());
+
+    // Taken from keripy/tests/core/test_kevery.py::test kevery
+    let kerl_str= br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw{"v":"KERI10JSON000155_","t":"rot","d":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"1","p":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","kt":"1","k":["DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI"],"n":"E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI","bt":"0","br":[],"ba":[],"a":[]}-AABAAwoiqt07w2UInzzo2DmtwkBfqX1-tTO4cYk_7YdlbJ95qA7PO5sEUkER8fZySQMNCVh64ruAh1yoew3TikwVGAQ"#;
+    let (_rest, messages) = signed_event_stream(kerl_str).unwrap();
+    for msg in messages {
+        event_processor.process(Message::try_from(msg)?)?;
+    }
+
+    let id: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+    assert_eq!(event_processor.compute_state(&id)?.unwrap().sn, 1);
+    assert_eq!(event_processor.get_first_seen(&id, 1)?.unwrap().ordinal, 1);
+    assert_eq!(event_processor.get_kerl(&id)?.unwrap(), kerl_str.to_vec());
+
+    // KEL can be queried with SQL.
+    let rows = db.client.lock().unwrap().query(
+        "SELECT sn, event_type FROM kels WHERE prefix = $1 ORDER BY sn",
+        &[&id.to_str()],
+    )?;
+    let events: Vec<(i64, String)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+    assert_eq!(events, vec![(0, "icp".into()), (1, "rot".into())]);
+    let rot_digest = "Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA".parse()?;
+    assert_eq!(
+        db.get_event_by_digest(&rot_digest)
+            .unwrap()
+            .signed_event_message
+            .event_message
+            .event
+            .get_sn(),
+        1
+    );
+
+    db.client
+        .lock()
+        .unwrap()
+        .batch_execute(&format!("DROP SCHEMA {} CASCADE", schema))?;
+
+    Ok(())
+}
@@ -0,0 +1,462 @@
+pub(crate) mod tables;
+
+use super::{Escrowed, EventDatabase, EventLocation, FirstSeen, Subscription};
+use crate::{
+    error::Error,
+    event_message::signed_event_message::{
+        SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+        TimestampedSignedEventMessage,
+    },
+    prefix::{IdentifierPrefix, Prefix, SelfAddressingPrefix},
+};
+use chrono::{DateTime, Local};
+use rocksdb::{Options, DB};
+use std::{path::Path, sync::Arc};
+use tables::{RocksEventTreeVec, RocksIndexTree};
+
+#[cfg(feature = "query")]
+use crate::query::reply::SignedReply;
+
+/// RocksDB Config
+///
+/// Tuning of the write path. Bigger and more numerous write buffers let
+/// witness ingest bursts of events without stalling on flushes, at the
+/// cost of memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RocksDbConfig {
+    /// Size of single memtable of a column family, in bytes.
+    pub write_buffer_size: usize,
+    /// Max number of memtables of a column family kept in memory.
+    pub max_write_buffer_number: i32,
+    /// Number of memtables merged together before flush.
+    pub min_write_buffer_number_to_merge: i32,
+    /// Number of background threads for flushes and compactions.
+    pub parallelism: i32,
+}
+
+impl Default for RocksDbConfig {
+    fn default() -> Self {
+        Self {
+            write_buffer_size: 64 * 1024 * 1024,
+            max_write_buffer_number: 4,
+            min_write_buffer_number_to_merge: 1,
+            parallelism: 2,
+        }
+    }
+}
+
+impl RocksDbConfig {
+    pub fn with_write_buffer_size(self, write_buffer_size: usize) -> Self {
+        Self {
+            write_buffer_size,
+            ..self
+        }
+    }
+
+    pub fn with_max_write_buffer_number(self, max_write_buffer_number: i32) -> Self {
+        Self {
+            max_write_buffer_number,
+            ..self
+        }
+    }
+
+    pub fn with_min_write_buffer_number_to_merge(
+        self,
+        min_write_buffer_number_to_merge: i32,
+    ) -> Self {
+        Self {
+            min_write_buffer_number_to_merge,
+            ..self
+        }
+    }
+
+    pub fn with_parallelism(self, parallelism: i32) -> Self {
+        Self {
+            parallelism,
+            ..self
+        }
+    }
+
+    fn options(&self) -> Options {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        options.set_write_buffer_size(self.write_buffer_size);
+        options.set_max_write_buffer_number(self.max_write_buffer_number);
+        options.set_min_write_buffer_number_to_merge(self.min_write_buffer_number_to_merge);
+        options.increase_parallelism(self.parallelism);
+        options
+    }
+}
+
+/// RocksDB Event Database
+///
+/// Alternative to sled for deployments processing lots of events, eg.
+/// witnesses. Every table lives in its own column family, named after
+/// the corresponding sled tree.
+pub struct RocksEventDatabase {
+    // "kels" column family
+    key_event_logs: RocksEventTreeVec<TimestampedSignedEventMessage>,
+    // "digs" column family
+    event_digests: RocksIndexTree<EventLocation>,
+    // "dels" column family
+    duplicitous_events: RocksEventTreeVec<TimestampedSignedEventMessage>,
+    // "rcts" column family
+    receipts_nt: RocksEventTreeVec<SignedNontransferableReceipt>,
+    // "ures" column family
+    escrowed_receipts_nt: RocksEventTreeVec<Escrowed<SignedNontransferableReceipt>>,
+    // "vrcs" column family
+    receipts_t: RocksEventTreeVec<SignedTransferableReceipt>,
+    // "vres" column family
+    escrowed_receipts_t: RocksEventTreeVec<Escrowed<SignedTransferableReceipt>>,
+    // "fons" column family
+    first_seen: RocksEventTreeVec<FirstSeen>,
+    // "subs" column family
+    subscriptions: RocksEventTreeVec<Subscription>,
+
+    #[cfg(feature = "query")]
+    accepted_rpy: RocksEventTreeVec<SignedReply>,
+
+    #[cfg(feature = "query")]
+    escrowed_replys: RocksEventTreeVec<Escrowed<SignedReply>>,
+}
+
+impl RocksEventDatabase {
+    pub fn new<'a, P>(path: P) -> Result<Self, Error>
+    where
+        P: Into<&'a Path>,
+    {
+        Self::with_config(path, &RocksDbConfig::default())
+    }
+
+    pub fn with_config<'a, P>(path: P, config: &RocksDbConfig) -> Result<Self, Error>
+    where
+        P: Into<&'a Path>,
+    {
+        let mut column_families = vec![
+            "kels", "digs", "dels", "rcts", "ures", "vrcs", "vres", "fons", "subs",
+        ];
+        if cfg!(feature = "query") {
+            column_families.extend(["knas", "knes"]);
+        }
+        let db = Arc::new(DB::open_cf(
+            &config.options(),
+            path.into(),
+            column_families,
+        )?);
+        Ok(Self {
+            key_event_logs: RocksEventTreeVec::new(db.clone(), "kels"),
+            event_digests: RocksIndexTree::new(db.clone(), "digs"),
+            duplicitous_events: RocksEventTreeVec::new(db.clone(), "dels"),
+            receipts_nt: RocksEventTreeVec::new(db.clone(), "rcts"),
+            escrowed_receipts_nt: RocksEventTreeVec::new(db.clone(), "ures"),
+            receipts_t: RocksEventTreeVec::new(db.clone(), "vrcs"),
+            escrowed_receipts_t: RocksEventTreeVec::new(db.clone(), "vres"),
+            first_seen: RocksEventTreeVec::new(db.clone(), "fons"),
+            subscriptions: RocksEventTreeVec::new(db.clone(), "subs"),
+            #[cfg(feature = "query")]
+            accepted_rpy: RocksEventTreeVec::new(db.clone(), "knas"),
+            #[cfg(feature = "query")]
+            escrowed_replys: RocksEventTreeVec::new(db, "knes"),
+        })
+    }
+}
+
+impl EventDatabase for RocksEventDatabase {
+    fn add_kel_finalized_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<(), Error> {
+        let location = EventLocation {
+            prefix: id.clone(),
+            sn: event.event_message.event.get_sn(),
+        };
+        let digest = event.event_message.get_digest().to_str();
+        self.key_event_logs.push(
+            &id.to_str(),
+            TimestampedSignedEventMessage::new_at(event, timestamp),
+        )?;
+        self.event_digests.insert(&digest, &location)
+    }
+
+    fn get_kel_finalized_events(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = TimestampedSignedEventMessage>>> {
+        self.key_event_logs
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn remove_kel_finalized_event(
+        &self,
+        id: &IdentifierPrefix,
+        event: &SignedEventMessage,
+    ) -> Result<(), Error> {
+        self.key_event_logs.remove(&id.to_str(), &event.into())?;
+        self.event_digests
+            .remove(&event.event_message.get_digest().to_str())
+    }
+
+    fn get_event_by_digest(
+        &self,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<TimestampedSignedEventMessage> {
+        let location = self.event_digests.get(&digest.to_str()).ok()??;
+        self.get_kel_finalized_events(&location.prefix)?
+            .find(|event| {
+                let event_message = &event.signed_event_message.event_message;
+                event_message.event.get_sn() == location.sn && &event_message.get_digest() == digest
+            })
+    }
+
+    fn add_first_seen(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+        digest: SelfAddressingPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<FirstSeen, Error> {
+        let ordinal = self
+            .first_seen
+            .iter_values(&id.to_str())
+            .map(|records| records.count() as u64)
+            .unwrap_or_default();
+        let record = FirstSeen {
+            ordinal,
+            sn,
+            digest,
+            timestamp,
+        };
+        self.first_seen.push(&id.to_str(), record.clone())?;
+        Ok(record)
+    }
+
+    fn get_first_seen_records(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = FirstSeen>>> {
+        self.first_seen
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_duplicious_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.duplicitous_events.push(&id.to_str(), event.into())
+    }
+
+    fn add_receipt_t(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.receipts_t.push(&id.to_str(), receipt)
+    }
+
+    fn get_receipts_t(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.receipts_t
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_receipt_nt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.receipts_nt.push(&id.to_str(), receipt)
+    }
+
+    fn get_receipts_nt(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.receipts_nt
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_escrow_t_receipt(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_t
+            .push(&id.to_str(), Escrowed::new(receipt))
+    }
+
+    fn get_escrow_t_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.escrowed_receipts_t
+            .iter_values(&id.to_str())
+            .map(|it| {
+                Box::new(it.map(|escrowed| escrowed.value))
+                    as Box<dyn DoubleEndedIterator<Item = _>>
+            })
+    }
+
+    fn remove_escrow_t_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedTransferableReceipt,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_t
+            .remove(&id.to_str(), &Escrowed::new(receipt.clone()))
+    }
+
+    fn get_all_escrow_t_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedTransferableReceipt>>>> {
+        self.escrowed_receipts_t
+            .get_all()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_escrow_nt_receipt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_nt
+            .push(&id.to_str(), Escrowed::new(receipt))
+    }
+
+    fn get_escrow_nt_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.escrowed_receipts_nt
+            .iter_values(&id.to_str())
+            .map(|it| {
+                Box::new(it.map(|escrowed| escrowed.value))
+                    as Box<dyn DoubleEndedIterator<Item = _>>
+            })
+    }
+
+    fn remove_escrow_nt_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedNontransferableReceipt,
+    ) -> Result<(), Error> {
+        self.escrowed_receipts_nt
+            .remove(&id.to_str(), &Escrowed::new(receipt.clone()))
+    }
+
+    fn get_all_escrow_nt_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedNontransferableReceipt>>>> {
+        self.escrowed_receipts_nt
+            .get_all()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn update_subscription(&self, subscription: Subscription) -> Result<(), Error> {
+        let key = subscription.prefix.to_str();
+        if let Some(old) = self.get_subscription(&subscription.prefix, &subscription.source) {
+            self.subscriptions.remove(&key, &old)?;
+        }
+        self.subscriptions.push(&key, subscription)
+    }
+
+    fn get_subscription(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Option<Subscription> {
+        self.subscriptions
+            .iter_values(&id.to_str())
+            .and_then(|mut subs| subs.find(|s| &s.source == source))
+    }
+
+    #[cfg(feature = "query")]
+    fn update_accepted_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        let key = id.to_str();
+        for old in self.accepted_rpy.iter_values(&key).into_iter().flatten() {
+            if old.reply.event.is_same_subject(&rpy.reply.event) {
+                self.accepted_rpy.remove(&key, &old)?;
+            }
+        }
+        self.accepted_rpy.push(&key, rpy)
+    }
+
+    #[cfg(feature = "query")]
+    fn get_accepted_replys(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedReply>>> {
+        self.accepted_rpy
+            .iter_values(&id.to_str())
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    #[cfg(feature = "query")]
+    fn add_escrowed_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.escrowed_replys.push(&id.to_str(), Escrowed::new(rpy))
+    }
+
+    #[cfg(feature = "query")]
+    fn get_all_escrowed_replys(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedReply>>>> {
+        self.escrowed_replys
+            .get_all()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    #[cfg(feature = "query")]
+    fn remove_escrowed_reply(&self, id: &IdentifierPrefix, rpy: SignedReply) -> Result<(), Error> {
+        self.escrowed_replys
+            .remove(&id.to_str(), &Escrowed::new(rpy))
+    }
+}
+
+#[test]
+fn test_rocks_event_database() -> Result<(), Error> {
+    use crate::{
+        event_message::signed_event_message::Message, event_parsing::message::signed_event_stream,
+        processor::EventProcessor,
+    };
+    use std::convert::TryFrom;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let config = RocksDbConfig::default()
+        .with_write_buffer_size(4 * 1024 * 1024)
+        .with_max_write_buffer_number(2);
+    let db = Arc::new(RocksEventDatabase::with_config(root.path(), &config)?);
+    let event_processor = EventProcessor::new(db.clone());
+
+    // Taken from keripy/tests/core/test_kevery.py::test kevery
+    let kerl_str= br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw{"v":"KERI10JSON000155_","t":"rot","d":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"1","p":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","kt":"1","k":["DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI"],"n":"E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI","bt":"0","br":[],"ba":[],"a":[]}-AABAAwoiqt07w2UInzzo2DmtwkBfqX1-tTO4cYk_7YdlbJ95qA7PO5sEUkER8fZySQMNCVh64ruAh1yoew3TikwVGAQ"#;
+    let (_rest, messages) = signed_event_stream(kerl_str).unwrap();
+    for msg in messages {
+        event_processor.process(Message::try_from(msg)?)?;
+    }
+
+    let id: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+    assert_eq!(event_processor.compute_state(&id)?.unwrap().sn, 1);
+    assert_eq!(event_processor.get_first_seen(&id, 1)?.unwrap().ordinal, 1);
+    assert_eq!(event_processor.get_kerl(&id)?.unwrap(), kerl_str.to_vec());
+
+    // Events are kept in column family in order they were added.
+    let kel: Vec<_> = db.get_kel_finalized_events(&id).unwrap().collect();
+    assert_eq!(kel.len(), 2);
+    let rot_digest = "Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA".parse()?;
+    assert!(db.get_event_by_digest(&rot_digest).is_some());
+    db.remove_kel_finalized_event(&id, &kel[1].signed_event_message)?;
+    assert_eq!(event_processor.compute_state(&id)?.unwrap().sn, 0);
+    assert!(db.get_event_by_digest(&rot_digest).is_none());
+
+    Ok(())
+}
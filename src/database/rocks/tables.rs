@@ -0,0 +1,205 @@
+#![allow(dead_code)]
+use crate::error::Error;
+use arrayref::array_ref;
+use rocksdb::{ColumnFamily, Direction, IteratorMode, DB};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+// Element of table with its whole key.
+type Entry<T> = (Box<[u8]>, T);
+
+/// Imitates collection table per key
+///
+/// Every element is stored under its own key, made of the table key
+/// and index of the element, so pushing doesn't rewrite the whole
+/// collection as in sled tables.
+pub(crate) struct RocksEventTreeVec<T> {
+    db: Arc<DB>,
+    // column family name
+    name: &'static str,
+    // guards index of pushed element
+    lock: Mutex<()>,
+    marker: PhantomData<T>,
+}
+
+impl<T> RocksEventTreeVec<T> {
+    /// table constructor
+    ///
+    pub fn new(db: Arc<DB>, name: &'static str) -> Self {
+        Self {
+            db,
+            name,
+            lock: Mutex::new(()),
+            marker: PhantomData,
+        }
+    }
+
+    fn cf(&self) -> &ColumnFamily {
+        self.db
+            .cf_handle(self.name)
+            .expect("column families are created on open")
+    }
+}
+
+/// DB "Tables" functionality
+///
+impl<T> RocksEventTreeVec<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Pushes element to the end of set of T
+    ///
+    pub fn push(&self, key: &str, value: T) -> Result<(), Error> {
+        let _guard = self.lock.lock().map_err(|_| Error::MutexPoisoned)?;
+        let last = self
+            .db
+            .iterator_cf(
+                self.cf(),
+                IteratorMode::From(&element_key(key, u64::MAX), Direction::Reverse),
+            )
+            .next()
+            .transpose()?
+            .filter(|(k, _)| k.starts_with(&key_prefix(key)))
+            .map(|(k, _)| element_index(&k));
+        let index = last.map_or(0, |index| index + 1);
+        self.db.put_cf(
+            self.cf(),
+            element_key(key, index),
+            serde_cbor::to_vec(&value)?,
+        )?;
+        Ok(())
+    }
+
+    /// Removes value `T` if present
+    ///
+    pub fn remove(&self, key: &str, value: &T) -> Result<(), Error>
+    where
+        T: PartialEq,
+    {
+        for (k, element) in self.entries(key)? {
+            if &element == value {
+                self.db.delete_cf(self.cf(), k)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterator over elements stored under `key`, None if there are
+    /// none.
+    ///
+    pub fn iter_values(&self, key: &str) -> Option<impl DoubleEndedIterator<Item = T>> {
+        let values: Vec<T> = self
+            .entries(key)
+            .ok()?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.into_iter())
+        }
+    }
+
+    /// Iterator over all elements of the table.
+    ///
+    pub fn get_all(&self) -> Option<impl DoubleEndedIterator<Item = T>> {
+        let values = self
+            .db
+            .iterator_cf(self.cf(), IteratorMode::Start)
+            .map(|entry| Ok(serde_cbor::from_slice(&entry?.1)?))
+            .collect::<Result<Vec<T>, Error>>()
+            .ok()?;
+        Some(values.into_iter())
+    }
+
+    fn entries(&self, key: &str) -> Result<Vec<Entry<T>>, Error> {
+        let prefix = key_prefix(key);
+        let mut entries = vec![];
+        for entry in self
+            .db
+            .iterator_cf(self.cf(), IteratorMode::From(&prefix, Direction::Forward))
+        {
+            let (k, v) = entry?;
+            if !k.starts_with(&prefix) {
+                break;
+            }
+            entries.push((k, serde_cbor::from_slice(&v)?));
+        }
+        Ok(entries)
+    }
+}
+
+/// Index table keyed by qb64 string, eg. of event digest
+///
+pub(crate) struct RocksIndexTree<T> {
+    db: Arc<DB>,
+    // column family name
+    name: &'static str,
+    marker: PhantomData<T>,
+}
+
+impl<T> RocksIndexTree<T> {
+    /// table constructor
+    ///
+    pub fn new(db: Arc<DB>, name: &'static str) -> Self {
+        Self {
+            db,
+            name,
+            marker: PhantomData,
+        }
+    }
+
+    fn cf(&self) -> &ColumnFamily {
+        self.db
+            .cf_handle(self.name)
+            .expect("column families are created on open")
+    }
+}
+
+/// DB "Tables" functionality
+///
+impl<T> RocksIndexTree<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// get `T` indexed under `key`
+    ///
+    pub fn get(&self, key: &str) -> Result<Option<T>, Error> {
+        match self.db.get_cf(self.cf(), key)? {
+            Some(value) => Ok(Some(serde_cbor::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// insert `T` under given `key`
+    /// Warning! This will rewrite existing value with the same `key`
+    ///
+    pub fn insert(&self, key: &str, value: &T) -> Result<(), Error> {
+        self.db.put_cf(self.cf(), key, serde_cbor::to_vec(value)?)?;
+        Ok(())
+    }
+
+    /// removes `T` indexed under `key` if present
+    ///
+    pub fn remove(&self, key: &str) -> Result<(), Error> {
+        self.db.delete_cf(self.cf(), key)?;
+        Ok(())
+    }
+}
+
+// '.' isn't part of qb64 alphabet, so one key can't be prefix of another.
+fn key_prefix(key: &str) -> Vec<u8> {
+    [key.as_bytes(), b"."].concat()
+}
+
+fn element_key(key: &str, index: u64) -> Vec<u8> {
+    [&key_prefix(key), &index.to_be_bytes()[..]].concat()
+}
+
+fn element_index(element_key: &[u8]) -> u64 {
+    u64::from_be_bytes(*array_ref!(element_key, element_key.len() - 8, 8))
+}
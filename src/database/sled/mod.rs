@@ -1,30 +1,102 @@
-mod tables;
+pub(crate) mod tables;
 
+use super::{Escrowed, EventDatabase, EventLocation, FirstSeen, Subscription};
 use crate::{
+    directory::AliasRecord,
     error::Error,
-    event::EventMessage,
+    event::{
+        sections::seal::{EventSeal, Seal},
+        EventMessage,
+    },
     event_message::{
         key_event_message::KeyEvent,
         signed_event_message::{
-            SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+            Message, SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
             TimestampedSignedEventMessage,
         },
         TimestampedEventMessage,
     },
-    prefix::IdentifierPrefix,
+    event_parsing::{message::signed_event_stream, SignedEventData},
+    mailbox::{MailboxMessage, Topic},
+    prefix::{BasicPrefix, IdentifierPrefix, Prefix, SelfAddressingPrefix},
+    processor::{audit::AuditRecord, rejected::RejectedMessage, EventProcessor},
 };
-use std::path::Path;
-use tables::{SledEventTree, SledEventTreeVec};
+use chrono::{DateTime, Local};
+use sled::{transaction::TransactionError, Transactional};
+use std::{convert::TryFrom, path::Path, sync::Arc, time::Duration};
+use tables::{SledEventTree, SledEventTreeVec, SledIndexTree};
 
 #[cfg(feature = "query")]
 use crate::query::reply::SignedReply;
 
+/// Flush Policy
+///
+/// When writes are made durable on disk. Flushing more often is safer
+/// in case of crash, but lowers throughput.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlushPolicy {
+    /// Every write is flushed before it returns.
+    EveryWrite,
+    /// Writes are flushed in background at given interval.
+    Interval(Duration),
+    /// Writes are flushed only by `SledEventDatabase::flush`.
+    Manual,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        // Same as sled default.
+        FlushPolicy::Interval(Duration::from_millis(500))
+    }
+}
+
+/// Event Batch
+///
+/// KEL events and receipts stored together by `apply_batch`, so
+/// either all of them are written or none.
+#[derive(Default)]
+pub struct EventBatch {
+    events: Vec<(IdentifierPrefix, TimestampedSignedEventMessage)>,
+    receipts_t: Vec<(IdentifierPrefix, SignedTransferableReceipt)>,
+    receipts_nt: Vec<(IdentifierPrefix, SignedNontransferableReceipt)>,
+}
+
+impl EventBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_kel_finalized_event(&mut self, event: SignedEventMessage, id: &IdentifierPrefix) {
+        self.events.push((id.clone(), event.into()));
+    }
+
+    pub fn add_receipt_t(&mut self, receipt: SignedTransferableReceipt, id: &IdentifierPrefix) {
+        self.receipts_t.push((id.clone(), receipt));
+    }
+
+    pub fn add_receipt_nt(&mut self, receipt: SignedNontransferableReceipt, id: &IdentifierPrefix) {
+        self.receipts_nt.push((id.clone(), receipt));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.receipts_t.is_empty() && self.receipts_nt.is_empty()
+    }
+}
+
 pub struct SledEventDatabase {
+    db: sled::Db,
+    flush_policy: FlushPolicy,
     // "iids" tree
     // this thing is expensive, but everything else is cheeeeeep
     identifiers: SledEventTree<IdentifierPrefix>,
     // "kels" tree
     key_event_logs: SledEventTreeVec<TimestampedSignedEventMessage>,
+    // "digs" tree
+    event_digests: SledIndexTree<EventLocation>,
+    // "ancs" tree
+    // sn of event which first anchored digest, keyed by identifier and
+    // digest
+    anchors: SledIndexTree<u64>,
     // "ldes" tree
     likely_duplicious_events: SledEventTreeVec<TimestampedEventMessage>,
     // "dels" tree
@@ -32,17 +104,35 @@ pub struct SledEventDatabase {
     // "rcts" tree
     receipts_nt: SledEventTreeVec<SignedNontransferableReceipt>,
     // "ures" tree
-    escrowed_receipts_nt: SledEventTreeVec<SignedNontransferableReceipt>,
+    escrowed_receipts_nt: SledEventTreeVec<Escrowed<SignedNontransferableReceipt>>,
     // "vrcs" tree
     receipts_t: SledEventTreeVec<SignedTransferableReceipt>,
     // "vres" tree
-    escrowed_receipts_t: SledEventTreeVec<SignedTransferableReceipt>,
+    escrowed_receipts_t: SledEventTreeVec<Escrowed<SignedTransferableReceipt>>,
+    // "fons" tree
+    first_seen: SledEventTreeVec<FirstSeen>,
+    // "subs" tree
+    subscriptions: SledEventTreeVec<Subscription>,
+    // "seen" tree
+    seen_events: SledEventTreeVec<TimestampedSignedEventMessage>,
+    // "mbxs" tree
+    mailbox: SledEventTreeVec<MailboxMessage>,
+    // "bans" tree
+    banned: SledEventTreeVec<IdentifierPrefix>,
+    // "srcb" tree
+    banned_sources: SledEventTreeVec<IdentifierPrefix>,
+    // "rjts" tree
+    rejected: SledEventTreeVec<RejectedMessage>,
+    // "adts" tree
+    audit_trail: SledEventTree<AuditRecord>,
+    // "alss" tree
+    aliases: SledIndexTree<AliasRecord>,
 
     #[cfg(feature = "query")]
     accepted_rpy: SledEventTreeVec<SignedReply>,
 
     #[cfg(feature = "query")]
-    escrowed_replys: SledEventTreeVec<SignedReply>,
+    escrowed_replys: SledEventTreeVec<Escrowed<SignedReply>>,
 }
 
 impl SledEventDatabase {
@@ -50,30 +140,163 @@ impl SledEventDatabase {
     where
         P: Into<&'a Path>,
     {
-        let db = sled::open(path.into())?;
-        Ok(Self {
+        Self::with_flush_policy(path, FlushPolicy::default())
+    }
+
+    pub fn with_flush_policy<'a, P>(path: P, flush_policy: FlushPolicy) -> Result<Self, Error>
+    where
+        P: Into<&'a Path>,
+    {
+        let db = sled::Config::new()
+            .path(path.into())
+            .flush_every_ms(match flush_policy {
+                FlushPolicy::Interval(interval) => Some(interval.as_millis() as u64),
+                FlushPolicy::EveryWrite | FlushPolicy::Manual => None,
+            })
+            .open()?;
+        let flush = flush_policy == FlushPolicy::EveryWrite;
+        // Seal index of databases created before it is built on open.
+        let index_anchors = !db.tree_names().iter().any(|name| name == b"ancs");
+        let database = Self {
             identifiers: SledEventTree::new(db.open_tree(b"iids")?),
-            escrowed_receipts_nt: SledEventTreeVec::new(db.open_tree(b"ures")?),
-            receipts_t: SledEventTreeVec::new(db.open_tree(b"vrcs")?),
-            escrowed_receipts_t: SledEventTreeVec::new(db.open_tree(b"vres")?),
-            receipts_nt: SledEventTreeVec::new(db.open_tree(b"rcts")?),
-            key_event_logs: SledEventTreeVec::new(db.open_tree(b"kels")?),
-            likely_duplicious_events: SledEventTreeVec::new(db.open_tree(b"ldes")?),
-            duplicitous_events: SledEventTreeVec::new(db.open_tree(b"dels")?),
+            escrowed_receipts_nt: SledEventTreeVec::with_flush(db.open_tree(b"ures")?, flush),
+            receipts_t: SledEventTreeVec::with_flush(db.open_tree(b"vrcs")?, flush),
+            escrowed_receipts_t: SledEventTreeVec::with_flush(db.open_tree(b"vres")?, flush),
+            receipts_nt: SledEventTreeVec::with_flush(db.open_tree(b"rcts")?, flush),
+            key_event_logs: SledEventTreeVec::with_flush(db.open_tree(b"kels")?, flush),
+            event_digests: SledIndexTree::with_flush(db.open_tree(b"digs")?, flush),
+            anchors: SledIndexTree::with_flush(db.open_tree(b"ancs")?, flush),
+            likely_duplicious_events: SledEventTreeVec::with_flush(db.open_tree(b"ldes")?, flush),
+            duplicitous_events: SledEventTreeVec::with_flush(db.open_tree(b"dels")?, flush),
+            first_seen: SledEventTreeVec::with_flush(db.open_tree(b"fons")?, flush),
+            subscriptions: SledEventTreeVec::with_flush(db.open_tree(b"subs")?, flush),
+            seen_events: SledEventTreeVec::with_flush(db.open_tree(b"seen")?, flush),
+            mailbox: SledEventTreeVec::with_flush(db.open_tree(b"mbxs")?, flush),
+            banned: SledEventTreeVec::with_flush(db.open_tree(b"bans")?, flush),
+            banned_sources: SledEventTreeVec::with_flush(db.open_tree(b"srcb")?, flush),
+            rejected: SledEventTreeVec::with_flush(db.open_tree(b"rjts")?, flush),
+            audit_trail: SledEventTree::new(db.open_tree(b"adts")?),
+            aliases: SledIndexTree::with_flush(db.open_tree(b"alss")?, flush),
             #[cfg(feature = "query")]
-            accepted_rpy: SledEventTreeVec::new(db.open_tree(b"knas")?),
+            accepted_rpy: SledEventTreeVec::with_flush(db.open_tree(b"knas")?, flush),
             #[cfg(feature = "query")]
-            escrowed_replys: SledEventTreeVec::new(db.open_tree(b"knes")?),
-        })
+            escrowed_replys: SledEventTreeVec::with_flush(db.open_tree(b"knes")?, flush),
+            db,
+            flush_policy,
+        };
+        if index_anchors {
+            for event in database.key_event_logs.get_all().into_iter().flatten() {
+                database.index_anchors(&event.signed_event_message.event_message)?;
+            }
+        }
+        Ok(database)
+    }
+
+    /// Flushes all writes to disk, returns number of flushed bytes.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn flush(&self) -> Result<usize, Error> {
+        Ok(self.db.flush()?)
+    }
+
+    /// Apply Batch
+    ///
+    /// Stores all events and receipts of the batch in one transaction.
+    /// Batch is written as a whole or not at all, and is flushed at
+    /// once if flush policy requires it.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn apply_batch(&self, batch: EventBatch) -> Result<(), Error> {
+        // Identifiers are designated up front, outside of transaction.
+        let key = |id: &IdentifierPrefix| self.identifiers.designated_key(id);
+        let events: Vec<_> = batch
+            .events
+            .into_iter()
+            .map(|(id, event)| (key(&id), event))
+            .collect();
+        let receipts_t: Vec<_> = batch
+            .receipts_t
+            .into_iter()
+            .map(|(id, receipt)| (key(&id), receipt))
+            .collect();
+        let receipts_nt: Vec<_> = batch
+            .receipts_nt
+            .into_iter()
+            .map(|(id, receipt)| (key(&id), receipt))
+            .collect();
+        (
+            self.key_event_logs.tree(),
+            self.event_digests.tree(),
+            self.anchors.tree(),
+            self.receipts_t.tree(),
+            self.receipts_nt.tree(),
+        )
+            .transaction(|(kels, digs, ancs, vrcs, rcts)| {
+                for (key, event) in &events {
+                    SledEventTreeVec::push_transactional(kels, *key, event)?;
+                    let event_message = &event.signed_event_message.event_message;
+                    SledIndexTree::insert_transactional(
+                        digs,
+                        &event_message.get_digest().to_str(),
+                        &EventLocation {
+                            prefix: event_message.event.get_prefix(),
+                            sn: event_message.event.get_sn(),
+                        },
+                    )?;
+                    for key in anchor_keys(event_message) {
+                        if ancs.get(key.as_str())?.is_none() {
+                            SledIndexTree::insert_transactional(
+                                ancs,
+                                &key,
+                                &event_message.event.get_sn(),
+                            )?;
+                        }
+                    }
+                }
+                for (key, receipt) in &receipts_t {
+                    SledEventTreeVec::push_transactional(vrcs, *key, receipt)?;
+                }
+                for (key, receipt) in &receipts_nt {
+                    SledEventTreeVec::push_transactional(rcts, *key, receipt)?;
+                }
+                Ok(())
+            })
+            .map_err(|e| match e {
+                TransactionError::Abort(e) => e,
+                TransactionError::Storage(e) => e.into(),
+            })?;
+        if self.flush_policy == FlushPolicy::EveryWrite {
+            self.flush()?;
+        }
+        Ok(())
     }
 
     pub fn add_kel_finalized_event(
         &self,
         event: SignedEventMessage,
         id: &IdentifierPrefix,
+        timestamp: DateTime<Local>,
     ) -> Result<(), Error> {
-        self.key_event_logs
-            .push(self.identifiers.designated_key(id), event.into())
+        let location = EventLocation {
+            prefix: id.clone(),
+            sn: event.event_message.event.get_sn(),
+        };
+        let digest = event.event_message.get_digest().to_str();
+        self.index_anchors(&event.event_message)?;
+        self.key_event_logs.push(
+            self.identifiers.designated_key(id),
+            TimestampedSignedEventMessage::new_at(event, timestamp),
+        )?;
+        self.event_digests.insert(&digest, &location)
+    }
+
+    /// Indexes digests anchored by event, unless they were anchored by
+    /// an earlier one.
+    fn index_anchors(&self, event: &EventMessage<KeyEvent>) -> Result<(), Error> {
+        for key in anchor_keys(event) {
+            if self.anchors.get(&key)?.is_none() {
+                self.anchors.insert(&key, &event.event.get_sn())?;
+            }
+        }
+        Ok(())
     }
 
     pub fn get_kel_finalized_events(
@@ -84,13 +307,201 @@ impl SledEventDatabase {
             .iter_values(self.identifiers.designated_key(id))
     }
 
+    /// Export
+    ///
+    /// Returns CESR stream of finalized events and receipts of given
+    /// identifier, or of all identifiers if `None`. All KELs go before
+    /// receipts, so receipts can be validated when stream is imported.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn export(&self, id: Option<&IdentifierPrefix>) -> Result<Vec<u8>, Error> {
+        let ids: Vec<IdentifierPrefix> = match id {
+            Some(id) => vec![id.clone()],
+            None => self.identifiers.iter().collect(),
+        };
+        let events = ids
+            .iter()
+            .flat_map(|id| self.get_kel_finalized_events(id).into_iter().flatten())
+            .map(|event| SignedEventData::from(&event.signed_event_message));
+        let receipts_nt = ids
+            .iter()
+            .flat_map(|id| self.get_receipts_nt(id).into_iter().flatten())
+            .map(SignedEventData::from);
+        let receipts_t = ids
+            .iter()
+            .flat_map(|id| self.get_receipts_t(id).into_iter().flatten())
+            .map(SignedEventData::from);
+        events
+            .chain(receipts_nt)
+            .chain(receipts_t)
+            .try_fold(vec![], |mut stream, message| {
+                stream.extend(message.to_cesr()?);
+                Ok(stream)
+            })
+    }
+
+    /// Import
+    ///
+    /// Processes CESR stream, eg. made by `export`, so everything is
+    /// validated again before it's stored. Events and receipts which
+    /// are already in database are skipped.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn import(self: &Arc<Self>, stream: &[u8]) -> Result<(), Error> {
+        let (rest, messages) =
+            signed_event_stream(stream).map_err(|e| Error::DeserializeError(e.to_string()))?;
+        if !rest.is_empty() {
+            return Err(Error::DeserializeError("Unparsable stream rest".into()));
+        }
+        let processor = EventProcessor::new(self.clone());
+        for message in messages {
+            let message = Message::try_from(message)?;
+            // Processor doesn't deduplicate receipts.
+            let known = match &message {
+                Message::TransferableRct(rct) => self
+                    .get_receipts_t(&rct.body.event.prefix)
+                    .into_iter()
+                    .flatten()
+                    .any(|receipt| &receipt == rct),
+                Message::NontransferableRct(rct) => self
+                    .get_receipts_nt(&rct.body.event.prefix)
+                    .into_iter()
+                    .flatten()
+                    .any(|receipt| &receipt == rct),
+                _ => false,
+            };
+            if known {
+                continue;
+            }
+            match processor.process(message) {
+                Ok(_) | Err(Error::EventDuplicateError) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
     pub fn remove_kel_finalized_event(
         &self,
         id: &IdentifierPrefix,
         event: &SignedEventMessage,
     ) -> Result<(), Error> {
         self.key_event_logs
-            .remove(self.identifiers.designated_key(id), &event.into())
+            .remove(self.identifiers.designated_key(id), &event.into())?;
+        let sn = event.event_message.event.get_sn();
+        for key in anchor_keys(&event.event_message) {
+            if self.anchors.get(&key)? == Some(sn) {
+                self.anchors.remove(&key)?;
+            }
+        }
+        self.event_digests
+            .remove(&event.event_message.get_digest().to_str())
+    }
+
+    /// Returns finalized event of given digest. Digest index points to
+    /// identifier and sn of event, so only its KEL is searched.
+    pub fn get_event_by_digest(
+        &self,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<TimestampedSignedEventMessage> {
+        let location = self.event_digests.get(&digest.to_str()).ok()??;
+        self.get_kel_finalized_events(&location.prefix)?
+            .find(|event| {
+                let event_message = &event.signed_event_message.event_message;
+                event_message.event.get_sn() == location.sn && &event_message.get_digest() == digest
+            })
+    }
+
+    /// Returns sn of event of identifier which anchors seal of given
+    /// digest, looked up in seal index.
+    pub fn get_anchoring_sn(
+        &self,
+        id: &IdentifierPrefix,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<u64> {
+        self.anchors.get(&anchor_key(id, digest)).ok()?
+    }
+
+    /// Adds first seen record for event of given sn and digest.
+    /// Ordinal of the record follows the last one for identifier.
+    pub fn add_first_seen(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+        digest: SelfAddressingPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<FirstSeen, Error> {
+        let key = self.identifiers.designated_key(id);
+        let ordinal = self
+            .first_seen
+            .get(key)?
+            .map(|records| records.len() as u64)
+            .unwrap_or_default();
+        let record = FirstSeen {
+            ordinal,
+            sn,
+            digest,
+            timestamp,
+        };
+        self.first_seen.push(key, record.clone())?;
+        Ok(record)
+    }
+
+    pub fn get_first_seen_records(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<impl DoubleEndedIterator<Item = FirstSeen>> {
+        self.first_seen
+            .iter_values(self.identifiers.designated_key(id))
+    }
+
+    /// Adds subscription or replaces the one with the same prefix
+    /// and source, which updates its cursor.
+    pub fn update_subscription(&self, subscription: Subscription) -> Result<(), Error> {
+        let key = self.identifiers.designated_key(&subscription.prefix);
+        let subscriptions = self
+            .subscriptions
+            .iter_values(key)
+            .map(|subs| {
+                subs.filter(|s| s.source != subscription.source)
+                    .chain(Some(subscription.clone()))
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![subscription.clone()]);
+        self.subscriptions.put(key, subscriptions)
+    }
+
+    pub fn get_subscription(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Option<Subscription> {
+        self.subscriptions
+            .iter_values(self.identifiers.designated_key(id))
+            .and_then(|mut subs| subs.find(|s| &s.source == source))
+    }
+
+    pub fn get_subscriptions(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<impl DoubleEndedIterator<Item = Subscription>> {
+        self.subscriptions
+            .iter_values(self.identifiers.designated_key(id))
+    }
+
+    pub fn get_all_subscriptions(&self) -> Option<impl DoubleEndedIterator<Item = Subscription>> {
+        self.subscriptions.get_all()
+    }
+
+    pub fn remove_subscription(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        match self.get_subscription(id, source) {
+            Some(subscription) => self
+                .subscriptions
+                .remove(self.identifiers.designated_key(id), &subscription),
+            None => Ok(()),
+        }
     }
 
     pub fn add_receipt_t(
@@ -127,6 +538,29 @@ impl SledEventDatabase {
             .iter_values(self.identifiers.designated_key(id))
     }
 
+    /// Get events receipted by
+    ///
+    /// Returns seals of events, of any identifier, receipted by
+    /// `witness` with accepted nontransferable receipt.
+    pub fn get_events_receipted_by(&self, witness: &BasicPrefix) -> Vec<EventSeal> {
+        self.receipts_nt
+            .get_all()
+            .into_iter()
+            .flatten()
+            .filter(|rct| rct.couplets.iter().any(|(w, _)| w == witness))
+            .map(|rct| EventSeal {
+                prefix: rct.body.event.prefix,
+                sn: rct.body.event.sn,
+                event_digest: rct.body.event.receipted_event_digest,
+            })
+            .fold(vec![], |mut seals, seal| {
+                if !seals.contains(&seal) {
+                    seals.push(seal);
+                }
+                seals
+            })
+    }
+
     pub fn remove_receipts_nt(&self, id: &IdentifierPrefix) -> Result<(), Error> {
         if let Some(receipts) = self.get_receipts_nt(id) {
             for receipt in receipts {
@@ -143,7 +577,7 @@ impl SledEventDatabase {
         id: &IdentifierPrefix,
     ) -> Result<(), Error> {
         self.escrowed_receipts_t
-            .push(self.identifiers.designated_key(id), receipt)
+            .push(self.identifiers.designated_key(id), Escrowed::new(receipt))
     }
 
     pub fn get_escrow_t_receipts(
@@ -152,6 +586,7 @@ impl SledEventDatabase {
     ) -> Option<impl DoubleEndedIterator<Item = SignedTransferableReceipt>> {
         self.escrowed_receipts_t
             .iter_values(self.identifiers.designated_key(id))
+            .map(|receipts| receipts.map(|escrowed| escrowed.value))
     }
 
     pub fn remove_escrow_t_receipt(
@@ -159,8 +594,16 @@ impl SledEventDatabase {
         id: &IdentifierPrefix,
         receipt: &SignedTransferableReceipt,
     ) -> Result<(), Error> {
-        self.escrowed_receipts_t
-            .remove(self.identifiers.designated_key(id), receipt)
+        self.escrowed_receipts_t.remove(
+            self.identifiers.designated_key(id),
+            &Escrowed::new(receipt.clone()),
+        )
+    }
+
+    pub fn get_all_escrow_t_receipts(
+        &self,
+    ) -> Option<impl DoubleEndedIterator<Item = Escrowed<SignedTransferableReceipt>>> {
+        self.escrowed_receipts_t.get_all()
     }
 
     pub fn add_escrow_nt_receipt(
@@ -169,7 +612,7 @@ impl SledEventDatabase {
         id: &IdentifierPrefix,
     ) -> Result<(), Error> {
         self.escrowed_receipts_nt
-            .push(self.identifiers.designated_key(id), receipt)
+            .push(self.identifiers.designated_key(id), Escrowed::new(receipt))
     }
 
     pub fn get_escrow_nt_receipts(
@@ -178,6 +621,7 @@ impl SledEventDatabase {
     ) -> Option<impl DoubleEndedIterator<Item = SignedNontransferableReceipt>> {
         self.escrowed_receipts_nt
             .iter_values(self.identifiers.designated_key(id))
+            .map(|receipts| receipts.map(|escrowed| escrowed.value))
     }
 
     pub fn remove_escrow_nt_receipt(
@@ -185,8 +629,16 @@ impl SledEventDatabase {
         id: &IdentifierPrefix,
         receipt: &SignedNontransferableReceipt,
     ) -> Result<(), Error> {
-        self.escrowed_receipts_nt
-            .remove(self.identifiers.designated_key(id), receipt)
+        self.escrowed_receipts_nt.remove(
+            self.identifiers.designated_key(id),
+            &Escrowed::new(receipt.clone()),
+        )
+    }
+
+    pub fn get_all_escrow_nt_receipts(
+        &self,
+    ) -> Option<impl DoubleEndedIterator<Item = Escrowed<SignedNontransferableReceipt>>> {
+        self.escrowed_receipts_nt.get_all()
     }
 
     pub fn add_likely_duplicious_event(
@@ -223,6 +675,201 @@ impl SledEventDatabase {
             .iter_values(self.identifiers.designated_key(id))
     }
 
+    /// Add seen event
+    ///
+    /// Records version of event no matter if it was accepted or not.
+    /// Versions with the same digest are recorded only once.
+    pub fn add_seen_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        let key = self.identifiers.designated_key(id);
+        let already_seen = self
+            .seen_events
+            .iter_values(key)
+            .into_iter()
+            .flatten()
+            .any(|ev| {
+                ev.signed_event_message.event_message.get_digest()
+                    == event.event_message.get_digest()
+            });
+        if already_seen {
+            Ok(())
+        } else {
+            self.seen_events.push(key, event.into())
+        }
+    }
+
+    pub fn get_seen_events(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<impl DoubleEndedIterator<Item = TimestampedSignedEventMessage>> {
+        self.seen_events
+            .iter_values(self.identifiers.designated_key(id))
+    }
+
+    /// Add mailbox message
+    ///
+    /// Stores message for recipient under given topic. Messages are
+    /// numbered per recipient and topic in order of arrival.
+    pub fn add_mailbox_message(
+        &self,
+        recipient: &IdentifierPrefix,
+        topic: Topic,
+        message: Vec<u8>,
+    ) -> Result<MailboxMessage, Error> {
+        let key = self.identifiers.designated_key(recipient);
+        let index = self
+            .mailbox
+            .iter_values(key)
+            .map(|msgs| msgs.filter(|m| m.topic == topic).count() as u64)
+            .unwrap_or_default();
+        let record = MailboxMessage {
+            index,
+            topic,
+            message,
+        };
+        self.mailbox.push(key, record.clone())?;
+        Ok(record)
+    }
+
+    pub fn get_mailbox_messages(
+        &self,
+        recipient: &IdentifierPrefix,
+        topic: Topic,
+    ) -> Option<impl DoubleEndedIterator<Item = MailboxMessage>> {
+        self.mailbox
+            .iter_values(self.identifiers.designated_key(recipient))
+            .map(|msgs| msgs.filter(move |m| m.topic == topic))
+    }
+
+    /// Marks identifier as banned, so messages from it or about it are
+    /// dropped by processing guard.
+    pub fn add_banned(&self, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.banned
+            .put(self.identifiers.designated_key(id), vec![id.clone()])
+    }
+
+    pub fn remove_banned(&self, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.banned.remove(self.identifiers.designated_key(id), id)
+    }
+
+    pub fn is_banned(&self, id: &IdentifierPrefix) -> bool {
+        self.banned
+            .iter_values(self.identifiers.designated_key(id))
+            .is_some_and(|mut ids| ids.next().is_some())
+    }
+
+    pub fn get_all_banned(&self) -> Option<impl DoubleEndedIterator<Item = IdentifierPrefix>> {
+        self.banned.get_all()
+    }
+
+    /// Marks source as banned, so messages it sends are dropped by
+    /// processing guard. Unlike `add_banned`, messages about the
+    /// identifier coming from other sources aren't affected.
+    pub fn add_banned_source(&self, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.banned_sources
+            .put(self.identifiers.designated_key(id), vec![id.clone()])
+    }
+
+    pub fn remove_banned_source(&self, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.banned_sources
+            .remove(self.identifiers.designated_key(id), id)
+    }
+
+    pub fn is_banned_source(&self, id: &IdentifierPrefix) -> bool {
+        self.banned_sources
+            .iter_values(self.identifiers.designated_key(id))
+            .is_some_and(|mut ids| ids.next().is_some())
+    }
+
+    /// Add rejected message
+    ///
+    /// Keeps message rejected by processing under identifier it is
+    /// about, in order of rejection. Only `limit` most recent records
+    /// are kept for one identifier, older ones are dropped.
+    pub fn add_rejected(
+        &self,
+        id: &IdentifierPrefix,
+        record: RejectedMessage,
+        limit: usize,
+    ) -> Result<(), Error> {
+        let key = self.identifiers.designated_key(id);
+        let mut records = self.rejected.get(key)?.unwrap_or_default();
+        records.push(record);
+        let excess = records.len().saturating_sub(limit);
+        records.drain(..excess);
+        self.rejected.put(key, records)
+    }
+
+    pub fn get_rejected(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<impl DoubleEndedIterator<Item = RejectedMessage>> {
+        self.rejected
+            .iter_values(self.identifiers.designated_key(id))
+    }
+
+    pub fn get_all_rejected(&self) -> Option<impl DoubleEndedIterator<Item = RejectedMessage>> {
+        self.rejected.get_all()
+    }
+
+    /// Add audit record
+    ///
+    /// Appends record to the audit trail. Records are never changed or
+    /// removed.
+    pub fn add_audit_record(&self, record: AuditRecord) -> Result<(), Error> {
+        self.audit_trail.append(&record)?;
+        if self.flush_policy == FlushPolicy::EveryWrite {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn get_audit_trail(&self) -> impl DoubleEndedIterator<Item = AuditRecord> {
+        self.audit_trail.iter()
+    }
+
+    /// Add alias
+    ///
+    /// Stores alias of identifier, or replaces record of the same alias
+    /// and identifier, eg. to attach attestation. Alias which points to
+    /// other identifier has to be removed first.
+    pub fn add_alias(&self, record: AliasRecord) -> Result<(), Error> {
+        if record.alias.is_empty() || record.alias.parse::<IdentifierPrefix>().is_ok() {
+            return Err(Error::SemanticError(format!(
+                "Improper alias: {:?}",
+                record.alias
+            )));
+        }
+        match self.aliases.get(&record.alias)? {
+            Some(taken) if taken.prefix != record.prefix => Err(Error::AliasTaken(record.alias)),
+            _ => self.aliases.insert(&record.alias, &record),
+        }
+    }
+
+    pub fn get_alias(&self, alias: &str) -> Result<Option<AliasRecord>, Error> {
+        self.aliases.get(alias)
+    }
+
+    /// Returns identifier the alias points to.
+    pub fn resolve_alias(&self, alias: &str) -> Result<Option<IdentifierPrefix>, Error> {
+        Ok(self.aliases.get(alias)?.map(|record| record.prefix))
+    }
+
+    /// Returns aliases of identifier, in alphabetical order.
+    pub fn get_aliases(&self, id: &IdentifierPrefix) -> Vec<AliasRecord> {
+        self.aliases
+            .iter()
+            .filter(|record| &record.prefix == id)
+            .collect()
+    }
+
+    pub fn remove_alias(&self, alias: &str) -> Result<(), Error> {
+        self.aliases.remove(alias)
+    }
+
     #[cfg(feature = "query")]
     pub fn update_accepted_reply(
         &self,
@@ -235,7 +882,7 @@ impl SledEventDatabase {
         {
             Some(rpys) => {
                 let filtered = rpys
-                    .filter(|s| s.reply.event.get_route() != rpy.reply.event.get_route())
+                    .filter(|s| !s.reply.event.is_same_subject(&rpy.reply.event))
                     .chain(Some(rpy.clone()).into_iter())
                     .collect();
                 self.accepted_rpy
@@ -269,7 +916,7 @@ impl SledEventDatabase {
     #[cfg(feature = "query")]
     pub fn add_escrowed_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
         self.escrowed_replys
-            .push(self.identifiers.designated_key(id), rpy)
+            .push(self.identifiers.designated_key(id), Escrowed::new(rpy))
     }
 
     #[cfg(feature = "query")]
@@ -279,6 +926,7 @@ impl SledEventDatabase {
     ) -> Option<impl DoubleEndedIterator<Item = SignedReply>> {
         self.escrowed_replys
             .iter_values(self.identifiers.designated_key(id))
+            .map(|replys| replys.map(|escrowed| escrowed.value))
     }
 
     #[cfg(feature = "query")]
@@ -288,11 +936,281 @@ impl SledEventDatabase {
         rpy: SignedReply,
     ) -> Result<(), Error> {
         self.escrowed_replys
-            .remove(self.identifiers.designated_key(id), &rpy)
+            .remove(self.identifiers.designated_key(id), &Escrowed::new(rpy))
     }
 
     #[cfg(feature = "query")]
-    pub fn get_all_escrowed_replys(&self) -> Option<impl DoubleEndedIterator<Item = SignedReply>> {
+    pub fn get_all_escrowed_replys(
+        &self,
+    ) -> Option<impl DoubleEndedIterator<Item = Escrowed<SignedReply>>> {
         self.escrowed_replys.get_all()
     }
 }
+
+fn anchor_key(id: &IdentifierPrefix, digest: &SelfAddressingPrefix) -> String {
+    format!("{}.{}", id.to_str(), digest.to_str())
+}
+
+fn anchor_keys(event: &EventMessage<KeyEvent>) -> Vec<String> {
+    let id = event.event.get_prefix();
+    let data = event.event.get_event_data();
+    data.seals()
+        .iter()
+        .filter_map(Seal::anchored_digest)
+        .map(|digest| anchor_key(&id, digest))
+        .collect()
+}
+
+// Inherent methods take precedence, so these only delegate to them.
+impl EventDatabase for SledEventDatabase {
+    fn add_kel_finalized_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<(), Error> {
+        self.add_kel_finalized_event(event, id, timestamp)
+    }
+
+    fn get_kel_finalized_events(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = TimestampedSignedEventMessage>>> {
+        self.get_kel_finalized_events(id)
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn remove_kel_finalized_event(
+        &self,
+        id: &IdentifierPrefix,
+        event: &SignedEventMessage,
+    ) -> Result<(), Error> {
+        self.remove_kel_finalized_event(id, event)
+    }
+
+    fn get_event_by_digest(
+        &self,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<TimestampedSignedEventMessage> {
+        self.get_event_by_digest(digest)
+    }
+
+    fn get_anchoring_sn(
+        &self,
+        id: &IdentifierPrefix,
+        digest: &SelfAddressingPrefix,
+    ) -> Option<u64> {
+        self.get_anchoring_sn(id, digest)
+    }
+
+    fn add_first_seen(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+        digest: SelfAddressingPrefix,
+        timestamp: DateTime<Local>,
+    ) -> Result<FirstSeen, Error> {
+        self.add_first_seen(id, sn, digest, timestamp)
+    }
+
+    fn get_first_seen_records(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = FirstSeen>>> {
+        self.get_first_seen_records(id)
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_duplicious_event(
+        &self,
+        event: SignedEventMessage,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.add_duplicious_event(event, id)
+    }
+
+    fn add_receipt_t(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.add_receipt_t(receipt, id)
+    }
+
+    fn get_receipts_t(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.get_receipts_t(id)
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_receipt_nt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.add_receipt_nt(receipt, id)
+    }
+
+    fn get_receipts_nt(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.get_receipts_nt(id)
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_escrow_t_receipt(
+        &self,
+        receipt: SignedTransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.add_escrow_t_receipt(receipt, id)
+    }
+
+    fn get_escrow_t_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedTransferableReceipt>>> {
+        self.get_escrow_t_receipts(id)
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn remove_escrow_t_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedTransferableReceipt,
+    ) -> Result<(), Error> {
+        self.remove_escrow_t_receipt(id, receipt)
+    }
+
+    fn get_all_escrow_t_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedTransferableReceipt>>>> {
+        self.get_all_escrow_t_receipts()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn add_escrow_nt_receipt(
+        &self,
+        receipt: SignedNontransferableReceipt,
+        id: &IdentifierPrefix,
+    ) -> Result<(), Error> {
+        self.add_escrow_nt_receipt(receipt, id)
+    }
+
+    fn get_escrow_nt_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedNontransferableReceipt>>> {
+        self.get_escrow_nt_receipts(id)
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn remove_escrow_nt_receipt(
+        &self,
+        id: &IdentifierPrefix,
+        receipt: &SignedNontransferableReceipt,
+    ) -> Result<(), Error> {
+        self.remove_escrow_nt_receipt(id, receipt)
+    }
+
+    fn get_all_escrow_nt_receipts(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedNontransferableReceipt>>>> {
+        self.get_all_escrow_nt_receipts()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    fn update_subscription(&self, subscription: Subscription) -> Result<(), Error> {
+        self.update_subscription(subscription)
+    }
+
+    fn get_subscription(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Option<Subscription> {
+        self.get_subscription(id, source)
+    }
+
+    fn add_audit_record(&self, record: AuditRecord) -> Result<(), Error> {
+        self.add_audit_record(record)
+    }
+
+    fn get_audit_trail(&self) -> Option<Box<dyn DoubleEndedIterator<Item = AuditRecord>>> {
+        Some(Box::new(self.get_audit_trail()))
+    }
+
+    #[cfg(feature = "query")]
+    fn update_accepted_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.update_accepted_reply(rpy, id)
+    }
+
+    #[cfg(feature = "query")]
+    fn get_accepted_replys(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = SignedReply>>> {
+        self.get_accepted_replys(id)
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    #[cfg(feature = "query")]
+    fn add_escrowed_reply(&self, rpy: SignedReply, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.add_escrowed_reply(rpy, id)
+    }
+
+    #[cfg(feature = "query")]
+    fn get_all_escrowed_replys(
+        &self,
+    ) -> Option<Box<dyn DoubleEndedIterator<Item = Escrowed<SignedReply>>>> {
+        self.get_all_escrowed_replys()
+            .map(|it| Box::new(it) as Box<dyn DoubleEndedIterator<Item = _>>)
+    }
+
+    #[cfg(feature = "query")]
+    fn remove_escrowed_reply(&self, id: &IdentifierPrefix, rpy: SignedReply) -> Result<(), Error> {
+        self.remove_escrowed_reply(id, rpy)
+    }
+}
+
+#[test]
+fn test_apply_batch() -> Result<(), Error> {
+    use tempfile::Builder;
+
+    // Taken from keripy/tests/core/test_kevery.py::test kevery
+    let kerl_str= br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw{"v":"KERI10JSON000155_","t":"rot","d":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"1","p":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","kt":"1","k":["DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI"],"n":"E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI","bt":"0","br":[],"ba":[],"a":[]}-AABAAwoiqt07w2UInzzo2DmtwkBfqX1-tTO4cYk_7YdlbJ95qA7PO5sEUkER8fZySQMNCVh64ruAh1yoew3TikwVGAQ"#;
+    let (_rest, messages) = signed_event_stream(kerl_str).unwrap();
+    let id: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+
+    for policy in [
+        FlushPolicy::EveryWrite,
+        FlushPolicy::Interval(Duration::from_millis(100)),
+        FlushPolicy::Manual,
+    ] {
+        let root = Builder::new().prefix("test-db").tempdir().unwrap();
+        let db = Arc::new(SledEventDatabase::with_flush_policy(root.path(), policy)?);
+
+        let mut batch = EventBatch::new();
+        assert!(batch.is_empty());
+        for message in messages.clone() {
+            if let Message::Event(event) = Message::try_from(message)? {
+                batch.add_kel_finalized_event(event, &id);
+            }
+        }
+        db.apply_batch(batch)?;
+        if policy == FlushPolicy::Manual {
+            db.flush()?;
+        }
+
+        assert_eq!(db.get_kel_finalized_events(&id).unwrap().count(), 2);
+        let rot_digest = "Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA".parse()?;
+        assert!(db.get_event_by_digest(&rot_digest).is_some());
+        let state = EventProcessor::new(db).compute_state(&id)?.unwrap();
+        assert_eq!(state.sn, 1);
+    }
+
+    Ok(())
+}
@@ -2,12 +2,15 @@
 use crate::error::Error;
 use arrayref::array_ref;
 use serde::{de::DeserializeOwned, Serialize};
+use sled::transaction::{ConflictableTransactionError, TransactionalTree};
 use std::marker::PhantomData;
 
 /// Imitates collection table per key
 ///
 pub(crate) struct SledEventTreeVec<T> {
     tree: sled::Tree,
+    // flushes whole db after every write
+    flush_on_write: bool,
     marker: PhantomData<T>,
 }
 
@@ -15,11 +18,23 @@ impl<T> SledEventTreeVec<T> {
     /// table constructor
     ///
     pub fn new(tree: sled::Tree) -> Self {
+        Self::with_flush(tree, false)
+    }
+
+    /// table constructor, flushing db after every write if
+    /// `flush_on_write` is set
+    ///
+    pub fn with_flush(tree: sled::Tree, flush_on_write: bool) -> Self {
         Self {
             tree,
+            flush_on_write,
             marker: PhantomData,
         }
     }
+
+    pub fn tree(&self) -> &sled::Tree {
+        &self.tree
+    }
 }
 
 /// DB "Tables" functionality
@@ -44,6 +59,30 @@ where
     pub fn put(&self, key: u64, value: Vec<T>) -> Result<(), Error> {
         self.tree
             .insert(key_bytes(key), serde_cbor::to_vec(&value)?)?;
+        if self.flush_on_write {
+            self.tree.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Pushes element to set of T within transaction on the table
+    /// tree
+    ///
+    pub fn push_transactional(
+        tree: &TransactionalTree,
+        key: u64,
+        value: &T,
+    ) -> Result<(), ConflictableTransactionError<Error>>
+    where
+        T: Clone,
+    {
+        let abort = |e: serde_cbor::Error| ConflictableTransactionError::Abort(e.into());
+        let mut set: Vec<T> = match tree.get(key_bytes(key))? {
+            Some(set) => serde_cbor::from_slice(&set).map_err(abort)?,
+            None => vec![],
+        };
+        set.push(value.clone());
+        tree.insert(&key_bytes(key), serde_cbor::to_vec(&set).map_err(abort)?)?;
         Ok(())
     }
 
@@ -204,6 +243,24 @@ where
         }
     }
 
+    /// inserts `T` under the next free key, so values are kept in
+    /// order they were appended; returns the key
+    ///
+    pub fn append(&self, value: &T) -> Result<u64, Error> {
+        let value = serde_cbor::to_vec(value)?;
+        loop {
+            let key = self.get_next_key();
+            // Retried if other writer took the key in the meantime.
+            if self
+                .tree
+                .compare_and_swap(key_bytes(key), None as Option<&[u8]>, Some(value.clone()))?
+                .is_ok()
+            {
+                return Ok(key);
+            }
+        }
+    }
+
     /// somewhat expensive! gets optional `u64` key for given `&T`
     /// if present in the db
     ///
@@ -239,6 +296,92 @@ where
     }
 }
 
+/// Index table keyed by qb64 string, eg. of event digest
+///
+pub(crate) struct SledIndexTree<T> {
+    tree: sled::Tree,
+    // flushes whole db after every write
+    flush_on_write: bool,
+    marker: PhantomData<T>,
+}
+
+impl<T> SledIndexTree<T> {
+    /// table constructor, flushing db after every write if
+    /// `flush_on_write` is set
+    ///
+    pub fn with_flush(tree: sled::Tree, flush_on_write: bool) -> Self {
+        Self {
+            tree,
+            flush_on_write,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn tree(&self) -> &sled::Tree {
+        &self.tree
+    }
+}
+
+/// DB "Tables" functionality
+///
+impl<T> SledIndexTree<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// get `T` indexed under `key`
+    ///
+    pub fn get(&self, key: &str) -> Result<Option<T>, Error> {
+        match self.tree.get(key)? {
+            Some(value) => Ok(Some(serde_cbor::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// insert `T` under given `key`
+    /// Warning! This will rewrite existing value with the same `key`
+    ///
+    pub fn insert(&self, key: &str, value: &T) -> Result<(), Error> {
+        self.tree.insert(key, serde_cbor::to_vec(value)?)?;
+        if self.flush_on_write {
+            self.tree.flush()?;
+        }
+        Ok(())
+    }
+
+    /// insert `T` under given `key` within transaction on the table
+    /// tree
+    ///
+    pub fn insert_transactional(
+        tree: &TransactionalTree,
+        key: &str,
+        value: &T,
+    ) -> Result<(), ConflictableTransactionError<Error>> {
+        let value =
+            serde_cbor::to_vec(value).map_err(|e| ConflictableTransactionError::Abort(e.into()))?;
+        tree.insert(key, value)?;
+        Ok(())
+    }
+
+    /// iterator over `T` deserialized from the db, in order of keys
+    ///
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = T> {
+        self.tree
+            .iter()
+            .flatten()
+            .flat_map(|(_, v)| serde_cbor::from_slice(&v))
+    }
+
+    /// removes `T` indexed under `key` if present
+    ///
+    pub fn remove(&self, key: &str) -> Result<(), Error> {
+        self.tree.remove(key)?;
+        if self.flush_on_write {
+            self.tree.flush()?;
+        }
+        Ok(())
+    }
+}
+
 fn key_bytes(key: u64) -> [u8; 8] {
     key.to_be_bytes()
 }
@@ -1,7 +1,8 @@
 use super::{self_signing::SelfSigning, DerivationCode};
 use crate::error::Error;
-use base64::{decode_config, encode_config};
-use core::str::FromStr;
+use alloc::string::{String, ToString};
+use base64::encode_config;
+use core::{convert::TryFrom, str::FromStr};
 
 /// Attached Signature Derivation Codes
 ///
@@ -16,34 +17,81 @@ impl AttachedSignatureCode {
     pub fn new(code: SelfSigning, index: u16) -> Self {
         Self { index, code }
     }
-}
 
-impl DerivationCode for AttachedSignatureCode {
-    // TODO, this will only work with indicies up to 63
-    fn to_str(&self) -> String {
-        [
+    /// Big Code
+    ///
+    /// Code and length of index of signatures which index doesn't fit
+    /// in one base64 character, eg. of big multisig groups or witness
+    /// lists. Index is followed by other index of the same length, which
+    /// is written the same as index and ignored when parsing. Returns
+    /// error if index doesn't fit in any code of the signature type.
+    fn big_code(&self) -> Result<Option<(&'static str, usize)>, Error> {
+        if self.index < 64 {
+            return Ok(None);
+        }
+        let out_of_range = || {
+            Error::SemanticError(format!(
+                "Signature index {} out of range of its code",
+                self.index
+            ))
+        };
+        let (code, index_len) = match self.code {
+            SelfSigning::Ed25519Sha512 => ("2A", 2),
+            SelfSigning::ECDSAsecp256k1Sha256 => ("2C", 2),
+            SelfSigning::Ed448 => ("3A", 3),
+            // No big code, index has to be lower than 64.
+            #[cfg(feature = "pq")]
+            SelfSigning::SphincsSha2128s => return Err(out_of_range()),
+        };
+        if u32::from(self.index) >= 64u32.pow(index_len as u32) {
+            return Err(out_of_range());
+        }
+        Ok(Some((code, index_len)))
+    }
+
+    /// Checks whether index fits in index part of the code.
+    pub fn is_index_encodable(&self) -> bool {
+        self.big_code().is_ok()
+    }
+
+    /// Encodes the code with its index. Returns error if index doesn't
+    /// fit in the code.
+    pub fn to_str(&self) -> Result<String, Error> {
+        if let Some((code, index_len)) = self.big_code()? {
+            let index = num_to_b64_padded(self.index, index_len)?;
+            return Ok([code, &index, &index].join(""));
+        }
+        Ok([
             match self.code {
                 SelfSigning::Ed25519Sha512 => "A",
                 SelfSigning::ECDSAsecp256k1Sha256 => "B",
                 SelfSigning::Ed448 => "0AA",
+                #[cfg(feature = "pq")]
+                SelfSigning::SphincsSha2128s => "0ZA",
             },
             &num_to_b64(self.index),
         ]
-        .join("")
+        .join(""))
     }
 
-    fn code_len(&self) -> usize {
+    pub fn code_len(&self) -> usize {
+        if let Ok(Some((code, index_len))) = self.big_code() {
+            return code.len() + 2 * index_len;
+        }
         match self.code {
             SelfSigning::Ed25519Sha512 | SelfSigning::ECDSAsecp256k1Sha256 => 2,
             SelfSigning::Ed448 => 4,
+            #[cfg(feature = "pq")]
+            SelfSigning::SphincsSha2128s => 4,
         }
     }
 
-    fn derivative_b64_len(&self) -> usize {
-        match self.code {
-            SelfSigning::Ed25519Sha512 | SelfSigning::ECDSAsecp256k1Sha256 => 86,
-            SelfSigning::Ed448 => 152,
-        }
+    pub fn derivative_b64_len(&self) -> usize {
+        self.code.derivative_b64_len()
+    }
+
+    pub fn prefix_b64_len(&self) -> usize {
+        self.code_len() + self.derivative_b64_len()
     }
 }
 
@@ -65,6 +113,29 @@ impl FromStr for AttachedSignatureCode {
                     SelfSigning::Ed448,
                     b64_to_num(&s.as_bytes()[3..4])?,
                 )),
+                #[cfg(feature = "pq")]
+                "ZA" => Ok(Self::new(
+                    SelfSigning::SphincsSha2128s,
+                    b64_to_num(&s.as_bytes()[3..4])?,
+                )),
+                _ => Err(Error::DeserializeError("Unknows signature code".into())),
+            },
+            "2" => match &s[1..2] {
+                "A" => Ok(Self::new(
+                    SelfSigning::Ed25519Sha512,
+                    b64_to_num(&s.as_bytes()[2..4])?,
+                )),
+                "C" => Ok(Self::new(
+                    SelfSigning::ECDSAsecp256k1Sha256,
+                    b64_to_num(&s.as_bytes()[2..4])?,
+                )),
+                _ => Err(Error::DeserializeError("Unknows signature code".into())),
+            },
+            "3" => match &s[1..2] {
+                "A" => Ok(Self::new(
+                    SelfSigning::Ed448,
+                    b64_to_num(&s.as_bytes()[2..5])?,
+                )),
                 _ => Err(Error::DeserializeError("Unknows signature code".into())),
             },
             _ => Err(Error::DeserializeError("Unknown attachment code".into())),
@@ -72,37 +143,67 @@ impl FromStr for AttachedSignatureCode {
     }
 }
 
-// returns the u16 from the lowest 2 bytes of the b64 string
-// currently only works for strings 4 chars or less
+/// Decodes number written in base64 characters, most significant
+/// first. Returns error if it doesn't fit in u16, so every number has
+/// only one encoding of given length.
 pub fn b64_to_num(b64: &[u8]) -> Result<u16, Error> {
-    let slice = decode_config(
-        match b64.len() {
-            1 => [r"AAA".as_bytes(), b64].concat(),
-            2 => [r"AA".as_bytes(), b64].concat(),
-            _ => b64.to_owned(),
-        },
-        base64::URL_SAFE,
-    )
-    .map_err(|e| Error::Base64DecodingError { source: e })?;
-    let len = slice.len();
-
-    Ok(u16::from_be_bytes(match len {
-        0 => [0u8; 2],
-        1 => [0, slice[0]],
-        _ => [slice[len - 2], slice[len - 1]],
-    }))
+    let num = b64.iter().try_fold(0u32, |num, c| {
+        let sextet = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => {
+                return Err(Error::DeserializeError(format!(
+                    "Improper base64 character: {}",
+                    *c as char
+                )))
+            }
+        };
+        num.checked_mul(64)
+            .map(|num| num + u32::from(sextet))
+            .ok_or_else(|| Error::DeserializeError("Base64 number out of range".into()))
+    })?;
+    u16::try_from(num)
+        .map_err(|_| Error::DeserializeError(format!("Base64 number {} out of range of u16", num)))
 }
 
 pub fn num_to_b64(num: u16) -> String {
     match num {
-        n if n < 63 => {
+        n if n < 64 => {
             encode_config([num.to_be_bytes()[1] << 2], base64::URL_SAFE_NO_PAD)[..1].to_string()
         }
-        n if n < 4095 => encode_config(num.to_be_bytes(), base64::URL_SAFE_NO_PAD)[..2].to_string(),
+        // 12 bits of number shifted to fill first two base64 characters
+        n if n < 4096 => {
+            encode_config((num << 4).to_be_bytes(), base64::URL_SAFE_NO_PAD)[..2].to_string()
+        }
         _ => encode_config(num.to_be_bytes(), base64::URL_SAFE_NO_PAD),
     }
 }
 
+/// Encodes `num` in exactly `len` base64 characters, most significant
+/// first. Returns error if `num` doesn't fit in them.
+pub fn num_to_b64_padded(num: u16, len: usize) -> Result<String, Error> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    if 64u32
+        .checked_pow(len as u32)
+        .is_some_and(|max| u32::from(num) >= max)
+    {
+        return Err(Error::SemanticError(format!(
+            "Number {} doesn't fit in {} base64 characters",
+            num, len
+        )));
+    }
+    Ok((0..len)
+        .rev()
+        .map(|i| {
+            let sextet = (u32::from(num) >> (6 * i)) & 0x3f;
+            ALPHABET[sextet as usize] as char
+        })
+        .collect())
+}
+
 #[test]
 fn num_to_b64_test() {
     assert_eq!("A", num_to_b64(0));
@@ -110,5 +211,44 @@ fn num_to_b64_test() {
     assert_eq!("C", num_to_b64(2));
     assert_eq!("D", num_to_b64(3));
     assert_eq!("b", num_to_b64(27));
-    assert_eq!("AE", num_to_b64(64));
+    assert_eq!("_", num_to_b64(63));
+    assert_eq!("BA", num_to_b64(64));
+    assert_eq!(b64_to_num(num_to_b64(4095).as_bytes()).unwrap(), 4095);
+    assert_eq!("BA", num_to_b64_padded(64, 2).unwrap());
+    assert_eq!("AAB", num_to_b64_padded(1, 3).unwrap());
+    assert_eq!("P__", num_to_b64_padded(u16::MAX, 3).unwrap());
+    assert_eq!("__", num_to_b64_padded(4095, 2).unwrap());
+    assert!(num_to_b64_padded(4096, 2).is_err());
+    assert!(num_to_b64_padded(64, 1).is_err());
+    assert_eq!(b64_to_num(b"P__").unwrap(), u16::MAX);
+    assert!(b64_to_num(b"QAA").is_err());
+    assert!(b64_to_num(b"A=").is_err());
+}
+
+#[test]
+fn test_big_index_code() -> Result<(), Error> {
+    let small = AttachedSignatureCode::new(SelfSigning::Ed25519Sha512, 63);
+    assert_eq!(small.to_str()?, "A_");
+    let big = AttachedSignatureCode::new(SelfSigning::Ed25519Sha512, 64);
+    assert_eq!(big.to_str()?, "2ABABA");
+    assert_eq!(big.code_len(), 6);
+    assert_eq!(big.prefix_b64_len(), 92);
+    let secp = AttachedSignatureCode::new(SelfSigning::ECDSAsecp256k1Sha256, 4095);
+    assert_eq!(secp.to_str()?, "2C____");
+    let ed448 = AttachedSignatureCode::new(SelfSigning::Ed448, 4096);
+    assert_eq!(ed448.to_str()?, "3ABAABAA");
+    assert_eq!(ed448.prefix_b64_len(), 160);
+    for code in [big, secp, ed448] {
+        assert_eq!(code.to_str()?.parse::<AttachedSignatureCode>()?, code);
+    }
+    // Index 4096 doesn't fit in two characters of big Ed25519 code.
+    let too_big = AttachedSignatureCode::new(SelfSigning::Ed25519Sha512, 4096);
+    assert!(!too_big.is_index_encodable());
+    assert!(too_big.to_str().is_err());
+    // Index doesn't wrap around, so it has only one encoding.
+    let ed448 = format!("3A{0}{0}{1}", "BAA", "A".repeat(152));
+    assert!(ed448.parse::<AttachedSignatureCode>().is_ok());
+    let wrapped = format!("3A{0}{0}{1}", "QAA", "A".repeat(152));
+    assert!(wrapped.parse::<AttachedSignatureCode>().is_err());
+    Ok(())
 }
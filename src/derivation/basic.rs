@@ -1,5 +1,6 @@
 use super::DerivationCode;
 use crate::{error::Error, keys::PublicKey, prefix::BasicPrefix};
+use alloc::string::String;
 use core::str::FromStr;
 use serde::{Deserialize, Serialize};
 
@@ -16,12 +17,33 @@ pub enum Basic {
     Ed448,
     X25519,
     X448,
+    #[cfg(feature = "pq")]
+    SphincsSha2128s,
 }
 
 impl Basic {
     pub fn derive(&self, public_key: PublicKey) -> BasicPrefix {
         BasicPrefix::new(*self, public_key)
     }
+
+    /// Keys of non-transferable derivations can't be rotated, so
+    /// identifier using them has no next keys commitment.
+    pub fn is_transferable(&self) -> bool {
+        !matches!(
+            self,
+            Self::Ed25519NT | Self::ECDSAsecp256k1NT | Self::Ed448NT
+        )
+    }
+
+    /// Returns non-transferable counterpart of signing key derivation.
+    pub fn to_non_transferable(&self) -> Result<Self, Error> {
+        match self {
+            Self::Ed25519 | Self::Ed25519NT => Ok(Self::Ed25519NT),
+            Self::ECDSAsecp256k1 | Self::ECDSAsecp256k1NT => Ok(Self::ECDSAsecp256k1NT),
+            Self::Ed448 | Self::Ed448NT => Ok(Self::Ed448NT),
+            _ => Err(Error::ImproperPrefixType),
+        }
+    }
 }
 
 impl DerivationCode for Basic {
@@ -35,6 +57,8 @@ impl DerivationCode for Basic {
             Self::ECDSAsecp256k1 => "1AAB",
             Self::Ed448NT => "1AAC",
             Self::Ed448 => "1AAD",
+            #[cfg(feature = "pq")]
+            Self::SphincsSha2128s => "M",
         }
         .into()
     }
@@ -42,6 +66,8 @@ impl DerivationCode for Basic {
     fn code_len(&self) -> usize {
         match self {
             Self::Ed25519NT | Self::Ed25519 | Self::X25519 | Self::X448 => 1,
+            #[cfg(feature = "pq")]
+            Self::SphincsSha2128s => 1,
             Self::ECDSAsecp256k1NT | Self::ECDSAsecp256k1 | Self::Ed448NT | Self::Ed448 => 4,
         }
     }
@@ -50,8 +76,10 @@ impl DerivationCode for Basic {
         match self {
             Self::Ed25519NT | Self::Ed25519 | Self::X25519 => 43,
             Self::X448 => 75,
-            Self::ECDSAsecp256k1NT | Self::ECDSAsecp256k1 => 47,
+            Self::ECDSAsecp256k1NT | Self::ECDSAsecp256k1 => 44,
             Self::Ed448NT | Self::Ed448 => 76,
+            #[cfg(feature = "pq")]
+            Self::SphincsSha2128s => 43,
         }
     }
 }
@@ -68,6 +96,8 @@ impl FromStr for Basic {
             "C" => Ok(Self::X25519),
             "D" => Ok(Self::Ed25519),
             "L" => Ok(Self::X448),
+            #[cfg(feature = "pq")]
+            "M" => Ok(Self::SphincsSha2128s),
             "1" => match &s[1..4] {
                 "AAA" => Ok(Self::ECDSAsecp256k1NT),
                 "AAB" => Ok(Self::ECDSAsecp256k1),
@@ -3,6 +3,8 @@ pub mod basic;
 pub mod self_addressing;
 pub mod self_signing;
 
+use alloc::string::String;
+
 pub trait DerivationCode {
     fn code_len(&self) -> usize;
     fn derivative_b64_len(&self) -> usize;
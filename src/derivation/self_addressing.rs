@@ -1,5 +1,6 @@
 use super::DerivationCode;
 use crate::{error::Error, prefix::SelfAddressingPrefix};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
 use blake2::{Blake2b, Digest, VarBlake2b, VarBlake2s};
 use blake3;
 use core::str::FromStr;
@@ -122,7 +123,9 @@ fn blake2s_256_digest(input: &[u8], key: &[u8]) -> Vec<u8> {
     use blake2::digest::{Update, VariableOutput};
     let mut hasher = VarBlake2s::new_keyed(key, 256);
     hasher.update(input);
-    hasher.finalize_boxed().to_vec()
+    let mut digest = Vec::new();
+    hasher.finalize_variable(|res| digest.extend_from_slice(res));
+    digest
 }
 
 // TODO it seems that blake2b is always defined as outputting 512 bits?
@@ -131,7 +134,9 @@ fn blake2b_256_digest(input: &[u8], key: &[u8]) -> Vec<u8> {
     use blake2::digest::{Update, VariableOutput};
     let mut hasher = VarBlake2b::new_keyed(key, 256);
     hasher.update(input);
-    hasher.finalize_boxed().to_vec()
+    let mut digest = Vec::new();
+    hasher.finalize_variable(|res| digest.extend_from_slice(res));
+    digest
 }
 
 fn blake3_512_digest(input: &[u8]) -> Vec<u8> {
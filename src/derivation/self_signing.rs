@@ -1,5 +1,6 @@
 use super::DerivationCode;
 use crate::{error::Error, prefix::SelfSigningPrefix};
+use alloc::{string::String, vec::Vec};
 use core::str::FromStr;
 
 /// Self Signing Derivations
@@ -10,12 +11,39 @@ pub enum SelfSigning {
     Ed25519Sha512,
     ECDSAsecp256k1Sha256,
     Ed448,
+    #[cfg(feature = "pq")]
+    SphincsSha2128s,
 }
 
 impl SelfSigning {
     pub fn derive(&self, sig: Vec<u8>) -> SelfSigningPrefix {
         SelfSigningPrefix::new(*self, sig)
     }
+
+    /// Number of zero bytes put before signature when it is base64
+    /// encoded, so that encoded signature is aligned to 24 bits.
+    pub fn lead_size(&self) -> usize {
+        match self {
+            #[cfg(feature = "pq")]
+            Self::SphincsSha2128s => 1,
+            _ => 0,
+        }
+    }
+
+    /// Signature with lead bytes prepended.
+    pub(crate) fn add_lead(&self, sig: &[u8]) -> Vec<u8> {
+        [&vec![0; self.lead_size()][..], sig].concat()
+    }
+
+    /// Strips lead bytes from decoded signature.
+    pub(crate) fn strip_lead(&self, raw: Vec<u8>) -> Result<Vec<u8>, Error> {
+        match raw.get(..self.lead_size()) {
+            Some(lead) if lead.iter().all(|b| *b == 0) => Ok(raw[self.lead_size()..].to_vec()),
+            _ => Err(Error::DeserializeError(
+                "Improper signature lead bytes".into(),
+            )),
+        }
+    }
 }
 
 impl DerivationCode for SelfSigning {
@@ -24,6 +52,10 @@ impl DerivationCode for SelfSigning {
             Self::Ed25519Sha512 => "0B",
             Self::ECDSAsecp256k1Sha256 => "0C",
             Self::Ed448 => "1AAE",
+            // Variable size code with one lead byte, followed by size
+            // of signature in quadlets (2619).
+            #[cfg(feature = "pq")]
+            Self::SphincsSha2128s => "5Zo7",
         }
         .into()
     }
@@ -32,6 +64,8 @@ impl DerivationCode for SelfSigning {
         match self {
             Self::Ed25519Sha512 | Self::ECDSAsecp256k1Sha256 => 2,
             Self::Ed448 => 4,
+            #[cfg(feature = "pq")]
+            Self::SphincsSha2128s => 4,
         }
     }
 
@@ -39,6 +73,8 @@ impl DerivationCode for SelfSigning {
         match self {
             Self::Ed25519Sha512 | Self::ECDSAsecp256k1Sha256 => 86,
             Self::Ed448 => 152,
+            #[cfg(feature = "pq")]
+            Self::SphincsSha2128s => 10476,
         }
     }
 }
@@ -64,6 +100,13 @@ impl FromStr for SelfSigning {
                     "Unknown signature type code".into(),
                 )),
             },
+            #[cfg(feature = "pq")]
+            "5" => match s.get(1..4) {
+                Some("Zo7") => Ok(Self::SphincsSha2128s),
+                _ => Err(Error::DeserializeError(
+                    "Unknown signature type code".into(),
+                )),
+            },
             _ => Err(Error::DeserializeError(format!(
                 "Unknown master code: {}",
                 s
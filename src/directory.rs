@@ -0,0 +1,78 @@
+//! Alias Directory
+//!
+//! Human readable aliases of identifiers, kept in database, so tools
+//! and agents can refer to `alice` instead of its prefix. Alias points
+//! to exactly one identifier, identifier can have many aliases.
+//!
+//! Aliases are local names and aren't verified by themselves. Alias can
+//! be backed by self-attestation: `/alias` reply message in which the
+//! identifier claims the alias, signed by the identifier itself.
+
+use serde::{Deserialize, Serialize};
+
+use crate::prefix::IdentifierPrefix;
+#[cfg(feature = "query")]
+use crate::{
+    error::Error,
+    query::reply::{ReplyBody, SignedReply},
+};
+
+/// Alias Record
+///
+/// Alias of identifier `prefix`, with optional self-attestation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AliasRecord {
+    pub alias: String,
+    pub prefix: IdentifierPrefix,
+    #[cfg(feature = "query")]
+    #[serde(default)]
+    pub attestation: Option<SignedReply>,
+}
+
+impl AliasRecord {
+    pub fn new(alias: &str, prefix: IdentifierPrefix) -> Self {
+        Self {
+            alias: alias.to_string(),
+            prefix,
+            #[cfg(feature = "query")]
+            attestation: None,
+        }
+    }
+
+    /// Attaches `/alias` reply of identifier claiming the alias.
+    /// Signature of the reply isn't verified here, see
+    /// `EventProcessor::verify_alias`.
+    #[cfg(feature = "query")]
+    pub fn with_attestation(self, attestation: SignedReply) -> Result<Self, Error> {
+        if !self.is_claimed_by(&attestation) {
+            return Err(Error::SemanticError(
+                "Attestation doesn't claim the alias".into(),
+            ));
+        }
+        Ok(Self {
+            attestation: Some(attestation),
+            ..self
+        })
+    }
+
+    /// Checks if reply is `/alias` reply claiming this alias for this
+    /// identifier.
+    #[cfg(feature = "query")]
+    pub fn is_claimed_by(&self, reply: &SignedReply) -> bool {
+        matches!(
+            reply.reply.event.get_reply_data(),
+            ReplyBody::Alias(claim) if claim.alias == self.alias && claim.prefix == self.prefix
+        )
+    }
+}
+
+/// Alias Claim
+///
+/// Data of `/alias` reply message, in which identifier claims alias.
+#[cfg(feature = "query")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AliasClaim {
+    #[serde(rename = "i")]
+    pub prefix: IdentifierPrefix,
+    pub alias: String,
+}
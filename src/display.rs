@@ -0,0 +1,469 @@
+//! Human Readable Rendering
+//!
+//! Annotated, indented renderings of events, attachments and key
+//! state. Derivation codes of prefixes, thresholds, seals and config
+//! traits are decoded, so it's easy to see what exactly other
+//! implementation, eg. keripy, put into a stream. Output is meant for
+//! people and can change, don't parse it.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use crate::{
+    derivation::{
+        basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning, DerivationCode,
+    },
+    event::{
+        event_data::EventData,
+        receipt::Receipt,
+        sections::{
+            key_config::NextKeysCommitment, seal::Seal, threshold::SignatureThreshold, ConfigTrait,
+            KeyConfig,
+        },
+    },
+    event_message::{
+        key_event_message::KeyEvent, signed_event_message::SignedEventMessage, Digestible,
+        EventMessage, Typeable,
+    },
+    event_parsing::{Attachment, EventType, SignedEventData},
+    prefix::{
+        AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, Prefix, SelfAddressingPrefix,
+        SelfSigningPrefix,
+    },
+    state::IdentifierState,
+};
+
+/// Types which can be rendered in human readable form.
+pub trait Pretty {
+    fn render(&self, out: &mut Renderer);
+
+    fn pretty(&self) -> String {
+        let mut out = Renderer::default();
+        self.render(&mut out);
+        out.finish()
+    }
+}
+
+/// Collects indented lines of rendering.
+#[derive(Default)]
+pub struct Renderer {
+    text: String,
+    depth: usize,
+}
+
+impl Renderer {
+    pub fn line(&mut self, line: impl Display) {
+        self.text.push_str(&"  ".repeat(self.depth));
+        self.text.push_str(&line.to_string());
+        self.text.push('\n');
+    }
+
+    pub fn field(&mut self, label: &str, value: impl Display) {
+        self.line(format!("{}: {}", label, value))
+    }
+
+    /// Renders `items` as section under `label`, one level deeper, or
+    /// marks the section as empty.
+    pub fn list<T>(&mut self, label: &str, items: &[T], item: impl Fn(&mut Self, &T)) {
+        if items.is_empty() {
+            return self.field(label, "none");
+        }
+        self.line(format!("{}:", label));
+        self.depth += 1;
+        items.iter().for_each(|i| item(self, i));
+        self.depth -= 1;
+    }
+
+    pub fn nested(&mut self, header: impl Display, body: impl FnOnce(&mut Self)) {
+        self.line(header);
+        self.depth += 1;
+        body(self);
+        self.depth -= 1;
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+pub fn basic_name(code: &Basic) -> &'static str {
+    match code {
+        Basic::ECDSAsecp256k1NT => "ECDSA secp256k1 key, non-transferable",
+        Basic::ECDSAsecp256k1 => "ECDSA secp256k1 key",
+        Basic::Ed25519NT => "Ed25519 key, non-transferable",
+        Basic::Ed25519 => "Ed25519 key",
+        Basic::Ed448NT => "Ed448 key, non-transferable",
+        Basic::Ed448 => "Ed448 key",
+        Basic::X25519 => "X25519 encryption key",
+        Basic::X448 => "X448 encryption key",
+        #[cfg(feature = "pq")]
+        Basic::SphincsSha2128s => "SPHINCS+ SHA2-128s key",
+    }
+}
+
+pub fn digest_name(code: &SelfAddressing) -> &'static str {
+    match code {
+        SelfAddressing::Blake3_256 => "Blake3-256 digest",
+        SelfAddressing::Blake2B256(_) => "Blake2b-256 digest",
+        SelfAddressing::Blake2S256(_) => "Blake2s-256 digest",
+        SelfAddressing::SHA3_256 => "SHA3-256 digest",
+        SelfAddressing::SHA2_256 => "SHA2-256 digest",
+        SelfAddressing::Blake3_512 => "Blake3-512 digest",
+        SelfAddressing::SHA3_512 => "SHA3-512 digest",
+        SelfAddressing::Blake2B512 => "Blake2b-512 digest",
+        SelfAddressing::SHA2_512 => "SHA2-512 digest",
+    }
+}
+
+pub fn signature_name(code: &SelfSigning) -> &'static str {
+    match code {
+        SelfSigning::Ed25519Sha512 => "Ed25519 signature",
+        SelfSigning::ECDSAsecp256k1Sha256 => "ECDSA secp256k1 signature",
+        SelfSigning::Ed448 => "Ed448 signature",
+        #[cfg(feature = "pq")]
+        SelfSigning::SphincsSha2128s => "SPHINCS+ SHA2-128s signature",
+    }
+}
+
+/// Prefix followed by its derivation code and what the code means, eg.
+/// `DSuh... (D: Ed25519 key)`.
+pub fn basic(prefix: &BasicPrefix) -> String {
+    annotate(prefix, &prefix.derivation, basic_name(&prefix.derivation))
+}
+
+pub fn digest(prefix: &SelfAddressingPrefix) -> String {
+    annotate(prefix, &prefix.derivation, digest_name(&prefix.derivation))
+}
+
+pub fn signature(prefix: &SelfSigningPrefix) -> String {
+    annotate(
+        prefix,
+        &prefix.derivation,
+        signature_name(&prefix.derivation),
+    )
+}
+
+pub fn identifier(prefix: &IdentifierPrefix) -> String {
+    match prefix {
+        IdentifierPrefix::Basic(bp) => basic(bp),
+        IdentifierPrefix::SelfAddressing(sap) => digest(sap),
+        IdentifierPrefix::SelfSigning(ssp) => signature(ssp),
+    }
+}
+
+pub fn indexed_signature(sig: &AttachedSignaturePrefix) -> String {
+    format!(
+        "[{}] {} ({})",
+        sig.index,
+        sig.to_str(),
+        signature_name(&sig.signature.derivation)
+    )
+}
+
+fn annotate(prefix: &impl Prefix, code: &impl DerivationCode, name: &str) -> String {
+    format!("{} ({}: {})", prefix.to_str(), code.to_str(), name)
+}
+
+pub fn threshold(threshold: &SignatureThreshold, keys: usize) -> String {
+    match threshold {
+        SignatureThreshold::Simple(t) => format!("{} of {} keys", t, keys),
+        SignatureThreshold::Weighted(w) => format!("weighted {}", w.extract_threshold()),
+    }
+}
+
+pub fn config_trait(config: &ConfigTrait) -> String {
+    let meaning = match config {
+        ConfigTrait::EstablishmentOnly => "establishment events only",
+        ConfigTrait::DoNotDelegate => "can't be delegator",
+        ConfigTrait::NoBackers => "no witnesses",
+        ConfigTrait::RegistrarBackers => "registrar backers",
+        ConfigTrait::Other(_) => "unknown trait",
+    };
+    format!("{} ({})", config.as_str(), meaning)
+}
+
+fn key_config(out: &mut Renderer, config: &KeyConfig) {
+    out.field(
+        "signing threshold",
+        threshold(&config.threshold, config.public_keys.len()),
+    );
+    let keys: Vec<_> = config.public_keys.iter().enumerate().collect();
+    out.list("keys", &keys, |out, (i, key)| {
+        out.line(format!("[{}] {}", i, basic(key)))
+    });
+    match &config.threshold_key_digest {
+        None => out.field("next keys", "none, identifier is abandoned"),
+        Some(NextKeysCommitment::Aggregated(dig)) => out.field("next keys commitment", digest(dig)),
+        Some(NextKeysCommitment::Keys(digs)) => {
+            if let Some(t) = &config.next_threshold {
+                out.field("next threshold", threshold(t, digs.len()));
+            }
+            out.list("next key digests", digs, |out, dig| out.line(digest(dig)));
+        }
+    }
+}
+
+impl Pretty for Seal {
+    fn render(&self, out: &mut Renderer) {
+        match self {
+            Seal::Event(seal) => out.nested("event seal", |out| {
+                out.field("prefix", identifier(&seal.prefix));
+                out.field("sn", seal.sn);
+                out.field("digest", digest(&seal.event_digest));
+            }),
+            Seal::Location(seal) => out.nested("location seal", |out| {
+                out.field("prefix", identifier(&seal.prefix));
+                out.field("sn", seal.sn);
+                out.field("type", &seal.ilk);
+                out.field("prior digest", digest(&seal.prior_digest));
+            }),
+            Seal::Backer(seal) => out.nested("backer seal", |out| {
+                out.field("backer", basic(&seal.backer));
+                out.field("metadata digest", digest(&seal.metadata_digest));
+            }),
+            Seal::Digest(seal) => out.field("digest seal", digest(&seal.dig)),
+            Seal::Root(seal) => out.field("merkle root seal", digest(&seal.tree_root)),
+            Seal::LastEstablishment(seal) => {
+                out.field("last establishment seal", identifier(&seal.prefix))
+            }
+        }
+    }
+}
+
+impl Pretty for EventMessage<KeyEvent> {
+    fn render(&self, out: &mut Renderer) {
+        let header = format!(
+            "{} event, sn {} ({})",
+            self.event.get_type().as_str(),
+            self.event.get_sn(),
+            self.serialization_info.to_str()
+        );
+        out.nested(header, |out| {
+            out.field("prefix", identifier(&self.event.get_prefix()));
+            out.field("digest", digest(&self.event.get_digest()));
+            let (inception, seals) = match &self.event.content.event_data {
+                EventData::Icp(icp) => (Some(icp), &icp.data),
+                EventData::Dip(dip) => {
+                    out.field("delegator", identifier(&dip.delegator));
+                    (Some(&dip.inception_data), &dip.inception_data.data)
+                }
+                EventData::Rot(rot) | EventData::Drt(rot) => {
+                    out.field("previous event", digest(&rot.previous_event_hash));
+                    key_config(out, &rot.key_config);
+                    out.field("witness threshold", rot.witness_config.tally);
+                    out.list("removed witnesses", &rot.witness_config.prune, |out, w| {
+                        out.line(basic(w))
+                    });
+                    out.list("added witnesses", &rot.witness_config.graft, |out, w| {
+                        out.line(basic(w))
+                    });
+                    (None, &rot.data)
+                }
+                EventData::Ixn(ixn) => {
+                    out.field("previous event", digest(&ixn.previous_event_hash));
+                    (None, &ixn.data)
+                }
+            };
+            if let Some(icp) = inception {
+                key_config(out, &icp.key_config);
+                out.field("witness threshold", icp.witness_config.tally);
+                out.list(
+                    "witnesses",
+                    &icp.witness_config.initial_witnesses,
+                    |out, w| out.line(basic(w)),
+                );
+                out.list("config", &icp.inception_configuration, |out, c| {
+                    out.line(config_trait(c))
+                });
+            }
+            out.list("anchored seals", seals, |out, seal| seal.render(out));
+        })
+    }
+}
+
+impl Pretty for EventMessage<Receipt> {
+    fn render(&self, out: &mut Renderer) {
+        let header = format!(
+            "{} of event sn {} ({})",
+            self.event.get_type().as_str(),
+            self.event.sn,
+            self.serialization_info.to_str()
+        );
+        out.nested(header, |out| {
+            out.field("prefix", identifier(&self.event.prefix));
+            out.field("receipted event", digest(&self.event.get_digest()));
+        })
+    }
+}
+
+impl Pretty for Attachment {
+    fn render(&self, out: &mut Renderer) {
+        match self {
+            Attachment::SealSourceCouplets(seals) => {
+                out.list("delegating event seals", seals, |out, seal| {
+                    out.line(format!("sn {}, digest {}", seal.sn, digest(&seal.digest)))
+                })
+            }
+            Attachment::AttachedSignatures(sigs) => {
+                out.list("controller signatures", sigs, |out, sig| {
+                    out.line(indexed_signature(sig))
+                })
+            }
+            Attachment::AttachedWitnessSignatures(sigs) => {
+                out.list("witness signatures", sigs, |out, sig| {
+                    out.line(indexed_signature(sig))
+                })
+            }
+            Attachment::ReceiptCouplets(couplets) => {
+                out.list("receipt couplets", couplets, |out, (witness, sig)| {
+                    out.nested(basic(witness), |out| out.line(signature(sig)))
+                })
+            }
+            Attachment::FirstSeenReplayCouplets(couplets) => {
+                out.list("first seen", couplets, |out, (sn, dt)| {
+                    out.line(format!("sn {} at {}", sn, dt.to_rfc3339()))
+                })
+            }
+            Attachment::SealSignaturesGroups(groups) => {
+                out.list("transferable signatures", groups, |out, (seal, sigs)| {
+                    let header = format!(
+                        "by {} at sn {}, event {}",
+                        identifier(&seal.prefix),
+                        seal.sn,
+                        digest(&seal.event_digest)
+                    );
+                    out.nested(header, |out| {
+                        sigs.iter().for_each(|sig| out.line(indexed_signature(sig)))
+                    })
+                })
+            }
+            Attachment::LastEstSignaturesGroups(groups) => out.list(
+                "last establishment signatures",
+                groups,
+                |out, (prefix, sigs)| {
+                    out.nested(format!("by {}", identifier(prefix)), |out| {
+                        sigs.iter().for_each(|sig| out.line(indexed_signature(sig)))
+                    })
+                },
+            ),
+            Attachment::PathedMaterialQuadruplets(path, attachments) => out.list(
+                &format!("attachments at {}", path),
+                attachments,
+                |out, a| a.render(out),
+            ),
+            Attachment::Frame(attachments) => {
+                out.list("frame", attachments, |out, a| a.render(out))
+            }
+        }
+    }
+}
+
+impl Pretty for SignedEventData {
+    fn render(&self, out: &mut Renderer) {
+        match &self.deserialized_event {
+            EventType::KeyEvent(event, _) => event.render(out),
+            EventType::Receipt(rct) => rct.render(out),
+            #[cfg(feature = "query")]
+            EventType::Qry(qry) => out.field("qry", serde_json::json!(qry)),
+            #[cfg(feature = "query")]
+            EventType::Rpy(rpy) => out.field("rpy", serde_json::json!(rpy)),
+            #[cfg(feature = "query")]
+            EventType::Exn(exn) => out.field("exn", serde_json::json!(exn)),
+        };
+        out.depth += 1;
+        self.attachments.iter().for_each(|a| a.render(out));
+        out.depth -= 1;
+    }
+}
+
+impl Pretty for SignedEventMessage {
+    fn render(&self, out: &mut Renderer) {
+        SignedEventData::from(self).render(out)
+    }
+}
+
+impl Pretty for IdentifierState {
+    fn render(&self, out: &mut Renderer) {
+        out.nested(
+            format!("key state of {}", identifier(&self.prefix)),
+            |out| {
+                let last_type = self.last_event_type.as_ref().map_or("none", |t| t.as_str());
+                out.field(
+                    "last event",
+                    format!(
+                        "{} at sn {}, {}",
+                        last_type,
+                        self.sn,
+                        digest(&self.last_event_digest)
+                    ),
+                );
+                out.field(
+                    "last establishment event",
+                    format!("sn {}, {}", self.last_est.sn, digest(&self.last_est.digest)),
+                );
+                key_config(out, &self.current);
+                out.field("witness threshold", self.tally);
+                out.list("witnesses", &self.witnesses, |out, w| out.line(basic(w)));
+                if let Some(delegator) = &self.delegator {
+                    out.field("delegator", identifier(delegator));
+                }
+                out.list("config", &self.config, |out, c| out.line(config_trait(c)));
+                if let Some(backer) = &self.backer {
+                    out.field("registrar backer", basic(&backer.backer));
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::Error, event::sections::seal::EventSeal,
+        event_message::test_fixture::TestIdentityFixture, state::EventSemantics,
+    };
+
+    #[test]
+    fn test_pretty() -> Result<(), Error> {
+        let mut alice = TestIdentityFixture::new(b"alice")?;
+        alice.rotate()?;
+        let seal = Seal::Event(EventSeal {
+            prefix: alice.prefix().clone(),
+            sn: 1,
+            event_digest: alice.events()[1].event_message.event.get_digest(),
+        });
+        let ixn = alice.interact(vec![seal])?;
+
+        let icp = alice.events()[0].pretty();
+        assert!(icp.starts_with("icp event, sn 0 (KERI10JSON"));
+        assert!(icp.contains(&format!(
+            "  prefix: {} (D: Ed25519 key)",
+            alice.prefix().to_str()
+        )));
+        assert!(icp.contains("  signing threshold: 1 of 1 keys\n"));
+        assert!(icp.contains("(D: Ed25519 key)\n"));
+        assert!(icp.contains("  witnesses: none\n"));
+        assert!(icp.contains("  controller signatures:\n    [0] "));
+
+        let ixn = ixn.pretty();
+        assert!(ixn.contains("  anchored seals:\n    event seal\n      prefix: "));
+        assert!(ixn.contains("      sn: 1\n"));
+
+        let state = alice
+            .events()
+            .iter()
+            .try_fold(IdentifierState::default(), |state, ev| {
+                ev.event_message.apply_to(state)
+            })?;
+        let state = state.pretty();
+        assert!(state.starts_with(&format!("key state of {}", alice.prefix().to_str())));
+        assert!(state.contains("  last event: ixn at sn 2, "));
+        assert!(state.contains("  last establishment event: sn 1, "));
+
+        Ok(())
+    }
+}
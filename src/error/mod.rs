@@ -1,11 +1,15 @@
+use alloc::string::String;
 use base64::DecodeError;
 use core::num::ParseIntError;
 use ed25519_dalek;
+#[cfg(feature = "std")]
 use rmp_serde as serde_mgpk;
 use serde_cbor;
 use serde_json;
 use thiserror::Error;
 
+use crate::prefix::{BasicPrefix, IdentifierPrefix, Prefix, SelfAddressingPrefix};
+
 pub mod serializer_error;
 
 #[derive(Error, Debug)]
@@ -20,11 +24,9 @@ pub enum Error {
     },
 
     #[error("CBOR Serialization error")]
-    CBORSerializationError {
-        #[from]
-        source: serde_cbor::Error,
-    },
+    CBORSerializationError(#[cfg_attr(feature = "std", from)] serde_cbor::Error),
 
+    #[cfg(feature = "std")]
     #[error("MessagePack Serialization error")]
     MsgPackSerializationError {
         #[from]
@@ -52,12 +54,36 @@ pub enum Error {
     #[error("Not enough signatures while verifying")]
     NotEnoughSigsError,
 
+    #[error("Keys don't match next keys commitment")]
+    NextKeysMismatch,
+
+    #[error("Not enough next keys exposed to satisfy next threshold")]
+    NotEnoughNextKeys,
+
+    #[error("Not enough witness receipts")]
+    NotEnoughReceiptsError,
+
     #[error("Signature verification failed")]
     SignatureVerificationError,
 
     #[error("Deserialize error: {0}")]
     DeserializeError(String),
 
+    #[error("Unsupported protocol version: {major}.{minor}")]
+    UnsupportedVersion { major: u8, minor: u8 },
+
+    #[error("Message of {size} bytes exceeds limit of {limit} bytes")]
+    MessageTooLarge { size: usize, limit: usize },
+
+    #[error("Limit of {limit} {what} exceeded")]
+    LimitExceeded { what: &'static str, limit: usize },
+
+    #[error("Non-canonical event: {0}")]
+    NonCanonical(String),
+
+    #[error("Invalid attachment: {0}")]
+    InvalidAttachment(String),
+
     #[error("Identifier is not indexed into the DB")]
     NotIndexedError,
 
@@ -65,20 +91,24 @@ pub enum Error {
     IdentifierPresentError,
 
     #[error("Base64 Decoding error")]
-    Base64DecodingError {
-        #[from]
-        source: DecodeError,
-    },
+    Base64DecodingError(#[cfg_attr(feature = "std", from)] DecodeError),
 
     #[error("Improper Prefix Type")]
     ImproperPrefixType,
 
+    #[error("Invalid prefix: {0}")]
+    InvalidPrefix(String),
+
     #[error("Storage error")]
     StorageError,
 
     #[error("Invalid identifier state")]
     InvalidIdentifierStat,
 
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
     #[cfg(feature = "async")]
     #[error("Zero send error")]
     ZeroSendError,
@@ -86,12 +116,22 @@ pub enum Error {
     #[error("Failed to obtain mutable ref to Ark of KeyManager")]
     MutArcKeyVaultError,
 
-    #[error(transparent)]
-    Ed25519DalekSignatureError(#[from] ed25519_dalek::SignatureError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    #[cfg_attr(not(feature = "std"), error("{0}"))]
+    Ed25519DalekSignatureError(#[cfg_attr(feature = "std", from)] ed25519_dalek::SignatureError),
 
+    #[cfg(feature = "sled-db")]
     #[error(transparent)]
     SledError(#[from] sled::Error),
 
+    #[cfg(feature = "rocks-db")]
+    #[error(transparent)]
+    RocksDbError(#[from] rocksdb::Error),
+
+    #[cfg(feature = "postgres-db")]
+    #[error(transparent)]
+    PostgresError(#[from] postgres::Error),
+
     #[error(transparent)]
     SerdeSerError(#[from] serializer_error::Error),
 
@@ -99,13 +139,136 @@ pub enum Error {
     #[error(transparent)]
     WalletError(#[from] universal_wallet::Error),
 
+    #[cfg(feature = "pkcs11")]
+    #[error("PKCS#11 token error: {0}")]
+    Pkcs11Error(String),
+
+    #[cfg(feature = "pkcs11")]
+    #[error(transparent)]
+    CryptokiError(#[from] cryptoki::error::Error),
+
+    #[cfg(feature = "keystore")]
+    #[error("Keystore error: {0}")]
+    KeystoreError(String),
+
     #[error("mutex is poisoned")]
     MutexPoisoned,
 
     #[error("Incorrect event digest")]
     IncorrectDigest,
 
+    #[error("Delegation chain contains a cycle")]
+    DelegationCycleError,
+
+    #[error("Prefix does not match: expected {}, got {}", .expected.to_str(), .got.to_str())]
+    PrefixMismatch {
+        expected: IdentifierPrefix,
+        got: IdentifierPrefix,
+    },
+
+    #[error("SN is not correct: expected {expected}, got {got}")]
+    SnMismatch { expected: u64, got: u64 },
+
+    #[error("Event digests doesn't match: expected {expected}, got {got}")]
+    DigestMismatch {
+        expected: SelfAddressingPrefix,
+        got: SelfAddressingPrefix,
+    },
+
+    #[error("Invalid identifier prefix binding of {}", .0.to_str())]
+    InvalidPrefixBinding(IdentifierPrefix),
+
+    #[error("Missing source seal of delegated event {} at sn {sn}", .prefix.to_str())]
+    MissingSourceSeal { prefix: IdentifierPrefix, sn: u64 },
+
+    #[error("Event of {} at sn {sn} isn't anchored by its registrar backer", .prefix.to_str())]
+    MissingBackerAnchor { prefix: IdentifierPrefix, sn: u64 },
+
+    #[error("Event of {} at sn {sn} doesn't contain delegating event seal", .delegator.to_str())]
+    MissingDelegatingSeal {
+        delegator: IdentifierPrefix,
+        sn: u64,
+    },
+
+    #[error("Missing delegator of {}", .0.to_str())]
+    MissingDelegator(IdentifierPrefix),
+
+    #[error("{} is not allowed to delegate", .0.to_str())]
+    DelegationNotAllowed(IdentifierPrefix),
+
+    #[error("Delegation of {} refused by delegator", .0.to_str())]
+    DelegationRefused(IdentifierPrefix),
+
+    #[error("{} is not a delegate of {}", .delegate.to_str(), .delegator.to_str())]
+    UnknownDelegate {
+        delegator: IdentifierPrefix,
+        delegate: IdentifierPrefix,
+    },
+
+    #[error("Delegation of {} is revoked", .0.to_str())]
+    DelegationRevoked(IdentifierPrefix),
+
+    #[error("Interaction event of establishment only identifier {}", .0.to_str())]
+    EstablishmentOnly(IdentifierPrefix),
+
+    #[error("Identifier {} is non-transferable", .0.to_str())]
+    NonTransferable(IdentifierPrefix),
+
+    #[error("Identifier {} is abandoned", .0.to_str())]
+    IdentifierAbandoned(IdentifierPrefix),
+
+    #[error("Identifier {} is banned", .0.to_str())]
+    Banned(IdentifierPrefix),
+
+    #[error("Rate limit of source {} exceeded", .0.to_str())]
+    RateLimited(IdentifierPrefix),
+
+    #[error("{} is not a designated witness of {} at sn {sn}", .witness.to_str(), .prefix.to_str())]
+    UnauthorizedWitness {
+        prefix: IdentifierPrefix,
+        sn: u64,
+        witness: BasicPrefix,
+    },
+
+    #[error("Unknown identifier {}", .0.to_str())]
+    UnknownIdentifier(IdentifierPrefix),
+
+    #[error("Alias {0} is already taken")]
+    AliasTaken(String),
+
+    #[error("Derivation code not allowed: {0}")]
+    DerivationNotAllowed(String),
+
+    #[error("Decryption error")]
+    DecryptionError,
+
+    #[error("Transport error: {0}")]
+    TransportError(String),
+
     #[cfg(feature = "query")]
     #[error(transparent)]
     QueryError(#[from] crate::query::QueryError),
 }
+
+// Without `std` these errors don't implement `core::error::Error`, so
+// they are kept in variants without being their source.
+#[cfg(not(feature = "std"))]
+impl From<serde_cbor::Error> for Error {
+    fn from(e: serde_cbor::Error) -> Self {
+        Error::CBORSerializationError(e)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Base64DecodingError(e)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<ed25519_dalek::SignatureError> for Error {
+    fn from(e: ed25519_dalek::SignatureError) -> Self {
+        Error::Ed25519DalekSignatureError(e)
+    }
+}
@@ -1,9 +1,9 @@
-use std;
-use std::fmt::{self, Display};
+use alloc::string::{String, ToString};
+use core::fmt::{self, Display};
 
 use serde::{de, ser};
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 // This is a bare-bones implementation. A real library would provide additional
 // information in its error type, for example the line and column at which the
@@ -77,4 +77,4 @@ impl Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
@@ -1,5 +1,5 @@
 use super::{
-    super::sections::{InceptionWitnessConfig, KeyConfig},
+    super::sections::{ConfigTrait, InceptionWitnessConfig, KeyConfig},
     EventData,
 };
 use crate::{
@@ -13,6 +13,7 @@ use crate::{
     prefix::IdentifierPrefix,
     state::{EventSemantics, IdentifierState, LastEstablishmentData},
 };
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
 
 /// Inception Event
@@ -27,7 +28,7 @@ pub struct InceptionEvent {
     pub witness_config: InceptionWitnessConfig,
 
     #[serde(rename = "c")]
-    pub inception_configuration: Vec<String>,
+    pub inception_configuration: Vec<ConfigTrait>,
 
     #[serde(rename = "a")]
     pub data: Vec<Seal>,
@@ -37,7 +38,7 @@ impl InceptionEvent {
     pub fn new(
         key_config: KeyConfig,
         witness_config: Option<InceptionWitnessConfig>,
-        inception_config: Option<Vec<String>>,
+        inception_config: Option<Vec<ConfigTrait>>,
     ) -> Self {
         Self {
             key_config,
@@ -80,13 +81,15 @@ impl EventSemantics for InceptionEvent {
             br: vec![],
             ba: vec![],
         };
-        Ok(IdentifierState {
+        IdentifierState {
             current: self.key_config.clone(),
             witnesses: self.witness_config.initial_witnesses.clone(),
             tally: self.witness_config.tally,
+            config: self.inception_configuration.clone(),
             last_est,
             ..state
-        })
+        }
+        .apply_backer_seals(&self.data)
     }
 }
 
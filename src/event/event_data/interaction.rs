@@ -1,7 +1,8 @@
-use super::super::sections::seal::*;
+use super::super::sections::{seal::*, ConfigTrait};
 use crate::error::Error;
 use crate::prefix::SelfAddressingPrefix;
 use crate::state::{EventSemantics, IdentifierState};
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -24,6 +25,12 @@ impl InteractionEvent {
 
 impl EventSemantics for InteractionEvent {
     fn apply_to(&self, state: IdentifierState) -> Result<IdentifierState, Error> {
+        if !state.prefix.is_transferable() {
+            return Err(Error::NonTransferable(state.prefix));
+        }
+        if state.has_config_trait(&ConfigTrait::EstablishmentOnly) {
+            return Err(Error::EstablishmentOnly(state.prefix));
+        }
         Ok(IdentifierState { ..state })
     }
 }
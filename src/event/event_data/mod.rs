@@ -5,6 +5,7 @@ pub mod rotation;
 
 use crate::{
     error::Error,
+    event::sections::seal::Seal,
     event_message::{EventTypeTag, Typeable},
     state::{EventSemantics, IdentifierState},
 };
@@ -63,6 +64,18 @@ impl<'de> Deserialize<'de> for EventData {
     }
 }
 
+impl EventData {
+    /// Seals anchored in data field of event.
+    pub fn seals(&self) -> &[Seal] {
+        match self {
+            Self::Icp(e) => &e.data,
+            Self::Rot(e) | Self::Drt(e) => &e.data,
+            Self::Ixn(e) => &e.data,
+            Self::Dip(e) => &e.inception_data.data,
+        }
+    }
+}
+
 impl EventSemantics for EventData {
     fn apply_to(&self, state: IdentifierState) -> Result<IdentifierState, Error> {
         match self {
@@ -1,9 +1,10 @@
 use super::super::sections::{seal::*, KeyConfig, WitnessConfig};
 use crate::{
     error::Error,
-    prefix::{BasicPrefix, SelfAddressingPrefix},
+    prefix::SelfAddressingPrefix,
     state::{EventSemantics, IdentifierState, LastEstablishmentData},
 };
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
 
 /// Rotation Event
@@ -26,36 +27,26 @@ pub struct RotationEvent {
 
 impl EventSemantics for RotationEvent {
     fn apply_to(&self, state: IdentifierState) -> Result<IdentifierState, Error> {
-        if state.current.verify_next(&self.key_config) {
-            // witness rotation processing
-            let witnesses =
-                if !self.witness_config.prune.is_empty() || !self.witness_config.graft.is_empty() {
-                    let mut prunned = state
-                        .witnesses
-                        .into_iter()
-                        .filter(|e| !self.witness_config.prune.contains(e))
-                        .collect::<Vec<BasicPrefix>>();
-                    prunned.append(&mut self.witness_config.graft.clone());
-                    prunned
-                } else {
-                    state.witnesses.clone()
-                };
-            let last_est = LastEstablishmentData {
-                sn: state.sn,
-                digest: state.last_event_digest.clone(),
-                br: self.witness_config.graft.clone(),
-                ba: self.witness_config.prune.clone(),
-            };
+        if !state.prefix.is_transferable() {
+            return Err(Error::NonTransferable(state.prefix));
+        }
+        state.current.check_next(&self.key_config)?;
+        // witness rotation processing
+        let witnesses = self.witness_config.apply_to_witnesses(&state.witnesses)?;
+        let last_est = LastEstablishmentData {
+            sn: state.sn,
+            digest: state.last_event_digest.clone(),
+            br: self.witness_config.prune.clone(),
+            ba: self.witness_config.graft.clone(),
+        };
 
-            Ok(IdentifierState {
-                current: self.key_config.clone(),
-                tally: self.witness_config.tally,
-                witnesses,
-                last_est,
-                ..state
-            })
-        } else {
-            Err(Error::SemanticError("Incorrect Key Config binding".into()))
+        IdentifierState {
+            current: self.key_config.clone(),
+            tally: self.witness_config.tally,
+            witnesses,
+            last_est,
+            ..state
         }
+        .apply_backer_seals(&self.data)
     }
 }
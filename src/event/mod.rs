@@ -10,14 +10,13 @@ pub mod sections;
 use self::event_data::EventData;
 use crate::error::Error;
 use crate::state::EventSemantics;
-use serde_hex::{Compact, SerHex};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Event {
     #[serde(rename = "i")]
     pub prefix: IdentifierPrefix,
 
-    #[serde(rename = "s", with = "SerHex::<Compact>")]
+    #[serde(rename = "s", with = "crate::hex")]
     pub sn: u64,
 
     #[serde(flatten)]
@@ -65,13 +64,19 @@ impl EventSemantics for Event {
                     return Err(Error::EventDuplicateError);
                 }
                 if self.sn != 0 {
-                    return Err(Error::SemanticError("SN is not correct".to_string()));
+                    return Err(Error::SnMismatch {
+                        expected: 0,
+                        got: self.sn,
+                    });
                 }
             }
             _ => {
                 // prefix must equal.
                 if self.prefix != state.prefix {
-                    return Err(Error::SemanticError("Prefix does not match".to_string()));
+                    return Err(Error::PrefixMismatch {
+                        expected: state.prefix,
+                        got: self.prefix.clone(),
+                    });
                 // sn must be incremented
                 // TODO recovery will break this rule when we implement it
                 } else if self.sn < state.sn + 1 {
@@ -84,6 +89,7 @@ impl EventSemantics for Event {
         self.event_data.apply_to(IdentifierState {
             sn: self.sn,
             prefix: self.prefix.clone(),
+            last_event_type: Some(self.get_type()),
             ..state
         })
     }
@@ -6,7 +6,6 @@ use crate::event_message::Typeable;
 use crate::prefix::IdentifierPrefix;
 use crate::prefix::SelfAddressingPrefix;
 use serde::{Deserialize, Serialize};
-use serde_hex::{Compact, SerHex};
 
 use super::EventMessage;
 use super::SerializationFormats;
@@ -25,7 +24,7 @@ pub struct Receipt {
     pub prefix: IdentifierPrefix,
 
     /// Receipted Event sn
-    #[serde(rename = "s", with = "SerHex::<Compact>")]
+    #[serde(rename = "s", with = "crate::hex")]
     pub sn: u64,
 }
 
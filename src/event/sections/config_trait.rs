@@ -0,0 +1,63 @@
+use alloc::string::{String, ToString};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Config Trait
+///
+/// Configuration of identifier set in `c` field of inception event.
+/// Traits unknown to keriox are kept as they are, so events which use
+/// them can still be verified.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigTrait {
+    /// Only establishment events are allowed in KEL.
+    EstablishmentOnly,
+    /// Identifier can't be delegator.
+    DoNotDelegate,
+    /// Identifier has no witnesses.
+    NoBackers,
+    /// Events of identifier are anchored in a ledger by registrar
+    /// backer designated with backer seal.
+    RegistrarBackers,
+    Other(String),
+}
+
+impl ConfigTrait {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::EstablishmentOnly => "EO",
+            Self::DoNotDelegate => "DND",
+            Self::NoBackers => "NB",
+            Self::RegistrarBackers => "RB",
+            Self::Other(code) => code,
+        }
+    }
+}
+
+impl From<&str> for ConfigTrait {
+    fn from(code: &str) -> Self {
+        match code {
+            "EO" => Self::EstablishmentOnly,
+            "DND" => Self::DoNotDelegate,
+            "NB" => Self::NoBackers,
+            "RB" => Self::RegistrarBackers,
+            _ => Self::Other(code.to_string()),
+        }
+    }
+}
+
+impl Serialize for ConfigTrait {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigTrait {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(String::deserialize(deserializer)?.as_str().into())
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::{borrow::ToOwned, vec::Vec};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -8,6 +9,26 @@ use crate::{
 
 use super::threshold::SignatureThreshold;
 
+/// Next Keys Commitment
+///
+/// Commitment to pre-rotated next keys. Aggregated digest binds next
+/// threshold with all next keys, so all of them have to be exposed by
+/// rotation. List of digests of every single next key, with next
+/// threshold kept in `nt` field, allows partial rotation, which exposes
+/// only subset of next keys that satisfies next threshold.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum NextKeysCommitment {
+    Aggregated(SelfAddressingPrefix),
+    Keys(Vec<SelfAddressingPrefix>),
+}
+
+impl From<SelfAddressingPrefix> for NextKeysCommitment {
+    fn from(digest: SelfAddressingPrefix) -> Self {
+        Self::Aggregated(digest)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct KeyConfig {
     #[serde(rename = "kt")]
@@ -16,8 +37,11 @@ pub struct KeyConfig {
     #[serde(rename = "k")]
     pub public_keys: Vec<BasicPrefix>,
 
+    #[serde(rename = "nt", default, skip_serializing_if = "Option::is_none")]
+    pub next_threshold: Option<SignatureThreshold>,
+
     #[serde(rename = "n", with = "empty_string_as_none")]
-    pub threshold_key_digest: Option<SelfAddressingPrefix>,
+    pub threshold_key_digest: Option<NextKeysCommitment>,
 }
 
 impl KeyConfig {
@@ -32,7 +56,25 @@ impl KeyConfig {
                 |t| t,
             ),
             public_keys,
-            threshold_key_digest,
+            next_threshold: None,
+            threshold_key_digest: threshold_key_digest.map(NextKeysCommitment::Aggregated),
+        }
+    }
+
+    /// New With Next Keys Digests
+    ///
+    /// Creates KeyConfig committing to every next key separately, which
+    /// allows partial rotation of next keys.
+    pub fn new_with_next_keys_digests(
+        public_keys: Vec<BasicPrefix>,
+        next_keys_digests: Vec<SelfAddressingPrefix>,
+        next_threshold: SignatureThreshold,
+        threshold: Option<SignatureThreshold>,
+    ) -> Self {
+        Self {
+            next_threshold: Some(next_threshold),
+            threshold_key_digest: Some(NextKeysCommitment::Keys(next_keys_digests)),
+            ..Self::new(public_keys, None, threshold)
         }
     }
 
@@ -41,6 +83,14 @@ impl KeyConfig {
     /// Verifies the given sigs against the given message using the KeyConfigs
     /// Public Keys, according to the indexes in the sigs.
     pub fn verify(&self, message: &[u8], sigs: &[AttachedSignaturePrefix]) -> Result<bool, Error> {
+        // ensure that every key has its weight
+        if let SignatureThreshold::Weighted(ref thresh) = self.threshold {
+            if thresh.length() != self.public_keys.len() {
+                return Err(Error::SemanticError(
+                    "Number of weights doesn't match number of keys".into(),
+                ));
+            }
+        }
         // ensure there's enough sigs
         if !self.threshold.enough_signatures(sigs)? {
             Err(Error::NotEnoughSigsError)
@@ -51,7 +101,9 @@ impl KeyConfig {
             && sigs
                 .iter()
                 .fold(vec![0u64; self.public_keys.len()], |mut acc, sig| {
-                    acc[sig.index as usize] += 1;
+                    if let Some(n) = acc.get_mut(sig.index as usize) {
+                        *n += 1;
+                    }
                     acc
                 })
                 .iter()
@@ -74,14 +126,96 @@ impl KeyConfig {
         }
     }
 
+    /// Verify Rotation
+    ///
+    /// Verifies signatures of rotation which establishes `next` keys.
+    /// Signatures have to satisfy threshold of `next` and, if next keys
+    /// were committed one by one, also next threshold of this config.
+    /// Against the latter, signatures of exposed keys are weighted by
+    /// positions of the keys in the commitment.
+    pub fn verify_rotation(
+        &self,
+        next: &KeyConfig,
+        message: &[u8],
+        sigs: &[AttachedSignaturePrefix],
+    ) -> Result<bool, Error> {
+        if !next.verify(message, sigs)? {
+            return Ok(false);
+        }
+        match (&self.threshold_key_digest, &self.next_threshold) {
+            (Some(NextKeysCommitment::Keys(digests)), Some(next_threshold)) => {
+                // Signatures of keys not committed to before don't count.
+                let mut prior_indexes = sigs
+                    .iter()
+                    .filter_map(|sig| next.public_keys.get(sig.index as usize))
+                    .filter_map(|key| {
+                        digests
+                            .iter()
+                            .position(|dig| dig.verify_binding(key.to_str().as_bytes()))
+                            .map(|index| index as u16)
+                    })
+                    .collect::<Vec<_>>();
+                prior_indexes.sort_unstable();
+                prior_indexes.dedup();
+                if next_threshold.enough_indexes(&prior_indexes)? {
+                    Ok(true)
+                } else {
+                    Err(Error::NotEnoughSigsError)
+                }
+            }
+            _ => Ok(true),
+        }
+    }
+
     /// Verify Next
     ///
     /// Verifies that the given next KeyConfig matches that which is committed
     /// to in the threshold_key_digest of this KeyConfig
     pub fn verify_next(&self, next: &KeyConfig) -> bool {
+        self.check_next(next).is_ok()
+    }
+
+    /// Check Next
+    ///
+    /// Like `verify_next`, but tells why the given next KeyConfig doesn't
+    /// match the commitment. Every key of partial rotation has to be one of
+    /// committed next keys and exposed keys have to satisfy committed next
+    /// threshold.
+    pub fn check_next(&self, next: &KeyConfig) -> Result<(), Error> {
         match &self.threshold_key_digest {
-            Some(n) => n == &next.commit(&n.derivation),
-            None => false,
+            Some(NextKeysCommitment::Aggregated(n)) => {
+                if n == &next.commit(&n.derivation) {
+                    Ok(())
+                } else {
+                    Err(Error::NextKeysMismatch)
+                }
+            }
+            Some(NextKeysCommitment::Keys(digests)) => {
+                let next_threshold = self
+                    .next_threshold
+                    .as_ref()
+                    .ok_or_else(|| Error::SemanticError("Missing next threshold".into()))?;
+                // Exposed keys are weighted by their position in commitment.
+                let mut exposed = next
+                    .public_keys
+                    .iter()
+                    .map(|key| {
+                        digests
+                            .iter()
+                            .position(|dig| dig.verify_binding(key.to_str().as_bytes()))
+                            .map(|index| index as u16)
+                            .ok_or(Error::NextKeysMismatch)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                exposed.sort_unstable();
+                exposed.dedup();
+                if next_threshold.enough_indexes(&exposed)? {
+                    Ok(())
+                } else {
+                    Err(Error::NotEnoughNextKeys)
+                }
+            }
+            None => Err(Error::SemanticError("No next keys commitment".into())),
         }
     }
 
@@ -94,6 +228,18 @@ impl KeyConfig {
     }
 }
 
+/// Next Keys Digests
+///
+/// Digests of every next key, for commitment allowing partial rotation.
+pub fn next_keys_digests(
+    keys: &[BasicPrefix],
+    derivation: &SelfAddressing,
+) -> Vec<SelfAddressingPrefix> {
+    keys.iter()
+        .map(|key| derivation.derive(key.to_str().as_bytes()))
+        .collect()
+}
+
 /// Serialize For Commitment
 ///
 /// Serializes a threshold and key set into the form
@@ -123,30 +269,45 @@ pub fn nxt_commitment(
 }
 
 mod empty_string_as_none {
-    use serde::{de::IntoDeserializer, Deserialize, Deserializer, Serializer};
+    use super::NextKeysCommitment;
+    use alloc::{string::String, vec::Vec};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-    pub fn deserialize<'d, D, T>(de: D) -> Result<Option<T>, D::Error>
+    pub fn deserialize<'d, D>(de: D) -> Result<Option<NextKeysCommitment>, D::Error>
     where
         D: Deserializer<'d>,
-        T: Deserialize<'d>,
     {
-        let opt = Option::<String>::deserialize(de)?;
-        let opt = opt.as_deref();
-        match opt {
-            None | Some("") => Ok(None),
-            Some(s) => T::deserialize(s.into_deserializer()).map(Some),
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Commitment {
+            Digest(String),
+            Keys(Vec<String>),
+        }
+
+        match Option::<Commitment>::deserialize(de)? {
+            None => Ok(None),
+            Some(Commitment::Digest(s)) if s.is_empty() => Ok(None),
+            Some(Commitment::Digest(s)) => s
+                .parse()
+                .map(|dig| Some(NextKeysCommitment::Aggregated(dig)))
+                .map_err(serde::de::Error::custom),
+            Some(Commitment::Keys(digests)) => digests
+                .iter()
+                .map(|dig| dig.parse())
+                .collect::<Result<_, _>>()
+                .map(|digs| Some(NextKeysCommitment::Keys(digs)))
+                .map_err(serde::de::Error::custom),
         }
     }
 
-    pub fn serialize<S, T>(t: &Option<T>, s: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(t: &Option<NextKeysCommitment>, s: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
-        T: ToString,
     {
-        s.serialize_str(&match &t {
-            Some(v) => v.to_string(),
-            None => "".into(),
-        })
+        match t {
+            Some(commitment) => commitment.serialize(s),
+            None => s.serialize_str(""),
+        }
     }
 }
 
@@ -186,6 +347,74 @@ fn test_next_commitment() {
     assert_eq!(nxt.to_str(), "EhJGhyJQTpSlZ9oWfQT-lHNl1woMazLC42O89fRHocTI");
 }
 
+#[test]
+fn test_partial_rotation() -> Result<(), Error> {
+    let next_keys: Vec<BasicPrefix> = [
+        "DeonYM2bKnAwp6VZcuCXdX72kNFw56czlZ_Tc7XHHVGI",
+        "DQghKIy-2do9OkweSgazh3Ql1vCOt5bnc5QF8x50tRoU",
+        "DNAUn-5dxm6b8Njo01O0jlStMRCjo9FYQA2mfqFW1_JA",
+    ]
+    .iter()
+    .map(|x| x.parse().unwrap())
+    .collect();
+    let current: BasicPrefix = "DK4OJI8JOr6oEEUMeSF_X-SbKysfwpKwW-ho5KARvH5c".parse()?;
+    let key_config = KeyConfig::new_with_next_keys_digests(
+        vec![current.clone()],
+        next_keys_digests(&next_keys, &SelfAddressing::Blake3_256),
+        SignatureThreshold::single_weighted(vec![(1, 2), (1, 2), (1, 2)]),
+        None,
+    );
+
+    // Commitment survives serialization.
+    let serialized = serde_json::to_string(&key_config)?;
+    assert!(serialized.contains(r#""nt":["1/2","1/2","1/2"],"n":["#));
+    assert_eq!(serde_json::from_str::<KeyConfig>(&serialized)?, key_config);
+
+    // All next keys.
+    assert!(key_config
+        .check_next(&KeyConfig::new(next_keys.clone(), None, None))
+        .is_ok());
+
+    // Part of next keys which satisfies next threshold.
+    let partial = KeyConfig::new(vec![next_keys[2].clone(), next_keys[0].clone()], None, None);
+    assert!(key_config.check_next(&partial).is_ok());
+
+    // Not enough next keys.
+    let partial = KeyConfig::new(vec![next_keys[1].clone()], None, None);
+    assert!(matches!(
+        key_config.check_next(&partial),
+        Err(Error::NotEnoughNextKeys)
+    ));
+
+    // Key which wasn't committed to.
+    let wrong = KeyConfig::new(vec![next_keys[0].clone(), current.clone()], None, None);
+    assert!(matches!(
+        key_config.check_next(&wrong),
+        Err(Error::NextKeysMismatch)
+    ));
+
+    // Aggregated commitment requires all next keys.
+    let key_config = KeyConfig::new(
+        vec![current],
+        Some(nxt_commitment(
+            &SignatureThreshold::Simple(2),
+            &next_keys,
+            &SelfAddressing::Blake3_256,
+        )),
+        None,
+    );
+    assert!(key_config
+        .check_next(&KeyConfig::new(next_keys.clone(), None, None))
+        .is_ok());
+    let partial = KeyConfig::new(next_keys[..2].to_vec(), None, None);
+    assert!(matches!(
+        key_config.check_next(&partial),
+        Err(Error::NextKeysMismatch)
+    ));
+
+    Ok(())
+}
+
 #[test]
 fn test_threshold() -> Result<(), Error> {
     use crate::derivation::{basic::Basic, self_signing::SelfSigning};
@@ -266,6 +495,18 @@ fn test_threshold() -> Result<(), Error> {
     );
     assert!(matches!(st, Err(Error::NotEnoughSigsError)));
 
+    // Signature index out of key set.
+    let mut out_of_range = signatures[2].clone();
+    out_of_range.index = 3;
+    let st = key_config.verify(msg_to_sign, &[signatures[1].clone(), out_of_range]);
+    assert!(st.is_err());
+
+    // Weights don't match keys.
+    let mut key_config = key_config;
+    key_config.threshold = SignatureThreshold::single_weighted(vec![(1, 2), (1, 2)]);
+    let st = key_config.verify(msg_to_sign, &[signatures[0].clone(), signatures[1].clone()]);
+    assert!(st.is_err());
+
     Ok(())
 }
 
@@ -297,10 +538,35 @@ fn test_verify() -> Result<(), Error> {
     let signed_msg = Message::try_from(parsed).unwrap();
     match signed_msg {
         Message::Event(ref e) => {
-            if let EventData::Icp(icp) = e.to_owned().event_message.event.get_event_data() {
-                let kc = icp.key_config;
+            if let EventData::Rot(rot) = e.to_owned().event_message.event.get_event_data() {
+                let kc = rot.key_config;
                 let msg = e.event_message.serialize()?;
                 assert!(kc.verify(&msg, &e.signatures)?);
+
+                // Both clauses have to be satisfied.
+                let sigs = [0, 1, 3]
+                    .iter()
+                    .map(|i| e.signatures[*i].clone())
+                    .collect::<Vec<_>>();
+                assert!(kc.verify(&msg, &sigs)?);
+                let sigs = [0, 1, 2]
+                    .iter()
+                    .map(|i| e.signatures[*i].clone())
+                    .collect::<Vec<_>>();
+                assert!(matches!(
+                    kc.verify(&msg, &sigs),
+                    Err(Error::NotEnoughSigsError)
+                ));
+                let sigs = [0, 3, 4]
+                    .iter()
+                    .map(|i| e.signatures[*i].clone())
+                    .collect::<Vec<_>>();
+                assert!(matches!(
+                    kc.verify(&msg, &sigs),
+                    Err(Error::NotEnoughSigsError)
+                ));
+            } else {
+                panic!("Expected rotation event");
             }
         }
         _ => (),
@@ -308,3 +574,96 @@ fn test_verify() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_verify_big_index() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::test_fixture::keypair_from_seed,
+        event_parsing::{attachment::attachment, Attachment},
+    };
+
+    // Group with more keys than small index code can address.
+    let keypairs: Vec<_> = (0..70).map(|i| keypair_from_seed(b"group", i)).collect();
+    let kc = KeyConfig::new(
+        keypairs
+            .iter()
+            .map(|(pk, _)| Basic::Ed25519.derive(pk.clone()))
+            .collect(),
+        None,
+        Some(SignatureThreshold::Simple(2)),
+    );
+    let msg = b"group message";
+    let sign = |index: u16| -> Result<AttachedSignaturePrefix, Error> {
+        Ok(AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            keypairs[index as usize].1.sign_ed(msg)?,
+            index,
+        ))
+    };
+    let sigs = vec![sign(3)?, sign(68)?];
+
+    // Signatures survive CESR round trip.
+    let cesr = Attachment::AttachedSignatures(sigs.clone()).to_cesr()?;
+    let (rest, parsed) = attachment(cesr.as_bytes()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Attachment::AttachedSignatures(sigs.clone()));
+    assert!(kc.verify(msg, &sigs)?);
+
+    // Signature indexed to other key doesn't verify.
+    let mut wrong = sign(68)?;
+    wrong.index = 67;
+    assert!(!kc.verify(msg, &[sign(3)?, wrong])?);
+
+    Ok(())
+}
+
+#[test]
+fn test_partial_rotation_signatures() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::test_fixture::keypair_from_seed,
+    };
+
+    let keypairs: Vec<_> = (0..3).map(|i| keypair_from_seed(b"next", i)).collect();
+    let next_keys: Vec<BasicPrefix> = keypairs
+        .iter()
+        .map(|(pk, _)| Basic::Ed25519.derive(pk.clone()))
+        .collect();
+    let prior = KeyConfig::new_with_next_keys_digests(
+        vec![Basic::Ed25519.derive(keypair_from_seed(b"current", 0).0)],
+        next_keys_digests(&next_keys, &SelfAddressing::Blake3_256),
+        SignatureThreshold::Simple(2),
+        None,
+    );
+    let msg = b"rotation";
+    // Signature of key at `index` of prior commitment, indexed into
+    // keys of the rotation.
+    let sign = |index: usize, new_index: u16| -> Result<AttachedSignaturePrefix, Error> {
+        Ok(AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            keypairs[index].1.sign_ed(msg)?,
+            new_index,
+        ))
+    };
+
+    // Rotation exposes third and first key, with current threshold 1.
+    let rotated = KeyConfig::new(
+        vec![next_keys[2].clone(), next_keys[0].clone()],
+        None,
+        Some(SignatureThreshold::Simple(1)),
+    );
+    assert!(prior.check_next(&rotated).is_ok());
+    let both = [sign(2, 0)?, sign(0, 1)?];
+    assert!(prior.verify_rotation(&rotated, msg, &both)?);
+
+    // One signature meets current threshold, but not prior next one.
+    let one = [sign(0, 1)?];
+    assert!(rotated.verify(msg, &one)?);
+    assert!(matches!(
+        prior.verify_rotation(&rotated, msg, &one),
+        Err(Error::NotEnoughSigsError)
+    ));
+
+    Ok(())
+}
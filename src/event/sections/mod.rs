@@ -1,15 +1,17 @@
-use crate::prefix::BasicPrefix;
+use crate::{error::Error, prefix::BasicPrefix};
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
-use serde_hex::{Compact, SerHex};
 
+pub mod config_trait;
 pub mod key_config;
 pub mod seal;
 pub mod threshold;
 
+pub use config_trait::ConfigTrait;
 pub use key_config::KeyConfig;
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct WitnessConfig {
-    #[serde(rename = "bt", with = "SerHex::<Compact>")]
+    #[serde(rename = "bt", with = "crate::hex")]
     pub tally: u64,
 
     #[serde(rename = "br")]
@@ -19,11 +21,104 @@ pub struct WitnessConfig {
     pub graft: Vec<BasicPrefix>,
 }
 
+impl WitnessConfig {
+    /// Apply To Witnesses
+    ///
+    /// Validates witness rotation against current witness list and
+    /// returns list of witnesses after removing pruned (br) and adding
+    /// grafted (ba) ones.
+    pub fn apply_to_witnesses(&self, witnesses: &[BasicPrefix]) -> Result<Vec<BasicPrefix>, Error> {
+        let has_duplicates =
+            |list: &[BasicPrefix]| list.iter().enumerate().any(|(i, w)| list[..i].contains(w));
+        if has_duplicates(&self.prune) || has_duplicates(&self.graft) {
+            return Err(Error::SemanticError(
+                "Duplicated witnesses in witness rotation".into(),
+            ));
+        }
+        if self.prune.iter().any(|w| !witnesses.contains(w)) {
+            return Err(Error::SemanticError(
+                "Witness to remove is not in witness list".into(),
+            ));
+        }
+        let mut new_witnesses = witnesses
+            .iter()
+            .filter(|w| !self.prune.contains(w))
+            .cloned()
+            .collect::<Vec<_>>();
+        if self
+            .graft
+            .iter()
+            .any(|w| new_witnesses.contains(w) || self.prune.contains(w))
+        {
+            return Err(Error::SemanticError(
+                "Witness to add is already in witness list".into(),
+            ));
+        }
+        new_witnesses.extend(self.graft.iter().cloned());
+        if self.tally > new_witnesses.len() as u64 {
+            return Err(Error::SemanticError(
+                "Witness threshold exceeds number of witnesses".into(),
+            ));
+        }
+        Ok(new_witnesses)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct InceptionWitnessConfig {
-    #[serde(rename = "bt", with = "SerHex::<Compact>")]
+    #[serde(rename = "bt", with = "crate::hex")]
     pub tally: u64,
 
     #[serde(rename = "b")]
     pub initial_witnesses: Vec<BasicPrefix>,
 }
+
+#[test]
+fn test_witness_rotation() -> Result<(), Error> {
+    let witnesses: Vec<BasicPrefix> = vec![
+        "BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo".parse()?,
+        "BuyRFMideczFZoapylLIyCjSdhtqVb31wZkRKvPfNqkw".parse()?,
+        "Bgoq68HCmYNUDgOz4Skvlu306o_NY-NrYuKAVhk3Zh9c".parse()?,
+    ];
+    let new_witness: BasicPrefix = "BtR0LbSJ1sZ1qvKjQ4Bm5X5M5I1cK-lZzY9wExUNIH6o".parse()?;
+
+    let rotation = WitnessConfig {
+        tally: 2,
+        prune: vec![witnesses[0].clone()],
+        graft: vec![new_witness.clone()],
+    };
+    assert_eq!(
+        rotation.apply_to_witnesses(&witnesses)?,
+        vec![
+            witnesses[1].clone(),
+            witnesses[2].clone(),
+            new_witness.clone()
+        ]
+    );
+
+    // Removing witness which is not in the list.
+    let rotation = WitnessConfig {
+        tally: 1,
+        prune: vec![new_witness.clone()],
+        graft: vec![],
+    };
+    assert!(rotation.apply_to_witnesses(&witnesses).is_err());
+
+    // Adding witness which is already in the list.
+    let rotation = WitnessConfig {
+        tally: 1,
+        prune: vec![],
+        graft: vec![witnesses[1].clone()],
+    };
+    assert!(rotation.apply_to_witnesses(&witnesses).is_err());
+
+    // Threshold bigger than witness number.
+    let rotation = WitnessConfig {
+        tally: 3,
+        prune: vec![witnesses[0].clone()],
+        graft: vec![],
+    };
+    assert!(rotation.apply_to_witnesses(&witnesses).is_err());
+
+    Ok(())
+}
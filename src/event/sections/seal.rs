@@ -1,14 +1,32 @@
-use crate::prefix::{IdentifierPrefix, SelfAddressingPrefix};
+use crate::prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix};
+use alloc::string::String;
 use serde::{Deserialize, Serialize};
-use serde_hex::{Compact, SerHex};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum Seal {
     Location(LocationSeal),
     Event(EventSeal),
+    // Has to precede digest seal, which matches any object with digest.
+    Backer(BackerSeal),
     Digest(DigestSeal),
     Root(RootSeal),
+    // Matches any object with identifier prefix, so it has to be the
+    // last one.
+    LastEstablishment(LastEstablishmentSeal),
+}
+
+impl Seal {
+    /// Digest of data or event the seal commits to, if any.
+    pub fn anchored_digest(&self) -> Option<&SelfAddressingPrefix> {
+        match self {
+            Seal::Digest(seal) => Some(&seal.dig),
+            Seal::Root(seal) => Some(&seal.tree_root),
+            Seal::Event(seal) => Some(&seal.event_digest),
+            Seal::Backer(seal) => Some(&seal.metadata_digest),
+            Seal::Location(_) | Seal::LastEstablishment(_) => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -17,18 +35,53 @@ pub struct DigestSeal {
     pub dig: SelfAddressingPrefix,
 }
 
+impl DigestSeal {
+    /// Checks if seal commits to `data`.
+    pub fn verify(&self, data: &[u8]) -> bool {
+        self.dig.verify_binding(data)
+    }
+}
+
+/// Backer Seal
+///
+/// Designates registrar backer of identifier incepted with registrar
+/// backers trait, which anchors its events in a ledger, and commits to
+/// backer's metadata, eg. ledger address.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackerSeal {
+    #[serde(rename = "bi")]
+    pub backer: BasicPrefix,
+
+    #[serde(rename = "d")]
+    pub metadata_digest: SelfAddressingPrefix,
+}
+
+/// Merkle Root Seal
+///
+/// Commits to the root of merkle tree of anchored data.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct RootSeal {
     #[serde(rename = "rd")]
     pub tree_root: SelfAddressingPrefix,
 }
 
+/// Last Establishment Event Seal
+///
+/// Refers to the last establishment event of identifier, whichever it
+/// is at the time the seal is resolved. Used to sign with current keys
+/// of identifier without committing to its sn.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LastEstablishmentSeal {
+    #[serde(rename = "i")]
+    pub prefix: IdentifierPrefix,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct EventSeal {
     #[serde(rename = "i")]
     pub prefix: IdentifierPrefix,
 
-    #[serde(rename = "s", with = "SerHex::<Compact>")]
+    #[serde(rename = "s", with = "crate::hex")]
     pub sn: u64,
 
     #[serde(rename = "d")]
@@ -40,7 +93,7 @@ pub struct LocationSeal {
     #[serde(rename = "i")]
     pub prefix: IdentifierPrefix,
 
-    #[serde(rename = "s", with = "SerHex::<Compact>")]
+    #[serde(rename = "s", with = "crate::hex")]
     pub sn: u64,
 
     #[serde(rename = "t")]
@@ -90,4 +143,22 @@ fn test_seal_deserialization() {
     let seal: Seal = serde_json::from_str(seal_str).unwrap();
     assert!(matches!(seal, Seal::Digest(_)));
     assert_eq!(serde_json::to_string(&seal).unwrap(), seal_str);
+
+    // Backer seal
+    let seal_str = r#"{"bi":"BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo","d":"Ey-05xXgtfYvKyMGa-dladxUQyXv4JaPg-gaKuXLfceQ"}"#;
+    let seal: Seal = serde_json::from_str(seal_str).unwrap();
+    assert!(matches!(seal, Seal::Backer(_)));
+    assert_eq!(serde_json::to_string(&seal).unwrap(), seal_str);
+
+    // Merkle root seal
+    let seal_str = r#"{"rd":"Ey-05xXgtfYvKyMGa-dladxUQyXv4JaPg-gaKuXLfceQ"}"#;
+    let seal: Seal = serde_json::from_str(seal_str).unwrap();
+    assert!(matches!(seal, Seal::Root(_)));
+    assert_eq!(serde_json::to_string(&seal).unwrap(), seal_str);
+
+    // Last establishment event seal
+    let seal_str = r#"{"i":"EXmV-FiCyD7U76DoXSQoHlG30hFLD2cuYWEQPp0mEu1U"}"#;
+    let seal: Seal = serde_json::from_str(seal_str).unwrap();
+    assert!(matches!(seal, Seal::LastEstablishment(_)));
+    assert_eq!(serde_json::to_string(&seal).unwrap(), seal_str);
 }
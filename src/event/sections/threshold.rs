@@ -1,19 +1,64 @@
-use std::{fmt, str::FromStr};
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
 
 use crate::{error::Error, prefix::AttachedSignaturePrefix};
-use fraction::{Fraction, One, Zero};
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use serde_hex::{Compact, SerHex};
+
+/// Reduced fraction of key weight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fraction {
+    n: u64,
+    d: u64,
+}
+
+impl Fraction {
+    fn new(n: u64, d: u64) -> Self {
+        match gcd(n, d) {
+            0 => Self { n, d },
+            g => Self { n: n / g, d: d / g },
+        }
+    }
+}
+
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.d {
+            1 => write!(f, "{}", self.n),
+            d => write!(f, "{}/{}", self.n, d),
+        }
+    }
+}
+
+fn gcd<T>(mut a: T, mut b: T) -> T
+where
+    T: Copy + PartialEq + Default + core::ops::Rem<Output = T>,
+{
+    while b != T::default() {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ThresholdFraction {
     fraction: Fraction,
+    // Fraction as written in event. It's kept for serialization, because
+    // signatures are made over event with unreduced fractions, like "1/1".
+    repr: String,
 }
 
 impl ThresholdFraction {
     pub fn new(n: u64, d: u64) -> Self {
+        let fraction = Fraction::new(n, d);
         Self {
-            fraction: Fraction::new(n, d),
+            repr: fraction.to_string(),
+            fraction,
         }
     }
 }
@@ -29,20 +74,18 @@ impl FromStr for ThresholdFraction {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let f: Vec<_> = s.split('/').collect();
-        if f.len() > 2 {
-            Err(Error::SemanticError("Improper threshold fraction".into()))
-        } else if f.len() == 1 {
-            let a = f[0].parse::<u64>()?;
-            Ok(ThresholdFraction {
-                fraction: Fraction::new(a, 1u64),
-            })
-        } else {
-            let a = f[0].parse::<u64>()?;
-            let b = f[1].parse::<u64>()?;
-            Ok(ThresholdFraction {
-                fraction: Fraction::new(a, b),
-            })
-        }
+        let fraction = match f.as_slice() {
+            [n] => Fraction::new(n.parse::<u64>()?, 1u64),
+            [n, d] => match d.parse::<u64>()? {
+                0 => return Err(Error::SemanticError("Improper threshold fraction".into())),
+                d => Fraction::new(n.parse::<u64>()?, d),
+            },
+            _ => return Err(Error::SemanticError("Improper threshold fraction".into())),
+        };
+        Ok(ThresholdFraction {
+            fraction,
+            repr: s.to_string(),
+        })
     }
 }
 impl<'de> Deserialize<'de> for ThresholdFraction {
@@ -60,14 +103,14 @@ impl Serialize for ThresholdFraction {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&format!("{}", self.fraction))
+        serializer.serialize_str(&self.repr)
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum SignatureThreshold {
-    #[serde(with = "SerHex::<Compact>")]
+    #[serde(with = "crate::hex")]
     Simple(u64),
     Weighted(WeightedThreshold),
 }
@@ -81,9 +124,22 @@ pub enum WeightedThreshold {
 
 impl WeightedThreshold {
     pub fn enough_signatures(&self, sigs: &[AttachedSignaturePrefix]) -> Result<bool, Error> {
+        self.enough_indexes(&unique_indexes(sigs))
+    }
+
+    pub fn enough_indexes(&self, indexes: &[u16]) -> Result<bool, Error> {
         match self {
-            WeightedThreshold::Single(clause) => clause.enough_signatures(0, sigs),
-            WeightedThreshold::Multi(clauses) => clauses.enough_signatures(sigs),
+            WeightedThreshold::Single(clause) => clause.enough_indexes(0, indexes),
+            WeightedThreshold::Multi(clauses) => clauses.enough_indexes(indexes),
+        }
+    }
+
+    /// Number of weights in threshold, which has to be equal to the
+    /// number of keys.
+    pub fn length(&self) -> usize {
+        match self {
+            WeightedThreshold::Single(clause) => clause.0.len(),
+            WeightedThreshold::Multi(clauses) => clauses.length(),
         }
     }
 
@@ -120,10 +176,24 @@ impl SignatureThreshold {
         )))
     }
 
+    /// Weighted threshold from list of clauses of fractions. Single
+    /// clause is serialized as flat list of weights.
+    pub fn weighted(fracs: Vec<Vec<(u64, u64)>>) -> Self {
+        match fracs.len() {
+            1 => Self::single_weighted(fracs.into_iter().flatten().collect()),
+            _ => Self::multi_weighted(fracs),
+        }
+    }
+
     pub fn enough_signatures(&self, sigs: &[AttachedSignaturePrefix]) -> Result<bool, Error> {
+        self.enough_indexes(&unique_indexes(sigs))
+    }
+
+    /// Checks if keys of given, unique indexes satisfy threshold.
+    pub fn enough_indexes(&self, indexes: &[u16]) -> Result<bool, Error> {
         match self {
-            SignatureThreshold::Simple(ref t) => Ok((sigs.len() as u64) >= *t),
-            SignatureThreshold::Weighted(ref thresh) => thresh.enough_signatures(sigs),
+            SignatureThreshold::Simple(ref t) => Ok((indexes.len() as u64) >= *t),
+            SignatureThreshold::Weighted(ref thresh) => thresh.enough_indexes(indexes),
         }
     }
 }
@@ -154,9 +224,34 @@ impl ThresholdClause {
         start_index: u16,
         sigs: &[AttachedSignaturePrefix],
     ) -> Result<bool, Error> {
-        Ok(sigs.iter().fold(Zero::zero(), |acc: Fraction, sig| {
-            acc + self.0[(sig.index - start_index) as usize].fraction
-        }) >= One::one())
+        self.enough_indexes(start_index, &unique_indexes(sigs))
+    }
+
+    pub fn enough_indexes(&self, start_index: u16, indexes: &[u16]) -> Result<bool, Error> {
+        // Sum of weights as numerator and denominator, kept reduced.
+        let (n, d) =
+            indexes
+                .iter()
+                .try_fold((0u128, 1u128), |(n, d), index| -> Result<_, Error> {
+                    let weight = index
+                        .checked_sub(start_index)
+                        .and_then(|i| self.0.get(i as usize))
+                        .ok_or_else(|| Error::SemanticError("Key index not present in set".into()))?
+                        .fraction;
+                    let (wn, wd) = (weight.n as u128, weight.d as u128);
+                    let sum = n
+                        .checked_mul(wd)
+                        .zip(wn.checked_mul(d))
+                        .and_then(|(a, b)| a.checked_add(b))
+                        .zip(d.checked_mul(wd))
+                        .map(|(n, d)| match gcd(n, d) {
+                            0 => (n, d),
+                            g => (n / g, d / g),
+                        })
+                        .ok_or_else(|| Error::SemanticError("Threshold weights overflow".into()))?;
+                    Ok(sum)
+                })?;
+        Ok(n >= d)
     }
 
     pub fn extract_threshold(&self) -> String {
@@ -190,24 +285,35 @@ impl MultiClauses {
     }
 
     pub fn enough_signatures(&self, sigs: &[AttachedSignaturePrefix]) -> Result<bool, Error> {
+        self.enough_indexes(&unique_indexes(sigs))
+    }
+
+    pub fn enough_indexes(&self, indexes: &[u16]) -> Result<bool, Error> {
+        if indexes.iter().any(|index| *index as usize >= self.length()) {
+            return Err(Error::SemanticError("Key index not present in set".into()));
+        }
         Ok(self
             .0
             .iter()
             .fold(Ok((0, true)), |acc, clause| -> Result<_, Error> {
                 let (start, enough) = acc?;
-                let sigs: Vec<AttachedSignaturePrefix> = sigs
-                    .to_owned()
-                    .into_iter()
-                    .filter(|sig| sig.index >= start && sig.index < start + clause.0.len() as u16)
+                let indexes: Vec<u16> = indexes
+                    .iter()
+                    .cloned()
+                    .filter(|index| *index >= start && *index < start + clause.0.len() as u16)
                     .collect();
                 Ok((
                     start + clause.0.len() as u16,
-                    enough && clause.enough_signatures(start, &sigs)?,
+                    enough && clause.enough_indexes(start, &indexes)?,
                 ))
             })?
             .1)
     }
 
+    pub fn length(&self) -> usize {
+        self.0.iter().map(|clause| clause.0.len()).sum()
+    }
+
     pub fn extract_threshold(&self) -> String {
         self.0
             .iter()
@@ -217,6 +323,14 @@ impl MultiClauses {
     }
 }
 
+/// Indexes of signatures, without duplicates.
+fn unique_indexes(sigs: &[AttachedSignaturePrefix]) -> Vec<u16> {
+    let mut indexes: Vec<u16> = sigs.iter().map(|sig| sig.index).collect();
+    indexes.sort_unstable();
+    indexes.dedup();
+    indexes
+}
+
 #[test]
 fn test_enough_sigs() -> Result<(), Error> {
     use crate::derivation::self_signing::SelfSigning;
@@ -241,6 +355,19 @@ fn test_enough_sigs() -> Result<(), Error> {
     let not_enough = vec![dump_signatures[0].clone()];
     assert!(!wt.enough_signatures(&not_enough.clone())?);
 
+    // Duplicated signatures are counted once.
+    let duplicated = vec![
+        dump_signatures[0].clone(),
+        dump_signatures[1].clone(),
+        dump_signatures[1].clone(),
+    ];
+    assert!(!wt.enough_signatures(&duplicated)?);
+
+    // Signature index out of threshold.
+    let out_of_range = AttachedSignaturePrefix::new(SelfSigning::Ed25519Sha512, vec![], 4);
+    let st = wt.enough_signatures(&[out_of_range]);
+    assert!(st.is_err());
+
     Ok(())
 }
 
@@ -255,5 +382,19 @@ pub fn test_weighted_treshold_serialization() -> Result<(), Error> {
     let wt: WeightedThreshold = serde_json::from_str(&single_threshold)?;
     assert!(matches!(wt, WeightedThreshold::Single(_)));
     assert_eq!(serde_json::to_string(&wt).unwrap(), single_threshold);
+
+    // Fractions are serialized as they were written.
+    let unreduced_threshold = r#"[["1/2","1/2","1/2"],["1/1","1/1"]]"#.to_string();
+    let wt: WeightedThreshold = serde_json::from_str(&unreduced_threshold)?;
+    assert_eq!(serde_json::to_string(&wt).unwrap(), unreduced_threshold);
+    assert_eq!(wt.extract_threshold(), "1/2,1/2,1/2&1,1");
+
+    let st = SignatureThreshold::weighted(vec![vec![(1, 2), (1, 2), (1, 2)], vec![(1, 1), (1, 1)]]);
+    assert_eq!(
+        serde_json::to_string(&st).unwrap(),
+        r#"[["1/2","1/2","1/2"],["1","1"]]"#
+    );
+
+    assert!(serde_json::from_str::<WeightedThreshold>(r#"["1/0"]"#).is_err());
     Ok(())
 }
@@ -0,0 +1,149 @@
+//! Canonical Serialization
+//!
+//! Event digests and signatures are computed over event bytes as
+//! received, but the same event can be serialized in more than one way,
+//! eg. with other field order or whitespace. Checks in this module make
+//! sure that received bytes are exactly what re-serialization of parsed
+//! event gives, so no two different byte strings pass as the same event.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use serde::{
+    de::{IgnoredAny, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+use super::{serialization_info::SerializationFormats, Digestible, EventMessage, Typeable};
+use crate::error::Error;
+
+/// Checks if `raw` is canonical serialization of `event`, ie. its size
+/// field matches its length, fields are in canonical order and
+/// re-serialization of `event` gives exactly the same bytes.
+pub fn check_canonical<T>(event: &EventMessage<T>, raw: &[u8]) -> Result<(), Error>
+where
+    T: Clone + Serialize + Digestible + Typeable,
+{
+    let size = event.serialization_info.size;
+    if size != raw.len() {
+        return Err(Error::NonCanonical(format!(
+            "size field is {} but event has {} bytes",
+            size,
+            raw.len()
+        )));
+    }
+    let canonical = event.serialize()?;
+    if canonical == raw {
+        return Ok(());
+    }
+    let kind = event.serialization();
+    let (expected, got) = (field_labels(kind, &canonical)?, field_labels(kind, raw)?);
+    if expected != got {
+        return Err(Error::NonCanonical(format!(
+            "fields in order {}, expected {}",
+            got.join(","),
+            expected.join(",")
+        )));
+    }
+    let position = canonical
+        .iter()
+        .zip(raw)
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| canonical.len().min(raw.len()));
+    Err(Error::NonCanonical(format!(
+        "re-serialization differs at byte {}",
+        position
+    )))
+}
+
+/// Labels of top level fields of serialized event, in order of
+/// appearance.
+fn field_labels(kind: SerializationFormats, data: &[u8]) -> Result<Vec<String>, Error> {
+    let labels: FieldLabels = match kind {
+        SerializationFormats::JSON => serde_json::from_slice(data)?,
+        SerializationFormats::CBOR => serde_cbor::from_slice(data)?,
+        #[cfg(feature = "std")]
+        SerializationFormats::MGPK => {
+            rmp_serde::from_read_ref(data).map_err(|e| Error::DeserializeError(e.to_string()))?
+        }
+        #[cfg(not(feature = "std"))]
+        SerializationFormats::MGPK => {
+            return Err(Error::DeserializeError(
+                "MessagePack requires std feature".into(),
+            ))
+        }
+    };
+    Ok(labels.0)
+}
+
+struct FieldLabels(Vec<String>);
+
+impl<'de> Deserialize<'de> for FieldLabels {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LabelsVisitor;
+
+        impl<'de> Visitor<'de> for LabelsVisitor {
+            type Value = FieldLabels;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("map of event fields")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut labels = vec![];
+                while let Some(label) = map.next_key::<String>()? {
+                    map.next_value::<IgnoredAny>()?;
+                    labels.push(label);
+                }
+                Ok(FieldLabels(labels))
+            }
+        }
+
+        deserializer.deserialize_map(LabelsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_message::test_fixture::TestIdentityFixture;
+
+    #[test]
+    fn test_check_canonical() -> Result<(), Error> {
+        let alice = TestIdentityFixture::new(b"alice")?;
+        let icp = alice.events()[0].event_message.clone();
+        let raw = icp.serialize()?;
+        check_canonical(&icp, &raw)?;
+
+        // Size field doesn't match the length.
+        let padded = [raw.clone(), b" ".to_vec()].concat();
+        assert!(matches!(
+            check_canonical(&icp, &padded),
+            Err(Error::NonCanonical(_))
+        ));
+
+        // Same fields of the same length, in alphabetical order.
+        let value: serde_json::Value = serde_json::from_slice(&raw)?;
+        let reordered = serde_json::to_vec(&value)?;
+        assert_eq!(reordered.len(), raw.len());
+        assert_ne!(reordered, raw);
+        match check_canonical(&icp, &reordered) {
+            Err(Error::NonCanonical(reason)) => assert!(reason.starts_with("fields in order")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Size and field order are fine, but there is extra whitespace.
+        let mut longer = icp.clone();
+        longer.serialization_info.size += 1;
+        let spaced = [longer.serialize()?, b" ".to_vec()].concat();
+        assert_eq!(spaced.len(), longer.serialization_info.size);
+        match check_canonical(&longer, &spaced) {
+            Err(Error::NonCanonical(reason)) => {
+                assert!(reason.starts_with("re-serialization differs"))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        Ok(())
+    }
+}
@@ -6,10 +6,10 @@ use crate::{
         SerializationFormats,
     },
 };
+use alloc::{string::String, vec::Vec};
 
 use super::{serialization_info::SerializationInfo, EventTypeTag, Typeable};
 use serde::Serialize;
-use serde_hex::{Compact, SerHex};
 
 pub fn dummy_prefix(derivation: &SelfAddressing) -> String {
     "#".repeat(derivation.code_len() + derivation.derivative_b64_len())
@@ -28,7 +28,7 @@ pub(crate) struct DummyInceptionEvent {
     digest: String,
     #[serde(rename = "i")]
     prefix: String,
-    #[serde(rename = "s", with = "SerHex::<Compact>")]
+    #[serde(rename = "s", with = "crate::hex")]
     sn: u8,
     #[serde(flatten)]
     data: EventData,
@@ -95,38 +95,7 @@ pub(crate) struct DummyEventMessage<T: Serialize> {
     pub data: T,
 }
 
-impl<T: Serialize + Typeable + Clone> DummyEventMessage<T> {
-    pub fn dummy_event(
-        event: T,
-        format: SerializationFormats,
-        derivation: &SelfAddressing,
-    ) -> Result<Self, Error> {
-        Ok(Self {
-            serialization_info: SerializationInfo::new(
-                format,
-                Self::get_size(&event, format, derivation)?,
-            ),
-            event_type: event.get_type(),
-            data: event,
-            digest: dummy_prefix(derivation),
-        })
-    }
-
-    fn get_size(
-        event: &T,
-        format: SerializationFormats,
-        derivation: &SelfAddressing,
-    ) -> Result<usize, Error> {
-        Ok(Self {
-            serialization_info: SerializationInfo::new(format, 0),
-            event_type: event.get_type(),
-            data: event.clone(),
-            digest: dummy_prefix(derivation),
-        }
-        .serialize()?
-        .len())
-    }
-
+impl<T: Serialize> DummyEventMessage<T> {
     pub fn serialize(&self) -> Result<Vec<u8>, Error> {
         self.serialization_info.kind.encode(&self)
     }
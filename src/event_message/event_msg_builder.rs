@@ -1,13 +1,20 @@
+#[cfg(feature = "query")]
+use crate::query::{
+    key_state_notice::KeyStateNotice,
+    query_event::{QueryArgs, QueryData, QueryEvent},
+    reply::{ReplyBody, ReplyData, ReplyEvent},
+    Envelope, Route,
+};
 use crate::{
-    derivation::{basic::Basic, self_addressing::SelfAddressing},
+    derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
     error::Error,
-    event::sections::key_config::nxt_commitment,
+    event::sections::key_config::{next_keys_digests, nxt_commitment},
     event::{
         event_data::{
             delegated::DelegatedInceptionEvent, interaction::InteractionEvent,
             rotation::RotationEvent,
         },
-        sections::{threshold::SignatureThreshold, WitnessConfig},
+        sections::{threshold::SignatureThreshold, ConfigTrait, WitnessConfig},
         SerializationFormats,
     },
     event::{
@@ -19,12 +26,55 @@ use crate::{
         Event, EventMessage,
     },
     keys::PublicKey,
-    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
+    prefix::{
+        AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, SelfAddressingPrefix,
+        SelfSigningPrefix,
+    },
+    signer::KeyManager,
+    state::IdentifierState,
 };
 use ed25519_dalek::Keypair;
 use rand::rngs::OsRng;
+#[cfg(feature = "query")]
+use std::collections::BTreeMap;
+
+#[cfg(any(test, feature = "test-utils"))]
+use super::test_fixture::keypair_from_seed;
+use super::{signed_event_message::SignedEventMessage, EventTypeTag, KeyEvent};
+
+/// Source of signatures for `EventMsgBuilder::build_and_sign`.
+pub trait EventSigner {
+    /// Public key which signatures are verified with.
+    fn signing_key(&self) -> BasicPrefix;
+    fn sign_event(&self, data: &[u8]) -> Result<SelfSigningPrefix, Error>;
+}
 
-use super::{EventTypeTag, KeyEvent};
+impl<K: KeyManager + ?Sized> EventSigner for K {
+    fn signing_key(&self) -> BasicPrefix {
+        self.key_type().derive(self.public_key())
+    }
+
+    fn sign_event(&self, data: &[u8]) -> Result<SelfSigningPrefix, Error> {
+        Ok(SelfSigningPrefix::new(
+            self.signature_type(),
+            self.sign(data)?,
+        ))
+    }
+}
+
+impl EventSigner for Keypair {
+    fn signing_key(&self) -> BasicPrefix {
+        Basic::Ed25519.derive(PublicKey::new(self.public.to_bytes().to_vec()))
+    }
+
+    fn sign_event(&self, data: &[u8]) -> Result<SelfSigningPrefix, Error> {
+        use ed25519_dalek::Signer;
+        Ok(SelfSigningPrefix::new(
+            SelfSigning::Ed25519Sha512,
+            self.sign(data).to_bytes().to_vec(),
+        ))
+    }
+}
 
 pub struct EventMsgBuilder {
     event_type: EventTypeTag,
@@ -41,8 +91,10 @@ pub struct EventMsgBuilder {
     witnesses: Vec<BasicPrefix>,
     witness_to_add: Vec<BasicPrefix>,
     witness_to_remove: Vec<BasicPrefix>,
+    inception_configuration: Vec<ConfigTrait>,
     format: SerializationFormats,
     derivation: SelfAddressing,
+    partial_rotation: bool,
 }
 
 impl EventMsgBuilder {
@@ -68,8 +120,37 @@ impl EventMsgBuilder {
             witnesses: vec![],
             witness_to_add: vec![],
             witness_to_remove: vec![],
+            inception_configuration: vec![],
             format: SerializationFormats::JSON,
             derivation: SelfAddressing::Blake3_256,
+            partial_rotation: false,
+        }
+    }
+
+    /// Starts next event of identifier in given `state`, with prefix,
+    /// sn, previous event digest, current keys and thresholds taken from
+    /// the state. Rotation still needs new keys and next keys to be set.
+    pub fn from_state(event_type: EventTypeTag, state: &IdentifierState) -> Self {
+        // Rotation keys have to satisfy threshold committed to in the
+        // last establishment event.
+        let key_threshold = match event_type {
+            EventTypeTag::Rot | EventTypeTag::Drt => state
+                .current
+                .next_threshold
+                .clone()
+                .unwrap_or_else(|| state.current.threshold.clone()),
+            _ => state.current.threshold.clone(),
+        };
+        EventMsgBuilder {
+            prefix: state.prefix.clone(),
+            sn: state.sn + 1,
+            prev_event: state.last_event_digest.clone(),
+            keys: state.current.public_keys.clone(),
+            next_key_threshold: key_threshold.clone(),
+            key_threshold,
+            witness_threshold: state.tally,
+            derivation: state.last_event_digest.derivation.clone(),
+            ..EventMsgBuilder::new(event_type)
         }
     }
 
@@ -84,13 +165,34 @@ impl EventMsgBuilder {
         EventMsgBuilder { keys, ..self }
     }
 
+    /// Sets next keys. Empty next keys make event commit to no next
+    /// keys, so rotation with them abandons identifier.
     pub fn with_next_keys(self, next_keys: Vec<BasicPrefix>) -> Self {
         EventMsgBuilder { next_keys, ..self }
     }
 
+    /// Sets current and next keys to the first two key pairs derived
+    /// from `seed`, instead of random ones, so built event is the same
+    /// on every run. Keys derived this way are predictable, so it's
+    /// only available in tests.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn with_keypair_seed(self, seed: &[u8]) -> Self {
+        let key = |index| Basic::Ed25519.derive(keypair_from_seed(seed, index).0);
+        EventMsgBuilder {
+            keys: vec![key(0)],
+            next_keys: vec![key(1)],
+            ..self
+        }
+    }
+
     pub fn with_sn(self, sn: u64) -> Self {
         EventMsgBuilder { sn, ..self }
     }
+
+    /// Sets derivation of the event digest and of next keys commitment.
+    pub fn with_derivation(self, derivation: SelfAddressing) -> Self {
+        EventMsgBuilder { derivation, ..self }
+    }
     pub fn with_previous_event(self, prev_event: &SelfAddressingPrefix) -> Self {
         EventMsgBuilder {
             prev_event: prev_event.clone(),
@@ -124,13 +226,57 @@ impl EventMsgBuilder {
         }
     }
 
-    pub fn with_witness_list(self, witnesses: &[BasicPrefix]) -> Self {
+    /// Sets weighted current keys threshold. Every clause is a list of
+    /// (numerator, denominator) weights of consecutive keys, eg.
+    /// `vec![vec![(1, 2), (1, 2), (1, 2)], vec![(1, 1), (1, 1)]]`.
+    pub fn with_weighted_threshold(self, fracs: Vec<Vec<(u64, u64)>>) -> Self {
+        EventMsgBuilder {
+            key_threshold: SignatureThreshold::weighted(fracs),
+            ..self
+        }
+    }
+
+    /// Sets weighted next keys threshold, in the same form as
+    /// `with_weighted_threshold`.
+    pub fn with_next_weighted_threshold(self, fracs: Vec<Vec<(u64, u64)>>) -> Self {
+        EventMsgBuilder {
+            next_key_threshold: SignatureThreshold::weighted(fracs),
+            ..self
+        }
+    }
+
+    /// Commits to every next key separately, so next rotation can
+    /// expose only part of them.
+    pub fn with_partial_rotation(self, partial_rotation: bool) -> Self {
+        EventMsgBuilder {
+            partial_rotation,
+            ..self
+        }
+    }
+
+    /// Sets initial witnesses of inception or delegated inception
+    /// event (`b` field).
+    pub fn with_witnesses(self, witnesses: &[BasicPrefix]) -> Self {
         EventMsgBuilder {
             witnesses: witnesses.to_vec(),
             ..self
         }
     }
 
+    pub fn with_format(self, format: SerializationFormats) -> Self {
+        EventMsgBuilder { format, ..self }
+    }
+
+    /// Sets witness threshold (`bt` field) of establishment event.
+    pub fn with_witness_threshold(self, witness_threshold: u64) -> Self {
+        EventMsgBuilder {
+            witness_threshold,
+            ..self
+        }
+    }
+
+    /// Sets witnesses added by rotation or delegated rotation event
+    /// (`ba` field).
     pub fn with_witness_to_add(self, witness_to_add: &[BasicPrefix]) -> Self {
         EventMsgBuilder {
             witness_to_add: witness_to_add.to_vec(),
@@ -138,6 +284,8 @@ impl EventMsgBuilder {
         }
     }
 
+    /// Sets witnesses removed by rotation or delegated rotation event
+    /// (`br` field).
     pub fn with_witness_to_remove(self, witness_to_remove: &[BasicPrefix]) -> Self {
         EventMsgBuilder {
             witness_to_remove: witness_to_remove.to_vec(),
@@ -145,10 +293,67 @@ impl EventMsgBuilder {
         }
     }
 
+    /// Sets config traits of inception or delegated inception event
+    /// (`c` field), eg. `ConfigTrait::EstablishmentOnly`.
+    pub fn with_inception_configuration(self, inception_configuration: Vec<ConfigTrait>) -> Self {
+        EventMsgBuilder {
+            inception_configuration,
+            ..self
+        }
+    }
+
+    /// Builds event and signs it with `signer`. Signature is indexed by
+    /// position of signer's key in current keys of the event. Interaction
+    /// events don't carry keys, so current keys of identifier can be set
+    /// with `with_keys`; if signer's key isn't among them, it's assumed to
+    /// be the only one.
+    pub fn build_and_sign<S: EventSigner + ?Sized>(
+        self,
+        signer: &S,
+    ) -> Result<SignedEventMessage, Error> {
+        let key = signer.signing_key();
+        let index = match self.keys.iter().position(|k| k == &key) {
+            Some(index) => index,
+            None if self.event_type == EventTypeTag::Ixn => 0,
+            None => {
+                return Err(Error::SemanticError(
+                    "Signing key is not one of event keys".into(),
+                ))
+            }
+        };
+        let event = self.build()?;
+        let signature = AttachedSignaturePrefix {
+            index: index as u16,
+            signature: signer.sign_event(&event.serialize()?)?,
+        };
+        Ok(event.sign(vec![signature], None))
+    }
+
     pub fn build(self) -> Result<EventMessage<KeyEvent>, Error> {
-        let next_key_hash =
-            nxt_commitment(&self.next_key_threshold, &self.next_keys, &self.derivation);
-        let key_config = KeyConfig::new(self.keys, Some(next_key_hash), Some(self.key_threshold));
+        let inception = matches!(self.event_type, EventTypeTag::Icp | EventTypeTag::Dip);
+        if inception && self.witness_threshold > self.witnesses.len() as u64 {
+            return Err(Error::SemanticError(
+                "Witness threshold higher than number of witnesses".into(),
+            ));
+        }
+        // Non-transferable keys can't be rotated and no next keys
+        // abandon identifier, so there is nothing to commit to.
+        let key_config = if !self.keys.iter().all(|k| k.derivation.is_transferable())
+            || self.next_keys.is_empty()
+        {
+            KeyConfig::new(self.keys, None, Some(self.key_threshold))
+        } else if self.partial_rotation {
+            KeyConfig::new_with_next_keys_digests(
+                self.keys,
+                next_keys_digests(&self.next_keys, &self.derivation),
+                self.next_key_threshold,
+                Some(self.key_threshold),
+            )
+        } else {
+            let next_key_hash =
+                nxt_commitment(&self.next_key_threshold, &self.next_keys, &self.derivation);
+            KeyConfig::new(self.keys, Some(next_key_hash), Some(self.key_threshold))
+        };
         let prefix = if self.prefix == IdentifierPrefix::default() {
             if key_config.public_keys.len() == 1 {
                 IdentifierPrefix::Basic(key_config.public_keys[0].clone())
@@ -169,8 +374,8 @@ impl EventMsgBuilder {
                         tally: self.witness_threshold,
                         initial_witnesses: self.witnesses,
                     },
-                    inception_configuration: vec![],
-                    data: vec![],
+                    inception_configuration: self.inception_configuration,
+                    data: self.data,
                 };
 
                 match prefix {
@@ -210,9 +415,12 @@ impl EventMsgBuilder {
             EventTypeTag::Dip => {
                 let icp_data = InceptionEvent {
                     key_config,
-                    witness_config: InceptionWitnessConfig::default(),
-                    inception_configuration: vec![],
-                    data: vec![],
+                    witness_config: InceptionWitnessConfig {
+                        tally: self.witness_threshold,
+                        initial_witnesses: self.witnesses,
+                    },
+                    inception_configuration: self.inception_configuration,
+                    data: self.data,
                 };
                 DelegatedInceptionEvent {
                     inception_data: icp_data,
@@ -224,7 +432,11 @@ impl EventMsgBuilder {
                 let rotation_data = RotationEvent {
                     previous_event_hash: self.prev_event,
                     key_config,
-                    witness_config: WitnessConfig::default(),
+                    witness_config: WitnessConfig {
+                        tally: self.witness_threshold,
+                        prune: self.witness_to_remove,
+                        graft: self.witness_to_add,
+                    },
                     data: self.data,
                 };
                 Event::new(prefix, self.sn, EventData::Drt(rotation_data))
@@ -281,6 +493,169 @@ impl ReceiptBuilder {
     }
 }
 
+/// Query Builder
+///
+/// Builds `qry` message about identifier, by default asking for its
+/// KEL (`log` route).
+#[cfg(feature = "query")]
+pub struct QueryBuilder {
+    route: Route,
+    reply_route: String,
+    prefix: IdentifierPrefix,
+    sn: Option<u64>,
+    topics: Option<BTreeMap<String, u64>>,
+    format: SerializationFormats,
+    derivation: SelfAddressing,
+}
+
+#[cfg(feature = "query")]
+impl Default for QueryBuilder {
+    fn default() -> Self {
+        Self {
+            route: Route::Log,
+            reply_route: "route".into(),
+            prefix: IdentifierPrefix::default(),
+            sn: None,
+            topics: None,
+            format: SerializationFormats::JSON,
+            derivation: SelfAddressing::Blake3_256,
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl QueryBuilder {
+    pub fn with_route(self, route: Route) -> Self {
+        Self { route, ..self }
+    }
+
+    pub fn with_reply_route(self, reply_route: &str) -> Self {
+        Self {
+            reply_route: reply_route.into(),
+            ..self
+        }
+    }
+
+    pub fn with_prefix(self, prefix: &IdentifierPrefix) -> Self {
+        Self {
+            prefix: prefix.clone(),
+            ..self
+        }
+    }
+
+    /// Sets sn of the first event to return, for `log` query.
+    pub fn with_sn(self, sn: u64) -> Self {
+        Self {
+            sn: Some(sn),
+            ..self
+        }
+    }
+
+    /// Sets mailbox topics with index of the first message to return,
+    /// for `mbx` query.
+    pub fn with_topics(self, topics: BTreeMap<String, u64>) -> Self {
+        Self {
+            topics: Some(topics),
+            ..self
+        }
+    }
+
+    pub fn with_format(self, format: SerializationFormats) -> Self {
+        Self { format, ..self }
+    }
+
+    pub fn with_derivation(self, derivation: SelfAddressing) -> Self {
+        Self { derivation, ..self }
+    }
+
+    pub fn build(self) -> Result<EventMessage<QueryEvent>, Error> {
+        if self.prefix == IdentifierPrefix::default() {
+            return Err(Error::SemanticError("Missing queried prefix".into()));
+        }
+        let data = QueryData {
+            reply_route: self.reply_route,
+            data: QueryArgs {
+                i: self.prefix,
+                sn: self.sn,
+                topics: self.topics,
+            },
+        };
+        Envelope::new(self.route, data).to_message(self.format, &self.derivation)
+    }
+}
+
+/// Reply Builder
+///
+/// Builds `rpy` message with key state notice, location scheme or
+/// endpoint role. Route of key state notice has to be set explicitly,
+/// as it contains prefix of the replier.
+#[cfg(feature = "query")]
+pub struct ReplyBuilder {
+    route: Option<Route>,
+    body: Option<ReplyBody>,
+    format: SerializationFormats,
+    derivation: SelfAddressing,
+}
+
+#[cfg(feature = "query")]
+impl Default for ReplyBuilder {
+    fn default() -> Self {
+        Self {
+            route: None,
+            body: None,
+            format: SerializationFormats::JSON,
+            derivation: SelfAddressing::Blake3_256,
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl ReplyBuilder {
+    pub fn with_route(self, route: Route) -> Self {
+        Self {
+            route: Some(route),
+            ..self
+        }
+    }
+
+    pub fn with_body(self, body: ReplyBody) -> Self {
+        Self {
+            body: Some(body),
+            ..self
+        }
+    }
+
+    /// Sets key state notice of given state as reply body.
+    pub fn with_key_state(self, state: IdentifierState) -> Self {
+        let ksn = KeyStateNotice::new_ksn(state, self.format);
+        self.with_body(ReplyBody::Ksn(Box::new(ksn)))
+    }
+
+    pub fn with_format(self, format: SerializationFormats) -> Self {
+        Self { format, ..self }
+    }
+
+    pub fn with_derivation(self, derivation: SelfAddressing) -> Self {
+        Self { derivation, ..self }
+    }
+
+    pub fn build(self) -> Result<EventMessage<ReplyEvent>, Error> {
+        let body = self
+            .body
+            .ok_or_else(|| Error::SemanticError("Missing reply body".into()))?;
+        let route = match (self.route, &body) {
+            (Some(route), _) => route,
+            (None, ReplyBody::LocScheme(_)) => Route::LocScheme,
+            (None, ReplyBody::EndRole(_)) => Route::EndRoleAdd,
+            (None, ReplyBody::Alias(_)) => Route::Alias,
+            (None, ReplyBody::Ksn(_)) => {
+                return Err(Error::SemanticError("Missing reply route".into()))
+            }
+        };
+        Envelope::new(route, ReplyData { data: body }).to_message(self.format, &self.derivation)
+    }
+}
+
 #[test]
 fn test_multisig_prefix_derivation() {
     // Keys taken from keripy: keripy/tests/core/test_eventing.py::test_multisig_digprefix (line 2255)
@@ -317,3 +692,221 @@ fn test_multisig_prefix_derivation() {
 
     assert_eq!(expected_event.to_vec(), msg.serialize().unwrap());
 }
+
+#[test]
+fn test_weighted_threshold_derivation() {
+    // Data taken from keripy: keripy/tests/core/test_weighted_threshold.py::test_weighted
+    let expected_event = br#"{"v":"KERI10JSON00018e_","t":"icp","d":"EZgXYINAQWXFpxAmWI9AwOwjVOYXzjyEE_-DdTfkEk8s","i":"EZgXYINAQWXFpxAmWI9AwOwjVOYXzjyEE_-DdTfkEk8s","s":"0","kt":["1/2","1/2","1/2"],"k":["DK4OJI8JOr6oEEUMeSF_X-SbKysfwpKwW-ho5KARvH5c","D1RZLgYke0GmfZm-CH8AsW4HoTU4m-2mFgu8kbwp8jQU","DBVwzum-jPfuUXUcHEWdplB4YcoL3BWGXK0TMoF_NeFU"],"n":"EhJGhyJQTpSlZ9oWfQT-lHNl1woMazLC42O89fRHocTI","bt":"0","b":[],"c":[],"a":[]}"#;
+    let keys: Vec<BasicPrefix> = vec![
+        "DK4OJI8JOr6oEEUMeSF_X-SbKysfwpKwW-ho5KARvH5c"
+            .parse()
+            .unwrap(),
+        "D1RZLgYke0GmfZm-CH8AsW4HoTU4m-2mFgu8kbwp8jQU"
+            .parse()
+            .unwrap(),
+        "DBVwzum-jPfuUXUcHEWdplB4YcoL3BWGXK0TMoF_NeFU"
+            .parse()
+            .unwrap(),
+    ];
+    let next_keys: Vec<BasicPrefix> = vec![
+        "DeonYM2bKnAwp6VZcuCXdX72kNFw56czlZ_Tc7XHHVGI"
+            .parse()
+            .unwrap(),
+        "DQghKIy-2do9OkweSgazh3Ql1vCOt5bnc5QF8x50tRoU"
+            .parse()
+            .unwrap(),
+        "DNAUn-5dxm6b8Njo01O0jlStMRCjo9FYQA2mfqFW1_JA"
+            .parse()
+            .unwrap(),
+    ];
+
+    let msg = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(keys)
+        .with_next_keys(next_keys)
+        .with_weighted_threshold(vec![vec![(1, 2), (1, 2), (1, 2)]])
+        .with_next_weighted_threshold(vec![vec![(1, 2), (1, 2), (1, 2)]])
+        .build()
+        .unwrap();
+
+    assert_eq!(expected_event.to_vec(), msg.serialize().unwrap());
+}
+
+#[test]
+fn test_witness_configuration() -> Result<(), Error> {
+    use crate::prefix::Prefix;
+
+    let witnesses: Vec<BasicPrefix> = vec![
+        "BSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?,
+        "BVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI".parse()?,
+    ];
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_witnesses(&witnesses)
+        .with_witness_threshold(2)
+        .build()?;
+    let icp_json = String::from_utf8(icp.serialize()?).unwrap();
+    assert!(icp_json.contains(&format!(
+        r#""bt":"2","b":["{}","{}"]"#,
+        witnesses[0].to_str(),
+        witnesses[1].to_str()
+    )));
+
+    let rot = EventMsgBuilder::new(EventTypeTag::Rot)
+        .with_prefix(&icp.event.get_prefix())
+        .with_witness_threshold(1)
+        .with_witness_to_remove(&witnesses[..1])
+        .build()?;
+    let rot_json = String::from_utf8(rot.serialize()?).unwrap();
+    assert!(rot_json.contains(&format!(
+        r#""bt":"1","br":["{}"],"ba":[]"#,
+        witnesses[0].to_str()
+    )));
+
+    let dip = EventMsgBuilder::new(EventTypeTag::Dip)
+        .with_delegator(&icp.event.get_prefix())
+        .with_witnesses(&witnesses[1..])
+        .with_witness_threshold(1)
+        .build()?;
+    let dip_json = String::from_utf8(dip.serialize()?).unwrap();
+    assert!(dip_json.contains(&format!(r#""bt":"1","b":["{}"]"#, witnesses[1].to_str())));
+
+    // Threshold can't be satisfied by initial witnesses.
+    assert!(EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_witnesses(&witnesses)
+        .with_witness_threshold(3)
+        .build()
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_inception_configuration_builder() -> Result<(), Error> {
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_inception_configuration(vec![
+            ConfigTrait::EstablishmentOnly,
+            ConfigTrait::DoNotDelegate,
+        ])
+        .build()?;
+    let icp_json = String::from_utf8(icp.serialize()?).unwrap();
+    assert!(icp_json.contains(r#""c":["EO","DND"]"#));
+
+    Ok(())
+}
+
+#[cfg(feature = "query")]
+#[test]
+fn test_query_reply_builders() -> Result<(), Error> {
+    use crate::{
+        event_message::Digestible,
+        event_parsing::{message::query_message, EventType},
+        prefix::Prefix,
+    };
+
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp).build()?;
+    let id = icp.event.get_prefix();
+
+    let qry = QueryBuilder::default()
+        .with_route(Route::Ksn)
+        .with_prefix(&id)
+        .build()?;
+    assert_eq!(qry.event.get_route(), Route::Ksn);
+    assert_eq!(qry.event.get_query_data().data.i, id);
+    // Version string and SAID survive round trip.
+    let serialized = qry.serialize()?;
+    match query_message(&serialized).unwrap().1 {
+        EventType::Qry(parsed) => assert_eq!(parsed, qry),
+        _ => panic!("Expected qry"),
+    };
+    assert!(QueryBuilder::default().build().is_err());
+
+    let witness: BasicPrefix = "BSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+    let state = IdentifierState::default().apply(&icp)?;
+    let rpy = ReplyBuilder::default()
+        .with_route(Route::ReplyKsn(IdentifierPrefix::Basic(witness)))
+        .with_key_state(state.clone())
+        .build()?;
+    rpy.check_digest()?;
+    assert_eq!(rpy.event.get_state(), Some(state.clone()));
+    assert!(!rpy.event.get_digest().to_str().is_empty());
+    // Route of key state notice can't be guessed.
+    assert!(ReplyBuilder::default()
+        .with_key_state(state)
+        .build()
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_build_and_sign() -> Result<(), Error> {
+    use crate::{
+        database::memory::MemoryDatabase, event_message::signed_event_message::Message,
+        processor::EventProcessor, signer::CryptoBox,
+    };
+    use std::sync::Arc;
+
+    let processor = EventProcessor::new(Arc::new(MemoryDatabase::new()));
+    let km = CryptoBox::new()?;
+    let other = Keypair::generate(&mut OsRng);
+    let keys = vec![other.signing_key(), km.signing_key()];
+
+    // Signature of the second key gets index 1.
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(keys.clone())
+        .build_and_sign(&km)?;
+    assert_eq!(icp.signatures[0].index, 1);
+    let id = icp.event_message.event.get_prefix();
+    processor.process(Message::Event(icp.clone()))?;
+
+    // Any signer can be used through trait object.
+    let signer: &dyn EventSigner = &other;
+    let ixn = EventMsgBuilder::new(EventTypeTag::Ixn)
+        .with_prefix(&id)
+        .with_keys(keys)
+        .with_previous_event(&icp.event_message.get_digest())
+        .build_and_sign(signer)?;
+    assert_eq!(ixn.signatures[0].index, 0);
+    let state = processor.process(Message::Event(ixn))?.unwrap();
+    assert_eq!(state.sn, 1);
+
+    // Signer has to hold one of establishment event keys.
+    assert!(EventMsgBuilder::new(EventTypeTag::Icp)
+        .build_and_sign(&other)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_builder_from_state() -> Result<(), Error> {
+    use crate::{
+        database::memory::MemoryDatabase, event_message::signed_event_message::Message,
+        processor::EventProcessor, signer::CryptoBox,
+    };
+    use std::sync::Arc;
+
+    let processor = EventProcessor::new(Arc::new(MemoryDatabase::new()));
+    let mut km = CryptoBox::new()?;
+    let next_key = |km: &CryptoBox| km.key_type().derive(km.next_public_key());
+
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![km.signing_key()])
+        .with_next_keys(vec![next_key(&km)])
+        .build_and_sign(&km)?;
+    let state = processor.process(Message::Event(icp))?.unwrap();
+
+    let ixn = EventMsgBuilder::from_state(EventTypeTag::Ixn, &state).build_and_sign(&km)?;
+    let state = processor.process(Message::Event(ixn))?.unwrap();
+    assert_eq!(state.sn, 1);
+
+    km.rotate()?;
+    let rot = EventMsgBuilder::from_state(EventTypeTag::Rot, &state)
+        .with_keys(vec![km.signing_key()])
+        .with_next_keys(vec![next_key(&km)])
+        .build_and_sign(&km)?;
+    let state = processor.process(Message::Event(rot))?.unwrap();
+    assert_eq!(state.sn, 2);
+    assert_eq!(state.current.public_keys, vec![km.signing_key()]);
+
+    Ok(())
+}
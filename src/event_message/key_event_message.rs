@@ -1,9 +1,11 @@
 use crate::{
+    derivation::self_addressing::SelfAddressing,
     error::Error,
     event::{event_data::EventData, sections::seal::SourceSeal, Event},
     prefix::{AttachedSignaturePrefix, IdentifierPrefix, SelfAddressingPrefix},
     state::{EventSemantics, IdentifierState},
 };
+use alloc::vec::Vec;
 
 use super::{
     dummy_event::{dummy_prefix, DummyEventMessage, DummyInceptionEvent},
@@ -60,6 +62,18 @@ impl EventMessage<KeyEvent> {
         }
     }
 
+    /// Digest of the event computed with `derivation`. It's the event's
+    /// own digest if derivation is the same, so the result always
+    /// passes `check_digest`.
+    pub fn digest_with(&self, derivation: &SelfAddressing) -> Result<SelfAddressingPrefix, Error> {
+        let self_dig = self.event.get_digest();
+        if &self_dig.derivation == derivation {
+            Ok(self_dig)
+        } else {
+            Ok(derivation.derive(&self.to_derivation_data()?))
+        }
+    }
+
     fn to_derivation_data(&self) -> Result<Vec<u8>, Error> {
         Ok(match self.event.get_event_data() {
             EventData::Icp(icp) => DummyInceptionEvent::dummy_inception_data(
@@ -98,9 +112,7 @@ impl EventSemantics for EventMessage<KeyEvent> {
                         ..state
                     })
                 } else {
-                    Err(Error::SemanticError(
-                        "Invalid Identifier Prefix Binding".into(),
-                    ))
+                    Err(Error::InvalidPrefixBinding(self.event.get_prefix()))
                 }
             }
             EventData::Rot(ref rot) => {
@@ -121,9 +133,10 @@ impl EventSemantics for EventMessage<KeyEvent> {
                                 ..next_state
                             })
                         } else {
-                            Err(Error::SemanticError(
-                                "Last event does not match previous event".into(),
-                            ))
+                            Err(Error::DigestMismatch {
+                                expected: state.last_event_digest,
+                                got: rot.previous_event_hash.clone(),
+                            })
                         }
                     })
                 }
@@ -140,9 +153,10 @@ impl EventSemantics for EventMessage<KeyEvent> {
                         ..next_state
                     })
                 } else {
-                    Err(Error::SemanticError(
-                        "Last event does not match previous event".into(),
-                    ))
+                    Err(Error::DigestMismatch {
+                        expected: state.last_event_digest,
+                        got: drt.previous_event_hash.clone(),
+                    })
                 }
             }),
             EventData::Ixn(ref inter) => {
@@ -154,9 +168,10 @@ impl EventSemantics for EventMessage<KeyEvent> {
                             ..next_state
                         })
                     } else {
-                        Err(Error::SemanticError(
-                            "Last event does not match previous event".to_string(),
-                        ))
+                        Err(Error::DigestMismatch {
+                            expected: state.last_event_digest,
+                            got: inter.previous_event_hash.clone(),
+                        })
                     }
                 })
             }
@@ -168,8 +183,11 @@ pub fn verify_identifier_binding(icp_event: &EventMessage<KeyEvent>) -> Result<b
     let event_data = &icp_event.event.get_event_data();
     match event_data {
         EventData::Icp(icp) => match &icp_event.event.get_prefix() {
+            // Non-transferable identifier can't commit to next keys.
             IdentifierPrefix::Basic(bp) => Ok(icp.key_config.public_keys.len() == 1
-                && bp == icp.key_config.public_keys.first().unwrap()),
+                && bp == icp.key_config.public_keys.first().unwrap()
+                && (bp.derivation.is_transferable()
+                    || icp.key_config.threshold_key_digest.is_none())),
             IdentifierPrefix::SelfAddressing(sap) => {
                 Ok(icp_event.check_digest(sap)? && icp_event.get_digest().eq(sap))
             }
@@ -1,21 +1,28 @@
+pub mod canonical;
 pub mod dummy_event;
+#[cfg(feature = "std")]
 pub mod event_msg_builder;
 pub mod key_event_message;
 pub mod serialization_info;
 pub mod serializer;
 pub mod signature;
 pub mod signed_event_message;
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub mod test_fixture;
 
-use std::cmp::Ordering;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cmp::Ordering;
 
 use crate::{
     derivation::self_addressing::SelfAddressing, error::Error, prefix::SelfAddressingPrefix,
 };
+#[cfg(feature = "std")]
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize, Serializer};
 use serialization_info::*;
 
-use self::{dummy_event::DummyEventMessage, key_event_message::KeyEvent};
+use self::key_event_message::KeyEvent;
 
 pub trait Typeable {
     fn get_type(&self) -> EventTypeTag;
@@ -23,7 +30,7 @@ pub trait Typeable {
 pub trait Digestible {
     fn get_digest(&self) -> SelfAddressingPrefix;
 }
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum EventTypeTag {
     Icp,
@@ -32,11 +39,56 @@ pub enum EventTypeTag {
     Dip,
     Drt,
     Rct,
+    Vcp,
+    Vrt,
+    Iss,
+    Rev,
+    Bis,
+    Brv,
     #[cfg(feature = "query")]
     Rpy,
     #[cfg(feature = "query")]
     Qry,
+    #[cfg(feature = "query")]
+    Exn,
 }
+
+impl EventTypeTag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventTypeTag::Icp => "icp",
+            EventTypeTag::Rot => "rot",
+            EventTypeTag::Ixn => "ixn",
+            EventTypeTag::Dip => "dip",
+            EventTypeTag::Drt => "drt",
+            EventTypeTag::Rct => "rct",
+            EventTypeTag::Vcp => "vcp",
+            EventTypeTag::Vrt => "vrt",
+            EventTypeTag::Iss => "iss",
+            EventTypeTag::Rev => "rev",
+            EventTypeTag::Bis => "bis",
+            EventTypeTag::Brv => "brv",
+            #[cfg(feature = "query")]
+            EventTypeTag::Rpy => "rpy",
+            #[cfg(feature = "query")]
+            EventTypeTag::Qry => "qry",
+            #[cfg(feature = "query")]
+            EventTypeTag::Exn => "exn",
+        }
+    }
+}
+
+impl Serialize for EventTypeTag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Serialized as plain string in every format. MessagePack encoder
+        // would encode unit variant as single entry map otherwise.
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SaidEvent<D> {
     #[serde(rename = "d", skip_serializing)]
@@ -55,11 +107,16 @@ impl<D: Serialize + Clone + Typeable> SaidEvent<D> {
         format: SerializationFormats,
         derivation: &SelfAddressing,
     ) -> Result<EventMessage<SaidEvent<D>>, Error> {
-        let dummy_event = DummyEventMessage::dummy_event(event.clone(), format, derivation)?;
-        let digest = derivation.derive(&dummy_event.serialize()?);
+        let (serialization_info, digest) = crate::sad::compute_versioned_said(
+            |size| SerializationInfo::new(format, size),
+            Some(event.get_type()),
+            &event,
+            derivation,
+            format,
+        )?;
 
         Ok(EventMessage {
-            serialization_info: dummy_event.serialization_info,
+            serialization_info,
             event: Self {
                 digest,
                 content: event,
@@ -132,12 +189,14 @@ impl<D: Digestible + Typeable + Serialize + Clone> Serialize for EventMessage<D>
     }
 }
 
+#[cfg(feature = "std")]
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct TimestampedEventMessage {
     pub timestamp: DateTime<Local>,
     pub event_message: EventMessage<KeyEvent>,
 }
 
+#[cfg(feature = "std")]
 impl TimestampedEventMessage {
     pub fn new(event: EventMessage<KeyEvent>) -> Self {
         Self {
@@ -147,6 +206,7 @@ impl TimestampedEventMessage {
     }
 }
 
+#[cfg(feature = "std")]
 impl PartialOrd for TimestampedEventMessage {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(
@@ -163,6 +223,7 @@ impl PartialOrd for TimestampedEventMessage {
     }
 }
 
+#[cfg(feature = "std")]
 impl Ord for TimestampedEventMessage {
     fn cmp(&self, other: &Self) -> Ordering {
         match self.event_message.event.get_sn() == other.event_message.event.get_sn() {
@@ -175,14 +236,17 @@ impl Ord for TimestampedEventMessage {
     }
 }
 
+#[cfg(feature = "std")]
 impl Eq for TimestampedEventMessage {}
 
+#[cfg(feature = "std")]
 impl From<TimestampedEventMessage> for EventMessage<KeyEvent> {
     fn from(event: TimestampedEventMessage) -> EventMessage<KeyEvent> {
         event.event_message
     }
 }
 
+#[cfg(feature = "std")]
 /// WARNING: timestamp will change on conversion to current time
 impl From<EventMessage<KeyEvent>> for TimestampedEventMessage {
     fn from(event: EventMessage<KeyEvent>) -> TimestampedEventMessage {
@@ -287,7 +351,7 @@ mod tests {
         assert_eq!(s0.current.public_keys.len(), 1);
         assert_eq!(s0.current.public_keys[0], pref0);
         assert_eq!(s0.current.threshold, SignatureThreshold::Simple(1));
-        assert_eq!(s0.current.threshold_key_digest, Some(nxt));
+        assert_eq!(s0.current.threshold_key_digest, Some(nxt.into()));
         assert_eq!(s0.witnesses, vec![]);
         assert_eq!(s0.tally, 0);
 
@@ -370,7 +434,7 @@ mod tests {
         assert_eq!(s0.current.public_keys[0], sig_pref_0);
         assert_eq!(s0.current.public_keys[1], enc_pref_0);
         assert_eq!(s0.current.threshold, SignatureThreshold::default());
-        assert_eq!(s0.current.threshold_key_digest, Some(nexter_pref));
+        assert_eq!(s0.current.threshold_key_digest, Some(nexter_pref.into()));
         assert_eq!(s0.witnesses, vec![]);
         assert_eq!(s0.tally, 0);
 
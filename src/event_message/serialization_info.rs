@@ -1,5 +1,10 @@
 use crate::error::Error;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::str::FromStr;
+#[cfg(feature = "std")]
 use rmp_serde as serde_mgpk;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
@@ -15,7 +20,12 @@ impl SerializationFormats {
         match self {
             Self::JSON => serde_json::to_vec(message).map_err(|e| e.into()),
             Self::CBOR => serde_cbor::to_vec(message).map_err(|e| e.into()),
-            Self::MGPK => serde_mgpk::to_vec(message).map_err(|e| e.into()),
+            #[cfg(feature = "std")]
+            Self::MGPK => serde_mgpk::to_vec_named(message).map_err(|e| e.into()),
+            #[cfg(not(feature = "std"))]
+            Self::MGPK => Err(Error::SerializationError(
+                "MessagePack requires std feature".into(),
+            )),
         }
     }
 
@@ -41,6 +51,13 @@ impl FromStr for SerializationFormats {
     }
 }
 
+/// Size of version string, eg. `KERI10JSON00014b_`.
+pub const VERSION_SIZE: usize = 17;
+
+/// Protocol versions (major, minor) of messages which can be parsed and
+/// processed. Messages of other versions are rejected.
+pub const SUPPORTED_VERSIONS: &[(u8, u8)] = &[(1, 0)];
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct SerializationInfo {
     pub major_version: u8,
@@ -67,17 +84,38 @@ impl SerializationInfo {
             self.size
         )
     }
+
+    /// Checks if protocol version of message is supported.
+    pub fn check_version(&self) -> Result<(), Error> {
+        if SUPPORTED_VERSIONS.contains(&(self.major_version, self.minor_version)) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedVersion {
+                major: self.major_version,
+                minor: self.minor_version,
+            })
+        }
+    }
 }
 
 impl FromStr for SerializationInfo {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let is_hex = |part: &str| part.chars().all(|c| c.is_ascii_hexdigit());
+        if s.len() != VERSION_SIZE
+            || !s.is_ascii()
+            || !s.ends_with('_')
+            || !is_hex(&s[4..6])
+            || !is_hex(&s[10..16])
+        {
+            return Err(Error::DeserializeError("Metadata parsing error".into()));
+        }
         match &s[..4] {
             "KERI" => Ok(Self {
                 major_version: u8::from_str_radix(&s[4..5], 16)?,
                 minor_version: u8::from_str_radix(&s[5..6], 16)?,
                 kind: SerializationFormats::from_str(&s[6..10])?,
-                size: u16::from_str_radix(&s[10..16], 16)? as usize,
+                size: usize::from_str_radix(&s[10..16], 16)?,
             }),
             _ => Err(Error::DeserializeError("Metadata parsing error".into())),
         }
@@ -136,3 +174,37 @@ fn basic_deserialize() -> Result<(), Error> {
     assert_eq!(si.size, 291);
     Ok(())
 }
+
+#[test]
+fn version_string_errors() -> Result<(), Error> {
+    for malformed in [
+        "",
+        "KERI10JSON",
+        "KERI10JSON00014b",
+        "KERI10JSON00014b_0",
+        "ACDC10JSON00014b_",
+        "KERI10XML_00014b_",
+        "KERI10JSON+0014b_",
+        "KERIz0JSON00014b_",
+        "KERI10JSON00014bX",
+        "KERI10JSON0001ł_",
+    ] {
+        assert!(SerializationInfo::from_str(malformed).is_err());
+    }
+
+    // Size takes all six hex digits.
+    let si = SerializationInfo::from_str("KERI10JSONffffff_")?;
+    assert_eq!(si.size, 0xffffff);
+    assert!(si.check_version().is_ok());
+
+    // Well formed version string of future version is parsed, but
+    // rejected as unsupported.
+    let si = SerializationInfo::from_str("KERI20JSON00014b_")?;
+    assert!(matches!(
+        si.check_version(),
+        Err(Error::UnsupportedVersion { major: 2, minor: 0 })
+    ));
+    let si = SerializationInfo::from_str("KERI11JSON00014b_")?;
+    assert!(si.check_version().is_err());
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use crate::error::serializer_error::Error;
+use alloc::string::{String, ToString};
 use serde::{ser, Serialize};
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 pub struct KeriJsonSerializer {
     output: String,
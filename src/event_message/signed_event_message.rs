@@ -1,9 +1,12 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use chrono::{DateTime, Local};
+#[cfg(feature = "std")]
+use core::cmp::Ordering;
 use serde::{ser::SerializeStruct, Deserialize, Serialize};
-use std::cmp::Ordering;
 
 use super::EventMessage;
-use super::{serializer::to_string, KeyEvent};
+use super::KeyEvent;
 use crate::{
     error::Error,
     event::{
@@ -16,7 +19,7 @@ use crate::{
 };
 
 #[cfg(feature = "query")]
-use crate::query::{query::SignedQuery, reply::SignedReply};
+use crate::query::{exchange::SignedExchange, query_event::SignedQuery, reply::SignedReply};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Message {
@@ -25,10 +28,13 @@ pub enum Message {
     // use SignedNontransferableReceipt and SignedTransferableReceipt
     NontransferableRct(SignedNontransferableReceipt),
     TransferableRct(SignedTransferableReceipt),
+    WitnessRct(SignedWitnessReceipt),
     #[cfg(feature = "query")]
     KeyStateNotice(SignedReply),
     #[cfg(feature = "query")]
     Query(SignedQuery),
+    #[cfg(feature = "query")]
+    Exchange(SignedExchange),
 }
 
 // KERI serializer should be used to serialize this
@@ -39,6 +45,9 @@ pub struct SignedEventMessage {
     pub signatures: Vec<AttachedSignaturePrefix>,
     #[serde(skip_serializing)]
     pub delegator_seal: Option<SourceSeal>,
+    // event bytes exactly as received, if event was parsed
+    #[serde(default, skip_serializing)]
+    pub raw: Option<Vec<u8>>,
 }
 
 impl Serialize for SignedEventMessage {
@@ -51,13 +60,15 @@ impl Serialize for SignedEventMessage {
             let mut em = serializer.serialize_struct("EventMessage", 2)?;
             em.serialize_field("", &self.event_message)?;
             let att_sigs = Attachment::AttachedSignatures(self.signatures.clone());
-            em.serialize_field("-", &att_sigs.to_cesr())?;
+            em.serialize_field("-", &att_sigs.to_cesr().map_err(serde::ser::Error::custom)?)?;
             em.end()
         // . else - we pack as it is for DB / CBOR purpose
         } else {
-            let mut em = serializer.serialize_struct("SignedEventMessage", 2)?;
+            let mut em = serializer.serialize_struct("SignedEventMessage", 4)?;
             em.serialize_field("event_message", &self.event_message)?;
             em.serialize_field("signatures", &self.signatures)?;
+            em.serialize_field("delegator_seal", &self.delegator_seal)?;
+            em.serialize_field("raw", &self.raw)?;
             em.end()
         }
     }
@@ -69,45 +80,56 @@ impl PartialEq for SignedEventMessage {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct TimestampedSignedEventMessage {
     pub timestamp: DateTime<Local>,
     pub signed_event_message: SignedEventMessage,
 }
 
+#[cfg(feature = "std")]
 impl TimestampedSignedEventMessage {
     pub fn new(event: SignedEventMessage) -> Self {
+        Self::new_at(event, Local::now())
+    }
+
+    pub fn new_at(event: SignedEventMessage, timestamp: DateTime<Local>) -> Self {
         Self {
-            timestamp: Local::now(),
+            timestamp,
             signed_event_message: event,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<TimestampedSignedEventMessage> for SignedEventMessage {
     fn from(event: TimestampedSignedEventMessage) -> SignedEventMessage {
         event.signed_event_message
     }
 }
 
+#[cfg(feature = "std")]
 impl From<SignedEventMessage> for TimestampedSignedEventMessage {
     fn from(event: SignedEventMessage) -> TimestampedSignedEventMessage {
         TimestampedSignedEventMessage::new(event)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<&SignedEventMessage> for TimestampedSignedEventMessage {
     fn from(event: &SignedEventMessage) -> TimestampedSignedEventMessage {
         TimestampedSignedEventMessage::new(event.clone())
     }
 }
 
+#[cfg(feature = "std")]
 impl PartialEq for TimestampedSignedEventMessage {
     fn eq(&self, other: &Self) -> bool {
         self.signed_event_message == other.signed_event_message
     }
 }
 
+#[cfg(feature = "std")]
 impl PartialOrd for TimestampedSignedEventMessage {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(
@@ -128,6 +150,7 @@ impl PartialOrd for TimestampedSignedEventMessage {
     }
 }
 
+#[cfg(feature = "std")]
 impl Ord for TimestampedSignedEventMessage {
     fn cmp(&self, other: &Self) -> Ordering {
         match self.signed_event_message.event_message.event.get_sn()
@@ -144,6 +167,7 @@ impl Ord for TimestampedSignedEventMessage {
     }
 }
 
+#[cfg(feature = "std")]
 impl Eq for TimestampedSignedEventMessage {}
 
 impl SignedEventMessage {
@@ -156,11 +180,22 @@ impl SignedEventMessage {
             event_message: message.clone(),
             signatures: sigs,
             delegator_seal,
+            raw: None,
+        }
+    }
+
+    /// Returns event bytes signatures were made over: the original
+    /// bytes if event was received, or its serialization otherwise.
+    pub fn raw_event(&self) -> Result<Vec<u8>, Error> {
+        match &self.raw {
+            Some(raw) => Ok(raw.clone()),
+            None => self.event_message.serialize(),
         }
     }
 
     pub fn serialize(&self) -> Result<Vec<u8>, Error> {
-        Ok(to_string(&self)?.as_bytes().to_vec())
+        let att_sigs = Attachment::AttachedSignatures(self.signatures.clone());
+        Ok([self.raw_event()?, att_sigs.to_cesr()?.into_bytes()].concat())
     }
 }
 
@@ -220,3 +255,23 @@ impl SignedNontransferableReceipt {
         }
     }
 }
+
+/// Signed Witness Receipt
+///
+/// A receipt signed by witnesses of receipted event. Signatures are
+/// indexed by position of witness in witness list of the event, so
+/// they can be verified only against its key state.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignedWitnessReceipt {
+    pub body: EventMessage<Receipt>,
+    pub signatures: Vec<AttachedSignaturePrefix>,
+}
+
+impl SignedWitnessReceipt {
+    pub fn new(message: &EventMessage<Receipt>, sigs: Vec<AttachedSignaturePrefix>) -> Self {
+        Self {
+            body: message.clone(),
+            signatures: sigs,
+        }
+    }
+}
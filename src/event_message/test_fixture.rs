@@ -0,0 +1,288 @@
+//! Test Fixture
+//!
+//! Deterministic identities for test suites. All keys are derived from
+//! a seed, so the same seed always gives the same, fully signed KEL.
+//! Keys derived this way are predictable, never use them outside of
+//! tests. Only compiled for tests and with `test-utils` feature.
+
+use ed25519_dalek::SecretKey;
+
+use crate::{
+    derivation::{basic::Basic, self_signing::SelfSigning},
+    error::Error,
+    event::sections::seal::{EventSeal, Seal, SourceSeal},
+    event_parsing::SignedEventData,
+    keys::{PrivateKey, PublicKey},
+    prefix::{AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, SeedPrefix},
+    signer::CryptoBox,
+};
+
+use super::{
+    event_msg_builder::EventMsgBuilder, signed_event_message::SignedEventMessage, EventMessage,
+    EventTypeTag, KeyEvent,
+};
+
+const KEY_DERIVATION_CONTEXT: &str = "keriox test fixture 2022 ed25519 key";
+
+fn secret_from_seed(seed: &[u8], index: u32) -> [u8; 32] {
+    blake3::derive_key(
+        KEY_DERIVATION_CONTEXT,
+        &[seed, &index.to_be_bytes()].concat(),
+    )
+}
+
+/// Derives `index`-th Ed25519 key pair from `seed`.
+pub fn keypair_from_seed(seed: &[u8], index: u32) -> (PublicKey, PrivateKey) {
+    let secret_bytes = secret_from_seed(seed, index);
+    // Every 32 bytes are a valid Ed25519 secret key.
+    let secret = SecretKey::from_bytes(&secret_bytes).unwrap();
+    (
+        PublicKey::new(ed25519_dalek::PublicKey::from(&secret).to_bytes().to_vec()),
+        PrivateKey::new(secret_bytes.to_vec()),
+    )
+}
+
+/// Test Identity Fixture
+///
+/// Single key identifier, which key pair is rotated to the next one
+/// derived from the seed on every rotation. Events of delegated
+/// identifier are anchored by its delegator fixture.
+pub struct TestIdentityFixture {
+    seed: Vec<u8>,
+    key_type: Basic,
+    key_index: u32,
+    prefix: IdentifierPrefix,
+    kel: Vec<SignedEventMessage>,
+}
+
+impl TestIdentityFixture {
+    /// Incepts identifier with keys derived from `seed`.
+    pub fn new(seed: &[u8]) -> Result<Self, Error> {
+        Self::incept_with(seed, |icp| icp)
+    }
+
+    /// Like `new`, with `customize` setting other fields of inception
+    /// event, eg. its configuration or seals.
+    pub fn incept_with(
+        seed: &[u8],
+        customize: impl FnOnce(EventMsgBuilder) -> EventMsgBuilder,
+    ) -> Result<Self, Error> {
+        let icp = customize(EventMsgBuilder::new(EventTypeTag::Icp).with_keypair_seed(seed));
+        Self::incept(seed, Basic::Ed25519, icp, None)
+    }
+
+    /// Incepts non-transferable identifier, which prefix is its only key.
+    pub fn non_transferable(seed: &[u8]) -> Result<Self, Error> {
+        let key = Basic::Ed25519NT.derive(keypair_from_seed(seed, 0).0);
+        let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+            .with_keys(vec![key])
+            .with_next_keys(vec![]);
+        Self::incept(seed, Basic::Ed25519NT, icp, None)
+    }
+
+    /// Incepts identifier delegated by `delegator`, which anchors the
+    /// inception in its KEL.
+    pub fn delegated(seed: &[u8], delegator: &mut TestIdentityFixture) -> Result<Self, Error> {
+        let dip = EventMsgBuilder::new(EventTypeTag::Dip)
+            .with_keypair_seed(seed)
+            .with_delegator(delegator.prefix());
+        Self::incept(seed, Basic::Ed25519, dip, Some(delegator))
+    }
+
+    fn incept(
+        seed: &[u8],
+        key_type: Basic,
+        builder: EventMsgBuilder,
+        delegator: Option<&mut TestIdentityFixture>,
+    ) -> Result<Self, Error> {
+        let icp = builder.build()?;
+        let mut fixture = Self {
+            seed: seed.to_vec(),
+            key_type,
+            key_index: 0,
+            prefix: icp.event.get_prefix(),
+            kel: vec![],
+        };
+        fixture.append(icp, delegator)?;
+        Ok(fixture)
+    }
+
+    pub fn prefix(&self) -> &IdentifierPrefix {
+        &self.prefix
+    }
+
+    /// Signed events of the identifier, in order.
+    pub fn events(&self) -> &[SignedEventMessage] {
+        &self.kel
+    }
+
+    /// KEL of the identifier as CESR stream.
+    pub fn kel(&self) -> Result<Vec<u8>, Error> {
+        self.kel.iter().try_fold(vec![], |mut kel, event| {
+            kel.extend(SignedEventData::from(event).to_cesr()?);
+            Ok(kel)
+        })
+    }
+
+    /// Current public key of the identifier.
+    pub fn public_key(&self) -> BasicPrefix {
+        self.key(self.key_index)
+    }
+
+    /// Signs `data` with current key of the identifier.
+    pub fn sign(&self, data: &[u8]) -> Result<AttachedSignaturePrefix, Error> {
+        let (_, private_key) = keypair_from_seed(&self.seed, self.key_index);
+        Ok(AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            private_key.sign_ed(data)?,
+            0,
+        ))
+    }
+
+    /// Key manager holding current and next keys of the identifier.
+    pub fn key_manager(&self) -> Result<CryptoBox, Error> {
+        let seed =
+            |index| SeedPrefix::RandomSeed256Ed25519(secret_from_seed(&self.seed, index).to_vec());
+        CryptoBox::from_seeds(&seed(self.key_index), &seed(self.key_index + 1))
+    }
+
+    /// Rotates to the pre-committed next key.
+    pub fn rotate(&mut self) -> Result<SignedEventMessage, Error> {
+        self.rotate_with(|rot| rot)
+    }
+
+    /// Like `rotate`, with `customize` setting other fields of rotation
+    /// event.
+    pub fn rotate_with(
+        &mut self,
+        customize: impl FnOnce(EventMsgBuilder) -> EventMsgBuilder,
+    ) -> Result<SignedEventMessage, Error> {
+        self.rotate_anchored(None, customize)
+    }
+
+    /// Rotates to null next keys, which abandons the identifier.
+    pub fn abandon(&mut self) -> Result<SignedEventMessage, Error> {
+        self.rotate_with(|rot| rot.with_next_keys(vec![]))
+    }
+
+    /// Delegated rotation, anchored by `delegator`.
+    pub fn rotate_delegated(
+        &mut self,
+        delegator: &mut TestIdentityFixture,
+        customize: impl FnOnce(EventMsgBuilder) -> EventMsgBuilder,
+    ) -> Result<SignedEventMessage, Error> {
+        self.rotate_anchored(Some(delegator), customize)
+    }
+
+    fn rotate_anchored(
+        &mut self,
+        delegator: Option<&mut TestIdentityFixture>,
+        customize: impl FnOnce(EventMsgBuilder) -> EventMsgBuilder,
+    ) -> Result<SignedEventMessage, Error> {
+        let event_type = match delegator {
+            Some(_) => EventTypeTag::Drt,
+            None => EventTypeTag::Rot,
+        };
+        let rot = customize(
+            self.next_event(event_type)
+                .with_keys(vec![self.key(self.key_index + 1)])
+                .with_next_keys(vec![self.key(self.key_index + 2)]),
+        )
+        .build()?;
+        self.key_index += 1;
+        self.append(rot, delegator)
+    }
+
+    /// Anchors `seals` in interaction event.
+    pub fn interact(&mut self, seals: Vec<Seal>) -> Result<SignedEventMessage, Error> {
+        let ixn = self
+            .next_event(EventTypeTag::Ixn)
+            .with_seal(seals)
+            .build()?;
+        self.append(ixn, None)
+    }
+
+    fn key(&self, index: u32) -> BasicPrefix {
+        self.key_type.derive(keypair_from_seed(&self.seed, index).0)
+    }
+
+    /// Builder of the next event, following digest derivation of the
+    /// last one.
+    fn next_event(&self, event_type: EventTypeTag) -> EventMsgBuilder {
+        let last = &self.kel[self.kel.len() - 1].event_message;
+        EventMsgBuilder::new(event_type)
+            .with_prefix(&self.prefix)
+            .with_sn(last.event.get_sn() + 1)
+            .with_previous_event(&last.get_digest())
+            .with_derivation(last.get_digest().derivation)
+    }
+
+    /// Signs `event` and adds it to the KEL. Event is anchored first by
+    /// `delegator`, if any.
+    fn append(
+        &mut self,
+        event: EventMessage<KeyEvent>,
+        delegator: Option<&mut TestIdentityFixture>,
+    ) -> Result<SignedEventMessage, Error> {
+        let source_seal = match delegator {
+            Some(delegator) => {
+                let ixn = delegator.interact(vec![Seal::Event(EventSeal {
+                    prefix: event.event.get_prefix(),
+                    sn: event.event.get_sn(),
+                    event_digest: event.get_digest(),
+                })])?;
+                Some(SourceSeal::new(
+                    ixn.event_message.event.get_sn(),
+                    ixn.event_message.get_digest(),
+                ))
+            }
+            None => None,
+        };
+        let signed = event.sign(vec![self.sign(&event.serialize()?)?], source_seal);
+        self.kel.push(signed.clone());
+        Ok(signed)
+    }
+}
+
+#[test]
+fn test_identity_fixture() -> Result<(), Error> {
+    use crate::{
+        database::memory::MemoryDatabase, event::sections::seal::DigestSeal,
+        event_message::signed_event_message::Message, event_parsing::message::signed_event_stream,
+        processor::EventProcessor,
+    };
+    use std::{convert::TryFrom, sync::Arc};
+
+    let chain = |seed: &[u8]| -> Result<TestIdentityFixture, Error> {
+        let mut fixture = TestIdentityFixture::new(seed)?;
+        fixture.interact(vec![Seal::Digest(DigestSeal {
+            dig: "EZAoTNZH3ULvaU6Z-i0d8JJR2nmwyYAfSVPzhzS6b5CM".parse()?,
+        })])?;
+        fixture.rotate()?;
+        fixture.rotate()?;
+        fixture.interact(vec![])?;
+        Ok(fixture)
+    };
+
+    let alice = chain(b"alice")?;
+    assert_eq!(alice.kel()?, chain(b"alice")?.kel()?);
+    assert_ne!(alice.prefix(), chain(b"bob")?.prefix());
+    assert_eq!(alice.events().len(), 5);
+
+    // Builder with the same seed incepts the same identifier.
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keypair_seed(b"alice")
+        .build()?;
+    assert_eq!(icp, alice.events()[0].event_message);
+
+    let processor = EventProcessor::new(Arc::new(MemoryDatabase::new()));
+    let kel = alice.kel()?;
+    for message in signed_event_stream(&kel).unwrap().1 {
+        processor.process(Message::try_from(message)?)?;
+    }
+    let state = processor.compute_state(alice.prefix())?.unwrap();
+    assert_eq!(state.sn, 4);
+    assert_eq!(state.current.public_keys, vec![alice.public_key()]);
+
+    Ok(())
+}
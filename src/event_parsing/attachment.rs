@@ -1,5 +1,10 @@
-use std::convert::TryFrom;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::convert::TryFrom;
 
+use chrono::{DateTime, FixedOffset};
 use nom::{
     bytes::complete::take,
     combinator::map,
@@ -17,12 +22,16 @@ use crate::{
 
 use super::{
     prefix::{
-        attached_signature, attached_sn, basic_prefix, prefix, self_addressing_prefix,
-        self_signing_prefix,
+        attached_datetime, attached_signature, attached_sn, basic_prefix, prefix,
+        self_addressing_prefix, self_signing_prefix,
     },
     Attachment,
 };
 
+/// Max nesting of frames and pathed material groups, so deeply nested
+/// attachments can't exhaust the stack.
+const MAX_NESTING: usize = 8;
+
 /// returns attached source seals
 fn source_seal(s: &[u8]) -> nom::IResult<&[u8], Vec<SourceSeal>> {
     let (rest, sc) = b64_count(s)?;
@@ -56,7 +65,8 @@ fn event_seal(s: &[u8]) -> nom::IResult<&[u8], EventSeal> {
 
 pub(crate) fn b64_count(s: &[u8]) -> nom::IResult<&[u8], u16> {
     let (rest, t) = map(nom::bytes::complete::take(2u8), |b64_count| {
-        b64_to_num(b64_count).map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))
+        b64_to_num(b64_count)
+            .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))
     })(s)?;
 
     Ok((rest, t?))
@@ -76,11 +86,52 @@ fn couplets(s: &[u8]) -> nom::IResult<&[u8], Vec<(BasicPrefix, SelfSigningPrefix
     )(rest)
 }
 
+fn first_seen_couplets(s: &[u8]) -> nom::IResult<&[u8], Vec<(u64, DateTime<FixedOffset>)>> {
+    let (rest, sc) = b64_count(s)?;
+
+    count(
+        nom::sequence::tuple((attached_sn, attached_datetime)),
+        sc as usize,
+    )(rest)
+}
+
+/// returns path of pathed material, without padding
+fn material_path(s: &[u8]) -> nom::IResult<&[u8], String> {
+    let (rest, code) = take(2u8)(s)?;
+    // Base64 string codes with lead size 0, 1 and 2
+    if !matches!(code, b"4A" | b"5A" | b"6A") {
+        return Err(nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)));
+    }
+    let (rest, quadlets) = b64_count(rest)?;
+    let (rest, path) = take(quadlets as usize * 4)(rest)?;
+    let path = core::str::from_utf8(path)
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?;
+
+    Ok((rest, path.trim_start_matches('A').to_string()))
+}
+
+fn pathed_material(s: &[u8], depth: usize) -> nom::IResult<&[u8], (String, Vec<Attachment>)> {
+    let (rest, sc) = b64_count(s)?;
+    // sc * 4 is length of path and all attachments
+    let (rest, material) = take(sc as usize * 4)(rest)?;
+    let (material, path) = material_path(material)?;
+    let (extra, atts) = many0(|s| nested_attachment(s, depth + 1))(material)?;
+    if !extra.is_empty() {
+        return Err(nom::Err::Failure(nom::error::Error::new(s, ErrorKind::Eof)));
+    }
+
+    Ok((rest, (path, atts)))
+}
+
 fn indexed_signatures(input: &[u8]) -> nom::IResult<&[u8], Vec<AttachedSignaturePrefix>> {
-    attachment(input).map(|(rest, att)| match att {
-        Attachment::AttachedSignatures(sigs) => Ok((rest, sigs)),
-        _ => Err(nom::Err::Error((rest, ErrorKind::IsNot))),
-    })?
+    let (rest, payload_type) = take(2u8)(input)?;
+    if payload_type != b"-A" {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            ErrorKind::IsNot,
+        )));
+    }
+    signatures(rest)
 }
 
 fn identifier_signatures(
@@ -104,12 +155,24 @@ fn seal_signatures(
 }
 
 pub fn attachment(s: &[u8]) -> nom::IResult<&[u8], Attachment> {
+    nested_attachment(s, 0)
+}
+
+fn nested_attachment(s: &[u8], depth: usize) -> nom::IResult<&[u8], Attachment> {
     let (rest, payload_type) = take(2u8)(s)?;
+    if depth > MAX_NESTING {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            s,
+            ErrorKind::TooLarge,
+        )));
+    }
     let payload_type: PayloadType = PayloadType::try_from(
-        std::str::from_utf8(payload_type).map_err(|_e| nom::Err::Failure((s, ErrorKind::IsNot)))?,
+        // Not an attachment, but eg. binary message which follows.
+        core::str::from_utf8(payload_type)
+            .map_err(|_e| nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)))?,
     )
     // Can't parse payload type
-    .map_err(|_e| nom::Err::Error((s, ErrorKind::IsNot)))?;
+    .map_err(|_e| nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)))?;
     match payload_type {
         PayloadType::MG => {
             let (rest, source_seals) = source_seal(rest)?;
@@ -123,6 +186,10 @@ pub fn attachment(s: &[u8]) -> nom::IResult<&[u8], Attachment> {
             let (rest, sigs) = signatures(rest)?;
             Ok((rest, Attachment::AttachedSignatures(sigs)))
         }
+        PayloadType::MB => {
+            let (rest, sigs) = signatures(rest)?;
+            Ok((rest, Attachment::AttachedWitnessSignatures(sigs)))
+        }
         PayloadType::MC => {
             let (rest, couplets) = couplets(rest)?;
             Ok((rest, Attachment::ReceiptCouplets(couplets)))
@@ -131,29 +198,38 @@ pub fn attachment(s: &[u8]) -> nom::IResult<&[u8], Attachment> {
             let (rest, identifier_sigs) = identifier_signatures(rest)?;
             Ok((rest, Attachment::LastEstSignaturesGroups(identifier_sigs)))
         }
+        PayloadType::ME => {
+            let (rest, couplets) = first_seen_couplets(rest)?;
+            Ok((rest, Attachment::FirstSeenReplayCouplets(couplets)))
+        }
+        PayloadType::ML => {
+            let (rest, (path, atts)) = pathed_material(rest, depth)?;
+            Ok((rest, Attachment::PathedMaterialQuadruplets(path, atts)))
+        }
         PayloadType::MV => {
             let (rest, sc) = b64_count(rest)?;
             // sc * 4 is all attachments length
             match nom::bytes::complete::take(sc * 4)(rest) {
                 Ok((rest, total)) => {
-                    let (extra, atts) = many0(attachment)(total)?;
+                    let (extra, atts) = many0(|s| nested_attachment(s, depth + 1))(total)?;
                     if !extra.is_empty() {
                         // something is wrong, should not happend
-                        Err(nom::Err::Incomplete(Needed::Size(
+                        Err(nom::Err::Incomplete(Needed::new(
                             (sc * 4) as usize - rest.len(),
                         )))
                     } else {
                         Ok((rest, Attachment::Frame(atts)))
                     }
                 }
-                Err(nom::Err::Error((rest, _))) => Err(nom::Err::Incomplete(Needed::Size(
-                    (sc * 4) as usize - rest.len(),
-                ))),
+                Err(nom::Err::Error(nom::error::Error { input: rest, .. })) => Err(
+                    nom::Err::Incomplete(Needed::new((sc * 4) as usize - rest.len())),
+                ),
                 Err(e) => Err(e),
             }
         }
 
-        _ => todo!(),
+        // Not supported group
+        _ => Err(nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot))),
     }
 }
 
@@ -250,4 +326,55 @@ fn test_attachement() {
     let (rest, att) = attachment(cesr_attachment.as_bytes()).unwrap();
     assert!(matches!(att, Attachment::Frame(_)));
     assert!(rest.is_empty());
+    assert_eq!(att.to_cesr().unwrap(), cesr_attachment);
+
+    let attached_str = "-BABAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+    let (rest, att) = attachment(attached_str.as_bytes()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(
+        att,
+        Attachment::AttachedWitnessSignatures(vec!["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".parse().unwrap()])
+    );
+    assert_eq!(att.to_cesr().unwrap(), attached_str);
+
+    let attached_str = "-EAB0AAAAAAAAAAAAAAAAAAAAAAQ1AAG2020-08-22T17c50c09d988921p00c00";
+    let (rest, att) = attachment(attached_str.as_bytes()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(
+        att,
+        Attachment::FirstSeenReplayCouplets(vec![(
+            1,
+            "2020-08-22T17:50:09.988921+00:00".parse().unwrap()
+        )])
+    );
+    assert_eq!(att.to_cesr().unwrap(), attached_str);
+
+    let pathed = Attachment::PathedMaterialQuadruplets(
+        "-a".into(),
+        vec![Attachment::AttachedSignatures(vec!["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".parse().unwrap()])],
+    );
+    let attached_str = pathed.to_cesr().unwrap();
+    assert!(attached_str.starts_with("-LAZ5AABAA-a-AAB"));
+    let (rest, att) = attachment(attached_str.as_bytes()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(att, pathed);
+}
+
+#[test]
+fn test_nesting_limit() {
+    let nested = |depth: usize| {
+        (0..depth)
+            .fold(Attachment::Frame(vec![]), |inner, _| {
+                Attachment::Frame(vec![inner])
+            })
+            .to_cesr()
+            .unwrap()
+    };
+    let stream = nested(MAX_NESTING);
+    let (rest, _) = attachment(stream.as_bytes()).unwrap();
+    assert!(rest.is_empty());
+    assert!(matches!(
+        attachment(nested(MAX_NESTING + 1).as_bytes()),
+        Err(nom::Err::Failure(_))
+    ));
 }
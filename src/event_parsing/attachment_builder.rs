@@ -0,0 +1,298 @@
+//! Attachment Builder
+//!
+//! Typed construction of message attachments. Builder checks that
+//! signature indexes and digests can be encoded with their codes and
+//! that groups fit their count codes, and orders groups canonically, so
+//! serialized attachments always parse back the same.
+
+use alloc::{string::String, vec::Vec};
+use core::str::FromStr;
+
+use chrono::{DateTime, FixedOffset};
+
+use super::Attachment;
+use crate::{
+    derivation::attached_signature_code::AttachedSignatureCode,
+    error::Error,
+    event::sections::seal::{EventSeal, SourceSeal},
+    prefix::{
+        AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, Prefix, SelfAddressingPrefix,
+        SelfSigningPrefix,
+    },
+};
+
+/// Count codes are followed by two base64 characters.
+const MAX_COUNT: usize = 64 * 64 - 1;
+
+#[derive(Debug, Default, Clone)]
+pub struct AttachmentBuilder {
+    source_seals: Vec<SourceSeal>,
+    signatures: Vec<AttachedSignaturePrefix>,
+    witness_signatures: Vec<AttachedSignaturePrefix>,
+    receipt_couplets: Vec<(BasicPrefix, SelfSigningPrefix)>,
+    seal_signatures: Vec<(EventSeal, Vec<AttachedSignaturePrefix>)>,
+    last_establishment_signatures: Vec<(IdentifierPrefix, Vec<AttachedSignaturePrefix>)>,
+    first_seen: Vec<(u64, DateTime<FixedOffset>)>,
+}
+
+impl AttachmentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_source_seal(mut self, seal: SourceSeal) -> Self {
+        self.source_seals.push(seal);
+        self
+    }
+
+    /// Adds signatures of controller, indexed by position of key in its
+    /// current keys.
+    pub fn with_signatures(mut self, signatures: Vec<AttachedSignaturePrefix>) -> Self {
+        self.signatures.extend(signatures);
+        self
+    }
+
+    /// Adds signatures of witnesses, indexed by position of witness in
+    /// witness list.
+    pub fn with_witness_signatures(mut self, signatures: Vec<AttachedSignaturePrefix>) -> Self {
+        self.witness_signatures.extend(signatures);
+        self
+    }
+
+    pub fn with_receipt_couplet(
+        mut self,
+        witness: BasicPrefix,
+        signature: SelfSigningPrefix,
+    ) -> Self {
+        self.receipt_couplets.push((witness, signature));
+        self
+    }
+
+    /// Adds signatures made with keys of establishment event of `seal`.
+    pub fn with_seal_signatures(
+        mut self,
+        seal: EventSeal,
+        signatures: Vec<AttachedSignaturePrefix>,
+    ) -> Self {
+        self.seal_signatures.push((seal, signatures));
+        self
+    }
+
+    /// Adds signatures made with keys of last establishment event of
+    /// `signer`.
+    pub fn with_last_establishment_signatures(
+        mut self,
+        signer: IdentifierPrefix,
+        signatures: Vec<AttachedSignaturePrefix>,
+    ) -> Self {
+        self.last_establishment_signatures
+            .push((signer, signatures));
+        self
+    }
+
+    pub fn with_first_seen(mut self, sn: u64, timestamp: DateTime<FixedOffset>) -> Self {
+        self.first_seen.push((sn, timestamp));
+        self
+    }
+
+    /// Checks attachments and returns their groups in canonical order,
+    /// with signatures of each group ordered by index.
+    pub fn build(self) -> Result<Vec<Attachment>, Error> {
+        let mut attachments = vec![];
+        if !self.source_seals.is_empty() {
+            check_count(self.source_seals.len())?;
+            for seal in &self.source_seals {
+                check_digest(&seal.digest)?;
+            }
+            attachments.push(Attachment::SealSourceCouplets(self.source_seals));
+        }
+        if !self.signatures.is_empty() {
+            attachments.push(Attachment::AttachedSignatures(signature_group(
+                self.signatures,
+            )?));
+        }
+        if !self.witness_signatures.is_empty() {
+            attachments.push(Attachment::AttachedWitnessSignatures(signature_group(
+                self.witness_signatures,
+            )?));
+        }
+        if !self.receipt_couplets.is_empty() {
+            check_count(self.receipt_couplets.len())?;
+            for (i, (witness, signature)) in self.receipt_couplets.iter().enumerate() {
+                check_encoding(witness)?;
+                check_encoding(signature)?;
+                if self.receipt_couplets[..i].iter().any(|(w, _)| w == witness) {
+                    return Err(Error::InvalidAttachment(format!(
+                        "duplicate receipt of witness {}",
+                        witness.to_str()
+                    )));
+                }
+            }
+            attachments.push(Attachment::ReceiptCouplets(self.receipt_couplets));
+        }
+        if !self.seal_signatures.is_empty() {
+            check_count(self.seal_signatures.len())?;
+            let groups = self
+                .seal_signatures
+                .into_iter()
+                .map(|(seal, signatures)| {
+                    check_encoding(&seal.prefix)?;
+                    check_digest(&seal.event_digest)?;
+                    Ok((seal, signature_group(signatures)?))
+                })
+                .collect::<Result<_, Error>>()?;
+            attachments.push(Attachment::SealSignaturesGroups(groups));
+        }
+        if !self.last_establishment_signatures.is_empty() {
+            check_count(self.last_establishment_signatures.len())?;
+            let groups = self
+                .last_establishment_signatures
+                .into_iter()
+                .map(|(signer, signatures)| {
+                    check_encoding(&signer)?;
+                    Ok((signer, signature_group(signatures)?))
+                })
+                .collect::<Result<_, Error>>()?;
+            attachments.push(Attachment::LastEstSignaturesGroups(groups));
+        }
+        if !self.first_seen.is_empty() {
+            check_count(self.first_seen.len())?;
+            attachments.push(Attachment::FirstSeenReplayCouplets(self.first_seen));
+        }
+        Ok(attachments)
+    }
+
+    /// Builds attachments wrapped in one frame.
+    pub fn build_frame(self) -> Result<Attachment, Error> {
+        let attachments = self.build()?;
+        // Frame size is counted in quadlets.
+        let size: usize = attachments
+            .iter()
+            .map(|att| att.to_cesr().map(|cesr| cesr.len()))
+            .sum::<Result<_, _>>()?;
+        check_count(size / 4)?;
+        Ok(Attachment::Frame(attachments))
+    }
+
+    /// Builds attachments and serializes them as CESR text.
+    pub fn to_cesr(self) -> Result<String, Error> {
+        Ok(self
+            .build()?
+            .iter()
+            .map(Attachment::to_cesr)
+            .collect::<Result<Vec<_>, _>>()?
+            .concat())
+    }
+}
+
+/// Orders signatures by index and checks that every index is encodable
+/// and used once.
+fn signature_group(
+    mut signatures: Vec<AttachedSignaturePrefix>,
+) -> Result<Vec<AttachedSignaturePrefix>, Error> {
+    check_count(signatures.len())?;
+    signatures.sort_by_key(|signature| signature.index);
+    for (i, signature) in signatures.iter().enumerate() {
+        if !AttachedSignatureCode::new(signature.signature.derivation, signature.index)
+            .is_index_encodable()
+        {
+            return Err(Error::InvalidAttachment(format!(
+                "signature index {} out of range of its code",
+                signature.index
+            )));
+        }
+        if i > 0 && signatures[i - 1].index == signature.index {
+            return Err(Error::InvalidAttachment(format!(
+                "duplicate signature index {}",
+                signature.index
+            )));
+        }
+        check_encoding(&signature.signature)?;
+    }
+    Ok(signatures)
+}
+
+fn check_count(count: usize) -> Result<(), Error> {
+    if count > MAX_COUNT {
+        return Err(Error::LimitExceeded {
+            what: "attachment group items",
+            limit: MAX_COUNT,
+        });
+    }
+    Ok(())
+}
+
+fn check_digest(digest: &SelfAddressingPrefix) -> Result<(), Error> {
+    check_encoding(digest)
+}
+
+/// Checks that prefix parses back from its encoding, ie. its raw part
+/// has length its code requires.
+fn check_encoding<P: Prefix + FromStr + PartialEq>(prefix: &P) -> Result<(), Error> {
+    let encoded = prefix.to_str();
+    match encoded.parse::<P>() {
+        Ok(parsed) if &parsed == prefix => Ok(()),
+        _ => Err(Error::InvalidAttachment(format!(
+            "{} doesn't match its code",
+            encoded
+        ))),
+    }
+}
+
+#[test]
+fn test_attachment_builder() -> Result<(), Error> {
+    use crate::{derivation::self_signing::SelfSigning, event_parsing::attachment::attachment};
+
+    let digest: SelfAddressingPrefix = "E1_-icBrwC_HhxyFwsQLV6hZEbApOc_McGUjhLONpQuc".parse()?;
+    let signature =
+        |index| AttachedSignaturePrefix::new(SelfSigning::Ed25519Sha512, vec![0; 64], index);
+
+    // Groups are ordered canonically, signatures by index.
+    let attachments = AttachmentBuilder::new()
+        .with_signatures(vec![signature(1), signature(0)])
+        .with_source_seal(SourceSeal::new(1, digest.clone()))
+        .build()?;
+    assert_eq!(
+        attachments,
+        vec![
+            Attachment::SealSourceCouplets(vec![SourceSeal::new(1, digest.clone())]),
+            Attachment::AttachedSignatures(vec![signature(0), signature(1)]),
+        ]
+    );
+
+    // Serialized attachments parse back.
+    let frame = AttachmentBuilder::new()
+        .with_signatures(vec![signature(70)])
+        .with_source_seal(SourceSeal::new(1, digest.clone()))
+        .build_frame()?;
+    let cesr = frame.to_cesr()?;
+    assert!(cesr.starts_with("-V"));
+    let (rest, parsed) = attachment(cesr.as_bytes()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(parsed, frame);
+
+    // Duplicate and unencodable indexes are refused.
+    assert!(matches!(
+        AttachmentBuilder::new()
+            .with_signatures(vec![signature(0), signature(0)])
+            .build(),
+        Err(Error::InvalidAttachment(_))
+    ));
+    assert!(matches!(
+        AttachmentBuilder::new()
+            .with_signatures(vec![signature(4096)])
+            .build(),
+        Err(Error::InvalidAttachment(_))
+    ));
+
+    // Digest which raw part doesn't match its code is refused.
+    let truncated = SelfAddressingPrefix::new(digest.derivation.clone(), vec![0; 16]);
+    assert!(matches!(
+        AttachmentBuilder::new()
+            .with_source_seal(SourceSeal::new(1, truncated))
+            .build(),
+        Err(Error::InvalidAttachment(_))
+    ));
+
+    Ok(())
+}
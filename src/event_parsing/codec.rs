@@ -0,0 +1,94 @@
+//! KERI codec
+//!
+//! Frames KERI messages with their attachments for `tokio_util`
+//! `FramedRead`, `FramedWrite` and `Framed`, so byte streams such as TCP
+//! connections can be turned into streams of messages.
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    error::Error,
+    event_parsing::{stream::parse_message, SignedEventData},
+};
+
+/// KERI Codec
+///
+/// Decodes messages as soon as they are complete, see `StreamParser`.
+/// Encodes messages to CESR in text domain. Already serialized CESR
+/// streams, eg. responses, are passed through as they are.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KeriCodec;
+
+impl KeriCodec {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Decoder for KeriCodec {
+    type Item = SignedEventData;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match parse_message(src) {
+            Ok(Some((parsed, message))) => {
+                src.advance(parsed);
+                Ok(Some(message))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                // Next message start is unknown, so the rest is useless.
+                src.clear();
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Encoder<SignedEventData> for KeriCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: SignedEventData, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&item.to_cesr()?);
+        Ok(())
+    }
+}
+
+impl Encoder<Vec<u8>> for KeriCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Vec<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&item);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_codec() -> Result<(), Error> {
+    use crate::event_parsing::message::signed_event_stream;
+
+    // Taken from keripy/tests/core/test_kevery.py::test kevery
+    let kerl_str= br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw{"v":"KERI10JSON000155_","t":"rot","d":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"1","p":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","kt":"1","k":["DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI"],"n":"E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI","bt":"0","br":[],"ba":[],"a":[]}-AABAAwoiqt07w2UInzzo2DmtwkBfqX1-tTO4cYk_7YdlbJ95qA7PO5sEUkER8fZySQMNCVh64ruAh1yoew3TikwVGAQ"#;
+    let (_, expected) = signed_event_stream(kerl_str).unwrap();
+
+    let mut codec = KeriCodec::new();
+    let mut buffer = BytesMut::new();
+    let mut messages = vec![];
+    for chunk in kerl_str.chunks(64) {
+        buffer.extend_from_slice(chunk);
+        while let Some(message) = codec.decode(&mut buffer)? {
+            messages.push(message);
+        }
+    }
+    assert_eq!(messages, expected);
+    assert!(buffer.is_empty());
+
+    let mut encoded = BytesMut::new();
+    for message in messages {
+        codec.encode(message, &mut encoded)?;
+    }
+    assert_eq!(&encoded[..], &kerl_str[..]);
+
+    Ok(())
+}
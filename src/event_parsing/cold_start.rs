@@ -0,0 +1,55 @@
+//! Cold start
+//!
+//! Stream parser has to find out what comes next without any context,
+//! eg. after reconnection. Three most significant bits (tritet) of the
+//! first byte are enough to tell apart serialized messages and text or
+//! binary domain CESR groups.
+
+/// Cold Code
+///
+/// What starts with the byte of given tritet, as defined in CESR.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColdCode {
+    /// Annotated text domain stream
+    Annotated,
+    /// Text domain count code
+    CountCode,
+    /// Text domain op code
+    OpCode,
+    JSON,
+    /// MessagePack fixmap
+    MGPK1,
+    CBOR,
+    /// MessagePack map16 or map32
+    MGPK2,
+    /// Binary domain count or op code
+    BinaryCountCode,
+}
+
+pub fn cold_start(s: &[u8]) -> Option<ColdCode> {
+    let tritet = s.first()? >> 5;
+    Some(match tritet {
+        0b000 => ColdCode::Annotated,
+        0b001 => ColdCode::CountCode,
+        0b010 => ColdCode::OpCode,
+        0b011 => ColdCode::JSON,
+        0b100 => ColdCode::MGPK1,
+        0b101 => ColdCode::CBOR,
+        0b110 => ColdCode::MGPK2,
+        _ => ColdCode::BinaryCountCode,
+    })
+}
+
+#[test]
+fn test_cold_start() {
+    assert_eq!(cold_start(b"{\"v\":"), Some(ColdCode::JSON));
+    assert_eq!(cold_start(b"-AAB"), Some(ColdCode::CountCode));
+    assert_eq!(cold_start(b"_AAB"), Some(ColdCode::OpCode));
+    assert_eq!(cold_start(&[0xa7]), Some(ColdCode::CBOR));
+    assert_eq!(cold_start(&[0x87]), Some(ColdCode::MGPK1));
+    assert_eq!(cold_start(&[0xde]), Some(ColdCode::MGPK2));
+    // "-AAB" in binary domain
+    let binary = base64::decode_config("-AAB", base64::URL_SAFE).unwrap();
+    assert_eq!(cold_start(&binary), Some(ColdCode::BinaryCountCode));
+    assert_eq!(cold_start(&[]), None);
+}
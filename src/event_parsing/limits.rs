@@ -0,0 +1,201 @@
+//! Parser Limits
+//!
+//! Bounds for parsing data from untrusted sources, eg. network peers.
+//! Message is deserialized only if it declares acceptable size, and
+//! never reads beyond the limit, so malicious input can't make parser
+//! allocate unbounded memory.
+
+use crate::{error::Error, event_parsing::Attachment};
+use alloc::vec::Vec;
+
+use super::{
+    message::{attachments, event_message, version_string},
+    SignedEventData,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParserLimits {
+    /// Max size of message in bytes, without attachments.
+    pub max_message_size: usize,
+    /// Max number of attachments of single message, nested included.
+    pub max_attachments: usize,
+    /// Max number of signatures attached to single message.
+    pub max_signatures: usize,
+    /// Max number of messages in parsed stream.
+    pub max_messages: usize,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self {
+            max_message_size: 1 << 16,
+            max_attachments: 64,
+            max_signatures: 1024,
+            max_messages: 1024,
+        }
+    }
+}
+
+impl ParserLimits {
+    pub fn with_max_message_size(self, max_message_size: usize) -> Self {
+        Self {
+            max_message_size,
+            ..self
+        }
+    }
+
+    pub fn with_max_attachments(self, max_attachments: usize) -> Self {
+        Self {
+            max_attachments,
+            ..self
+        }
+    }
+
+    pub fn with_max_signatures(self, max_signatures: usize) -> Self {
+        Self {
+            max_signatures,
+            ..self
+        }
+    }
+
+    pub fn with_max_messages(self, max_messages: usize) -> Self {
+        Self {
+            max_messages,
+            ..self
+        }
+    }
+
+    fn check_attachments(&self, attachments: &[Attachment]) -> Result<(), Error> {
+        let (count, signatures) = count_attachments(attachments);
+        if count > self.max_attachments {
+            return Err(Error::LimitExceeded {
+                what: "attachments",
+                limit: self.max_attachments,
+            });
+        }
+        if signatures > self.max_signatures {
+            return Err(Error::LimitExceeded {
+                what: "signatures",
+                limit: self.max_signatures,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Returns number of attachments and signatures, nested included.
+fn count_attachments(attachments: &[Attachment]) -> (usize, usize) {
+    attachments
+        .iter()
+        .fold((0, 0), |(count, signatures), attachment| {
+            let (nested, sigs) = match attachment {
+                Attachment::AttachedSignatures(sigs)
+                | Attachment::AttachedWitnessSignatures(sigs) => (0, sigs.len()),
+                Attachment::ReceiptCouplets(couplets) => (0, couplets.len()),
+                Attachment::SealSignaturesGroups(groups) => {
+                    (0, groups.iter().map(|(_, sigs)| sigs.len()).sum())
+                }
+                Attachment::LastEstSignaturesGroups(groups) => {
+                    (0, groups.iter().map(|(_, sigs)| sigs.len()).sum())
+                }
+                Attachment::PathedMaterialQuadruplets(_, atts) | Attachment::Frame(atts) => {
+                    count_attachments(atts)
+                }
+                Attachment::SealSourceCouplets(_) | Attachment::FirstSeenReplayCouplets(_) => {
+                    (0, 0)
+                }
+            };
+            (count + 1 + nested, signatures + sigs)
+        })
+}
+
+/// Parses stream received from untrusted source. Unlike
+/// `signed_event_stream`, whole stream has to be parsed and each
+/// message has to fit in `limits`, otherwise error is returned.
+pub fn parse_untrusted(
+    stream: &[u8],
+    limits: &ParserLimits,
+) -> Result<Vec<SignedEventData>, Error> {
+    let mut rest = stream;
+    let mut messages = vec![];
+    while !rest.is_empty() {
+        if messages.len() == limits.max_messages {
+            return Err(Error::LimitExceeded {
+                what: "messages",
+                limit: limits.max_messages,
+            });
+        }
+        let version = version_string(rest)?
+            .ok_or_else(|| Error::DeserializeError("Incomplete message".into()))?;
+        version.check_version()?;
+        if version.size > limits.max_message_size {
+            return Err(Error::MessageTooLarge {
+                size: version.size,
+                limit: limits.max_message_size,
+            });
+        }
+        // Deserializer never sees more than the limit, whatever size
+        // message declares.
+        let window = &rest[..rest.len().min(limits.max_message_size)];
+        let (window_rest, event) =
+            event_message(window).map_err(|_| Error::DeserializeError("Invalid message".into()))?;
+        let (after, attachments) = attachments(&rest[window.len() - window_rest.len()..])
+            .map_err(|_| Error::DeserializeError("Invalid attachments".into()))?;
+        limits.check_attachments(&attachments)?;
+        messages.push(SignedEventData {
+            deserialized_event: event,
+            attachments,
+        });
+        rest = after;
+    }
+    Ok(messages)
+}
+
+#[test]
+fn test_parse_untrusted() -> Result<(), Error> {
+    // Multisig inception event with 3 signatures, from keripy
+    // `test_multisig_digprefix` test.
+    let icp = br#"{"v":"KERI10JSON00017e_","t":"icp","d":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","i":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","s":"0","kt":"2","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI","DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8"],"n":"E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw","bt":"0","b":[],"c":[],"a":[]}-AADAA39j08U7pcU66OPKsaPExhBuHsL5rO1Pjq5zMgt_X6jRbezevis6YBUg074ZNKAGdUwHLqvPX_kse4buuuSUpAQABphobpuQEZ6EhKLhBuwgJmIQu80ZUV1GhBL0Ht47Hsl1rJiMwE2yW7-yi8k3idw2ahlpgdd9ka9QOP9yQmMWGAQACM7yfK1b86p1H62gonh1C7MECDCFBkoH0NZRjHKAEHebvd2_LLz6cpCaqKWDhbM2Rq01f9pgyDTFNLJMxkC-fAQ"#;
+    let stream = [&icp[..], &icp[..]].concat();
+
+    let limits = ParserLimits::default();
+    assert_eq!(parse_untrusted(&stream, &limits)?.len(), 2);
+    assert!(parse_untrusted(&[], &limits)?.is_empty());
+
+    assert!(matches!(
+        parse_untrusted(&stream, &limits.with_max_message_size(0x17d)),
+        Err(Error::MessageTooLarge {
+            size: 0x17e,
+            limit: 0x17d
+        })
+    ));
+    assert!(matches!(
+        parse_untrusted(&stream, &limits.with_max_signatures(2)),
+        Err(Error::LimitExceeded {
+            what: "signatures",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse_untrusted(&stream, &limits.with_max_attachments(0)),
+        Err(Error::LimitExceeded {
+            what: "attachments",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse_untrusted(&stream, &limits.with_max_messages(1)),
+        Err(Error::LimitExceeded {
+            what: "messages",
+            ..
+        })
+    ));
+
+    // Truncated, trailing or malformed data is an error.
+    assert!(parse_untrusted(&stream[..stream.len() - 10], &limits).is_err());
+    assert!(parse_untrusted(&[&icp[..], b"garbage"].concat(), &limits).is_err());
+    assert!(parse_untrusted(&icp[..100], &limits).is_err());
+    assert!(parse_untrusted(b"{\"v\":\"KERI10JSON", &limits).is_err());
+
+    Ok(())
+}
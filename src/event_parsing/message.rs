@@ -1,5 +1,4 @@
-use std::io::Cursor;
-
+use alloc::{string::ToString, vec::Vec};
 use nom::{
     branch::alt,
     error::ErrorKind,
@@ -7,18 +6,25 @@ use nom::{
 };
 use serde::Deserialize;
 
-#[cfg(feature = "async")]
-use crate::event_message::serialization_info::SerializationInfo;
+use core::str::FromStr;
+
+use crate::event_message::serialization_info::{SerializationInfo, VERSION_SIZE};
 
 #[cfg(feature = "query")]
 use crate::event_message::{SaidEvent, Typeable};
 #[cfg(feature = "query")]
 use crate::query::Envelope;
 use crate::{
-    event::{receipt::Receipt, EventMessage},
+    error::Error,
+    event::{receipt::Receipt, EventMessage, SerializationFormats},
     event_message::{key_event_message::KeyEvent, Digestible},
-    event_parsing::{attachment::attachment, Attachment, EventType, SignedEventData},
+    event_parsing::{
+        attachment::attachment,
+        cold_start::{cold_start, ColdCode},
+        Attachment, EventType, SignedEventData,
+    },
 };
+#[cfg(feature = "std")]
 use rmp_serde as serde_mgpk;
 #[cfg(feature = "query")]
 use serde::Serialize;
@@ -29,7 +35,7 @@ fn json_message<'a, D: Deserialize<'a> + Digestible>(
     let mut stream = serde_json::Deserializer::from_slice(s).into_iter::<EventMessage<D>>();
     match stream.next() {
         Some(Ok(event)) => Ok((&s[stream.byte_offset()..], event)),
-        _ => Err(nom::Err::Error((s, ErrorKind::IsNot))),
+        _ => Err(nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot))),
     }
 }
 
@@ -37,32 +43,119 @@ fn cbor_message<'a, D: Deserialize<'a>>(s: &'a [u8]) -> nom::IResult<&[u8], Even
     let mut stream = serde_cbor::Deserializer::from_slice(s).into_iter::<EventMessage<D>>();
     match stream.next() {
         Some(Ok(event)) => Ok((&s[stream.byte_offset()..], event)),
-        _ => Err(nom::Err::Error((s, ErrorKind::IsNot))),
+        _ => Err(nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot))),
+    }
+}
+
+#[cfg(feature = "std")]
+fn mgpk_message<'a, D: Deserialize<'a>>(s: &'a [u8]) -> nom::IResult<&'a [u8], EventMessage<D>> {
+    // Slice deserializer doesn't track consumed bytes, so message length
+    // is taken from its version string.
+    let mut deser = serde_mgpk::Deserializer::from_read_ref(s);
+    let event: EventMessage<D> = Deserialize::deserialize(&mut deser)
+        .map_err(|_e| nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)))?;
+    match s.get(event.serialization_info.size..) {
+        Some(rest) => Ok((rest, event)),
+        None => Err(nom::Err::Error(nom::error::Error::new(s, ErrorKind::Eof))),
+    }
+}
+
+/// Detects serialization format of message from its first byte.
+fn sniff(s: &[u8]) -> Option<SerializationFormats> {
+    match cold_start(s)? {
+        ColdCode::JSON => Some(SerializationFormats::JSON),
+        ColdCode::CBOR => Some(SerializationFormats::CBOR),
+        ColdCode::MGPK1 | ColdCode::MGPK2 => Some(SerializationFormats::MGPK),
+        _ => None,
     }
 }
 
-fn mgpk_message<'a, D: Deserialize<'a>>(s: &[u8]) -> nom::IResult<&[u8], EventMessage<D>> {
-    let mut deser = serde_mgpk::Deserializer::new(Cursor::new(s));
-    match Deserialize::deserialize(&mut deser) {
-        Ok(event) => Ok((&s[deser.get_ref().position() as usize..], event)),
-        _ => Err(nom::Err::Error((s, ErrorKind::IsNot))),
+/// Max offset of version string from the beginning of the message.
+const MAX_VERSION_OFFSET: usize = 12;
+
+/// Finds and parses version string at the beginning of message, before
+/// message is deserialized. Returns None if not enough bytes arrived
+/// to read it.
+pub(crate) fn version_string(data: &[u8]) -> Result<Option<SerializationInfo>, Error> {
+    let window = &data[..data.len().min(MAX_VERSION_OFFSET + VERSION_SIZE)];
+    match window.windows(4).position(|w| w == b"KERI") {
+        Some(offset) if offset <= MAX_VERSION_OFFSET && offset + VERSION_SIZE <= window.len() => {
+            let version = core::str::from_utf8(&window[offset..offset + VERSION_SIZE])
+                .map_err(|e| Error::DeserializeError(e.to_string()))?;
+            Ok(Some(SerializationInfo::from_str(version)?))
+        }
+        _ if window.len() == MAX_VERSION_OFFSET + VERSION_SIZE => {
+            Err(Error::DeserializeError("Missing version string".into()))
+        }
+        _ => Ok(None),
     }
 }
 
 pub fn message<'a, D: Deserialize<'a> + Digestible>(
     s: &'a [u8],
 ) -> nom::IResult<&[u8], EventMessage<D>> {
-    alt((json_message::<D>, cbor_message::<D>, mgpk_message::<D>))(s)
+    let format = sniff(s).ok_or(nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)))?;
+    let version = match version_string(s) {
+        Ok(Some(version)) if version.check_version().is_ok() => version,
+        _ => {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                s,
+                ErrorKind::Verify,
+            )))
+        }
+    };
+    // Messages are deserialized according to protocol version they
+    // declare, so messages of different versions can be mixed in one
+    // stream. Parsers of new versions are added here.
+    let (rest, event) = match (version.major_version, format) {
+        (1, SerializationFormats::JSON) => json_message::<D>(s),
+        (1, SerializationFormats::CBOR) => cbor_message::<D>(s),
+        #[cfg(feature = "std")]
+        (1, SerializationFormats::MGPK) => mgpk_message::<D>(s),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            s,
+            ErrorKind::Verify,
+        ))),
+    }?;
+    // Version string has to declare format the message is encoded in.
+    if event.serialization_info.kind != format {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            s,
+            ErrorKind::Verify,
+        )));
+    }
+    Ok((rest, event))
+}
+
+/// Parses message and checks if its size is the one declared in its
+/// version string.
+fn sized_message<'a, D: Deserialize<'a> + Digestible>(
+    s: &'a [u8],
+) -> nom::IResult<&[u8], EventMessage<D>> {
+    let (rest, event) = message::<D>(s)?;
+    if s.len() - rest.len() != event.serialization_info.size {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            s,
+            ErrorKind::Verify,
+        )));
+    }
+    Ok((rest, event))
 }
 
 pub fn key_event_message(s: &[u8]) -> nom::IResult<&[u8], EventType> {
-    message::<KeyEvent>(s).map(|d| (d.0, EventType::KeyEvent(d.1)))
+    sized_message::<KeyEvent>(s).map(|(rest, event)| {
+        // Keep bytes of event as they were received.
+        let raw = s[..s.len() - rest.len()].to_vec();
+        (rest, EventType::KeyEvent(event, Some(raw)))
+    })
 }
 
 pub fn receipt_message(s: &[u8]) -> nom::IResult<&[u8], EventType> {
-    message::<Receipt>(s).map(|d| (d.0, EventType::Receipt(d.1)))
+    sized_message::<Receipt>(s).map(|d| (d.0, EventType::Receipt(d.1)))
 }
 
+// Size of query and reply isn't checked, as keripy used to sign them
+// with stale size. They are bound by their SAID instead.
 #[cfg(feature = "query")]
 fn envelope<'a, D: Serialize + Deserialize<'a> + Typeable>(
     s: &'a [u8],
@@ -72,7 +165,7 @@ fn envelope<'a, D: Serialize + Deserialize<'a> + Typeable>(
 
 #[cfg(feature = "query")]
 pub fn query_message<'a>(s: &'a [u8]) -> nom::IResult<&[u8], EventType> {
-    use crate::query::query::QueryData;
+    use crate::query::query_event::QueryData;
 
     envelope::<QueryData>(s).map(|d| (d.0, EventType::Qry(d.1)))
 }
@@ -84,21 +177,54 @@ pub fn reply_message<'a>(s: &'a [u8]) -> nom::IResult<&[u8], EventType> {
     envelope::<ReplyData>(s).map(|d| (d.0, EventType::Rpy(d.1)))
 }
 
-pub fn signed_message(s: &[u8]) -> nom::IResult<&[u8], SignedEventData> {
+#[cfg(feature = "query")]
+pub fn exchange_message(s: &[u8]) -> nom::IResult<&[u8], EventType> {
+    use crate::query::exchange::ExchangeEvent;
+
+    sized_message::<ExchangeEvent>(s).map(|d| (d.0, EventType::Exn(d.1)))
+}
+
+/// Parses attachments in binary domain of CESR. Every group takes whole
+/// quadlets in text domain, which are triplets of bytes in binary domain,
+/// so it is parsed from its text equivalent.
+fn binary_attachments(s: &[u8]) -> nom::IResult<&[u8], Vec<Attachment>> {
+    let text = base64::encode_config(&s[..s.len() / 3 * 3], base64::URL_SAFE);
+    let (text_rest, attachments) = many0(attachment)(text.as_bytes())
+        .map_err(|_e| nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)))?;
+    let parsed = (text.len() - text_rest.len()) / 4 * 3;
+    Ok((&s[parsed..], attachments))
+}
+
+/// Parses message of any supported type, without attachments.
+pub fn event_message(s: &[u8]) -> nom::IResult<&[u8], EventType> {
     #[cfg(feature = "query")]
-    let (rest, event) = alt((
+    let result = alt((
         key_event_message,
         reply_message,
         query_message,
         receipt_message,
-    ))(s)?;
+        // Exchange payload is arbitrary, so it has to be tried last.
+        exchange_message,
+    ))(s);
     #[cfg(not(feature = "query"))]
-    let (rest, event) = alt((key_event_message, receipt_message))(s)?;
-    let (rest, attachments): (&[u8], Vec<Attachment>) =
-        fold_many0(attachment, vec![], |mut acc: Vec<_>, item| {
+    let result = alt((key_event_message, receipt_message))(s);
+    result
+}
+
+/// Parses attachments following message, in text or binary domain.
+pub fn attachments(s: &[u8]) -> nom::IResult<&[u8], Vec<Attachment>> {
+    match cold_start(s) {
+        Some(ColdCode::BinaryCountCode) => binary_attachments(s),
+        _ => fold_many0(attachment, Vec::new, |mut acc: Vec<_>, item| {
             acc.push(item);
             acc
-        })(rest)?;
+        })(s),
+    }
+}
+
+pub fn signed_message(s: &[u8]) -> nom::IResult<&[u8], SignedEventData> {
+    let (rest, event) = event_message(s)?;
+    let (rest, attachments) = attachments(rest)?;
 
     Ok((
         rest,
@@ -118,7 +244,10 @@ pub fn signed_event_stream(s: &[u8]) -> nom::IResult<&[u8], Vec<SignedEventData>
 fn json_version(data: &[u8]) -> nom::IResult<&[u8], SerializationInfo> {
     match serde_json::from_slice(data) {
         Ok(vi) => Ok((data, vi)),
-        _ => Err(nom::Err::Error((data, ErrorKind::IsNot))),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            data,
+            ErrorKind::IsNot,
+        ))),
     }
 }
 
@@ -127,7 +256,10 @@ fn json_version(data: &[u8]) -> nom::IResult<&[u8], SerializationInfo> {
 fn cbor_version(data: &[u8]) -> nom::IResult<&[u8], SerializationInfo> {
     match serde_cbor::from_slice(data) {
         Ok(vi) => Ok((data, vi)),
-        _ => Err(nom::Err::Error((data, ErrorKind::IsNot))),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            data,
+            ErrorKind::IsNot,
+        ))),
     }
 }
 
@@ -136,7 +268,10 @@ fn cbor_version(data: &[u8]) -> nom::IResult<&[u8], SerializationInfo> {
 fn mgpk_version(data: &[u8]) -> nom::IResult<&[u8], SerializationInfo> {
     match serde_mgpk::from_slice(data) {
         Ok(vi) => Ok((data, vi)),
-        _ => Err(nom::Err::Error((data, ErrorKind::IsNot))),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            data,
+            ErrorKind::IsNot,
+        ))),
     }
 }
 
@@ -200,6 +335,34 @@ fn test_key_event_parsing() {
     assert_eq!(event.unwrap().1.serialize().unwrap(), stream);
 }
 
+#[test]
+fn test_version_enforcement() {
+    let icp = br#"{"v":"KERI10JSON000120_","t":"icp","d":"Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8","i":"Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8","s":"0","kt":"1","k":["DqI2cOZ06RwGNwCovYUWExmdKU983IasmUKMmZflvWdQ"],"n":"E7FuL3Z_KBgt_QAwuZi1lUFNC69wvyHSxnMFUsKjZHss","bt":"0","b":[],"c":[],"a":[]}"#;
+    assert!(key_event_message(icp).is_ok());
+
+    // Unsupported protocol version.
+    let future = String::from_utf8(icp.to_vec())
+        .unwrap()
+        .replace("KERI10JSON", "KERI20JSON");
+    assert!(key_event_message(future.as_bytes()).is_err());
+    assert!(matches!(
+        version_string(future.as_bytes()),
+        Ok(Some(version)) if version.check_version().is_err()
+    ));
+
+    // Size doesn't match size declared in version string.
+    let resized = String::from_utf8(icp.to_vec())
+        .unwrap()
+        .replace("KERI10JSON000120_", "KERI10JSON000121_");
+    assert!(key_event_message(resized.as_bytes()).is_err());
+
+    // Version string declares other serialization format.
+    let misdeclared = String::from_utf8(icp.to_vec())
+        .unwrap()
+        .replace("KERI10JSON", "KERI10CBOR");
+    assert!(key_event_message(misdeclared.as_bytes()).is_err());
+}
+
 #[test]
 fn test_receipt_parsing() {
     // Receipt event
@@ -241,7 +404,7 @@ fn test_signed_qry() {
     // Taken from keripy/tests/core/test_eventing.py::test_messagize (line 1471)
     let stream = br#"{"v":"KERI10JSON0000c9_","t":"qry","d":"E-WvgxrllmjGFhpn0oOiBkAVz3-dEm3bbiV_5qwj81xo","dt":"2021-01-01T00:00:00.000000+00:00","r":"log","rr":"","q":{"i":"DyvCLRr5luWmp7keDvDuLP0kIqcyBYq79b3Dho1QvrjI"}}-VAj-HABEZOIsLsfrVdBvULlg3Hg_Y1r-hadS82ZpglBLojPIQhg-AABAAuISeZIVO_wXjIrGJ-VcVMxr285OkKzAqVEQqVPFx8Ht2A9GQFB-zRA18J1lpqVphOnnXbTc51WR4uAvK90EHBg"#;
     let se = signed_message(&stream[..stream.len() - 1]);
-    assert!(matches!(se, Err(nom::Err::Incomplete(Needed::Size(1)))));
+    assert!(matches!(se, Err(nom::Err::Incomplete(n)) if n == Needed::new(1)));
     let se = signed_message(stream);
     assert!(se.is_ok());
 }
@@ -254,4 +417,21 @@ fn test_signed_events_stream() {
 
     assert!(rest.is_empty());
     assert_eq!(messages.len(), 7);
+
+    // The same stream with attachments of every second message in binary
+    // domain.
+    let mixed_stream = messages
+        .iter()
+        .enumerate()
+        .map(|(i, message)| match i % 2 {
+            0 => message.to_cesr_binary(),
+            _ => message.to_cesr(),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+        .concat();
+    assert!(mixed_stream.len() < kerl_str.len());
+    let (rest, mixed_messages) = signed_event_stream(&mixed_stream).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(mixed_messages, messages);
 }
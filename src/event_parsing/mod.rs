@@ -1,13 +1,21 @@
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
 use base64::URL_SAFE_NO_PAD;
+use chrono::{DateTime, FixedOffset};
+use core::convert::TryFrom;
 use serde::Deserialize;
-use std::convert::TryFrom;
 
+use crate::derivation::attached_signature_code::num_to_b64;
 use crate::event::receipt::Receipt;
 use crate::event::sections::seal::{EventSeal, SourceSeal};
 use crate::event::EventMessage;
 use crate::event_message::key_event_message::KeyEvent;
 use crate::event_message::signed_event_message::{
     Message, SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+    SignedWitnessReceipt,
 };
 use crate::event_parsing::payload_size::PayloadType;
 use crate::prefix::{
@@ -16,32 +24,47 @@ use crate::prefix::{
 
 #[cfg(feature = "query")]
 use crate::query::{
-    query::QueryEvent,
+    exchange::{ExchangeEvent, SignedExchange},
+    query_event::{QueryEvent, SignedQuery},
     reply::{ReplyEvent, SignedReply},
 };
 use crate::{error::Error, event::event_data::EventData};
 
 pub mod attachment;
+pub mod attachment_builder;
+#[cfg(feature = "async")]
+pub mod codec;
+pub mod cold_start;
+pub mod limits;
 pub mod message;
 pub mod payload_size;
 pub mod prefix;
+pub mod stream;
 
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum Attachment {
     // Count codes
     SealSourceCouplets(Vec<SourceSeal>),
     AttachedSignatures(Vec<AttachedSignaturePrefix>),
+    // Signatures of witnesses, indexed by position in witness list
+    AttachedWitnessSignatures(Vec<AttachedSignaturePrefix>),
     ReceiptCouplets(Vec<(BasicPrefix, SelfSigningPrefix)>),
+    // Sequence numbers and first seen datetimes of replayed events
+    FirstSeenReplayCouplets(Vec<(u64, DateTime<FixedOffset>)>),
     // Group codes
     SealSignaturesGroups(Vec<(EventSeal, Vec<AttachedSignaturePrefix>)>),
     // List of signatures made using keys from last establishment event od identifier of prefix
     LastEstSignaturesGroups(Vec<(IdentifierPrefix, Vec<AttachedSignaturePrefix>)>),
+    // Path to nested part of message and attachments which refer to it
+    PathedMaterialQuadruplets(String, Vec<Attachment>),
     // Frame codes
     Frame(Vec<Attachment>),
 }
 
 impl Attachment {
-    pub fn to_cesr(&self) -> String {
+    /// Attachment in text domain of CESR. Returns error if some
+    /// signature index doesn't fit in its code.
+    pub fn to_cesr(&self) -> Result<String, Error> {
         let (payload_type, att_len, serialized_attachment) = match self {
             Attachment::SealSourceCouplets(sources) => {
                 let serialzied_sources = sources.iter().fold("".into(), |acc, s| {
@@ -51,27 +74,43 @@ impl Attachment {
                 (PayloadType::MG, sources.len(), serialzied_sources)
             }
             Attachment::SealSignaturesGroups(seals_signatures) => {
-                let serialized_seals =
-                    seals_signatures
-                        .iter()
-                        .fold("".into(), |acc, (seal, sigs)| {
-                            [
-                                acc,
-                                seal.prefix.to_str(),
-                                Self::pack_sn(seal.sn),
-                                seal.event_digest.to_str(),
-                                Attachment::AttachedSignatures(sigs.to_vec()).to_cesr(),
-                            ]
-                            .join("")
-                        });
+                let serialized_seals = seals_signatures.iter().try_fold(
+                    String::new(),
+                    |acc, (seal, sigs)| -> Result<_, Error> {
+                        Ok([
+                            acc,
+                            seal.prefix.to_str(),
+                            Self::pack_sn(seal.sn),
+                            seal.event_digest.to_str(),
+                            Attachment::AttachedSignatures(sigs.to_vec()).to_cesr()?,
+                        ]
+                        .join(""))
+                    },
+                )?;
                 (PayloadType::MF, seals_signatures.len(), serialized_seals)
             }
             Attachment::AttachedSignatures(sigs) => {
                 let serialized_sigs = sigs
                     .iter()
-                    .fold("".into(), |acc, sig| [acc, sig.to_str()].join(""));
+                    .map(AttachedSignaturePrefix::encode)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join("");
                 (PayloadType::MA, sigs.len(), serialized_sigs)
             }
+            Attachment::AttachedWitnessSignatures(sigs) => {
+                let serialized_sigs = sigs
+                    .iter()
+                    .map(AttachedSignaturePrefix::encode)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join("");
+                (PayloadType::MB, sigs.len(), serialized_sigs)
+            }
+            Attachment::FirstSeenReplayCouplets(couplets) => {
+                let packed_couplets = couplets.iter().fold("".into(), |acc, (sn, dt)| {
+                    [acc, Self::pack_sn(*sn), Self::pack_datetime(dt)].join("")
+                });
+                (PayloadType::ME, couplets.len(), packed_couplets)
+            }
             Attachment::ReceiptCouplets(couplets) => {
                 let packed_couplets = couplets.iter().fold("".into(), |acc, (bp, sp)| {
                     [acc, bp.to_str(), sp.to_str()].join("")
@@ -80,32 +119,53 @@ impl Attachment {
                 (PayloadType::MC, couplets.len(), packed_couplets)
             }
             Attachment::LastEstSignaturesGroups(signers) => {
-                let packed_signers = signers.iter().fold("".to_string(), |acc, (signer, sigs)| {
-                    [
-                        acc,
-                        signer.to_str(),
-                        Attachment::AttachedSignatures(sigs.clone()).to_cesr(),
-                    ]
-                    .concat()
-                });
+                let packed_signers = signers.iter().try_fold(
+                    String::new(),
+                    |acc, (signer, sigs)| -> Result<_, Error> {
+                        Ok([
+                            acc,
+                            signer.to_str(),
+                            Attachment::AttachedSignatures(sigs.clone()).to_cesr()?,
+                        ]
+                        .concat())
+                    },
+                )?;
                 (PayloadType::MH, signers.len(), packed_signers)
             }
+            Attachment::PathedMaterialQuadruplets(path, att) => {
+                let packed_material = att
+                    .iter()
+                    .try_fold(Self::pack_path(path), |acc, att| -> Result<_, Error> {
+                        Ok([acc, att.to_cesr()?].concat())
+                    })?;
+                (PayloadType::ML, packed_material.len() / 4, packed_material)
+            }
             Attachment::Frame(att) => {
                 let packed_attachments = att
                     .iter()
-                    .fold("".to_string(), |acc, att| [acc, att.to_cesr()].concat());
+                    .map(Attachment::to_cesr)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .concat();
+                // Frame size is counted in quadlets
                 (
                     PayloadType::MV,
-                    packed_attachments.len(),
+                    packed_attachments.len() / 4,
                     packed_attachments,
                 )
             }
         };
-        [
+        Ok([
             payload_type.adjust_with_num(att_len as u16),
             serialized_attachment,
         ]
-        .join("")
+        .join(""))
+    }
+
+    /// Attachment in binary domain of CESR.
+    pub fn to_cesr_binary(&self) -> Result<Vec<u8>, Error> {
+        // Text domain groups are made of whole quadlets, so they are
+        // always decodable.
+        Ok(base64::decode_config(self.to_cesr()?, base64::URL_SAFE).unwrap_or_default())
     }
 
     fn pack_sn(sn: u64) -> String {
@@ -115,7 +175,7 @@ impl Attachment {
         // length. Master code size is expected padding size.
         let missing_zeros =
             payload_type.size() / 4 * 3 - payload_type.master_code_size(false) - sn_raw.len();
-        let sn_vec: Vec<u8> = std::iter::repeat(0)
+        let sn_vec: Vec<u8> = core::iter::repeat(0)
             .take(missing_zeros)
             .chain(sn_raw)
             .collect();
@@ -125,6 +185,38 @@ impl Attachment {
         ]
         .join("")
     }
+
+    // Datetime is encoded as in keripy, with characters out of base64
+    // alphabet replaced.
+    fn pack_datetime(dt: &DateTime<FixedOffset>) -> String {
+        let dts = dt
+            .format("%Y-%m-%dT%H:%M:%S%.6f%:z")
+            .to_string()
+            .replace(':', "c")
+            .replace('.', "d")
+            .replace('+', "p");
+        [PayloadType::IAAG.to_string(), dts].join("")
+    }
+
+    // Path is base64 string, so it is padded with leading "A"
+    // characters to full quadlets. Code depends on padding size.
+    fn pack_path(path: &str) -> String {
+        let padding = (4 - path.len() % 4) % 4;
+        let code = match padding {
+            0 | 1 => "4A",
+            2 => "5A",
+            _ => "6A",
+        };
+        let quadlets = num_to_b64(((path.len() + padding) / 4) as u16);
+        [
+            code,
+            &"A".repeat(2 - quadlets.len()),
+            &quadlets,
+            &"A".repeat(padding),
+            path,
+        ]
+        .join("")
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -135,23 +227,29 @@ pub struct SignedEventData {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum EventType {
-    KeyEvent(EventMessage<KeyEvent>),
+    /// Key event together with its bytes as received, if parsed.
+    KeyEvent(EventMessage<KeyEvent>, Option<Vec<u8>>),
     Receipt(EventMessage<Receipt>),
     #[cfg(feature = "query")]
     Qry(EventMessage<QueryEvent>),
     #[cfg(feature = "query")]
     Rpy(EventMessage<ReplyEvent>),
+    #[cfg(feature = "query")]
+    Exn(EventMessage<ExchangeEvent>),
 }
 
 impl EventType {
     pub fn serialize(&self) -> Result<Vec<u8>, Error> {
         match self {
-            EventType::KeyEvent(event) => event.serialize(),
+            EventType::KeyEvent(_, Some(raw)) => Ok(raw.clone()),
+            EventType::KeyEvent(event, None) => event.serialize(),
             EventType::Receipt(rcp) => rcp.serialize(),
             #[cfg(feature = "query")]
             EventType::Qry(qry) => qry.serialize(),
             #[cfg(feature = "query")]
             EventType::Rpy(rpy) => rpy.serialize(),
+            #[cfg(feature = "query")]
+            EventType::Exn(exn) => exn.serialize(),
         }
     }
 }
@@ -161,9 +259,21 @@ impl SignedEventData {
         let attachments = self
             .attachments
             .iter()
-            .fold(String::default(), |acc, att| [acc, att.to_cesr()].concat())
-            .as_bytes()
-            .to_vec();
+            .map(Attachment::to_cesr)
+            .collect::<Result<Vec<_>, _>>()?
+            .concat()
+            .into_bytes();
+        Ok([self.deserialized_event.serialize()?, attachments].concat())
+    }
+
+    /// Serializes message with attachments in binary domain of CESR.
+    pub fn to_cesr_binary(&self) -> Result<Vec<u8>, Error> {
+        let attachments = self
+            .attachments
+            .iter()
+            .map(Attachment::to_cesr_binary)
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
         Ok([self.deserialized_event.serialize()?, attachments].concat())
     }
 }
@@ -180,7 +290,7 @@ impl From<&SignedEventMessage> for SignedEventData {
         };
 
         SignedEventData {
-            deserialized_event: EventType::KeyEvent(ev.event_message.clone()),
+            deserialized_event: EventType::KeyEvent(ev.event_message.clone(), ev.raw.clone()),
             attachments,
         }
     }
@@ -210,6 +320,16 @@ impl From<SignedTransferableReceipt> for SignedEventData {
     }
 }
 
+impl From<SignedWitnessReceipt> for SignedEventData {
+    fn from(rcp: SignedWitnessReceipt) -> SignedEventData {
+        let attachments = [Attachment::AttachedWitnessSignatures(rcp.signatures)].into();
+        SignedEventData {
+            deserialized_event: EventType::Receipt(rcp.body),
+            attachments,
+        }
+    }
+}
+
 #[cfg(feature = "query")]
 impl From<SignedReply> for SignedEventData {
     fn from(ev: SignedReply) -> Self {
@@ -228,17 +348,66 @@ impl From<SignedReply> for SignedEventData {
     }
 }
 
+#[cfg(feature = "query")]
+impl From<SignedQuery> for SignedEventData {
+    fn from(ev: SignedQuery) -> Self {
+        let attachments = vec![Attachment::LastEstSignaturesGroups(vec![(
+            ev.signer,
+            ev.signatures,
+        )])];
+
+        SignedEventData {
+            deserialized_event: EventType::Qry(ev.envelope),
+            attachments,
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl From<SignedExchange> for SignedEventData {
+    fn from(ev: SignedExchange) -> Self {
+        let attachments = vec![Attachment::LastEstSignaturesGroups(vec![(
+            ev.signer,
+            ev.signatures,
+        )])];
+
+        SignedEventData {
+            deserialized_event: EventType::Exn(ev.exchange),
+            attachments,
+        }
+    }
+}
+
+impl From<Message> for SignedEventData {
+    fn from(message: Message) -> Self {
+        match message {
+            Message::Event(ev) => SignedEventData::from(&ev),
+            Message::NontransferableRct(rct) => rct.into(),
+            Message::TransferableRct(rct) => rct.into(),
+            Message::WitnessRct(rct) => rct.into(),
+            #[cfg(feature = "query")]
+            Message::KeyStateNotice(rpy) => rpy.into(),
+            #[cfg(feature = "query")]
+            Message::Query(qry) => qry.into(),
+            #[cfg(feature = "query")]
+            Message::Exchange(exn) => exn.into(),
+        }
+    }
+}
+
 impl TryFrom<SignedEventData> for Message {
     type Error = Error;
 
     fn try_from(value: SignedEventData) -> Result<Self, Self::Error> {
         match value.deserialized_event {
-            EventType::KeyEvent(ev) => signed_key_event(ev, value.attachments),
+            EventType::KeyEvent(ev, raw) => signed_key_event(ev, raw, value.attachments),
             EventType::Receipt(rct) => signed_receipt(rct, value.attachments),
             #[cfg(feature = "query")]
             EventType::Qry(qry) => signed_query(qry, value.attachments),
             #[cfg(feature = "query")]
             EventType::Rpy(rpy) => signed_reply(rpy, value.attachments),
+            #[cfg(feature = "query")]
+            EventType::Exn(exn) => signed_exchange(exn, value.attachments),
         }
     }
 }
@@ -282,8 +451,6 @@ fn signed_query(
     qry: EventMessage<QueryEvent>,
     mut attachments: Vec<Attachment>,
 ) -> Result<Message, Error> {
-    use crate::query::query::SignedQuery;
-
     match attachments
         .pop()
         .ok_or_else(|| Error::SemanticError("Missing attachment".into()))?
@@ -306,62 +473,87 @@ fn signed_query(
     }
 }
 
-fn signed_key_event(
-    event_message: EventMessage<KeyEvent>,
+#[cfg(feature = "query")]
+fn signed_exchange(
+    exn: EventMessage<ExchangeEvent>,
     mut attachments: Vec<Attachment>,
 ) -> Result<Message, Error> {
-    match event_message.event.get_event_data() {
-        EventData::Dip(_) | EventData::Drt(_) => {
-            let (att1, att2) = (
-                attachments
-                    .pop()
-                    .ok_or_else(|| Error::SemanticError("Missing attachment".into()))?,
-                attachments
-                    .pop()
-                    .ok_or_else(|| Error::SemanticError("Missing attachment".into()))?,
-            );
-
-            let (seals, sigs) = match (att1, att2) {
-                (Attachment::SealSourceCouplets(seals), Attachment::AttachedSignatures(sigs)) => {
-                    Ok((seals, sigs))
-                }
-                (Attachment::AttachedSignatures(sigs), Attachment::SealSourceCouplets(seals)) => {
-                    Ok((seals, sigs))
-                }
-                _ => {
-                    // Improper attachment type
-                    Err(Error::SemanticError("Improper attachment type".into()))
-                }
-            }?;
-            let delegator_seal = match seals.len() {
-                0 => Err(Error::SemanticError("Missing delegator seal".into())),
-                1 => Ok(seals.first().cloned()),
-                _ => Err(Error::SemanticError("Too many seals".into())),
-            };
-
-            Ok(Message::Event(SignedEventMessage::new(
-                &event_message,
-                sigs,
-                delegator_seal?,
+    match attachments
+        .pop()
+        .ok_or_else(|| Error::SemanticError("Missing attachment".into()))?
+    {
+        Attachment::LastEstSignaturesGroups(groups) => {
+            let (signer, signatures) = groups
+                .first()
+                .ok_or_else(|| Error::SemanticError("Missing signatures".into()))?
+                .to_owned();
+            Ok(Message::Exchange(SignedExchange::new(
+                exn, signer, signatures,
             )))
         }
+        Attachment::Frame(atts) => signed_exchange(exn, atts),
         _ => {
-            let sigs = attachments
-                .first()
-                .cloned()
-                .ok_or_else(|| Error::SemanticError("Missing attachment".into()))?;
-            if let Attachment::AttachedSignatures(sigs) = sigs {
-                Ok(Message::Event(SignedEventMessage::new(
-                    &event_message,
-                    sigs.to_vec(),
-                    None,
-                )))
-            } else {
+            // Improper payload type
+            Err(Error::SemanticError(
+                "Improper attachments for exchange message".into(),
+            ))
+        }
+    }
+}
+
+fn signed_key_event(
+    event_message: EventMessage<KeyEvent>,
+    raw: Option<Vec<u8>>,
+    attachments: Vec<Attachment>,
+) -> Result<Message, Error> {
+    if attachments.is_empty() {
+        return Err(Error::SemanticError("Missing attachment".into()));
+    }
+    let (mut sigs, mut seals) = (vec![], vec![]);
+    for attachment in flatten(attachments) {
+        match attachment {
+            Attachment::AttachedSignatures(mut att_sigs) => sigs.append(&mut att_sigs),
+            Attachment::SealSourceCouplets(mut att_seals) => seals.append(&mut att_seals),
+            // Replayed events come with witness signatures, receipts and
+            // first seen datetimes, which aren't part of the event itself.
+            Attachment::AttachedWitnessSignatures(_)
+            | Attachment::ReceiptCouplets(_)
+            | Attachment::FirstSeenReplayCouplets(_) => (),
+            _ => {
                 // Improper attachment type
-                Err(Error::SemanticError("Improper attachment type".into()))
+                return Err(Error::SemanticError("Improper attachment type".into()));
             }
         }
     }
+    if sigs.is_empty() {
+        return Err(Error::SemanticError("Missing signatures".into()));
+    }
+    let delegator_seal = match event_message.event.get_event_data() {
+        // Delegated event may be not anchored yet, eg. delegation request
+        EventData::Dip(_) | EventData::Drt(_) => match seals.len() {
+            0 => Ok(None),
+            1 => Ok(seals.pop()),
+            _ => Err(Error::SemanticError("Too many seals".into())),
+        },
+        _ if seals.is_empty() => Ok(None),
+        _ => Err(Error::SemanticError("Improper attachment type".into())),
+    }?;
+
+    Ok(Message::Event(SignedEventMessage {
+        raw,
+        ..SignedEventMessage::new(&event_message, sigs, delegator_seal)
+    }))
+}
+
+/// Unpacks attachments grouped in frames.
+fn flatten(attachments: Vec<Attachment>) -> Vec<Attachment> {
+    attachments
+        .into_iter()
+        .flat_map(|attachment| match attachment {
+            Attachment::Frame(atts) => flatten(atts),
+            attachment => vec![attachment],
+        })
+        .collect()
 }
 
 fn signed_receipt(
@@ -392,6 +584,9 @@ fn signed_receipt(
                 sigs,
             )))
         }
+        Attachment::AttachedWitnessSignatures(sigs) => Ok(Message::WitnessRct(
+            SignedWitnessReceipt::new(&event_message, sigs),
+        )),
         Attachment::Frame(atts) => signed_receipt(event_message, atts),
         _ => {
             // Improper payload type
@@ -426,6 +621,21 @@ fn test_stream1() {
     }
 }
 
+#[test]
+fn test_replayed_stream() {
+    use crate::event_parsing;
+    // Event replayed with witness signatures and first seen couple, as in
+    // keripy clone of KEL.
+    let stream = br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-VA--AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw-BABAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA-EAB0AAAAAAAAAAAAAAAAAAAAAAA1AAG2020-08-22T17c50c09d988921p00c00"#;
+
+    let (rest, parsed) = event_parsing::message::signed_message(stream).unwrap();
+    assert!(rest.is_empty());
+    match Message::try_from(parsed).unwrap() {
+        Message::Event(signed_event) => assert_eq!(signed_event.signatures.len(), 1),
+        _ => assert!(false),
+    }
+}
+
 #[test]
 fn test_stream2() {
     use crate::event_parsing;
@@ -470,9 +680,14 @@ fn test_deserialize_signed_receipt() {
     assert!(msg.is_ok());
     assert!(matches!(msg, Ok(Message::NontransferableRct(_))));
 
-    // Nontrans receipt with alternative attachment with -B payload type. Not implemented yet.
+    // Nontrans receipt with alternative attachment with -B payload type.
     // takien from keripy/tests/core/test_witness.py::test_indexed_witness_reply
-    // let wintess_receipts = r#"{"v":"KERI10JSON000091_","t":"rct","d":"EHz9RXAr9JiJn-3wkBvsUo1Qq3hvMQPaITxzcfJND8NM","i":"EHz9RXAr9JiJn-3wkBvsUo1Qq3hvMQPaITxzcfJND8NM","s":"0"}-BADAAdgQkf11JTyF2WVA1Vji1ZhXD8di4AJsfro-sN_jURM1SUioeOleik7w8lkDldKtg0-Nr1X32V9Q8tk8RvBGxDgABZmkRun-qNliRA8WR2fIUnVeB8eFLF7aLFtn2hb31iW7wYSYafR0kT3fV_r1wNNdjm9dkBw-_2xsxThTGfO5UAwACRGJiRPFe4ClvpqZL3LHcEAeT396WVrYV10EaTdt0trINT8rPbz96deSFT32z3myNPVwLlNcq4FzIaQCooM2HDQ"#;
-    // let msg = signed_message(witness_receipts.as_bytes());
-    // assert!(msg.is_ok());
+    let witness_receipts = br#"{"v":"KERI10JSON000091_","t":"rct","d":"EHz9RXAr9JiJn-3wkBvsUo1Qq3hvMQPaITxzcfJND8NM","i":"EHz9RXAr9JiJn-3wkBvsUo1Qq3hvMQPaITxzcfJND8NM","s":"0"}-BADAAdgQkf11JTyF2WVA1Vji1ZhXD8di4AJsfro-sN_jURM1SUioeOleik7w8lkDldKtg0-Nr1X32V9Q8tk8RvBGxDgABZmkRun-qNliRA8WR2fIUnVeB8eFLF7aLFtn2hb31iW7wYSYafR0kT3fV_r1wNNdjm9dkBw-_2xsxThTGfO5UAwACRGJiRPFe4ClvpqZL3LHcEAeT396WVrYV10EaTdt0trINT8rPbz96deSFT32z3myNPVwLlNcq4FzIaQCooM2HDQ"#;
+    let parsed_witness_receipts = signed_message(witness_receipts).unwrap().1;
+    let msg = Message::try_from(parsed_witness_receipts.clone());
+    assert!(matches!(msg, Ok(Message::WitnessRct(ref rct)) if rct.signatures.len() == 3));
+    assert_eq!(
+        parsed_witness_receipts.to_cesr().unwrap(),
+        witness_receipts.to_vec()
+    );
 }
@@ -1,6 +1,7 @@
 use crate::{derivation::attached_signature_code::num_to_b64, error::Error};
+use alloc::string::{String, ToString};
+use core::{convert::TryFrom, fmt::Display};
 use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, fmt::Display};
 
 // Payload sizes pre unit
 // according to:
@@ -62,6 +63,7 @@ pub enum PayloadType {
     MC,
     #[serde(rename = "-D")]
     MD,
+    /// Count of attached first seen replay couples fn+dt
     #[serde(rename = "-E")]
     ME,
     /// Count of attached qualified Base64 transferable indexed sig groups
@@ -74,6 +76,9 @@ pub enum PayloadType {
     /// Count of composed group: IdentifierPrefix + Controller Indexed Signatures
     #[serde(rename = "-H")]
     MH,
+    /// Count of quadlets of path and attachments related to it
+    #[serde(rename = "-L")]
+    ML,
     #[serde(rename = "-U")]
     MU,
     #[serde(rename = "-V")]
@@ -139,6 +144,8 @@ impl PayloadType {
             | Self::ME
             | Self::MF
             | Self::MG
+            | Self::MH
+            | Self::ML
             | Self::MU
             | Self::MV
             | Self::MW
@@ -180,6 +187,8 @@ impl PayloadType {
             | Self::ME
             | Self::MF
             | Self::MG
+            | Self::MH
+            | Self::ML
             | Self::MU
             | Self::MV
             | Self::MW
@@ -247,6 +256,7 @@ impl TryFrom<&str> for PayloadType {
             "-F" => Ok(Self::MF),
             "-G" => Ok(Self::MG),
             "-H" => Ok(Self::MH),
+            "-L" => Ok(Self::ML),
             "-U" => Ok(Self::MU),
             "-V" => Ok(Self::MV),
             "-W" => Ok(Self::MW),
@@ -259,7 +269,7 @@ impl TryFrom<&str> for PayloadType {
 }
 
 impl Display for PayloadType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::A => f.write_str("A"),
             Self::B => f.write_str("B"),
@@ -297,6 +307,7 @@ impl Display for PayloadType {
             Self::MF => f.write_str("-F"),
             Self::MG => f.write_str("-G"),
             Self::MH => f.write_str("-H"),
+            Self::ML => f.write_str("-L"),
             Self::MU => f.write_str("-U"),
             Self::MV => f.write_str("-V"),
             Self::MW => f.write_str("-W"),
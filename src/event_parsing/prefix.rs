@@ -10,7 +10,9 @@ use crate::{
         SelfSigningPrefix,
     },
 };
+use alloc::string::String;
 use base64::URL_SAFE;
+use chrono::{DateTime, FixedOffset};
 use nom::{bytes::complete::take, error::ErrorKind};
 
 // TODO this could be a lot nicer, but is currently written to be careful and "easy" to follow
@@ -19,19 +21,24 @@ pub fn attached_signature(s: &[u8]) -> nom::IResult<&[u8], AttachedSignaturePref
 
     const a: &[u8] = "A".as_bytes();
     const b: &[u8] = "B".as_bytes();
+    const c: &[u8] = "C".as_bytes();
     const z: &[u8] = "0".as_bytes();
+    const two: &[u8] = "2".as_bytes();
+    const three: &[u8] = "3".as_bytes();
+    #[cfg(feature = "pq")]
+    const sphincs: &[u8] = "Z".as_bytes();
 
     match type_c {
         a => {
             let (maybe_sig, index_c) = take(1u8)(more)?;
 
-            let index =
-                b64_to_num(index_c).map_err(|_| nom::Err::Error((index_c, ErrorKind::IsNot)))?;
+            let index = b64_to_num(index_c)
+                .map_err(|_| nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot)))?;
 
             let (rest, sig_s) = take(86u8)(maybe_sig)?;
 
             let sig = base64::decode_config(sig_s, base64::URL_SAFE)
-                .map_err(|_| nom::Err::Error((index_c, ErrorKind::IsNot)))?;
+                .map_err(|_| nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot)))?;
 
             Ok((
                 rest,
@@ -41,13 +48,13 @@ pub fn attached_signature(s: &[u8]) -> nom::IResult<&[u8], AttachedSignaturePref
         b => {
             let (maybe_sig, index_c) = take(1u8)(more)?;
 
-            let index =
-                b64_to_num(index_c).map_err(|_| nom::Err::Error((index_c, ErrorKind::IsNot)))?;
+            let index = b64_to_num(index_c)
+                .map_err(|_| nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot)))?;
 
             let (rest, sig_s) = take(86u8)(maybe_sig)?;
 
             let sig = base64::decode_config(sig_s, base64::URL_SAFE)
-                .map_err(|_| nom::Err::Error((index_c, ErrorKind::IsNot)))?;
+                .map_err(|_| nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot)))?;
 
             Ok((
                 rest,
@@ -60,23 +67,82 @@ pub fn attached_signature(s: &[u8]) -> nom::IResult<&[u8], AttachedSignaturePref
                 a => {
                     let (maybe_sig, index_c) = take(2u8)(maybe_count)?;
 
-                    let index = b64_to_num(index_c)
-                        .map_err(|_| nom::Err::Error((index_c, ErrorKind::IsNot)))?;
+                    let index = b64_to_num(index_c).map_err(|_| {
+                        nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot))
+                    })?;
 
                     let (rest, sig_s) = take(152u8)(maybe_sig)?;
 
-                    let sig = base64::decode_config(sig_s, base64::URL_SAFE)
-                        .map_err(|_| nom::Err::Error((index_c, ErrorKind::IsNot)))?;
+                    let sig = base64::decode_config(sig_s, base64::URL_SAFE).map_err(|_| {
+                        nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot))
+                    })?;
 
                     Ok((
                         rest,
                         AttachedSignaturePrefix::new(SelfSigning::Ed448, sig, index),
                     ))
                 }
-                _ => Err(nom::Err::Error((type_c_2, ErrorKind::IsNot))),
+                #[cfg(feature = "pq")]
+                sphincs => {
+                    let (maybe_sig, index_c) = take(2u8)(maybe_count)?;
+
+                    let index = b64_to_num(index_c).map_err(|_| {
+                        nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot))
+                    })?;
+
+                    let (rest, sig_s) =
+                        take(SelfSigning::SphincsSha2128s.derivative_b64_len())(maybe_sig)?;
+
+                    let sig = base64::decode_config(sig_s, base64::URL_SAFE)
+                        .ok()
+                        .and_then(|raw| SelfSigning::SphincsSha2128s.strip_lead(raw).ok())
+                        .ok_or_else(|| {
+                            nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot))
+                        })?;
+
+                    Ok((
+                        rest,
+                        AttachedSignaturePrefix::new(SelfSigning::SphincsSha2128s, sig, index),
+                    ))
+                }
+                _ => Err(nom::Err::Error(nom::error::Error::new(
+                    type_c_2,
+                    ErrorKind::IsNot,
+                ))),
             }
         }
-        _ => Err(nom::Err::Error((type_c, ErrorKind::IsNot))),
+        // Big index codes, where index is followed by other index of the
+        // same length, which is skipped.
+        two | three => {
+            let (maybe_index, type_c_2) = take(1u8)(more)?;
+            let (code, index_len) = match (type_c, type_c_2) {
+                (two, a) => (SelfSigning::Ed25519Sha512, 2u8),
+                (two, c) => (SelfSigning::ECDSAsecp256k1Sha256, 2u8),
+                (three, a) => (SelfSigning::Ed448, 3u8),
+                _ => {
+                    return Err(nom::Err::Error(nom::error::Error::new(
+                        type_c_2,
+                        ErrorKind::IsNot,
+                    )))
+                }
+            };
+            let (maybe_ondex, index_c) = take(index_len)(maybe_index)?;
+
+            let index = b64_to_num(index_c)
+                .map_err(|_| nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot)))?;
+
+            let (maybe_sig, _ondex) = take(index_len)(maybe_ondex)?;
+            let (rest, sig_s) = take(code.derivative_b64_len())(maybe_sig)?;
+
+            let sig = base64::decode_config(sig_s, base64::URL_SAFE)
+                .map_err(|_| nom::Err::Error(nom::error::Error::new(index_c, ErrorKind::IsNot)))?;
+
+            Ok((rest, AttachedSignaturePrefix::new(code, sig, index)))
+        }
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            type_c,
+            ErrorKind::IsNot,
+        ))),
     }
 }
 
@@ -91,14 +157,14 @@ pub fn basic_prefix(s: &[u8]) -> nom::IResult<&[u8], BasicPrefix> {
     })(s)?;
 
     let code: Basic = String::from_utf8(code_str.to_vec())
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?
         .parse()
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?;
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?;
 
     let (extra, b) = take(code.derivative_b64_len())(rest)?;
     let pk = PublicKey::new(
         base64::decode_config(b.to_vec(), URL_SAFE)
-            .map_err(|_| nom::Err::Error((s, ErrorKind::IsNot)))?,
+            .map_err(|_| nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)))?,
     );
     Ok((extra, code.derive(pk)))
 }
@@ -113,38 +179,41 @@ pub fn self_addressing_prefix(s: &[u8]) -> nom::IResult<&[u8], SelfAddressingPre
     })(s)?;
 
     let code: SelfAddressing = String::from_utf8(code_str.to_vec())
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?
         .parse()
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?;
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?;
 
     let (extra, b) = take(code.derivative_b64_len())(rest)?;
 
-    let pref: SelfAddressingPrefix = std::str::from_utf8(&[code_str, b].concat())
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?
+    let pref: SelfAddressingPrefix = core::str::from_utf8(&[code_str, b].concat())
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?
         .parse()
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?;
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?;
     Ok((extra, pref))
 }
 
 pub fn self_signing_prefix(s: &[u8]) -> nom::IResult<&[u8], SelfSigningPrefix> {
     const EXT: &[u8] = "1".as_bytes();
+    const LEAD_1: &[u8] = "5".as_bytes();
 
     let (_, type_c) = take(1u8)(s)?;
 
     let (rest, code_str) = take(match type_c {
-        EXT => 4u8,
+        EXT | LEAD_1 => 4u8,
         _ => 2u8,
     })(s)?;
 
     let code: SelfSigning = String::from_utf8(code_str.to_vec())
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?
         .parse()
-        .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?;
+        .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?;
 
     let (extra, b) = take(code.derivative_b64_len())(rest)?;
 
-    let sig =
-        base64::decode_config(b, URL_SAFE).map_err(|_| nom::Err::Error((s, ErrorKind::IsNot)))?;
+    let sig = base64::decode_config(b, URL_SAFE)
+        .ok()
+        .and_then(|raw| code.strip_lead(raw).ok())
+        .ok_or_else(|| nom::Err::Error(nom::error::Error::new(s, ErrorKind::IsNot)))?;
     Ok((extra, code.derive(sig)))
 }
 
@@ -159,7 +228,7 @@ pub fn attached_sn(s: &[u8]) -> nom::IResult<&[u8], u64> {
 
             let sn = {
                 let b64decode = base64::decode_config(parsed_sn, URL_SAFE)
-                    .map_err(|_| nom::Err::Failure((s, ErrorKind::IsNot)))?;
+                    .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?;
                 let mut sn_array: [u8; 8] = [0; 8];
                 sn_array.copy_from_slice(&b64decode[8..]);
                 u64::from_be_bytes(sn_array)
@@ -167,7 +236,34 @@ pub fn attached_sn(s: &[u8]) -> nom::IResult<&[u8], u64> {
 
             Ok((rest, sn))
         }
-        _ => Err(nom::Err::Error((type_c, ErrorKind::IsNot))),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            type_c,
+            ErrorKind::IsNot,
+        ))),
+    }
+}
+
+/// extracts first seen datetime, encoded as in keripy
+pub fn attached_datetime(s: &[u8]) -> nom::IResult<&[u8], DateTime<FixedOffset>> {
+    let (more, type_c) = take(4u8)(s)?;
+
+    match type_c {
+        b"1AAG" => {
+            let (rest, parsed_dt) = take(32u8)(more)?;
+            let dts = core::str::from_utf8(parsed_dt)
+                .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?
+                .replace('c', ":")
+                .replace('d', ".")
+                .replace('p', "+");
+            let dt = DateTime::parse_from_rfc3339(&dts)
+                .map_err(|_| nom::Err::Failure(nom::error::Error::new(s, ErrorKind::IsNot)))?;
+
+            Ok((rest, dt))
+        }
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            type_c,
+            ErrorKind::IsNot,
+        ))),
     }
 }
 
@@ -185,6 +281,8 @@ pub fn prefix(s: &[u8]) -> nom::IResult<&[u8], IdentifierPrefix> {
 
 #[test]
 fn test() {
+    use crate::prefix::Prefix;
+
     assert_eq!(
         attached_signature("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".as_bytes()),
         Ok(("".as_bytes(), AttachedSignaturePrefix::new(SelfSigning::Ed25519Sha512, vec![0u8; 64], 0)))
@@ -194,6 +292,15 @@ fn test() {
         attached_signature("BCAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".as_bytes()),
         Ok(("AA".as_bytes(), AttachedSignaturePrefix::new(SelfSigning::ECDSAsecp256k1Sha256, vec![0u8; 64], 2)))
     );
+
+    // Big index code.
+    let big = AttachedSignaturePrefix::new(SelfSigning::Ed25519Sha512, vec![0u8; 64], 70);
+    let big_str = [big.to_str(), "AA".into()].concat();
+    assert!(big_str.starts_with("2ABGBG"));
+    assert_eq!(
+        attached_signature(big_str.as_bytes()),
+        Ok(("AA".as_bytes(), big))
+    );
 }
 
 #[test]
@@ -248,3 +355,10 @@ fn test_sn_parse() {
     let sn = attached_sn("0AAAAAAAAAAAAAAAAAAAAAAw".as_bytes()).unwrap();
     assert_eq!(sn, ("".as_bytes(), 3));
 }
+
+#[test]
+fn test_datetime_parse() {
+    let (rest, dt) = attached_datetime("1AAG2020-08-22T17c50c09d988921p00c00".as_bytes()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(dt.to_rfc3339(), "2020-08-22T17:50:09.988921+00:00");
+}
@@ -0,0 +1,166 @@
+//! Stream parser
+//!
+//! Incremental parser of CESR streams which arrive in parts, eg. from
+//! network. Fed bytes are kept in one buffer and messages are parsed
+//! in place, so nothing is copied before deserialization. Incomplete
+//! tail waits in the buffer for the rest of its bytes.
+
+use crate::{
+    error::Error,
+    event_parsing::{
+        cold_start::{cold_start, ColdCode},
+        message::{attachments, event_message, version_string},
+        SignedEventData,
+    },
+};
+use alloc::{string::ToString, vec::Vec};
+
+/// Stream Parser
+///
+/// Yields messages with attachments as soon as they are complete.
+/// Message attachments are complete when next message starts or when
+/// fed bytes end with the whole attachment group.
+#[derive(Default)]
+pub struct StreamParser {
+    buffer: Vec<u8>,
+    // Position of first not parsed byte
+    position: usize,
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends bytes to the stream, dropping already parsed part.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.drain(..self.position);
+        self.position = 0;
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Bytes waiting to be parsed.
+    pub fn pending(&self) -> &[u8] {
+        &self.buffer[self.position..]
+    }
+
+    /// Returns next complete message, or None if more bytes are needed.
+    /// On error not parsed part of the stream is dropped, as it can't
+    /// be told where the next message starts.
+    pub fn next_message(&mut self) -> Option<Result<SignedEventData, Error>> {
+        match parse_message(self.pending()) {
+            Ok(Some((parsed, message))) => {
+                self.position += parsed;
+                Some(Ok(message))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.position = self.buffer.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Iterator for StreamParser {
+    type Item = Result<SignedEventData, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message()
+    }
+}
+
+/// Parses message with attachments from the beginning of data. Returns
+/// None if message is not complete yet, otherwise number of parsed bytes
+/// and the message.
+pub fn parse_message(data: &[u8]) -> Result<Option<(usize, SignedEventData)>, Error> {
+    match cold_start(data) {
+        None => return Ok(None),
+        Some(ColdCode::JSON)
+        | Some(ColdCode::CBOR)
+        | Some(ColdCode::MGPK1)
+        | Some(ColdCode::MGPK2) => (),
+        Some(_) => return Err(Error::DeserializeError("Expected message".into())),
+    };
+    let size = match declared_size(data)? {
+        Some(size) if size <= data.len() => size,
+        _ => return Ok(None),
+    };
+    let (_, event) =
+        event_message(&data[..size]).map_err(|e| Error::DeserializeError(e.to_string()))?;
+    let (rest, attachments) =
+        attachments(&data[size..]).map_err(|e| Error::DeserializeError(e.to_string()))?;
+    match cold_start(rest) {
+        // Attachments may still be on their way.
+        None if attachments.is_empty() => return Ok(None),
+        None => (),
+        // Next message starts, so attachments are done.
+        Some(ColdCode::JSON)
+        | Some(ColdCode::CBOR)
+        | Some(ColdCode::MGPK1)
+        | Some(ColdCode::MGPK2) => (),
+        // Attachment group is not complete yet.
+        Some(ColdCode::CountCode) | Some(ColdCode::BinaryCountCode) => return Ok(None),
+        Some(_) => return Err(Error::DeserializeError("Unexpected stream content".into())),
+    };
+    Ok(Some((
+        data.len() - rest.len(),
+        SignedEventData {
+            deserialized_event: event,
+            attachments,
+        },
+    )))
+}
+
+/// Returns size of message declared in its version string, or None if
+/// not enough bytes arrived to read it. Messages of unsupported
+/// protocol version are rejected before they are deserialized.
+fn declared_size(data: &[u8]) -> Result<Option<usize>, Error> {
+    match version_string(data)? {
+        Some(version) => {
+            version.check_version()?;
+            Ok(Some(version.size))
+        }
+        None => Ok(None),
+    }
+}
+
+#[test]
+fn test_stream_parser() -> Result<(), Error> {
+    use crate::event_parsing::message::signed_event_stream;
+
+    // Taken from keripy/tests/core/test_kevery.py::test kevery
+    let kerl_str= br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw{"v":"KERI10JSON000155_","t":"rot","d":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"1","p":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","kt":"1","k":["DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI"],"n":"E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI","bt":"0","br":[],"ba":[],"a":[]}-AABAAwoiqt07w2UInzzo2DmtwkBfqX1-tTO4cYk_7YdlbJ95qA7PO5sEUkER8fZySQMNCVh64ruAh1yoew3TikwVGAQ{"v":"KERI10JSON000155_","t":"rot","d":"EmtXXRjyz6IdeX4201BgXKRDBm74gGqJF2r2umMMAL6I","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"2","p":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","kt":"1","k":["DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8"],"n":"EKrLE2h2nh3ClyJNEjKaikHWT7G-ngimNpK-QgVQv9As","bt":"0","br":[],"ba":[],"a":[]}-AABAAW_RsDfAcHkknyzh9oeliH90KGPJEI8AP3rJPyuTnpVg8yOVtSIp_JFlyRwjV5SEQOqddAcRV6JtaQO8oXtWFCQ{"v":"KERI10JSON0000cb_","t":"ixn","d":"EY7E4RJXPe7FF1zQPbpSMIY-TYz9eAmNIhuprPYqTQ5o","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"3","p":"EmtXXRjyz6IdeX4201BgXKRDBm74gGqJF2r2umMMAL6I","a":[]}-AABAAlB0Ui5NHJpcifXUB6bAutmpZkhSgwxyI5jEZ2JGVBgTI02sC0Ugbq3q0EpOae7ruXW-eabUz2s0FAs26jGwVBg{"v":"KERI10JSON0000cb_","t":"ixn","d":"ENVzbZieVIjYLYkPWQy0gfua11KqdRG-oku5Ut8Dl6hU","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"4","p":"EY7E4RJXPe7FF1zQPbpSMIY-TYz9eAmNIhuprPYqTQ5o","a":[]}-AABAAWITFg460TXvYvxxzN62vpqpLs-vGgeGAbd-onY3DYxd5e3AljHh85pTum4Ha48F5dui9IVYqYvuYJCG8p8KvDw{"v":"KERI10JSON000155_","t":"rot","d":"E6wrLhilpPo4ePq7m7ZccEcKjwPD2q9mqzLUb_aO2Hi0","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"5","p":"ENVzbZieVIjYLYkPWQy0gfua11KqdRG-oku5Ut8Dl6hU","kt":"1","k":["DKPE5eeJRzkRTMOoRGVd2m18o8fLqM2j9kaxLhV3x8AQ"],"n":"EhVTfJFfl6L0Z0432mDUxeaqB_hlWPJ2qUuzG95gEyJU","bt":"0","br":[],"ba":[],"a":[]}-AABAAnqz-vnMx1cqe_SkcIrlx092UhbYzvvkHXjtxfuNDDcqnVtH11_8ZPaWomn3n963_bFTjjRhJaAH1SK8LU7s1DA{"v":"KERI10JSON0000cb_","t":"ixn","d":"Ek9gvRbkCt-wlgQBoV1PGm2iI__gaPURtJ3YrNFsXLzE","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"6","p":"E6wrLhilpPo4ePq7m7ZccEcKjwPD2q9mqzLUb_aO2Hi0","a":[]}-AABAAwGGWMNDpu8t4NuF_3M0jnkn3P063oUHmluwRwsyCg5tIvu-BfwIJRruAsCKry4LaI84dJAfAT5KJnG8xz9lJCw"#;
+    let (_, expected) = signed_event_stream(kerl_str).unwrap();
+
+    // Stream arrives in small chunks, cut in the middle of messages and
+    // attachments.
+    let mut parser = StreamParser::new();
+    let mut messages = vec![];
+    for chunk in kerl_str.chunks(50) {
+        parser.feed(chunk);
+        for message in &mut parser {
+            messages.push(message?);
+        }
+    }
+    assert_eq!(messages, expected);
+    assert!(parser.pending().is_empty());
+
+    // Garbage is reported and dropped.
+    parser.feed(b"garbage which is not a KERI message");
+    assert!(matches!(parser.next(), Some(Err(_))));
+    assert!(parser.pending().is_empty());
+
+    // Message of unsupported version is rejected with dedicated error.
+    let future =
+        String::from_utf8(kerl_str.to_vec())
+            .unwrap()
+            .replacen("KERI10JSON", "KERI20JSON", 1);
+    parser.feed(future.as_bytes());
+    assert!(matches!(
+        parser.next(),
+        Some(Err(Error::UnsupportedVersion { major: 2, minor: 0 }))
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+//! C FFI
+//!
+//! `extern "C"` layer over the controller, for embedding keriox in
+//! mobile wallets (eg. through Kotlin JNA or Swift C interop). Header
+//! can be generated from this module with `cbindgen`.
+//!
+//! Controller is passed around as opaque pointer. Strings are nul
+//! terminated UTF-8, returned ones are owned by the caller and must be
+//! released with `keri_string_free`. Functions which fail return null
+//! pointer or negative number; message of the last error of calling
+//! thread is available from `keri_last_error`.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    path::Path,
+    ptr, slice,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    database::sled::SledEventDatabase,
+    error::Error,
+    event::sections::seal::{DigestSeal, Seal},
+    event_parsing::SignedEventData,
+    keri::Keri,
+    prefix::{IdentifierPrefix, Prefix},
+    processor::EventProcessor,
+    signer::CryptoBox,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opaque handle of identifier controller.
+pub struct KeriController(Keri<CryptoBox>);
+
+fn set_last_error(e: Error) {
+    let message = CString::new(e.to_string()).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, storing its error as the last one.
+fn catch<T>(f: impl FnOnce() -> Result<T, Error>) -> Option<T> {
+    f().map_err(set_last_error).ok()
+}
+
+fn to_c_string(bytes: Vec<u8>) -> Result<*mut c_char, Error> {
+    CString::new(bytes)
+        .map(CString::into_raw)
+        .map_err(|e| Error::SerializationError(e.to_string()))
+}
+
+unsafe fn from_c_str<'a>(s: *const c_char) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(Error::SemanticError("Null pointer".into()));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| Error::DeserializeError(e.to_string()))
+}
+
+unsafe fn controller<'a>(ctrl: *mut KeriController) -> Result<&'a mut Keri<CryptoBox>, Error> {
+    ctrl.as_mut()
+        .map(|ctrl| &mut ctrl.0)
+        .ok_or_else(|| Error::SemanticError("Null pointer".into()))
+}
+
+fn signed_to_c_string(event: SignedEventData) -> Result<*mut c_char, Error> {
+    to_c_string(event.to_cesr()?)
+}
+
+/// Returns message of the last error which occurred in calling thread,
+/// or null if there was none.
+#[no_mangle]
+pub extern "C" fn keri_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null_mut(), |e| e.clone().into_raw())
+    })
+}
+
+/// Releases string returned by any of `keri_*` functions.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library, not freed
+/// before.
+#[no_mangle]
+pub unsafe extern "C" fn keri_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Creates controller with fresh Ed25519 keys, keeping its database
+/// in `db_path` directory.
+///
+/// # Safety
+///
+/// `db_path` must be a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_new(db_path: *const c_char) -> *mut KeriController {
+    catch(|| {
+        let db = Arc::new(SledEventDatabase::new(Path::new(from_c_str(db_path)?))?);
+        let keri = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+        Ok(Box::into_raw(Box::new(KeriController(keri))))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Releases controller.
+///
+/// # Safety
+///
+/// `ctrl` must be null or a controller returned by
+/// `keri_controller_new`, not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_free(ctrl: *mut KeriController) {
+    if !ctrl.is_null() {
+        drop(Box::from_raw(ctrl));
+    }
+}
+
+/// Returns qb64 prefix of controlled identifier.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_prefix(ctrl: *mut KeriController) -> *mut c_char {
+    catch(|| to_c_string(controller(ctrl)?.prefix().to_str().into_bytes()))
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Incepts identifier. Returns the signed inception event.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_incept(ctrl: *mut KeriController) -> *mut c_char {
+    catch(|| signed_to_c_string((&controller(ctrl)?.incept(None)?).into()))
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Rotates keys of identifier. Returns the signed rotation event.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_rotate(ctrl: *mut KeriController) -> *mut c_char {
+    catch(|| signed_to_c_string((&controller(ctrl)?.rotate()?).into())).unwrap_or(ptr::null_mut())
+}
+
+/// Anchors qb64 `digest` of some data in interaction event. Returns
+/// the signed event.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller and `digest` a valid nul
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_anchor(
+    ctrl: *mut KeriController,
+    digest: *const c_char,
+) -> *mut c_char {
+    catch(|| {
+        let seal = Seal::Digest(DigestSeal {
+            dig: from_c_str(digest)?.parse()?,
+        });
+        signed_to_c_string((&controller(ctrl)?.anchor(&[seal])?).into())
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Processes CESR stream of `len` bytes. Returns receipts of processed
+/// events made by the controller.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller and `data` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_process(
+    ctrl: *mut KeriController,
+    data: *const u8,
+    len: usize,
+) -> *mut c_char {
+    catch(|| {
+        if data.is_null() {
+            return Err(Error::SemanticError("Null pointer".into()));
+        }
+        to_c_string(controller(ctrl)?.respond(slice::from_raw_parts(data, len))?)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Returns KEL of identifier of qb64 `prefix` known to the controller,
+/// or null if there is none.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller and `prefix` a valid nul
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_get_kel(
+    ctrl: *mut KeriController,
+    prefix: *const c_char,
+) -> *mut c_char {
+    catch(|| {
+        let prefix: IdentifierPrefix = from_c_str(prefix)?.parse()?;
+        let processor = EventProcessor::new(controller(ctrl)?.db());
+        match processor.get_kerl(&prefix)? {
+            Some(kel) => to_c_string(kel),
+            None => Err(Error::UnknownIdentifier(prefix)),
+        }
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Returns JSON of current state of identifier of qb64 `prefix`, or
+/// null if it's unknown.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller and `prefix` a valid nul
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_get_state(
+    ctrl: *mut KeriController,
+    prefix: *const c_char,
+) -> *mut c_char {
+    catch(|| {
+        let prefix: IdentifierPrefix = from_c_str(prefix)?.parse()?;
+        match controller(ctrl)?.get_state_for_prefix(&prefix)? {
+            Some(state) => to_c_string(serde_json::to_vec(&state)?),
+            None => Err(Error::UnknownIdentifier(prefix)),
+        }
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Returns sn of the identifier's last event, or -1 on error.
+///
+/// # Safety
+///
+/// `ctrl` must be a valid controller and `prefix` a valid nul
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn keri_controller_get_sn(
+    ctrl: *mut KeriController,
+    prefix: *const c_char,
+) -> i64 {
+    catch(|| {
+        let prefix: IdentifierPrefix = from_c_str(prefix)?.parse()?;
+        match controller(ctrl)?.get_state_for_prefix(&prefix)? {
+            Some(state) => Ok(state.sn as i64),
+            None => Err(Error::UnknownIdentifier(prefix)),
+        }
+    })
+    .unwrap_or(-1)
+}
+
+#[test]
+fn test_ffi() -> Result<(), Error> {
+    use tempfile::Builder;
+
+    unsafe fn take(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        let owned = CStr::from_ptr(s).to_str().unwrap().to_string();
+        keri_string_free(s);
+        owned
+    }
+
+    let alice_root = Builder::new().prefix("alice-db").tempdir().unwrap();
+    let bob_root = Builder::new().prefix("bob-db").tempdir().unwrap();
+    let path = |root: &tempfile::TempDir| CString::new(root.path().to_str().unwrap()).unwrap();
+
+    unsafe {
+        let alice = keri_controller_new(path(&alice_root).as_ptr());
+        let bob = keri_controller_new(path(&bob_root).as_ptr());
+        assert!(!alice.is_null() && !bob.is_null());
+
+        take(keri_controller_incept(alice));
+        take(keri_controller_incept(bob));
+        take(keri_controller_rotate(alice));
+        let digest = CString::new("EZAoTNZH3ULvaU6Z-i0d8JJR2nmwyYAfSVPzhzS6b5CM").unwrap();
+        take(keri_controller_anchor(alice, digest.as_ptr()));
+        let alice_prefix = CString::new(take(keri_controller_prefix(alice))).unwrap();
+
+        // Bob learns alice's KEL.
+        let kel = take(keri_controller_get_kel(alice, alice_prefix.as_ptr()));
+        take(keri_controller_process(bob, kel.as_ptr(), kel.len()));
+        assert_eq!(keri_controller_get_sn(bob, alice_prefix.as_ptr()), 2);
+        let state = take(keri_controller_get_state(bob, alice_prefix.as_ptr()));
+        assert!(state.contains(alice_prefix.to_str().unwrap()));
+
+        // Errors are reported through last error.
+        let unknown = CString::new("DntNTPnDFBnmlO6J44LXCrzZTAmpe-82b7BmQGtL4QhM").unwrap();
+        assert!(keri_controller_get_kel(bob, unknown.as_ptr()).is_null());
+        assert_eq!(keri_controller_get_sn(bob, unknown.as_ptr()), -1);
+        assert!(take(keri_last_error()).contains("DntNTPnDFBnmlO6J44LXCrzZTAmpe-82b7BmQGtL4QhM"));
+        assert!(keri_controller_prefix(ptr::null_mut()).is_null());
+
+        keri_controller_free(alice);
+        keri_controller_free(bob);
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Compact hex serialization of integers
+//!
+//! Sequence numbers and thresholds are written in events as lowercase
+//! hex strings without leading zeros, e.g. `"a"` for 10. Use it with
+//! `#[serde(with = "crate::hex")]`, or `crate::hex::opt` for optional
+//! fields.
+
+use alloc::string::{String, ToString};
+use core::convert::TryFrom;
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Copy + Into<u64>,
+    S: Serializer,
+{
+    serializer.serialize_str(&format!("{:x}", (*value).into()))
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: TryFrom<u64>,
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    from_hex(&s).map_err(de::Error::custom)
+}
+
+fn from_hex<T: TryFrom<u64>>(s: &str) -> Result<T, String> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("improper hex number: {:?}", s));
+    }
+    let n = u64::from_str_radix(s, 16).map_err(|e| e.to_string())?;
+    T::try_from(n).map_err(|_| format!("hex number out of range: {}", s))
+}
+
+pub mod opt {
+    use alloc::string::String;
+    use core::convert::TryFrom;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<u64>,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => super::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: TryFrom<u64>,
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| super::from_hex(&s).map_err(de::Error::custom))
+            .transpose()
+    }
+}
+
+#[test]
+fn test_compact_hex() {
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Sn {
+        #[serde(with = "crate::hex")]
+        s: u64,
+    }
+
+    for (sn, hex) in [(0, "0"), (10, "a"), (255, "ff"), (4096, "1000")] {
+        let json = format!(r#"{{"s":"{}"}}"#, hex);
+        assert_eq!(serde_json::to_string(&Sn { s: sn }).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Sn>(&json).unwrap(), Sn { s: sn });
+    }
+    assert!(serde_json::from_str::<Sn>(r#"{"s":""}"#).is_err());
+    assert!(serde_json::from_str::<Sn>(r#"{"s":"+1"}"#).is_err());
+    assert!(serde_json::from_str::<Sn>(r#"{"s":"xyz"}"#).is_err());
+}
@@ -0,0 +1,62 @@
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    event::sections::KeyConfig,
+    prefix::{AttachedSignaturePrefix, IdentifierPrefix, Prefix, SelfAddressingPrefix},
+    state::IdentifierState,
+};
+
+/// Next Keys Custody Proof
+///
+/// Statement proving possession of pre-rotated next keys. Next public
+/// keys are disclosed, so their digest can be compared with the `n`
+/// commitment of the key state, while the private keys are only used
+/// to sign the challenge bound to the digest of the last event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustodyProof {
+    #[serde(rename = "i")]
+    pub prefix: IdentifierPrefix,
+    #[serde(rename = "s", with = "crate::hex")]
+    pub sn: u64,
+    #[serde(rename = "d")]
+    pub digest: SelfAddressingPrefix,
+    #[serde(rename = "c")]
+    pub challenge: Vec<u8>,
+    #[serde(rename = "n")]
+    pub next_keys: KeyConfig,
+    #[serde(rename = "sigs")]
+    pub signatures: Vec<AttachedSignaturePrefix>,
+}
+
+impl CustodyProof {
+    /// Commitment
+    ///
+    /// Data signed with the next keys: the challenge followed by the
+    /// qb64 digest of the last event, so a signature can't be replayed
+    /// against other key state.
+    pub fn commitment(challenge: &[u8], digest: &SelfAddressingPrefix) -> Vec<u8> {
+        [challenge, digest.to_str().as_bytes()].concat()
+    }
+
+    /// Verify
+    ///
+    /// Checks if next keys of the proof match the commitment of the
+    /// given key state and if challenge is signed by those keys.
+    pub fn verify(&self, state: &IdentifierState) -> Result<bool, Error> {
+        if state.prefix != self.prefix
+            || state.sn != self.sn
+            || state.last_event_digest != self.digest
+        {
+            return Err(Error::SemanticError(
+                "Proof doesn't refer to given state".into(),
+            ));
+        }
+        Ok(state.current.verify_next(&self.next_keys)
+            && self.next_keys.verify(
+                &Self::commitment(&self.challenge, &self.digest),
+                &self.signatures,
+            )?)
+    }
+}
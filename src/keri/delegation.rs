@@ -0,0 +1,319 @@
+use std::convert::TryFrom;
+
+use super::Keri;
+use crate::{
+    error::Error,
+    event::{
+        event_data::EventData,
+        sections::{
+            seal::{EventSeal, Seal},
+            ConfigTrait,
+        },
+        EventMessage,
+    },
+    event_message::{
+        event_msg_builder::EventMsgBuilder,
+        key_event_message::KeyEvent,
+        signed_event_message::{Message, SignedEventMessage},
+        EventTypeTag,
+    },
+    event_parsing::{message::signed_event_stream, SignedEventData},
+    prefix::{AttachedSignaturePrefix, IdentifierPrefix},
+    signer::KeyManager,
+    state::IdentifierState,
+    transport::Transport,
+};
+
+/// Delegation Request
+///
+/// Delegated inception event received by delegator, with verified
+/// signatures, waiting for delegator's consent. Nothing is anchored
+/// until it's passed to `Keri::approve_delegation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelegationRequest {
+    dip: SignedEventMessage,
+}
+
+impl DelegationRequest {
+    /// Identifier which asks for delegation.
+    pub fn delegate(&self) -> IdentifierPrefix {
+        self.dip.event_message.event.get_prefix()
+    }
+
+    pub fn event(&self) -> &SignedEventMessage {
+        &self.dip
+    }
+}
+
+impl<K: KeyManager> Keri<K> {
+    /// Incept delegated identifier
+    ///
+    /// Builds and signs `dip` event naming `delegator` as delegator of
+    /// this instance's identifier. Event is not processed, as it can't
+    /// be accepted until delegator anchors it in its KEL. Source seal is
+    /// attached if delegator's anchoring event is already known.
+    pub fn incept_delegated(
+        &self,
+        delegator: &IdentifierPrefix,
+    ) -> Result<SignedEventMessage, Error> {
+        let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
+        let dip = EventMsgBuilder::new(EventTypeTag::Dip)
+            .with_keys(vec![km.key_type().derive(km.public_key())])
+            .with_next_keys(vec![km.key_type().derive(km.next_public_key())])
+            .with_delegator(delegator)
+            .build()?;
+
+        Ok(dip.sign(
+            vec![AttachedSignaturePrefix::new(
+                km.signature_type(),
+                km.sign(&dip.serialize()?)?,
+                0,
+            )],
+            self.processor.find_source_seal(&dip)?,
+        ))
+    }
+
+    /// Check delegation request
+    ///
+    /// Delegator side of cooperative delegation. Verifies that provided
+    /// `dip` event names this instance as delegator and that its
+    /// signatures are valid. Returns pending request, which is anchored
+    /// only if passed to `approve_delegation`.
+    pub fn check_delegation_request(
+        &self,
+        dip: &SignedEventMessage,
+    ) -> Result<DelegationRequest, Error> {
+        let (key_config, delegator) = match dip.event_message.event.get_event_data() {
+            EventData::Dip(dip) => (dip.inception_data.key_config, dip.delegator),
+            _ => {
+                return Err(Error::SemanticError(
+                    "Not a delegated inception event".into(),
+                ))
+            }
+        };
+        if delegator != self.prefix {
+            return Err(Error::SemanticError("Not a delegator of the event".into()));
+        }
+        // Don't anchor delegation which would be refused anyway.
+        if self
+            .get_state()?
+            .is_some_and(|state| state.has_config_trait(&ConfigTrait::DoNotDelegate))
+        {
+            return Err(Error::DelegationNotAllowed(delegator));
+        }
+        if !key_config.verify(&dip.raw_event()?, &dip.signatures)? {
+            return Err(Error::SignatureVerificationError);
+        }
+        Ok(DelegationRequest { dip: dip.clone() })
+    }
+
+    /// Approve delegation
+    ///
+    /// Anchors event of checked delegation `request` in an `ixn` event
+    /// of own KEL and processes it. Returns delegated event with source
+    /// seal of the anchoring event attached.
+    pub fn approve_delegation(
+        &self,
+        request: DelegationRequest,
+    ) -> Result<SignedEventMessage, Error> {
+        let dip = request.dip;
+        let seal = Seal::Event(EventSeal {
+            prefix: dip.event_message.event.get_prefix(),
+            sn: dip.event_message.event.get_sn(),
+            event_digest: dip.event_message.get_digest(),
+        });
+        self.anchor(&[seal])?;
+
+        let approved = self.processor.attach_source_seal(SignedEventMessage::new(
+            &dip.event_message,
+            dip.signatures.clone(),
+            None,
+        ))?;
+        self.processor.process(Message::Event(approved.clone()))?;
+
+        Ok(approved)
+    }
+
+    /// Respond to delegation request
+    ///
+    /// Parses delegation request received from delegatee and asks
+    /// `consent` whether to approve it. Approved request is anchored and
+    /// response contains delegator's KEL followed by the delegated event,
+    /// so delegatee can validate the anchoring seal. Refused request
+    /// ends with `DelegationRefused` error and nothing is anchored.
+    pub fn respond_to_delegation_request(
+        &self,
+        msg: &[u8],
+        consent: impl FnOnce(&DelegationRequest) -> bool,
+    ) -> Result<Vec<u8>, Error> {
+        let dip = match signed_event_stream(msg)
+            .map_err(|e| Error::DeserializeError(e.to_string()))?
+            .1
+            .into_iter()
+            .next()
+            .map(Message::try_from)
+        {
+            Some(Ok(Message::Event(dip))) => dip,
+            Some(Err(e)) => return Err(e),
+            _ => {
+                return Err(Error::SemanticError(
+                    "Missing delegated inception event".into(),
+                ))
+            }
+        };
+        let request = self.check_delegation_request(&dip)?;
+        if !consent(&request) {
+            return Err(Error::DelegationRefused(request.delegate()));
+        }
+        let approved = self.approve_delegation(request)?;
+
+        self.processor
+            .db
+            .get_kel_finalized_events(&self.prefix)
+            .ok_or_else(|| Error::SemanticError("KEL is empty".into()))?
+            .map(|event| SignedEventData::from(&event.signed_event_message).to_cesr())
+            .chain(std::iter::once(SignedEventData::from(&approved).to_cesr()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|events| events.concat())
+    }
+
+    /// Request delegation
+    ///
+    /// Delegatee side of cooperative delegation. Incepts delegated
+    /// identifier, sends `dip` event to the `delegator` over given
+    /// `transport` and finalizes delegated KEL with its response.
+    pub fn request_delegation(
+        &mut self,
+        delegator: &IdentifierPrefix,
+        transport: &dyn Transport,
+    ) -> Result<SignedEventMessage, Error> {
+        let dip = self.incept_delegated(delegator)?;
+        let request = SignedEventData::from(&dip).to_cesr()?;
+        let response = transport.send_message(delegator, &request)?;
+        self.finalize_delegation(&dip, &response)?;
+        Ok(dip)
+    }
+
+    /// Finalize delegation
+    ///
+    /// Processes delegator's response to delegation request and checks
+    /// if provided `dip` event was accepted. On success instance's
+    /// prefix is set to the delegated identifier.
+    pub fn finalize_delegation(
+        &mut self,
+        dip: &SignedEventMessage,
+        response: &[u8],
+    ) -> Result<IdentifierState, Error> {
+        let messages = signed_event_stream(response)
+            .map_err(|e| Error::DeserializeError(e.to_string()))?
+            .1;
+        for message in messages {
+            match self.processor.process(Message::try_from(message)?) {
+                Ok(_) | Err(Error::EventDuplicateError) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let prefix = dip.event_message.event.get_prefix();
+        let state = self
+            .processor
+            .compute_state(&prefix)?
+            .ok_or_else(|| Error::SemanticError("Delegation not approved".into()))?;
+        if state.last_event_digest != dip.event_message.get_digest() {
+            return Err(Error::SemanticError("Delegation not approved".into()));
+        }
+        self.prefix = prefix;
+        Ok(state)
+    }
+
+    /// Make group anchor
+    ///
+    /// Builds `ixn` event of multisig `group` delegator anchoring given
+    /// delegated event. Event is returned unsigned, so every member of
+    /// the group can sign it with `sign_group_event`.
+    pub fn make_group_anchor(
+        &self,
+        group: &IdentifierPrefix,
+        delegated: &EventMessage<KeyEvent>,
+    ) -> Result<EventMessage<KeyEvent>, Error> {
+        let state = self
+            .processor
+            .compute_state(group)?
+            .ok_or_else(|| Error::SemanticError("Unknown group identifier".into()))?;
+        if state.has_config_trait(&ConfigTrait::DoNotDelegate) {
+            return Err(Error::DelegationNotAllowed(group.clone()));
+        }
+        let seal = Seal::Event(EventSeal {
+            prefix: delegated.event.get_prefix(),
+            sn: delegated.event.get_sn(),
+            event_digest: delegated.get_digest(),
+        });
+        EventMsgBuilder::new(EventTypeTag::Ixn)
+            .with_prefix(group)
+            .with_sn(state.sn + 1)
+            .with_previous_event(&state.last_event_digest)
+            .with_seal(vec![seal])
+            .build()
+    }
+
+    /// Sign group event
+    ///
+    /// Signs event of multisig group with own current key. Signature is
+    /// indexed with position of the key in group's key set, so signatures
+    /// of all members can be attached to the event together.
+    pub fn sign_group_event(
+        &self,
+        event: &EventMessage<KeyEvent>,
+    ) -> Result<AttachedSignaturePrefix, Error> {
+        let keys = match event.event.get_event_data() {
+            EventData::Icp(icp) => icp.key_config.public_keys,
+            EventData::Dip(dip) => dip.inception_data.key_config.public_keys,
+            EventData::Rot(rot) | EventData::Drt(rot) => rot.key_config.public_keys,
+            EventData::Ixn(_) => {
+                self.processor
+                    .compute_state(&event.event.get_prefix())?
+                    .ok_or_else(|| Error::SemanticError("Unknown group identifier".into()))?
+                    .current
+                    .public_keys
+            }
+        };
+        let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
+        let own_key = km.key_type().derive(km.public_key());
+        let index = keys
+            .iter()
+            .position(|key| key == &own_key)
+            .ok_or_else(|| Error::SemanticError("Not a member of the group".into()))?;
+        Ok(AttachedSignaturePrefix::new(
+            km.signature_type(),
+            km.sign(&event.serialize()?)?,
+            index as u16,
+        ))
+    }
+
+    /// Approve group delegation
+    ///
+    /// Processes anchoring event of multisig delegator group, signed by
+    /// its members, and then the delegated event it anchors. Delegated
+    /// event is accepted only if anchoring event satisfies signing
+    /// threshold of the group and is witnessed by group's witnesses.
+    /// Returns delegated event with source seal of the anchoring event
+    /// attached.
+    pub fn approve_group_delegation(
+        &self,
+        delegated: &SignedEventMessage,
+        anchor: &SignedEventMessage,
+    ) -> Result<SignedEventMessage, Error> {
+        match self.processor.process(Message::Event(anchor.clone())) {
+            Ok(_) | Err(Error::EventDuplicateError) => (),
+            Err(e) => return Err(e),
+        }
+        let approved = self.processor.attach_source_seal(SignedEventMessage::new(
+            &delegated.event_message,
+            delegated.signatures.clone(),
+            None,
+        ))?;
+        self.processor.process(Message::Event(approved.clone()))?;
+
+        Ok(approved)
+    }
+}
@@ -7,9 +7,9 @@ use crate::{
     database::sled::SledEventDatabase,
     derivation::basic::Basic,
     derivation::self_addressing::SelfAddressing,
-    derivation::self_signing::SelfSigning,
     error::Error,
     event::sections::seal::{DigestSeal, Seal},
+    event::sections::{threshold::SignatureThreshold, KeyConfig},
     event::{event_data::EventData, receipt::Receipt, Event, EventMessage, SerializationFormats},
     event::{event_data::InteractionEvent, sections::seal::EventSeal},
     event_message::event_msg_builder::EventMsgBuilder,
@@ -24,20 +24,44 @@ use crate::{
         message::{signed_event_stream, signed_message},
         SignedEventData,
     },
-    keys::PublicKey,
+    keys::{sealed_box, PublicKey},
+    merkle::MerkleTree,
     prefix::AttachedSignaturePrefix,
-    prefix::{BasicPrefix, IdentifierPrefix, SelfSigningPrefix},
+    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix, SelfSigningPrefix},
     processor::EventProcessor,
     signer::KeyManager,
     state::{EventSemantics, IdentifierState},
 };
+#[cfg(feature = "query")]
+use crate::{
+    directory::{AliasClaim, AliasRecord},
+    event_message::event_msg_builder::ReplyBuilder,
+    query::reply::{ReplyBody, SignedReply},
+};
+use custody::CustodyProof;
 #[cfg(feature = "wallet")]
 use universal_wallet::prelude::{Content, UnlockedWallet};
 
+pub mod custody;
+pub mod delegation;
+#[cfg(feature = "query")]
+pub mod publisher;
+pub mod reconcile;
+#[cfg(feature = "query")]
+pub mod sync;
 #[cfg(test)]
 mod test;
-#[cfg(feature = "query")]
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_witness;
+pub mod watcher;
 pub mod witness;
+/// Controller
+///
+/// High level API of identifier controller. Couples key manager with
+/// event processor, so KEL of the identifier is maintained
+/// automatically while incepting, rotating and anchoring.
+pub type Controller<K> = Keri<K>;
+
 pub struct Keri<K: KeyManager + 'static> {
     prefix: IdentifierPrefix,
     key_manager: Arc<Mutex<K>>,
@@ -89,6 +113,56 @@ impl<K: KeyManager> Keri<K> {
         })
     }
 
+    /// Creates controller of identifier incepted before, which KEL is
+    /// in `db` and which keys are held by `key_manager`.
+    pub fn open(
+        db: Arc<SledEventDatabase>,
+        key_manager: Arc<Mutex<K>>,
+        prefix: IdentifierPrefix,
+    ) -> Result<Keri<K>, Error> {
+        let processor = EventProcessor::new(db);
+        if processor.compute_state(&prefix)?.is_none() {
+            return Err(Error::UnknownIdentifier(prefix));
+        }
+        Ok(Keri {
+            prefix,
+            key_manager,
+            processor,
+        })
+    }
+
+    /// Creates controller of ephemeral identifier, eg. of witness or
+    /// watcher. Its prefix is non-transferable basic prefix of current
+    /// key of `key_manager`, incepted without next keys commitment, so
+    /// the identifier can't be rotated.
+    pub fn new_ephemeral(
+        db: Arc<SledEventDatabase>,
+        key_manager: Arc<Mutex<K>>,
+    ) -> Result<Keri<K>, Error> {
+        let icp = {
+            let km = key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
+            let key = km.key_type().to_non_transferable()?.derive(km.public_key());
+            let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+                .with_keys(vec![key])
+                .build()?;
+            icp.sign(
+                vec![AttachedSignaturePrefix::new(
+                    km.signature_type(),
+                    km.sign(&icp.serialize()?)?,
+                    0,
+                )],
+                None,
+            )
+        };
+        let processor = EventProcessor::new(db);
+        processor.process(Message::Event(icp.clone()))?;
+        Ok(Keri {
+            prefix: icp.event_message.event.get_prefix(),
+            key_manager,
+            processor,
+        })
+    }
+
     /// Getter of the instance prefix
     ///
     pub fn prefix(&self) -> &IdentifierPrefix {
@@ -110,7 +184,7 @@ impl<K: KeyManager> Keri<K> {
     pub fn process(&self, id: &IdentifierPrefix, event: impl EventSemantics) -> Result<(), Error> {
         match self.processor.process_actual_event(id, event) {
             Ok(Some(_)) => Ok(()),
-            Ok(None) => Err(Error::SemanticError("Unknown identifier.".into())),
+            Ok(None) => Err(Error::UnknownIdentifier(id.clone())),
             Err(e) => Err(e),
         }
     }
@@ -122,14 +196,14 @@ impl<K: KeyManager> Keri<K> {
         let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
         let icp = EventMsgBuilder::new(EventTypeTag::Icp)
             .with_prefix(&self.prefix)
-            .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
-            .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
-            .with_witness_list(&initial_witness.unwrap_or_default())
+            .with_keys(vec![km.key_type().derive(km.public_key())])
+            .with_next_keys(vec![km.key_type().derive(km.next_public_key())])
+            .with_witnesses(&initial_witness.unwrap_or_default())
             .build()?;
 
         let signed = icp.sign(
             vec![AttachedSignaturePrefix::new(
-                SelfSigning::Ed25519Sha512,
+                km.signature_type(),
                 km.sign(&icp.serialize()?)?,
                 0,
             )],
@@ -163,16 +237,16 @@ impl<K: KeyManager> Keri<K> {
             .collect();
         // Signing key must be first
         let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
-        keys.insert(0, Basic::Ed25519.derive(km.public_key()));
+        keys.insert(0, km.key_type().derive(km.public_key()));
         let icp = EventMsgBuilder::new(EventTypeTag::Icp)
             .with_prefix(&self.prefix)
             .with_keys(keys)
-            .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+            .with_next_keys(vec![km.key_type().derive(km.next_public_key())])
             .build()?;
 
         let signed = icp.sign(
             vec![AttachedSignaturePrefix::new(
-                SelfSigning::Ed25519Sha512,
+                km.signature_type(),
                 km.sign(&icp.serialize()?)?,
                 0,
             )],
@@ -210,38 +284,33 @@ impl<K: KeyManager> Keri<K> {
             next_sn,
             EventData::Ixn(InteractionEvent::new(pref, vec![seal])),
         )
-        .to_message(SerializationFormats::JSON, &SelfAddressing::Blake3_256)?;
+        .to_message(SerializationFormats::JSON, &self.digest_derivation()?)?;
         let serialized = event.serialize()?;
-        let signature = self
-            .key_manager
-            .lock()
-            .map_err(|_| Error::MutexPoisoned)?
-            .sign(&serialized)?;
+        let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
         let asp = AttachedSignaturePrefix::new(
-            SelfSigning::ECDSAsecp256k1Sha256,
-            signature,
+            km.signature_type(),
+            km.sign(&serialized)?,
             0, // TODO: what is this?
         );
         let signed = SignedEventMessage::new(&event, vec![asp], None);
-        self.processor
-            .db
-            .add_kel_finalized_event(signed.clone(), &self.prefix)?;
+        self.processor.process(Message::Event(signed.clone()))?;
         Ok(signed)
     }
 
     pub fn rotate(&mut self) -> Result<SignedEventMessage, Error> {
+        if !self.prefix.is_transferable() {
+            return Err(Error::NonTransferable(self.prefix.clone()));
+        }
         self.key_manager
             .lock()
             .map_err(|_| Error::MutexPoisoned)?
             .rotate()?;
         let rot = self.make_rotation()?;
+        let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
         let rot = rot.sign(
             vec![AttachedSignaturePrefix::new(
-                SelfSigning::Ed25519Sha512,
-                self.key_manager
-                    .lock()
-                    .map_err(|_| Error::MutexPoisoned)?
-                    .sign(&rot.serialize()?)?,
+                km.signature_type(),
+                km.sign(&rot.serialize()?)?,
                 0,
             )],
             None,
@@ -262,22 +331,57 @@ impl<K: KeyManager> Keri<K> {
                 .with_prefix(&self.prefix)
                 .with_sn(state.sn + 1)
                 .with_previous_event(&state.last_event_digest)
-                .with_keys(vec![Basic::Ed25519.derive(kv.public_key())])
-                .with_next_keys(vec![Basic::Ed25519.derive(kv.next_public_key())])
+                .with_derivation(state.last_event_digest.derivation.clone())
+                .with_keys(vec![kv.key_type().derive(kv.public_key())])
+                .with_next_keys(vec![kv.key_type().derive(kv.next_public_key())])
                 .build(),
             Err(_) => Err(Error::MutexPoisoned),
         }
     }
 
     pub fn make_ixn(&mut self, payload: Option<&str>) -> Result<SignedEventMessage, Error> {
-        let seal_list = match payload {
-            Some(payload) => {
-                vec![Seal::Digest(DigestSeal {
-                    dig: SelfAddressing::Blake3_256.derive(payload.as_bytes()),
-                })]
-            }
+        let digests = match payload {
+            Some(payload) => vec![self.digest_derivation()?.derive(payload.as_bytes())],
             None => vec![],
         };
+        self.anchor_digests(&digests)
+    }
+
+    /// Anchor Digests
+    ///
+    /// Anchors SAIDs of application data, eg. documents or TEL events,
+    /// in controller's KEL as digest seals of `ixn` event. Returns the
+    /// event for dissemination.
+    pub fn anchor_digests(
+        &self,
+        digests: &[SelfAddressingPrefix],
+    ) -> Result<SignedEventMessage, Error> {
+        let seals: Vec<_> = digests
+            .iter()
+            .map(|dig| Seal::Digest(DigestSeal { dig: dig.clone() }))
+            .collect();
+        self.anchor(&seals)
+    }
+
+    /// Anchor Batch
+    ///
+    /// Builds merkle tree over `digests` and anchors only its root in
+    /// `ixn` event. Returns the event and the tree, which produces
+    /// inclusion proofs of anchored digests.
+    pub fn anchor_batch(
+        &self,
+        digests: &[SelfAddressingPrefix],
+    ) -> Result<(SignedEventMessage, MerkleTree), Error> {
+        let tree = MerkleTree::new(digests, &self.digest_derivation()?)?;
+        let ixn = self.anchor(&[tree.root_seal()])?;
+        Ok((ixn, tree))
+    }
+
+    /// Anchor
+    ///
+    /// Builds, signs and processes `ixn` event with provided `seals`
+    /// in its data. Returns the event for dissemination.
+    pub fn anchor(&self, seals: &[Seal]) -> Result<SignedEventMessage, Error> {
         let state = self
             .processor
             .compute_state(&self.prefix)?
@@ -287,16 +391,15 @@ impl<K: KeyManager> Keri<K> {
             .with_prefix(&self.prefix)
             .with_sn(state.sn + 1)
             .with_previous_event(&state.last_event_digest)
-            .with_seal(seal_list)
+            .with_derivation(state.last_event_digest.derivation.clone())
+            .with_seal(seals.to_vec())
             .build()?;
 
+        let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
         let ixn = ev.sign(
             vec![AttachedSignaturePrefix::new(
-                SelfSigning::Ed25519Sha512,
-                self.key_manager
-                    .lock()
-                    .map_err(|_| Error::MutexPoisoned)?
-                    .sign(&ev.serialize()?)?,
+                km.signature_type(),
+                km.sign(&ev.serialize()?)?,
                 0,
             )],
             None,
@@ -307,6 +410,95 @@ impl<K: KeyManager> Keri<K> {
         Ok(ixn)
     }
 
+    /// Derivation of digests of controller's events.
+    fn digest_derivation(&self) -> Result<SelfAddressing, Error> {
+        self.processor
+            .get_digest_derivation(&self.prefix)?
+            .ok_or_else(|| Error::SemanticError("There is no state".into()))
+    }
+
+    /// Sign
+    ///
+    /// Signs arbitrary data with current keys. Returned signatures are
+    /// indexed, so they can be verified against the instance key state.
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<AttachedSignaturePrefix>, Error> {
+        let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
+        Ok(vec![AttachedSignaturePrefix::new(
+            km.signature_type(),
+            km.sign(data)?,
+            0,
+        )])
+    }
+
+    /// Verify
+    ///
+    /// Verifies signatures of data against current keys of `signer`,
+    /// which KEL must be known to the instance.
+    pub fn verify(
+        &self,
+        signer: &IdentifierPrefix,
+        data: &[u8],
+        signatures: &[AttachedSignaturePrefix],
+    ) -> Result<bool, Error> {
+        self.processor
+            .compute_state(signer)?
+            .ok_or_else(|| Error::SemanticError("Unknown signer".into()))?
+            .current
+            .verify(data, signatures)
+    }
+
+    /// Attest Alias
+    ///
+    /// Makes `/alias` reply claiming `alias` for the controller, signed
+    /// with its current keys, and stores alias with the attestation.
+    #[cfg(feature = "query")]
+    pub fn attest_alias(&self, alias: &str) -> Result<SignedReply, Error> {
+        let claim = AliasClaim {
+            prefix: self.prefix.clone(),
+            alias: alias.to_string(),
+        };
+        let rpy = ReplyBuilder::default()
+            .with_body(ReplyBody::Alias(claim))
+            .with_derivation(self.digest_derivation()?)
+            .build()?;
+        let seal = self
+            .processor
+            .get_last_establishment_event_seal(&self.prefix)?
+            .ok_or_else(|| Error::SemanticError("There is no state".into()))?;
+        let attestation = SignedReply::new_trans(rpy.clone(), seal, self.sign(&rpy.serialize()?)?);
+        let record =
+            AliasRecord::new(alias, self.prefix.clone()).with_attestation(attestation.clone())?;
+        self.processor.db.add_alias(record)?;
+        Ok(attestation)
+    }
+
+    /// Encrypt to
+    ///
+    /// Seals data for controller of `receiver` identifier, so only it
+    /// can read it. X25519 form of receiver's first current key is used.
+    /// Basic prefix with unknown KEL is used as the key itself.
+    pub fn encrypt_to(&self, receiver: &IdentifierPrefix, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let key = match (self.processor.compute_state(receiver)?, receiver) {
+            (Some(state), _) => state
+                .current
+                .public_keys
+                .first()
+                .cloned()
+                .ok_or_else(|| Error::SemanticError("Receiver has no keys".into()))?,
+            (None, IdentifierPrefix::Basic(bp)) => bp.clone(),
+            (None, _) => return Err(Error::SemanticError("Unknown receiver".into())),
+        };
+        sealed_box::encrypt_to(&key, data)
+    }
+
+    /// Opens data sealed for current key of the instance.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.key_manager
+            .lock()
+            .map_err(|_| Error::MutexPoisoned)?
+            .decrypt(data)
+    }
+
     /// Process and respond to single event
     ///
     pub fn respond_single(&self, msg: &[u8]) -> Result<(IdentifierPrefix, Vec<u8>), Error> {
@@ -324,7 +516,12 @@ impl<K: KeyManager> Keri<K> {
         let events = signed_event_stream(msg)
             .map_err(|e| Error::DeserializeError(e.to_string()))?
             .1;
+        self.respond_events(events)
+    }
 
+    /// Processes already parsed messages and returns receipts of
+    /// processed events.
+    pub fn respond_events(&self, events: Vec<SignedEventData>) -> Result<Vec<u8>, Error> {
         let (processed_ok, _processed_failed): (Vec<_>, Vec<_>) = events
             .into_iter()
             .map(|event| {
@@ -357,9 +554,8 @@ impl<K: KeyManager> Keri<K> {
                         buf.append(&mut rcp.to_cesr().unwrap());
                         Ok(buf)
                     }
-                    Message::TransferableRct(_rct) => Ok(vec![]),
-                    // TODO: this should process properly
-                    _ => todo!(),
+                    // Nothing to respond to receipts and other messages.
+                    _ => Ok(vec![]),
                 }
             })
             .filter_map(|x| x.ok())
@@ -373,11 +569,10 @@ impl<K: KeyManager> Keri<K> {
         event: EventMessage<KeyEvent>,
     ) -> Result<SignedTransferableReceipt, Error> {
         let ser = event.serialize()?;
-        let signature = self
-            .key_manager
-            .lock()
-            .map_err(|_| Error::MutexPoisoned)?
-            .sign(&ser)?;
+        let (signature_type, signature) = {
+            let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
+            (km.signature_type(), km.sign(&ser)?)
+        };
         let validator_event_seal = self
             .processor
             .get_last_establishment_event_seal(&self.prefix)?
@@ -385,15 +580,11 @@ impl<K: KeyManager> Keri<K> {
         let rcp = Receipt {
             prefix: event.event.get_prefix(),
             sn: event.event.get_sn(),
-            receipted_event_digest: SelfAddressing::Blake3_256.derive(&ser),
+            receipted_event_digest: event.get_digest().derivation.derive(&ser),
         }
         .to_message(SerializationFormats::JSON)?;
 
-        let signatures = vec![AttachedSignaturePrefix::new(
-            SelfSigning::Ed25519Sha512,
-            signature,
-            0,
-        )];
+        let signatures = vec![AttachedSignaturePrefix::new(signature_type, signature, 0)];
         let signed_rcp = SignedTransferableReceipt::new(rcp, validator_event_seal, signatures);
 
         self.processor
@@ -426,7 +617,11 @@ impl<K: KeyManager> Keri<K> {
             // ICP requires check if we are in initial witnesses only
             EventData::Icp(evt) => {
                 if !evt.witness_config.initial_witnesses.contains(our_bp) {
-                    return Err(Error::SemanticError("we are not in a witness list.".into()));
+                    return Err(Error::UnauthorizedWitness {
+                        prefix: message.event.get_prefix(),
+                        sn: message.event.get_sn(),
+                        witness: our_bp.clone(),
+                    });
                 }
                 self.generate_ntr(message)
             }
@@ -467,6 +662,34 @@ impl<K: KeyManager> Keri<K> {
         self.processor.get_kerl(&self.prefix)
     }
 
+    /// Prove next keys custody
+    ///
+    /// Signs given challenge, bound to the digest of the last event, with
+    /// the pre-rotated next keys, so anyone can check that the controller
+    /// holds keys matching current next keys commitment.
+    pub fn prove_next_keys_custody(&self, challenge: &[u8]) -> Result<CustodyProof, Error> {
+        let state = self
+            .get_state()?
+            .ok_or_else(|| Error::SemanticError("There is no state".into()))?;
+        let km = self.key_manager.lock().map_err(|_| Error::MutexPoisoned)?;
+        let next_keys = KeyConfig::new(
+            vec![km.key_type().derive(km.next_public_key())],
+            None,
+            Some(SignatureThreshold::default()),
+        );
+        let commitment = CustodyProof::commitment(challenge, &state.last_event_digest);
+        let signature =
+            AttachedSignaturePrefix::new(km.signature_type(), km.sign_with_next(&commitment)?, 0);
+        Ok(CustodyProof {
+            prefix: self.prefix.clone(),
+            sn: state.sn,
+            digest: state.last_event_digest,
+            challenge: challenge.to_vec(),
+            next_keys,
+            signatures: vec![signature],
+        })
+    }
+
     pub fn get_state_for_prefix(
         &self,
         prefix: &IdentifierPrefix,
@@ -482,20 +705,22 @@ impl<K: KeyManager> Keri<K> {
         &self,
         message: EventMessage<KeyEvent>,
     ) -> Result<SignedNontransferableReceipt, Error> {
-        let signature;
+        let ssp;
         let bp;
         match self.key_manager.lock() {
             Ok(km) => {
-                signature = km.sign(&message.serialize()?)?;
-                bp = BasicPrefix::new(Basic::Ed25519, km.public_key());
+                ssp = SelfSigningPrefix::new(km.signature_type(), km.sign(&message.serialize()?)?);
+                bp = BasicPrefix::new(km.key_type(), km.public_key());
             }
             Err(_) => return Err(Error::MutexPoisoned),
         }
-        let ssp = SelfSigningPrefix::new(SelfSigning::Ed25519Sha512, signature);
         let rcp = Receipt {
             prefix: message.event.get_prefix(),
             sn: message.event.get_sn(),
-            receipted_event_digest: SelfAddressing::Blake3_256.derive(&message.serialize()?),
+            receipted_event_digest: message
+                .get_digest()
+                .derivation
+                .derive(&message.serialize()?),
         }
         .to_message(SerializationFormats::JSON)?;
         let ntr = SignedNontransferableReceipt::new(&rcp, vec![(bp, ssp)]);
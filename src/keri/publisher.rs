@@ -0,0 +1,181 @@
+//! Key State Publisher
+//!
+//! Publish half of key state dissemination. Every time KEL changes,
+//! signed `rpy` message with key state notice is queued for each
+//! configured recipient, eg. watchers or registries, and delivered
+//! through transport. Notice which wasn't delivered is retried with
+//! exponentially growing delay, until it's superseded by newer one or
+//! max number of attempts is reached.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    derivation::self_addressing::SelfAddressing,
+    error::Error,
+    event::SerializationFormats,
+    event_parsing::SignedEventData,
+    prefix::{BasicPrefix, IdentifierPrefix, Prefix, SelfAddressingPrefix},
+    query::{
+        key_state_notice::KeyStateNotice,
+        reply::{ReplyEvent, SignedReply},
+        Route,
+    },
+    signer::KeyManager,
+    state::IdentifierState,
+    transport::Transport,
+};
+
+/// Signed key state notice waiting for delivery to recipient.
+#[derive(Debug, Clone, PartialEq)]
+struct Pending {
+    recipient: IdentifierPrefix,
+    about: IdentifierPrefix,
+    rpy: Vec<u8>,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+/// Key State Notice Publisher
+///
+/// Queue of signed key state notices for configured recipients, with
+/// deduplication and exponential retry of failed deliveries.
+pub struct KsnPublisher<K: KeyManager> {
+    prefix: BasicPrefix,
+    signer: K,
+    recipients: Vec<IdentifierPrefix>,
+    retry_delay: Duration,
+    max_attempts: u32,
+    // Digest of last event of the most recently queued state, per
+    // identifier.
+    published: HashMap<String, SelfAddressingPrefix>,
+    queue: Vec<Pending>,
+}
+
+impl<K: KeyManager> KsnPublisher<K> {
+    /// Creates publisher which signs notices as nontransferable
+    /// `prefix` with keys of `signer`.
+    pub fn new(prefix: BasicPrefix, signer: K, recipients: Vec<IdentifierPrefix>) -> Self {
+        Self {
+            prefix,
+            signer,
+            recipients,
+            retry_delay: Duration::from_secs(1),
+            max_attempts: 8,
+            published: HashMap::new(),
+            queue: vec![],
+        }
+    }
+
+    /// Sets delay of the first retry. Every next retry waits twice as
+    /// long as the previous one.
+    pub fn with_retry_delay(self, retry_delay: Duration) -> Self {
+        Self {
+            retry_delay,
+            ..self
+        }
+    }
+
+    /// Sets how many times delivery is attempted before notice is
+    /// dropped.
+    pub fn with_max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..self
+        }
+    }
+
+    /// Number of notices waiting for delivery.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// KEL Changed
+    ///
+    /// Queues signed notice of `state` for all recipients. Returns
+    /// `false` if the same state was already queued. Notices of older
+    /// state of the identifier which weren't delivered yet are
+    /// replaced.
+    pub fn kel_changed(&mut self, state: IdentifierState) -> Result<bool, Error> {
+        let about = state.prefix.clone();
+        if self.published.get(&about.to_str()) == Some(&state.last_event_digest) {
+            return Ok(false);
+        }
+        let digest = state.last_event_digest.clone();
+        let rpy = SignedEventData::from(self.sign_ksn(state)?).to_cesr()?;
+
+        self.queue.retain(|pending| pending.about != about);
+        let now = Instant::now();
+        for recipient in &self.recipients {
+            self.queue.push(Pending {
+                recipient: recipient.clone(),
+                about: about.clone(),
+                rpy: rpy.clone(),
+                attempts: 0,
+                next_attempt: now,
+            });
+        }
+        self.published.insert(about.to_str(), digest);
+        Ok(true)
+    }
+
+    /// Sends all notices which are due now. Returns number of
+    /// delivered notices.
+    pub fn publish(&mut self, transport: &impl Transport) -> usize {
+        self.publish_at(transport, Instant::now())
+    }
+
+    /// Sends all notices which are due at `now`. Returns number of
+    /// delivered notices.
+    pub fn publish_at(&mut self, transport: &impl Transport, now: Instant) -> usize {
+        let mut delivered = 0;
+        let (retry_delay, max_attempts) = (self.retry_delay, self.max_attempts);
+        self.queue.retain_mut(|pending| {
+            if pending.next_attempt > now {
+                return true;
+            }
+            match transport.send_message(&pending.recipient, &pending.rpy) {
+                Ok(_) => {
+                    delivered += 1;
+                    false
+                }
+                Err(e) => {
+                    pending.attempts += 1;
+                    if pending.attempts >= max_attempts {
+                        tracing::warn!(
+                            recipient = %pending.recipient.to_str(),
+                            error = %e,
+                            "key state notice dropped"
+                        );
+                        return false;
+                    }
+                    pending.next_attempt =
+                        now + retry_delay * (1 << (pending.attempts - 1).min(16));
+                    true
+                }
+            }
+        });
+        delivered
+    }
+
+    fn sign_ksn(&self, state: IdentifierState) -> Result<SignedReply, Error> {
+        let ksn = KeyStateNotice::new_ksn(state, SerializationFormats::JSON);
+        let rpy = ReplyEvent::new_reply(
+            ksn,
+            Route::ReplyKsn(IdentifierPrefix::Basic(self.prefix.clone())),
+            SelfAddressing::Blake3_256,
+            SerializationFormats::JSON,
+        )?;
+        let signature = self
+            .signer
+            .signature_type()
+            .derive(self.signer.sign(&rpy.serialize()?)?);
+        Ok(SignedReply::new_nontrans(
+            rpy,
+            self.prefix.clone(),
+            signature,
+        ))
+    }
+}
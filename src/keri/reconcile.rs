@@ -0,0 +1,260 @@
+//! Watcher Reconciliation
+//!
+//! Judge side of watcher network: validator collects key states or
+//! KELs of an identifier reported by its watchers (jurors) and decides
+//! which state to accept. Any disagreement about event at some sn is
+//! duplicity, reported with its evidence instead of a state. Otherwise
+//! the latest state confirmed by quorum of watchers is accepted.
+
+use std::{cmp::Reverse, collections::BTreeMap, convert::TryFrom, iter, sync::Arc};
+
+use crate::{
+    database::{memory::MemoryDatabase, EventDatabase},
+    error::Error,
+    event_message::signed_event_message::Message,
+    event_parsing::message::signed_event_stream,
+    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
+    processor::EventProcessor,
+    state::IdentifierState,
+};
+
+use super::watcher::DuplicityEvidence;
+
+/// Number of agreeing watchers required to accept a state.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Quorum {
+    /// All watchers have to agree.
+    Unanimous,
+    /// More than half of watchers have to agree.
+    #[default]
+    Majority,
+    /// At least given number of watchers have to agree.
+    Threshold(usize),
+}
+
+impl Quorum {
+    fn required(&self, reports: usize) -> usize {
+        match self {
+            Self::Unanimous => reports,
+            Self::Majority => reports / 2 + 1,
+            Self::Threshold(threshold) => *threshold,
+        }
+    }
+}
+
+/// Watcher Report
+///
+/// Key state of identifier as seen by a watcher, with digests of
+/// events the watcher vouches for. Key state notice vouches only for
+/// the last event, KEL for all of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatcherReport {
+    pub watcher: BasicPrefix,
+    pub state: IdentifierState,
+    events: BTreeMap<u64, SelfAddressingPrefix>,
+}
+
+impl WatcherReport {
+    /// Report of key state, eg. from key state notice.
+    pub fn from_state(watcher: BasicPrefix, state: IdentifierState) -> Self {
+        let events = iter::once((state.sn, state.last_event_digest.clone())).collect();
+        Self {
+            watcher,
+            state,
+            events,
+        }
+    }
+
+    /// Report of KEL of identifier of `prefix`. KEL is verified in a
+    /// fresh in-memory database; events which don't verify are
+    /// ignored.
+    pub fn from_kel(
+        watcher: BasicPrefix,
+        prefix: &IdentifierPrefix,
+        kel: &[u8],
+    ) -> Result<Self, Error> {
+        let processor = EventProcessor::new(Arc::new(MemoryDatabase::new()));
+        let messages = signed_event_stream(kel)
+            .map_err(|e| Error::DeserializeError(e.to_string()))?
+            .1;
+        for message in messages {
+            let _ = processor.process(Message::try_from(message)?);
+        }
+        let state = processor
+            .compute_state(prefix)?
+            .ok_or_else(|| Error::UnknownIdentifier(prefix.clone()))?;
+        let events = processor
+            .db
+            .get_kel_finalized_events(prefix)
+            .into_iter()
+            .flatten()
+            .map(|event| {
+                let event = event.signed_event_message.event_message;
+                (event.event.get_sn(), event.get_digest())
+            })
+            .collect();
+        Ok(Self {
+            watcher,
+            state,
+            events,
+        })
+    }
+
+    /// Digest of event at `sn` the watcher vouches for, if any.
+    pub fn event_digest(&self, sn: u64) -> Option<&SelfAddressingPrefix> {
+        self.events.get(&sn)
+    }
+}
+
+/// Outcome of reconciliation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// State confirmed by quorum of `watchers`.
+    Accepted {
+        state: Box<IdentifierState>,
+        watchers: Vec<BasicPrefix>,
+    },
+    /// Watchers reported different events at the same sn. Watchers of
+    /// every digest of evidence are listed in the same order.
+    Duplicity {
+        evidence: DuplicityEvidence,
+        watchers: Vec<Vec<BasicPrefix>>,
+    },
+    /// Reports are consistent, but no state is confirmed by quorum,
+    /// eg. because watchers are not synchronized yet.
+    NoQuorum,
+}
+
+/// Reconcile
+///
+/// Judges reports of watchers about the same identifier. Duplicity at
+/// lowest sn watchers disagree on is reported first. Otherwise, reported
+/// states are checked from the latest one and the first one vouched
+/// for by at least `quorum` of watchers is accepted.
+pub fn reconcile(reports: &[WatcherReport], quorum: Quorum) -> Result<Verdict, Error> {
+    let prefix = &reports
+        .first()
+        .ok_or_else(|| Error::SemanticError("No watcher reports".into()))?
+        .state
+        .prefix;
+    if reports.iter().any(|report| &report.state.prefix != prefix) {
+        return Err(Error::SemanticError(
+            "Watcher reports of different identifiers".into(),
+        ));
+    }
+
+    // Watchers of every digest reported at given sn.
+    let mut seen: BTreeMap<u64, Vec<(SelfAddressingPrefix, Vec<BasicPrefix>)>> = BTreeMap::new();
+    for report in reports {
+        for (sn, digest) in &report.events {
+            let versions = seen.entry(*sn).or_default();
+            match versions.iter_mut().find(|(d, _)| d == digest) {
+                Some((_, watchers)) => watchers.push(report.watcher.clone()),
+                None => versions.push((digest.clone(), vec![report.watcher.clone()])),
+            }
+        }
+    }
+    if let Some((sn, versions)) = seen.into_iter().find(|(_, versions)| versions.len() > 1) {
+        let (digests, watchers) = versions.into_iter().unzip();
+        return Ok(Verdict::Duplicity {
+            evidence: DuplicityEvidence {
+                prefix: prefix.clone(),
+                sn,
+                digests,
+            },
+            watchers,
+        });
+    }
+
+    let mut candidates: Vec<&IdentifierState> = reports.iter().map(|r| &r.state).collect();
+    candidates.sort_by_key(|state| Reverse(state.sn));
+    candidates.dedup_by_key(|state| state.sn);
+    let required = quorum.required(reports.len());
+    for state in candidates {
+        let watchers: Vec<_> = reports
+            .iter()
+            .filter(|report| report.event_digest(state.sn) == Some(&state.last_event_digest))
+            .map(|report| report.watcher.clone())
+            .collect();
+        if watchers.len() >= required {
+            return Ok(Verdict::Accepted {
+                state: Box::new(state.clone()),
+                watchers,
+            });
+        }
+    }
+    Ok(Verdict::NoQuorum)
+}
+
+#[test]
+fn test_reconcile() -> Result<(), Error> {
+    use crate::{
+        derivation::basic::Basic,
+        event::sections::seal::{DigestSeal, Seal},
+        event_message::test_fixture::{keypair_from_seed, TestIdentityFixture},
+    };
+
+    let watcher = |i| Basic::Ed25519NT.derive(keypair_from_seed(b"watcher", i).0);
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    let prefix = alice.prefix().clone();
+    alice.rotate()?;
+    let lagging_kel = alice.kel()?;
+    alice.rotate()?;
+
+    let full = WatcherReport::from_kel(watcher(0), &prefix, &alice.kel()?)?;
+    let ksn = WatcherReport::from_state(watcher(1), full.state.clone());
+    let lagging = WatcherReport::from_kel(watcher(2), &prefix, &lagging_kel)?;
+    assert_eq!(lagging.state.sn, 1);
+    let reports = vec![full.clone(), ksn, lagging.clone()];
+
+    assert_eq!(
+        reconcile(&reports, Quorum::Majority)?,
+        Verdict::Accepted {
+            state: Box::new(full.state.clone()),
+            watchers: vec![watcher(0), watcher(1)],
+        }
+    );
+    // Watcher which sent KEL vouches for earlier states too, but the
+    // one which sent only key state notice doesn't.
+    assert_eq!(reconcile(&reports, Quorum::Unanimous)?, Verdict::NoQuorum);
+    assert_eq!(
+        reconcile(&[full.clone(), lagging.clone()], Quorum::Unanimous)?,
+        Verdict::Accepted {
+            state: Box::new(lagging.state.clone()),
+            watchers: vec![watcher(0), watcher(2)],
+        }
+    );
+
+    // Other version of alice's KEL with interaction event at sn 1.
+    let mut forked = TestIdentityFixture::new(b"alice")?;
+    let ixn = forked.interact(vec![Seal::Digest(DigestSeal {
+        dig: "EZAoTNZH3ULvaU6Z-i0d8JJR2nmwyYAfSVPzhzS6b5CM".parse()?,
+    })])?;
+    let forked = WatcherReport::from_kel(watcher(3), &prefix, &forked.kel()?)?;
+    match reconcile(&[full.clone(), lagging, forked], Quorum::Majority)? {
+        Verdict::Duplicity { evidence, watchers } => {
+            assert_eq!(evidence.sn, 1);
+            assert_eq!(
+                evidence.digests,
+                vec![
+                    full.event_digest(1).unwrap().clone(),
+                    ixn.event_message.get_digest()
+                ]
+            );
+            assert_eq!(
+                watchers,
+                vec![vec![watcher(0), watcher(2)], vec![watcher(3)]]
+            );
+        }
+        verdict => panic!("Unexpected verdict: {:?}", verdict),
+    }
+
+    // Reports have to be about the same identifier.
+    let bob = TestIdentityFixture::new(b"bob")?;
+    let bob = WatcherReport::from_kel(watcher(4), bob.prefix(), &bob.kel()?)?;
+    assert!(reconcile(&[full, bob], Quorum::Majority).is_err());
+    assert!(reconcile(&[], Quorum::Majority).is_err());
+
+    Ok(())
+}
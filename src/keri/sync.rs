@@ -0,0 +1,195 @@
+//! KEL Synchronization
+//!
+//! Client side of incremental KEL exchange. Instead of fetching the
+//! whole KEL on every contact, key state of the identifier is asked
+//! first (`ksn` query) and only events missing locally are requested
+//! with `log` query starting from the first unknown sn.
+
+use std::convert::TryFrom;
+
+use super::Keri;
+use crate::{
+    error::Error,
+    event::EventMessage,
+    event_message::{
+        event_msg_builder::QueryBuilder,
+        signed_event_message::{Message, SignedEventMessage},
+    },
+    event_parsing::{message::signed_event_stream, SignedEventData},
+    prefix::{IdentifierPrefix, Prefix},
+    query::{
+        query_event::{QueryEvent, SignedQuery},
+        Route,
+    },
+    signer::KeyManager,
+    state::IdentifierState,
+    transport::Transport,
+};
+
+/// Events applied to local copy of KEL by synchronization.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncDelta {
+    pub prefix: IdentifierPrefix,
+    /// Accepted events, in order.
+    pub events: Vec<SignedEventMessage>,
+    /// State of the identifier after synchronization.
+    pub state: Option<IdentifierState>,
+}
+
+impl<K: KeyManager> Keri<K> {
+    /// Sync KEL
+    ///
+    /// Brings local copy of KEL of `prefix` up to date with the one
+    /// known to `peer`. Key state notice of the peer only tells which
+    /// events are missing, they are accepted after regular validation.
+    /// Fails if the peer reports other event at sn known locally.
+    pub fn sync_kel(
+        &self,
+        peer: &IdentifierPrefix,
+        prefix: &IdentifierPrefix,
+        transport: &impl Transport,
+    ) -> Result<SyncDelta, Error> {
+        let local = self.processor.compute_state(prefix)?;
+        let remote = self.query_remote_state(peer, prefix, transport)?;
+        let from_sn = match &local {
+            Some(local) if local.sn >= remote.sn => {
+                let known = self
+                    .processor
+                    .get_event_at_sn(prefix, remote.sn)?
+                    .map(|event| event.signed_event_message.event_message.get_digest());
+                if known.as_ref() != Some(&remote.last_event_digest) {
+                    return Err(Error::SemanticError(format!(
+                        "KEL of {} known to the peer diverges at sn {}",
+                        prefix.to_str(),
+                        remote.sn
+                    )));
+                }
+                return Ok(SyncDelta {
+                    prefix: prefix.clone(),
+                    events: vec![],
+                    state: Some(local.clone()),
+                });
+            }
+            Some(local) => local.sn + 1,
+            None => 0,
+        };
+
+        let qry = QueryBuilder::default()
+            .with_route(Route::Log)
+            .with_prefix(prefix)
+            .with_sn(from_sn)
+            .build()?;
+        let kel = self.send_query(peer, qry, transport)?;
+        let mut events = vec![];
+        for message in parse(&kel)? {
+            if let Message::Event(event) = Message::try_from(message)? {
+                let event_message = &event.event_message.event;
+                // Peer may not support sn of `log` query and send
+                // whole KEL.
+                if &event_message.get_prefix() == prefix && event_message.get_sn() >= from_sn {
+                    self.processor.process(Message::Event(event.clone()))?;
+                    events.push(event);
+                }
+            }
+        }
+        Ok(SyncDelta {
+            prefix: prefix.clone(),
+            events,
+            state: self.processor.compute_state(prefix)?,
+        })
+    }
+
+    fn query_remote_state(
+        &self,
+        peer: &IdentifierPrefix,
+        prefix: &IdentifierPrefix,
+        transport: &impl Transport,
+    ) -> Result<IdentifierState, Error> {
+        let qry = QueryBuilder::default()
+            .with_route(Route::Ksn)
+            .with_prefix(prefix)
+            .build()?;
+        let response = self.send_query(peer, qry, transport)?;
+        parse(&response)?
+            .into_iter()
+            .find_map(|message| match Message::try_from(message) {
+                Ok(Message::KeyStateNotice(rpy)) => rpy.reply.event.get_state(),
+                _ => None,
+            })
+            .filter(|state| &state.prefix == prefix)
+            .ok_or_else(|| Error::SemanticError("No key state in peer's response".into()))
+    }
+
+    fn send_query(
+        &self,
+        peer: &IdentifierPrefix,
+        qry: EventMessage<QueryEvent>,
+        transport: &impl Transport,
+    ) -> Result<Vec<u8>, Error> {
+        let signatures = self.sign(&qry.serialize()?)?;
+        let qry = SignedQuery::new(qry, self.prefix.clone(), signatures);
+        transport.send_message(peer, &SignedEventData::from(qry).to_cesr()?)
+    }
+}
+
+fn parse(stream: &[u8]) -> Result<Vec<SignedEventData>, Error> {
+    Ok(signed_event_stream(stream)
+        .map_err(|e| Error::DeserializeError(e.to_string()))?
+        .1)
+}
+
+#[test]
+fn test_sync_kel() -> Result<(), Error> {
+    use super::witness::Witness;
+    use crate::{database::sled::SledEventDatabase, signer::CryptoBox};
+    use std::sync::{Arc, Mutex};
+    use tempfile::Builder;
+
+    // Transport delivering messages directly to the witness.
+    struct MockTransport<'a> {
+        witness: &'a Witness,
+    }
+
+    impl<'a> Transport for MockTransport<'a> {
+        fn send_message(&self, _to: &IdentifierPrefix, msg: &[u8]) -> Result<Vec<u8>, Error> {
+            self.witness.respond(msg)
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let witness = Witness::new(root.path().join("witness").as_path())?;
+    let peer = IdentifierPrefix::Basic(witness.prefix.clone());
+    let transport = MockTransport { witness: &witness };
+    let controller = |name: &str| -> Result<Keri<CryptoBox>, Error> {
+        let db = Arc::new(SledEventDatabase::new(root.path().join(name).as_path())?);
+        let mut keri = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+        witness.processor.process_event(&keri.incept(None)?)?;
+        Ok(keri)
+    };
+    let mut alice = controller("alice")?;
+    let bob = controller("bob")?;
+
+    let delta = bob.sync_kel(&peer, alice.prefix(), &transport)?;
+    assert_eq!(delta.events.len(), 1);
+    assert_eq!(delta.state, alice.get_state()?);
+
+    witness.processor.process_event(&alice.rotate()?)?;
+    witness.processor.process_event(&alice.make_ixn(None)?)?;
+
+    // Only missing events are fetched.
+    let delta = bob.sync_kel(&peer, alice.prefix(), &transport)?;
+    let sns: Vec<_> = delta
+        .events
+        .iter()
+        .map(|event| event.event_message.event.get_sn())
+        .collect();
+    assert_eq!(sns, vec![1, 2]);
+    assert_eq!(delta.state, alice.get_state()?);
+
+    // Nothing to fetch when KEL is up to date.
+    let delta = bob.sync_kel(&peer, alice.prefix(), &transport)?;
+    assert!(delta.events.is_empty());
+    assert_eq!(delta.state, alice.get_state()?);
+
+    Ok(())
+}
@@ -180,7 +180,7 @@ fn test_qry_rpy() -> Result<(), Error> {
         keri::witness::Witness,
         prefix::AttachedSignaturePrefix,
         query::{
-            query::{QueryEvent, SignedQuery},
+            query_event::{QueryEvent, SignedQuery},
             ReplyType, Route,
         },
         signer::KeyManager,
@@ -251,11 +251,11 @@ fn test_qry_rpy() -> Result<(), Error> {
     match rep {
         ReplyType::Rep(rep) => {
             assert_eq!(
-                &rep.reply.event.get_state(),
+                &rep.reply.event.get_state().unwrap(),
                 &alice.get_state().unwrap().unwrap()
             )
         }
-        ReplyType::Kel(_) => assert!(false),
+        _ => assert!(false),
     }
 
     Ok(())
@@ -344,3 +344,1243 @@ pub fn test_key_state_notice() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_next_keys_custody_proof() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing,
+        keri::custody::CustodyProof,
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let mut keri = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    keri.incept(None)?;
+
+    let challenge = b"custody audit challenge";
+    let proof = keri.prove_next_keys_custody(challenge)?;
+    let state = keri.get_state()?.unwrap();
+    assert!(proof.verify(&state)?);
+
+    // Challenge signed with current keys doesn't prove next keys custody.
+    let mut forged_proof = proof.clone();
+    {
+        let km = keri.key_manager();
+        let km = km.lock().unwrap();
+        forged_proof.signatures = vec![AttachedSignaturePrefix::new(
+            km.signature_type(),
+            km.sign(&CustodyProof::commitment(challenge, &proof.digest))?,
+            0,
+        )];
+    }
+    assert!(!forged_proof.verify(&state)?);
+
+    // Proof is bound to the last event digest.
+    let mut replayed_proof = proof.clone();
+    replayed_proof.digest = state.current.commit(&SelfAddressing::Blake3_256);
+    assert!(replayed_proof.verify(&state).is_err());
+
+    // Proof survives serialization.
+    let serialized = serde_json::to_string(&proof).unwrap();
+    assert_eq!(
+        serde_json::from_str::<CustodyProof>(&serialized).unwrap(),
+        proof
+    );
+
+    // After rotation old proof refers to outdated state.
+    keri.rotate()?;
+    let state = keri.get_state()?.unwrap();
+    assert!(proof.verify(&state).is_err());
+    assert!(keri.prove_next_keys_custody(challenge)?.verify(&state)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_witnessed_inception() -> Result<(), Error> {
+    use crate::{
+        keri::test_witness::{test_witnesses, TestWitness},
+        prefix::BasicPrefix,
+        signer::CryptoBox,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let witnesses = test_witnesses(3, root.path())?;
+    let witness_prefixes: Vec<BasicPrefix> = witnesses.iter().map(|w| w.prefix.clone()).collect();
+
+    // Witnesses created from the same index have the same identifier.
+    let other_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    assert_eq!(
+        TestWitness::with_index(1, other_root.path())?.prefix,
+        witness_prefixes[1]
+    );
+
+    let db = Arc::new(SledEventDatabase::new(root.path().join("controller").as_path()).unwrap());
+    let mut keri = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let icp = keri.incept(Some(witness_prefixes))?;
+
+    for witness in &witnesses {
+        let receipt = witness.receipt(&icp)?;
+        keri.processor
+            .process(Message::NontransferableRct(receipt))?;
+    }
+    let receipts = db
+        .get_receipts_nt(&keri.prefix)
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(receipts.len(), 3);
+
+    // Witness not designated in inception event refuses to receipt it.
+    let outsider = TestWitness::with_index(7, &root.path().join("outsider"))?;
+    assert!(outsider.receipt(&icp).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_cooperative_delegation() -> Result<(), Error> {
+    use crate::{
+        event::{event_data::EventData, sections::seal::SourceSeal},
+        event_message::signed_event_message::SignedEventMessage,
+        event_parsing::SignedEventData,
+        prefix::IdentifierPrefix,
+        signer::CryptoBox,
+        transport::Transport,
+    };
+    use tempfile::Builder;
+
+    // Transport delivering messages directly to delegator's instance.
+    struct MockTransport<'a> {
+        delegator: &'a Keri<CryptoBox>,
+    }
+
+    impl<'a> Transport for MockTransport<'a> {
+        fn send_message(&self, to: &IdentifierPrefix, msg: &[u8]) -> Result<Vec<u8>, Error> {
+            if to != self.delegator.prefix() {
+                return Err(Error::TransportError(format!("Unknown peer: {:?}", to)));
+            }
+            self.delegator.respond_to_delegation_request(msg, |_| true)
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let delegator_db =
+        Arc::new(SledEventDatabase::new(root.path().join("delegator").as_path()).unwrap());
+    let delegatee_db =
+        Arc::new(SledEventDatabase::new(root.path().join("delegatee").as_path()).unwrap());
+
+    let mut delegator = Keri::new(delegator_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    delegator.incept(None)?;
+    let mut delegatee = Keri::new(delegatee_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+
+    let dip = delegatee.request_delegation(
+        &delegator.prefix().clone(),
+        &MockTransport {
+            delegator: &delegator,
+        },
+    )?;
+    let delegated_prefix = dip.event_message.event.get_prefix();
+    assert_eq!(delegatee.prefix(), &delegated_prefix);
+
+    // Both instances accepted delegated KEL.
+    for keri in [&delegator, &delegatee] {
+        let state = keri.processor.compute_state(&delegated_prefix)?.unwrap();
+        assert_eq!(state.delegator, Some(delegator.prefix().clone()));
+        assert_eq!(state.last_event_digest, dip.event_message.get_digest());
+    }
+
+    // Delegator anchored delegated event in its KEL.
+    let delegator_state = delegatee
+        .processor
+        .compute_state(delegator.prefix())?
+        .unwrap();
+    assert_eq!(delegator_state.sn, 1);
+    assert_eq!(
+        delegator
+            .processor
+            .get_delegation_chain(&delegated_prefix)?,
+        vec![delegator.prefix().clone()]
+    );
+    for keri in [&delegator, &delegatee] {
+        assert_eq!(
+            keri.processor.get_delegates(delegator.prefix())?,
+            vec![delegated_prefix.clone()]
+        );
+    }
+    assert!(delegator
+        .processor
+        .get_delegates(&delegated_prefix)?
+        .is_empty());
+
+    // Source seal of anchoring event is found for delegated event.
+    let source_seal = SourceSeal::new(1, delegator_state.last_event_digest.clone());
+    assert_eq!(
+        delegatee.processor.find_source_seal(&dip.event_message)?,
+        Some(source_seal.clone())
+    );
+    let unsealed = SignedEventMessage::new(&dip.event_message, dip.signatures.clone(), None);
+    assert_eq!(
+        delegator
+            .processor
+            .attach_source_seal(unsealed)?
+            .delegator_seal,
+        Some(source_seal)
+    );
+
+    // Nothing is anchored if delegator doesn't consent to delegation.
+    let refused_dip = Keri::new(
+        Arc::new(SledEventDatabase::new(root.path().join("refused").as_path()).unwrap()),
+        Arc::new(Mutex::new(CryptoBox::new()?)),
+    )?
+    .incept_delegated(delegator.prefix())?;
+    let refused_prefix = refused_dip.event_message.event.get_prefix();
+    let request = SignedEventData::from(&refused_dip).to_cesr()?;
+    assert!(matches!(
+        delegator.respond_to_delegation_request(&request, |request| {
+            request.delegate() != refused_prefix
+        }),
+        Err(Error::DelegationRefused(ref id)) if id == &refused_prefix
+    ));
+    assert_eq!(delegator.get_state()?.unwrap().sn, 1);
+    assert!(delegator
+        .processor
+        .compute_state(&refused_prefix)?
+        .is_none());
+
+    // Delegator refuses to anchor event delegated to other identifier.
+    let mut other = Keri::new(
+        Arc::new(SledEventDatabase::new(root.path().join("other").as_path()).unwrap()),
+        Arc::new(Mutex::new(CryptoBox::new()?)),
+    )?;
+    other.incept(None)?;
+    let foreign_dip = delegatee.incept_delegated(other.prefix())?;
+    assert!(matches!(
+        foreign_dip.event_message.event.get_event_data(),
+        EventData::Dip(_)
+    ));
+    assert!(delegator.check_delegation_request(&foreign_dip).is_err());
+    assert!(foreign_dip.delegator_seal.is_none());
+    assert!(matches!(
+        delegator.processor.attach_source_seal(foreign_dip),
+        Err(Error::MissingSourceSeal { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_establishment_only_controller() -> Result<(), Error> {
+    use crate::{
+        derivation::basic::Basic,
+        event::sections::ConfigTrait,
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        prefix::{AttachedSignaturePrefix, IdentifierPrefix},
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let km = CryptoBox::new()?;
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .with_inception_configuration(vec![ConfigTrait::EstablishmentOnly])
+        .build()?;
+    let signed = icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            km.signature_type(),
+            km.sign(&icp.serialize()?)?,
+            0,
+        )],
+        None,
+    );
+    let prefix = icp.event.get_prefix();
+    let controller = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(km)))?;
+    controller.processor.process(Message::Event(signed))?;
+    let mut controller = Keri::open(db, controller.key_manager(), prefix.clone())?;
+
+    // Interaction events of establishment only identifier are refused,
+    // also the ones which aren't processed before being stored.
+    assert!(matches!(
+        controller.make_ixn(Some("data")),
+        Err(Error::EstablishmentOnly(ref id)) if id == &prefix
+    ));
+    let peer: IdentifierPrefix = "EsZuhYAPBDnexP3SOl9YsGvWBrYkjYcRjomUYmCcLAYY".parse()?;
+    assert!(matches!(
+        controller.interact(peer),
+        Err(Error::EstablishmentOnly(ref id)) if id == &prefix
+    ));
+    assert_eq!(controller.get_state()?.unwrap().sn, 0);
+
+    // Establishment events are still allowed.
+    controller.rotate()?;
+    assert_eq!(controller.get_state()?.unwrap().sn, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_do_not_delegate_controller() -> Result<(), Error> {
+    use crate::{
+        derivation::basic::Basic,
+        event::sections::ConfigTrait,
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path().join("delegator").as_path()).unwrap());
+    let km = CryptoBox::new()?;
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .with_inception_configuration(vec![ConfigTrait::DoNotDelegate])
+        .build()?;
+    let signed = icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            km.signature_type(),
+            km.sign(&icp.serialize()?)?,
+            0,
+        )],
+        None,
+    );
+    let prefix = icp.event.get_prefix();
+    let delegator = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(km)))?;
+    delegator.processor.process(Message::Event(signed))?;
+    let delegator = Keri::open(db, delegator.key_manager(), prefix.clone())?;
+
+    let delegatee = Keri::new(
+        Arc::new(SledEventDatabase::new(root.path().join("delegatee").as_path()).unwrap()),
+        Arc::new(Mutex::new(CryptoBox::new()?)),
+    )?;
+    let dip = delegatee.incept_delegated(&prefix)?;
+
+    // Delegator refuses to approve delegation before anchoring it.
+    assert!(matches!(
+        delegator.check_delegation_request(&dip),
+        Err(Error::DelegationNotAllowed(ref id)) if id == &prefix
+    ));
+    assert!(matches!(
+        delegator.make_group_anchor(&prefix, &dip.event_message),
+        Err(Error::DelegationNotAllowed(ref id)) if id == &prefix
+    ));
+    assert_eq!(delegator.get_state()?.unwrap().sn, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_group_delegation() -> Result<(), Error> {
+    use crate::{
+        derivation::basic::Basic,
+        event::sections::threshold::SignatureThreshold,
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        keri::test_witness::test_witnesses,
+        prefix::BasicPrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let witnesses = test_witnesses(2, root.path())?;
+    let witness_prefixes: Vec<BasicPrefix> = witnesses.iter().map(|w| w.prefix.clone()).collect();
+    let db = Arc::new(SledEventDatabase::new(root.path().join("controller").as_path()).unwrap());
+    let alice = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let bob = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let carol = Keri::new(Arc::clone(&db), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+
+    // Group of Alice and Bob, which events have to be signed by both
+    // of them and receipted by both witnesses.
+    let (keys, next_keys): (Vec<BasicPrefix>, Vec<BasicPrefix>) = [&alice, &bob]
+        .iter()
+        .map(|member| {
+            let km = member.key_manager();
+            let km = km.lock().unwrap();
+            (
+                Basic::Ed25519.derive(km.public_key()),
+                Basic::Ed25519.derive(km.next_public_key()),
+            )
+        })
+        .unzip();
+    let group_icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(keys)
+        .with_next_keys(next_keys)
+        .with_threshold(&SignatureThreshold::Simple(2))
+        .with_next_threshold(&SignatureThreshold::Simple(2))
+        .with_witnesses(&witness_prefixes)
+        .with_witness_threshold(2)
+        .build()?;
+    let group_icp = group_icp.sign(
+        vec![
+            alice.sign_group_event(&group_icp)?,
+            bob.sign_group_event(&group_icp)?,
+        ],
+        None,
+    );
+    alice.processor.process(Message::Event(group_icp.clone()))?;
+    for witness in &witnesses {
+        let receipt = witness.receipt(&group_icp)?;
+        alice
+            .processor
+            .process(Message::NontransferableRct(receipt))?;
+    }
+    let group = group_icp.event_message.event.get_prefix();
+
+    let dip = carol.incept_delegated(&group)?;
+    let anchor = alice.make_group_anchor(&group, &dip.event_message)?;
+
+    // Anchoring event signed by one member only isn't accepted.
+    let partially_signed = anchor.sign(vec![alice.sign_group_event(&anchor)?], None);
+    assert!(matches!(
+        alice.approve_group_delegation(&dip, &partially_signed),
+        Err(Error::NotEnoughSigsError)
+    ));
+
+    // Fully signed anchoring event has to be witnessed as well.
+    let signed_anchor = anchor.sign(
+        vec![
+            alice.sign_group_event(&anchor)?,
+            bob.sign_group_event(&anchor)?,
+        ],
+        None,
+    );
+    assert!(matches!(
+        alice.approve_group_delegation(&dip, &signed_anchor),
+        Err(Error::NotEnoughReceiptsError)
+    ));
+    let receipt = witnesses[0].receipt(&signed_anchor)?;
+    alice
+        .processor
+        .process(Message::NontransferableRct(receipt))?;
+    assert!(matches!(
+        bob.approve_group_delegation(&dip, &signed_anchor),
+        Err(Error::NotEnoughReceiptsError)
+    ));
+    let receipt = witnesses[1].receipt(&signed_anchor)?;
+    alice
+        .processor
+        .process(Message::NontransferableRct(receipt))?;
+
+    let approved = bob.approve_group_delegation(&dip, &signed_anchor)?;
+    let delegated = approved.event_message.event.get_prefix();
+    let state = carol.processor.compute_state(&delegated)?.unwrap();
+    assert_eq!(state.delegator, Some(group.clone()));
+    assert_eq!(
+        carol.processor.get_delegation_chain(&delegated)?,
+        vec![group]
+    );
+
+    // Only group members can sign group events.
+    assert!(carol.sign_group_event(&anchor).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_witness_component() -> Result<(), Error> {
+    use crate::{
+        derivation::basic::Basic,
+        event_parsing::SignedEventData,
+        keri::{test_witness::test_witnesses, witness::Witness},
+        prefix::BasicPrefix,
+        signer::CryptoBox,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let witnesses = test_witnesses(2, root.path())?;
+    let witness_prefixes: Vec<BasicPrefix> = witnesses.iter().map(|w| w.prefix.clone()).collect();
+
+    let db = Arc::new(SledEventDatabase::new(root.path().join("controller").as_path()).unwrap());
+    let controller_key_manager = Arc::new(Mutex::new(CryptoBox::new()?));
+    let mut controller = Keri::new(Arc::clone(&db), Arc::clone(&controller_key_manager))?;
+    let icp = controller.incept(Some(witness_prefixes))?;
+    let icp_stream = SignedEventData::from(&icp).to_cesr()?;
+
+    // Each witness receipts controller's event.
+    let receipts = witnesses
+        .iter()
+        .map(|witness| witness.respond(&icp_stream))
+        .collect::<Result<Vec<_>, _>>()?;
+    for receipt in &receipts {
+        for message in signed_event_stream(receipt).unwrap().1 {
+            controller.processor.process(Message::try_from(message)?)?;
+        }
+    }
+    assert_eq!(
+        db.get_receipts_nt(controller.prefix())
+            .unwrap()
+            .flat_map(|rct| rct.couplets)
+            .count(),
+        2
+    );
+
+    // First witness stores receipt of the second one.
+    witnesses[0].respond(&receipts[1])?;
+    let stored_couplets: Vec<_> = witnesses[0]
+        .processor
+        .db
+        .get_receipts_nt(controller.prefix())
+        .unwrap()
+        .flat_map(|rct| rct.couplets)
+        .map(|(witness, _)| witness)
+        .collect();
+    assert!(stored_couplets.contains(&witnesses[1].prefix));
+
+    // Witness not designated by controller doesn't receipt its events.
+    let outsider = Witness::new(root.path().join("outsider").as_path())?;
+    assert_eq!(outsider.prefix.derivation, Basic::Ed25519NT);
+    assert!(outsider.respond(&icp_stream).is_err());
+
+    // Witness answers queries about receipted KEL.
+    #[cfg(feature = "query")]
+    {
+        use crate::{
+            derivation::{self_addressing::SelfAddressing, self_signing::SelfSigning},
+            event::SerializationFormats,
+            prefix::AttachedSignaturePrefix,
+            query::{
+                query_event::{QueryEvent, SignedQuery},
+                Route,
+            },
+            signer::KeyManager,
+        };
+
+        let qry = QueryEvent::new_query(
+            Route::Log,
+            controller.prefix(),
+            SerializationFormats::JSON,
+            &SelfAddressing::Blake3_256,
+        )?;
+        let signature = AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            controller_key_manager
+                .lock()
+                .unwrap()
+                .sign(&qry.serialize()?)?,
+            0,
+        );
+        let qry_stream = SignedEventData::from(SignedQuery::new(
+            qry,
+            controller.prefix().clone(),
+            vec![signature],
+        ))
+        .to_cesr()?;
+        let kel = witnesses[0].respond(&qry_stream)?;
+        assert_eq!(kel, controller.get_kerl()?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_watcher_duplicity() -> Result<(), Error> {
+    use crate::{
+        derivation::{self_addressing::SelfAddressing, self_signing::SelfSigning},
+        event::sections::seal::{DigestSeal, Seal},
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        event_parsing::SignedEventData,
+        keri::watcher::Watcher,
+        prefix::{AttachedSignaturePrefix, IdentifierPrefix},
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path().join("controller").as_path()).unwrap());
+    let mut controller = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let icp = controller.incept(None)?;
+    let ixn = controller.make_ixn(Some("first"))?;
+
+    // Conflicting version of the interaction event.
+    let forked = EventMsgBuilder::new(EventTypeTag::Ixn)
+        .with_prefix(controller.prefix())
+        .with_sn(1)
+        .with_previous_event(&icp.event_message.get_digest())
+        .with_seal(vec![Seal::Digest(DigestSeal {
+            dig: SelfAddressing::Blake3_256.derive(b"second"),
+        })])
+        .build()?;
+    let signature = controller
+        .key_manager()
+        .lock()
+        .unwrap()
+        .sign(&forked.serialize()?)?;
+    let forked = forked.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+        None,
+    );
+
+    let watcher = Watcher::new(root.path().join("watcher").as_path())?;
+    let source = IdentifierPrefix::Basic(watcher.prefix.clone());
+    watcher.watch(controller.prefix(), &source)?;
+
+    let stream = [
+        SignedEventData::from(&icp).to_cesr()?,
+        SignedEventData::from(&ixn).to_cesr()?,
+    ]
+    .concat();
+    assert!(watcher.process(&source, &stream)?.is_empty());
+    assert_eq!(
+        watcher
+            .processor
+            .db
+            .get_subscription(controller.prefix(), &source)
+            .unwrap()
+            .last_sn,
+        Some(1)
+    );
+
+    // Seeing the same event again isn't duplicity.
+    assert!(watcher
+        .process(&source, &SignedEventData::from(&ixn).to_cesr()?)?
+        .is_empty());
+
+    let attestations = watcher.process(&source, &SignedEventData::from(&forked).to_cesr()?)?;
+    assert_eq!(attestations.len(), 1);
+    let attestation = &attestations[0];
+    assert_eq!(attestation.evidence.sn, 1);
+    assert_eq!(
+        attestation.evidence.digests,
+        vec![
+            ixn.event_message.get_digest(),
+            forked.event_message.get_digest()
+        ]
+    );
+    let prior = watcher
+        .processor
+        .compute_state_at_sn(controller.prefix(), 0)?
+        .unwrap();
+    assert!(attestation.verify(&prior)?);
+
+    // Rejected version is seen, but first seen one stays in KEL.
+    assert_eq!(watcher.get_seen_events(controller.prefix(), 1).len(), 2);
+    assert_eq!(
+        watcher
+            .get_state(controller.prefix())?
+            .unwrap()
+            .last_event_digest,
+        ixn.event_message.get_digest()
+    );
+
+    // Tampered evidence doesn't verify.
+    let mut tampered = attestation.clone();
+    tampered.evidence.digests.reverse();
+    assert!(!tampered.verify(&prior)?);
+
+    // Tampered copy of event is seen, but not attested as duplicity.
+    let mut forged = forked.clone();
+    forged.event_message = EventMsgBuilder::new(EventTypeTag::Ixn)
+        .with_prefix(controller.prefix())
+        .with_sn(1)
+        .with_previous_event(&icp.event_message.get_digest())
+        .with_seal(vec![Seal::Digest(DigestSeal {
+            dig: SelfAddressing::Blake3_256.derive(b"third"),
+        })])
+        .build()?;
+    let attestations = watcher.process(&source, &SignedEventData::from(&forged).to_cesr()?)?;
+    assert!(attestations.iter().all(|attestation| !attestation
+        .evidence
+        .digests
+        .contains(&forged.event_message.get_digest())));
+    assert_eq!(watcher.get_seen_events(controller.prefix(), 1).len(), 3);
+    let mut tampered = attestation.clone();
+    tampered.events[1] = forged.clone();
+    tampered.evidence.digests[1] = forged.event_message.get_digest();
+    assert!(!tampered.verify(&prior)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_controller() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing,
+        event::{
+            event_data::EventData,
+            sections::seal::{DigestSeal, EventSeal, Seal},
+        },
+        event_parsing::SignedEventData,
+        keri::Controller,
+        signer::CryptoBox,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice_db = Arc::new(SledEventDatabase::new(root.path().join("alice").as_path()).unwrap());
+    let bob_db = Arc::new(SledEventDatabase::new(root.path().join("bob").as_path()).unwrap());
+    let mut alice = Controller::new(alice_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let mut bob = Controller::new(bob_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    alice.incept(None)?;
+    bob.incept(None)?;
+
+    let data = b"some data";
+    let signatures = alice.sign(data)?;
+    // Signer's KEL is unknown to bob yet.
+    assert!(bob.verify(alice.prefix(), data, &signatures).is_err());
+    bob.respond(&alice.get_kerl()?.unwrap())?;
+    assert!(bob.verify(alice.prefix(), data, &signatures)?);
+    assert!(!bob.verify(alice.prefix(), b"other data", &signatures)?);
+
+    // Anchored seals are kept in controller's KEL.
+    let seal = Seal::Digest(DigestSeal {
+        dig: SelfAddressing::Blake3_256.derive(data),
+    });
+    let seals = vec![seal];
+    let ixn = alice.anchor(&seals)?;
+    match ixn.event_message.event.get_event_data() {
+        EventData::Ixn(ixn) => assert_eq!(ixn.data, seals),
+        _ => panic!("Not an interaction event"),
+    };
+    assert_eq!(alice.get_state()?.unwrap().sn, 1);
+
+    // SAIDs of application data are anchored as digest seals.
+    let digests = vec![
+        SelfAddressing::Blake3_256.derive(b"document"),
+        SelfAddressing::SHA3_256.derive(b"other document"),
+    ];
+    let digests_ixn = alice.anchor_digests(&digests)?;
+    match digests_ixn.event_message.event.get_event_data() {
+        EventData::Ixn(ixn) => assert_eq!(
+            ixn.data,
+            digests
+                .iter()
+                .map(|dig| Seal::Digest(DigestSeal { dig: dig.clone() }))
+                .collect::<Vec<_>>()
+        ),
+        _ => panic!("Not an interaction event"),
+    };
+    assert_eq!(alice.get_state()?.unwrap().sn, 2);
+
+    // Batch of digests is anchored with single root seal, and each of
+    // them is proven to be included in it.
+    let batch: Vec<_> = (0..5u8)
+        .map(|i| SelfAddressing::Blake3_256.derive(&[i]))
+        .collect();
+    let (batch_ixn, tree) = alice.anchor_batch(&batch)?;
+    bob.respond(
+        &[
+            SignedEventData::from(&ixn).to_cesr()?,
+            SignedEventData::from(&digests_ixn).to_cesr()?,
+            SignedEventData::from(&batch_ixn).to_cesr()?,
+        ]
+        .concat(),
+    )?;
+    let anchoring = EventSeal {
+        prefix: alice.prefix().clone(),
+        sn: 3,
+        event_digest: batch_ixn.event_message.get_digest(),
+    };
+    for (index, digest) in batch.iter().enumerate() {
+        let proof = tree.proof(index).unwrap();
+        assert!(bob.processor.verify_inclusion(&anchoring, digest, &proof)?);
+    }
+    assert!(!bob
+        .processor
+        .verify_inclusion(&anchoring, &digests[0], &tree.proof(0).unwrap())?);
+
+    // After rotation signatures made with previous keys aren't valid.
+    let rot = alice.rotate()?;
+    bob.respond(&SignedEventData::from(&rot).to_cesr()?)?;
+    assert_eq!(bob.get_state_for_prefix(alice.prefix())?.unwrap().sn, 4);
+    assert!(!bob.verify(alice.prefix(), data, &signatures)?);
+    assert!(bob.verify(alice.prefix(), data, &alice.sign(data)?)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_custom_key_manager() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        keri::Controller,
+        keys::{PrivateKey, PublicKey},
+        signer::KeyManager,
+    };
+    use k256::ecdsa::SigningKey;
+    use tempfile::Builder;
+
+    // Key manager holding secp256k1 keys derived from consecutive seeds.
+    struct Secp256k1Box {
+        seed: u8,
+    }
+
+    impl Secp256k1Box {
+        fn key_pair(seed: u8) -> (PublicKey, PrivateKey) {
+            let sk = SigningKey::from_bytes(&[seed; 32]).unwrap();
+            (
+                PublicKey::new(sk.verifying_key().to_bytes().to_vec()),
+                PrivateKey::new(sk.to_bytes().to_vec()),
+            )
+        }
+    }
+
+    impl KeyManager for Secp256k1Box {
+        fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+            Self::key_pair(self.seed).1.sign_ecdsa(msg)
+        }
+        fn public_key(&self) -> PublicKey {
+            Self::key_pair(self.seed).0
+        }
+        fn next_public_key(&self) -> PublicKey {
+            Self::key_pair(self.seed + 1).0
+        }
+        fn sign_with_next(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+            Self::key_pair(self.seed + 1).1.sign_ecdsa(msg)
+        }
+        fn rotate(&mut self) -> Result<(), Error> {
+            self.seed += 1;
+            Ok(())
+        }
+        fn key_type(&self) -> Basic {
+            Basic::ECDSAsecp256k1
+        }
+        fn signature_type(&self) -> SelfSigning {
+            SelfSigning::ECDSAsecp256k1Sha256
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let mut controller = Controller::new(db, Arc::new(Mutex::new(Secp256k1Box { seed: 1 })))?;
+    controller.incept(None)?;
+    controller.rotate()?;
+
+    let state = controller.get_state()?.unwrap();
+    assert_eq!(state.sn, 1);
+    assert_eq!(
+        state.current.public_keys,
+        vec![Basic::ECDSAsecp256k1.derive(Secp256k1Box::key_pair(2).0)]
+    );
+    let data = b"some data";
+    let signatures = controller.sign(data)?;
+    assert_eq!(
+        signatures[0].signature.derivation,
+        SelfSigning::ECDSAsecp256k1Sha256
+    );
+    assert!(controller.verify(controller.prefix(), data, &signatures)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_secp256k1_controller() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_parsing::SignedEventData,
+        keri::Controller,
+        signer::CryptoBox,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice_db = Arc::new(SledEventDatabase::new(root.path().join("alice").as_path()).unwrap());
+    let bob_db = Arc::new(SledEventDatabase::new(root.path().join("bob").as_path()).unwrap());
+    let key_manager = CryptoBox::new_secp256k1()?;
+    let (current_seed, next_seed) = key_manager.seeds();
+    let mut alice = Controller::new(alice_db, Arc::new(Mutex::new(key_manager)))?;
+    let bob = Controller::new(bob_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+
+    let icp = alice.incept(None)?;
+    assert_eq!(
+        icp.signatures[0].signature.derivation,
+        SelfSigning::ECDSAsecp256k1Sha256
+    );
+    let rot = alice.rotate()?;
+    let ixn = alice.make_ixn(Some("data"))?;
+
+    // KEL with secp256k1 keys and signatures goes through CESR stream.
+    let kel = [&icp, &rot, &ixn]
+        .iter()
+        .map(|event| SignedEventData::from(*event).to_cesr())
+        .collect::<Result<Vec<_>, _>>()?
+        .concat();
+    bob.respond(&kel)?;
+    let state = bob.get_state_for_prefix(alice.prefix())?.unwrap();
+    assert_eq!(state.sn, 2);
+    assert_eq!(
+        state.current.public_keys,
+        vec![Basic::ECDSAsecp256k1
+            .derive(CryptoBox::from_seeds(&current_seed, &next_seed)?.next_pub_key)]
+    );
+
+    let data = b"some data";
+    assert!(bob.verify(alice.prefix(), data, &alice.sign(data)?)?);
+
+    Ok(())
+}
+
+#[cfg(feature = "pq")]
+#[test]
+fn test_pq_controller() -> Result<(), Error> {
+    use crate::{
+        derivation::self_signing::SelfSigning,
+        event_parsing::SignedEventData,
+        keri::Controller,
+        signer::{sphincs::SphincsBox, CryptoBox},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice_db = Arc::new(SledEventDatabase::new(root.path().join("alice").as_path()).unwrap());
+    let bob_db = Arc::new(SledEventDatabase::new(root.path().join("bob").as_path()).unwrap());
+    let mut alice = Controller::new(alice_db, Arc::new(Mutex::new(SphincsBox::new())))?;
+    let bob = Controller::new(bob_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+
+    let icp = alice.incept(None)?;
+    assert_eq!(
+        icp.signatures[0].signature.derivation,
+        SelfSigning::SphincsSha2128s
+    );
+    let ixn = alice.make_ixn(None)?;
+    let rot = alice.rotate()?;
+
+    // Large signatures are parsed from CESR stream.
+    let kel = [
+        SignedEventData::from(&icp).to_cesr()?,
+        SignedEventData::from(&ixn).to_cesr()?,
+        SignedEventData::from(&rot).to_cesr()?,
+    ]
+    .concat();
+    bob.respond(&kel)?;
+    assert_eq!(bob.get_state_for_prefix(alice.prefix())?.unwrap().sn, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_encrypt_to() -> Result<(), Error> {
+    use crate::{event_parsing::SignedEventData, keri::Controller, signer::CryptoBox};
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice_db = Arc::new(SledEventDatabase::new(root.path().join("alice").as_path()).unwrap());
+    let bob_db = Arc::new(SledEventDatabase::new(root.path().join("bob").as_path()).unwrap());
+    let mut alice = Controller::new(alice_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let mut bob = Controller::new(bob_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    alice.incept(None)?;
+    bob.incept(None)?;
+
+    let data = b"exn payload";
+    alice.respond(&bob.get_kerl()?.unwrap())?;
+    let sealed = alice.encrypt_to(bob.prefix(), data)?;
+    assert_eq!(bob.decrypt(&sealed)?, data);
+    assert!(alice.decrypt(&sealed).is_err());
+
+    // After rotation data is sealed for new keys.
+    let rot = bob.rotate()?;
+    assert!(bob.decrypt(&sealed).is_err());
+    alice.respond(&SignedEventData::from(&rot).to_cesr()?)?;
+    assert_eq!(bob.decrypt(&alice.encrypt_to(bob.prefix(), data)?)?, data);
+
+    Ok(())
+}
+
+#[test]
+fn test_mixed_format_stream() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event::SerializationFormats,
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        event_parsing::SignedEventData,
+        prefix::AttachedSignaturePrefix,
+        processor::EventProcessor,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(db);
+
+    // Inception events of three identifiers, each in other format.
+    let formats = [
+        SerializationFormats::CBOR,
+        SerializationFormats::MGPK,
+        SerializationFormats::JSON,
+    ];
+    let mut stream = vec![];
+    for format in formats.iter() {
+        let km = CryptoBox::new()?;
+        let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+            .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+            .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+            .with_format(*format)
+            .build()?;
+        let raw = icp.serialize()?;
+        assert_eq!(icp.serialization_info.size, raw.len());
+        let signature = AttachedSignaturePrefix::new(SelfSigning::Ed25519Sha512, km.sign(&raw)?, 0);
+        stream.extend(SignedEventData::from(&icp.sign(vec![signature], None)).to_cesr()?);
+    }
+
+    let (rest, messages) = signed_event_stream(&stream).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(messages.len(), 3);
+    for (message, format) in messages.into_iter().zip(formats.iter()) {
+        let message = Message::try_from(message)?;
+        if let Message::Event(ref event) = message {
+            assert_eq!(event.event_message.serialization_info.kind, *format);
+        }
+        let state = processor.process(message)?.unwrap();
+        assert_eq!(state.sn, 0);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_export_import() -> Result<(), Error> {
+    use crate::{keri::Controller, signer::CryptoBox};
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice_db = Arc::new(SledEventDatabase::new(root.path().join("alice").as_path()).unwrap());
+    let bob_db = Arc::new(SledEventDatabase::new(root.path().join("bob").as_path()).unwrap());
+    let mut alice = Controller::new(alice_db.clone(), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let mut bob = Controller::new(bob_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    alice.incept(None)?;
+    alice.rotate()?;
+    bob.incept(None)?;
+
+    // Alice gets bob's KEL with receipt of her events.
+    alice.respond(&bob.respond(&alice.get_kerl()?.unwrap())?)?;
+    assert!(alice_db.get_receipts_t(alice.prefix()).is_some());
+
+    // Everything is restored from backup.
+    let backup = alice_db.export(None)?;
+    let restored_db =
+        Arc::new(SledEventDatabase::new(root.path().join("restored").as_path()).unwrap());
+    restored_db.import(&backup)?;
+    assert_eq!(restored_db.export(None)?, backup);
+    let restored = Controller::new(restored_db.clone(), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    assert_eq!(
+        restored.get_state_for_prefix(alice.prefix())?,
+        alice.get_state()?
+    );
+    assert_eq!(
+        restored.get_state_for_prefix(bob.prefix())?,
+        bob.get_state()?
+    );
+
+    // Importing again changes nothing.
+    restored_db.import(&backup)?;
+    assert_eq!(restored_db.export(None)?, backup);
+
+    // Single identifier can be exported as well, with receipts of its
+    // events after KEL.
+    let bob_export = alice_db.export(Some(bob.prefix()))?;
+    let bob_kel = bob.get_kerl()?.unwrap();
+    assert!(bob_export.len() > bob_kel.len());
+    assert!(bob_export.starts_with(&bob_kel));
+
+    Ok(())
+}
+
+#[cfg(feature = "query")]
+#[test]
+fn test_ksn_publisher() -> Result<(), Error> {
+    use crate::{
+        derivation::basic::Basic,
+        keri::publisher::KsnPublisher,
+        prefix::IdentifierPrefix,
+        signer::{CryptoBox, KeyManager},
+        transport::Transport,
+    };
+    use std::{cell::RefCell, time::Duration, time::Instant};
+    use tempfile::Builder;
+
+    // Transport which records delivered messages and fails to deliver
+    // to recipients which are down.
+    struct MockTransport {
+        down: RefCell<Vec<IdentifierPrefix>>,
+        delivered: RefCell<Vec<(IdentifierPrefix, Vec<u8>)>>,
+    }
+
+    impl Transport for MockTransport {
+        fn send_message(&self, to: &IdentifierPrefix, msg: &[u8]) -> Result<Vec<u8>, Error> {
+            if self.down.borrow().contains(to) {
+                return Err(Error::TransportError("Recipient is down".into()));
+            }
+            self.delivered.borrow_mut().push((to.clone(), msg.to_vec()));
+            Ok(vec![])
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let mut controller = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    controller.incept(None)?;
+
+    let signer = CryptoBox::new()?;
+    let publisher_prefix = Basic::Ed25519NT.derive(signer.public_key());
+    let recipients: Vec<IdentifierPrefix> = (0..2)
+        .map(|_| -> Result<_, Error> {
+            Ok(IdentifierPrefix::Basic(
+                Basic::Ed25519NT.derive(CryptoBox::new()?.public_key()),
+            ))
+        })
+        .collect::<Result<_, _>>()?;
+    let retry_delay = Duration::from_secs(10);
+    let mut publisher = KsnPublisher::new(publisher_prefix, signer, recipients.clone())
+        .with_retry_delay(retry_delay)
+        .with_max_attempts(3);
+    let transport = MockTransport {
+        down: RefCell::new(vec![recipients[1].clone()]),
+        delivered: RefCell::new(vec![]),
+    };
+
+    // The same state is queued only once.
+    let state = controller.get_state()?.unwrap();
+    assert!(publisher.kel_changed(state.clone())?);
+    assert!(!publisher.kel_changed(state)?);
+    assert_eq!(publisher.pending(), 2);
+
+    let now = Instant::now();
+    assert_eq!(publisher.publish_at(&transport, now), 1);
+    assert_eq!(publisher.pending(), 1);
+    // Delivered message is signed ksn reply.
+    let (recipient, rpy) = transport.delivered.borrow()[0].clone();
+    assert_eq!(recipient, recipients[0]);
+    let message = Message::try_from(signed_event_stream(&rpy).unwrap().1.remove(0))?;
+    assert!(matches!(message, Message::KeyStateNotice(_)));
+
+    // Failed delivery isn't retried before retry delay.
+    transport.down.borrow_mut().clear();
+    assert_eq!(publisher.publish_at(&transport, now), 0);
+    assert_eq!(publisher.publish_at(&transport, now + retry_delay), 1);
+    assert_eq!(publisher.pending(), 0);
+
+    // Notice is dropped after max attempts, with delay doubled after
+    // each of them.
+    controller.rotate()?;
+    transport.down.replace(recipients.clone());
+    assert!(publisher.kel_changed(controller.get_state()?.unwrap())?);
+    let now = Instant::now();
+    assert_eq!(publisher.publish_at(&transport, now), 0);
+    assert_eq!(publisher.publish_at(&transport, now + retry_delay), 0);
+    assert_eq!(publisher.pending(), 2);
+    assert_eq!(publisher.publish_at(&transport, now + retry_delay * 3), 0);
+    assert_eq!(publisher.pending(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_ephemeral_controller() -> Result<(), Error> {
+    use crate::{derivation::basic::Basic, prefix::IdentifierPrefix, signer::CryptoBox};
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let mut ephemeral = Keri::new_ephemeral(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+
+    let prefix = ephemeral.prefix().clone();
+    assert!(matches!(
+        prefix,
+        IdentifierPrefix::Basic(ref bp) if bp.derivation == Basic::Ed25519NT
+    ));
+    let state = ephemeral.get_state()?.unwrap();
+    assert_eq!(state.sn, 0);
+    assert_eq!(state.current.threshold_key_digest, None);
+
+    // Ephemeral identifier can't be rotated.
+    assert!(matches!(
+        ephemeral.rotate(),
+        Err(Error::NonTransferable(ref id)) if id == &prefix
+    ));
+
+    // Its KEL is accepted by others.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let bob = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    bob.respond(&ephemeral.get_kerl()?.unwrap())?;
+    assert_eq!(bob.get_state_for_prefix(&prefix)?, Some(state));
+
+    Ok(())
+}
+
+#[cfg(feature = "query")]
+#[test]
+fn test_alias_directory() -> Result<(), Error> {
+    use crate::{
+        directory::AliasRecord, prefix::Prefix, processor::EventProcessor, signer::CryptoBox,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let bob_db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let mut bob = Keri::new(Arc::clone(&bob_db), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let bob_icp = bob.incept(None)?;
+    let bob_prefix = bob.prefix().clone();
+
+    // Bob attests his own alias.
+    let attestation = bob.attest_alias("bob")?;
+    assert_eq!(bob_db.resolve_alias("bob")?, Some(bob_prefix.clone()));
+    let aliases = bob_db.get_aliases(&bob_prefix);
+    assert_eq!(aliases.len(), 1);
+    assert_eq!(aliases[0].attestation.as_ref(), Some(&attestation));
+
+    // Alice keeps bob's alias, backed by his attestation.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice_db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let alice = EventProcessor::new(Arc::clone(&alice_db));
+    alice.process(Message::Event(bob_icp))?;
+    let record =
+        AliasRecord::new("bob", bob_prefix.clone()).with_attestation(attestation.clone())?;
+    alice.verify_alias(&record)?;
+    alice_db.add_alias(record.clone())?;
+    assert_eq!(alice_db.get_alias("bob")?, Some(record));
+
+    // Attestation still verifies after bob rotates keys.
+    alice.process(Message::Event(bob.rotate()?))?;
+    alice.verify_alias(&alice_db.get_alias("bob")?.unwrap())?;
+
+    // Bob's attestation doesn't back alias of somebody else.
+    let carol: crate::prefix::IdentifierPrefix =
+        "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+    assert!(AliasRecord::new("bob", carol.clone())
+        .with_attestation(attestation.clone())
+        .is_err());
+    let forged = AliasRecord {
+        prefix: carol.clone(),
+        ..alice_db.get_alias("bob")?.unwrap()
+    };
+    assert!(alice.verify_alias(&forged).is_err());
+    assert!(alice
+        .verify_alias(&AliasRecord::new("carol", carol.clone()))
+        .is_err());
+
+    // Alias points to one identifier only, and can't look like a prefix.
+    assert!(matches!(
+        alice_db.add_alias(AliasRecord::new("bob", carol.clone())),
+        Err(Error::AliasTaken(alias)) if alias == "bob"
+    ));
+    assert!(alice_db
+        .add_alias(AliasRecord::new(&carol.to_str(), carol.clone()))
+        .is_err());
+    alice_db.add_alias(AliasRecord::new("robert", bob_prefix.clone()))?;
+    let aliases: Vec<_> = alice_db
+        .get_aliases(&bob_prefix)
+        .into_iter()
+        .map(|record| record.alias)
+        .collect();
+    assert_eq!(aliases, vec!["bob", "robert"]);
+
+    alice_db.remove_alias("bob")?;
+    assert_eq!(alice_db.resolve_alias("bob")?, None);
+    alice_db.add_alias(AliasRecord::new("bob", carol.clone()))?;
+    assert_eq!(alice_db.resolve_alias("bob")?, Some(carol));
+
+    Ok(())
+}
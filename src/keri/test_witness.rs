@@ -0,0 +1,42 @@
+use std::{ops::Deref, path::Path};
+
+use super::witness::Witness;
+use crate::{error::Error, prefix::SeedPrefix};
+
+/// Test Witness
+///
+/// Witness which keys are derived from seed, so the same seed always
+/// gives the same witness, which makes multi-witness scenarios
+/// reproducible.
+pub struct TestWitness {
+    witness: Witness,
+}
+
+impl TestWitness {
+    pub fn new(seed: &SeedPrefix, path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            witness: Witness::from_seed(seed, path)?,
+        })
+    }
+
+    /// Creates witness with seed made of given index.
+    pub fn with_index(index: u8, path: &Path) -> Result<Self, Error> {
+        Self::new(&SeedPrefix::RandomSeed256Ed25519(vec![index; 32]), path)
+    }
+}
+
+impl Deref for TestWitness {
+    type Target = Witness;
+
+    fn deref(&self) -> &Witness {
+        &self.witness
+    }
+}
+
+/// Creates given number of deterministic test witnesses, each one
+/// with its own database in separate subdirectory of root path.
+pub fn test_witnesses(count: u8, root: &Path) -> Result<Vec<TestWitness>, Error> {
+    (0..count)
+        .map(|i| TestWitness::with_index(i, &root.join(format!("witness-{}", i))))
+        .collect()
+}
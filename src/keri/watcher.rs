@@ -0,0 +1,222 @@
+use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{sled::SledEventDatabase, Subscription},
+    derivation::{basic::Basic, self_signing::SelfSigning},
+    error::Error,
+    event_message::signed_event_message::{Message, SignedEventMessage},
+    event_parsing::message::signed_event_stream,
+    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix, SelfSigningPrefix},
+    processor::EventProcessor,
+    signer::{CryptoBox, KeyManager},
+    state::IdentifierState,
+};
+
+/// Duplicity Evidence
+///
+/// Digests of conflicting versions of event of given prefix and sn.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DuplicityEvidence {
+    #[serde(rename = "i")]
+    pub prefix: IdentifierPrefix,
+
+    #[serde(rename = "s", with = "crate::hex")]
+    pub sn: u64,
+
+    #[serde(rename = "d")]
+    pub digests: Vec<SelfAddressingPrefix>,
+}
+
+impl DuplicityEvidence {
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+/// Duplicity Attestation
+///
+/// Evidence of duplicity together with the conflicting events,
+/// signed by the watcher which observed them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicityAttestation {
+    pub evidence: DuplicityEvidence,
+    pub events: Vec<SignedEventMessage>,
+    pub watcher: BasicPrefix,
+    pub signature: SelfSigningPrefix,
+}
+
+impl DuplicityAttestation {
+    /// Checks if attached events match the evidence, if they are signed
+    /// with keys established by `prior` key state, ie. state of the
+    /// identifier before event at evidence's sn, and if evidence is
+    /// signed by the watcher.
+    pub fn verify(&self, prior: &IdentifierState) -> Result<bool, Error> {
+        let events_match = self.events.len() == self.evidence.digests.len()
+            && self.evidence.digests.len() > 1
+            && self
+                .events
+                .iter()
+                .zip(self.evidence.digests.iter())
+                .all(|(event, digest)| {
+                    event.event_message.event.get_prefix() == self.evidence.prefix
+                        && event.event_message.event.get_sn() == self.evidence.sn
+                        && &event.event_message.get_digest() == digest
+                });
+        if !events_match {
+            return Ok(false);
+        }
+        for event in &self.events {
+            if !is_signed_version(prior, event)? {
+                return Ok(false);
+            }
+        }
+        self.watcher
+            .verify(&self.evidence.serialize()?, &self.signature)
+    }
+}
+
+/// Checks if `event` applies to `prior` key state and is signed with
+/// keys it establishes.
+fn is_signed_version(prior: &IdentifierState, event: &SignedEventMessage) -> Result<bool, Error> {
+    match prior.clone().apply(&event.event_message) {
+        Ok(state) => prior.verify_signatures(&state, &event.raw_event()?, &event.signatures),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Watcher
+///
+/// Nontransferable identifier which tracks KELs on behalf of
+/// validators. Records every version of every event it sees, even
+/// rejected ones, and attests duplicity when conflicting versions of
+/// the same event, signed by the controller, are observed.
+pub struct Watcher {
+    pub prefix: BasicPrefix,
+    signer: CryptoBox,
+    pub processor: EventProcessor,
+}
+
+impl Watcher {
+    pub fn new(path: &Path) -> Result<Self, Error> {
+        let signer = CryptoBox::new()?;
+        let processor = EventProcessor::new(Arc::new(SledEventDatabase::new(path)?));
+        let prefix = Basic::Ed25519NT.derive(signer.public_key());
+        Ok(Self {
+            prefix,
+            signer,
+            processor,
+        })
+    }
+
+    /// Starts tracking KEL of given prefix fetched from given source.
+    pub fn watch(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Result<Subscription, Error> {
+        self.processor.subscribe(id, source)
+    }
+
+    /// Process
+    ///
+    /// Processes stream of messages fetched from given source.
+    /// Returns attestations of duplicity detected in events of the
+    /// stream.
+    pub fn process(
+        &self,
+        source: &IdentifierPrefix,
+        msg: &[u8],
+    ) -> Result<Vec<DuplicityAttestation>, Error> {
+        let messages = signed_event_stream(msg)
+            .map_err(|e| Error::DeserializeError(e.to_string()))?
+            .1;
+        let mut attestations = vec![];
+        for message in messages {
+            match Message::try_from(message)? {
+                Message::Event(event) => {
+                    let id = event.event_message.event.get_prefix();
+                    let sn = event.event_message.event.get_sn();
+                    self.processor.db.add_seen_event(event.clone(), &id)?;
+                    // Rejected event is still kept as seen one.
+                    let _ = self.processor.process(Message::Event(event));
+                    if self.processor.db.get_subscription(&id, source).is_some() {
+                        self.processor.update_subscription_cursor(&id, source)?;
+                    }
+                    if let Some(attestation) = self.attest_duplicity(&id, sn)? {
+                        attestations.push(attestation);
+                    }
+                }
+                message => {
+                    let _ = self.processor.process(message);
+                }
+            }
+        }
+        Ok(attestations)
+    }
+
+    /// Returns all seen versions of event of given prefix and sn.
+    pub fn get_seen_events(&self, id: &IdentifierPrefix, sn: u64) -> Vec<SignedEventMessage> {
+        self.processor
+            .db
+            .get_seen_events(id)
+            .into_iter()
+            .flatten()
+            .map(|event| event.signed_event_message)
+            .filter(|event| event.event_message.event.get_sn() == sn)
+            .collect()
+    }
+
+    /// Attest Duplicity
+    ///
+    /// Returns signed attestation if more than one version of event
+    /// of given prefix and sn, signed with keys of accepted key state
+    /// preceding it, was seen. Versions which aren't signed by the
+    /// controller, eg. tampered copies, are left out.
+    pub fn attest_duplicity(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+    ) -> Result<Option<DuplicityAttestation>, Error> {
+        let prior = match sn {
+            0 => IdentifierState::default(),
+            _ => match self.processor.compute_state_at_sn(id, sn - 1)? {
+                Some(state) if state.sn == sn - 1 => state,
+                _ => return Ok(None),
+            },
+        };
+        let mut events = vec![];
+        for event in self.get_seen_events(id, sn) {
+            if is_signed_version(&prior, &event)? {
+                events.push(event);
+            }
+        }
+        if events.len() < 2 {
+            return Ok(None);
+        }
+        let evidence = DuplicityEvidence {
+            prefix: id.clone(),
+            sn,
+            digests: events
+                .iter()
+                .map(|event| event.event_message.get_digest())
+                .collect(),
+        };
+        let signature =
+            SelfSigning::Ed25519Sha512.derive(self.signer.sign(&evidence.serialize()?)?);
+        Ok(Some(DuplicityAttestation {
+            evidence,
+            events,
+            watcher: self.prefix.clone(),
+            signature,
+        }))
+    }
+
+    /// Returns key state of tracked prefix as accepted by watcher.
+    pub fn get_state(&self, id: &IdentifierPrefix) -> Result<Option<IdentifierState>, Error> {
+        self.processor.compute_state(id)
+    }
+}
@@ -1,23 +1,38 @@
+use std::convert::TryFrom;
 use std::path::Path;
 use std::sync::Arc;
 
+#[cfg(feature = "query")]
 use crate::query::reply::{ReplyEvent, SignedReply};
+#[cfg(feature = "query")]
 use crate::query::{
-    key_state_notice::KeyStateNotice,
-    query::{QueryData, SignedQuery},
-    ReplyType, Route,
+    key_state_notice::KeyStateNotice, query_event::SignedQuery, ReplyPayload, ReplyType, Route,
 };
 
 use crate::{
     database::sled::SledEventDatabase,
-    derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
+    derivation::basic::Basic,
     error::Error,
-    event::SerializationFormats,
-    prefix::{BasicPrefix, IdentifierPrefix},
+    event_message::signed_event_message::{
+        Message, SignedEventMessage, SignedNontransferableReceipt,
+    },
+    event_parsing::{message::signed_event_stream, SignedEventData},
+    prefix::{BasicPrefix, SeedPrefix},
     processor::EventProcessor,
     signer::{CryptoBox, KeyManager},
 };
+#[cfg(feature = "query")]
+use crate::{
+    derivation::{self_addressing::SelfAddressing, self_signing::SelfSigning},
+    event::SerializationFormats,
+    prefix::IdentifierPrefix,
+};
 
+/// Witness
+///
+/// Nontransferable identifier which receipts events of controllers
+/// that designated it as their witness. Keeps receipted KELs together
+/// with receipts of other witnesses and answers queries about them.
 pub struct Witness {
     pub prefix: BasicPrefix,
     signer: CryptoBox,
@@ -26,12 +41,23 @@ pub struct Witness {
 
 impl Witness {
     pub fn new(path: &Path) -> Result<Self, Error> {
-        let signer = CryptoBox::new()?;
+        Self::with_signer(CryptoBox::new()?, path)
+    }
+
+    /// Creates witness with keys derived from seed, so the same seed
+    /// always gives the same witness identifier.
+    pub fn from_seed(seed: &SeedPrefix, path: &Path) -> Result<Self, Error> {
+        // Nontransferable identifier never rotates, so next keys are
+        // never used. Seed is reused to keep key manager complete.
+        Self::with_signer(CryptoBox::from_seeds(seed, seed)?, path)
+    }
+
+    fn with_signer(signer: CryptoBox, path: &Path) -> Result<Self, Error> {
         let processor = {
-            let witness_db = Arc::new(SledEventDatabase::new(path).unwrap());
-            EventProcessor::new(witness_db.clone())
+            let witness_db = Arc::new(SledEventDatabase::new(path)?);
+            EventProcessor::new(witness_db)
         };
-        let prefix = Basic::Ed25519.derive(signer.public_key());
+        let prefix = Basic::Ed25519NT.derive(signer.public_key());
         Ok(Self {
             prefix,
             signer,
@@ -39,6 +65,64 @@ impl Witness {
         })
     }
 
+    /// Receipt
+    ///
+    /// Processes given event and returns receipt signed by witness,
+    /// if witness is designated for it in controller's key state.
+    pub fn receipt(
+        &self,
+        event: &SignedEventMessage,
+    ) -> Result<SignedNontransferableReceipt, Error> {
+        self.processor.process(Message::Event(event.clone()))?;
+        self.processor.receipt(&event.event_message, &self.signer)
+    }
+
+    /// Respond
+    ///
+    /// Processes stream of messages. Events are receipted and receipts
+    /// of other witnesses are stored. Returns receipts and replies to
+    /// queries as CESR stream.
+    pub fn respond(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let messages = signed_event_stream(msg)
+            .map_err(|e| Error::DeserializeError(e.to_string()))?
+            .1;
+        messages
+            .into_iter()
+            .map(|message| -> Result<Vec<u8>, Error> {
+                match Message::try_from(message)? {
+                    Message::Event(event) => SignedEventData::from(self.receipt(&event)?).to_cesr(),
+                    Message::NontransferableRct(rct) => {
+                        self.processor.process(Message::NontransferableRct(rct))?;
+                        Ok(vec![])
+                    }
+                    #[cfg(feature = "query")]
+                    Message::Query(qry) => match self.process_signed_query(qry)? {
+                        ReplyType::Rep(rpy) => SignedEventData::from(rpy).to_cesr(),
+                        ReplyType::Kel(kel) => Ok(kel),
+                        ReplyType::Mbx(messages) => messages
+                            .into_iter()
+                            .map(|message| match message {
+                                Message::NontransferableRct(rct) => {
+                                    SignedEventData::from(rct).to_cesr()
+                                }
+                                Message::TransferableRct(rct) => {
+                                    SignedEventData::from(rct).to_cesr()
+                                }
+                                _ => Err(Error::SemanticError("Improper mailbox message".into())),
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                            .map(|receipts| receipts.concat()),
+                    },
+                    _ => Err(Error::SemanticError(
+                        "Message not supported by witness".into(),
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|responses| responses.concat())
+    }
+
+    #[cfg(feature = "query")]
     pub fn get_ksn_for_prefix(&self, prefix: &IdentifierPrefix) -> Result<SignedReply, Error> {
         let state = self.processor.compute_state(prefix).unwrap().unwrap();
         let ksn = KeyStateNotice::new_ksn(state, SerializationFormats::JSON);
@@ -58,44 +142,15 @@ impl Witness {
         ))
     }
 
-    pub fn process_signed_query(&self, qr: SignedQuery) -> Result<ReplyType, Error> {
-        let signatures = qr.signatures;
-        // check signatures
-        let kc = self
-            .processor
-            .compute_state(&qr.signer)?
-            .ok_or(Error::SemanticError("No identifier in db".into()))?
-            .current;
-
-        if kc.verify(&qr.envelope.serialize().unwrap(), &signatures)? {
-            // TODO check timestamps
-            // unpack and check what's inside
-            let route = qr.envelope.event.get_route();
-            self.process_query(route, qr.envelope.event.get_query_data())
-        } else {
-            Err(Error::SignatureVerificationError)
-        }
-    }
-
     #[cfg(feature = "query")]
-    fn process_query(&self, route: Route, qr: QueryData) -> Result<ReplyType, Error> {
-        match route {
-            Route::Log => {
-                Ok(ReplyType::Kel(self.processor.get_kerl(&qr.data.i)?.ok_or(
-                    Error::SemanticError("No identifier in db".into()),
-                )?))
-            }
-            Route::Ksn => {
-                let i = qr.data.i;
+    pub fn process_signed_query(&self, qr: SignedQuery) -> Result<ReplyType, Error> {
+        // TODO check timestamps
+        match self.processor.process_query(&qr)? {
+            ReplyPayload::Kel(kel) => Ok(ReplyType::Kel(kel)),
+            ReplyPayload::Ksn(ksn) => {
                 // return reply message with ksn inside
-                let state = self
-                    .processor
-                    .compute_state(&i)
-                    .unwrap()
-                    .ok_or(Error::SemanticError("No id in database".into()))?;
-                let ksn = KeyStateNotice::new_ksn(state, SerializationFormats::JSON);
                 let rpy = ReplyEvent::new_reply(
-                    ksn,
+                    *ksn,
                     Route::ReplyKsn(IdentifierPrefix::Basic(self.prefix.clone())),
                     SelfAddressing::Blake3_256,
                     SerializationFormats::JSON,
@@ -108,7 +163,7 @@ impl Witness {
                 );
                 Ok(ReplyType::Rep(rpy))
             }
-            _ => todo!(),
+            ReplyPayload::Mbx(messages) => Ok(ReplyType::Mbx(messages)),
         }
     }
 }
@@ -1,9 +1,17 @@
 use crate::error::Error;
+use alloc::{borrow::ToOwned, vec::Vec};
 use ed25519_dalek::{ExpandedSecretKey, SecretKey};
 use k256::ecdsa::{signature::Signer as EcdsaSigner, Signature as EcdsaSignature, SigningKey};
 use k256::ecdsa::{signature::Verifier as EcdsaVerifier, VerifyingKey};
+use sha2::{Digest, Sha512};
 use zeroize::Zeroize;
 
+#[cfg(feature = "std")]
+pub mod sealed_box;
+
+#[cfg(feature = "pq")]
+pub mod sphincs;
+
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub struct PublicKey {
     public_key: Vec<u8>,
@@ -39,8 +47,8 @@ impl PublicKey {
     pub fn verify_ecdsa(&self, msg: &[u8], sig: &[u8]) -> bool {
         match VerifyingKey::from_sec1_bytes(&self.key()) {
             Ok(k) => {
+                use core::convert::TryFrom;
                 use k256::ecdsa::Signature;
-                use std::convert::TryFrom;
                 if let Ok(sig) = Signature::try_from(sig) {
                     match k.verify(msg, &sig) {
                         Ok(()) => true,
@@ -53,6 +61,20 @@ impl PublicKey {
             Err(_) => false,
         }
     }
+
+    /// Converts Ed25519 public key to X25519 public key of the same
+    /// key pair.
+    pub fn ed_to_x25519(&self) -> Result<PublicKey, Error> {
+        let point = curve25519_dalek::edwards::CompressedEdwardsY::from_slice(&self.key())
+            .decompress()
+            .ok_or_else(|| Error::SemanticError("Improper Ed25519 key".into()))?;
+        Ok(PublicKey::new(point.to_montgomery().to_bytes().to_vec()))
+    }
+
+    #[cfg(feature = "pq")]
+    pub fn verify_sphincs(&self, msg: &[u8], sig: &[u8]) -> bool {
+        sphincs::verify(&self.key(), msg, sig)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -79,6 +101,21 @@ impl PrivateKey {
             .to_vec())
     }
 
+    /// Converts Ed25519 private key to X25519 private key of the same
+    /// key pair.
+    pub fn ed_to_x25519(&self) -> Result<PrivateKey, Error> {
+        let sk = SecretKey::from_bytes(&self.key)?;
+        let mut hash = Sha512::digest(sk.as_bytes());
+        let key = PrivateKey::new(hash[..32].to_vec());
+        hash.as_mut_slice().zeroize();
+        Ok(key)
+    }
+
+    #[cfg(feature = "pq")]
+    pub fn sign_sphincs(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        sphincs::sign(&self.key, msg)
+    }
+
     pub fn key(&self) -> Vec<u8> {
         self.key.clone()
     }
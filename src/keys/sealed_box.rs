@@ -0,0 +1,128 @@
+//! Sealed box
+//!
+//! Anonymous public key encryption. Data is encrypted with
+//! XChaCha20-Poly1305 under key agreed between fresh ephemeral X25519
+//! key pair and recipient's X25519 key. Box consists of ephemeral
+//! public key followed by ciphertext, so only recipient can open it.
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use curve25519_dalek::{constants::X25519_BASEPOINT, montgomery::MontgomeryPoint, scalar::Scalar};
+use rand::{rngs::OsRng, RngCore};
+
+use super::{PrivateKey, PublicKey};
+use crate::{derivation::basic::Basic, error::Error, prefix::BasicPrefix};
+
+const KEY_CONTEXT: &str = "KERI sealed box 2022-03-01 XChaCha20-Poly1305 key";
+const NONCE_CONTEXT: &str = "KERI sealed box 2022-03-01 XChaCha20-Poly1305 nonce";
+
+fn x25519(scalar: &[u8], point: &[u8]) -> Result<[u8; 32], Error> {
+    let mut bits = [0u8; 32];
+    let mut point_bytes = [0u8; 32];
+    if scalar.len() != 32 || point.len() != 32 {
+        return Err(Error::SemanticError("Improper X25519 key length".into()));
+    }
+    bits.copy_from_slice(scalar);
+    point_bytes.copy_from_slice(point);
+    // Clamping as defined for X25519 in RFC 7748
+    bits[0] &= 248;
+    bits[31] &= 127;
+    bits[31] |= 64;
+    Ok((MontgomeryPoint(point_bytes) * Scalar::from_bits(bits)).to_bytes())
+}
+
+fn cipher(shared: &[u8], ephemeral: &[u8], recipient: &[u8]) -> (XChaCha20Poly1305, XNonce) {
+    let key = blake3::Hasher::new_derive_key(KEY_CONTEXT)
+        .update(shared)
+        .update(ephemeral)
+        .update(recipient)
+        .finalize();
+    let nonce = blake3::Hasher::new_derive_key(NONCE_CONTEXT)
+        .update(ephemeral)
+        .update(recipient)
+        .finalize();
+    (
+        XChaCha20Poly1305::new(Key::from_slice(key.as_bytes())),
+        *XNonce::from_slice(&nonce.as_bytes()[..24]),
+    )
+}
+
+/// Seals `data` for owner of X25519 `recipient` key.
+pub fn seal(recipient: &PublicKey, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut ephemeral_secret = [0u8; 32];
+    OsRng.fill_bytes(&mut ephemeral_secret);
+    let ephemeral = x25519(&ephemeral_secret, X25519_BASEPOINT.as_bytes())?;
+    let shared = x25519(&ephemeral_secret, &recipient.key())?;
+    let (cipher, nonce) = cipher(&shared, &ephemeral, &recipient.key());
+    let ciphertext = cipher
+        .encrypt(&nonce, data)
+        .map_err(|_| Error::SemanticError("Encryption failed".into()))?;
+    Ok([ephemeral.to_vec(), ciphertext].concat())
+}
+
+/// Opens box sealed for X25519 key of given private key.
+pub fn open(key: &PrivateKey, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed.len() < 32 {
+        return Err(Error::DecryptionError);
+    }
+    let (ephemeral, ciphertext) = sealed.split_at(32);
+    let recipient = x25519(&key.key(), X25519_BASEPOINT.as_bytes())?;
+    let shared = x25519(&key.key(), ephemeral)?;
+    let (cipher, nonce) = cipher(&shared, ephemeral, &recipient);
+    cipher
+        .decrypt(&nonce, ciphertext)
+        .map_err(|_| Error::DecryptionError)
+}
+
+/// Encrypt to
+///
+/// Seals `data` for controller of basic prefix. Ed25519 keys are
+/// converted to their X25519 form.
+pub fn encrypt_to(prefix: &BasicPrefix, data: &[u8]) -> Result<Vec<u8>, Error> {
+    match prefix.to_x25519()? {
+        BasicPrefix {
+            derivation: Basic::X25519,
+            public_key,
+        } => seal(&public_key, data),
+        _ => Err(Error::ImproperPrefixType),
+    }
+}
+
+/// Decrypt
+///
+/// Opens box sealed with `encrypt_to` for basic prefix of Ed25519
+/// private key.
+pub fn decrypt(key: &PrivateKey, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    open(&key.ed_to_x25519()?, sealed)
+}
+
+#[test]
+fn test_sealed_box() -> Result<(), Error> {
+    use crate::prefix::Prefix;
+    use ed25519_dalek::Keypair;
+
+    let kp = Keypair::generate(&mut OsRng);
+    let prefix = Basic::Ed25519.derive(PublicKey::new(kp.public.to_bytes().to_vec()));
+    let key = PrivateKey::new(kp.secret.to_bytes().to_vec());
+    let data = b"secret payload";
+
+    let sealed = encrypt_to(&prefix, data)?;
+    assert_eq!(decrypt(&key, &sealed)?, data);
+
+    // X25519 form of the key is stable and decrypts the same way.
+    let x_prefix = prefix.to_x25519()?;
+    assert_eq!(x_prefix.derivation, Basic::X25519);
+    assert_eq!(x_prefix.to_str().len(), prefix.to_str().len());
+    assert_eq!(decrypt(&key, &encrypt_to(&x_prefix, data)?)?, data);
+
+    // Nobody else can open the box, nor tamper with it.
+    let other = PrivateKey::new(Keypair::generate(&mut OsRng).secret.to_bytes().to_vec());
+    assert!(decrypt(&other, &sealed).is_err());
+    let mut tampered = sealed.clone();
+    tampered[40] ^= 1;
+    assert!(decrypt(&key, &tampered).is_err());
+
+    Ok(())
+}
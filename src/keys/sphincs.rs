@@ -0,0 +1,56 @@
+//! SPHINCS+ signatures
+//!
+//! Stateless hash based signature scheme, believed to be secure against
+//! quantum adversaries. Uses SHA2-128s simple parameter set from
+//! `pqcrypto-sphincsplus`, which wraps the reference implementation
+//! submitted to NIST. Unlike one-time schemes, key can sign any number
+//! of messages, so KELs using it don't need to rotate with every event.
+
+use pqcrypto_sphincsplus::sphincssha2128ssimple as sphincs;
+use pqcrypto_traits::sign::{DetachedSignature as _, PublicKey as _, SecretKey as _};
+
+use crate::error::Error;
+
+/// Length of public key in bytes
+pub const PUBLIC_KEY_LEN: usize = 32;
+/// Length of signature in bytes
+pub const SIGNATURE_LEN: usize = 7856;
+
+/// Generates random key pair, returned as public and secret key bytes.
+pub fn generate_key_pair() -> (Vec<u8>, Vec<u8>) {
+    let (pk, sk) = sphincs::keypair();
+    (pk.as_bytes().to_vec(), sk.as_bytes().to_vec())
+}
+
+pub fn sign(secret_key: &[u8], msg: &[u8]) -> Result<Vec<u8>, Error> {
+    let sk = sphincs::SecretKey::from_bytes(secret_key)
+        .map_err(|_| Error::SemanticError("Improper SPHINCS+ secret key".into()))?;
+    Ok(sphincs::detached_sign(msg, &sk).as_bytes().to_vec())
+}
+
+pub fn verify(public_key: &[u8], msg: &[u8], sig: &[u8]) -> bool {
+    match (
+        sphincs::PublicKey::from_bytes(public_key),
+        sphincs::DetachedSignature::from_bytes(sig),
+    ) {
+        (Ok(pk), Ok(sig)) => sphincs::verify_detached_signature(&sig, msg, &pk).is_ok(),
+        _ => false,
+    }
+}
+
+#[test]
+fn test_sphincs() -> Result<(), Error> {
+    let (pk, sk) = generate_key_pair();
+    assert_eq!(pk.len(), PUBLIC_KEY_LEN);
+    let sig = sign(&sk, b"message")?;
+    assert_eq!(sig.len(), SIGNATURE_LEN);
+    assert!(verify(&pk, b"message", &sig));
+    assert!(!verify(&pk, b"other message", &sig));
+    assert!(!verify(&generate_key_pair().0, b"message", &sig));
+    assert!(!verify(&pk, b"message", &sig[1..]));
+    // The same key signs more than one message.
+    assert!(verify(&pk, b"other message", &sign(&sk, b"other message")?));
+    assert!(sign(&sk[..16], b"message").is_err());
+
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Encrypted keystore
+//!
+//! Persists seeds of controllers' current and next keys, encrypted
+//! with XChaCha20-Poly1305 under a key derived from passphrase with
+//! Argon2id. Entries are keyed by identifier prefix, which is also
+//! authenticated as associated data, so entry of one identifier can't
+//! be passed off as keys of another.
+
+use std::path::Path;
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::{
+    error::Error,
+    prefix::{IdentifierPrefix, Prefix, SeedPrefix},
+    signer::CryptoBox,
+};
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Key Seeds
+///
+/// Seeds of current and next key pairs of identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeySeeds {
+    pub current: SeedPrefix,
+    pub next: SeedPrefix,
+}
+
+impl KeySeeds {
+    pub fn new(current: SeedPrefix, next: SeedPrefix) -> Self {
+        Self { current, next }
+    }
+
+    /// Recreates key manager holding keys derived from the seeds.
+    pub fn key_manager(&self) -> Result<CryptoBox, Error> {
+        CryptoBox::from_seeds(&self.current, &self.next)
+    }
+}
+
+impl From<&CryptoBox> for KeySeeds {
+    fn from(key_manager: &CryptoBox) -> Self {
+        let (current, next) = key_manager.seeds();
+        Self { current, next }
+    }
+}
+
+/// Encrypted Keys
+///
+/// Key seeds of identifier encrypted with passphrase. Serialized to
+/// JSON it is a self-contained bundle which can be moved between
+/// keystores.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EncryptedKeys {
+    #[serde(rename = "i")]
+    pub prefix: IdentifierPrefix,
+
+    #[serde(rename = "s")]
+    salt: String,
+
+    #[serde(rename = "n")]
+    nonce: String,
+
+    #[serde(rename = "c")]
+    ciphertext: String,
+}
+
+impl EncryptedKeys {
+    pub fn encrypt(
+        prefix: &IdentifierPrefix,
+        seeds: &KeySeeds,
+        passphrase: &[u8],
+    ) -> Result<Self, Error> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut plaintext = serde_json::to_vec(&[seeds.current.to_str(), seeds.next.to_str()])?;
+        let ciphertext = cipher(passphrase, &salt)?.encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: prefix.to_str().as_bytes(),
+            },
+        );
+        plaintext.zeroize();
+
+        Ok(Self {
+            prefix: prefix.clone(),
+            salt: base64::encode_config(salt, base64::URL_SAFE),
+            nonce: base64::encode_config(nonce, base64::URL_SAFE),
+            ciphertext: base64::encode_config(
+                ciphertext.map_err(|_| Error::KeystoreError("Encryption failed".into()))?,
+                base64::URL_SAFE,
+            ),
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &[u8]) -> Result<KeySeeds, Error> {
+        let salt = base64::decode_config(&self.salt, base64::URL_SAFE)?;
+        let nonce = base64::decode_config(&self.nonce, base64::URL_SAFE)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(Error::KeystoreError("Improper nonce length".into()));
+        }
+        let ciphertext = base64::decode_config(&self.ciphertext, base64::URL_SAFE)?;
+
+        let mut plaintext = cipher(passphrase, &salt)?
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: self.prefix.to_str().as_bytes(),
+                },
+            )
+            .map_err(|_| Error::KeystoreError("Wrong passphrase".into()))?;
+        let seeds: Result<Vec<String>, _> = serde_json::from_slice(&plaintext);
+        plaintext.zeroize();
+
+        match seeds?.as_slice() {
+            [current, next] => Ok(KeySeeds::new(current.parse()?, next.parse()?)),
+            _ => Err(Error::KeystoreError("Improper keys content".into())),
+        }
+    }
+}
+
+/// Derives encryption key from passphrase with Argon2id.
+fn cipher(passphrase: &[u8], salt: &[u8]) -> Result<XChaCha20Poly1305, Error> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|e| Error::KeystoreError(e.to_string()))?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    key.zeroize();
+    Ok(cipher)
+}
+
+/// Keystore
+///
+/// Sled backed storage of encrypted keys, keyed by identifier prefix.
+pub struct Keystore {
+    db: sled::Db,
+}
+
+impl Keystore {
+    pub fn new(path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+
+    /// Encrypts seeds of identifier keys with passphrase and stores
+    /// them, replacing keys stored before.
+    pub fn store(
+        &self,
+        prefix: &IdentifierPrefix,
+        seeds: &KeySeeds,
+        passphrase: &[u8],
+    ) -> Result<(), Error> {
+        self.insert(&EncryptedKeys::encrypt(prefix, seeds, passphrase)?)
+    }
+
+    /// Decrypts stored seeds of identifier keys.
+    pub fn unlock(&self, prefix: &IdentifierPrefix, passphrase: &[u8]) -> Result<KeySeeds, Error> {
+        self.get(prefix)?.decrypt(passphrase)
+    }
+
+    /// Re-encrypts stored keys of identifier with new passphrase.
+    pub fn change_passphrase(
+        &self,
+        prefix: &IdentifierPrefix,
+        old_passphrase: &[u8],
+        new_passphrase: &[u8],
+    ) -> Result<(), Error> {
+        let seeds = self.unlock(prefix, old_passphrase)?;
+        self.store(prefix, &seeds, new_passphrase)
+    }
+
+    /// Exports encrypted keys of identifier as JSON bundle. Keys stay
+    /// encrypted with passphrase they were stored with.
+    pub fn export(&self, prefix: &IdentifierPrefix) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec(&self.get(prefix)?)?)
+    }
+
+    /// Imports bundle made by `export`. Returns prefix of identifier
+    /// which keys were imported.
+    pub fn import(&self, bundle: &[u8]) -> Result<IdentifierPrefix, Error> {
+        let keys: EncryptedKeys = serde_json::from_slice(bundle)?;
+        self.insert(&keys)?;
+        Ok(keys.prefix)
+    }
+
+    fn get(&self, prefix: &IdentifierPrefix) -> Result<EncryptedKeys, Error> {
+        match self.db.get(prefix.to_str().as_bytes())? {
+            Some(keys) => Ok(serde_cbor::from_slice(&keys)?),
+            None => Err(Error::KeystoreError("No keys for identifier".into())),
+        }
+    }
+
+    fn insert(&self, keys: &EncryptedKeys) -> Result<(), Error> {
+        self.db
+            .insert(keys.prefix.to_str().as_bytes(), serde_cbor::to_vec(keys)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_keystore() -> Result<(), Error> {
+    use crate::signer::KeyManager;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let keystore = Keystore::new(root.path().join("keys").as_path())?;
+    let key_manager = CryptoBox::new()?;
+    let prefix: IdentifierPrefix = "EyX-zd8YpAYxq7Zp8Y8pKZsqNcWRpkRgxMR8ZLbF1SPE".parse()?;
+
+    keystore.store(&prefix, &KeySeeds::from(&key_manager), b"passphrase")?;
+    assert!(keystore.unlock(&prefix, b"wrong passphrase").is_err());
+    let unlocked = keystore.unlock(&prefix, b"passphrase")?.key_manager()?;
+    assert_eq!(unlocked.public_key(), key_manager.public_key());
+    assert_eq!(unlocked.next_public_key(), key_manager.next_public_key());
+
+    keystore.change_passphrase(&prefix, b"passphrase", b"new passphrase")?;
+    assert!(keystore.unlock(&prefix, b"passphrase").is_err());
+    assert_eq!(
+        keystore.unlock(&prefix, b"new passphrase")?,
+        KeySeeds::from(&key_manager)
+    );
+
+    // Exported bundle can be imported into another keystore.
+    let other = Keystore::new(root.path().join("other").as_path())?;
+    let bundle = keystore.export(&prefix)?;
+    assert_eq!(other.import(&bundle)?, prefix);
+    assert_eq!(
+        other.unlock(&prefix, b"new passphrase")?,
+        KeySeeds::from(&key_manager)
+    );
+
+    // Bundle can't be used as keys of other identifier.
+    let mut keys: EncryptedKeys = serde_json::from_slice(&bundle)?;
+    keys.prefix = "EZAoTNZH3ULvaU6Z-i0d8JJR2nmwyYAfSVPzhzS6b5CM".parse()?;
+    assert!(keys.decrypt(b"new passphrase").is_err());
+
+    Ok(())
+}
@@ -1,15 +1,114 @@
-pub mod database;
+//! Core modules (parsing, prefixes, derivations, events, state and
+//! signature verification) don't depend on `std` only functionality,
+//! only on `alloc`. Databases, processor, controller and transports
+//! need the `std` feature (enabled by default), so embedded targets can
+//! build just the core with `--no-default-features`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+
 pub mod derivation;
+pub mod display;
 pub mod error;
 pub mod event;
 pub mod event_message;
 pub mod event_parsing;
-pub mod keri;
+pub mod hex;
 pub mod keys;
+pub mod merkle;
 pub mod prefix;
+pub mod sad;
+pub mod state;
+
+#[cfg(feature = "std")]
+pub mod acdc;
+#[cfg(feature = "std")]
+pub mod database;
+#[cfg(feature = "std")]
+pub mod directory;
+#[cfg(feature = "std")]
+pub mod keri;
+#[cfg(feature = "std")]
+pub mod mailbox;
+#[cfg(feature = "std")]
+pub mod oobi;
+#[cfg(feature = "std")]
 pub mod processor;
+#[cfg(feature = "std")]
 pub mod signer;
-pub mod state;
+#[cfg(feature = "std")]
+pub mod tel;
+#[cfg(feature = "std")]
+pub mod transport;
+#[cfg(feature = "std")]
+pub mod vectors;
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "keystore")]
+pub mod keystore;
 #[cfg(feature = "query")]
 pub mod query;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use crate::{
+    derivation::self_addressing::SelfAddressing,
+    error::Error,
+    prefix::{AttachedSignaturePrefix, BasicPrefix, Prefix},
+};
+use alloc::string::String;
+use core::str::FromStr;
+
+/// Derive SAID
+///
+/// Computes self-addressing identifier of given data, using digest
+/// algorithm represented by derivation code (e.g. "E" for Blake3-256).
+/// Returns qb64 representation of the identifier.
+pub fn derive_said(code: &str, data: &[u8]) -> Result<String, Error> {
+    Ok(SelfAddressing::from_str(code)?.derive(data).to_str())
+}
+
+/// Verify Attached Signature
+///
+/// Checks indexed signature against some data, with the key
+/// and signature given in their qb64 representation.
+pub fn verify_attached_sig(key_qb64: &str, sig_qb64: &str, data: &[u8]) -> Result<bool, Error> {
+    let key = BasicPrefix::from_str(key_qb64)?;
+    let attached_sig = AttachedSignaturePrefix::from_str(sig_qb64)?;
+    key.verify(data, &attached_sig.signature)
+}
+
+#[test]
+fn test_micro_api() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        keys::{PrivateKey, PublicKey},
+    };
+    use ed25519_dalek::Keypair;
+    use rand::rngs::OsRng;
+
+    let data = b"some data";
+    assert_eq!(
+        derive_said("E", data)?,
+        SelfAddressing::Blake3_256.derive(data).to_str()
+    );
+    assert!(derive_said("!", data).is_err());
+
+    let kp = Keypair::generate(&mut OsRng);
+    let pub_key = PublicKey::new(kp.public.to_bytes().to_vec());
+    let priv_key = PrivateKey::new(kp.secret.to_bytes().to_vec());
+    let key = Basic::Ed25519.derive(pub_key);
+    let sig = AttachedSignaturePrefix::new(SelfSigning::Ed25519Sha512, priv_key.sign_ed(data)?, 0);
+
+    assert!(verify_attached_sig(&key.to_str(), &sig.to_str(), data)?);
+    assert!(!verify_attached_sig(
+        &key.to_str(),
+        &sig.to_str(),
+        b"other data"
+    )?);
+
+    Ok(())
+}
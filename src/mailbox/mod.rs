@@ -0,0 +1,271 @@
+//! Mailbox
+//!
+//! Store and forward of messages for controllers which aren't always
+//! online. Messages are kept per recipient identifier under topics and
+//! numbered per topic, so recipient can ask only for the ones it hasn't
+//! seen yet, eg. with `mbx` query.
+
+use std::{convert::TryFrom, str::FromStr, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "query")]
+use crate::query::{query_event::SignedQuery, QueryError, Route};
+use crate::{
+    database::sled::SledEventDatabase, error::Error, event::event_data::EventData,
+    event_message::signed_event_message::Message, event_parsing::SignedEventData,
+    prefix::IdentifierPrefix, processor::EventProcessor,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Topic {
+    /// Receipts of recipient's events
+    #[serde(rename = "/receipt")]
+    Receipt,
+    /// Messages of multisig groups recipient is member of
+    #[serde(rename = "/multisig")]
+    Multisig,
+    /// Events delegated by recipient
+    #[serde(rename = "/delegate")]
+    Delegate,
+}
+
+impl Topic {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Topic::Receipt => "/receipt",
+            Topic::Multisig => "/multisig",
+            Topic::Delegate => "/delegate",
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Topic may be given with or without leading slash.
+        match s.trim_start_matches('/') {
+            "receipt" => Ok(Topic::Receipt),
+            "multisig" => Ok(Topic::Multisig),
+            "delegate" => Ok(Topic::Delegate),
+            _ => Err(Error::DeserializeError(format!("Unknown topic: {}", s))),
+        }
+    }
+}
+
+/// Mailbox Message
+///
+/// CESR stream of a single message stored for recipient, with its
+/// index within the topic.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MailboxMessage {
+    #[serde(rename = "i")]
+    pub index: u64,
+
+    #[serde(rename = "t")]
+    pub topic: Topic,
+
+    #[serde(rename = "m")]
+    pub message: Vec<u8>,
+}
+
+pub struct Mailbox {
+    db: Arc<SledEventDatabase>,
+}
+
+impl Mailbox {
+    pub fn new(db: Arc<SledEventDatabase>) -> Self {
+        Self { db }
+    }
+
+    pub fn store(
+        &self,
+        recipient: &IdentifierPrefix,
+        topic: Topic,
+        message: &[u8],
+    ) -> Result<MailboxMessage, Error> {
+        self.db
+            .add_mailbox_message(recipient, topic, message.to_vec())
+    }
+
+    /// Returns messages of given topic, starting from message of index
+    /// `from`.
+    pub fn get(
+        &self,
+        recipient: &IdentifierPrefix,
+        topic: Topic,
+        from: u64,
+    ) -> Vec<MailboxMessage> {
+        self.db
+            .get_mailbox_messages(recipient, topic)
+            .into_iter()
+            .flatten()
+            .filter(|msg| msg.index >= from)
+            .collect()
+    }
+
+    /// Forward
+    ///
+    /// Stores message in mailbox of identifier it is meant for:
+    ///  * receipts go to controller of receipted event,
+    ///  * delegated events go to their delegator,
+    ///  * `/fwd` exn messages go to `pre` under their `topic`.
+    ///
+    /// Returns recipient and topic, or None if message isn't meant for
+    /// anybody's mailbox.
+    pub fn forward(
+        &self,
+        message: SignedEventData,
+    ) -> Result<Option<(IdentifierPrefix, Topic)>, Error> {
+        let cesr = message.to_cesr()?;
+        let destination = match Message::try_from(message)? {
+            Message::NontransferableRct(rct) => Some((rct.body.event.prefix, Topic::Receipt)),
+            Message::TransferableRct(rct) => Some((rct.body.event.prefix, Topic::Receipt)),
+            Message::WitnessRct(rct) => Some((rct.body.event.prefix, Topic::Receipt)),
+            Message::Event(ev) => match ev.event_message.event.get_event_data() {
+                EventData::Dip(dip) => Some((dip.delegator, Topic::Delegate)),
+                EventData::Drt(_) => EventProcessor::new(self.db.clone())
+                    .compute_state(&ev.event_message.event.get_prefix())?
+                    .and_then(|state| state.delegator)
+                    .map(|delegator| (delegator, Topic::Delegate)),
+                _ => None,
+            },
+            #[cfg(feature = "query")]
+            Message::Exchange(exn) if exn.exchange.event.get_route() == "/fwd" => {
+                let data = exn.exchange.event.get_data();
+                let recipient = data["pre"]
+                    .as_str()
+                    .ok_or_else(|| Error::SemanticError("Missing forward recipient".into()))?
+                    .parse()?;
+                let topic = data["topic"]
+                    .as_str()
+                    .ok_or_else(|| Error::SemanticError("Missing forward topic".into()))?
+                    .parse()?;
+                Some((recipient, topic))
+            }
+            #[cfg(feature = "query")]
+            _ => None,
+        };
+        if let Some((recipient, topic)) = &destination {
+            self.store(recipient, *topic, &cesr)?;
+        }
+        Ok(destination)
+    }
+
+    /// Process Query
+    ///
+    /// Answers `mbx` query signed by the mailbox owner with CESR stream
+    /// of messages of requested topics. All topics are returned if
+    /// query doesn't name any.
+    #[cfg(feature = "query")]
+    pub fn process_query(&self, qry: &SignedQuery) -> Result<Vec<u8>, Error> {
+        if qry.envelope.event.get_route() != Route::Mbx {
+            return Err(QueryError::Error("Improper query route".into()).into());
+        }
+        let args = qry.envelope.event.get_query_data().data;
+        if args.i != qry.signer {
+            return Err(QueryError::Error("Mailbox can be read by its owner only".into()).into());
+        }
+        let owner_keys = EventProcessor::new(self.db.clone())
+            .compute_state(&qry.signer)?
+            .ok_or_else(|| QueryError::Error("No querier identifier in db".into()))?
+            .current;
+        if !owner_keys.verify(&qry.envelope.serialize()?, &qry.signatures)? {
+            return Err(Error::SignatureVerificationError);
+        }
+        let topics = match &args.topics {
+            Some(topics) => topics
+                .iter()
+                .map(|(topic, from)| Ok((topic.parse()?, *from)))
+                .collect::<Result<Vec<_>, Error>>()?,
+            None => vec![
+                (Topic::Receipt, 0),
+                (Topic::Multisig, 0),
+                (Topic::Delegate, 0),
+            ],
+        };
+        Ok(topics
+            .into_iter()
+            .flat_map(|(topic, from)| self.get(&args.i, topic, from))
+            .flat_map(|msg| msg.message)
+            .collect())
+    }
+}
+
+#[cfg(feature = "query")]
+#[test]
+fn test_mailbox() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing, event::SerializationFormats, keri::Keri,
+        query::query_event::QueryEvent, signer::CryptoBox,
+    };
+    use std::{collections::BTreeMap, sync::Mutex};
+    use tempfile::Builder;
+
+    let controller = |root: &std::path::Path| -> Result<Keri<CryptoBox>, Error> {
+        let db = Arc::new(SledEventDatabase::new(root)?);
+        let mut keri = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+        keri.incept(None)?;
+        Ok(keri)
+    };
+    let alice_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice = controller(alice_root.path())?;
+    let bob_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let bob = controller(bob_root.path())?;
+
+    // Mailbox provider knows alice and bob.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path())?);
+    let provider = Keri::new(db.clone(), Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    provider.respond(&alice.get_kerl()?.unwrap())?;
+    provider.respond(&bob.get_kerl()?.unwrap())?;
+    let mailbox = Mailbox::new(db);
+
+    // Bob receipts alice's inception while she is offline.
+    bob.respond(&alice.get_kerl()?.unwrap())?;
+    let alice_icp = alice
+        .db()
+        .get_kel_finalized_events(alice.prefix())
+        .unwrap()
+        .next()
+        .unwrap();
+    let rct: SignedEventData = bob
+        .make_rct(alice_icp.signed_event_message.event_message)?
+        .into();
+    assert_eq!(
+        mailbox.forward(rct.clone())?,
+        Some((alice.prefix().clone(), Topic::Receipt))
+    );
+    let stored = mailbox.get(alice.prefix(), Topic::Receipt, 0);
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].index, 0);
+    assert_eq!(stored[0].message, rct.to_cesr()?);
+
+    // Alice picks it up with mbx query.
+    let query =
+        |topics: BTreeMap<String, u64>, signer: &Keri<CryptoBox>| -> Result<SignedQuery, Error> {
+            let qry = QueryEvent::new_mailbox_query(
+                alice.prefix(),
+                topics,
+                SerializationFormats::JSON,
+                &SelfAddressing::Blake3_256,
+            )?;
+            let signatures = signer.sign(&qry.serialize()?)?;
+            Ok(SignedQuery::new(qry, signer.prefix().clone(), signatures))
+        };
+    let topics: BTreeMap<_, _> = vec![("/receipt".to_string(), 0)].into_iter().collect();
+    assert_eq!(
+        mailbox.process_query(&query(topics, &alice)?)?,
+        rct.to_cesr()?
+    );
+    let topics: BTreeMap<_, _> = vec![("/receipt".to_string(), 1)].into_iter().collect();
+    assert!(mailbox
+        .process_query(&query(topics.clone(), &alice)?)?
+        .is_empty());
+
+    // Nobody else can read it.
+    assert!(mailbox.process_query(&query(topics, &bob)?).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+//! Merkle Tree
+//!
+//! Batches many data digests into one merkle tree, so only its root
+//! has to be anchored in KEL (as root seal of single `ixn` event).
+//! Inclusion of each digest in anchored batch is later proven with
+//! path of sibling nodes from its leaf to the root.
+//!
+//! Leaves and inner nodes are hashed with different one byte prefix,
+//! so inner node can't be presented as leaf. Node without sibling is
+//! moved to the upper level unchanged.
+
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    derivation::self_addressing::SelfAddressing,
+    error::Error,
+    event::sections::seal::{RootSeal, Seal},
+    prefix::{Prefix, SelfAddressingPrefix},
+};
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+fn hash_leaf(derivation: &SelfAddressing, leaf: &SelfAddressingPrefix) -> SelfAddressingPrefix {
+    derivation.derive(&[&[LEAF_PREFIX], leaf.to_str().as_bytes()].concat())
+}
+
+fn hash_node(
+    derivation: &SelfAddressing,
+    left: &SelfAddressingPrefix,
+    right: &SelfAddressingPrefix,
+) -> SelfAddressingPrefix {
+    derivation.derive(
+        &[
+            &[NODE_PREFIX],
+            left.to_str().as_bytes(),
+            right.to_str().as_bytes(),
+        ]
+        .concat(),
+    )
+}
+
+/// Merkle Tree
+///
+/// Tree built over batch of digests, with all levels kept, so
+/// inclusion proof of any leaf can be produced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MerkleTree {
+    // Levels of tree, from hashed leaves up to the root.
+    levels: Vec<Vec<SelfAddressingPrefix>>,
+}
+
+impl MerkleTree {
+    /// Builds tree over `leaves`, hashing its nodes with `derivation`.
+    pub fn new(
+        leaves: &[SelfAddressingPrefix],
+        derivation: &SelfAddressing,
+    ) -> Result<Self, Error> {
+        if leaves.is_empty() {
+            return Err(Error::SemanticError("Empty merkle tree".into()));
+        }
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| hash_leaf(derivation, leaf))
+            .collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let upper = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(derivation, left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(upper);
+        }
+        Ok(Self { levels })
+    }
+
+    /// Number of leaves in the tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn root(&self) -> SelfAddressingPrefix {
+        self.levels.last().unwrap()[0].clone()
+    }
+
+    /// Seal which anchors root of the tree in KEL.
+    pub fn root_seal(&self) -> Seal {
+        Seal::Root(RootSeal {
+            tree_root: self.root(),
+        })
+    }
+
+    /// Returns proof of inclusion of leaf of given index, or None if
+    /// there is no such leaf.
+    pub fn proof(&self, index: usize) -> Option<InclusionProof> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut position = index;
+        let mut path = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if let Some(node) = level.get(sibling) {
+                let side = if sibling < position {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                path.push((side, node.clone()));
+            }
+            position /= 2;
+        }
+        Some(InclusionProof { index, path })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    #[serde(rename = "l")]
+    Left,
+    #[serde(rename = "r")]
+    Right,
+}
+
+/// Inclusion Proof
+///
+/// Siblings of nodes on the path from leaf to the root of tree,
+/// together with side they are on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InclusionProof {
+    #[serde(rename = "n")]
+    pub index: usize,
+
+    #[serde(rename = "p")]
+    pub path: Vec<(Side, SelfAddressingPrefix)>,
+}
+
+impl InclusionProof {
+    /// Computes root of tree which `leaf` belongs to according to the
+    /// proof.
+    pub fn compute_root(
+        &self,
+        leaf: &SelfAddressingPrefix,
+        derivation: &SelfAddressing,
+    ) -> SelfAddressingPrefix {
+        self.path.iter().fold(
+            hash_leaf(derivation, leaf),
+            |node, (side, sibling)| match side {
+                Side::Left => hash_node(derivation, sibling, &node),
+                Side::Right => hash_node(derivation, &node, sibling),
+            },
+        )
+    }
+
+    /// Checks if `leaf` is included in tree of given `root`.
+    pub fn verify(&self, leaf: &SelfAddressingPrefix, root: &SelfAddressingPrefix) -> bool {
+        &self.compute_root(leaf, &root.derivation) == root
+    }
+}
+
+#[test]
+fn test_merkle_tree() -> Result<(), Error> {
+    let leaves: Vec<_> = (0..11u8)
+        .map(|i| SelfAddressing::Blake3_256.derive(&[i]))
+        .collect();
+    assert!(MerkleTree::new(&[], &SelfAddressing::Blake3_256).is_err());
+
+    for size in 1..=leaves.len() {
+        let tree = MerkleTree::new(&leaves[..size], &SelfAddressing::SHA3_256)?;
+        let root = tree.root();
+        assert_eq!(root.derivation, SelfAddressing::SHA3_256);
+        for (index, leaf) in leaves[..size].iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            assert!(proof.verify(leaf, &root));
+            // Proof of one leaf doesn't prove inclusion of other data.
+            assert!(!proof.verify(&leaves[(index + 1) % leaves.len()], &root));
+        }
+        assert!(tree.proof(size).is_none());
+    }
+
+    // Single leaf tree has hashed leaf as its root.
+    let tree = MerkleTree::new(&leaves[..1], &SelfAddressing::Blake3_256)?;
+    assert_eq!(
+        tree.root(),
+        hash_leaf(&SelfAddressing::Blake3_256, &leaves[0])
+    );
+    assert!(tree.proof(0).unwrap().path.is_empty());
+
+    // Inner node can't be proven as a leaf.
+    let tree = MerkleTree::new(&leaves[..4], &SelfAddressing::Blake3_256)?;
+    let inner = tree.levels[1][0].clone();
+    let forged = InclusionProof {
+        index: 0,
+        path: vec![(Side::Right, tree.levels[1][1].clone())],
+    };
+    assert!(!forged.verify(&inner, &tree.root()));
+
+    // Proof survives serialization.
+    let proof = tree.proof(2).unwrap();
+    let serialized = serde_json::to_string(&proof)?;
+    let deserialized: InclusionProof = serde_json::from_str(&serialized)?;
+    assert!(deserialized.verify(&leaves[2], &tree.root()));
+
+    Ok(())
+}
@@ -0,0 +1,404 @@
+//! Out-Of-Band Introduction
+//!
+//! OOBI urls associate identifier with location it can be discovered
+//! at, eg. `http://127.0.0.1:5642/oobi/{cid}/witness/{eid}`. Resolving
+//! an OOBI fetches messages served under the url and processes them.
+//! Introduced location is trusted only if it's confirmed by signed
+//! `/loc/scheme` and `/end/role` replies.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    prefix::{IdentifierPrefix, Prefix},
+};
+#[cfg(feature = "query")]
+use crate::{
+    event_message::signed_event_message::Message, event_parsing::message::signed_event_stream,
+    processor::EventProcessor, query::QueryError,
+};
+#[cfg(feature = "query")]
+use std::convert::TryFrom;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Controller,
+    Witness,
+    Watcher,
+    Agent,
+    Mailbox,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Controller => "controller",
+            Role::Witness => "witness",
+            Role::Watcher => "watcher",
+            Role::Agent => "agent",
+            Role::Mailbox => "mailbox",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "controller" => Ok(Role::Controller),
+            "witness" => Ok(Role::Witness),
+            "watcher" => Ok(Role::Watcher),
+            "agent" => Ok(Role::Agent),
+            "mailbox" => Ok(Role::Mailbox),
+            _ => Err(Error::DeserializeError(format!("Unknown role: {}", s))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    Http,
+    Https,
+    Tcp,
+}
+
+impl Scheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+            Scheme::Tcp => "tcp",
+        }
+    }
+}
+
+impl FromStr for Scheme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "http" => Ok(Scheme::Http),
+            "https" => Ok(Scheme::Https),
+            "tcp" => Ok(Scheme::Tcp),
+            _ => Err(Error::DeserializeError(format!("Unknown scheme: {}", s))),
+        }
+    }
+}
+
+/// Out-Of-Band Introduction
+///
+/// Parsed OOBI url. Role defaults to `Controller` if url doesn't
+/// specify it, and endpoint identifier is then the controller itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Oobi {
+    pub scheme: Scheme,
+    /// Host with optional port, eg. `127.0.0.1:5642`
+    pub location: String,
+    pub cid: IdentifierPrefix,
+    pub role: Role,
+    pub eid: Option<IdentifierPrefix>,
+}
+
+impl Oobi {
+    /// Base url of the endpoint, without the oobi path
+    pub fn base_url(&self) -> String {
+        [self.scheme.as_str(), "://", &self.location].concat()
+    }
+
+    /// Identifier of the endpoint introduced by this OOBI
+    pub fn get_eid(&self) -> IdentifierPrefix {
+        self.eid.clone().unwrap_or_else(|| self.cid.clone())
+    }
+}
+
+impl FromStr for Oobi {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let improper = || Error::DeserializeError(format!("Improper OOBI url: {}", s));
+        let (scheme, rest) = s.split_once("://").ok_or_else(improper)?;
+        let (location, path) = rest.split_once("/oobi/").ok_or_else(improper)?;
+        if location.is_empty() || location.contains('/') {
+            return Err(improper());
+        }
+        let mut segments = path.trim_end_matches('/').split('/');
+        let cid = segments.next().ok_or_else(improper)?.parse()?;
+        let role = match segments.next() {
+            Some(role) => role.parse()?,
+            None => Role::Controller,
+        };
+        let eid = segments
+            .next()
+            .map(IdentifierPrefix::from_str)
+            .transpose()?;
+        if segments.next().is_some() {
+            return Err(improper());
+        }
+        Ok(Oobi {
+            scheme: scheme.parse()?,
+            location: location.to_string(),
+            cid,
+            role,
+            eid,
+        })
+    }
+}
+
+impl fmt::Display for Oobi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/oobi/{}/{}",
+            self.base_url(),
+            self.cid.to_str(),
+            self.role.as_str()
+        )?;
+        match &self.eid {
+            Some(eid) => write!(f, "/{}", eid.to_str()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Location Scheme
+///
+/// Url under which endpoint `eid` is reachable with given `scheme`.
+/// Data of `/loc/scheme` reply message, signed by the endpoint itself.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LocationScheme {
+    pub eid: IdentifierPrefix,
+    pub scheme: Scheme,
+    pub url: String,
+}
+
+impl LocationScheme {
+    pub fn new(eid: IdentifierPrefix, scheme: Scheme, url: String) -> Self {
+        Self { eid, scheme, url }
+    }
+}
+
+/// End Role
+///
+/// Authorization of endpoint `eid` to play `role` for controller
+/// `cid`. Data of `/end/role/add` and `/end/role/cut` reply messages,
+/// signed by the controller.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EndRole {
+    pub cid: IdentifierPrefix,
+    pub role: Role,
+    pub eid: IdentifierPrefix,
+}
+
+impl EndRole {
+    pub fn new(cid: IdentifierPrefix, role: Role, eid: IdentifierPrefix) -> Self {
+        Self { cid, role, eid }
+    }
+}
+
+#[cfg(feature = "query")]
+pub trait OobiTransport {
+    /// Fetch
+    ///
+    /// Gets messages served under OOBI url as CESR stream.
+    fn fetch(&self, oobi: &Oobi) -> Result<Vec<u8>, Error>;
+}
+
+/// OOBI Resolver
+///
+/// Fetches messages referenced by OOBI with provided transport and
+/// processes them. Served stream has to contain KEL of the controller,
+/// `/end/role` reply signed by the controller, which authorizes the
+/// endpoint to play OOBI role, and `/loc/scheme` reply signed by the
+/// endpoint. Accepted replies are stored with other replies, so
+/// resolved location is then returned by `EventProcessor::get_end_point`.
+#[cfg(feature = "query")]
+pub struct OobiResolver<'a> {
+    processor: &'a EventProcessor,
+    transport: &'a dyn OobiTransport,
+}
+
+#[cfg(feature = "query")]
+impl<'a> OobiResolver<'a> {
+    pub fn new(processor: &'a EventProcessor, transport: &'a dyn OobiTransport) -> Self {
+        Self {
+            processor,
+            transport,
+        }
+    }
+
+    /// Resolves `oobi` and returns location of its endpoint, as signed
+    /// by the endpoint.
+    pub fn resolve(&self, oobi: &Oobi) -> Result<LocationScheme, Error> {
+        let stream = self.transport.fetch(oobi)?;
+        let messages = signed_event_stream(&stream)
+            .map_err(|e| Error::DeserializeError(e.to_string()))?
+            .1;
+        for message in messages {
+            match self.processor.process(Message::try_from(message)?) {
+                Ok(_)
+                | Err(Error::EventDuplicateError)
+                | Err(Error::QueryError(QueryError::StaleRpy)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        self.processor
+            .compute_state(&oobi.cid)?
+            .ok_or_else(|| Error::SemanticError("Unknown OOBI identifier".into()))?;
+
+        let eid = oobi.get_eid();
+        self.processor
+            .get_end_point(&oobi.cid, oobi.role, oobi.scheme)
+            .into_iter()
+            .find(|loc| loc.eid == eid)
+            .ok_or_else(|| {
+                Error::SemanticError("OOBI location not confirmed by signed replies".into())
+            })
+    }
+}
+
+#[test]
+fn test_oobi_parsing() -> Result<(), Error> {
+    let url = "http://127.0.0.1:5642/oobi/EyX-zd8YpAYxq7Zp8Y8pKZsqNcWRpkRgxMR8ZLbF1SPE/witness/BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo";
+    let oobi: Oobi = url.parse()?;
+    assert_eq!(oobi.scheme, Scheme::Http);
+    assert_eq!(oobi.location, "127.0.0.1:5642");
+    assert_eq!(oobi.role, Role::Witness);
+    assert_eq!(
+        oobi.get_eid().to_str(),
+        "BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo"
+    );
+    assert_eq!(oobi.to_string(), url);
+
+    let oobi: Oobi =
+        "tcp://localhost:5621/oobi/EyX-zd8YpAYxq7Zp8Y8pKZsqNcWRpkRgxMR8ZLbF1SPE/".parse()?;
+    assert_eq!(oobi.role, Role::Controller);
+    assert_eq!(oobi.get_eid(), oobi.cid);
+    assert_eq!(oobi.base_url(), "tcp://localhost:5621");
+
+    assert!(
+        "http://127.0.0.1:5642/EyX-zd8YpAYxq7Zp8Y8pKZsqNcWRpkRgxMR8ZLbF1SPE"
+            .parse::<Oobi>()
+            .is_err()
+    );
+    assert!(
+        "ftp://127.0.0.1/oobi/EyX-zd8YpAYxq7Zp8Y8pKZsqNcWRpkRgxMR8ZLbF1SPE"
+            .parse::<Oobi>()
+            .is_err()
+    );
+    assert!(
+        "http://127.0.0.1/oobi/EyX-zd8YpAYxq7Zp8Y8pKZsqNcWRpkRgxMR8ZLbF1SPE/judge"
+            .parse::<Oobi>()
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "query")]
+#[test]
+fn test_oobi_resolution() -> Result<(), Error> {
+    use crate::{
+        database::sled::SledEventDatabase,
+        derivation::self_addressing::SelfAddressing,
+        event::{sections::seal::EventSeal, SerializationFormats},
+        event_parsing::SignedEventData,
+        keri::Keri,
+        prefix::AttachedSignaturePrefix,
+        query::reply::{ReplyEvent, SignedReply},
+        signer::{CryptoBox, KeyManager},
+    };
+    use std::sync::{Arc, Mutex};
+    use tempfile::Builder;
+
+    // Transport serving the same stream under every url.
+    struct MockTransport {
+        stream: Vec<u8>,
+    }
+
+    impl OobiTransport for MockTransport {
+        fn fetch(&self, _oobi: &Oobi) -> Result<Vec<u8>, Error> {
+            Ok(self.stream.clone())
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let controller_db =
+        Arc::new(SledEventDatabase::new(root.path().join("controller").as_path()).unwrap());
+    let mut controller = Keri::new(controller_db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+    let icp = controller.incept(None)?;
+    let cid = controller.prefix().clone();
+    let seal = EventSeal {
+        prefix: cid.clone(),
+        sn: 0,
+        event_digest: icp.event_message.get_digest(),
+    };
+    let km = controller.key_manager();
+    let sign = |rpy: crate::event::EventMessage<ReplyEvent>| -> Result<Vec<u8>, Error> {
+        let signature = km.lock().unwrap().sign(&rpy.serialize()?)?;
+        SignedEventData::from(SignedReply::new_trans(
+            rpy,
+            seal.clone(),
+            vec![AttachedSignaturePrefix::new(
+                km.lock().unwrap().signature_type(),
+                signature,
+                0,
+            )],
+        ))
+        .to_cesr()
+    };
+    let kel = SignedEventData::from(&icp).to_cesr()?;
+    let loc = LocationScheme::new(cid.clone(), Scheme::Http, "http://127.0.0.1:5642".into());
+    let loc_rpy = sign(ReplyEvent::new_loc_scheme(
+        loc.clone(),
+        SelfAddressing::Blake3_256,
+        SerializationFormats::JSON,
+    )?)?;
+    let end_role_rpy = sign(ReplyEvent::new_end_role(
+        EndRole::new(cid.clone(), Role::Controller, cid.clone()),
+        false,
+        SelfAddressing::Blake3_256,
+        SerializationFormats::JSON,
+    )?)?;
+
+    let db = Arc::new(SledEventDatabase::new(root.path().join("resolver").as_path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+    let oobi: Oobi = format!("http://127.0.0.1:5642/oobi/{}/controller", cid.to_str()).parse()?;
+
+    // KEL alone doesn't confirm the location.
+    let transport = MockTransport {
+        stream: kel.clone(),
+    };
+    assert!(OobiResolver::new(&processor, &transport)
+        .resolve(&oobi)
+        .is_err());
+    assert!(processor
+        .get_end_point(&cid, Role::Controller, Scheme::Http)
+        .is_empty());
+
+    let transport = MockTransport {
+        stream: [kel, loc_rpy, end_role_rpy].concat(),
+    };
+    let resolver = OobiResolver::new(&processor, &transport);
+    assert_eq!(resolver.resolve(&oobi)?, loc);
+    assert_eq!(
+        processor.get_end_point(&cid, Role::Controller, Scheme::Http),
+        vec![loc.clone()]
+    );
+    assert!(processor
+        .get_end_point(&cid, Role::Witness, Scheme::Http)
+        .is_empty());
+    // Resolving the same OOBI again is fine.
+    assert_eq!(resolver.resolve(&oobi)?, loc);
+
+    // OOBI of identifier which KEL isn't served can't be resolved.
+    let unknown: Oobi =
+        "http://127.0.0.1:5642/oobi/EyX-zd8YpAYxq7Zp8Y8pKZsqNcWRpkRgxMR8ZLbF1SPE".parse()?;
+    assert!(resolver.resolve(&unknown).is_err());
+
+    Ok(())
+}
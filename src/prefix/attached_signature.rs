@@ -1,11 +1,10 @@
 use super::{Prefix, SelfSigningPrefix};
 use crate::{
-    derivation::{
-        attached_signature_code::AttachedSignatureCode, self_signing::SelfSigning, DerivationCode,
-    },
+    derivation::{attached_signature_code::AttachedSignatureCode, self_signing::SelfSigning},
     error::Error,
 };
-use base64::decode_config;
+use alloc::{string::String, vec::Vec};
+use base64::{decode_config, encode_config};
 use core::str::FromStr;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
@@ -22,6 +21,16 @@ impl AttachedSignaturePrefix {
             index,
         }
     }
+
+    /// Text representation of signature. Unlike `to_str`, returns error
+    /// if index doesn't fit in the code.
+    pub fn encode(&self) -> Result<String, Error> {
+        Ok([
+            AttachedSignatureCode::new(self.signature.derivation, self.index).to_str()?,
+            encode_config(self.derivative(), base64::URL_SAFE_NO_PAD),
+        ]
+        .join(""))
+    }
 }
 
 impl FromStr for AttachedSignaturePrefix {
@@ -33,7 +42,10 @@ impl FromStr for AttachedSignaturePrefix {
         if (s.len()) == code.prefix_b64_len() {
             Ok(Self::new(
                 code.code,
-                decode_config(&s[code.code_len()..code.prefix_b64_len()], base64::URL_SAFE)?,
+                code.code.strip_lead(decode_config(
+                    &s[code.code_len()..code.prefix_b64_len()],
+                    base64::URL_SAFE,
+                )?)?,
                 code.index,
             ))
         } else {
@@ -47,10 +59,15 @@ impl FromStr for AttachedSignaturePrefix {
 
 impl Prefix for AttachedSignaturePrefix {
     fn derivative(&self) -> Vec<u8> {
-        self.signature.signature.to_vec()
+        self.signature.derivative()
     }
+    /// Code of signature which index doesn't fit in it is empty, so
+    /// `to_str` output won't parse back. See `encode` for the checked
+    /// version.
     fn derivation_code(&self) -> String {
-        AttachedSignatureCode::new(self.signature.derivation, self.index).to_str()
+        AttachedSignatureCode::new(self.signature.derivation, self.index)
+            .to_str()
+            .unwrap_or_default()
     }
 }
 
@@ -60,7 +77,7 @@ impl Serialize for AttachedSignaturePrefix {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.to_str())
+        serializer.serialize_str(&self.encode().map_err(serde::ser::Error::custom)?)
     }
 }
 
@@ -4,6 +4,7 @@ use crate::{
     error::Error,
     keys::PublicKey,
 };
+use alloc::{string::String, vec::Vec};
 use base64::decode_config;
 use core::str::FromStr;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -25,6 +26,18 @@ impl BasicPrefix {
     pub fn verify(&self, data: &[u8], signature: &SelfSigningPrefix) -> Result<bool, Error> {
         verify(data, self, signature)
     }
+
+    /// Returns X25519 encryption key prefix matching Ed25519 key of
+    /// this prefix.
+    pub fn to_x25519(&self) -> Result<BasicPrefix, Error> {
+        match self.derivation {
+            Basic::Ed25519 | Basic::Ed25519NT => {
+                Ok(Basic::X25519.derive(self.public_key.ed_to_x25519()?))
+            }
+            Basic::X25519 => Ok(self.clone()),
+            _ => Err(Error::ImproperPrefixType),
+        }
+    }
 }
 
 impl PartialEq for BasicPrefix {
@@ -1,7 +1,11 @@
 use crate::{
-    derivation::{basic::Basic, self_signing::SelfSigning},
+    derivation::{basic::Basic, self_signing::SelfSigning, DerivationCode},
     error::Error,
 };
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use base64::encode_config;
 use core::str::FromStr;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -32,6 +36,16 @@ pub trait Prefix: FromStr<Err = Error> {
             }
         }
     }
+
+    /// Binary domain form of prefix, which is 3/4 of its text size.
+    fn to_binary(&self) -> Vec<u8> {
+        // Prefixes take whole quadlets, so they are always decodable.
+        base64::decode_config(self.to_str(), base64::URL_SAFE).unwrap_or_default()
+    }
+
+    fn from_binary(data: &[u8]) -> Result<Self, Error> {
+        encode_config(data, base64::URL_SAFE).parse()
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -41,9 +55,11 @@ pub enum IdentifierPrefix {
     SelfSigning(SelfSigningPrefix),
 }
 
-impl FromStr for IdentifierPrefix {
-    type Err = Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl IdentifierPrefix {
+    /// Parses prefix without checking if it's valid and in canonical
+    /// form, eg. to read data of other implementations which are less
+    /// strict. `from_str` should be used otherwise.
+    pub fn from_str_lenient(s: &str) -> Result<Self, Error> {
         match BasicPrefix::from_str(s) {
             Ok(bp) => Ok(Self::Basic(bp)),
             Err(_) => match SelfAddressingPrefix::from_str(s) {
@@ -52,6 +68,53 @@ impl FromStr for IdentifierPrefix {
             },
         }
     }
+
+    /// Basic prefix of non-transferable key identifies ephemeral
+    /// identifier, which can't have any events after inception.
+    pub fn is_transferable(&self) -> bool {
+        match self {
+            Self::Basic(bp) => bp.derivation.is_transferable(),
+            _ => true,
+        }
+    }
+
+    /// Validate
+    ///
+    /// Checks if key, digest or signature of prefix has length required
+    /// by its derivation code, and if text form of prefix parses back
+    /// to the same prefix.
+    pub fn validate(&self) -> Result<(), Error> {
+        let expected_len = match self {
+            Self::Basic(bp) => bp.derivation.prefix_b64_len(),
+            Self::SelfAddressing(sap) => sap.derivation.prefix_b64_len(),
+            Self::SelfSigning(ssp) => ssp.derivation.prefix_b64_len(),
+        };
+        let text = self.to_str();
+        if text.len() != expected_len {
+            return Err(Error::InvalidPrefix(format!(
+                "Incorrect derivative length for code {}",
+                self.derivation_code()
+            )));
+        }
+        if Self::from_str_lenient(&text)? != *self {
+            return Err(Error::InvalidPrefix(format!("Not canonical: {}", text)));
+        }
+        Ok(())
+    }
+}
+
+/// Parsing is strict, only valid prefix in canonical form is accepted,
+/// so the same identifier can't be written in two ways.
+impl FromStr for IdentifierPrefix {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let prefix = Self::from_str_lenient(s)?;
+        prefix.validate()?;
+        if prefix.to_str() != s {
+            return Err(Error::InvalidPrefix(format!("Not canonical: {}", s)));
+        }
+        Ok(prefix)
+    }
 }
 
 impl Prefix for IdentifierPrefix {
@@ -121,6 +184,13 @@ pub fn verify(
                 .verify_ecdsa(data.as_ref(), &signature.signature)),
             _ => Err(Error::SemanticError("wrong sig type".to_string())),
         },
+        #[cfg(feature = "pq")]
+        Basic::SphincsSha2128s => match signature.derivation {
+            SelfSigning::SphincsSha2128s => Ok(key
+                .public_key
+                .verify_sphincs(data.as_ref(), &signature.signature)),
+            _ => Err(Error::SemanticError("wrong sig type".to_string())),
+        },
         _ => Err(Error::SemanticError("inelligable key type".to_string())),
     }
 }
@@ -192,6 +262,43 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn validate() -> Result<(), Error> {
+        let pref: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+        assert!(pref.validate().is_ok());
+
+        // key too short for its derivation code
+        let short_key =
+            IdentifierPrefix::Basic(Basic::Ed25519.derive(PublicKey::new(vec![0u8; 31])));
+        assert!(matches!(short_key.validate(), Err(Error::InvalidPrefix(_))));
+
+        // unused bits of last character set, so the same key could be
+        // written in more than one way
+        assert!(
+            IdentifierPrefix::from_str("BAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAB").is_err()
+        );
+
+        // padding instead of last character
+        assert!(
+            IdentifierPrefix::from_str("BAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn binary_domain() -> Result<(), Error> {
+        let pref: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+        let binary = pref.to_binary();
+        assert_eq!(binary.len(), 33);
+        assert_eq!(IdentifierPrefix::from_binary(&binary)?, pref);
+
+        let sig: AttachedSignaturePrefix = "AA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw".parse()?;
+        assert_eq!(AttachedSignaturePrefix::from_binary(&sig.to_binary())?, sig);
+
+        Ok(())
+    }
+
     #[test]
     fn simple_serialize() -> Result<(), Error> {
         let pref = Basic::Ed25519NT.derive(PublicKey::new(
@@ -251,7 +358,7 @@ mod tests {
         // All codes that are mapped to `BasicPrefix`.
         let basic_codes = vec!["B", "C", "D", "L", "1AAA", "1AAB", "1AAC", "1AAD"].into_iter();
         // Allowed string lengths for respective basic codes.
-        let allowed_lengths = vec![43, 43, 43, 75, 47, 47, 76, 76].into_iter();
+        let allowed_lengths = vec![43, 43, 43, 75, 44, 44, 76, 76].into_iter();
         let is_basic = |identifier| matches!(&identifier, IdentifierPrefix::Basic(_));
         all_codes(basic_codes.zip(allowed_lengths).collect(), is_basic)?;
 
@@ -3,6 +3,11 @@ use crate::{
     error::Error,
     keys::{PrivateKey, PublicKey},
 };
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
 use base64::decode_config;
 use core::str::FromStr;
 use ed25519_dalek::SecretKey;
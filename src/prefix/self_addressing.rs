@@ -1,6 +1,7 @@
 use super::Prefix;
 use crate::derivation::{self_addressing::SelfAddressing, DerivationCode};
 use crate::error::Error;
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
 use base64::decode_config;
 use core::{fmt, str::FromStr};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -3,6 +3,7 @@ use crate::{
     derivation::{self_signing::SelfSigning, DerivationCode},
     error::Error,
 };
+use alloc::{string::String, vec::Vec};
 use base64::decode_config;
 use core::str::FromStr;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -31,7 +32,10 @@ impl FromStr for SelfSigningPrefix {
         if s.len() == code.prefix_b64_len() {
             Ok(Self::new(
                 code,
-                decode_config(&s[code.code_len()..code.prefix_b64_len()], base64::URL_SAFE)?,
+                code.strip_lead(decode_config(
+                    &s[code.code_len()..code.prefix_b64_len()],
+                    base64::URL_SAFE,
+                )?)?,
             ))
         } else {
             Err(Error::SemanticError(format!(
@@ -44,7 +48,7 @@ impl FromStr for SelfSigningPrefix {
 
 impl Prefix for SelfSigningPrefix {
     fn derivative(&self) -> Vec<u8> {
-        self.signature.to_owned()
+        self.derivation.add_lead(&self.signature)
     }
     fn derivation_code(&self) -> String {
         self.derivation.to_str()
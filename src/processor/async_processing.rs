@@ -1,4 +1,8 @@
+use super::EventProcessor;
 use crate::{
+    database::{sled::SledEventDatabase, EventDatabase},
+    error::Error,
+    event_message::signed_event_message::Message,
     event_parsing::{
         attachment::b64_count, message::message, message::version, payload_size::PayloadType,
         SignedEventData,
@@ -6,16 +10,24 @@ use crate::{
     keri::Keri,
     prefix::IdentifierPrefix,
     signer::KeyManager,
+    state::IdentifierState,
 };
 use arrayref::array_ref;
 use async_std::{
-    channel::Sender,
+    channel::{unbounded, Sender},
     io::{BufRead, BufReader, Read, Write},
-    task::{block_on, Context, Poll},
+    task::{block_on, spawn_blocking, Context, Poll},
 };
 use bitpat::bitpat;
+use futures_core::Stream;
+use futures_sink::Sink;
 use pin_project::pin_project;
-use std::{convert::TryFrom, future::Future, pin::Pin, sync::Arc};
+use std::{
+    convert::TryFrom,
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
 
 pub type Result<T> = std::result::Result<T, String>;
 
@@ -182,6 +194,142 @@ where
     processor.await
 }
 
+/// Processes messages of framed stream, eg. `Framed<TcpStream, KeriCodec>`,
+/// and sends responses back through it until the stream ends.
+pub async fn process_framed<F, K>(keri: Arc<Keri<K>>, mut framed: F) -> Result<()>
+where
+    F: Stream<Item = std::result::Result<SignedEventData, Error>>
+        + Sink<Vec<u8>, Error = Error>
+        + Unpin,
+    K: KeyManager + 'static,
+{
+    while let Some(message) = poll_fn(|cx| Pin::new(&mut framed).poll_next(cx)).await {
+        let message = message.map_err(|e| e.to_string())?;
+        let response = keri
+            .respond_events(vec![message])
+            .map_err(|e| e.to_string())?;
+        if !response.is_empty() {
+            poll_fn(|cx| Pin::new(&mut framed).poll_ready(cx))
+                .await
+                .map_err(|e| e.to_string())?;
+            Pin::new(&mut framed)
+                .start_send(response)
+                .map_err(|e| e.to_string())?;
+            poll_fn(|cx| Pin::new(&mut framed).poll_flush(cx))
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of processing message by `AsyncEventProcessor`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// Message was accepted, with state of identifier it's about.
+    Accepted {
+        message: Message,
+        state: Option<IdentifierState>,
+    },
+    /// Message was rejected, with reason of rejection.
+    Rejected { message: Message, reason: String },
+}
+
+/// Async Event Processor
+///
+/// Wraps `EventProcessor`, so it can be used from async runtime without
+/// blocking it. Every call to database runs on blocking thread pool.
+/// Outcome of each processed message is sent to all `notifications`
+/// streams.
+pub struct AsyncEventProcessor<D: EventDatabase = SledEventDatabase> {
+    processor: Arc<EventProcessor<D>>,
+    subscribers: Mutex<Vec<Sender<Notification>>>,
+}
+
+impl<D> AsyncEventProcessor<D>
+where
+    D: EventDatabase + Send + Sync + 'static,
+{
+    pub fn new(processor: EventProcessor<D>) -> Self {
+        Self {
+            processor: Arc::new(processor),
+            subscribers: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn processor(&self) -> &EventProcessor<D> {
+        &self.processor
+    }
+
+    /// Returns stream of outcomes of messages processed from now on.
+    /// Stream ends when processor is dropped.
+    pub fn notifications(&self) -> impl Stream<Item = Notification> + Unpin {
+        let (sender, receiver) = unbounded();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sender);
+        receiver
+    }
+
+    pub async fn process(&self, message: Message) -> Result<Option<IdentifierState>, Error> {
+        let processor = Arc::clone(&self.processor);
+        let to_process = message.clone();
+        let result = spawn_blocking(move || processor.process(to_process)).await;
+        let notification = match &result {
+            Ok(state) => Notification::Accepted {
+                message,
+                state: state.clone(),
+            },
+            Err(e) => Notification::Rejected {
+                message,
+                reason: e.to_string(),
+            },
+        };
+        self.notify(notification);
+        result
+    }
+
+    pub async fn compute_state(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Result<Option<IdentifierState>, Error> {
+        let processor = Arc::clone(&self.processor);
+        let id = id.clone();
+        spawn_blocking(move || processor.compute_state(&id)).await
+    }
+
+    pub async fn get_kerl(&self, id: &IdentifierPrefix) -> Result<Option<Vec<u8>>, Error> {
+        let processor = Arc::clone(&self.processor);
+        let id = id.clone();
+        spawn_blocking(move || processor.get_kerl(&id)).await
+    }
+
+    pub async fn get_kel_range(
+        &self,
+        id: &IdentifierPrefix,
+        from_sn: u64,
+        to_sn: u64,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let processor = Arc::clone(&self.processor);
+        let id = id.clone();
+        spawn_blocking(move || processor.get_kel_range(&id, from_sn, to_sn)).await
+    }
+
+    pub async fn process_escrows(&self) -> Result<(), Error> {
+        let processor = Arc::clone(&self.processor);
+        spawn_blocking(move || processor.process_escrows()).await
+    }
+
+    fn notify(&self, notification: Notification) {
+        // Subscribers which dropped their stream are removed.
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|subscriber| subscriber.try_send(notification.clone()).is_ok());
+    }
+}
+
 fn binary_attachments_len() -> usize {
     todo!()
 }
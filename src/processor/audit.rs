@@ -0,0 +1,65 @@
+//! Audit Trail
+//!
+//! Append-only log of processing decisions. Every message processed
+//! with audit trail enabled leaves a record with digest of the message,
+//! tag of its source, outcome of processing and time of the decision,
+//! so operators, eg. of witnesses, can show what they accepted and
+//! when.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    derivation::self_addressing::SelfAddressing, event_message::signed_event_message::Message,
+    event_parsing::SignedEventData, prefix::SelfAddressingPrefix,
+};
+
+/// What processor decided about the message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AuditOutcome {
+    Accepted,
+    /// Kept in escrow until what it depends on arrives.
+    Escrowed,
+    /// Other version of already accepted event.
+    Duplicitous,
+    /// Rejected, with the error message.
+    Rejected(String),
+}
+
+/// Audit Record
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    #[serde(rename = "dt")]
+    pub timestamp: DateTime<Local>,
+
+    /// Tag of the message source, eg. identifier or address of the
+    /// peer, if known.
+    #[serde(rename = "src")]
+    pub source: Option<String>,
+
+    /// Blake3 digest of the message in CESR.
+    #[serde(rename = "d")]
+    pub digest: SelfAddressingPrefix,
+
+    #[serde(rename = "o")]
+    pub outcome: AuditOutcome,
+}
+
+impl AuditRecord {
+    pub fn new(
+        message: &Message,
+        source: Option<&str>,
+        outcome: AuditOutcome,
+        timestamp: DateTime<Local>,
+    ) -> Self {
+        let cesr = SignedEventData::from(message.clone())
+            .to_cesr()
+            .unwrap_or_default();
+        Self {
+            timestamp,
+            source: source.map(str::to_string),
+            digest: SelfAddressing::Blake3_256.derive(&cesr),
+            outcome,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Registrar Backers
+//!
+//! Identifier incepted with registrar backers trait (`RB`) has its
+//! events anchored in a ledger by its registrar backer, designated with
+//! backer seal in data of establishment event. The ledger is out of
+//! scope of keriox, so processor checks anchors with `BackerVerifier`
+//! provided by external crate with `EventProcessor::with_backer_verifier`.
+//! Without verifier, events are accepted without checking the ledger.
+
+use super::EventProcessor;
+use crate::{
+    database::EventDatabase,
+    error::Error,
+    event::{sections::seal::BackerSeal, EventMessage},
+    event_message::key_event_message::KeyEvent,
+    state::IdentifierState,
+};
+
+pub trait BackerVerifier: Send + Sync {
+    /// Checks whether `event` is anchored in ledger by registrar
+    /// `backer`.
+    fn verify(&self, backer: &BackerSeal, event: &EventMessage<KeyEvent>) -> Result<bool, Error>;
+}
+
+impl<D: EventDatabase> EventProcessor<D> {
+    /// Checks ledger anchor of event which results in `state`, by
+    /// backer designated in that state, ie. the new one if event
+    /// rotates backer.
+    pub(crate) fn verify_backer_anchor(
+        &self,
+        state: &IdentifierState,
+        event: &EventMessage<KeyEvent>,
+    ) -> Result<(), Error> {
+        match (&self.backer_verifier, &state.backer) {
+            (Some(verifier), Some(backer)) if !verifier.verify(backer, event)? => {
+                Err(Error::MissingBackerAnchor {
+                    prefix: state.prefix.clone(),
+                    sn: state.sn,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Processing Clock
+//!
+//! Source of time processor stamps accepted events, first seen records
+//! and audit records with. System time by default. Tests, deterministic replays or environments
+//! without trusted system time can inject their own clock with
+//! `EventProcessor::with_clock`. Escrowed messages are still stamped
+//! and expired by system time.
+
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Local};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// Clock of the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// Clock which stands still until it's moved on manually.
+#[derive(Debug)]
+pub struct ManualClock(Mutex<DateTime<Local>>);
+
+impl ManualClock {
+    pub fn new(start: DateTime<Local>) -> Self {
+        Self(Mutex::new(start))
+    }
+
+    pub fn set(&self, time: DateTime<Local>) {
+        if let Ok(mut now) = self.0.lock() {
+            *now = time;
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        if let Ok(mut now) = self.0.lock() {
+            *now += duration;
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        match self.0.lock() {
+            Ok(now) => *now,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+}
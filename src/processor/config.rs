@@ -0,0 +1,255 @@
+use crate::{
+    derivation::{
+        basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning, DerivationCode,
+    },
+    error::Error,
+    event::{
+        event_data::EventData,
+        sections::{key_config::NextKeysCommitment, seal::Seal},
+    },
+    event_message::{
+        canonical::check_canonical,
+        signed_event_message::{
+            Message, SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+        },
+    },
+    prefix::{AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
+};
+use chrono::{DateTime, Local};
+use std::time::Duration;
+
+/// Processor Config
+///
+/// Policy of derivation codes accepted by event processor. `None`
+/// means that all codes of given kind are accepted. Messages which use
+/// not allowed code are rejected with `DerivationNotAllowed` error
+/// before any other processing.
+///
+/// Escrow timeout is how long messages are kept in escrow waiting for
+/// what they depend on, before `process_escrows` purges them.
+///
+/// Seal digest is derivation of event digests in seals made by the
+/// processor. `None` means each identifier's own event digests are
+/// used.
+///
+/// Strict processor rejects received events which aren't in canonical
+/// serialization with `NonCanonical` error, see `canonical` module.
+///
+/// With audit trail enabled, outcome of every processed message is
+/// recorded in database, see `audit` module.
+///
+/// State cache is capacity of the processor's cache of identifier
+/// states, see `state_store` module. Zero, the default, disables it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessorConfig {
+    pub allowed_digests: Option<Vec<SelfAddressing>>,
+    pub allowed_keys: Option<Vec<Basic>>,
+    pub allowed_signatures: Option<Vec<SelfSigning>>,
+    pub escrow_timeout: Duration,
+    pub seal_digest: Option<SelfAddressing>,
+    pub strict: bool,
+    pub audit_trail: bool,
+    pub state_cache: usize,
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        Self {
+            allowed_digests: None,
+            allowed_keys: None,
+            allowed_signatures: None,
+            // Same as keripy timeout of unverified receipts escrow.
+            escrow_timeout: Duration::from_secs(3600),
+            seal_digest: None,
+            strict: false,
+            audit_trail: false,
+            state_cache: 0,
+        }
+    }
+}
+
+impl ProcessorConfig {
+    pub fn with_allowed_digests(self, digests: &[SelfAddressing]) -> Self {
+        Self {
+            allowed_digests: Some(digests.to_vec()),
+            ..self
+        }
+    }
+
+    pub fn with_allowed_keys(self, keys: &[Basic]) -> Self {
+        Self {
+            allowed_keys: Some(keys.to_vec()),
+            ..self
+        }
+    }
+
+    pub fn with_allowed_signatures(self, signatures: &[SelfSigning]) -> Self {
+        Self {
+            allowed_signatures: Some(signatures.to_vec()),
+            ..self
+        }
+    }
+
+    pub fn with_escrow_timeout(self, escrow_timeout: Duration) -> Self {
+        Self {
+            escrow_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_seal_digest(self, derivation: SelfAddressing) -> Self {
+        Self {
+            seal_digest: Some(derivation),
+            ..self
+        }
+    }
+
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    pub fn with_audit_trail(self, audit_trail: bool) -> Self {
+        Self {
+            audit_trail,
+            ..self
+        }
+    }
+
+    pub fn with_state_cache(self, capacity: usize) -> Self {
+        Self {
+            state_cache: capacity,
+            ..self
+        }
+    }
+
+    /// Checks if entry escrowed at `timestamp` has been kept in escrow
+    /// for longer than escrow timeout.
+    pub fn is_escrow_expired(&self, timestamp: &DateTime<Local>) -> bool {
+        match chrono::Duration::from_std(self.escrow_timeout) {
+            Ok(timeout) => Local::now() - *timestamp > timeout,
+            // Timeout out of range never expires.
+            Err(_) => false,
+        }
+    }
+
+    pub fn check_digest(&self, digest: &SelfAddressingPrefix) -> Result<(), Error> {
+        check(&self.allowed_digests, &digest.derivation)
+    }
+
+    pub fn check_key(&self, key: &BasicPrefix) -> Result<(), Error> {
+        check(&self.allowed_keys, &key.derivation)
+    }
+
+    pub fn check_signatures(&self, signatures: &[AttachedSignaturePrefix]) -> Result<(), Error> {
+        signatures
+            .iter()
+            .try_for_each(|sig| check(&self.allowed_signatures, &sig.signature.derivation))
+    }
+
+    pub fn check_identifier(&self, id: &IdentifierPrefix) -> Result<(), Error> {
+        match id {
+            IdentifierPrefix::Basic(bp) => self.check_key(bp),
+            IdentifierPrefix::SelfAddressing(sap) => self.check_digest(sap),
+            IdentifierPrefix::SelfSigning(ssp) => check(&self.allowed_signatures, &ssp.derivation),
+        }
+    }
+
+    pub fn check_event(&self, event: &SignedEventMessage) -> Result<(), Error> {
+        let em = &event.event_message;
+        // Events made locally have no raw bytes and are canonical.
+        if let (true, Some(raw)) = (self.strict, &event.raw) {
+            check_canonical(em, raw)?;
+        }
+        self.check_identifier(&em.event.get_prefix())?;
+        self.check_digest(&em.get_digest())?;
+        self.check_signatures(&event.signatures)?;
+        if let Some(seal) = &event.delegator_seal {
+            self.check_digest(&seal.digest)?;
+        }
+        let (key_config, witnesses, previous, seals) = match em.event.get_event_data() {
+            EventData::Icp(icp) => (
+                Some(icp.key_config),
+                icp.witness_config.initial_witnesses,
+                None,
+                icp.data,
+            ),
+            EventData::Dip(dip) => (
+                Some(dip.inception_data.key_config),
+                dip.inception_data.witness_config.initial_witnesses,
+                None,
+                dip.inception_data.data,
+            ),
+            EventData::Rot(rot) | EventData::Drt(rot) => (
+                Some(rot.key_config),
+                rot.witness_config.graft,
+                Some(rot.previous_event_hash),
+                rot.data,
+            ),
+            EventData::Ixn(ixn) => (None, vec![], Some(ixn.previous_event_hash), ixn.data),
+        };
+        if let Some(key_config) = key_config {
+            key_config
+                .public_keys
+                .iter()
+                .try_for_each(|key| self.check_key(key))?;
+            match &key_config.threshold_key_digest {
+                Some(NextKeysCommitment::Aggregated(next)) => self.check_digest(next)?,
+                Some(NextKeysCommitment::Keys(next)) => {
+                    next.iter().try_for_each(|dig| self.check_digest(dig))?
+                }
+                None => (),
+            }
+        }
+        witnesses
+            .iter()
+            .try_for_each(|witness| self.check_key(witness))?;
+        if let Some(previous) = &previous {
+            self.check_digest(previous)?;
+        }
+        seals.iter().try_for_each(|seal| match seal {
+            Seal::Event(es) => self.check_digest(&es.event_digest),
+            Seal::Digest(ds) => self.check_digest(&ds.dig),
+            Seal::Root(rs) => self.check_digest(&rs.tree_root),
+            _ => Ok(()),
+        })
+    }
+
+    pub fn check_nontransferable_receipt(
+        &self,
+        rct: &SignedNontransferableReceipt,
+    ) -> Result<(), Error> {
+        self.check_digest(&rct.body.event.receipted_event_digest)?;
+        rct.couplets.iter().try_for_each(|(witness, sig)| {
+            self.check_key(witness)?;
+            check(&self.allowed_signatures, &sig.derivation)
+        })
+    }
+
+    pub fn check_transferable_receipt(&self, rct: &SignedTransferableReceipt) -> Result<(), Error> {
+        self.check_digest(&rct.body.event.receipted_event_digest)?;
+        self.check_identifier(&rct.validator_seal.prefix)?;
+        self.check_digest(&rct.validator_seal.event_digest)?;
+        self.check_signatures(&rct.signatures)
+    }
+
+    pub fn check_message(&self, message: &Message) -> Result<(), Error> {
+        match message {
+            Message::Event(event) => self.check_event(event),
+            Message::NontransferableRct(rct) => self.check_nontransferable_receipt(rct),
+            Message::TransferableRct(rct) => self.check_transferable_receipt(rct),
+            Message::WitnessRct(rct) => {
+                self.check_digest(&rct.body.event.receipted_event_digest)?;
+                self.check_signatures(&rct.signatures)
+            }
+            #[cfg(feature = "query")]
+            _ => Ok(()),
+        }
+    }
+}
+
+fn check<C: DerivationCode + PartialEq>(allowed: &Option<Vec<C>>, code: &C) -> Result<(), Error> {
+    match allowed {
+        Some(allowed) if !allowed.contains(code) => Err(Error::DerivationNotAllowed(code.to_str())),
+        _ => Ok(()),
+    }
+}
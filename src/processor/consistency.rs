@@ -0,0 +1,70 @@
+use crate::{
+    error::Error,
+    event_message::signed_event_message::SignedEventMessage,
+    prefix::{IdentifierPrefix, SelfAddressingPrefix},
+    state::IdentifierState,
+};
+
+/// Consistency Proof
+///
+/// Demonstrates that KEL of given Prefix at `to_sn` is an append-only
+/// extension of the KEL at `from_sn`. Contains all events which
+/// follows the event of `from_sn`, so it can be checked by a client
+/// that keeps only the key state from the earlier snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsistencyProof {
+    pub prefix: IdentifierPrefix,
+    pub from_sn: u64,
+    pub from_digest: SelfAddressingPrefix,
+    pub events: Vec<SignedEventMessage>,
+}
+
+impl ConsistencyProof {
+    pub fn to_sn(&self) -> u64 {
+        self.events
+            .last()
+            .map(|event| event.event_message.event.get_sn())
+            .unwrap_or(self.from_sn)
+    }
+
+    pub fn to_digest(&self) -> SelfAddressingPrefix {
+        self.events
+            .last()
+            .map(|event| event.event_message.get_digest())
+            .unwrap_or_else(|| self.from_digest.clone())
+    }
+
+    /// Verify
+    ///
+    /// Checks proof against state of the earlier snapshot. Each event
+    /// has to be bound to the previous one by its digest, establishment
+    /// events have to satisfy the next keys commitment and all events
+    /// have to be signed by the current keys. Returns the state of the
+    /// later snapshot. Note that delegating seals of delegated events
+    /// are not checked, because it requires delegator's KEL.
+    pub fn verify(&self, state: IdentifierState) -> Result<IdentifierState, Error> {
+        if state.prefix != self.prefix
+            || state.sn != self.from_sn
+            || state.last_event_digest != self.from_digest
+        {
+            return Err(Error::SemanticError(
+                "Proof doesn't start at given state".into(),
+            ));
+        }
+        self.events.iter().try_fold(state, |state, event| {
+            let prefix = event.event_message.event.get_prefix();
+            if prefix != self.prefix {
+                return Err(Error::PrefixMismatch {
+                    expected: self.prefix.clone(),
+                    got: prefix,
+                });
+            }
+            let new_state = state.apply(&event.event_message)?;
+            new_state
+                .current
+                .verify(&event.raw_event()?, &event.signatures)?
+                .then_some(new_state)
+                .ok_or(Error::SignatureVerificationError)
+        })
+    }
+}
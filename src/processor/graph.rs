@@ -0,0 +1,177 @@
+use serde::Serialize;
+
+use crate::{
+    error::Error,
+    event::{event_data::EventData, sections::seal::Seal},
+    event_message::{
+        signed_event_message::{
+            SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
+        },
+        EventTypeTag,
+    },
+    prefix::{IdentifierPrefix, Prefix},
+};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum GraphNode {
+    Event {
+        id: String,
+        prefix: IdentifierPrefix,
+        sn: u64,
+        event_type: EventTypeTag,
+    },
+    Receiptor {
+        id: String,
+    },
+}
+
+impl GraphNode {
+    pub fn id(&self) -> &str {
+        match self {
+            GraphNode::Event { id, .. } | GraphNode::Receiptor { id } => id,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    /// Event points to its previous event.
+    Previous,
+    /// Event anchors seal of other event.
+    Seal,
+    /// Delegated event points to the delegating event.
+    Delegation,
+    /// Receiptor points to the receipted event.
+    Receipt,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+/// Event Graph
+///
+/// Relationships between events, seals, delegations and receipts of
+/// KELs. Nodes are identified by event digest or by receiptor prefix.
+/// Edges may point to the events that are not part of the graph, for
+/// example to delegating events when delegator KEL wasn't exported.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct EventGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl EventGraph {
+    pub fn add_event(&mut self, event: &SignedEventMessage) {
+        let id = event.event_message.get_digest().to_str();
+        let event_data = event.event_message.event.get_event_data();
+        self.nodes.push(GraphNode::Event {
+            id: id.clone(),
+            prefix: event.event_message.event.get_prefix(),
+            sn: event.event_message.event.get_sn(),
+            event_type: (&event_data).into(),
+        });
+
+        let (previous, seals) = match event_data {
+            EventData::Icp(_) | EventData::Dip(_) => (None, vec![]),
+            EventData::Rot(rot) | EventData::Drt(rot) => (Some(rot.previous_event_hash), rot.data),
+            EventData::Ixn(ixn) => (Some(ixn.previous_event_hash), ixn.data),
+        };
+        if let Some(previous) = previous {
+            self.add_edge(&id, &previous.to_str(), EdgeKind::Previous);
+        }
+        seals
+            .into_iter()
+            .filter_map(|seal| match seal {
+                Seal::Event(seal) => Some(seal.event_digest),
+                Seal::Digest(seal) => Some(seal.dig),
+                Seal::Root(seal) => Some(seal.tree_root),
+                _ => None,
+            })
+            .for_each(|anchored| self.add_edge(&id, &anchored.to_str(), EdgeKind::Seal));
+        if let Some(source_seal) = &event.delegator_seal {
+            self.add_edge(&id, &source_seal.digest.to_str(), EdgeKind::Delegation);
+        }
+    }
+
+    pub fn add_nontransferable_receipt(&mut self, rct: &SignedNontransferableReceipt) {
+        let receipted = rct.body.event.receipted_event_digest.to_str();
+        for (witness, _signature) in &rct.couplets {
+            self.add_receiptor(witness.to_str(), &receipted);
+        }
+    }
+
+    pub fn add_transferable_receipt(&mut self, rct: &SignedTransferableReceipt) {
+        let receipted = rct.body.event.receipted_event_digest.to_str();
+        self.add_receiptor(rct.validator_seal.prefix.to_str(), &receipted);
+    }
+
+    fn add_receiptor(&mut self, receiptor: String, receipted: &str) {
+        if !self.nodes.iter().any(|node| node.id() == receiptor) {
+            self.nodes.push(GraphNode::Receiptor {
+                id: receiptor.clone(),
+            });
+        }
+        self.add_edge(&receiptor, receipted, EdgeKind::Receipt);
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, kind: EdgeKind) {
+        let edge = GraphEdge {
+            from: from.into(),
+            to: to.into(),
+            kind,
+        };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Returns graph in GraphViz DOT format.
+    pub fn to_dot(&self) -> Result<String, Error> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                GraphNode::Event {
+                    id,
+                    prefix,
+                    sn,
+                    event_type,
+                } => Ok(format!(
+                    "  \"{}\" [label=\"{} {}\\n{}\"];\n",
+                    id,
+                    serde_json::to_value(event_type)?
+                        .as_str()
+                        .unwrap_or_default(),
+                    sn,
+                    prefix.to_str()
+                )),
+                GraphNode::Receiptor { id } => Ok(format!("  \"{}\" [shape=box];\n", id)),
+            })
+            .collect::<Result<String, Error>>()?;
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                Ok(format!(
+                    "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    edge.from,
+                    edge.to,
+                    serde_json::to_value(edge.kind)?
+                        .as_str()
+                        .unwrap_or_default()
+                ))
+            })
+            .collect::<Result<String, Error>>()?;
+        Ok(["digraph KEL {\n", &nodes, &edges, "}\n"].concat())
+    }
+}
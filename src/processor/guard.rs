@@ -0,0 +1,556 @@
+//! Processing Guard
+//!
+//! Anti-spam layer in front of event processor of network-facing
+//! deployments, eg. witnesses. Messages are counted per source
+//! identifier in fixed time windows, and messages over the limit are
+//! dropped without being processed. Number of messages escrowed about
+//! one identifier is capped, so nobody can grow escrows without
+//! bounds. Identifiers can be banned by operator, which drops messages
+//! from them and about them. Sources repeatedly exceeding the rate
+//! limit are banned automatically, but only as sources: source is
+//! whatever identifier the peer claims, so messages about it sent by
+//! others still get through. Bans are kept in database, so they survive
+//! restarts.
+//!
+//! Messages which fail processing are kept in database with the reason
+//! of rejection, up to a limit per identifier. Messages dropped by the
+//! limits themselves aren't, so flooding guard can't grow the database.
+//! Counters of sources which went quiet are forgotten, together with
+//! their violations.
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{
+    rejected::{RejectedMessage, RejectionReason},
+    EventProcessor,
+};
+use crate::{
+    error::Error,
+    event_message::signed_event_message::Message,
+    event_parsing::{message::signed_message, SignedEventData},
+    prefix::{IdentifierPrefix, Prefix},
+    state::IdentifierState,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuardConfig {
+    /// Messages accepted from one source within `window`.
+    pub max_messages: usize,
+    pub window: Duration,
+    /// Receipts and replies kept in escrow about one identifier.
+    pub max_escrowed: usize,
+    /// Number of windows in which source exceeded the rate limit,
+    /// after which it's banned as source. `None` means sources are never
+    /// banned automatically.
+    pub ban_after: Option<usize>,
+    /// Rejected messages kept about one identifier. Older ones are
+    /// dropped first.
+    pub max_rejected: usize,
+    /// Time after which counters of silent source are dropped.
+    pub idle_timeout: Duration,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            max_messages: 100,
+            window: Duration::from_secs(1),
+            max_escrowed: 100,
+            ban_after: None,
+            max_rejected: 100,
+            idle_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl GuardConfig {
+    pub fn with_rate_limit(self, max_messages: usize, window: Duration) -> Self {
+        Self {
+            max_messages,
+            window,
+            ..self
+        }
+    }
+
+    pub fn with_max_escrowed(self, max_escrowed: usize) -> Self {
+        Self {
+            max_escrowed,
+            ..self
+        }
+    }
+
+    pub fn with_ban_after(self, violations: usize) -> Self {
+        Self {
+            ban_after: Some(violations),
+            ..self
+        }
+    }
+
+    pub fn with_max_rejected(self, max_rejected: usize) -> Self {
+        Self {
+            max_rejected,
+            ..self
+        }
+    }
+
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            ..self
+        }
+    }
+}
+
+/// Messages counted in current window of a source.
+struct SourceWindow {
+    start: Instant,
+    count: usize,
+    violations: usize,
+}
+
+pub struct ProcessingGuard {
+    processor: EventProcessor,
+    config: GuardConfig,
+    sources: Mutex<Sources>,
+}
+
+/// Windows of sources, keyed by qb64 of source identifier.
+#[derive(Default)]
+struct Sources {
+    windows: HashMap<String, SourceWindow>,
+    last_sweep: Option<Instant>,
+}
+
+impl Sources {
+    /// Drops windows of sources idle for `idle_timeout`. Runs at most
+    /// once per `idle_timeout`, so counting stays cheap.
+    fn sweep(&mut self, now: Instant, idle_timeout: Duration) {
+        match self.last_sweep {
+            Some(last) if now.saturating_duration_since(last) < idle_timeout => (),
+            _ => {
+                self.windows
+                    .retain(|_, w| now.saturating_duration_since(w.start) < idle_timeout);
+                self.last_sweep = Some(now);
+            }
+        }
+    }
+}
+
+impl ProcessingGuard {
+    pub fn new(processor: EventProcessor, config: GuardConfig) -> Self {
+        Self {
+            processor,
+            config,
+            sources: Mutex::new(Sources::default()),
+        }
+    }
+
+    pub fn processor(&self) -> &EventProcessor {
+        &self.processor
+    }
+
+    /// Process
+    ///
+    /// Processes `message` received from `source`, unless source or
+    /// identifier of the message is banned, source exceeded the rate
+    /// limit or the message could add to full escrow. Sources banned
+    /// for exceeding the rate limit don't block messages about them.
+    pub fn process(
+        &self,
+        source: &IdentifierPrefix,
+        message: Message,
+    ) -> Result<Option<IdentifierState>, Error> {
+        self.process_at(source, message, Instant::now())
+    }
+
+    /// Like `process`, with explicit current time.
+    pub fn process_at(
+        &self,
+        source: &IdentifierPrefix,
+        message: Message,
+        now: Instant,
+    ) -> Result<Option<IdentifierState>, Error> {
+        if self.is_banned(source) || self.is_source_banned(source) {
+            return Err(Error::Banned(source.clone()));
+        }
+        let subject = subject(&message);
+        if let Some(id) = subject.as_ref().filter(|id| self.is_banned(id)) {
+            return Err(Error::Banned(id.clone()));
+        }
+        self.count(source, now)?;
+        if let (Some(id), true) = (&subject, may_be_escrowed(&message)) {
+            if self.escrowed(id) >= self.config.max_escrowed {
+                return Err(Error::LimitExceeded {
+                    what: "escrowed messages",
+                    limit: self.config.max_escrowed,
+                });
+            }
+        }
+        let result = self
+            .processor
+            .process_from(&source.to_str(), message.clone());
+        if let Err(e) = &result {
+            let id = subject.as_ref().unwrap_or(source);
+            let cesr = SignedEventData::from(message).to_cesr();
+            self.reject(source, id, e, cesr.unwrap_or_default());
+        }
+        result
+    }
+
+    /// Process Raw
+    ///
+    /// Like `process`, for single message in CESR. Message which can't
+    /// be parsed is rejected as malformed.
+    pub fn process_raw(
+        &self,
+        source: &IdentifierPrefix,
+        raw: &[u8],
+    ) -> Result<Option<IdentifierState>, Error> {
+        let message = match signed_message(raw) {
+            Ok((_, parsed)) => Message::try_from(parsed),
+            Err(e) => Err(Error::DeserializeError(e.to_string())),
+        };
+        match message {
+            Ok(message) => self.process(source, message),
+            Err(e) => {
+                if self.is_banned(source) || self.is_source_banned(source) {
+                    return Err(Error::Banned(source.clone()));
+                }
+                self.count(source, Instant::now())?;
+                self.reject(source, source, &e, raw.to_vec());
+                Err(e)
+            }
+        }
+    }
+
+    /// Messages about identifier `id` rejected so far, in order of
+    /// rejection. Malformed messages are kept under their source.
+    pub fn rejected(&self, id: &IdentifierPrefix) -> Vec<RejectedMessage> {
+        self.processor
+            .db
+            .get_rejected(id)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Bans identifier, so messages from it and about it are dropped.
+    pub fn ban(&self, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.processor.db.add_banned(id)
+    }
+
+    /// Lifts both operator and automatic source ban of identifier.
+    pub fn unban(&self, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.sources
+            .lock()
+            .map_err(|_| Error::MutexPoisoned)?
+            .windows
+            .remove(&id.to_str());
+        self.processor.db.remove_banned_source(id)?;
+        self.processor.db.remove_banned(id)
+    }
+
+    pub fn is_banned(&self, id: &IdentifierPrefix) -> bool {
+        self.processor.db.is_banned(id)
+    }
+
+    /// Checks whether source was banned for exceeding the rate limit.
+    pub fn is_source_banned(&self, id: &IdentifierPrefix) -> bool {
+        self.processor.db.is_banned_source(id)
+    }
+
+    /// Counts message of `source` in its current window.
+    fn count(&self, source: &IdentifierPrefix, now: Instant) -> Result<(), Error> {
+        let mut sources = self.sources.lock().map_err(|_| Error::MutexPoisoned)?;
+        // Windows still in progress are never dropped.
+        sources.sweep(now, self.config.idle_timeout.max(self.config.window));
+        let window = sources
+            .windows
+            .entry(source.to_str())
+            .or_insert(SourceWindow {
+                start: now,
+                count: 0,
+                violations: 0,
+            });
+        if now.duration_since(window.start) >= self.config.window {
+            window.start = now;
+            window.count = 0;
+        }
+        window.count += 1;
+        if window.count <= self.config.max_messages {
+            return Ok(());
+        }
+        // Violation is counted once per window.
+        if window.count == self.config.max_messages + 1 {
+            window.violations += 1;
+            if self
+                .config
+                .ban_after
+                .is_some_and(|ban_after| window.violations >= ban_after)
+            {
+                drop(sources);
+                self.processor.db.add_banned_source(source)?;
+                return Err(Error::Banned(source.clone()));
+            }
+        }
+        Err(Error::RateLimited(source.clone()))
+    }
+
+    fn reject(
+        &self,
+        source: &IdentifierPrefix,
+        id: &IdentifierPrefix,
+        error: &Error,
+        message: Vec<u8>,
+    ) {
+        if let Some(reason) = RejectionReason::from_error(error) {
+            let record = RejectedMessage::new(source.clone(), reason, message);
+            if let Err(e) = self
+                .processor
+                .db
+                .add_rejected(id, record, self.config.max_rejected)
+            {
+                tracing::warn!(error = %e, "can't store rejected message");
+            }
+        }
+    }
+
+    fn escrowed(&self, id: &IdentifierPrefix) -> usize {
+        let db = &self.processor.db;
+        [
+            db.get_escrow_nt_receipts(id).map_or(0, Iterator::count),
+            db.get_escrow_t_receipts(id).map_or(0, Iterator::count),
+            #[cfg(feature = "query")]
+            db.get_escrowed_replys(id).map_or(0, Iterator::count),
+        ]
+        .iter()
+        .sum()
+    }
+}
+
+/// Identifier which the message is about.
+fn subject(message: &Message) -> Option<IdentifierPrefix> {
+    match message {
+        Message::Event(event) => Some(event.event_message.event.get_prefix()),
+        Message::NontransferableRct(rct) => Some(rct.body.event.prefix.clone()),
+        Message::TransferableRct(rct) => Some(rct.body.event.prefix.clone()),
+        Message::WitnessRct(rct) => Some(rct.body.event.prefix.clone()),
+        #[cfg(feature = "query")]
+        Message::KeyStateNotice(rpy) => Some(rpy.reply.event.get_prefix()),
+        #[cfg(feature = "query")]
+        Message::Query(_) | Message::Exchange(_) => None,
+    }
+}
+
+fn may_be_escrowed(message: &Message) -> bool {
+    !matches!(message, Message::Event(_))
+}
+
+#[test]
+fn test_processing_guard() -> Result<(), Error> {
+    use crate::database::sled::SledEventDatabase;
+    use crate::event_message::{
+        event_msg_builder::ReceiptBuilder, signed_event_message::SignedNontransferableReceipt,
+        test_fixture::TestIdentityFixture,
+    };
+    use std::sync::Arc;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let config = GuardConfig::default()
+        .with_rate_limit(2, Duration::from_secs(10))
+        .with_ban_after(2);
+    let guard = ProcessingGuard::new(EventProcessor::new(Arc::clone(&db)), config.clone());
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    for _ in 0..5 {
+        alice.rotate()?;
+    }
+    let events = alice.events().to_vec();
+    let source = alice.prefix().clone();
+    let start = Instant::now();
+
+    guard.process_at(&source, Message::Event(events[0].clone()), start)?;
+    guard.process_at(&source, Message::Event(events[1].clone()), start)?;
+    assert!(matches!(
+        guard.process_at(&source, Message::Event(events[2].clone()), start),
+        Err(Error::RateLimited(ref id)) if id == &source
+    ));
+    assert_eq!(guard.processor().compute_state(&source)?.unwrap().sn, 1);
+
+    // Limit is reset in next window, second violation bans the source.
+    let next = start + Duration::from_secs(10);
+    guard.process_at(&source, Message::Event(events[2].clone()), next)?;
+    guard.process_at(&source, Message::Event(events[3].clone()), next)?;
+    assert!(matches!(
+        guard.process_at(&source, Message::Event(events[4].clone()), next),
+        Err(Error::Banned(_))
+    ));
+    let later = next + Duration::from_secs(10);
+    assert!(matches!(
+        guard.process_at(&source, Message::Event(events[4].clone()), later),
+        Err(Error::Banned(_))
+    ));
+
+    // Counters of idle sources are dropped.
+    let carol = TestIdentityFixture::new(b"carol")?;
+    guard.process_at(
+        carol.prefix(),
+        Message::Event(carol.events()[0].clone()),
+        later + config.idle_timeout,
+    )?;
+    assert_eq!(
+        guard
+            .sources
+            .lock()
+            .unwrap()
+            .windows
+            .keys()
+            .collect::<Vec<_>>(),
+        vec![&carol.prefix().to_str()]
+    );
+
+    // Ban is persistent.
+    drop(guard);
+    let guard = ProcessingGuard::new(EventProcessor::new(Arc::clone(&db)), config);
+    assert!(guard.is_source_banned(&source));
+    assert!(!guard.is_banned(&source));
+    // Rate limited source doesn't block events about it sent by others,
+    // as anybody can claim to be the source.
+    let mut bob = TestIdentityFixture::new(b"bob")?;
+    assert!(guard
+        .process_at(bob.prefix(), Message::Event(events[4].clone()), later)?
+        .is_some());
+    // Events of identifier banned by operator are dropped, whatever the
+    // source is.
+    guard.ban(&source)?;
+    assert!(matches!(
+        guard.process_at(bob.prefix(), Message::Event(events[5].clone()), later),
+        Err(Error::Banned(_))
+    ));
+    guard.unban(&source)?;
+    assert!(!guard.is_banned(&source));
+    assert!(!guard.is_source_banned(&source));
+    assert!(guard
+        .process_at(&source, Message::Event(events[5].clone()), later)?
+        .is_some());
+
+    // Receipts of unknown events are escrowed, up to the limit.
+    let guard = ProcessingGuard::new(
+        EventProcessor::new(Arc::clone(&db)),
+        GuardConfig::default().with_max_escrowed(1),
+    );
+    bob.rotate()?;
+    let receipts = bob
+        .events()
+        .iter()
+        .map(|event| {
+            let rct = ReceiptBuilder::default()
+                .with_receipted_event(event.event_message.clone())
+                .build()?;
+            Ok(Message::NontransferableRct(
+                SignedNontransferableReceipt::new(&rct, vec![]),
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    guard.process(&source, receipts[0].clone())?;
+    assert!(matches!(
+        guard.process(&source, receipts[1].clone()),
+        Err(Error::LimitExceeded { limit: 1, .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_rejected_messages() -> Result<(), Error> {
+    use crate::database::sled::SledEventDatabase;
+    use crate::event_message::{
+        signed_event_message::SignedEventMessage, test_fixture::TestIdentityFixture,
+    };
+    use std::sync::Arc;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let guard = ProcessingGuard::new(EventProcessor::new(Arc::clone(&db)), GuardConfig::default());
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.rotate()?;
+    alice.rotate()?;
+    let events = alice.events().to_vec();
+    let bob = TestIdentityFixture::new(b"bob")?;
+    let source = bob.prefix().clone();
+
+    guard.process(&source, Message::Event(events[0].clone()))?;
+    assert!(guard.rejected(alice.prefix()).is_empty());
+
+    // Rotation signed with somebody else's key.
+    let forged = SignedEventMessage {
+        signatures: vec![bob.sign(&events[1].event_message.serialize()?)?],
+        ..events[1].clone()
+    };
+    assert!(guard
+        .process(&source, Message::Event(forged.clone()))
+        .is_err());
+    assert!(guard
+        .process(&source, Message::Event(events[2].clone()))
+        .is_err());
+
+    let rejected = guard.rejected(alice.prefix());
+    let reasons: Vec<_> = rejected.iter().map(|r| r.reason.clone()).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            RejectionReason::InvalidSignature,
+            RejectionReason::OutOfOrder
+        ]
+    );
+    assert!(rejected.iter().all(|r| r.source == source));
+    // Evidence is kept as received.
+    assert_eq!(
+        rejected[0].message,
+        SignedEventData::from(&forged).to_cesr()?
+    );
+
+    // Stream which can't be parsed is kept under its source.
+    assert!(matches!(
+        guard.process_raw(&source, b"not a keri message"),
+        Err(Error::DeserializeError(_))
+    ));
+    let malformed = guard.rejected(&source);
+    assert_eq!(malformed.len(), 1);
+    assert!(matches!(malformed[0].reason, RejectionReason::Malformed(_)));
+    assert_eq!(malformed[0].message, b"not a keri message".to_vec());
+
+    // Accepted messages aren't recorded.
+    let raw = SignedEventData::from(&events[1]).to_cesr()?;
+    assert!(guard.process_raw(&source, &raw)?.is_some());
+    assert_eq!(db.get_all_rejected().unwrap().count(), 3);
+
+    // Only the most recent rejections are kept.
+    let guard = ProcessingGuard::new(
+        EventProcessor::new(Arc::clone(&db)),
+        GuardConfig::default().with_max_rejected(2),
+    );
+    for garbage in [b"first garbage", b"other garbage"] {
+        assert!(guard.process_raw(&source, garbage).is_err());
+    }
+    let messages: Vec<_> = guard
+        .rejected(&source)
+        .into_iter()
+        .map(|r| r.message)
+        .collect();
+    assert_eq!(
+        messages,
+        vec![b"first garbage".to_vec(), b"other garbage".to_vec()]
+    );
+
+    Ok(())
+}
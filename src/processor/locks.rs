@@ -0,0 +1,52 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::prefix::{IdentifierPrefix, Prefix};
+
+/// Prefix Locks
+///
+/// Lock per identifier, so events of the same identifier are processed
+/// one at a time, while events of different identifiers are processed
+/// in parallel. Lock is created when it's needed and dropped when no
+/// thread holds or waits for it, so the map doesn't grow with number of
+/// processed identifiers.
+#[derive(Default)]
+pub(crate) struct PrefixLocks {
+    locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl PrefixLocks {
+    /// Runs `f` while holding lock of identifier `id`.
+    pub fn with_lock<T>(&self, id: &IdentifierPrefix, f: impl FnOnce() -> T) -> T {
+        let key = id.to_str();
+        let lock = Arc::clone(
+            self.map()
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(Mutex::new(()))),
+        );
+        let result = {
+            // Poisoned lock only means that other thread panicked while
+            // processing, database is still consistent.
+            let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+            f()
+        };
+        let mut locks = self.map();
+        // One reference is kept in map and one is ours, so nobody else
+        // is waiting for the lock.
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(&key);
+        }
+        result
+    }
+
+    fn map(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Mutex<()>>>> {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[cfg(test)]
+    pub fn count(&self) -> usize {
+        self.map().len()
+    }
+}
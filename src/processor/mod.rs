@@ -1,17 +1,28 @@
 #[cfg(feature = "query")]
-use crate::query::{key_state_notice::KeyStateNotice, reply::SignedReply, QueryError};
+use crate::{
+    directory::AliasRecord,
+    oobi::{LocationScheme, Role, Scheme},
+    query::{
+        key_state_notice::KeyStateNotice,
+        query_event::SignedQuery,
+        reply::{ReplyBody, SignedReply},
+        QueryError, ReplyPayload,
+    },
+};
 #[cfg(feature = "query")]
-use chrono::{DateTime, FixedOffset};
-use std::sync::Arc;
+use chrono::FixedOffset;
+use chrono::{DateTime, Local};
+use std::{collections::HashSet, sync::Arc};
 
 use crate::{
-    database::sled::SledEventDatabase,
+    database::{sled::SledEventDatabase, EventDatabase, FirstSeen, Subscription},
+    derivation::self_addressing::SelfAddressing,
     error::Error,
     event::{
         event_data::EventData,
         sections::{
-            seal::{EventSeal, Seal},
-            KeyConfig,
+            seal::{EventSeal, LastEstablishmentSeal, Seal, SourceSeal},
+            ConfigTrait, KeyConfig,
         },
         EventMessage,
     },
@@ -20,25 +31,175 @@ use crate::{
         signature::Signature,
         signed_event_message::{
             Message, SignedEventMessage, SignedNontransferableReceipt, SignedTransferableReceipt,
-            TimestampedSignedEventMessage,
+            SignedWitnessReceipt, TimestampedSignedEventMessage,
         },
     },
-    prefix::{IdentifierPrefix, SelfAddressingPrefix},
+    event_parsing::{Attachment, SignedEventData},
+    merkle::InclusionProof,
+    prefix::{
+        AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, Prefix, SelfAddressingPrefix,
+    },
     state::{EventSemantics, IdentifierState},
 };
+use audit::{AuditOutcome, AuditRecord};
+use backer::BackerVerifier;
+use clock::{Clock, SystemClock};
+use config::ProcessorConfig;
+use consistency::ConsistencyProof;
+use graph::EventGraph;
+use locks::PrefixLocks;
+use state_store::StateStore;
 
 #[cfg(feature = "async")]
 pub mod async_processing;
+pub mod audit;
+pub mod backer;
+pub mod clock;
+pub mod config;
+pub mod consistency;
+pub mod graph;
+pub mod guard;
+mod locks;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod receipting;
+pub mod rejected;
+pub mod state_store;
+pub mod telemetry;
 #[cfg(test)]
 mod tests;
 
-pub struct EventProcessor {
-    pub db: Arc<SledEventDatabase>,
+/// KERL Chunks
+///
+/// Iterator of consecutive parts of serialized KEL, returned by
+/// `EventProcessor::get_kerl_chunks`.
+pub struct KerlChunks {
+    events: Box<dyn DoubleEndedIterator<Item = TimestampedSignedEventMessage>>,
+    // Serialized event which didn't fit into the previous chunk.
+    pending: Option<Vec<u8>>,
+    max_bytes: usize,
+}
+
+impl Iterator for KerlChunks {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = match self.pending.take() {
+            Some(event) => event,
+            None => match self.events.next()?.signed_event_message.serialize() {
+                Ok(event) => event,
+                Err(e) => return Some(Err(e)),
+            },
+        };
+        while chunk.len() < self.max_bytes {
+            let event = match self.events.next() {
+                Some(event) => event.signed_event_message.serialize(),
+                None => break,
+            };
+            match event {
+                Ok(event) if chunk.len() + event.len() <= self.max_bytes => chunk.extend(event),
+                Ok(event) => {
+                    self.pending = Some(event);
+                    break;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(Ok(chunk))
+    }
+}
+
+/// Event Processor
+///
+/// Validates and stores messages in database `D`, which is sled by
+/// default.
+///
+/// Processor can be shared between threads, eg. in `Arc`. Events of
+/// the same identifier are processed one at a time, so state they are
+/// validated against can't be changed by other thread before they are
+/// stored. Events of different identifiers are processed in parallel.
+/// The guarantee holds only within one processor, so all threads should
+/// use the same processor instance for given database.
+pub struct EventProcessor<D: EventDatabase = SledEventDatabase> {
+    pub db: Arc<D>,
+    config: ProcessorConfig,
+    clock: Arc<dyn Clock>,
+    backer_verifier: Option<Arc<dyn BackerVerifier>>,
+    states: StateStore,
+    locks: PrefixLocks,
 }
 
-impl EventProcessor {
-    pub fn new(db: Arc<SledEventDatabase>) -> Self {
-        Self { db }
+impl<D: EventDatabase> EventProcessor<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self {
+            db,
+            config: ProcessorConfig::default(),
+            clock: Arc::new(SystemClock),
+            backer_verifier: None,
+            states: StateStore::new(0),
+            locks: PrefixLocks::default(),
+        }
+    }
+
+    /// Sets policy of accepted derivation codes.
+    pub fn with_config(self, config: ProcessorConfig) -> Self {
+        Self {
+            states: StateStore::new(config.state_cache),
+            config,
+            ..self
+        }
+    }
+
+    pub fn config(&self) -> &ProcessorConfig {
+        &self.config
+    }
+
+    /// Sets clock accepted events and first seen records are stamped
+    /// with, see `clock` module.
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// Sets verifier of ledger anchors of events of identifiers with
+    /// registrar backers, see `backer` module.
+    pub fn with_backer_verifier(self, verifier: Arc<dyn BackerVerifier>) -> Self {
+        Self {
+            backer_verifier: Some(verifier),
+            ..self
+        }
+    }
+
+    /// Current time of processor clock.
+    pub fn now(&self) -> DateTime<Local> {
+        self.clock.now()
+    }
+
+    /// Cache of identifier states, see `state_store` module.
+    pub fn states(&self) -> &StateStore {
+        &self.states
+    }
+
+    /// Restores cached states from snapshot, eg. made by
+    /// `StateStore::snapshot` before restart. States which don't match
+    /// the last accepted event of identifier are skipped. Returns number
+    /// of restored states.
+    pub fn restore_states(&self, states: Vec<IdentifierState>) -> usize {
+        let epoch = self.states.epoch();
+        let mut restored = 0;
+        for state in states {
+            // Records of events superseded by recovery are skipped, so
+            // KEL recovered to earlier sn isn't taken as continued.
+            let last = self.db.get_accepted_first_seen(&state.prefix, state.sn);
+            let is_current = matches!(last, Some(seen) if seen.digest == state.last_event_digest)
+                && self
+                    .db
+                    .get_accepted_first_seen(&state.prefix, state.sn + 1)
+                    .is_none();
+            if is_current && self.states.insert_computed(state, epoch) {
+                restored += 1;
+            }
+        }
+        restored
     }
 
     /// Compute State for Prefix
@@ -46,28 +207,39 @@ impl EventProcessor {
     /// Returns the current State associated with
     /// the given Prefix
     pub fn compute_state(&self, id: &IdentifierPrefix) -> Result<Option<IdentifierState>, Error> {
+        if let Some(state) = self.states.get(id) {
+            return Ok(Some(state));
+        }
+        let epoch = self.states.epoch();
         // start with empty state
         let mut state = IdentifierState::default();
         if let Some(events) = self.db.get_kel_finalized_events(id) {
             // we sort here to get inception first
             let mut sorted_events = events.collect::<Vec<TimestampedSignedEventMessage>>();
-            sorted_events.sort();
-            for event in sorted_events {
-                state = match state.clone().apply(&event.signed_event_message) {
-                    Ok(s) => s,
-                    // will happen when a recovery has overridden some part of the KEL,
-                    Err(e) => match e {
-                        // skip out of order and partially signed events
-                        Error::EventOutOfOrderError | Error::NotEnoughSigsError => continue,
-                        // stop processing here
-                        _ => break,
-                    },
-                };
+            if sorted_events.is_empty() {
+                // all events were removed, e.g. rejected inception
+                return Ok(None);
             }
+            sorted_events.sort();
+            telemetry::time_kel_replay(|| {
+                for event in sorted_events {
+                    state = match state.clone().apply(&event.signed_event_message) {
+                        Ok(s) => s,
+                        // will happen when a recovery has overridden some part of the KEL,
+                        Err(e) => match e {
+                            // skip out of order and partially signed events
+                            Error::EventOutOfOrderError | Error::NotEnoughSigsError => continue,
+                            // stop processing here
+                            _ => break,
+                        },
+                    };
+                }
+            });
         } else {
             // no inception event, no state
             return Ok(None);
         }
+        self.states.insert_computed(state.clone(), epoch);
         Ok(Some(state))
     }
 
@@ -117,20 +289,194 @@ impl EventProcessor {
                     .event
                     .get_event_data()
                 {
-                    EventData::Icp(_) => Some(event.signed_event_message),
-                    EventData::Rot(_) => Some(event.signed_event_message),
-                    _ => last_est,
+                    EventData::Ixn(_) => last_est,
+                    _ => Some(event.signed_event_message),
                 }
             }
         } else {
             return Ok(None);
         }
-        let seal = last_est.map(|event| EventSeal {
-            prefix: event.event_message.event.get_prefix(),
-            sn: event.event_message.event.get_sn(),
-            event_digest: event.event_message.get_digest(),
-        });
-        Ok(seal)
+        last_est
+            .map(|event| {
+                let event_digest = match &self.config.seal_digest {
+                    Some(derivation) => event.event_message.digest_with(derivation)?,
+                    None => event.event_message.get_digest(),
+                };
+                Ok(EventSeal {
+                    prefix: event.event_message.event.get_prefix(),
+                    sn: event.event_message.event.get_sn(),
+                    event_digest,
+                })
+            })
+            .transpose()
+    }
+
+    /// Get digest derivation for Prefix
+    ///
+    /// Returns derivation of digests of events in KEL of given Prefix,
+    /// which its next events and digests made for it should follow.
+    pub fn get_digest_derivation(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Result<Option<SelfAddressing>, Error> {
+        Ok(self
+            .compute_state(id)?
+            .map(|state| state.last_event_digest.derivation))
+    }
+
+    /// Is Anchored
+    ///
+    /// Checks if event of `anchoring` seal is accepted and anchors
+    /// `seal` in its data field.
+    pub fn is_anchored(&self, anchoring: &EventSeal, seal: &Seal) -> Result<bool, Error> {
+        let event =
+            self.get_event_at_seal(&anchoring.prefix, anchoring.sn, &anchoring.event_digest)?;
+        Ok(event
+            .signed_event_message
+            .event_message
+            .event
+            .get_event_data()
+            .seals()
+            .contains(seal))
+    }
+
+    /// Verify Digest Anchor
+    ///
+    /// Checks if event of `anchoring` seal anchors digest seal which
+    /// commits to `data`.
+    pub fn verify_digest_anchor(&self, anchoring: &EventSeal, data: &[u8]) -> Result<bool, Error> {
+        let event =
+            self.get_event_at_seal(&anchoring.prefix, anchoring.sn, &anchoring.event_digest)?;
+        Ok(event
+            .signed_event_message
+            .event_message
+            .event
+            .get_event_data()
+            .seals()
+            .iter()
+            .any(|seal| matches!(seal, Seal::Digest(ds) if ds.verify(data))))
+    }
+
+    /// Find Anchoring Event
+    ///
+    /// Returns sn and event of identifier which anchors seal of given
+    /// digest, eg. of document or TEL event. Looked up in seal index
+    /// instead of scanning data fields of the whole KEL, if database
+    /// maintains one.
+    pub fn find_anchoring_event(
+        &self,
+        id: &IdentifierPrefix,
+        said: &SelfAddressingPrefix,
+    ) -> Result<Option<(u64, EventMessage<KeyEvent>)>, Error> {
+        let sn = match self.db.get_anchoring_sn(id, said) {
+            Some(sn) => sn,
+            None => return Ok(None),
+        };
+        Ok(self
+            .get_event_at_sn(id, sn)?
+            .map(|event| (sn, event.signed_event_message.event_message)))
+    }
+
+    /// Find Source Seal
+    ///
+    /// Returns source seal of delegating event, which anchors given
+    /// `dip` or `drt` event in its delegator's KEL. Returns `None` if
+    /// event isn't delegated or delegator's anchoring event isn't known.
+    pub fn find_source_seal(
+        &self,
+        event: &EventMessage<KeyEvent>,
+    ) -> Result<Option<SourceSeal>, Error> {
+        let delegator = match event.event.get_event_data() {
+            EventData::Dip(dip) => dip.delegator,
+            EventData::Drt(_) => match self.compute_state(&event.event.get_prefix())? {
+                Some(IdentifierState {
+                    delegator: Some(delegator),
+                    ..
+                }) => delegator,
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        Ok(self
+            .find_anchoring_event(&delegator, &event.get_digest())?
+            .map(|(sn, anchoring)| SourceSeal::new(sn, anchoring.get_digest())))
+    }
+
+    /// Attach Source Seal
+    ///
+    /// Attaches source seal of delegating event to delegated event, if
+    /// it has none yet. Fails with `MissingSourceSeal` if delegator
+    /// hasn't anchored the event.
+    pub fn attach_source_seal(
+        &self,
+        signed_event: SignedEventMessage,
+    ) -> Result<SignedEventMessage, Error> {
+        if signed_event.delegator_seal.is_some() {
+            return Ok(signed_event);
+        }
+        let event = &signed_event.event_message;
+        let seal = self
+            .find_source_seal(event)?
+            .ok_or_else(|| Error::MissingSourceSeal {
+                prefix: event.event.get_prefix(),
+                sn: event.event.get_sn(),
+            })?;
+        Ok(SignedEventMessage {
+            delegator_seal: Some(seal),
+            ..signed_event
+        })
+    }
+
+    /// Verify Inclusion
+    ///
+    /// Checks if `leaf` belongs to merkle tree, which root is anchored
+    /// in event of `anchoring` seal.
+    pub fn verify_inclusion(
+        &self,
+        anchoring: &EventSeal,
+        leaf: &SelfAddressingPrefix,
+        proof: &InclusionProof,
+    ) -> Result<bool, Error> {
+        let event =
+            self.get_event_at_seal(&anchoring.prefix, anchoring.sn, &anchoring.event_digest)?;
+        Ok(event
+            .signed_event_message
+            .event_message
+            .event
+            .get_event_data()
+            .seals()
+            .iter()
+            .any(|seal| matches!(seal, Seal::Root(rs) if proof.verify(leaf, &rs.tree_root))))
+    }
+
+    /// Resolve Last Establishment Seal
+    ///
+    /// Returns seal of the event which is the last establishment event
+    /// of identifier referred by `seal` at the moment.
+    pub fn resolve_last_establishment_seal(
+        &self,
+        seal: &LastEstablishmentSeal,
+    ) -> Result<Option<EventSeal>, Error> {
+        self.get_last_establishment_event_seal(&seal.prefix)
+    }
+
+    /// Verify With Last Establishment
+    ///
+    /// Verifies signatures of `data` made with keys of the last
+    /// establishment event of identifier referred by `seal`.
+    pub fn verify_with_last_establishment(
+        &self,
+        seal: &LastEstablishmentSeal,
+        data: &[u8],
+        signatures: &[AttachedSignaturePrefix],
+    ) -> Result<(), Error> {
+        let event_seal = self
+            .resolve_last_establishment_seal(seal)?
+            .ok_or_else(|| Error::UnknownIdentifier(seal.prefix.clone()))?;
+        self.verify(
+            data,
+            &Signature::Transferable(event_seal, signatures.to_vec()),
+        )
     }
 
     /// Get KERL for Prefix
@@ -150,6 +496,183 @@ impl EventProcessor {
         }
     }
 
+    /// Get KEL range for Prefix
+    ///
+    /// Returns events of validated KEL for a given Prefix with sn from
+    /// `from_sn` to `to_sn`, both inclusive.
+    pub fn get_kel_range(
+        &self,
+        id: &IdentifierPrefix,
+        from_sn: u64,
+        to_sn: u64,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        match self.db.get_kel_finalized_events(id) {
+            Some(events) => events
+                .map(|event| event.signed_event_message)
+                .filter(|event| (from_sn..=to_sn).contains(&event.event_message.event.get_sn()))
+                .try_fold(vec![], |mut accum, event| {
+                    accum.extend(event.serialize()?);
+                    Ok(accum)
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Get KERL chunks for Prefix
+    ///
+    /// Returns the current validated KEL for a given Prefix as iterator
+    /// of streams of at most `max_bytes`, so long KEL can be sent
+    /// without serializing it at once. Events are never split; event
+    /// longer than `max_bytes` is returned as a chunk on its own.
+    pub fn get_kerl_chunks(&self, id: &IdentifierPrefix, max_bytes: usize) -> Option<KerlChunks> {
+        self.db
+            .get_kel_finalized_events(id)
+            .map(|events| KerlChunks {
+                events,
+                pending: None,
+                max_bytes,
+            })
+    }
+
+    /// Get KEL with receipts for Prefix
+    ///
+    /// Returns the current validated KEL for a given Prefix as CESR
+    /// stream, where each event is followed by its nontransferable and
+    /// transferable receipts, as other nodes expect it when syncing.
+    pub fn get_kel_with_receipts(&self, id: &IdentifierPrefix) -> Result<Option<Vec<u8>>, Error> {
+        self.replay_kel(id, false)
+    }
+
+    /// Get KEL replay for Prefix
+    ///
+    /// Same as `get_kel_with_receipts`, but each event carries also its
+    /// first seen replay couple, so receiver learns in which order and
+    /// when events were accepted.
+    pub fn get_kel_replay(&self, id: &IdentifierPrefix) -> Result<Option<Vec<u8>>, Error> {
+        self.replay_kel(id, true)
+    }
+
+    fn replay_kel(
+        &self,
+        id: &IdentifierPrefix,
+        with_first_seen: bool,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let events = match self.db.get_kel_finalized_events(id) {
+            Some(events) => events,
+            None => return Ok(None),
+        };
+        let receipts_nt: Vec<_> = self
+            .db
+            .get_receipts_nt(id)
+            .map(|rcts| rcts.collect())
+            .unwrap_or_default();
+        let receipts_t: Vec<_> = self
+            .db
+            .get_receipts_t(id)
+            .map(|rcts| rcts.collect())
+            .unwrap_or_default();
+        let first_seen: Vec<_> = self
+            .db
+            .get_first_seen_records(id)
+            .map(|records| records.collect())
+            .unwrap_or_default();
+        let mut stream = vec![];
+        for event in events {
+            let signed_event = &event.signed_event_message;
+            let sn = signed_event.event_message.event.get_sn();
+            let mut event_data = SignedEventData::from(signed_event);
+            if with_first_seen {
+                let digest = signed_event.event_message.get_digest();
+                if let Some(first_seen) = first_seen.iter().find(|seen| seen.digest == digest) {
+                    event_data
+                        .attachments
+                        .push(Attachment::FirstSeenReplayCouplets(vec![(
+                            first_seen.ordinal,
+                            first_seen.timestamp.into(),
+                        )]));
+                }
+            }
+            stream.extend(event_data.to_cesr()?);
+            for rct in receipts_nt.iter().filter(|rct| rct.body.event.sn == sn) {
+                stream.extend(SignedEventData::from(rct.clone()).to_cesr()?);
+            }
+            for rct in receipts_t.iter().filter(|rct| rct.body.event.sn == sn) {
+                stream.extend(SignedEventData::from(rct.clone()).to_cesr()?);
+            }
+        }
+        Ok(Some(stream))
+    }
+
+    /// Get consistency proof for Prefix
+    ///
+    /// Returns proof that KEL of given Prefix at `to_sn` is
+    /// an extension of the KEL at `from_sn`.
+    pub fn consistency_proof(
+        &self,
+        id: &IdentifierPrefix,
+        from_sn: u64,
+        to_sn: u64,
+    ) -> Result<Option<ConsistencyProof>, Error> {
+        if from_sn > to_sn {
+            return Err(Error::SemanticError("Improper sn range".into()));
+        }
+        let mut events = match self.db.get_kel_finalized_events(id) {
+            Some(events) => events
+                .filter(|event| {
+                    let sn = event.signed_event_message.event_message.event.get_sn();
+                    sn >= from_sn && sn <= to_sn
+                })
+                .collect::<Vec<TimestampedSignedEventMessage>>(),
+            None => return Ok(None),
+        };
+        events.sort();
+        if events.len() as u64 != to_sn - from_sn + 1 {
+            return Err(Error::EventOutOfOrderError);
+        }
+        let mut events = events.into_iter().map(|event| event.signed_event_message);
+        let from_digest = events
+            .next()
+            .map(|event| event.event_message.get_digest())
+            .ok_or(Error::EventOutOfOrderError)?;
+        Ok(Some(ConsistencyProof {
+            prefix: id.clone(),
+            from_sn,
+            from_digest,
+            events: events.collect(),
+        }))
+    }
+
+    /// Get event graph
+    ///
+    /// Returns graph of events, seals, delegations and receipts
+    /// of given Prefixes, which can be exported as DOT or JSON.
+    pub fn event_graph(&self, ids: &[IdentifierPrefix]) -> Result<EventGraph, Error> {
+        let mut graph = EventGraph::default();
+        for id in ids {
+            let mut events = self
+                .db
+                .get_kel_finalized_events(id)
+                .ok_or(Error::NotIndexedError)?
+                .collect::<Vec<TimestampedSignedEventMessage>>();
+            events.sort();
+            events
+                .iter()
+                .for_each(|event| graph.add_event(&event.signed_event_message));
+            self.db
+                .get_receipts_nt(id)
+                .into_iter()
+                .flatten()
+                .for_each(|rct| graph.add_nontransferable_receipt(&rct));
+            self.db
+                .get_receipts_t(id)
+                .into_iter()
+                .flatten()
+                .for_each(|rct| graph.add_transferable_receipt(&rct));
+        }
+        Ok(graph)
+    }
+
     /// Get keys from Establishment Event
     ///
     /// Returns the current Key Config associated with
@@ -161,34 +684,70 @@ impl EventProcessor {
         sn: u64,
         event_digest: &SelfAddressingPrefix,
     ) -> Result<Option<KeyConfig>, Error> {
-        if let Ok(Some(event)) = self.get_event_at_sn(id, sn) {
-            // if it's the event we're looking for
-            if event
+        let event = self.get_event_at_seal(id, sn, event_digest)?;
+        // return the config or error if it's not an establishment event
+        Ok(Some(
+            match event
                 .signed_event_message
                 .event_message
-                .check_digest(event_digest)?
+                .event
+                .get_event_data()
             {
-                // return the config or error if it's not an establishment event
-                Ok(Some(
-                    match event
-                        .signed_event_message
-                        .event_message
-                        .event
-                        .get_event_data()
-                    {
-                        EventData::Icp(icp) => icp.key_config,
-                        EventData::Rot(rot) => rot.key_config,
-                        EventData::Dip(dip) => dip.inception_data.key_config,
-                        EventData::Drt(drt) => drt.key_config,
-                        // the receipt has a binding but it's NOT an establishment event
-                        _ => return Err(Error::SemanticError("Receipt binding incorrect".into())),
-                    },
-                ))
+                EventData::Icp(icp) => icp.key_config,
+                EventData::Rot(rot) => rot.key_config,
+                EventData::Dip(dip) => dip.inception_data.key_config,
+                EventData::Drt(drt) => drt.key_config,
+                // the receipt has a binding but it's NOT an establishment event
+                _ => return Err(Error::SemanticError("Receipt binding incorrect".into())),
+            },
+        ))
+    }
+
+    /// Get event bound by seal
+    ///
+    /// Returns finalized event of given prefix, sn and digest. Event is
+    /// found by digest index, and only digests of other derivation than
+    /// the one event was stored with need scanning KEL by sn.
+    fn get_event_at_seal(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+        event_digest: &SelfAddressingPrefix,
+    ) -> Result<TimestampedSignedEventMessage, Error> {
+        if let Some(event) = self.get_event_by_digest(event_digest)? {
+            let event_message = &event.signed_event_message.event_message;
+            let (prefix, event_sn) = (
+                event_message.event.get_prefix(),
+                event_message.event.get_sn(),
+            );
+            return if &prefix != id {
+                Err(Error::PrefixMismatch {
+                    expected: id.clone(),
+                    got: prefix,
+                })
+            } else if event_sn != sn {
+                Err(Error::SnMismatch {
+                    expected: sn,
+                    got: event_sn,
+                })
             } else {
-                Err(Error::SemanticError("Event digests doesn't match".into()))
+                Ok(event)
+            };
+        }
+        match self.get_event_at_sn(id, sn)? {
+            Some(event)
+                if event
+                    .signed_event_message
+                    .event_message
+                    .check_digest(event_digest)? =>
+            {
+                Ok(event)
             }
-        } else {
-            Err(Error::EventOutOfOrderError)
+            Some(event) => Err(Error::DigestMismatch {
+                expected: event_digest.clone(),
+                got: event.signed_event_message.event_message.get_digest(),
+            }),
+            None => Err(Error::EventOutOfOrderError),
         }
     }
 
@@ -201,34 +760,234 @@ impl EventProcessor {
         seal: EventSeal,
         delegated_event: &EventMessage<KeyEvent>,
     ) -> Result<(), Error> {
-        // Check if event of seal's prefix and sn is in db.
-        if let Ok(Some(event)) = self.get_event_at_sn(&seal.prefix, seal.sn) {
-            // Extract prior_digest and data field from delegating event.
-            let data = match event
+        // Check if event of seal's prefix, sn and digest is in db.
+        let event = self.get_event_at_seal(&seal.prefix, seal.sn, &seal.event_digest)?;
+        // Delegator can't be incepted with do not delegate trait.
+        if self
+            .compute_state(&seal.prefix)?
+            .is_some_and(|state| state.has_config_trait(&ConfigTrait::DoNotDelegate))
+        {
+            return Err(Error::DelegationNotAllowed(seal.prefix));
+        }
+        // Extract prior_digest and data field from delegating event.
+        let data = match event
+            .signed_event_message
+            .event_message
+            .event
+            .get_event_data()
+        {
+            EventData::Rot(rot) => rot.data,
+            EventData::Ixn(ixn) => ixn.data,
+            EventData::Drt(drt) => drt.data,
+            _ => {
+                return Err(Error::MissingDelegatingSeal {
+                    delegator: seal.prefix,
+                    sn: seal.sn,
+                })
+            }
+        };
+
+        // Check if event seal list contains delegating event seal.
+        if !data.iter().any(|s| match s {
+            Seal::Event(es) => delegated_event.check_digest(&es.event_digest).unwrap(),
+            _ => false,
+        }) {
+            return Err(Error::MissingDelegatingSeal {
+                delegator: seal.prefix,
+                sn: seal.sn,
+            });
+        };
+        // Delegating event has to be witnessed before delegated event
+        // is accepted.
+        if !self.is_witnessed(&seal.prefix, seal.sn)? {
+            return Err(Error::NotEnoughReceiptsError);
+        }
+        Ok(())
+    }
+
+    /// Validate delegator seal of delegated event.
+    ///
+    /// Builds delegating event seal from source seal attached to
+    /// delegated event and checks if delegator's event anchors it.
+    fn validate_delegator_seal(
+        &self,
+        delegator: &IdentifierPrefix,
+        signed_event: &SignedEventMessage,
+    ) -> Result<(), Error> {
+        let (sn, dig) = signed_event
+            .delegator_seal
+            .as_ref()
+            .map(|seal| (seal.sn, seal.digest.clone()))
+            .ok_or_else(|| Error::MissingSourceSeal {
+                prefix: signed_event.event_message.event.get_prefix(),
+                sn: signed_event.event_message.event.get_sn(),
+            })?;
+        let seal = EventSeal {
+            prefix: delegator.clone(),
+            sn,
+            event_digest: dig,
+        };
+        self.validate_seal(seal, &signed_event.event_message)
+    }
+
+    /// Get delegation chain for Prefix
+    ///
+    /// Walks delegator links from given Prefix up to the root
+    /// (non delegated) identifier and verifies anchoring seals of all
+    /// delegated establishment events on the way. Returns delegators
+    /// ordered from the immediate one to the root.
+    pub fn get_delegation_chain(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Result<Vec<IdentifierPrefix>, Error> {
+        let mut chain: Vec<IdentifierPrefix> = vec![];
+        let mut current = id.clone();
+        while let Some(delegator) = self
+            .compute_state(&current)?
+            .ok_or(Error::EventOutOfOrderError)?
+            .delegator
+        {
+            if delegator == *id || chain.contains(&delegator) {
+                return Err(Error::DelegationCycleError);
+            }
+            if let Some(events) = self.db.get_kel_finalized_events(&current) {
+                events
+                    .filter(|event| {
+                        matches!(
+                            event
+                                .signed_event_message
+                                .event_message
+                                .event
+                                .get_event_data(),
+                            EventData::Dip(_) | EventData::Drt(_)
+                        )
+                    })
+                    .try_for_each(|event| {
+                        self.validate_delegator_seal(&delegator, &event.signed_event_message)
+                    })?;
+            };
+            chain.push(delegator.clone());
+            current = delegator;
+        }
+        Ok(chain)
+    }
+
+    /// Get Delegates
+    ///
+    /// Returns identifiers which inception events `delegator` anchored
+    /// with event seals in its KEL, in order of anchoring.
+    pub fn get_delegates(
+        &self,
+        delegator: &IdentifierPrefix,
+    ) -> Result<Vec<IdentifierPrefix>, Error> {
+        let mut delegates: Vec<IdentifierPrefix> = vec![];
+        for event in self
+            .db
+            .get_kel_finalized_events(delegator)
+            .into_iter()
+            .flatten()
+        {
+            let data = event
                 .signed_event_message
                 .event_message
                 .event
-                .get_event_data()
-            {
-                EventData::Rot(rot) => rot.data,
-                EventData::Ixn(ixn) => ixn.data,
-                EventData::Drt(drt) => drt.data,
-                _ => return Err(Error::SemanticError("Improper event type".to_string())),
-            };
-
-            // Check if event seal list contains delegating event seal.
-            if !data.iter().any(|s| match s {
-                Seal::Event(es) => delegated_event.check_digest(&es.event_digest).unwrap(),
-                _ => false,
-            }) {
-                return Err(Error::SemanticError(
-                    "Data field doesn't contain delegating event seal.".to_string(),
-                ));
-            };
-        } else {
-            return Err(Error::EventOutOfOrderError);
+                .get_event_data();
+            for seal in data.seals() {
+                match seal {
+                    Seal::Event(seal)
+                        if seal.sn == 0
+                            && &seal.prefix != delegator
+                            && !delegates.contains(&seal.prefix) =>
+                    {
+                        delegates.push(seal.prefix.clone())
+                    }
+                    _ => (),
+                }
+            }
         }
-        Ok(())
+        Ok(delegates)
+    }
+
+    /// Is Delegation Active
+    ///
+    /// Checks if delegated identifier can still change its KEL.
+    /// Delegator revokes delegation by anchoring rotation of the
+    /// delegate to null next keys, after which no more events of the
+    /// delegate are accepted.
+    pub fn is_delegation_active(&self, delegate: &IdentifierPrefix) -> Result<bool, Error> {
+        let state = self
+            .compute_state(delegate)?
+            .ok_or_else(|| Error::UnknownIdentifier(delegate.clone()))?;
+        self.delegation_active(&state)
+    }
+
+    fn delegation_active(&self, state: &IdentifierState) -> Result<bool, Error> {
+        let delegator = state
+            .delegator
+            .as_ref()
+            .ok_or_else(|| Error::MissingDelegator(state.prefix.clone()))?;
+        Ok(!state.is_abandoned() && self.get_delegates(delegator)?.contains(&state.prefix))
+    }
+
+    /// Is Witnessed
+    ///
+    /// Checks if event of given Prefix and sn is receipted by enough of
+    /// its designated witnesses to reach witness threshold.
+    pub fn is_witnessed(&self, id: &IdentifierPrefix, sn: u64) -> Result<bool, Error> {
+        let state = self
+            .compute_state_at_sn(id, sn)?
+            .ok_or(Error::EventOutOfOrderError)?;
+        let designated = state.witnesses_at_sn(sn).unwrap_or_default();
+        let mut witnesses: Vec<BasicPrefix> = vec![];
+        self.get_nt_receipts_for_event(id, sn)?
+            .into_iter()
+            .flat_map(|rct| rct.couplets.into_iter().map(|(witness, _)| witness))
+            .filter(|witness| designated.contains(witness))
+            .for_each(|witness| {
+                if !witnesses.contains(&witness) {
+                    witnesses.push(witness)
+                }
+            });
+        Ok(witnesses.len() as u64 >= state.tally)
+    }
+
+    /// Get nontransferable receipts for event
+    ///
+    /// Returns accepted nontransferable receipts of event of given
+    /// Prefix and sn, in order of arrival. They may come from non
+    /// witnesses too.
+    pub fn get_nt_receipts_for_event(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+    ) -> Result<Vec<SignedNontransferableReceipt>, Error> {
+        Ok(self
+            .db
+            .get_receipts_nt(id)
+            .into_iter()
+            .flatten()
+            .filter(|rct| rct.body.event.sn == sn)
+            .collect())
+    }
+
+    /// Get receipt count
+    ///
+    /// Returns number of distinct nontransferable receiptors and
+    /// validators which receipted event of given Prefix and sn.
+    pub fn get_receipt_count(&self, id: &IdentifierPrefix, sn: u64) -> Result<usize, Error> {
+        let witnesses = self
+            .get_nt_receipts_for_event(id, sn)?
+            .into_iter()
+            .flat_map(|rct| rct.couplets)
+            .map(|(witness, _)| witness.to_str());
+        let validators = self
+            .db
+            .get_receipts_t(id)
+            .into_iter()
+            .flatten()
+            .filter(|rct| rct.body.event.sn == sn)
+            .map(|rct| rct.validator_seal.prefix.to_str());
+        Ok(witnesses.chain(validators).collect::<HashSet<_>>().len())
     }
 
     pub fn has_receipt(
@@ -250,14 +1009,134 @@ impl EventProcessor {
     ///
     /// Process a deserialized KERI message
     pub fn process(&self, data: Message) -> Result<Option<IdentifierState>, Error> {
+        self.process_tagged(None, data, None)
+    }
+
+    /// Like `process`, with tag of the message source, eg. identifier or
+    /// address of the peer, which is kept in audit trail.
+    pub fn process_from(
+        &self,
+        source: &str,
+        data: Message,
+    ) -> Result<Option<IdentifierState>, Error> {
+        self.process_tagged(Some(source), data, None)
+    }
+
+    /// Processes message from `source`. Signatures of event already
+    /// verified against `verified_keys` aren't checked again if the keys
+    /// are still current.
+    fn process_tagged(
+        &self,
+        source: Option<&str>,
+        data: Message,
+        verified_keys: Option<&KeyConfig>,
+    ) -> Result<Option<IdentifierState>, Error> {
+        let message_type = telemetry::message_type(&data);
+        let _span = tracing::debug_span!("process", message_type).entered();
+        let audited = self.config.audit_trail.then(|| data.clone());
+        let result = self.process_message(data, verified_keys);
+        telemetry::record_processed(message_type, &result);
+        if let Some(message) = audited {
+            self.audit(&message, source, &result);
+        }
+        result
+    }
+
+    fn audit(
+        &self,
+        message: &Message,
+        source: Option<&str>,
+        result: &Result<Option<IdentifierState>, Error>,
+    ) {
+        let outcome = match result {
+            Err(Error::EventDuplicateError) => AuditOutcome::Duplicitous,
+            #[cfg(feature = "query")]
+            Err(Error::QueryError(QueryError::OutOfOrderEventError)) => AuditOutcome::Escrowed,
+            Ok(_) | Err(Error::SemanticError(_)) if self.is_receipt_escrowed(message) => {
+                AuditOutcome::Escrowed
+            }
+            Ok(_) => AuditOutcome::Accepted,
+            Err(e) => AuditOutcome::Rejected(e.to_string()),
+        };
+        let record = AuditRecord::new(message, source, outcome, self.clock.now());
+        if let Err(e) = self.db.add_audit_record(record) {
+            tracing::warn!(error = %e, "can't store audit record");
+        }
+    }
+
+    /// Checks if message is receipt of event which isn't accepted yet,
+    /// so receipt waits in escrow.
+    fn is_receipt_escrowed(&self, message: &Message) -> bool {
+        let receipted = match message {
+            Message::NontransferableRct(rct) => &rct.body.event,
+            Message::TransferableRct(rct) => &rct.body.event,
+            _ => return false,
+        };
+        matches!(
+            self.get_event_at_sn(&receipted.prefix, receipted.sn),
+            Ok(None)
+        )
+    }
+
+    /// Audit records made between `from` and `to`, both inclusive, in
+    /// order they were made.
+    pub fn get_audit_trail(
+        &self,
+        from: Option<DateTime<Local>>,
+        to: Option<DateTime<Local>>,
+    ) -> Vec<AuditRecord> {
+        self.db
+            .get_audit_trail()
+            .into_iter()
+            .flatten()
+            .filter(|record| from.map_or(true, |from| record.timestamp >= from))
+            .filter(|record| to.map_or(true, |to| record.timestamp <= to))
+            .collect()
+    }
+
+    /// Export Audit Trail
+    ///
+    /// Audit records made between `from` and `to` as JSON lines, one
+    /// record per line, for compliance audits.
+    pub fn export_audit_trail(
+        &self,
+        from: Option<DateTime<Local>>,
+        to: Option<DateTime<Local>>,
+    ) -> Result<Vec<u8>, Error> {
+        self.get_audit_trail(from, to)
+            .iter()
+            .try_fold(vec![], |mut export, record| {
+                export.extend(serde_json::to_vec(record)?);
+                export.push(b'\n');
+                Ok(export)
+            })
+    }
+
+    fn process_message(
+        &self,
+        data: Message,
+        verified_keys: Option<&KeyConfig>,
+    ) -> Result<Option<IdentifierState>, Error> {
+        self.config.check_message(&data)?;
         match data {
-            Message::Event(e) => self.process_event(&e),
+            Message::Event(e) => {
+                let id = &e.event_message.event.get_prefix();
+                self.locks
+                    .with_lock(id, || self.process_event_locked(id, &e, verified_keys))
+            }
             Message::NontransferableRct(rct) => self.process_witness_receipt(rct),
+            Message::WitnessRct(rct) => self.process_indexed_witness_receipt(rct),
             Message::TransferableRct(rct) => self.process_validator_receipt(rct),
             #[cfg(feature = "query")]
             Message::KeyStateNotice(ksn_rpy) => self.process_signed_reply(&ksn_rpy),
             #[cfg(feature = "query")]
-            Message::Query(_qry) => todo!(),
+            Message::Query(_qry) => Err(Error::SemanticError(
+                "Query should be processed with process_query".into(),
+            )),
+            #[cfg(feature = "query")]
+            Message::Exchange(_exn) => Err(Error::SemanticError(
+                "Exchange should be dispatched with ExchangeRouter".into(),
+            )),
         }
     }
 
@@ -279,81 +1158,156 @@ impl EventProcessor {
     /// of the Identifier and applies it to update the state
     /// returns the updated state
     /// TODO improve checking and handling of errors!
+    ///
+    /// Holds lock of event identifier for the whole processing.
     pub fn process_event(
         &self,
         signed_event: &SignedEventMessage,
     ) -> Result<Option<IdentifierState>, Error> {
-        // Log event.
-
         let id = &signed_event.event_message.event.get_prefix();
+        self.locks
+            .with_lock(id, || self.process_event_locked(id, signed_event, None))
+    }
 
-        // If delegated event, check its delegator seal.
+    fn process_event_locked(
+        &self,
+        id: &IdentifierPrefix,
+        signed_event: &SignedEventMessage,
+        verified_keys: Option<&KeyConfig>,
+    ) -> Result<Option<IdentifierState>, Error> {
+        let state = self.compute_state(id)?;
+        // Abandoned identifier, or delegate which delegation was revoked,
+        // can't add new events.
+        match &state {
+            Some(state) if signed_event.event_message.event.get_sn() > state.sn => {
+                if state.delegator.is_some() && !self.delegation_active(state)? {
+                    return Err(Error::DelegationRevoked(id.clone()));
+                }
+                if state.is_abandoned() {
+                    return Err(Error::IdentifierAbandoned(id.clone()));
+                }
+            }
+            _ => (),
+        }
+        // If delegated event, check its delegator seal and delegation chain
+        // of its delegator.
         match signed_event.event_message.event.get_event_data() {
             EventData::Dip(dip) => {
-                let (sn, dig) = signed_event
-                    .delegator_seal
-                    .as_ref()
-                    .map(|seal| (seal.sn, seal.digest.clone()))
-                    .ok_or_else(|| Error::SemanticError("Missing source seal".into()))?;
-                let seal = EventSeal {
-                    prefix: dip.delegator,
-                    sn,
-                    event_digest: dig,
-                };
-                self.validate_seal(seal, &signed_event.event_message)
+                self.validate_delegator_seal(&dip.delegator, signed_event)?;
+                self.get_delegation_chain(&dip.delegator).map(|_| ())
             }
             EventData::Drt(_drt) => {
+                let prefix = signed_event.event_message.event.get_prefix();
                 let delegator = self
-                    .compute_state(&signed_event.event_message.event.get_prefix())?
-                    .ok_or_else(|| {
-                        Error::SemanticError("Missing state of delegated identifier".into())
-                    })?
+                    .compute_state(&prefix)?
+                    .ok_or_else(|| Error::UnknownIdentifier(prefix.clone()))?
                     .delegator
-                    .ok_or_else(|| Error::SemanticError("Missing delegator".into()))?;
-                let (sn, dig) = signed_event
-                    .delegator_seal
-                    .as_ref()
-                    .map(|seal| (seal.sn, seal.digest.clone()))
-                    .ok_or_else(|| Error::SemanticError("Missing source seal".into()))?;
-                let seal = EventSeal {
-                    prefix: delegator,
-                    sn,
-                    event_digest: dig,
-                };
-                self.validate_seal(seal, &signed_event.event_message)
+                    .ok_or_else(|| Error::MissingDelegator(prefix.clone()))?;
+                if !self.get_delegates(&delegator)?.contains(&prefix) {
+                    return Err(Error::UnknownDelegate {
+                        delegator,
+                        delegate: prefix,
+                    });
+                }
+                self.validate_delegator_seal(&delegator, signed_event)?;
+                self.get_delegation_chain(&delegator).map(|_| ())
             }
             _ => Ok(()),
         }?;
-        self.apply_to_state(&signed_event.event_message)
-            .and_then(|new_state| {
-                // add event from the get go and clean it up on failure later
-                self.db.add_kel_finalized_event(signed_event.clone(), id)?;
-                // match on verification result
-                match new_state
-                    .current
-                    .verify(
-                        &signed_event.event_message.serialize()?,
-                        &signed_event.signatures,
-                    )
-                    .and_then(|result| {
-                        if !result {
-                            Err(Error::SignatureVerificationError)
-                        } else {
-                            // TODO should check if there are enough receipts and probably escrow
-                            Ok(new_state)
-                        }
-                    }) {
-                    Ok(state) => Ok(Some(state)),
-                    Err(e) => {
-                        if let Error::EventDuplicateError = e {
-                            self.db.add_duplicious_event(signed_event.clone(), id)?
-                        };
-                        // remove last added event
-                        self.db.remove_kel_finalized_event(id, signed_event)?;
-                        Err(e)
-                    }
+        // First seen version of event wins. Other version of accepted
+        // event is either recovery or duplicity.
+        let sn = signed_event.event_message.event.get_sn();
+        let prior = state.clone().unwrap_or_default();
+        if let Some(state) = state.filter(|state| sn <= state.sn) {
+            if let Some(accepted) = self.get_event_at_sn(id, sn)? {
+                let accepted = accepted.signed_event_message.event_message;
+                if accepted.get_digest() != signed_event.event_message.get_digest() {
+                    return self.process_conflicting_event(id, signed_event, &accepted, state);
                 }
-            })
+            }
+        }
+        let new_state = self.apply_to_state(&signed_event.event_message)?;
+        // Event is verified before it's written, so KEL and its digest
+        // and anchor indexes never hold unverified events.
+        let verified = match verified_keys {
+            Some(keys) if keys == &new_state.current => true,
+            _ => prior.verify_signatures(
+                &new_state,
+                &signed_event.raw_event()?,
+                &signed_event.signatures,
+            )?,
+        };
+        if !verified {
+            return Err(Error::SignatureVerificationError);
+        }
+        // TODO should check if there are enough receipts and probably escrow
+        self.verify_backer_anchor(&new_state, &signed_event.event_message)?;
+
+        let accepted_at = self.clock.now();
+        self.states.invalidate(id);
+        self.db
+            .add_kel_finalized_event(signed_event.clone(), id, accepted_at)?;
+        self.db
+            .add_first_seen(id, sn, signed_event.event_message.get_digest(), accepted_at)?;
+        self.states.insert(new_state.clone());
+        Ok(Some(new_state))
+    }
+
+    /// Process Conflicting Event
+    ///
+    /// Verifies version of event at sn of already accepted `accepted`
+    /// event against key state preceding it, so forged copies aren't
+    /// taken for duplicity evidence. Rotation superseding interaction
+    /// events made after the last establishment event recovers the KEL,
+    /// which replaces superseded events with the rotation. Any other
+    /// verified version is stored as evidence of duplicity.
+    fn process_conflicting_event(
+        &self,
+        id: &IdentifierPrefix,
+        signed_event: &SignedEventMessage,
+        accepted: &EventMessage<KeyEvent>,
+        current: IdentifierState,
+    ) -> Result<Option<IdentifierState>, Error> {
+        let sn = signed_event.event_message.event.get_sn();
+        let prior = match sn {
+            0 => IdentifierState::default(),
+            _ => self
+                .compute_state_at_sn(id, sn - 1)?
+                .ok_or(Error::EventOutOfOrderError)?,
+        };
+        let new_state = prior.clone().apply(&signed_event.event_message)?;
+        if !prior.verify_signatures(
+            &new_state,
+            &signed_event.raw_event()?,
+            &signed_event.signatures,
+        )? {
+            return Err(Error::SignatureVerificationError);
+        }
+        let is_recovery = matches!(
+            signed_event.event_message.event.get_event_data(),
+            EventData::Rot(_) | EventData::Drt(_)
+        ) && matches!(accepted.event.get_event_data(), EventData::Ixn(_))
+            && sn > current.last_est.sn;
+        if !is_recovery {
+            self.db.add_duplicious_event(signed_event.clone(), id)?;
+            return Err(Error::EventDuplicateError);
+        }
+        self.verify_backer_anchor(&new_state, &signed_event.event_message)?;
+
+        self.states.invalidate(id);
+        for superseded in sn..=current.sn {
+            if let Some(event) = self.get_event_at_sn(id, superseded)? {
+                self.db
+                    .remove_kel_finalized_event(id, &event.signed_event_message)?;
+            }
+        }
+        let accepted_at = self.clock.now();
+        self.db
+            .add_kel_finalized_event(signed_event.clone(), id, accepted_at)?;
+        self.db
+            .add_first_seen(id, sn, signed_event.event_message.get_digest(), accepted_at)?;
+        self.states.insert(new_state.clone());
+        Ok(Some(new_state))
     }
 
     /// Process Validator Receipt
@@ -373,10 +1327,9 @@ impl EventProcessor {
                 &vrc.validator_seal.event_digest,
             )?;
             if kp.is_some()
-                && kp.unwrap().verify(
-                    &event.signed_event_message.event_message.serialize()?,
-                    &vrc.signatures,
-                )?
+                && kp
+                    .unwrap()
+                    .verify(&event.signed_event_message.raw_event()?, &vrc.signatures)?
             {
                 self.db.add_receipt_t(vrc.clone(), &vrc.body.event.prefix)
             } else {
@@ -403,18 +1356,19 @@ impl EventProcessor {
         // get event which is being receipted
         let id = &rct.body.event.prefix.to_owned();
         if let Ok(Some(event)) = self.get_event_at_sn(&rct.body.event.prefix, rct.body.event.sn) {
-            let serialized_event = event.signed_event_message.serialize()?;
-            let (_, mut errors): (Vec<_>, Vec<Result<bool, Error>>) = rct
-                .clone()
-                .couplets
-                .into_iter()
-                .map(|(witness, receipt)| witness.verify(&serialized_event, &receipt))
-                .partition(Result::is_ok);
-            if errors.is_empty() {
-                self.db.add_receipt_nt(rct, id)?
-            } else {
-                let e = errors.pop().unwrap().unwrap_err();
-                return Err(e);
+            // Receipts of non witnesses are stored too, but only these of
+            // designated witnesses count towards witness threshold.
+            // Couplets which signature doesn't verify are dropped.
+            let serialized_event = event.signed_event_message.raw_event()?;
+            let mut couplets = vec![];
+            for (witness, receipt) in rct.couplets {
+                if witness.verify(&serialized_event, &receipt)? {
+                    couplets.push((witness, receipt));
+                }
+            }
+            if !couplets.is_empty() {
+                self.db
+                    .add_receipt_nt(SignedNontransferableReceipt::new(&rct.body, couplets), id)?
             }
         } else {
             self.db.add_escrow_nt_receipt(rct, id)?
@@ -422,6 +1376,94 @@ impl EventProcessor {
         self.compute_state(id)
     }
 
+    /// Process Indexed Witness Receipt
+    ///
+    /// Resolves witness signatures indexed into witness list of
+    /// receipted event and processes them as nontransferable receipt.
+    /// Receipted event has to be accepted already.
+    pub fn process_indexed_witness_receipt(
+        &self,
+        rct: SignedWitnessReceipt,
+    ) -> Result<Option<IdentifierState>, Error> {
+        let (id, sn) = (&rct.body.event.prefix, rct.body.event.sn);
+        let state = self
+            .compute_state_at_sn(id, sn)?
+            .ok_or(Error::EventOutOfOrderError)?;
+        let witnesses = state
+            .witnesses_at_sn(sn)
+            .ok_or(Error::EventOutOfOrderError)?;
+        let couplets = rct
+            .signatures
+            .into_iter()
+            .map(|sig| {
+                witnesses
+                    .get(sig.index as usize)
+                    .map(|witness| (witness.clone(), sig.signature))
+                    .ok_or_else(|| Error::SemanticError("Witness index out of range".into()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.process_witness_receipt(SignedNontransferableReceipt::new(&rct.body, couplets))
+    }
+
+    /// Get First Seen
+    ///
+    /// Returns record of the first accepted version of event
+    /// of given Prefix and sn.
+    pub fn get_first_seen(
+        &self,
+        id: &IdentifierPrefix,
+        sn: u64,
+    ) -> Result<Option<FirstSeen>, Error> {
+        Ok(self.db.get_first_seen(id, sn))
+    }
+
+    /// Subscribe
+    ///
+    /// Starts watching given Prefix fetched from given source.
+    /// If subscription already exists its cursor is kept, so
+    /// fetching is resumed from the last seen event.
+    pub fn subscribe(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Result<Subscription, Error> {
+        match self.db.get_subscription(id, source) {
+            Some(subscription) => Ok(subscription),
+            None => {
+                let subscription = Subscription::new(id.clone(), source.clone());
+                self.db.update_subscription(subscription.clone())?;
+                Ok(subscription)
+            }
+        }
+    }
+
+    /// Update Subscription Cursor
+    ///
+    /// Moves cursor of subscription to the last event of the
+    /// current KEL of given Prefix. Should be called after
+    /// processing events fetched from the source.
+    pub fn update_subscription_cursor(
+        &self,
+        id: &IdentifierPrefix,
+        source: &IdentifierPrefix,
+    ) -> Result<Subscription, Error> {
+        let mut subscription = self.subscribe(id, source)?;
+        if let Some(state) = self.compute_state(id)? {
+            subscription.last_sn = Some(state.sn);
+            subscription.last_digest = Some(state.last_event_digest);
+            self.db.update_subscription(subscription.clone())?;
+        }
+        Ok(subscription)
+    }
+
+    /// Returns finalized event of given digest, without scanning KELs.
+    pub fn get_event_by_digest(
+        &self,
+        digest: &SelfAddressingPrefix,
+    ) -> Result<Option<TimestampedSignedEventMessage>, Error> {
+        Ok(self.db.get_event_by_digest(digest))
+    }
+
     pub fn get_event_at_sn(
         &self,
         id: &IdentifierPrefix,
@@ -460,7 +1502,7 @@ impl EventProcessor {
 
     #[cfg(feature = "query")]
     fn bada_logic(&self, new_rpy: &SignedReply) -> Result<(), Error> {
-        use crate::query::{reply::ReplyEvent, Route};
+        use crate::query::reply::ReplyEvent;
         let accepted_replys = self
             .db
             .get_accepted_replys(&new_rpy.reply.event.get_prefix());
@@ -488,9 +1530,7 @@ impl EventProcessor {
 
                 // get last reply for prefix with route with sender_prefix
                 match accepted_replys.and_then(|mut o| {
-                    o.find(|r: &SignedReply| {
-                        r.reply.event.get_route() == Route::ReplyKsn(seal.prefix.clone())
-                    })
+                    o.find(|r: &SignedReply| r.reply.event.is_same_subject(&new_rpy.reply.event))
                 }) {
                     Some(old_rpy) => {
                         // check sns
@@ -515,13 +1555,10 @@ impl EventProcessor {
                     None => Err(QueryError::NoSavedReply.into()),
                 }
             }
-            Signature::NonTransferable(bp, _sig) => {
+            Signature::NonTransferable(_bp, _sig) => {
                 //  If date-time-stamp of new is greater than old
                 match accepted_replys.and_then(|mut o| {
-                    o.find(|r| {
-                        r.reply.event.get_route()
-                            == Route::ReplyKsn(IdentifierPrefix::Basic(bp.clone()))
-                    })
+                    o.find(|r| r.reply.event.is_same_subject(&new_rpy.reply.event))
                 }) {
                     Some(old_rpy) => check_dts(&new_rpy.reply.event, &old_rpy.reply.event),
                     None => Err(QueryError::NoSavedReply.into()),
@@ -559,7 +1596,10 @@ impl EventProcessor {
                 anything => anything,
             }?;
             // now unpack ksn and check its details
-            let ksn = rpy.reply.event.get_reply_data();
+            let ksn = match rpy.reply.event.get_reply_data() {
+                ReplyBody::Ksn(ksn) => *ksn,
+                _ => return Err(QueryError::Error("Improper reply data".into()).into()),
+            };
             let ksn_checking_result = self.check_ksn(&ksn, aid);
             if let Err(Error::QueryError(QueryError::OutOfOrderEventError)) = ksn_checking_result {
                 self.escrow_reply(&rpy)?;
@@ -567,12 +1607,92 @@ impl EventProcessor {
             ksn_checking_result?;
             self.db
                 .update_accepted_reply(rpy.clone(), &rpy.reply.event.get_prefix())?;
-            Ok(Some(rpy.reply.event.get_state()))
+            Ok(rpy.reply.event.get_state())
+        } else if matches!(
+            route,
+            Route::LocScheme | Route::EndRoleAdd | Route::EndRoleCut
+        ) {
+            self.process_endpoint_reply(rpy).map(|_| None)
         } else {
             Err(Error::SemanticError("wrong route type".into()))
         }
     }
 
+    /// Process endpoint reply
+    ///
+    /// Accepts `/loc/scheme` reply signed by the endpoint and
+    /// `/end/role` reply signed by the controller, unless it's stale
+    /// according to BADA rules. Accepted reply replaces previous one
+    /// of the same subject.
+    #[cfg(feature = "query")]
+    fn process_endpoint_reply(&self, rpy: &SignedReply) -> Result<(), Error> {
+        use crate::query::Route;
+
+        let signer = match (
+            rpy.reply.event.get_route(),
+            rpy.reply.event.get_reply_data(),
+        ) {
+            (Route::LocScheme, ReplyBody::LocScheme(loc)) => loc.eid,
+            (Route::EndRoleAdd, ReplyBody::EndRole(end_role))
+            | (Route::EndRoleCut, ReplyBody::EndRole(end_role)) => end_role.cid,
+            _ => return Err(QueryError::Error("Improper reply data".into()).into()),
+        };
+        if rpy.signature.get_signer() != signer {
+            return Err(QueryError::Error("Wrong reply message signer".into()).into());
+        };
+        let verification_result = self.verify(&rpy.reply.serialize()?, &rpy.signature);
+        if let Err(Error::EventOutOfOrderError) = verification_result {
+            self.escrow_reply(rpy)?;
+            return Err(Error::QueryError(QueryError::OutOfOrderEventError));
+        }
+        verification_result?;
+        rpy.reply.check_digest()?;
+        match self.bada_logic(rpy) {
+            Err(Error::QueryError(QueryError::NoSavedReply)) => Ok(()),
+            anything => anything,
+        }?;
+        self.db
+            .update_accepted_reply(rpy.clone(), &rpy.reply.event.get_prefix())
+    }
+
+    /// Get end point
+    ///
+    /// Returns locations with given `scheme` of all endpoints which
+    /// `id` authorized to play `role`, according to accepted
+    /// `/end/role` and `/loc/scheme` replies.
+    #[cfg(feature = "query")]
+    pub fn get_end_point(
+        &self,
+        id: &IdentifierPrefix,
+        role: Role,
+        scheme: Scheme,
+    ) -> Vec<LocationScheme> {
+        use crate::query::Route;
+
+        self.db
+            .get_accepted_replys(id)
+            .into_iter()
+            .flatten()
+            .filter(|rpy| rpy.reply.event.get_route() == Route::EndRoleAdd)
+            .filter_map(|rpy| match rpy.reply.event.get_reply_data() {
+                ReplyBody::EndRole(end_role) if end_role.role == role => Some(end_role.eid),
+                _ => None,
+            })
+            .flat_map(|eid| {
+                self.db
+                    .get_accepted_replys(&eid)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |rpy| match rpy.reply.event.get_reply_data() {
+                        ReplyBody::LocScheme(loc) if loc.eid == eid && loc.scheme == scheme => {
+                            Some(loc)
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
     #[cfg(feature = "query")]
     pub fn check_timestamp_with_last_ksn(
         &self,
@@ -644,6 +1764,93 @@ impl EventProcessor {
         }
     }
 
+    /// Process Query
+    ///
+    /// Verifies querier signatures against its current key state
+    /// and returns data requested by the query route.
+    #[cfg(feature = "query")]
+    pub fn process_query(&self, qry: &SignedQuery) -> Result<ReplyPayload, Error> {
+        use crate::{event::SerializationFormats, query::Route};
+
+        let querier_keys = self
+            .compute_state(&qry.signer)?
+            .ok_or_else(|| QueryError::Error("No querier identifier in db".into()))?
+            .current;
+        if !querier_keys.verify(&qry.envelope.serialize()?, &qry.signatures)? {
+            return Err(Error::SignatureVerificationError);
+        }
+        let args = qry.envelope.event.get_query_data().data;
+        let id = args.i;
+        match qry.envelope.event.get_route() {
+            Route::Log => {
+                let events = self
+                    .db
+                    .get_kel_finalized_events(&id)
+                    .ok_or_else(|| QueryError::Error("No identifier in db".into()))?;
+                // Only events from requested sn, if querier already has
+                // the beginning of the KEL.
+                let from_sn = args.sn.unwrap_or_default();
+                let kel = events
+                    .map(|event| event.signed_event_message)
+                    .filter(|event| event.event_message.event.get_sn() >= from_sn)
+                    .map(|event| event.serialize())
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(ReplyPayload::Kel(kel.concat()))
+            }
+            Route::Ksn => {
+                let state = self
+                    .compute_state(&id)?
+                    .ok_or_else(|| QueryError::Error("No identifier in db".into()))?;
+                Ok(ReplyPayload::Ksn(Box::new(KeyStateNotice::new_ksn(
+                    state,
+                    SerializationFormats::JSON,
+                ))))
+            }
+            Route::Mbx => {
+                let nt_receipts = self
+                    .db
+                    .get_receipts_nt(&id)
+                    .into_iter()
+                    .flatten()
+                    .map(Message::NontransferableRct);
+                let t_receipts = self
+                    .db
+                    .get_receipts_t(&id)
+                    .into_iter()
+                    .flatten()
+                    .map(Message::TransferableRct);
+                Ok(ReplyPayload::Mbx(nt_receipts.chain(t_receipts).collect()))
+            }
+            Route::ReplyKsn(_)
+            | Route::LocScheme
+            | Route::EndRoleAdd
+            | Route::EndRoleCut
+            | Route::Alias => Err(QueryError::Error("Improper query route".into()).into()),
+        }
+    }
+
+    /// Verify Alias
+    ///
+    /// Checks if alias is attested by the identifier it points to, ie.
+    /// its attestation claims the alias and is signed by the identifier.
+    #[cfg(feature = "query")]
+    pub fn verify_alias(&self, record: &AliasRecord) -> Result<(), Error> {
+        let attestation = record
+            .attestation
+            .as_ref()
+            .ok_or_else(|| Error::SemanticError("Alias isn't attested".into()))?;
+        if !record.is_claimed_by(attestation) {
+            return Err(Error::SemanticError(
+                "Attestation doesn't claim the alias".into(),
+            ));
+        }
+        if attestation.signature.get_signer() != record.prefix {
+            return Err(QueryError::Error("Wrong reply message signer".into()).into());
+        }
+        attestation.reply.check_digest()?;
+        self.verify(&attestation.reply.serialize()?, &attestation.signature)
+    }
+
     #[cfg(feature = "query")]
     fn escrow_reply(&self, rpy: &SignedReply) -> Result<(), Error> {
         let id = rpy.reply.event.get_prefix();
@@ -653,7 +1860,8 @@ impl EventProcessor {
     #[cfg(feature = "query")]
     pub fn process_escrow(&self) -> Result<(), Error> {
         self.db.get_all_escrowed_replys().map(|esc| {
-            esc.for_each(|sig_rep| {
+            esc.for_each(|escrowed| {
+                let sig_rep = escrowed.value;
                 match self.process_signed_reply(&sig_rep) {
                     Ok(_)
                     | Err(Error::SignatureVerificationError)
@@ -663,10 +1871,79 @@ impl EventProcessor {
                             .remove_escrowed_reply(&sig_rep.reply.event.get_prefix(), sig_rep)
                             .unwrap();
                     }
+                    // purge if kept in escrow for too long
+                    Err(_e) if self.config.is_escrow_expired(&escrowed.timestamp) => {
+                        self.db
+                            .remove_escrowed_reply(&sig_rep.reply.event.get_prefix(), sig_rep)
+                            .unwrap();
+                    }
                     Err(_e) => {} // keep in escrow,
                 }
             })
         });
         Ok(())
     }
+
+    /// Process Escrows
+    ///
+    /// Maintenance entry point, to be called periodically. Escrowed
+    /// messages are processed again if what they were waiting for has
+    /// arrived, and the ones kept in escrow for longer than escrow
+    /// timeout of processor config are purged.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn process_escrows(&self) -> Result<(), Error> {
+        for escrowed in self.db.get_all_escrow_nt_receipts().into_iter().flatten() {
+            let rct = escrowed.value;
+            let id = rct.body.event.prefix.clone();
+            if self.get_event_at_sn(&id, rct.body.event.sn)?.is_some() {
+                self.db.remove_escrow_nt_receipt(&id, &rct)?;
+                // receipt which isn't accepted now is invalid, so it's dropped
+                self.process_witness_receipt(rct).ok();
+            } else if self.config.is_escrow_expired(&escrowed.timestamp) {
+                self.db.remove_escrow_nt_receipt(&id, &rct)?;
+            }
+        }
+        for escrowed in self.db.get_all_escrow_t_receipts().into_iter().flatten() {
+            let vrc = escrowed.value;
+            let id = vrc.body.event.prefix.clone();
+            if self.get_event_at_sn(&id, vrc.body.event.sn)?.is_some() {
+                self.db.remove_escrow_t_receipt(&id, &vrc)?;
+                self.process_validator_receipt(vrc).ok();
+            } else if self.config.is_escrow_expired(&escrowed.timestamp) {
+                self.db.remove_escrow_t_receipt(&id, &vrc)?;
+            }
+        }
+        #[cfg(feature = "query")]
+        self.process_escrow()?;
+
+        telemetry::record_escrow_size(
+            "nt_receipts",
+            self.db
+                .get_all_escrow_nt_receipts()
+                .map_or(0, |e| e.count()),
+        );
+        telemetry::record_escrow_size(
+            "t_receipts",
+            self.db.get_all_escrow_t_receipts().map_or(0, |e| e.count()),
+        );
+        #[cfg(feature = "query")]
+        telemetry::record_escrow_size(
+            "replys",
+            self.db.get_all_escrowed_replys().map_or(0, |e| e.count()),
+        );
+        Ok(())
+    }
+
+    /// Runs `process_escrows` every `interval`, until it fails. Meant
+    /// to be spawned as background task.
+    #[cfg(feature = "async")]
+    pub async fn process_escrows_periodically(
+        &self,
+        interval: std::time::Duration,
+    ) -> Result<(), Error> {
+        loop {
+            async_std::task::sleep(interval).await;
+            self.process_escrows()?;
+        }
+    }
 }
@@ -0,0 +1,85 @@
+//! Parallel Verification
+//!
+//! Bulk import of many KELs spends most of its time checking event
+//! signatures. `process_parallel` checks signatures of events of
+//! different identifiers concurrently, on rayon thread pool, and then
+//! processes messages one by one in the usual way, which doesn't check
+//! again signatures verified against keys which are still current.
+//! Events of the same identifier are verified in order, one after
+//! another, as each of them can change keys for the next one.
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use super::EventProcessor;
+use crate::{
+    database::EventDatabase,
+    error::Error,
+    event::sections::KeyConfig,
+    event_message::signed_event_message::{Message, SignedEventMessage},
+    prefix::Prefix,
+    state::IdentifierState,
+};
+
+impl<D: EventDatabase + Send + Sync> EventProcessor<D> {
+    /// Processes messages as `process` would, one by one in order, with
+    /// event signatures verified in parallel beforehand. Returns result
+    /// of processing of each message.
+    pub fn process_parallel(
+        &self,
+        messages: Vec<Message>,
+    ) -> Vec<Result<Option<IdentifierState>, Error>> {
+        let verified = self.verify_parallel(&messages);
+        messages
+            .into_iter()
+            .enumerate()
+            .map(|(i, message)| self.process_tagged(None, message, verified.get(&i)))
+            .collect()
+    }
+
+    /// Verifies signatures of events of each identifier, starting from
+    /// its current state. Returns keys which signatures of event at
+    /// given position were verified against.
+    fn verify_parallel(&self, messages: &[Message]) -> HashMap<usize, KeyConfig> {
+        let mut kels: HashMap<String, Vec<(usize, &SignedEventMessage)>> = HashMap::new();
+        for (i, message) in messages.iter().enumerate() {
+            if let Message::Event(event) = message {
+                kels.entry(event.event_message.event.get_prefix().to_str())
+                    .or_default()
+                    .push((i, event));
+            }
+        }
+        kels.into_par_iter()
+            .flat_map_iter(|(_, events)| self.verify_kel(events))
+            .collect()
+    }
+
+    /// Events which can't be applied, eg. out of order ones, are left
+    /// for processing to handle.
+    fn verify_kel(&self, events: Vec<(usize, &SignedEventMessage)>) -> Vec<(usize, KeyConfig)> {
+        let mut state = match events.first() {
+            Some((_, event)) => self
+                .compute_state(&event.event_message.event.get_prefix())
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+            None => return vec![],
+        };
+        let mut verified = vec![];
+        for (i, event) in events {
+            let new_state = match state.clone().apply(event) {
+                Ok(new_state) => new_state,
+                Err(_) => continue,
+            };
+            let valid = event
+                .raw_event()
+                .and_then(|raw| state.verify_signatures(&new_state, &raw, &event.signatures));
+            if let Ok(true) = valid {
+                verified.push((i, new_state.current.clone()));
+                state = new_state;
+            }
+        }
+        verified
+    }
+}
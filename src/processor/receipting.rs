@@ -0,0 +1,184 @@
+//! Receipt Generation
+//!
+//! Receipts of accepted events signed with keys of the processor's
+//! owner, either as witness of the event or as transferable validator.
+//! Generated receipts are stored together with received ones, so
+//! they are served and counted the same way.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use super::EventProcessor;
+use crate::{
+    database::EventDatabase,
+    error::Error,
+    event::{sections::seal::EventSeal, EventMessage},
+    event_message::{
+        event_msg_builder::ReceiptBuilder,
+        key_event_message::KeyEvent,
+        signed_event_message::{SignedNontransferableReceipt, SignedTransferableReceipt},
+    },
+    prefix::{AttachedSignaturePrefix, IdentifierPrefix, Prefix},
+    signer::KeyManager,
+};
+
+impl<D: EventDatabase> EventProcessor<D> {
+    /// Witness Receipt
+    ///
+    /// Builds receipt of accepted `event`, signed with current key of
+    /// `key_manager` as nontransferable witness key, and stores it. Key
+    /// has to be one of witnesses designated for the event.
+    pub fn receipt<K: KeyManager + ?Sized>(
+        &self,
+        event: &EventMessage<KeyEvent>,
+        key_manager: &K,
+    ) -> Result<SignedNontransferableReceipt, Error> {
+        let id = event.event.get_prefix();
+        let sn = event.event.get_sn();
+        let raw = self.accepted_raw_event(event)?;
+        let witness = key_manager
+            .key_type()
+            .to_non_transferable()?
+            .derive(key_manager.public_key());
+        let state = self
+            .compute_state_at_sn(&id, sn)?
+            .ok_or(Error::EventOutOfOrderError)?;
+        if !state
+            .witnesses_at_sn(sn)
+            .unwrap_or_default()
+            .contains(&witness)
+        {
+            return Err(Error::UnauthorizedWitness {
+                prefix: id,
+                sn,
+                witness,
+            });
+        }
+        let rct = ReceiptBuilder::default()
+            .with_receipted_event(event.clone())
+            .build()?;
+        let signature = key_manager.signature_type().derive(key_manager.sign(&raw)?);
+        let receipt = SignedNontransferableReceipt::new(&rct, vec![(witness, signature)]);
+        self.db.add_receipt_nt(receipt.clone(), &id)?;
+        Ok(receipt)
+    }
+
+    /// Validator Receipt
+    ///
+    /// Builds receipt of accepted `event` on behalf of transferable
+    /// `validator`, with seal of its last establishment event, signs it
+    /// with current key of `key_manager` and stores it. Key has to be one
+    /// of validator's current keys.
+    pub fn validator_receipt<K: KeyManager + ?Sized>(
+        &self,
+        event: &EventMessage<KeyEvent>,
+        validator: &IdentifierPrefix,
+        key_manager: &K,
+    ) -> Result<SignedTransferableReceipt, Error> {
+        let raw = self.accepted_raw_event(event)?;
+        let state = self
+            .compute_state(validator)?
+            .ok_or_else(|| Error::UnknownIdentifier(validator.clone()))?;
+        let key = key_manager.key_type().derive(key_manager.public_key());
+        let index = state
+            .current
+            .public_keys
+            .iter()
+            .position(|current| current == &key)
+            .ok_or_else(|| Error::SemanticError("Not a current key of validator".into()))?;
+        let validator_seal = self
+            .get_last_establishment_event_seal(validator)?
+            .ok_or_else(|| Error::UnknownIdentifier(validator.clone()))?;
+        let rct = ReceiptBuilder::default()
+            .with_receipted_event(event.clone())
+            .build()?;
+        let signature = AttachedSignaturePrefix::new(
+            key_manager.signature_type(),
+            key_manager.sign(&raw)?,
+            index as u16,
+        );
+        let receipt = SignedTransferableReceipt::new(rct, validator_seal, vec![signature]);
+        self.db
+            .add_receipt_t(receipt.clone(), &event.event.get_prefix())?;
+        Ok(receipt)
+    }
+
+    /// Stale Receipts
+    ///
+    /// Transferable receipts of events of `id` signed with keys of
+    /// establishment events their validators already rotated away from,
+    /// unless validator receipted the event again with current keys.
+    pub fn get_stale_receipts(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Result<Vec<SignedTransferableReceipt>, Error> {
+        let receipts: Vec<_> = self.db.get_receipts_t(id).into_iter().flatten().collect();
+        let mut current_seals: HashMap<String, Option<EventSeal>> = HashMap::new();
+        for receipt in &receipts {
+            let validator = &receipt.validator_seal.prefix;
+            if let Entry::Vacant(entry) = current_seals.entry(validator.to_str()) {
+                entry.insert(self.get_last_establishment_event_seal(validator)?);
+            }
+        }
+        let current_seal = |receipt: &SignedTransferableReceipt| {
+            current_seals[&receipt.validator_seal.prefix.to_str()].as_ref()
+        };
+        let is_current = |receipt: &SignedTransferableReceipt| {
+            current_seal(receipt) == Some(&receipt.validator_seal)
+        };
+        Ok(receipts
+            .iter()
+            .filter(|receipt| current_seal(receipt).is_some() && !is_current(receipt))
+            .filter(|receipt| {
+                !receipts.iter().any(|other| {
+                    other.validator_seal.prefix == receipt.validator_seal.prefix
+                        && other.body.event.sn == receipt.body.event.sn
+                        && is_current(other)
+                })
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Refresh Receipts
+    ///
+    /// Receipts again, with current keys of `validator`, events of `id`
+    /// which its stale receipts refer to, as long as they are still in
+    /// KEL. Returns fresh receipts.
+    pub fn refresh_receipts<K: KeyManager + ?Sized>(
+        &self,
+        id: &IdentifierPrefix,
+        validator: &IdentifierPrefix,
+        key_manager: &K,
+    ) -> Result<Vec<SignedTransferableReceipt>, Error> {
+        let mut refreshed: Vec<SignedTransferableReceipt> = vec![];
+        for stale in self.get_stale_receipts(id)? {
+            let sn = stale.body.event.sn;
+            if &stale.validator_seal.prefix != validator
+                || refreshed.iter().any(|receipt| receipt.body.event.sn == sn)
+            {
+                continue;
+            }
+            if let Some(event) = self.get_event_at_sn(id, sn)? {
+                let event = event.signed_event_message.event_message;
+                refreshed.push(self.validator_receipt(&event, validator, key_manager)?);
+            }
+        }
+        Ok(refreshed)
+    }
+
+    /// Returns event as it was received, if it's the accepted event at
+    /// its sn. Receipts are verified against these bytes.
+    fn accepted_raw_event(&self, event: &EventMessage<KeyEvent>) -> Result<Vec<u8>, Error> {
+        let accepted = self
+            .get_event_at_sn(&event.event.get_prefix(), event.event.get_sn())?
+            .ok_or(Error::EventOutOfOrderError)?
+            .signed_event_message;
+        if accepted.event_message.get_digest() != event.get_digest() {
+            return Err(Error::DigestMismatch {
+                expected: accepted.event_message.get_digest(),
+                got: event.get_digest(),
+            });
+        }
+        accepted.raw_event()
+    }
+}
@@ -0,0 +1,94 @@
+//! Rejected Messages
+//!
+//! Messages dropped by processing are kept together with the reason of
+//! rejection, so operators can audit what was dropped and why, and keep
+//! the evidence, eg. of somebody sending events with forged signatures.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Error, prefix::IdentifierPrefix};
+
+/// Why message was rejected.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RejectionReason {
+    /// Signatures don't verify or don't satisfy the threshold.
+    InvalidSignature,
+    /// Delegated event without valid delegation.
+    InvalidDelegation(String),
+    /// Other version of already accepted event.
+    Duplicitous,
+    /// Event doesn't follow the last accepted one.
+    OutOfOrder,
+    /// Message couldn't be parsed or breaks message limits.
+    Malformed(String),
+    /// Message is well formed, but invalid otherwise.
+    Invalid(String),
+}
+
+impl RejectionReason {
+    /// Reason of rejection because of `error`. Returns None for errors
+    /// which aren't caused by the message itself, eg. storage errors.
+    pub fn from_error(error: &Error) -> Option<Self> {
+        Some(match error {
+            Error::StorageError
+            | Error::MutexPoisoned
+            | Error::Banned(_)
+            | Error::RateLimited(_) => return None,
+            #[cfg(feature = "std")]
+            Error::IoError(_) => return None,
+            #[cfg(feature = "sled-db")]
+            Error::SledError(_) => return None,
+            #[cfg(feature = "rocks-db")]
+            Error::RocksDbError(_) => return None,
+            #[cfg(feature = "postgres-db")]
+            Error::PostgresError(_) => return None,
+            Error::SignatureVerificationError
+            | Error::FaultySignatureVerification
+            | Error::NotEnoughSigsError
+            | Error::Ed25519DalekSignatureError(_) => Self::InvalidSignature,
+            Error::MissingSourceSeal { .. }
+            | Error::MissingDelegatingSeal { .. }
+            | Error::MissingDelegator(_)
+            | Error::DelegationNotAllowed(_)
+            | Error::DelegationCycleError => Self::InvalidDelegation(error.to_string()),
+            Error::EventDuplicateError => Self::Duplicitous,
+            Error::EventOutOfOrderError => Self::OutOfOrder,
+            Error::DeserializeError(_)
+            | Error::UnsupportedVersion { .. }
+            | Error::MessageTooLarge { .. }
+            | Error::NonCanonical(_) => Self::Malformed(error.to_string()),
+            _ => Self::Invalid(error.to_string()),
+        })
+    }
+}
+
+/// Rejected Message
+///
+/// CESR stream of rejected message as received from `source`, with the
+/// reason and time of rejection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RejectedMessage {
+    #[serde(rename = "dt")]
+    pub timestamp: DateTime<Local>,
+
+    #[serde(rename = "src")]
+    pub source: IdentifierPrefix,
+
+    #[serde(rename = "r")]
+    pub reason: RejectionReason,
+
+    #[serde(rename = "m")]
+    pub message: Vec<u8>,
+}
+
+impl RejectedMessage {
+    pub fn new(source: IdentifierPrefix, reason: RejectionReason, message: Vec<u8>) -> Self {
+        Self {
+            timestamp: Local::now(),
+            source,
+            reason,
+            message,
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! State Store
+//!
+//! Cache of identifier states in front of `compute_state`, which
+//! otherwise replays the whole KEL on every call. Verify-heavy
+//! workloads, eg. checking many signatures of the same identifiers,
+//! get their states without touching the database. Least recently used
+//! states are evicted when the store is full.
+//!
+//! Processor keeps cached states up to date with events it accepts and
+//! drops them on duplicity or failed event, so the cache is only safe
+//! when the processor is the only writer of its database.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use crate::{
+    prefix::{IdentifierPrefix, Prefix},
+    state::IdentifierState,
+};
+
+pub struct StateStore {
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+#[derive(Default)]
+struct Lru {
+    tick: u64,
+    // Bumped by every invalidation, so states computed concurrently
+    // with it aren't cached.
+    epoch: u64,
+    states: HashMap<String, (u64, IdentifierState)>,
+    order: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) -> Option<&IdentifierState> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (used, state) = self.states.get_mut(key)?;
+        self.order.remove(used);
+        self.order.insert(tick, key.to_string());
+        *used = tick;
+        Some(state)
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((used, _)) = self.states.remove(key) {
+            self.order.remove(&used);
+        }
+    }
+
+    fn insert(&mut self, state: IdentifierState, capacity: usize) {
+        let key = state.prefix.to_str();
+        self.remove(&key);
+        if self.states.len() >= capacity {
+            if let Some(evicted) = self.order.values().next().cloned() {
+                self.remove(&evicted);
+            }
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.states.insert(key, (self.tick, state));
+    }
+}
+
+impl StateStore {
+    /// Creates store of at most `capacity` states. Store of zero
+    /// capacity caches nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lru: Mutex::new(Lru::default()),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.lru.lock().map_or(0, |lru| lru.states.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, id: &IdentifierPrefix) -> Option<IdentifierState> {
+        self.lru.lock().ok()?.touch(&id.to_str()).cloned()
+    }
+
+    /// Caches current state of identifier, evicting the least recently
+    /// used state if store is full.
+    pub fn insert(&self, state: IdentifierState) {
+        match self.lru.lock() {
+            Ok(mut lru) if self.capacity > 0 => lru.insert(state, self.capacity),
+            _ => (),
+        }
+    }
+
+    /// Drops cached state of identifier, eg. when its KEL changed
+    /// outside of the processor.
+    pub fn invalidate(&self, id: &IdentifierPrefix) {
+        if let Ok(mut lru) = self.lru.lock() {
+            lru.epoch += 1;
+            lru.remove(&id.to_str());
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut lru) = self.lru.lock() {
+            lru.epoch += 1;
+            lru.states.clear();
+            lru.order.clear();
+        }
+    }
+
+    /// Cached states, least recently used first, so restoring them in
+    /// order keeps their recency.
+    pub fn snapshot(&self) -> Vec<IdentifierState> {
+        self.lru.lock().map_or_else(
+            |_| vec![],
+            |lru| {
+                lru.order
+                    .values()
+                    .filter_map(|key| lru.states.get(key))
+                    .map(|(_, state)| state.clone())
+                    .collect()
+            },
+        )
+    }
+
+    pub(crate) fn epoch(&self) -> u64 {
+        self.lru.lock().map_or(0, |lru| lru.epoch)
+    }
+
+    /// Caches state computed from database, unless there was any
+    /// invalidation since `epoch`. Returns whether state was cached.
+    pub(crate) fn insert_computed(&self, state: IdentifierState, epoch: u64) -> bool {
+        match self.lru.lock() {
+            Ok(mut lru) if self.capacity > 0 && lru.epoch == epoch => {
+                lru.insert(state, self.capacity);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(id: &str, sn: u64) -> IdentifierState {
+        IdentifierState {
+            prefix: id.parse().unwrap(),
+            sn,
+            ..IdentifierState::default()
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let ids = [
+            "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA",
+            "DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI",
+            "DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8",
+        ];
+        let prefix = |i: usize| ids[i].parse::<IdentifierPrefix>().unwrap();
+        let store = StateStore::new(2);
+        store.insert(state(ids[0], 0));
+        store.insert(state(ids[1], 0));
+        // Use the first one, so the second is the least recently used.
+        assert_eq!(store.get(&prefix(0)).unwrap().sn, 0);
+        store.insert(state(ids[2], 0));
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&prefix(1)).is_none());
+
+        // Newer state replaces cached one.
+        store.insert(state(ids[0], 1));
+        assert_eq!(store.get(&prefix(0)).unwrap().sn, 1);
+        assert_eq!(
+            store
+                .snapshot()
+                .into_iter()
+                .map(|s| s.prefix)
+                .collect::<Vec<_>>(),
+            vec![prefix(2), prefix(0)]
+        );
+
+        // States computed before invalidation aren't cached.
+        let epoch = store.epoch();
+        store.invalidate(&prefix(2));
+        store.insert_computed(state(ids[2], 0), epoch);
+        assert!(store.get(&prefix(2)).is_none());
+        assert_eq!(store.len(), 1);
+
+        let disabled = StateStore::new(0);
+        disabled.insert(state(ids[0], 0));
+        assert!(disabled.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+//! Processor Telemetry
+//!
+//! Metrics of event processing, reported through `metrics` facade when
+//! `metrics-facade` feature is enabled, so they are collected by any
+//! recorder installed by application, eg. prometheus exporter. Without
+//! the feature all functions are no-ops and call sites stay the same.
+
+use crate::{
+    error::Error, event::event_data::EventData, event_message::signed_event_message::Message,
+};
+
+/// Counter of processed messages, labeled with message `type`.
+pub const MESSAGES_PROCESSED: &str = "keri_messages_processed_total";
+/// Counter of messages rejected because of invalid or missing
+/// signatures, labeled with message `type`.
+pub const VERIFICATION_FAILURES: &str = "keri_verification_failures_total";
+/// Gauge of number of entries in escrow, labeled with `escrow` name.
+pub const ESCROW_SIZE: &str = "keri_escrow_size";
+/// Histogram of time it takes to replay KEL to compute its state, in
+/// seconds.
+pub const KEL_REPLAY_SECONDS: &str = "keri_kel_replay_seconds";
+
+/// Returns type of message used as metrics label.
+pub fn message_type(message: &Message) -> &'static str {
+    match message {
+        Message::Event(event) => match event.event_message.event.get_event_data() {
+            EventData::Icp(_) => "icp",
+            EventData::Rot(_) => "rot",
+            EventData::Ixn(_) => "ixn",
+            EventData::Dip(_) => "dip",
+            EventData::Drt(_) => "drt",
+        },
+        Message::NontransferableRct(_) | Message::TransferableRct(_) | Message::WitnessRct(_) => {
+            "rct"
+        }
+        #[cfg(feature = "query")]
+        Message::KeyStateNotice(_) => "rpy",
+        #[cfg(feature = "query")]
+        Message::Query(_) => "qry",
+        #[cfg(feature = "query")]
+        Message::Exchange(_) => "exn",
+    }
+}
+
+/// Records result of processing message of given type.
+pub(crate) fn record_processed<T>(message_type: &'static str, result: &Result<T, Error>) {
+    match result {
+        Ok(_) => {
+            tracing::debug!(message_type, "message processed");
+            #[cfg(feature = "metrics-facade")]
+            metrics::increment_counter!(MESSAGES_PROCESSED, "type" => message_type);
+        }
+        Err(e @ Error::SignatureVerificationError) | Err(e @ Error::NotEnoughSigsError) => {
+            tracing::warn!(message_type, error = %e, "message verification failed");
+            #[cfg(feature = "metrics-facade")]
+            metrics::increment_counter!(VERIFICATION_FAILURES, "type" => message_type);
+        }
+        Err(e) => tracing::debug!(message_type, error = %e, "message not processed"),
+    }
+}
+
+/// Records number of entries in escrow.
+pub(crate) fn record_escrow_size(escrow: &'static str, size: usize) {
+    tracing::debug!(escrow, size, "escrow size");
+    #[cfg(feature = "metrics-facade")]
+    metrics::gauge!(ESCROW_SIZE, size as f64, "escrow" => escrow);
+}
+
+/// Runs KEL replay `f`, recording how long it took.
+pub(crate) fn time_kel_replay<T>(f: impl FnOnce() -> T) -> T {
+    // Time is measured only if it's reported, as `Instant` isn't
+    // available on every target.
+    #[cfg(feature = "metrics-facade")]
+    {
+        let start = std::time::Instant::now();
+        let result = f();
+        metrics::histogram!(KEL_REPLAY_SECONDS, start.elapsed());
+        result
+    }
+    #[cfg(not(feature = "metrics-facade"))]
+    f()
+}
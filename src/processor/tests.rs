@@ -1,7 +1,6 @@
 use super::EventProcessor;
 use crate::event::sections::seal::EventSeal;
 use crate::event_message::signed_event_message::Message;
-use crate::event_message::Digestible;
 use crate::event_parsing::message::{signed_event_stream, signed_message};
 use crate::prefix::IdentifierPrefix;
 use crate::{database::sled::SledEventDatabase, error::Error};
@@ -168,6 +167,515 @@ fn test_process_receipt() -> Result<(), Error> {
 
     Ok(())
 }
+#[test]
+fn test_process_witness_receipt() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::{
+            event_msg_builder::{EventMsgBuilder, ReceiptBuilder},
+            signed_event_message::{SignedNontransferableReceipt, SignedWitnessReceipt},
+            EventTypeTag,
+        },
+        event_parsing::{Attachment, SignedEventData},
+        prefix::{AttachedSignaturePrefix, SelfSigningPrefix},
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+    // Create test db and event processor.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    fs::create_dir_all(root.path()).unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let controller_km = CryptoBox::new()?;
+    let witness_km = CryptoBox::new()?;
+    let other_km = CryptoBox::new()?;
+    let witness = Basic::Ed25519.derive(witness_km.public_key());
+
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(controller_km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(controller_km.next_public_key())])
+        .with_witnesses(std::slice::from_ref(&witness))
+        .with_witness_threshold(1)
+        .build()?;
+    let signature = controller_km.sign(&icp.serialize()?)?;
+    event_processor.process(Message::Event(icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+        None,
+    )))?;
+    let state = event_processor
+        .compute_state(&icp.event.get_prefix())?
+        .unwrap();
+    assert_eq!(state.witnesses_at_sn(0), Some(&[witness.clone()][..]));
+
+    let rct = ReceiptBuilder::default()
+        .with_receipted_event(icp.clone())
+        .build()?;
+    let make_receipt = |km: &CryptoBox| -> Result<Message, Error> {
+        let signature = km.sign(&icp.serialize()?)?;
+        Ok(Message::NontransferableRct(
+            SignedNontransferableReceipt::new(
+                &rct,
+                vec![(
+                    Basic::Ed25519.derive(km.public_key()),
+                    SelfSigningPrefix::new(SelfSigning::Ed25519Sha512, signature),
+                )],
+            ),
+        ))
+    };
+
+    let id = icp.event.get_prefix();
+
+    // Receipt from identifier which is not a witness is stored, but
+    // doesn't count towards witness threshold.
+    event_processor.process(make_receipt(&other_km)?)?;
+    assert_eq!(db.get_receipts_nt(&id).unwrap().count(), 1);
+    assert!(!event_processor.is_witnessed(&id, 0)?);
+
+    // Receipt from designated witness is accepted.
+    event_processor.process(make_receipt(&witness_km)?)?;
+    assert_eq!(db.get_receipts_nt(&id).unwrap().count(), 2);
+    assert!(event_processor.is_witnessed(&id, 0)?);
+
+    // Witness signature indexed into witness list of event is resolved
+    // to the witness.
+    let make_indexed_receipt = |index: u16| -> Result<Vec<u8>, Error> {
+        let signature = witness_km.sign(&icp.serialize()?)?;
+        SignedEventData::from(SignedWitnessReceipt::new(
+            &rct,
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                index,
+            )],
+        ))
+        .to_cesr()
+    };
+    let parse = |stream: &[u8]| Message::try_from(signed_message(stream).unwrap().1);
+    let result = event_processor.process(parse(&make_indexed_receipt(1)?)?);
+    assert!(matches!(result, Err(Error::SemanticError(_))));
+    let indexed_receipt = parse(&make_indexed_receipt(0)?)?;
+    assert!(matches!(indexed_receipt, Message::WitnessRct(_)));
+    event_processor.process(indexed_receipt)?;
+    assert_eq!(db.get_receipts_nt(&id).unwrap().count(), 3);
+
+    // Two of receipts are from the same witness.
+    assert_eq!(event_processor.get_nt_receipts_for_event(&id, 0)?.len(), 3);
+    assert!(event_processor
+        .get_nt_receipts_for_event(&id, 1)?
+        .is_empty());
+    assert_eq!(event_processor.get_receipt_count(&id, 0)?, 2);
+    assert_eq!(
+        db.get_events_receipted_by(&witness),
+        vec![EventSeal {
+            prefix: id.clone(),
+            sn: 0,
+            event_digest: rct.event.receipted_event_digest.clone(),
+        }]
+    );
+
+    // Exported KEL has receipts interleaved with events.
+    let kel = event_processor
+        .get_kel_with_receipts(&icp.event.get_prefix())?
+        .unwrap();
+    let messages = signed_event_stream(&kel).unwrap().1;
+    assert_eq!(messages.len(), 4);
+    assert!(matches!(
+        Message::try_from(messages[0].clone())?,
+        Message::Event(_)
+    ));
+    assert!(messages[1..].iter().all(|msg| matches!(
+        Message::try_from(msg.clone()),
+        Ok(Message::NontransferableRct(_))
+    )));
+
+    // Replay carries first seen couple of each event.
+    let replay = event_processor
+        .get_kel_replay(&icp.event.get_prefix())?
+        .unwrap();
+    let messages = signed_event_stream(&replay).unwrap().1;
+    assert!(messages[0].attachments.iter().any(
+        |att| matches!(att, Attachment::FirstSeenReplayCouplets(couplets) if couplets[0].0 == 0)
+    ));
+
+    // Other node accepts both events and receipts from the export.
+    let other_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let other_db = Arc::new(SledEventDatabase::new(other_root.path()).unwrap());
+    let other_processor = EventProcessor::new(Arc::clone(&other_db));
+    for msg in signed_event_stream(&kel).unwrap().1 {
+        other_processor.process(Message::try_from(msg)?)?;
+    }
+    assert_eq!(
+        other_db
+            .get_receipts_nt(&icp.event.get_prefix())
+            .unwrap()
+            .count(),
+        3
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_process_escrows() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::{
+            event_msg_builder::{EventMsgBuilder, ReceiptBuilder},
+            signed_event_message::SignedNontransferableReceipt,
+            EventTypeTag,
+        },
+        prefix::{AttachedSignaturePrefix, SelfSigningPrefix},
+        processor::config::ProcessorConfig,
+        signer::{CryptoBox, KeyManager},
+    };
+    use std::time::Duration;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let witness_km = CryptoBox::new()?;
+    let witness = Basic::Ed25519.derive(witness_km.public_key());
+    let make_icp = |km: &CryptoBox| {
+        EventMsgBuilder::new(EventTypeTag::Icp)
+            .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+            .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+            .with_witnesses(std::slice::from_ref(&witness))
+            .build()
+    };
+    let make_receipt = |icp: &crate::event::EventMessage<_>| -> Result<Message, Error> {
+        let rct = ReceiptBuilder::default()
+            .with_receipted_event(icp.clone())
+            .build()?;
+        let signature = witness_km.sign(&icp.serialize()?)?;
+        Ok(Message::NontransferableRct(
+            SignedNontransferableReceipt::new(
+                &rct,
+                vec![(
+                    witness.clone(),
+                    SelfSigningPrefix::new(SelfSigning::Ed25519Sha512, signature),
+                )],
+            ),
+        ))
+    };
+
+    let controller_km = CryptoBox::new()?;
+    let icp = make_icp(&controller_km)?;
+    let id = icp.event.get_prefix();
+    // Event of this identifier never arrives.
+    let other_icp = make_icp(&CryptoBox::new()?)?;
+    let other_id = other_icp.event.get_prefix();
+
+    // Receipts of unknown events are escrowed.
+    event_processor.process(make_receipt(&icp)?)?;
+    event_processor.process(make_receipt(&other_icp)?)?;
+    event_processor.process_escrows()?;
+    assert_eq!(db.get_escrow_nt_receipts(&id).unwrap().count(), 1);
+    assert_eq!(db.get_escrow_nt_receipts(&other_id).unwrap().count(), 1);
+
+    // Escrowed receipt is accepted after receipted event.
+    let signature = controller_km.sign(&icp.serialize()?)?;
+    event_processor.process(Message::Event(icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+        None,
+    )))?;
+    event_processor.process_escrows()?;
+    assert_eq!(db.get_escrow_nt_receipts(&id).unwrap().count(), 0);
+    assert_eq!(db.get_receipts_nt(&id).unwrap().count(), 1);
+    assert_eq!(db.get_escrow_nt_receipts(&other_id).unwrap().count(), 1);
+
+    // Receipt kept in escrow for longer than timeout is purged.
+    let event_processor = EventProcessor::new(Arc::clone(&db))
+        .with_config(ProcessorConfig::default().with_escrow_timeout(Duration::from_secs(0)));
+    event_processor.process_escrows()?;
+    assert_eq!(db.get_escrow_nt_receipts(&other_id).unwrap().count(), 0);
+    assert!(db.get_receipts_nt(&other_id).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_first_seen() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
+        event::sections::seal::{DigestSeal, Seal},
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+    // Create test db and event processor.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    fs::create_dir_all(root.path()).unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let km = CryptoBox::new()?;
+    let sign = |event: &crate::event::EventMessage<_>| -> Result<Message, Error> {
+        let signature = km.sign(&event.serialize()?)?;
+        Ok(Message::Event(event.sign(
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+            None,
+        )))
+    };
+
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let id = icp.event.get_prefix();
+    event_processor.process(sign(&icp)?)?;
+
+    let make_ixn = |payload: &[u8]| {
+        EventMsgBuilder::new(EventTypeTag::Ixn)
+            .with_prefix(&id)
+            .with_sn(1)
+            .with_previous_event(&icp.get_digest())
+            .with_seal(vec![Seal::Digest(DigestSeal {
+                dig: SelfAddressing::Blake3_256.derive(payload),
+            })])
+            .build()
+    };
+    let ixn = make_ixn(b"first")?;
+    event_processor.process(sign(&ixn)?)?;
+
+    let first_seen = event_processor.get_first_seen(&id, 0)?.unwrap();
+    assert_eq!(first_seen.ordinal, 0);
+    assert_eq!(first_seen.digest, icp.get_digest());
+    let first_seen = event_processor.get_first_seen(&id, 1)?.unwrap();
+    assert_eq!(first_seen.ordinal, 1);
+    assert_eq!(first_seen.digest, ixn.get_digest());
+    assert!(event_processor.get_first_seen(&id, 2)?.is_none());
+
+    // Conflicting version of already accepted event is rejected.
+    let conflicting_ixn = make_ixn(b"second")?;
+    let result = event_processor.process(sign(&conflicting_ixn)?);
+    assert!(matches!(result, Err(Error::EventDuplicateError)));
+    assert_eq!(db.get_duplicious_events(&id).unwrap().count(), 1);
+    assert_eq!(
+        event_processor
+            .get_event_at_sn(&id, 1)?
+            .unwrap()
+            .signed_event_message
+            .event_message,
+        ixn
+    );
+    assert_eq!(db.get_first_seen_records(&id).unwrap().count(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_conflicting_events() -> Result<(), Error> {
+    use crate::{
+        event::sections::seal::{DigestSeal, Seal},
+        event_message::{
+            signed_event_message::SignedEventMessage, test_fixture::TestIdentityFixture,
+        },
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+    let seal = |dig: &str| -> Result<Vec<Seal>, Error> {
+        Ok(vec![Seal::Digest(DigestSeal { dig: dig.parse()? })])
+    };
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.interact(seal("EZAoTNZH3ULvaU6Z-i0d8JJR2nmwyYAfSVPzhzS6b5CM")?)?;
+    alice.interact(vec![])?;
+    for event in alice.events() {
+        event_processor.process(Message::Event(event.clone()))?;
+    }
+    let id = alice.prefix().clone();
+
+    // Forged version of accepted event isn't evidence of duplicity.
+    let mut other = TestIdentityFixture::new(b"alice")?;
+    let conflicting = other.interact(seal("E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw")?)?;
+    let mallory = TestIdentityFixture::new(b"mallory")?;
+    let forged = SignedEventMessage::new(
+        &conflicting.event_message,
+        vec![mallory.sign(&conflicting.event_message.serialize()?)?],
+        None,
+    );
+    assert!(matches!(
+        event_processor.process(Message::Event(forged)),
+        Err(Error::SignatureVerificationError)
+    ));
+    assert!(db.get_duplicious_events(&id).is_none());
+
+    // Signed one is.
+    assert!(matches!(
+        event_processor.process(Message::Event(conflicting)),
+        Err(Error::EventDuplicateError)
+    ));
+    assert_eq!(db.get_duplicious_events(&id).unwrap().count(), 1);
+
+    // Rotation superseding interaction events recovers the KEL.
+    let mut recovered = TestIdentityFixture::new(b"alice")?;
+    let rot = recovered.rotate()?;
+    let state = event_processor
+        .process(Message::Event(rot.clone()))?
+        .unwrap();
+    assert_eq!(state.sn, 1);
+    assert_eq!(state.last_event_digest, rot.event_message.get_digest());
+    assert_eq!(event_processor.compute_state(&id)?, Some(state));
+    assert!(event_processor.get_event_at_sn(&id, 2)?.is_none());
+    // First seen record of superseded event is kept.
+    assert_eq!(
+        event_processor.get_first_seen(&id, 1)?.unwrap().digest,
+        alice.events()[1].event_message.get_digest()
+    );
+    assert_eq!(db.get_duplicious_events(&id).unwrap().count(), 1);
+
+    // KEL goes on from the rotation.
+    let ixn = recovered.interact(vec![])?;
+    assert_eq!(event_processor.process(Message::Event(ixn))?.unwrap().sn, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_consistency_proof() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event::EventMessage,
+        event_message::{
+            event_msg_builder::EventMsgBuilder, key_event_message::KeyEvent, EventTypeTag,
+        },
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+    // Create test db and event processor.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    fs::create_dir_all(root.path()).unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let mut km = CryptoBox::new()?;
+    let sign = |km: &CryptoBox, event: &EventMessage<KeyEvent>| -> Result<Message, Error> {
+        let signature = km.sign(&event.serialize()?)?;
+        Ok(Message::Event(event.sign(
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+            None,
+        )))
+    };
+
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let id = icp.event.get_prefix();
+    event_processor.process(sign(&km, &icp)?)?;
+
+    let ixn = EventMsgBuilder::new(EventTypeTag::Ixn)
+        .with_prefix(&id)
+        .with_sn(1)
+        .with_previous_event(&icp.get_digest())
+        .build()?;
+    event_processor.process(sign(&km, &ixn)?)?;
+
+    km.rotate()?;
+    let rot = EventMsgBuilder::new(EventTypeTag::Rot)
+        .with_prefix(&id)
+        .with_sn(2)
+        .with_previous_event(&ixn.get_digest())
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    event_processor.process(sign(&km, &rot)?)?;
+
+    let earlier_state = event_processor.compute_state_at_sn(&id, 1)?.unwrap();
+    let proof = event_processor.consistency_proof(&id, 1, 2)?.unwrap();
+    assert_eq!(proof.to_sn(), 2);
+    assert_eq!(proof.to_digest(), rot.get_digest());
+    assert_eq!(
+        proof.verify(earlier_state.clone())?,
+        event_processor.compute_state(&id)?.unwrap()
+    );
+
+    // Proof doesn't start at the given state.
+    let initial_state = event_processor.compute_state_at_sn(&id, 0)?.unwrap();
+    assert!(proof.verify(initial_state.clone()).is_err());
+    let proof = event_processor.consistency_proof(&id, 0, 2)?.unwrap();
+    assert!(proof.verify(initial_state.clone()).is_ok());
+
+    // Proof with missing link is rejected.
+    let mut broken_proof = proof.clone();
+    broken_proof.events.remove(0);
+    assert!(matches!(
+        broken_proof.verify(initial_state),
+        Err(Error::EventOutOfOrderError)
+    ));
+
+    // Range beyond the KEL.
+    assert!(event_processor.consistency_proof(&id, 1, 3).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_subscriptions() -> Result<(), Error> {
+    use tempfile::Builder;
+    // Create test db and event processor.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    fs::create_dir_all(root.path()).unwrap();
+
+    let icp_raw = br#"{"v":"KERI10JSON000120_","t":"icp","d":"EsZuhYAPBDnexP3SOl9YsGvWBrYkjYcRjomUYmCcLAYY","i":"EsZuhYAPBDnexP3SOl9YsGvWBrYkjYcRjomUYmCcLAYY","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAAWKO9bl3OhABTaevxYiXQ1poRIGfM9ndMPq4bvrKmU_3pTN3VLNDYOI8pJBeAQxRtajQn4CSWOqgdGnmeG6fBCQ"#;
+    let id: IdentifierPrefix = "EsZuhYAPBDnexP3SOl9YsGvWBrYkjYcRjomUYmCcLAYY".parse()?;
+    let source: IdentifierPrefix = "BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo".parse()?;
+
+    {
+        let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+        let event_processor = EventProcessor::new(Arc::clone(&db));
+
+        let subscription = event_processor.subscribe(&id, &source)?;
+        assert_eq!(subscription.next_sn(), 0);
+
+        let parsed = signed_message(icp_raw).unwrap().1;
+        event_processor.process(Message::try_from(parsed).unwrap())?;
+        let subscription = event_processor.update_subscription_cursor(&id, &source)?;
+        assert_eq!(subscription.last_sn, Some(0));
+        assert_eq!(subscription.next_sn(), 1);
+    }
+
+    // Reopen database, subscription should be resumed from the saved cursor.
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+    let subscription = event_processor.subscribe(&id, &source)?;
+    assert_eq!(subscription.last_sn, Some(0));
+    assert_eq!(
+        subscription.last_digest,
+        Some("EsZuhYAPBDnexP3SOl9YsGvWBrYkjYcRjomUYmCcLAYY".parse()?)
+    );
+    assert_eq!(db.get_all_subscriptions().unwrap().count(), 1);
+
+    db.remove_subscription(&id, &source)?;
+    assert!(db.get_subscription(&id, &source).is_none());
+
+    Ok(())
+}
+
 #[test]
 fn test_process_delegated() -> Result<(), Error> {
     use tempfile::Builder;
@@ -300,12 +808,11 @@ fn test_validate_seal() -> Result<(), Error> {
     let parsed = signed_message(dip_raw).unwrap().1;
     let msg = Message::try_from(parsed).unwrap();
     if let Message::Event(dip) = msg {
-        let delegated_event_digest = dip.event_message.event.get_digest();
         // Construct delegating seal.
         let seal = EventSeal {
             prefix: delegator_id,
             sn: 1,
-            event_digest: delegated_event_digest,
+            event_digest: "E1_-icBrwC_HhxyFwsQLV6hZEbApOc_McGUjhLONpQuc".parse()?,
         };
 
         // Try to validate seal before processing delegating event
@@ -329,6 +836,260 @@ fn test_validate_seal() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_raw_event_bytes() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        event_parsing::Attachment,
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let km = CryptoBox::new()?;
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let id = icp.event.get_prefix();
+
+    // Received event differs from its serialization, but is signed
+    // as it is. Its version string declares size of received bytes.
+    let serialized = String::from_utf8(icp.serialize()?).unwrap();
+    let spaced = serialized.replacen(",", ", ", 1);
+    let raw = spaced
+        .replacen(
+            &format!("{:06x}_", serialized.len()),
+            &format!("{:06x}_", spaced.len()),
+            1,
+        )
+        .into_bytes();
+    assert_ne!(raw, icp.serialize()?);
+    let signatures = vec![AttachedSignaturePrefix::new(
+        SelfSigning::Ed25519Sha512,
+        km.sign(&raw)?,
+        0,
+    )];
+    let stream = [
+        raw.clone(),
+        Attachment::AttachedSignatures(signatures)
+            .to_cesr()?
+            .into_bytes(),
+    ]
+    .concat();
+
+    let parsed = signed_message(&stream).unwrap().1;
+    let message = Message::try_from(parsed)?;
+    if let Message::Event(event) = &message {
+        assert_eq!(event.raw_event()?, raw);
+    }
+    event_processor.process(message)?;
+    assert_eq!(event_processor.compute_state(&id)?.unwrap().sn, 0);
+
+    // Event is replayed byte by byte.
+    assert_eq!(event_processor.get_kerl(&id)?.unwrap(), stream);
+
+    Ok(())
+}
+
+#[test]
+fn test_get_event_by_digest() -> Result<(), Error> {
+    use crate::prefix::SelfAddressingPrefix;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    // Taken from keripy/tests/core/test_kevery.py::test kevery
+    let kerl_str= br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw{"v":"KERI10JSON000155_","t":"rot","d":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"1","p":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","kt":"1","k":["DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI"],"n":"E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI","bt":"0","br":[],"ba":[],"a":[]}-AABAAwoiqt07w2UInzzo2DmtwkBfqX1-tTO4cYk_7YdlbJ95qA7PO5sEUkER8fZySQMNCVh64ruAh1yoew3TikwVGAQ"#;
+    let (_rest, messages) = signed_event_stream(kerl_str).unwrap();
+    for msg in messages {
+        event_processor.process(Message::try_from(msg)?)?;
+    }
+
+    let id: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+    let rot_digest: SelfAddressingPrefix =
+        "Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA".parse()?;
+    let rot = event_processor.get_event_by_digest(&rot_digest)?.unwrap();
+    let rot = rot.signed_event_message.event_message;
+    assert_eq!(rot.event.get_prefix(), id);
+    assert_eq!(rot.event.get_sn(), 1);
+    assert_eq!(rot.get_digest(), rot_digest);
+
+    let unknown_digest: SelfAddressingPrefix =
+        "E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI".parse()?;
+    assert!(event_processor
+        .get_event_by_digest(&unknown_digest)?
+        .is_none());
+
+    // Index is updated when event is removed from KEL.
+    let icp_digest: SelfAddressingPrefix =
+        "EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI".parse()?;
+    let icp = event_processor.get_event_by_digest(&icp_digest)?.unwrap();
+    db.remove_kel_finalized_event(&id, &icp.signed_event_message)?;
+    assert!(event_processor.get_event_by_digest(&icp_digest)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_delegation_chain() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event::sections::seal::{Seal, SourceSeal},
+        event::EventMessage,
+        event_message::{
+            event_msg_builder::EventMsgBuilder, key_event_message::KeyEvent, EventTypeTag,
+        },
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+    // Create test db and event processor.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    fs::create_dir_all(root.path()).unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let sign = |km: &CryptoBox,
+                event: &EventMessage<KeyEvent>,
+                source_seal: Option<SourceSeal>|
+     -> Result<Message, Error> {
+        let signature = km.sign(&event.serialize()?)?;
+        Ok(Message::Event(event.sign(
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+            source_seal,
+        )))
+    };
+    let incept_delegated =
+        |km: &CryptoBox, delegator: &IdentifierPrefix| -> Result<EventMessage<KeyEvent>, Error> {
+            EventMsgBuilder::new(EventTypeTag::Dip)
+                .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+                .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+                .with_delegator(delegator)
+                .build()
+        };
+    let anchor = |delegator: &EventMessage<KeyEvent>,
+                  delegated: &EventMessage<KeyEvent>,
+                  sn: u64|
+     -> Result<EventMessage<KeyEvent>, Error> {
+        EventMsgBuilder::new(EventTypeTag::Ixn)
+            .with_prefix(&delegator.event.get_prefix())
+            .with_sn(sn)
+            .with_previous_event(&delegator.get_digest())
+            .with_seal(vec![Seal::Event(EventSeal {
+                prefix: delegated.event.get_prefix(),
+                sn: 0,
+                event_digest: delegated.get_digest(),
+            })])
+            .build()
+    };
+
+    // Root identifier, not delegated.
+    let root_km = CryptoBox::new()?;
+    let root_icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(root_km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(root_km.next_public_key())])
+        .build()?;
+    let root_id = root_icp.event.get_prefix();
+    event_processor.process(sign(&root_km, &root_icp, None)?)?;
+    assert!(event_processor.get_delegation_chain(&root_id)?.is_empty());
+
+    // First level delegate, anchored in root's ixn.
+    let first_km = CryptoBox::new()?;
+    let first_dip = incept_delegated(&first_km, &root_id)?;
+    let first_id = first_dip.event.get_prefix();
+    let root_ixn = anchor(&root_icp, &first_dip, 1)?;
+    event_processor.process(sign(&root_km, &root_ixn, None)?)?;
+    event_processor.process(sign(
+        &first_km,
+        &first_dip,
+        Some(SourceSeal::new(1, root_ixn.get_digest())),
+    )?)?;
+    assert_eq!(
+        event_processor.get_delegation_chain(&first_id)?,
+        vec![root_id.clone()]
+    );
+
+    // Second level delegate, anchored in first delegate's ixn.
+    let second_km = CryptoBox::new()?;
+    let second_dip = incept_delegated(&second_km, &first_id)?;
+    let second_id = second_dip.event.get_prefix();
+    let first_ixn = anchor(&first_dip, &second_dip, 1)?;
+    event_processor.process(sign(&first_km, &first_ixn, None)?)?;
+
+    // Source seal pointing to wrong delegator's event is rejected.
+    let result = event_processor.process(sign(
+        &second_km,
+        &second_dip,
+        Some(SourceSeal::new(0, first_dip.get_digest())),
+    )?);
+    assert!(matches!(
+        result,
+        Err(Error::MissingDelegatingSeal { ref delegator, sn: 0 }) if delegator == &first_id
+    ));
+
+    event_processor.process(sign(
+        &second_km,
+        &second_dip,
+        Some(SourceSeal::new(1, first_ixn.get_digest())),
+    )?)?;
+    assert_eq!(
+        event_processor.get_delegation_chain(&second_id)?,
+        vec![first_id, root_id]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_delegation_chain_upper_seal() -> Result<(), Error> {
+    use crate::event_message::test_fixture::TestIdentityFixture;
+    use chrono::Local;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    // Events of first level delegate are written straight to the
+    // database, but root's event anchoring it isn't accepted.
+    let mut root = TestIdentityFixture::new(b"root")?;
+    let mut first = TestIdentityFixture::delegated(b"first", &mut root)?;
+    event_processor.process(Message::Event(root.events()[0].clone()))?;
+    db.add_kel_finalized_event(first.events()[0].clone(), first.prefix(), Local::now())?;
+
+    // Second level delegate is anchored by the first one, but delegation
+    // chain is broken above it.
+    let second = TestIdentityFixture::delegated(b"second", &mut first)?;
+    db.add_kel_finalized_event(first.events()[1].clone(), first.prefix(), Local::now())?;
+    assert!(matches!(
+        event_processor.process(Message::Event(second.events()[0].clone())),
+        Err(Error::EventOutOfOrderError)
+    ));
+    assert!(event_processor.compute_state(second.prefix())?.is_none());
+
+    // Once root's anchoring event is accepted, the chain verifies.
+    event_processor.process(Message::Event(root.events()[1].clone()))?;
+    event_processor.process(Message::Event(second.events()[0].clone()))?;
+    assert_eq!(
+        event_processor.get_delegation_chain(second.prefix())?,
+        vec![first.prefix().clone(), root.prefix().clone()]
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_compute_state_at_sn() -> Result<(), Error> {
     use crate::event::sections::seal::EventSeal;
@@ -488,3 +1249,1868 @@ pub fn test_query() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(feature = "query")]
+#[test]
+pub fn test_process_query() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
+        event::SerializationFormats,
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        prefix::AttachedSignaturePrefix,
+        query::{
+            query_event::{QueryEvent, SignedQuery},
+            ReplyPayload, Route,
+        },
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let km = CryptoBox::new()?;
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let id = icp.event.get_prefix();
+    let signature = km.sign(&icp.serialize()?)?;
+    event_processor.process(Message::Event(icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+        None,
+    )))?;
+
+    let make_query = |route: Route| -> Result<SignedQuery, Error> {
+        let qry = QueryEvent::new_query(
+            route,
+            &id,
+            SerializationFormats::JSON,
+            &SelfAddressing::Blake3_256,
+        )?;
+        let signature = km.sign(&qry.serialize()?)?;
+        Ok(SignedQuery::new(
+            qry,
+            id.clone(),
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+        ))
+    };
+
+    let reply = event_processor.process_query(&make_query(Route::Ksn)?)?;
+    assert!(
+        matches!(reply, ReplyPayload::Ksn(ksn) if ksn.state == event_processor.compute_state(&id)?.unwrap())
+    );
+
+    let reply = event_processor.process_query(&make_query(Route::Log)?)?;
+    assert_eq!(
+        reply,
+        ReplyPayload::Kel(event_processor.get_kerl(&id)?.unwrap())
+    );
+
+    let reply = event_processor.process_query(&make_query(Route::Mbx)?)?;
+    assert_eq!(reply, ReplyPayload::Mbx(vec![]));
+
+    // Query with signature which doesn't match query data.
+    let mut qry = make_query(Route::Ksn)?;
+    qry.signatures = vec![AttachedSignaturePrefix::new(
+        SelfSigning::Ed25519Sha512,
+        km.sign(b"other data")?,
+        0,
+    )];
+    assert!(matches!(
+        event_processor.process_query(&qry),
+        Err(Error::SignatureVerificationError)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "query")]
+#[test]
+pub fn test_exchange_dispatch() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
+        event::{EventMessage, SerializationFormats},
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        event_parsing::SignedEventData,
+        prefix::AttachedSignaturePrefix,
+        query::exchange::{ExchangeEvent, ExchangeHandler, ExchangeRouter, SignedExchange},
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    // Handler which responds with the same words on different route.
+    struct ChallengeHandler;
+    impl ExchangeHandler for ChallengeHandler {
+        fn handle(
+            &self,
+            exn: &SignedExchange,
+        ) -> Result<Option<EventMessage<ExchangeEvent>>, Error> {
+            Ok(Some(ExchangeEvent::new_exchange(
+                "/challenge/response",
+                exn.exchange.event.get_data(),
+                SerializationFormats::JSON,
+                &SelfAddressing::Blake3_256,
+            )?))
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let km = CryptoBox::new()?;
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let id = icp.event.get_prefix();
+    let signature = km.sign(&icp.serialize()?)?;
+    event_processor.process(Message::Event(icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+        None,
+    )))?;
+
+    let exn = ExchangeEvent::new_exchange(
+        "/challenge",
+        serde_json::json!({"words": ["able", "baker", "charlie"]}),
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?;
+    let signature = km.sign(&exn.serialize()?)?;
+    let signed_exn = SignedExchange::new(
+        exn,
+        id.clone(),
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+    );
+
+    // Check if signed exn survives serialization and parsing.
+    let stream = SignedEventData::from(signed_exn.clone()).to_cesr()?;
+    let parsed = Message::try_from(signed_message(&stream).unwrap().1)?;
+    assert_eq!(parsed, Message::Exchange(signed_exn.clone()));
+    assert!(event_processor.process(parsed).is_err());
+
+    let mut router = ExchangeRouter::default();
+    // No handler registered for the route yet.
+    assert!(router.dispatch(&event_processor, &signed_exn).is_err());
+
+    router.register("/challenge", Box::new(ChallengeHandler));
+    let response = router.dispatch(&event_processor, &signed_exn)?.unwrap();
+    assert_eq!(response.event.get_route(), "/challenge/response");
+    assert_eq!(
+        response.event.get_data(),
+        signed_exn.exchange.event.get_data()
+    );
+
+    // Exn signed with wrong key is rejected before reaching handler.
+    let forged_exn = SignedExchange {
+        signatures: vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            km.sign(b"other data")?,
+            0,
+        )],
+        ..signed_exn
+    };
+    assert!(matches!(
+        router.dispatch(&event_processor, &forged_exn),
+        Err(Error::SignatureVerificationError)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_event_graph() -> Result<(), Error> {
+    use crate::processor::graph::{EdgeKind, GraphEdge};
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    // Events and sigs are from keripy `test_delegation` test.
+    // (keripy/tests/core/test_delegating.py)
+    let kels = br#"{"v":"KERI10JSON000120_","t":"icp","d":"Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8","i":"Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8","s":"0","kt":"1","k":["DqI2cOZ06RwGNwCovYUWExmdKU983IasmUKMmZflvWdQ"],"n":"E7FuL3Z_KBgt_QAwuZi1lUFNC69wvyHSxnMFUsKjZHss","bt":"0","b":[],"c":[],"a":[]}-AABAAJEloPu7b4z8v1455StEJ1b7dMIz-P0tKJ_GBBCxQA8JEg0gm8qbS4TWGiHikLoZ2GtLA58l9dzIa2x_otJhoDA{"v":"KERI10JSON00013a_","t":"ixn","d":"E1_-icBrwC_HhxyFwsQLV6hZEbApOc_McGUjhLONpQuc","i":"Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8","s":"1","p":"Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8","a":[{"i":"Er4bHXd4piEtsQat1mquwsNZXItvuoj_auCUyICmwyXI","s":"0","d":"Er4bHXd4piEtsQat1mquwsNZXItvuoj_auCUyICmwyXI"}]}-AABAA6h5mD5stIwO_rwV9apMuhHXjxrKp2ATa35u-H6DM2X-BKo5NkJ1khzBdHo-VLQ6Zw_yajj2Ul_WOL8pFSk_ZDg{"v":"KERI10JSON000154_","t":"dip","d":"Er4bHXd4piEtsQat1mquwsNZXItvuoj_auCUyICmwyXI","i":"Er4bHXd4piEtsQat1mquwsNZXItvuoj_auCUyICmwyXI","s":"0","kt":"1","k":["DuK1x8ydpucu3480Jpd1XBfjnCwb3dZ3x5b1CJmuUphA"],"n":"EWWkjZkZDXF74O2bOQ4H5hu4nXDlKg2m4CBEBkUxibiU","bt":"0","b":[],"c":[],"a":[],"di":"Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8"}-AABAA_zcT2-86Zll3FG-hwoQiVuFiT0X28Ft0t4fZGNFISgtZjH2DCrBGoceko604NDZ0QF0Z3bSgEkN_y0lBafD_Bw-GAB0AAAAAAAAAAAAAAAAAAAAAAQE1_-icBrwC_HhxyFwsQLV6hZEbApOc_McGUjhLONpQuc"#;
+    let (_rest, messages) = signed_event_stream(kels).unwrap();
+    for msg in messages {
+        event_processor.process(Message::try_from(msg)?)?;
+    }
+
+    let bobs_pref: IdentifierPrefix = "Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8".parse()?;
+    let child_prefix: IdentifierPrefix = "Er4bHXd4piEtsQat1mquwsNZXItvuoj_auCUyICmwyXI".parse()?;
+    let graph = event_processor.event_graph(&[bobs_pref, child_prefix])?;
+
+    assert_eq!(graph.nodes.len(), 3);
+    let edge = |from: &str, to: &str, kind: EdgeKind| GraphEdge {
+        from: from.into(),
+        to: to.into(),
+        kind,
+    };
+    let icp = "Et78eYkh8A3H9w6Q87EC5OcijiVEJT8KyNtEGdpPVWV8";
+    let ixn = "E1_-icBrwC_HhxyFwsQLV6hZEbApOc_McGUjhLONpQuc";
+    let dip = "Er4bHXd4piEtsQat1mquwsNZXItvuoj_auCUyICmwyXI";
+    assert_eq!(
+        graph.edges,
+        vec![
+            edge(ixn, icp, EdgeKind::Previous),
+            edge(ixn, dip, EdgeKind::Seal),
+            edge(dip, ixn, EdgeKind::Delegation),
+        ]
+    );
+
+    let dot = graph.to_dot()?;
+    assert!(dot.starts_with("digraph KEL {"));
+    assert!(dot.contains(&format!(
+        "\"{}\" -> \"{}\" [label=\"delegation\"];",
+        dip, ixn
+    )));
+    let json: serde_json::Value = serde_json::from_str(&graph.to_json()?)?;
+    assert_eq!(json["nodes"][2]["event_type"], "dip");
+    assert_eq!(json["edges"][2]["kind"], "delegation");
+
+    // Unknown identifier can't be exported.
+    assert!(event_processor
+        .event_graph(&["DntNTPnDFBnmlO6J44LXCrzZTAmpe-82b7BmQGtL4QhM".parse()?])
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_derivation_policy() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
+        processor::config::ProcessorConfig,
+    };
+    use tempfile::Builder;
+
+    // Events and sigs are from keripy `test_multisig_digprefix` test.
+    // (keripy/tests/core/test_eventing.py#1138)
+    let icp_raw = br#"{"v":"KERI10JSON00017e_","t":"icp","d":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","i":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","s":"0","kt":"2","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI","DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8"],"n":"E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw","bt":"0","b":[],"c":[],"a":[]}-AADAA39j08U7pcU66OPKsaPExhBuHsL5rO1Pjq5zMgt_X6jRbezevis6YBUg074ZNKAGdUwHLqvPX_kse4buuuSUpAQABphobpuQEZ6EhKLhBuwgJmIQu80ZUV1GhBL0Ht47Hsl1rJiMwE2yW7-yi8k3idw2ahlpgdd9ka9QOP9yQmMWGAQACM7yfK1b86p1H62gonh1C7MECDCFBkoH0NZRjHKAEHebvd2_LLz6cpCaqKWDhbM2Rq01f9pgyDTFNLJMxkC-fAQ"#;
+    let icp = Message::try_from(signed_message(icp_raw).unwrap().1)?;
+    let id: IdentifierPrefix = "ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk".parse()?;
+
+    let process_with = |config: ProcessorConfig| -> Result<_, Error> {
+        let root = Builder::new().prefix("test-db").tempdir().unwrap();
+        let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+        let processor = EventProcessor::new(db).with_config(config);
+        let result = processor.process(icp.clone());
+        Ok((result, processor.compute_state(&id)?))
+    };
+
+    // Only SHA3 digests are allowed, but event uses Blake3 ones.
+    let (result, state) =
+        process_with(ProcessorConfig::default().with_allowed_digests(&[SelfAddressing::SHA3_256]))?;
+    assert!(matches!(result, Err(Error::DerivationNotAllowed(code)) if code == "E"));
+    assert_eq!(state, None);
+
+    // Transferable Ed25519 keys are not allowed.
+    let (result, state) =
+        process_with(ProcessorConfig::default().with_allowed_keys(&[Basic::Ed25519NT]))?;
+    assert!(matches!(result, Err(Error::DerivationNotAllowed(code)) if code == "D"));
+    assert_eq!(state, None);
+
+    let (result, state) = process_with(
+        ProcessorConfig::default()
+            .with_allowed_digests(&[SelfAddressing::Blake3_256])
+            .with_allowed_keys(&[Basic::Ed25519])
+            .with_allowed_signatures(&[SelfSigning::Ed25519Sha512]),
+    )?;
+    assert!(result.is_ok());
+    assert_eq!(state.unwrap().sn, 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "query")]
+#[test]
+pub fn test_endpoint_replies() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
+        event::SerializationFormats,
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        event_parsing::SignedEventData,
+        oobi::{EndRole, LocationScheme, Role, Scheme},
+        prefix::AttachedSignaturePrefix,
+        query::{
+            reply::{ReplyEvent, SignedReply},
+            QueryError,
+        },
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    // Incept controller.
+    let km = CryptoBox::new()?;
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let cid = icp.event.get_prefix();
+    let signature = km.sign(&icp.serialize()?)?;
+    event_processor.process(Message::Event(icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+        None,
+    )))?;
+    let controller_seal = EventSeal {
+        prefix: cid.clone(),
+        sn: 0,
+        event_digest: icp.get_digest(),
+    };
+
+    let witness_km = CryptoBox::new()?;
+    let witness = Basic::Ed25519NT.derive(witness_km.public_key());
+    let eid = IdentifierPrefix::Basic(witness.clone());
+
+    let sign_by_witness = |rpy: crate::event::EventMessage<ReplyEvent>| -> Result<_, Error> {
+        let signature = SelfSigning::Ed25519Sha512.derive(witness_km.sign(&rpy.serialize()?)?);
+        Ok(SignedReply::new_nontrans(rpy, witness.clone(), signature))
+    };
+    let sign_by_controller = |rpy: crate::event::EventMessage<ReplyEvent>| -> Result<_, Error> {
+        let signature = km.sign(&rpy.serialize()?)?;
+        Ok(SignedReply::new_trans(
+            rpy,
+            controller_seal.clone(),
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+        ))
+    };
+
+    let loc_scheme = |url: &str| {
+        ReplyEvent::new_loc_scheme(
+            LocationScheme::new(eid.clone(), Scheme::Http, url.into()),
+            SelfAddressing::Blake3_256,
+            SerializationFormats::JSON,
+        )
+    };
+    let old_loc = sign_by_witness(loc_scheme("http://localhost:3232")?)?;
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    let new_loc = sign_by_witness(loc_scheme("http://localhost:3233")?)?;
+
+    // Check if endpoint reply survives serialization and parsing.
+    let stream = SignedEventData::from(new_loc.clone()).to_cesr()?;
+    let parsed = Message::try_from(signed_message(&stream).unwrap().1)?;
+    assert_eq!(parsed, Message::KeyStateNotice(new_loc.clone()));
+
+    // Location of the witness must be signed by the witness.
+    let forged_loc = sign_by_controller(loc_scheme("http://localhost:6666")?)?;
+    assert!(event_processor
+        .process(Message::KeyStateNotice(forged_loc))
+        .is_err());
+
+    event_processor.process(parsed)?;
+    // Older location doesn't replace newer one.
+    assert!(matches!(
+        event_processor.process(Message::KeyStateNotice(old_loc)),
+        Err(Error::QueryError(QueryError::StaleRpy))
+    ));
+    // Location isn't returned until controller authorizes witness role.
+    assert!(event_processor
+        .get_end_point(&cid, Role::Witness, Scheme::Http)
+        .is_empty());
+
+    let end_role = EndRole::new(cid.clone(), Role::Witness, eid.clone());
+    let add = sign_by_controller(ReplyEvent::new_end_role(
+        end_role.clone(),
+        false,
+        SelfAddressing::Blake3_256,
+        SerializationFormats::JSON,
+    )?)?;
+    event_processor.process(Message::KeyStateNotice(add))?;
+    assert_eq!(
+        event_processor.get_end_point(&cid, Role::Witness, Scheme::Http),
+        vec![LocationScheme::new(
+            eid.clone(),
+            Scheme::Http,
+            "http://localhost:3233".into()
+        )]
+    );
+    assert!(event_processor
+        .get_end_point(&cid, Role::Watcher, Scheme::Http)
+        .is_empty());
+    assert!(event_processor
+        .get_end_point(&cid, Role::Witness, Scheme::Tcp)
+        .is_empty());
+
+    // Cutting role replaces its authorization.
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    let cut = sign_by_controller(ReplyEvent::new_end_role(
+        end_role,
+        true,
+        SelfAddressing::Blake3_256,
+        SerializationFormats::JSON,
+    )?)?;
+    event_processor.process(Message::KeyStateNotice(cut))?;
+    assert!(event_processor
+        .get_end_point(&cid, Role::Witness, Scheme::Http)
+        .is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_in_memory_database() -> Result<(), Error> {
+    use crate::database::memory::MemoryDatabase;
+
+    // Processor works the same on top of any database.
+    let event_processor = EventProcessor::new(Arc::new(MemoryDatabase::default()));
+
+    // Taken from keripy/tests/core/test_kevery.py::test kevery
+    let kerl_str= br#"{"v":"KERI10JSON000120_","t":"icp","d":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"0","kt":"1","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA"],"n":"EPYuj8mq_PYYsoBKkzX1kxSPGYBWaIya3slgCOyOtlqU","bt":"0","b":[],"c":[],"a":[]}-AABAA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw{"v":"KERI10JSON000155_","t":"rot","d":"Ej30AgJV14mTTs427F3kILLrP_l03a27APg2FBO0-QtA","i":"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","s":"1","p":"EG4EuTsxPiRM7soX10XXzNsS1KqXKUp8xsQ-kW_tWHoI","kt":"1","k":["DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI"],"n":"E-dapdcC6XR1KWmWDsNl4J_OxcGxNZw1Xd95JH5a34fI","bt":"0","br":[],"ba":[],"a":[]}-AABAAwoiqt07w2UInzzo2DmtwkBfqX1-tTO4cYk_7YdlbJ95qA7PO5sEUkER8fZySQMNCVh64ruAh1yoew3TikwVGAQ"#;
+    let (_rest, messages) = signed_event_stream(kerl_str).unwrap();
+    for msg in messages {
+        event_processor.process(Message::try_from(msg)?)?;
+    }
+
+    let id: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+    assert_eq!(event_processor.compute_state(&id)?.unwrap().sn, 1);
+    assert_eq!(event_processor.get_first_seen(&id, 1)?.unwrap().ordinal, 1);
+    assert_eq!(event_processor.get_kerl(&id)?.unwrap(), kerl_str.to_vec());
+
+    Ok(())
+}
+
+#[test]
+fn test_telemetry_message_type() -> Result<(), Error> {
+    use super::telemetry::message_type;
+
+    let icp_raw = br#"{"v":"KERI10JSON00017e_","t":"icp","d":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","i":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","s":"0","kt":"2","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI","DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8"],"n":"E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw","bt":"0","b":[],"c":[],"a":[]}-AADAA39j08U7pcU66OPKsaPExhBuHsL5rO1Pjq5zMgt_X6jRbezevis6YBUg074ZNKAGdUwHLqvPX_kse4buuuSUpAQABphobpuQEZ6EhKLhBuwgJmIQu80ZUV1GhBL0Ht47Hsl1rJiMwE2yW7-yi8k3idw2ahlpgdd9ka9QOP9yQmMWGAQACM7yfK1b86p1H62gonh1C7MECDCFBkoH0NZRjHKAEHebvd2_LLz6cpCaqKWDhbM2Rq01f9pgyDTFNLJMxkC-fAQ"#;
+    let icp = Message::try_from(signed_message(icp_raw).unwrap().1)?;
+    assert_eq!(message_type(&icp), "icp");
+
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_processing() -> Result<(), Error> {
+    use std::thread;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = Arc::new(EventProcessor::new(Arc::clone(&db)));
+
+    let kel = br#"{"v":"KERI10JSON00017e_","t":"icp","d":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","i":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","s":"0","kt":"2","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI","DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8"],"n":"E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw","bt":"0","b":[],"c":[],"a":[]}-AADAA39j08U7pcU66OPKsaPExhBuHsL5rO1Pjq5zMgt_X6jRbezevis6YBUg074ZNKAGdUwHLqvPX_kse4buuuSUpAQABphobpuQEZ6EhKLhBuwgJmIQu80ZUV1GhBL0Ht47Hsl1rJiMwE2yW7-yi8k3idw2ahlpgdd9ka9QOP9yQmMWGAQACM7yfK1b86p1H62gonh1C7MECDCFBkoH0NZRjHKAEHebvd2_LLz6cpCaqKWDhbM2Rq01f9pgyDTFNLJMxkC-fAQ{"v":"KERI10JSON0001b3_","t":"rot","d":"E0UUmo4JsLq9C6LDnerxTjV0PcegpXcPsT_m2J4SeQbE","i":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","s":"1","p":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","kt":"2","k":["DKPE5eeJRzkRTMOoRGVd2m18o8fLqM2j9kaxLhV3x8AQ","D1kcBE7h0ImWW6_Sp7MQxGYSshZZz6XM7OiUE5DXm0dU","D4JDgo3WNSUpt-NG14Ni31_GCmrU0r38yo7kgDuyGkQM"],"n":"EQpRYqbID2rW8X5lB6mOzDckJEIFae6NbJISXgJSN9qg","bt":"0","br":[],"ba":[],"a":[]}-AADAATWNmB15NNCgCUeFmDv9HbSkPzZ3hK1oS4DAnBVvA1hSkBm1biGDGPIVRPMLqB_MhAy516DV7B7AQs7eoS5b1DgABOXlDXb4TktNyn_Iindz3GLwRkH_lRo3rfez107T1GfoHFetzbpx3uQExyiuiQM2JRWuHCe3wUFdhzjqQ2_MpAgACVMBC6elfrKOfs2ZQxyXrzkuxNCgpgDBPmstysWo2P6GA2epCGnKwUPq83S_g6RC6oCl9N0-DEWf7tgaD0aTcCg"#;
+    let messages = signed_event_stream(kel)
+        .unwrap()
+        .1
+        .into_iter()
+        .map(Message::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    let id: IdentifierPrefix = "ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk".parse()?;
+
+    // The same KEL is processed by several threads at once. Each event
+    // should be accepted exactly once, the rest are duplicates.
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let processor = Arc::clone(&event_processor);
+            let messages = messages.clone();
+            thread::spawn(move || {
+                for message in messages {
+                    processor.process(message).ok();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(db.get_kel_finalized_events(&id).unwrap().count(), 2);
+    assert_eq!(event_processor.compute_state(&id)?.unwrap().sn, 1);
+    // Locks are dropped once nobody uses them.
+    assert_eq!(event_processor.locks.count(), 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_processor() -> Result<(), Error> {
+    use super::async_processing::{AsyncEventProcessor, Notification};
+    use async_std::{stream::StreamExt, task::block_on};
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = AsyncEventProcessor::new(EventProcessor::new(db));
+    let mut notifications = processor.notifications();
+
+    let icp_raw = br#"{"v":"KERI10JSON00017e_","t":"icp","d":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","i":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","s":"0","kt":"2","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI","DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8"],"n":"E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw","bt":"0","b":[],"c":[],"a":[]}-AADAA39j08U7pcU66OPKsaPExhBuHsL5rO1Pjq5zMgt_X6jRbezevis6YBUg074ZNKAGdUwHLqvPX_kse4buuuSUpAQABphobpuQEZ6EhKLhBuwgJmIQu80ZUV1GhBL0Ht47Hsl1rJiMwE2yW7-yi8k3idw2ahlpgdd9ka9QOP9yQmMWGAQACM7yfK1b86p1H62gonh1C7MECDCFBkoH0NZRjHKAEHebvd2_LLz6cpCaqKWDhbM2Rq01f9pgyDTFNLJMxkC-fAQ"#;
+    let icp = Message::try_from(signed_message(icp_raw).unwrap().1)?;
+    let id: IdentifierPrefix = "ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk".parse()?;
+
+    block_on(async {
+        let state = processor.process(icp.clone()).await?;
+        assert_eq!(processor.compute_state(&id).await?, state);
+        assert!(matches!(
+            notifications.next().await,
+            Some(Notification::Accepted { state: Some(_), .. })
+        ));
+
+        // Processing the same event again is rejected.
+        assert!(processor.process(icp).await.is_err());
+        assert!(matches!(
+            notifications.next().await,
+            Some(Notification::Rejected { .. })
+        ));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_config_traits() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event::sections::{
+            seal::{Seal, SourceSeal},
+            ConfigTrait,
+        },
+        event::EventMessage,
+        event_message::{
+            event_msg_builder::EventMsgBuilder, key_event_message::KeyEvent, EventTypeTag,
+        },
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let sign = |km: &CryptoBox,
+                event: &EventMessage<KeyEvent>,
+                source_seal: Option<SourceSeal>|
+     -> Result<Message, Error> {
+        let signature = km.sign(&event.serialize()?)?;
+        Ok(Message::Event(event.sign(
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+            source_seal,
+        )))
+    };
+    let incept = |km: &CryptoBox, config: Vec<ConfigTrait>| {
+        EventMsgBuilder::new(EventTypeTag::Icp)
+            .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+            .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+            .with_inception_configuration(config)
+            .build()
+    };
+
+    // Establishment only identifier can't make interaction events.
+    let eo_km = CryptoBox::new()?;
+    let eo_icp = incept(&eo_km, vec![ConfigTrait::EstablishmentOnly])?;
+    let eo_id = eo_icp.event.get_prefix();
+    let state = event_processor
+        .process(sign(&eo_km, &eo_icp, None)?)?
+        .unwrap();
+    assert_eq!(state.config, vec![ConfigTrait::EstablishmentOnly]);
+    let eo_ixn = EventMsgBuilder::new(EventTypeTag::Ixn)
+        .with_prefix(&eo_id)
+        .with_sn(1)
+        .with_previous_event(&eo_icp.get_digest())
+        .build()?;
+    assert!(matches!(
+        event_processor.process(sign(&eo_km, &eo_ixn, None)?),
+        Err(Error::EstablishmentOnly(ref id)) if id == &eo_id
+    ));
+    assert_eq!(event_processor.compute_state(&eo_id)?.unwrap().sn, 0);
+
+    // Identifier incepted with do not delegate trait can't be
+    // delegator, even if it anchors delegated event.
+    let dnd_km = CryptoBox::new()?;
+    let dnd_icp = incept(&dnd_km, vec![ConfigTrait::DoNotDelegate])?;
+    let dnd_id = dnd_icp.event.get_prefix();
+    event_processor.process(sign(&dnd_km, &dnd_icp, None)?)?;
+
+    let delegate_km = CryptoBox::new()?;
+    let dip = EventMsgBuilder::new(EventTypeTag::Dip)
+        .with_keys(vec![Basic::Ed25519.derive(delegate_km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(delegate_km.next_public_key())])
+        .with_delegator(&dnd_id)
+        .build()?;
+    let dnd_ixn = EventMsgBuilder::new(EventTypeTag::Ixn)
+        .with_prefix(&dnd_id)
+        .with_sn(1)
+        .with_previous_event(&dnd_icp.get_digest())
+        .with_seal(vec![Seal::Event(EventSeal {
+            prefix: dip.event.get_prefix(),
+            sn: 0,
+            event_digest: dip.get_digest(),
+        })])
+        .build()?;
+    event_processor.process(sign(&dnd_km, &dnd_ixn, None)?)?;
+    assert!(matches!(
+        event_processor.process(sign(
+            &delegate_km,
+            &dip,
+            Some(SourceSeal::new(1, dnd_ixn.get_digest())),
+        )?),
+        Err(Error::DelegationNotAllowed(ref id)) if id == &dnd_id
+    ));
+    assert!(event_processor
+        .compute_state(&dip.event.get_prefix())?
+        .is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_seal_verification() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing,
+        event::sections::seal::{DigestSeal, LastEstablishmentSeal, RootSeal, Seal},
+        event_message::test_fixture::TestIdentityFixture,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    let id = alice.prefix().clone();
+    let icp = alice.events()[0].clone();
+    event_processor.process(Message::Event(icp.clone()))?;
+
+    let document = b"document";
+    let digest_seal = Seal::Digest(DigestSeal {
+        dig: SelfAddressing::Blake3_256.derive(document),
+    });
+    let root_seal = Seal::Root(RootSeal {
+        tree_root: SelfAddressing::Blake3_256.derive(b"tree root"),
+    });
+    let ixn = alice.interact(vec![digest_seal.clone(), root_seal.clone()])?;
+    event_processor.process(Message::Event(ixn.clone()))?;
+
+    // Digest and root seals are found in anchoring event.
+    let anchoring = EventSeal {
+        prefix: id.clone(),
+        sn: 1,
+        event_digest: ixn.event_message.get_digest(),
+    };
+    assert!(event_processor.is_anchored(&anchoring, &digest_seal)?);
+    assert!(event_processor.is_anchored(&anchoring, &root_seal)?);
+    assert!(event_processor.verify_digest_anchor(&anchoring, document)?);
+    assert!(!event_processor.verify_digest_anchor(&anchoring, b"other document")?);
+    let icp_seal = EventSeal {
+        prefix: id.clone(),
+        sn: 0,
+        event_digest: icp.event_message.get_digest(),
+    };
+    assert!(!event_processor.is_anchored(&icp_seal, &digest_seal)?);
+
+    // Last establishment seal resolves to the current establishment
+    // event, even if interaction events follow it.
+    let last_est = LastEstablishmentSeal { prefix: id.clone() };
+    assert_eq!(
+        event_processor.resolve_last_establishment_seal(&last_est)?,
+        Some(icp_seal)
+    );
+    let signatures = vec![alice.sign(document)?];
+    event_processor.verify_with_last_establishment(&last_est, document, &signatures)?;
+
+    event_processor.process(Message::Event(alice.rotate()?))?;
+    assert_eq!(
+        event_processor
+            .resolve_last_establishment_seal(&last_est)?
+            .map(|seal| seal.sn),
+        Some(2)
+    );
+    assert!(event_processor
+        .verify_with_last_establishment(&last_est, document, &signatures)
+        .is_err());
+    event_processor.verify_with_last_establishment(
+        &last_est,
+        document,
+        &[alice.sign(document)?],
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_non_transferable_identifier() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_addressing::SelfAddressing},
+        event::{
+            event_data::{EventData, InceptionEvent},
+            sections::KeyConfig,
+            Event, SerializationFormats,
+        },
+        event_message::{
+            event_msg_builder::EventMsgBuilder,
+            test_fixture::{keypair_from_seed, TestIdentityFixture},
+            EventTypeTag,
+        },
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    // Builder incepts non-transferable key without next keys commitment.
+    let alice = TestIdentityFixture::non_transferable(b"alice")?;
+    let id = alice.prefix().clone();
+    let icp = alice.events()[0].clone();
+    assert_eq!(id, IdentifierPrefix::Basic(alice.public_key()));
+    assert!(!id.is_transferable());
+    assert!(String::from_utf8(icp.event_message.serialize()?)
+        .unwrap()
+        .contains(r#""n":"""#));
+    let state = event_processor
+        .process(Message::Event(icp.clone()))?
+        .unwrap();
+    assert_eq!(state.current.threshold_key_digest, None);
+
+    // It can't be rotated, nor have any other events.
+    let sign = |builder: EventMsgBuilder| -> Result<Message, Error> {
+        let event = builder
+            .with_prefix(&id)
+            .with_sn(1)
+            .with_previous_event(&icp.event_message.get_digest())
+            .build()?;
+        Ok(Message::Event(
+            event.sign(vec![alice.sign(&event.serialize()?)?], None),
+        ))
+    };
+    let rot = EventMsgBuilder::new(EventTypeTag::Rot).with_keys(vec![
+        Basic::Ed25519NT.derive(keypair_from_seed(b"alice", 1).0)
+    ]);
+    assert!(matches!(
+        event_processor.process(sign(rot)?),
+        Err(Error::NonTransferable(ref prefix)) if prefix == &id
+    ));
+    assert!(matches!(
+        event_processor.process(sign(EventMsgBuilder::new(EventTypeTag::Ixn))?),
+        Err(Error::NonTransferable(ref prefix)) if prefix == &id
+    ));
+    assert_eq!(event_processor.compute_state(&id)?.unwrap().sn, 0);
+
+    // Non-transferable identifier committing to next keys is rejected.
+    let bob = TestIdentityFixture::non_transferable(b"bob")?;
+    let key = bob.public_key();
+    let next_keys = vec![Basic::Ed25519.derive(keypair_from_seed(b"bob", 1).0)];
+    let key_config = KeyConfig::new(
+        vec![key.clone()],
+        Some(KeyConfig::new(next_keys, None, None).commit(&SelfAddressing::Blake3_256)),
+        None,
+    );
+    let icp = Event::new(
+        IdentifierPrefix::Basic(key),
+        0,
+        EventData::Icp(InceptionEvent::new(key_config, None, None)),
+    )
+    .to_message(SerializationFormats::JSON, &SelfAddressing::Blake3_256)?;
+    assert!(matches!(
+        event_processor.process(Message::Event(
+            icp.sign(vec![bob.sign(&icp.serialize()?)?], None)
+        )),
+        Err(Error::InvalidPrefixBinding(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_kel_range_and_chunks() -> Result<(), Error> {
+    use crate::event_message::test_fixture::TestIdentityFixture;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    for _ in 0..4 {
+        alice.rotate()?;
+    }
+    for event in alice.events() {
+        event_processor.process(Message::Event(event.clone()))?;
+    }
+    let id = alice.prefix().clone();
+    let events: Vec<Vec<u8>> = alice
+        .events()
+        .iter()
+        .map(|event| event.serialize())
+        .collect::<Result<_, _>>()?;
+    let kerl = event_processor.get_kerl(&id)?.unwrap();
+    assert_eq!(kerl, events.concat());
+
+    assert_eq!(
+        event_processor.get_kel_range(&id, 1, 3)?.unwrap(),
+        events[1..=3].concat()
+    );
+    assert_eq!(
+        event_processor.get_kel_range(&id, 3, 10)?.unwrap(),
+        events[3..].concat()
+    );
+    assert!(event_processor
+        .get_kel_range(&id, 5, 10)?
+        .unwrap()
+        .is_empty());
+    let unknown = TestIdentityFixture::new(b"bob")?;
+    assert!(event_processor
+        .get_kel_range(unknown.prefix(), 0, 10)?
+        .is_none());
+    assert!(event_processor
+        .get_kerl_chunks(unknown.prefix(), 1024)
+        .is_none());
+
+    // Chunks hold whole events and together give the whole KEL.
+    let max_bytes = events[0].len() + events[1].len();
+    let chunks = event_processor
+        .get_kerl_chunks(&id, max_bytes)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|chunk| chunk.len() <= max_bytes));
+    assert_eq!(chunks.concat(), kerl);
+    let stream = signed_event_stream(&chunks[0]).unwrap();
+    assert!(stream.0.is_empty());
+    assert_eq!(stream.1.len(), 2);
+
+    // Event longer than the limit is a chunk on its own.
+    let chunks = event_processor
+        .get_kerl_chunks(&id, 1)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(chunks, events);
+
+    Ok(())
+}
+
+#[test]
+fn test_seal_digest_derivation() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing,
+        event::sections::seal::LastEstablishmentSeal,
+        event_message::{
+            event_msg_builder::EventMsgBuilder, test_fixture::TestIdentityFixture, EventTypeTag,
+        },
+        processor::config::ProcessorConfig,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    // Identifier incepted with other digest than the default one.
+    let mut alice = TestIdentityFixture::incept_with(b"alice", |icp| {
+        icp.with_derivation(SelfAddressing::SHA3_256)
+    })?;
+    let icp = alice.events()[0].event_message.clone();
+    event_processor.process(Message::Event(alice.events()[0].clone()))?;
+    let id = alice.prefix().clone();
+    assert_eq!(
+        event_processor.get_digest_derivation(&id)?,
+        Some(SelfAddressing::SHA3_256)
+    );
+
+    // Next events follow its derivation.
+    let state = event_processor.compute_state(&id)?.unwrap();
+    let ixn = EventMsgBuilder::from_state(EventTypeTag::Ixn, &state).build()?;
+    assert_eq!(ixn.get_digest().derivation, SelfAddressing::SHA3_256);
+    let signed = alice.interact(vec![])?;
+    assert_eq!(signed.event_message, ixn);
+    event_processor.process(Message::Event(signed))?;
+
+    // Seals use digest of identifier's events by default.
+    let seal = event_processor
+        .get_last_establishment_event_seal(&id)?
+        .unwrap();
+    assert_eq!(seal.event_digest, icp.get_digest());
+
+    // Seals made with other derivation still verify.
+    let blake3_processor = EventProcessor::new(Arc::clone(&db))
+        .with_config(ProcessorConfig::default().with_seal_digest(SelfAddressing::Blake3_256));
+    let seal = blake3_processor
+        .get_last_establishment_event_seal(&id)?
+        .unwrap();
+    assert_eq!(seal.event_digest.derivation, SelfAddressing::Blake3_256);
+    assert_ne!(seal.event_digest, icp.get_digest());
+    assert!(icp.check_digest(&seal.event_digest)?);
+    let document = b"signed document";
+    for processor in [&event_processor, &blake3_processor] {
+        processor.verify_with_last_establishment(
+            &LastEstablishmentSeal { prefix: id.clone() },
+            document,
+            &[alice.sign(document)?],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_mode() -> Result<(), Error> {
+    use crate::{
+        event_message::{
+            signed_event_message::SignedEventMessage, test_fixture::TestIdentityFixture,
+        },
+        processor::config::ProcessorConfig,
+    };
+    use tempfile::Builder;
+
+    let strict_processor = || {
+        let root = Builder::new().prefix("test-db").tempdir().unwrap();
+        let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+        EventProcessor::new(db).with_config(ProcessorConfig::default().with_strict(true))
+    };
+
+    // Events serialized by keripy are canonical.
+    // (keripy/tests/core/test_eventing.py#1138)
+    let icp_raw = br#"{"v":"KERI10JSON00017e_","t":"icp","d":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","i":"ELYk-z-SuTIeDncLr6GhwVUKnv3n3F1bF18qkXNd2bpk","s":"0","kt":"2","k":["DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA","DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI","DT1iAhBWCkvChxNWsby2J0pJyxBIxbAtbLA0Ljx-Grh8"],"n":"E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw","bt":"0","b":[],"c":[],"a":[]}-AADAA39j08U7pcU66OPKsaPExhBuHsL5rO1Pjq5zMgt_X6jRbezevis6YBUg074ZNKAGdUwHLqvPX_kse4buuuSUpAQABphobpuQEZ6EhKLhBuwgJmIQu80ZUV1GhBL0Ht47Hsl1rJiMwE2yW7-yi8k3idw2ahlpgdd9ka9QOP9yQmMWGAQACM7yfK1b86p1H62gonh1C7MECDCFBkoH0NZRjHKAEHebvd2_LLz6cpCaqKWDhbM2Rq01f9pgyDTFNLJMxkC-fAQ"#;
+    let icp = Message::try_from(signed_message(icp_raw).unwrap().1)?;
+    assert_eq!(strict_processor().process(icp)?.unwrap().sn, 0);
+
+    // The same event with fields reordered, signed over the reordered
+    // bytes. It has the same digest, but other bytes.
+    let alice = TestIdentityFixture::new(b"alice")?;
+    let icp = &alice.events()[0];
+    let value: serde_json::Value = serde_json::from_slice(&icp.event_message.serialize()?)?;
+    let reordered = serde_json::to_vec(&value)?;
+    let malleated = SignedEventMessage {
+        signatures: vec![alice.sign(&reordered)?],
+        raw: Some(reordered),
+        ..icp.clone()
+    };
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let lenient = EventProcessor::new(db);
+    assert!(lenient.process(Message::Event(malleated.clone())).is_ok());
+
+    let strict = strict_processor();
+    assert!(matches!(
+        strict.process(Message::Event(malleated)),
+        Err(Error::NonCanonical(_))
+    ));
+    assert_eq!(strict.compute_state(alice.prefix())?, None);
+    assert!(strict.process(Message::Event(icp.clone())).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_audit_trail() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing,
+        event_message::{
+            event_msg_builder::ReceiptBuilder, signed_event_message::SignedNontransferableReceipt,
+            test_fixture::TestIdentityFixture,
+        },
+        event_parsing::SignedEventData,
+        processor::{audit::AuditOutcome, config::ProcessorConfig},
+    };
+    use chrono::Local;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db))
+        .with_config(ProcessorConfig::default().with_audit_trail(true));
+    let start = Local::now();
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.rotate()?;
+    alice.rotate()?;
+    // Other version of alice's KEL, made with the same keys.
+    let mut forked = TestIdentityFixture::new(b"alice")?;
+    forked.interact(vec![])?;
+    let bob = TestIdentityFixture::new(b"bob")?;
+    let receipt = ReceiptBuilder::default()
+        .with_receipted_event(bob.events()[0].event_message.clone())
+        .build()?;
+
+    let events = alice.events();
+    processor.process_from("peer", Message::Event(events[0].clone()))?;
+    assert!(processor
+        .process(Message::Event(events[2].clone()))
+        .is_err());
+    processor.process_from("peer", Message::Event(events[1].clone()))?;
+    assert!(processor
+        .process_from("peer", Message::Event(forked.events()[1].clone()))
+        .is_err());
+    let rct = Message::NontransferableRct(SignedNontransferableReceipt::new(&receipt, vec![]));
+    processor.process_from("witness", rct.clone())?;
+
+    let trail = processor.get_audit_trail(Some(start), None);
+    let outcomes: Vec<_> = trail.iter().map(|r| r.outcome.clone()).collect();
+    assert!(matches!(
+        &outcomes[..],
+        [
+            AuditOutcome::Accepted,
+            AuditOutcome::Rejected(_),
+            AuditOutcome::Accepted,
+            AuditOutcome::Duplicitous,
+            AuditOutcome::Escrowed,
+        ]
+    ));
+    let sources: Vec<_> = trail.iter().map(|r| r.source.as_deref()).collect();
+    assert_eq!(
+        sources,
+        vec![
+            Some("peer"),
+            None,
+            Some("peer"),
+            Some("peer"),
+            Some("witness")
+        ]
+    );
+    let cesr = SignedEventData::from(rct).to_cesr()?;
+    assert_eq!(trail[4].digest, SelfAddressing::Blake3_256.derive(&cesr));
+
+    // Nothing was recorded before the start.
+    assert!(processor.get_audit_trail(None, Some(start)).is_empty());
+
+    // Trail is exported as JSON lines.
+    let export = processor.export_audit_trail(None, None)?;
+    let lines: Vec<_> = export
+        .split(|b| *b == b'\n')
+        .filter(|l| !l.is_empty())
+        .collect();
+    assert_eq!(lines.len(), 5);
+    let first: crate::processor::audit::AuditRecord = serde_json::from_slice(lines[0])?;
+    assert_eq!(first, trail[0]);
+
+    // Processor without audit trail doesn't record anything.
+    EventProcessor::new(Arc::clone(&db)).process(Message::Event(events[2].clone()))?;
+    assert_eq!(processor.get_audit_trail(None, None).len(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_clock() -> Result<(), Error> {
+    use crate::{event_message::test_fixture::TestIdentityFixture, processor::clock::ManualClock};
+    use chrono::{Duration, Local};
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let start = Local::now() - Duration::days(365);
+    let clock = Arc::new(ManualClock::new(start));
+    let processor = EventProcessor::new(Arc::clone(&db)).with_clock(clock.clone());
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.rotate()?;
+    let id = alice.prefix().clone();
+    processor.process(Message::Event(alice.events()[0].clone()))?;
+    clock.advance(Duration::minutes(5));
+    processor.process(Message::Event(alice.events()[1].clone()))?;
+
+    // Events and first seen records are stamped with injected time.
+    let rotated_at = start + Duration::minutes(5);
+    assert_eq!(processor.now(), rotated_at);
+    assert_eq!(processor.get_first_seen(&id, 0)?.unwrap().timestamp, start);
+    assert_eq!(
+        processor.get_first_seen(&id, 1)?.unwrap().timestamp,
+        rotated_at
+    );
+    let timestamps: Vec<_> = db
+        .get_kel_finalized_events(&id)
+        .unwrap()
+        .map(|event| event.timestamp)
+        .collect();
+    assert_eq!(timestamps, vec![start, rotated_at]);
+
+    Ok(())
+}
+
+#[test]
+fn test_unverified_event_not_written() -> Result<(), Error> {
+    use crate::{
+        database::EventDatabase,
+        derivation::self_addressing::SelfAddressing,
+        event::{
+            sections::{
+                seal::{BackerSeal, DigestSeal, Seal},
+                ConfigTrait,
+            },
+            EventMessage,
+        },
+        event_message::{
+            key_event_message::KeyEvent, signed_event_message::SignedEventMessage,
+            test_fixture::TestIdentityFixture,
+        },
+        processor::backer::BackerVerifier,
+    };
+    use std::sync::Mutex;
+    use tempfile::Builder;
+
+    // Ledger without anchors, which records whether verified event was
+    // already in KEL.
+    struct Ledger {
+        db: Arc<SledEventDatabase>,
+        seen_in_kel: Mutex<Vec<bool>>,
+    }
+
+    impl BackerVerifier for Ledger {
+        fn verify(&self, _: &BackerSeal, event: &EventMessage<KeyEvent>) -> Result<bool, Error> {
+            let in_kel = self.db.get_event_by_digest(&event.get_digest()).is_some();
+            self.seen_in_kel.lock().unwrap().push(in_kel);
+            Ok(false)
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    let anchored = "EZAoTNZH3ULvaU6Z-i0d8JJR2nmwyYAfSVPzhzS6b5CM".parse()?;
+    let ixn = alice.interact(vec![Seal::Digest(DigestSeal { dig: anchored })])?;
+    let anchored = "EZAoTNZH3ULvaU6Z-i0d8JJR2nmwyYAfSVPzhzS6b5CM".parse()?;
+    let id = alice.prefix().clone();
+    processor.process(Message::Event(alice.events()[0].clone()))?;
+
+    // Interaction signed with other key is refused, with nothing about
+    // it written.
+    let mallory = TestIdentityFixture::new(b"mallory")?;
+    let forged = SignedEventMessage::new(
+        &ixn.event_message,
+        vec![mallory.sign(&ixn.event_message.serialize()?)?],
+        None,
+    );
+    assert!(matches!(
+        processor.process(Message::Event(forged)),
+        Err(Error::SignatureVerificationError)
+    ));
+    let digest = ixn.event_message.get_digest();
+    assert!(db.get_event_by_digest(&digest).is_none());
+    assert!(db.get_anchoring_sn(&id, &anchored).is_none());
+    assert!(db.get_first_seen(&id, 1).is_none());
+
+    // Signed one is written with its indexes.
+    assert_eq!(processor.process(Message::Event(ixn))?.unwrap().sn, 1);
+    assert!(db.get_event_by_digest(&digest).is_some());
+    assert_eq!(db.get_anchoring_sn(&id, &anchored), Some(1));
+    assert!(db.get_first_seen(&id, 1).is_some());
+
+    // Event isn't in KEL yet while its anchor is checked.
+    let ledger = Arc::new(Ledger {
+        db: Arc::clone(&db),
+        seen_in_kel: Mutex::new(vec![]),
+    });
+    let processor = EventProcessor::new(Arc::clone(&db)).with_backer_verifier(ledger.clone());
+    let bob = TestIdentityFixture::incept_with(b"bob", |icp| {
+        icp.with_inception_configuration(vec![ConfigTrait::RegistrarBackers])
+            .with_seal(vec![Seal::Backer(BackerSeal {
+                backer: "BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo"
+                    .parse()
+                    .unwrap(),
+                metadata_digest: SelfAddressing::Blake3_256.derive(b"ledger address"),
+            })])
+    })?;
+    assert!(matches!(
+        processor.process(Message::Event(bob.events()[0].clone())),
+        Err(Error::MissingBackerAnchor { .. })
+    ));
+    assert_eq!(*ledger.seen_in_kel.lock().unwrap(), vec![false]);
+    assert!(db.get_kel_finalized_events(bob.prefix()).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_state_cache() -> Result<(), Error> {
+    use crate::{
+        event_message::test_fixture::TestIdentityFixture, processor::config::ProcessorConfig,
+        state::IdentifierState,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let config = ProcessorConfig::default().with_state_cache(10);
+    let processor = EventProcessor::new(Arc::clone(&db)).with_config(config.clone());
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.rotate()?;
+    // Other version of alice's KEL, made with the same keys.
+    let mut forked = TestIdentityFixture::new(b"alice")?;
+    forked.interact(vec![])?;
+    let id = alice.prefix().clone();
+
+    processor.process(Message::Event(alice.events()[0].clone()))?;
+    let stale = processor.states().snapshot();
+    processor.process(Message::Event(alice.events()[1].clone()))?;
+
+    // Cached state is the same as replayed from KEL.
+    let cached = processor.states().get(&id).unwrap();
+    assert_eq!(cached.sn, 1);
+    assert_eq!(
+        Some(cached),
+        EventProcessor::new(Arc::clone(&db)).compute_state(&id)?
+    );
+
+    // Snapshot can be serialized and restored, stale one is skipped.
+    let snapshot = serde_json::to_vec(&processor.states().snapshot())?;
+    let restarted = EventProcessor::new(Arc::clone(&db)).with_config(config);
+    assert_eq!(restarted.restore_states(stale), 0);
+    let snapshot: Vec<IdentifierState> = serde_json::from_slice(&snapshot)?;
+    assert_eq!(restarted.restore_states(snapshot), 1);
+    assert_eq!(restarted.states().get(&id).unwrap().sn, 1);
+
+    // Duplicitous version isn't added to KEL, so cached state stays.
+    assert!(matches!(
+        processor.process(Message::Event(forked.events()[1].clone())),
+        Err(Error::EventDuplicateError)
+    ));
+    assert_eq!(processor.states().get(&id).unwrap().sn, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_restore_states_after_recovery() -> Result<(), Error> {
+    use crate::{
+        database::EventDatabase, event_message::test_fixture::TestIdentityFixture,
+        processor::config::ProcessorConfig,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let config = ProcessorConfig::default().with_state_cache(10);
+    let processor = EventProcessor::new(Arc::clone(&db)).with_config(config.clone());
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.interact(vec![])?;
+    alice.interact(vec![])?;
+    for event in alice.events() {
+        processor.process(Message::Event(event.clone()))?;
+    }
+    let id = alice.prefix().clone();
+
+    // Rotation recovers the KEL from interaction events at sn 1 and 2.
+    let mut recovered = TestIdentityFixture::new(b"alice")?;
+    let rot = recovered.rotate()?;
+    assert_eq!(
+        processor.process(Message::Event(rot.clone()))?.unwrap().sn,
+        1
+    );
+
+    // The earliest record is of superseded event, accepted one is of
+    // the rotation and superseded sn 2 has no accepted event.
+    let ixn_digest = alice.events()[1].event_message.get_digest();
+    assert_eq!(db.get_first_seen(&id, 1).unwrap().digest, ixn_digest);
+    let accepted = db.get_accepted_first_seen(&id, 1).unwrap();
+    assert_eq!(accepted.digest, rot.event_message.get_digest());
+    assert_eq!(accepted.ordinal, 3);
+    assert!(db.get_first_seen(&id, 2).is_some());
+    assert!(db.get_accepted_first_seen(&id, 2).is_none());
+
+    // State of recovered KEL is restored after restart.
+    let snapshot = processor.states().snapshot();
+    let restarted = EventProcessor::new(Arc::clone(&db)).with_config(config.clone());
+    assert_eq!(restarted.restore_states(snapshot.clone()), 1);
+    assert_eq!(
+        restarted.states().get(&id).unwrap().last_event_digest,
+        rot.event_message.get_digest()
+    );
+
+    // Once KEL goes on from the rotation, the snapshot is stale.
+    let ixn = recovered.interact(vec![])?;
+    processor.process(Message::Event(ixn.clone()))?;
+    assert_eq!(
+        db.get_accepted_first_seen(&id, 2).unwrap().digest,
+        ixn.event_message.get_digest()
+    );
+    let restarted = EventProcessor::new(Arc::clone(&db)).with_config(config);
+    assert_eq!(restarted.restore_states(snapshot), 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_process_parallel() -> Result<(), Error> {
+    use crate::event_message::test_fixture::TestIdentityFixture;
+    use tempfile::Builder;
+
+    let mut fixtures = vec![
+        TestIdentityFixture::new(b"alice")?,
+        TestIdentityFixture::new(b"bob")?,
+        TestIdentityFixture::new(b"carol")?,
+    ];
+    for fixture in fixtures.iter_mut() {
+        fixture.rotate()?;
+        fixture.interact(vec![])?;
+        fixture.rotate()?;
+    }
+    // KELs interleaved, with bob's interaction signed with wrong key.
+    let mut messages = vec![];
+    for sn in 0..4 {
+        for fixture in &fixtures {
+            let mut event = fixture.events()[sn].clone();
+            if sn == 2 && fixture.prefix() == fixtures[1].prefix() {
+                event.signatures = vec![fixtures[0].sign(b"other data")?];
+            }
+            messages.push(Message::Event(event));
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+    let results = processor.process_parallel(messages.clone());
+    assert_eq!(results.len(), 12);
+    // Bob's interaction is rejected and his next rotation is out of order.
+    assert!(matches!(results[7], Err(Error::SignatureVerificationError)));
+    assert!(results[10].is_err());
+    assert!(results
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 7 && *i != 10)
+        .all(|(_, result)| result.is_ok()));
+
+    // Outcome is the same as of processing one by one.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let sequential = EventProcessor::new(Arc::new(SledEventDatabase::new(root.path()).unwrap()));
+    for message in messages {
+        sequential.process(message).ok();
+    }
+    for fixture in &fixtures {
+        assert_eq!(
+            processor.compute_state(fixture.prefix())?,
+            sequential.compute_state(fixture.prefix())?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_find_anchoring_event() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing,
+        event::sections::seal::{DigestSeal, RootSeal, Seal},
+        event_message::test_fixture::TestIdentityFixture,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+
+    let document = SelfAddressing::Blake3_256.derive(b"document");
+    let tree_root = SelfAddressing::Blake3_256.derive(b"tree root");
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.interact(vec![Seal::Digest(DigestSeal {
+        dig: document.clone(),
+    })])?;
+    alice.rotate()?;
+    // Anchored again later, the first anchoring event is found.
+    alice.interact(vec![
+        Seal::Root(RootSeal {
+            tree_root: tree_root.clone(),
+        }),
+        Seal::Digest(DigestSeal {
+            dig: document.clone(),
+        }),
+    ])?;
+    for event in alice.events() {
+        processor.process(Message::Event(event.clone()))?;
+    }
+
+    let (sn, event) = processor
+        .find_anchoring_event(alice.prefix(), &document)?
+        .unwrap();
+    assert_eq!(sn, 1);
+    assert_eq!(event, alice.events()[1].event_message);
+    let (sn, _) = processor
+        .find_anchoring_event(alice.prefix(), &tree_root)?
+        .unwrap();
+    assert_eq!(sn, 3);
+
+    // Digest isn't anchored in other identifier's KEL.
+    let bob = TestIdentityFixture::new(b"bob")?;
+    processor.process(Message::Event(bob.events()[0].clone()))?;
+    assert!(processor
+        .find_anchoring_event(bob.prefix(), &document)?
+        .is_none());
+
+    // Seals of rejected event aren't indexed.
+    let other = SelfAddressing::Blake3_256.derive(b"other document");
+    let mut forged = alice.interact(vec![Seal::Digest(DigestSeal { dig: other.clone() })])?;
+    forged.signatures = vec![bob.sign(&forged.event_message.serialize()?)?];
+    assert!(processor.process(Message::Event(forged)).is_err());
+    assert!(processor
+        .find_anchoring_event(alice.prefix(), &other)?
+        .is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_delegation_revocation() -> Result<(), Error> {
+    use crate::event_message::{
+        signed_event_message::SignedEventMessage, test_fixture::TestIdentityFixture,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+    let process = |events: &[SignedEventMessage]| -> Result<(), Error> {
+        for event in events {
+            processor.process(Message::Event(event.clone()))?;
+        }
+        Ok(())
+    };
+
+    let mut bob = TestIdentityFixture::new(b"bob")?;
+    let mut alice = TestIdentityFixture::delegated(b"alice", &mut bob)?;
+    let (delegator, delegate) = (bob.prefix().clone(), alice.prefix().clone());
+    process(bob.events())?;
+    process(alice.events())?;
+    assert!(processor.is_delegation_active(&delegate)?);
+    assert!(matches!(
+        processor.is_delegation_active(&delegator),
+        Err(Error::MissingDelegator(_))
+    ));
+
+    // Delegator anchors rotation of the delegate to null next keys.
+    let drt = alice.rotate_delegated(&mut bob, |drt| drt.with_next_keys(vec![]))?;
+    process(&bob.events()[2..])?;
+    process(&[drt])?;
+    assert!(!processor.is_delegation_active(&delegate)?);
+
+    // No more events of the delegate are accepted.
+    assert!(matches!(
+        processor.process(Message::Event(alice.interact(vec![])?)),
+        Err(Error::DelegationRevoked(_))
+    ));
+    assert_eq!(processor.compute_state(&delegate)?.unwrap().sn, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_abandonment() -> Result<(), Error> {
+    use crate::{event::event_data::EventData, event_message::test_fixture::TestIdentityFixture};
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    processor.process(Message::Event(alice.events()[0].clone()))?;
+    let state = processor.compute_state(alice.prefix())?.unwrap();
+    assert!(!state.is_abandoned());
+
+    // Rotation to null next keys.
+    let rot = alice.abandon()?;
+    assert!(matches!(
+        rot.event_message.event.get_event_data(),
+        EventData::Rot(rot) if rot.key_config.threshold_key_digest.is_none()
+    ));
+    processor.process(Message::Event(rot))?;
+    let state = processor.compute_state(alice.prefix())?.unwrap();
+    assert!(state.is_abandoned());
+
+    // Abandoned identifier can't add any more events.
+    assert!(matches!(
+        processor.process(Message::Event(alice.interact(vec![])?)),
+        Err(Error::IdentifierAbandoned(_))
+    ));
+    assert_eq!(processor.compute_state(alice.prefix())?.unwrap().sn, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_witness_receipt() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::{
+            event_msg_builder::EventMsgBuilder, test_fixture::keypair_from_seed, EventTypeTag,
+        },
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+
+    let witness_km = CryptoBox::new()?;
+    let witness = Basic::Ed25519NT.derive(witness_km.public_key());
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keypair_seed(b"alice")
+        .with_witnesses(&[witness.clone()])
+        .with_witness_threshold(1)
+        .build()?;
+    let id = icp.event.get_prefix();
+    let signature = keypair_from_seed(b"alice", 0)
+        .1
+        .sign_ed(&icp.serialize()?)?;
+    let signed_icp = icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            signature,
+            0,
+        )],
+        None,
+    );
+
+    // Only accepted events are receipted.
+    assert!(matches!(
+        processor.receipt(&icp, &witness_km),
+        Err(Error::EventOutOfOrderError)
+    ));
+    processor.process(Message::Event(signed_icp.clone()))?;
+
+    let receipt = processor.receipt(&icp, &witness_km)?;
+    assert_eq!(receipt.body.event.prefix, id);
+    assert_eq!(receipt.couplets[0].0, witness);
+    assert!(witness.verify(&signed_icp.raw_event()?, &receipt.couplets[0].1)?);
+    assert_eq!(
+        db.get_receipts_nt(&id).unwrap().collect::<Vec<_>>(),
+        vec![receipt.clone()]
+    );
+
+    // Key of witness not designated for the event can't receipt it.
+    assert!(matches!(
+        processor.receipt(&icp, &CryptoBox::new()?),
+        Err(Error::UnauthorizedWitness { .. })
+    ));
+
+    // Receipt with tampered witness signature is dropped and doesn't
+    // count towards witness threshold.
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let other_db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let other_processor = EventProcessor::new(Arc::clone(&other_db));
+    other_processor.process(Message::Event(signed_icp))?;
+    let mut tampered = receipt.clone();
+    tampered.couplets[0].1 = SelfSigning::Ed25519Sha512.derive(vec![0; 64]);
+    other_processor.process(Message::NontransferableRct(tampered))?;
+    assert!(other_db
+        .get_receipts_nt(&id)
+        .is_none_or(|mut rcts| rcts.next().is_none()));
+    assert!(!other_processor.is_witnessed(&id, 0)?);
+    other_processor.process(Message::NontransferableRct(receipt))?;
+    assert!(other_processor.is_witnessed(&id, 0)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_validator_receipt() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::{
+            event_msg_builder::EventMsgBuilder, test_fixture::TestIdentityFixture, EventTypeTag,
+        },
+        prefix::AttachedSignaturePrefix,
+        signer::{CryptoBox, KeyManager},
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+
+    let validator_km = CryptoBox::new()?;
+    let validator_icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(validator_km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(validator_km.next_public_key())])
+        .build()?;
+    let validator = validator_icp.event.get_prefix();
+    let validator_icp = validator_icp.sign(
+        vec![AttachedSignaturePrefix::new(
+            SelfSigning::Ed25519Sha512,
+            validator_km.sign(&validator_icp.serialize()?)?,
+            0,
+        )],
+        None,
+    );
+    processor.process(Message::Event(validator_icp.clone()))?;
+
+    let alice = TestIdentityFixture::new(b"alice")?;
+    let icp = &alice.events()[0].event_message;
+    assert!(matches!(
+        processor.validator_receipt(icp, &validator, &validator_km),
+        Err(Error::EventOutOfOrderError)
+    ));
+    processor.process(Message::Event(alice.events()[0].clone()))?;
+
+    let receipt = processor.validator_receipt(icp, &validator, &validator_km)?;
+    assert_eq!(receipt.validator_seal.prefix, validator);
+    assert_eq!(receipt.validator_seal.sn, 0);
+    assert_eq!(
+        db.get_receipts_t(alice.prefix())
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec![receipt.clone()]
+    );
+
+    // Receipt is valid for other validators of both KELs.
+    let other_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let other_db = Arc::new(SledEventDatabase::new(other_root.path()).unwrap());
+    let other = EventProcessor::new(Arc::clone(&other_db));
+    other.process(Message::Event(validator_icp))?;
+    other.process(Message::Event(alice.events()[0].clone()))?;
+    other.process(Message::TransferableRct(receipt))?;
+    assert_eq!(other_db.get_receipts_t(alice.prefix()).unwrap().count(), 1);
+
+    // Key which isn't validator's current key can't sign for it.
+    assert!(processor
+        .validator_receipt(icp, &validator, &CryptoBox::new()?)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_refresh_receipts() -> Result<(), Error> {
+    use crate::event_message::test_fixture::TestIdentityFixture;
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let processor = EventProcessor::new(Arc::clone(&db));
+
+    let mut validator_fixture = TestIdentityFixture::new(b"validator")?;
+    let validator = validator_fixture.prefix().clone();
+    let validator_km = validator_fixture.key_manager()?;
+    processor.process(Message::Event(validator_fixture.events()[0].clone()))?;
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    alice.interact(vec![])?;
+    for event in alice.events() {
+        processor.process(Message::Event(event.clone()))?;
+        processor.validator_receipt(&event.event_message, &validator, &validator_km)?;
+    }
+    assert!(processor.get_stale_receipts(alice.prefix())?.is_empty());
+
+    // Validator rotates, so its receipts refer to old keys.
+    processor.process(Message::Event(validator_fixture.rotate()?))?;
+    let validator_km = validator_fixture.key_manager()?;
+    assert_eq!(processor.get_stale_receipts(alice.prefix())?.len(), 2);
+
+    let fresh = processor.refresh_receipts(alice.prefix(), &validator, &validator_km)?;
+    assert_eq!(fresh.len(), 2);
+    assert!(fresh.iter().all(|receipt| receipt.validator_seal.sn == 1));
+    assert!(processor.get_stale_receipts(alice.prefix())?.is_empty());
+    assert_eq!(db.get_receipts_t(alice.prefix()).unwrap().count(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_registrar_backers() -> Result<(), Error> {
+    use crate::{
+        derivation::self_addressing::SelfAddressing,
+        event::sections::{
+            seal::{BackerSeal, Seal},
+            ConfigTrait,
+        },
+        event::EventMessage,
+        event_message::{key_event_message::KeyEvent, test_fixture::TestIdentityFixture},
+        prefix::{BasicPrefix, SelfAddressingPrefix},
+        processor::backer::BackerVerifier,
+    };
+    use std::sync::Mutex;
+    use tempfile::Builder;
+
+    // Ledger of events anchored by backers.
+    #[derive(Default)]
+    struct Ledger(Mutex<Vec<(BasicPrefix, SelfAddressingPrefix)>>);
+
+    impl Ledger {
+        fn anchor(&self, backer: &BasicPrefix, event: &EventMessage<KeyEvent>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((backer.clone(), event.get_digest()));
+        }
+    }
+
+    impl BackerVerifier for Ledger {
+        fn verify(
+            &self,
+            backer: &BackerSeal,
+            event: &EventMessage<KeyEvent>,
+        ) -> Result<bool, Error> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .contains(&(backer.backer.clone(), event.get_digest())))
+        }
+    }
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let ledger = Arc::new(Ledger::default());
+    let event_processor = EventProcessor::new(db).with_backer_verifier(ledger.clone());
+
+    let backer_seal = |backer: &BasicPrefix| {
+        Seal::Backer(BackerSeal {
+            backer: backer.clone(),
+            metadata_digest: SelfAddressing::Blake3_256.derive(b"ledger address"),
+        })
+    };
+    let first_backer: BasicPrefix = "BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo".parse()?;
+    let second_backer: BasicPrefix = "BuyRFMideczFZoapylLIyCjSdhtqVb31wZkRKvPfNqkw".parse()?;
+
+    let mut alice = TestIdentityFixture::incept_with(b"alice", |icp| {
+        icp.with_inception_configuration(vec![ConfigTrait::RegistrarBackers])
+            .with_seal(vec![backer_seal(&first_backer)])
+    })?;
+    let id = alice.prefix().clone();
+    let icp = alice.events()[0].clone();
+
+    // Event is accepted only after its backer anchored it in ledger.
+    assert!(matches!(
+        event_processor.process(Message::Event(icp.clone())),
+        Err(Error::MissingBackerAnchor { ref prefix, sn: 0 }) if prefix == &id
+    ));
+    assert!(event_processor.compute_state(&id)?.is_none());
+    ledger.anchor(&first_backer, &icp.event_message);
+    let state = event_processor.process(Message::Event(icp))?.unwrap();
+    assert_eq!(state.backer.unwrap().backer, first_backer);
+
+    // Rotation to new backer has to be anchored by the new one.
+    let rot = alice.rotate_with(|rot| rot.with_seal(vec![backer_seal(&second_backer)]))?;
+    ledger.anchor(&first_backer, &rot.event_message);
+    assert!(matches!(
+        event_processor.process(Message::Event(rot.clone())),
+        Err(Error::MissingBackerAnchor { sn: 1, .. })
+    ));
+    ledger.anchor(&second_backer, &rot.event_message);
+    let state = event_processor.process(Message::Event(rot))?.unwrap();
+    assert_eq!(state.sn, 1);
+    assert_eq!(state.backer.unwrap().backer, second_backer);
+
+    // Backer seals are refused in KEL of identifier without registrar
+    // backers trait.
+    let bob = TestIdentityFixture::incept_with(b"bob", |icp| {
+        icp.with_seal(vec![backer_seal(&first_backer)])
+    })?;
+    assert!(matches!(
+        event_processor.process(Message::Event(bob.events()[0].clone())),
+        Err(Error::SemanticError(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "pq")]
+#[test]
+fn test_sphincs_identifier() -> Result<(), Error> {
+    use crate::{
+        derivation::{basic::Basic, self_signing::SelfSigning},
+        event_message::{event_msg_builder::EventMsgBuilder, EventTypeTag},
+        event_parsing::SignedEventData,
+        keys::{sphincs, PrivateKey, PublicKey},
+        prefix::AttachedSignaturePrefix,
+    };
+    use tempfile::Builder;
+
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let event_processor = EventProcessor::new(Arc::clone(&db));
+
+    let key_pair = || {
+        let (pk, sk) = sphincs::generate_key_pair();
+        (
+            Basic::SphincsSha2128s.derive(PublicKey::new(pk)),
+            PrivateKey::new(sk),
+        )
+    };
+    let sign = |key: &PrivateKey, data: &[u8]| -> Result<AttachedSignaturePrefix, Error> {
+        Ok(AttachedSignaturePrefix::new(
+            SelfSigning::SphincsSha2128s,
+            key.sign_sphincs(data)?,
+            0,
+        ))
+    };
+    let (current_pk, current_sk) = key_pair();
+    let (next_pk, next_sk) = key_pair();
+
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![current_pk])
+        .with_next_keys(vec![next_pk.clone()])
+        .build()?;
+    let id = icp.event.get_prefix();
+    let signed_icp = icp.sign(vec![sign(&current_sk, &icp.serialize()?)?], None);
+
+    // Signature is aligned to quadlets and parsed back from the stream.
+    let stream = SignedEventData::from(&signed_icp).to_cesr()?;
+    let attachment = &stream[icp.serialize()?.len()..];
+    assert_eq!(attachment.len() % 4, 0);
+    let parsed = Message::try_from(signed_message(&stream).unwrap().1)?;
+    assert_eq!(event_processor.process(parsed)?.unwrap().sn, 0);
+
+    // Key which signed inception signs interaction event as well.
+    let ixn = EventMsgBuilder::new(EventTypeTag::Ixn)
+        .with_prefix(&id)
+        .with_sn(1)
+        .with_previous_event(&icp.get_digest())
+        .build()?;
+    let signed_ixn = ixn.sign(vec![sign(&current_sk, &ixn.serialize()?)?], None);
+    assert_eq!(
+        event_processor
+            .process(Message::Event(signed_ixn))?
+            .unwrap()
+            .sn,
+        1
+    );
+
+    let (new_next_pk, _) = key_pair();
+    let rot = EventMsgBuilder::new(EventTypeTag::Rot)
+        .with_prefix(&id)
+        .with_sn(2)
+        .with_previous_event(&ixn.get_digest())
+        .with_keys(vec![next_pk.clone()])
+        .with_next_keys(vec![new_next_pk])
+        .build()?;
+    let signed_rot = rot.sign(vec![sign(&next_sk, &rot.serialize()?)?], None);
+    let state = event_processor
+        .process(Message::Event(signed_rot))?
+        .unwrap();
+    assert_eq!(state.sn, 2);
+    assert_eq!(state.current.public_keys, vec![next_pk]);
+
+    Ok(())
+}
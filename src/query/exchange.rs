@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use chrono::{SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    derivation::self_addressing::SelfAddressing,
+    error::Error,
+    event::{EventMessage, SerializationFormats},
+    event_message::{EventTypeTag, SaidEvent, Typeable},
+    prefix::{AttachedSignaturePrefix, IdentifierPrefix},
+    processor::EventProcessor,
+};
+
+use super::{serialize_timestamp, QueryError, TimeStamp};
+
+/// Exchange
+///
+/// Peer to peer message. Route decides how the payload should be
+/// interpreted, so applications can build their own protocols
+/// (multisig coordination, challenge/response etc.) on top of it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Exchange {
+    #[serde(rename = "dt", serialize_with = "serialize_timestamp")]
+    pub timestamp: TimeStamp,
+
+    #[serde(rename = "r")]
+    pub route: String,
+
+    #[serde(rename = "q")]
+    pub data: Value,
+}
+
+impl Typeable for Exchange {
+    fn get_type(&self) -> EventTypeTag {
+        EventTypeTag::Exn
+    }
+}
+
+pub type ExchangeEvent = SaidEvent<Exchange>;
+
+impl ExchangeEvent {
+    pub fn new_exchange(
+        route: &str,
+        data: Value,
+        serialization_format: SerializationFormats,
+        derivation: &SelfAddressing,
+    ) -> Result<EventMessage<Self>, Error> {
+        // Timestamp is serialized with microseconds precision, so
+        // truncate it to keep message the same after parsing.
+        let exn = Exchange {
+            timestamp: Utc::now().trunc_subsecs(6).into(),
+            route: route.into(),
+            data,
+        };
+        SaidEvent::<Exchange>::to_message(exn, serialization_format, derivation)
+    }
+
+    pub fn get_route(&self) -> String {
+        self.content.route.clone()
+    }
+
+    pub fn get_timestamp(&self) -> TimeStamp {
+        self.content.timestamp
+    }
+
+    pub fn get_data(&self) -> Value {
+        self.content.data.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedExchange {
+    pub exchange: EventMessage<ExchangeEvent>,
+    pub signer: IdentifierPrefix,
+    pub signatures: Vec<AttachedSignaturePrefix>,
+}
+
+impl SignedExchange {
+    pub fn new(
+        exchange: EventMessage<ExchangeEvent>,
+        signer: IdentifierPrefix,
+        signatures: Vec<AttachedSignaturePrefix>,
+    ) -> Self {
+        Self {
+            exchange,
+            signer,
+            signatures,
+        }
+    }
+
+    /// Verify
+    ///
+    /// Checks signatures against current key state of the signer
+    /// known by given processor.
+    pub fn verify(&self, processor: &EventProcessor) -> Result<(), Error> {
+        let signer_keys = processor
+            .compute_state(&self.signer)?
+            .ok_or_else(|| QueryError::Error("No signer identifier in db".into()))?
+            .current;
+        signer_keys
+            .verify(&self.exchange.serialize()?, &self.signatures)?
+            .then_some(())
+            .ok_or(Error::SignatureVerificationError)
+    }
+}
+
+/// Exchange Handler
+///
+/// Application logic for exn messages of one route. Returned message,
+/// if any, is the response that should be signed and sent back to the
+/// exn signer.
+pub trait ExchangeHandler {
+    fn handle(&self, exn: &SignedExchange) -> Result<Option<EventMessage<ExchangeEvent>>, Error>;
+}
+
+/// Exchange Router
+///
+/// Registry of exn handlers. Dispatches verified messages to handler
+/// registered for their route.
+#[derive(Default)]
+pub struct ExchangeRouter {
+    handlers: HashMap<String, Box<dyn ExchangeHandler>>,
+}
+
+impl ExchangeRouter {
+    /// Registers handler for given route. Replaces previously
+    /// registered one, if there was any.
+    pub fn register(&mut self, route: &str, handler: Box<dyn ExchangeHandler>) {
+        self.handlers.insert(route.into(), handler);
+    }
+
+    pub fn routes(&self) -> Vec<String> {
+        self.handlers.keys().cloned().collect()
+    }
+
+    pub fn dispatch(
+        &self,
+        processor: &EventProcessor,
+        exn: &SignedExchange,
+    ) -> Result<Option<EventMessage<ExchangeEvent>>, Error> {
+        let route = exn.exchange.event.get_route();
+        let handler = self
+            .handlers
+            .get(&route)
+            .ok_or_else(|| QueryError::Error(format!("No handler for exchange route {}", route)))?;
+        exn.verify(processor)?;
+        handler.handle(exn)
+    }
+}
+
+#[test]
+fn test_exchange_serialization() -> Result<(), Error> {
+    let exn = ExchangeEvent::new_exchange(
+        "/challenge/response",
+        serde_json::json!({"words": ["able", "baker"]}),
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?;
+    let serialized = exn.serialize()?;
+    let parsed: EventMessage<ExchangeEvent> = serde_json::from_slice(&serialized).unwrap();
+    assert_eq!(parsed, exn);
+    assert_eq!(parsed.event.get_route(), "/challenge/response");
+    assert_eq!(parsed.event.get_data()["words"][1], "baker");
+    assert_eq!(parsed.serialize()?, serialized);
+
+    Ok(())
+}
@@ -1,9 +1,11 @@
+use std::cmp::Ordering;
+
 use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
 use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
-use serde_hex::{Compact, SerHex};
 
 use crate::{
-    event::SerializationFormats, event_message::serialization_info::SerializationInfo,
+    derivation::self_addressing::SelfAddressing, error::Error, event::SerializationFormats,
+    event_message::serialization_info::SerializationInfo, prefix::SelfAddressingPrefix,
     state::IdentifierState,
 };
 
@@ -15,14 +17,11 @@ pub struct KeyStateNotice {
     #[serde(flatten)]
     pub state: IdentifierState,
 
-    #[serde(rename = "f", with = "SerHex::<Compact>")]
+    #[serde(rename = "f", with = "crate::hex")]
     first_seen_sn: u64,
 
     #[serde(rename = "dt")]
     pub timestamp: DateTime<FixedOffset>,
-
-    #[serde(rename = "c")]
-    config: Vec<String>,
 }
 
 impl Serialize for KeyStateNotice {
@@ -44,13 +43,17 @@ impl Serialize for KeyStateNotice {
         em.serialize_field("et", &self.state.last_event_type)?;
         em.serialize_field("kt", &self.state.current.threshold)?;
         em.serialize_field("k", &self.state.current.public_keys)?;
-        em.serialize_field(
-            "n",
-            &self.state.current.threshold_key_digest.clone().unwrap(),
-        )?;
+        if let Some(next_threshold) = &self.state.current.next_threshold {
+            em.serialize_field("nt", next_threshold)?;
+        }
+        // Non-transferable identifiers have no next keys commitment.
+        match &self.state.current.threshold_key_digest {
+            Some(commitment) => em.serialize_field("n", commitment)?,
+            None => em.serialize_field("n", "")?,
+        }
         em.serialize_field("bt", &self.state.tally.to_string())?;
         em.serialize_field("b", &self.state.witnesses)?;
-        em.serialize_field("c", &self.config)?;
+        em.serialize_field("c", &self.state.config)?;
         em.serialize_field("ee", &self.state.last_est)?;
         em.serialize_field("di", &self.state.delegator.clone().unwrap_or_default())?;
         em.end()
@@ -66,9 +69,104 @@ impl KeyStateNotice {
             timestamp: dt,
             state,
             first_seen_sn: 0,
-            config: vec![],
         };
 
         ksn.clone()
     }
+
+    /// SAID
+    ///
+    /// Digest of the key state carried by the notice. Version string,
+    /// first seen sn and timestamp are left out, so notices of the same
+    /// state received from different watchers have the same SAID.
+    pub fn said(&self, derivation: &SelfAddressing) -> Result<SelfAddressingPrefix, Error> {
+        let mut ksn = serde_json::to_value(self)?;
+        if let Some(fields) = ksn.as_object_mut() {
+            for field in ["v", "f", "dt"] {
+                fields.remove(field);
+            }
+        }
+        Ok(derivation.derive(&serde_json::to_vec(&ksn)?))
+    }
+
+    /// Compares key states of the same identifier by their sn. Returns
+    /// `None` if notices are about different identifiers or they are
+    /// divergent, so none of them can be considered more recent.
+    pub fn compare(&self, other: &KeyStateNotice) -> Option<Ordering> {
+        if self.state.prefix != other.state.prefix || self.is_divergent(other) {
+            None
+        } else {
+            Some(self.state.sn.cmp(&other.state.sn))
+        }
+    }
+
+    /// Checks if `other` notice reports later state of the same
+    /// identifier.
+    pub fn is_stale(&self, other: &KeyStateNotice) -> bool {
+        self.compare(other) == Some(Ordering::Less)
+    }
+
+    /// Checks if notices report different events of the same identifier
+    /// at the same sn, which is evidence of duplicity of the identifier
+    /// or of one of the sources of the notices.
+    pub fn is_divergent(&self, other: &KeyStateNotice) -> bool {
+        self.state.prefix == other.state.prefix
+            && self.state.sn == other.state.sn
+            && self.state.last_event_digest != other.state.last_event_digest
+    }
+}
+
+#[test]
+fn test_ksn_comparison() -> Result<(), Error> {
+    use crate::{
+        database::memory::MemoryDatabase,
+        event_message::{signed_event_message::Message, test_fixture::TestIdentityFixture},
+        processor::EventProcessor,
+    };
+    use std::sync::Arc;
+
+    let mut alice = TestIdentityFixture::new(b"alice")?;
+    let processor = EventProcessor::new(Arc::new(MemoryDatabase::new()));
+    processor.process(Message::Event(alice.events()[0].clone()))?;
+    let icp_state = processor.compute_state(alice.prefix())?.unwrap();
+    processor.process(Message::Event(alice.rotate()?))?;
+    let rot_state = processor.compute_state(alice.prefix())?.unwrap();
+
+    let old = icp_state.to_ksn(SerializationFormats::JSON);
+    let new = rot_state.to_ksn(SerializationFormats::JSON);
+    assert_eq!(old.compare(&new), Some(Ordering::Less));
+    assert!(old.is_stale(&new));
+    assert!(!new.is_stale(&old));
+    assert!(!new.is_stale(&new));
+
+    // SAID doesn't depend on time the notice was made.
+    let mut later = new.clone();
+    later.timestamp = DateTime::parse_from_rfc3339("2030-01-01T00:00:00+00:00").unwrap();
+    assert_eq!(
+        later.said(&SelfAddressing::Blake3_256)?,
+        new.said(&SelfAddressing::Blake3_256)?
+    );
+    assert_ne!(
+        old.said(&SelfAddressing::Blake3_256)?,
+        new.said(&SelfAddressing::Blake3_256)?
+    );
+
+    // Different events at the same sn.
+    let mut forked = new.clone();
+    forked.state.last_event_digest = icp_state.last_event_digest;
+    assert!(forked.is_divergent(&new));
+    assert_eq!(forked.compare(&new), None);
+    assert!(!forked.is_stale(&new));
+
+    // Notices about other identifiers are not comparable.
+    let bob = TestIdentityFixture::new(b"bob")?;
+    processor.process(Message::Event(bob.events()[0].clone()))?;
+    let bob_ksn = processor
+        .compute_state(bob.prefix())?
+        .unwrap()
+        .to_ksn(SerializationFormats::JSON);
+    assert_eq!(old.compare(&bob_ksn), None);
+    assert!(!old.is_divergent(&bob_ksn));
+
+    Ok(())
 }
@@ -5,15 +5,17 @@ use crate::{
     event_message::{EventTypeTag, SaidEvent, Typeable},
     prefix::{IdentifierPrefix, Prefix},
 };
-use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use chrono::{DateTime, FixedOffset, SecondsFormat, SubsecRound, Utc};
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use self::reply::SignedReply;
+use self::{key_state_notice::KeyStateNotice, reply::SignedReply};
+use crate::event_message::signed_event_message::Message;
 
 use thiserror::Error;
 
+pub mod exchange;
 pub mod key_state_notice;
-pub mod query;
+pub mod query_event;
 pub mod reply;
 
 pub type TimeStamp = DateTime<FixedOffset>;
@@ -30,7 +32,10 @@ pub struct Envelope<D: Serialize + Typeable> {
     pub data: D,
 }
 
-fn serialize_timestamp<S>(timestamp: &DateTime<FixedOffset>, s: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_timestamp<S>(
+    timestamp: &DateTime<FixedOffset>,
+    s: S,
+) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
@@ -39,7 +44,8 @@ where
 
 impl<D: Serialize + Typeable + Clone> Envelope<D> {
     pub fn new(route: Route, data: D) -> Self {
-        let timestamp: DateTime<FixedOffset> = Utc::now().into();
+        // Timestamp is serialized with microseconds precision
+        let timestamp: DateTime<FixedOffset> = Utc::now().trunc_subsecs(6).into();
         Envelope {
             timestamp,
             route,
@@ -47,7 +53,7 @@ impl<D: Serialize + Typeable + Clone> Envelope<D> {
         }
     }
 
-    fn to_message(
+    pub(crate) fn to_message(
         self,
         format: SerializationFormats,
         derivation: &SelfAddressing,
@@ -66,7 +72,12 @@ impl<D: Serialize + Typeable> Typeable for Envelope<D> {
 pub enum Route {
     Log,
     Ksn,
+    Mbx,
     ReplyKsn(IdentifierPrefix),
+    LocScheme,
+    EndRoleAdd,
+    EndRoleCut,
+    Alias,
 }
 
 impl Serialize for Route {
@@ -77,7 +88,12 @@ impl Serialize for Route {
         serializer.serialize_str(&match self {
             Route::Log => "log".into(),
             Route::Ksn => "ksn".into(),
+            Route::Mbx => "mbx".into(),
             Route::ReplyKsn(id) => ["/ksn/", &id.to_str()].join(""),
+            Route::LocScheme => "/loc/scheme".into(),
+            Route::EndRoleAdd => "/end/role/add".into(),
+            Route::EndRoleCut => "/end/role/cut".into(),
+            Route::Alias => "/alias".into(),
         })
     }
 }
@@ -94,7 +110,12 @@ impl<'de> Deserialize<'de> for Route {
         } else {
             match &s[..] {
                 "ksn" => Ok(Route::Ksn),
-                "log" => Ok(Route::Log),
+                "log" | "logs" => Ok(Route::Log),
+                "mbx" => Ok(Route::Mbx),
+                "/loc/scheme" => Ok(Route::LocScheme),
+                "/end/role/add" => Ok(Route::EndRoleAdd),
+                "/end/role/cut" => Ok(Route::EndRoleCut),
+                "/alias" => Ok(Route::Alias),
                 _ => Err(Error::SemanticError("".into())).map_err(de::Error::custom),
             }
         }
@@ -105,6 +126,18 @@ impl<'de> Deserialize<'de> for Route {
 pub enum ReplyType {
     Rep(SignedReply),
     Kel(Vec<u8>),
+    Mbx(Vec<Message>),
+}
+
+/// Reply Payload
+///
+/// Data requested by the query. It isn't signed, so it's up to
+/// the responder to pack it into the reply message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplyPayload {
+    Kel(Vec<u8>),
+    Ksn(Box<KeyStateNotice>),
+    Mbx(Vec<Message>),
 }
 
 #[derive(Error, Debug)]
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -22,6 +24,20 @@ pub struct QueryData {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct QueryArgs {
     pub i: IdentifierPrefix,
+
+    /// Sn of the first event to return by `log` query, whole KEL is
+    /// returned if not set.
+    #[serde(
+        rename = "s",
+        with = "crate::hex::opt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub sn: Option<u64>,
+
+    /// Mailbox topics with index of the first message to return.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub topics: Option<BTreeMap<String, u64>>,
 }
 
 pub type QueryEvent = SaidEvent<Envelope<QueryData>>;
@@ -35,13 +51,36 @@ impl QueryEvent {
     ) -> Result<EventMessage<Self>, Error> {
         let message = QueryData {
             reply_route: "route".into(),
-            data: QueryArgs { i: id.clone() },
+            data: QueryArgs {
+                i: id.clone(),
+                sn: None,
+                topics: None,
+            },
         };
 
         let env = Envelope::new(route, message);
         env.to_message(serialization_format, derivation)
     }
 
+    /// Makes `mbx` query for messages stored for `id` under given
+    /// topics, starting from given index of each topic.
+    pub fn new_mailbox_query(
+        id: &IdentifierPrefix,
+        topics: BTreeMap<String, u64>,
+        serialization_format: SerializationFormats,
+        derivation: &SelfAddressing,
+    ) -> Result<EventMessage<Self>, Error> {
+        let message = QueryData {
+            reply_route: "route".into(),
+            data: QueryArgs {
+                i: id.clone(),
+                sn: None,
+                topics: Some(topics),
+            },
+        };
+        Envelope::new(Route::Mbx, message).to_message(serialization_format, derivation)
+    }
+
     pub fn get_route(&self) -> Route {
         self.content.route.clone()
     }
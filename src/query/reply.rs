@@ -3,22 +3,34 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     derivation::self_addressing::SelfAddressing,
+    directory::AliasClaim,
     error::Error,
     event::{sections::seal::EventSeal, EventMessage, SerializationFormats},
-    event_message::{
-        dummy_event::DummyEventMessage, signature::Signature, Digestible, EventTypeTag, SaidEvent,
-        Typeable,
-    },
+    event_message::{signature::Signature, Digestible, EventTypeTag, SaidEvent, Typeable},
+    oobi::{EndRole, LocationScheme},
     prefix::{AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, SelfSigningPrefix},
+    sad,
     state::IdentifierState,
 };
 
 use super::{key_state_notice::KeyStateNotice, Envelope, Route};
 
+/// Reply Body
+///
+/// Data of reply message, depending on its route.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum ReplyBody {
+    Ksn(Box<KeyStateNotice>),
+    LocScheme(LocationScheme),
+    EndRole(EndRole),
+    Alias(AliasClaim),
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct ReplyData {
     #[serde(rename = "a")]
-    pub data: KeyStateNotice,
+    pub data: ReplyBody,
 }
 
 pub type ReplyEvent = SaidEvent<Envelope<ReplyData>>;
@@ -31,8 +43,69 @@ impl ReplyEvent {
         self_addressing: SelfAddressing,
         serialization: SerializationFormats,
     ) -> Result<EventMessage<ReplyEvent>, Error> {
-        let rpy_data = ReplyData { data: ksn.clone() };
-        let env = Envelope::new(route.clone(), rpy_data);
+        Self::new_with_body(
+            ReplyBody::Ksn(Box::new(ksn)),
+            route,
+            self_addressing,
+            serialization,
+        )
+    }
+
+    pub fn new_loc_scheme(
+        loc_scheme: LocationScheme,
+        self_addressing: SelfAddressing,
+        serialization: SerializationFormats,
+    ) -> Result<EventMessage<ReplyEvent>, Error> {
+        Self::new_with_body(
+            ReplyBody::LocScheme(loc_scheme),
+            Route::LocScheme,
+            self_addressing,
+            serialization,
+        )
+    }
+
+    /// Creates `/end/role/add` reply or `/end/role/cut` one if `cut`
+    /// is set.
+    pub fn new_end_role(
+        end_role: EndRole,
+        cut: bool,
+        self_addressing: SelfAddressing,
+        serialization: SerializationFormats,
+    ) -> Result<EventMessage<ReplyEvent>, Error> {
+        let route = if cut {
+            Route::EndRoleCut
+        } else {
+            Route::EndRoleAdd
+        };
+        Self::new_with_body(
+            ReplyBody::EndRole(end_role),
+            route,
+            self_addressing,
+            serialization,
+        )
+    }
+
+    pub fn new_alias(
+        claim: AliasClaim,
+        self_addressing: SelfAddressing,
+        serialization: SerializationFormats,
+    ) -> Result<EventMessage<ReplyEvent>, Error> {
+        Self::new_with_body(
+            ReplyBody::Alias(claim),
+            Route::Alias,
+            self_addressing,
+            serialization,
+        )
+    }
+
+    fn new_with_body(
+        body: ReplyBody,
+        route: Route,
+        self_addressing: SelfAddressing,
+        serialization: SerializationFormats,
+    ) -> Result<EventMessage<ReplyEvent>, Error> {
+        let rpy_data = ReplyData { data: body };
+        let env = Envelope::new(route, rpy_data);
         env.to_message(serialization, &self_addressing)
     }
 
@@ -40,36 +113,62 @@ impl ReplyEvent {
         self.content.timestamp
     }
 
+    /// Returns identifier the reply is about: prefix of key state
+    /// for `ksn`, endpoint for `/loc/scheme` and controller for
+    /// `/end/role` and identifier claiming alias for `/alias`.
     pub fn get_prefix(&self) -> IdentifierPrefix {
-        self.content.data.data.state.prefix.clone()
+        match &self.content.data.data {
+            ReplyBody::Ksn(ksn) => ksn.state.prefix.clone(),
+            ReplyBody::LocScheme(loc) => loc.eid.clone(),
+            ReplyBody::EndRole(end_role) => end_role.cid.clone(),
+            ReplyBody::Alias(claim) => claim.prefix.clone(),
+        }
     }
 
-    pub fn get_state(&self) -> IdentifierState {
-        self.content.data.data.state.clone()
+    pub fn get_state(&self) -> Option<IdentifierState> {
+        match &self.content.data.data {
+            ReplyBody::Ksn(ksn) => Some(ksn.state.clone()),
+            _ => None,
+        }
     }
 
     pub fn get_route(&self) -> Route {
         self.content.route.clone()
     }
 
-    pub fn get_reply_data(&self) -> KeyStateNotice {
+    pub fn get_reply_data(&self) -> ReplyBody {
         self.content.data.data.clone()
     }
+
+    /// Checks if both replies concern the same subject, so the newer
+    /// one replaces the other, eg. role authorization of the same
+    /// endpoint, no matter if it was added or cut.
+    pub fn is_same_subject(&self, other: &ReplyEvent) -> bool {
+        match (&self.content.data.data, &other.content.data.data) {
+            (ReplyBody::Ksn(_), ReplyBody::Ksn(_)) => self.get_route() == other.get_route(),
+            (ReplyBody::LocScheme(loc), ReplyBody::LocScheme(other_loc)) => {
+                loc.eid == other_loc.eid && loc.scheme == other_loc.scheme
+            }
+            (ReplyBody::EndRole(end_role), ReplyBody::EndRole(other_end_role)) => {
+                end_role == other_end_role
+            }
+            (ReplyBody::Alias(claim), ReplyBody::Alias(other_claim)) => claim == other_claim,
+            _ => false,
+        }
+    }
 }
 
 impl EventMessage<ReplyEvent> {
     pub fn check_digest(&self) -> Result<(), Error> {
-        let dummy = DummyEventMessage::dummy_event(
-            self.event.clone(),
+        sad::verify_versioned_said(
+            &self.serialization_info,
+            Some(self.event.get_type()),
+            &self.event.get_digest(),
+            &self.event.content,
             self.serialization_info.kind,
-            &self.event.get_digest().derivation,
         )?
-        .serialize()?;
-        self.event
-            .get_digest()
-            .verify_binding(&dummy)
-            .then(|| ())
-            .ok_or(Error::IncorrectDigest)
+        .then(|| ())
+        .ok_or(Error::IncorrectDigest)
     }
 }
 
@@ -0,0 +1,243 @@
+//! Self-Addressing Data
+//!
+//! Data which contains its own digest (SAID). Digest is computed over
+//! serialization of data with digest field filled with `#` characters,
+//! as many as the digest of given derivation takes, and then it's put
+//! in place of them. The same rules are used for inception events,
+//! replies, exchange messages and ACDC credentials.
+//!
+//! Messages with version string, which holds size of the whole
+//! serialization, have it in `v` field before the digest. Its size is
+//! known only after serializing data with placeholder, so it's computed
+//! in two passes, see `compute_versioned_said`.
+
+use alloc::{string::String, vec::Vec};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    derivation::self_addressing::SelfAddressing,
+    error::Error,
+    event::SerializationFormats,
+    event_message::{dummy_event::dummy_prefix, EventTypeTag},
+    prefix::SelfAddressingPrefix,
+};
+
+/// Self Addressed
+///
+/// Data `D` with its SAID in `d` field. Digest field is serialized
+/// first, followed by fields of data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelfAddressed<D> {
+    #[serde(rename = "d")]
+    pub digest: SelfAddressingPrefix,
+
+    #[serde(flatten)]
+    pub data: D,
+}
+
+/// Data with digest field filled with placeholder characters.
+#[derive(Serialize)]
+struct Placeholder<'a, D> {
+    #[serde(rename = "d")]
+    digest: String,
+
+    #[serde(flatten)]
+    data: &'a D,
+}
+
+impl<D: Serialize> SelfAddressed<D> {
+    /// Computes SAID of `data` and embeds it in digest field.
+    pub fn new(
+        data: D,
+        derivation: &SelfAddressing,
+        format: SerializationFormats,
+    ) -> Result<Self, Error> {
+        let digest = compute_said(
+            &Placeholder {
+                digest: dummy_prefix(derivation),
+                data: &data,
+            },
+            derivation,
+            format,
+        )?;
+        Ok(Self { digest, data })
+    }
+
+    /// Checks if digest field holds SAID of data, computed with the
+    /// same derivation.
+    pub fn verify(&self, format: SerializationFormats) -> Result<bool, Error> {
+        let placeholder = Placeholder {
+            digest: dummy_prefix(&self.digest.derivation),
+            data: &self.data,
+        };
+        Ok(self.digest.verify_binding(&format.encode(&placeholder)?))
+    }
+
+    pub fn serialize(&self, format: SerializationFormats) -> Result<Vec<u8>, Error> {
+        format.encode(self)
+    }
+}
+
+/// Versioned data with digest field filled with placeholder characters.
+/// Message type, if any, goes between version and digest, as in KERI
+/// messages.
+#[derive(Serialize)]
+struct VersionedPlaceholder<'a, V, D> {
+    #[serde(rename = "v")]
+    version: &'a V,
+
+    #[serde(rename = "t", skip_serializing_if = "Option::is_none")]
+    message_type: Option<EventTypeTag>,
+
+    #[serde(rename = "d")]
+    digest: String,
+
+    #[serde(flatten)]
+    data: &'a D,
+}
+
+/// Computes SAID of versioned data. `version` makes version string for
+/// given serialization size. Returns version string of the final
+/// serialization and the SAID.
+pub fn compute_versioned_said<V: Serialize, D: Serialize>(
+    version: impl Fn(usize) -> V,
+    message_type: Option<EventTypeTag>,
+    data: &D,
+    derivation: &SelfAddressing,
+    format: SerializationFormats,
+) -> Result<(V, SelfAddressingPrefix), Error> {
+    let mut placeholder = VersionedPlaceholder {
+        version: &version(0),
+        message_type,
+        digest: dummy_prefix(derivation),
+        data,
+    };
+    let size = format.encode(&placeholder)?.len();
+    let version = version(size);
+    placeholder.version = &version;
+    let digest = compute_said(&placeholder, derivation, format)?;
+    Ok((version, digest))
+}
+
+/// Checks if `digest` is SAID of versioned data, computed with the same
+/// derivation. Placeholder is as long as the digest, so version string
+/// of the message is used as is.
+pub fn verify_versioned_said<V: Serialize, D: Serialize>(
+    version: &V,
+    message_type: Option<EventTypeTag>,
+    digest: &SelfAddressingPrefix,
+    data: &D,
+    format: SerializationFormats,
+) -> Result<bool, Error> {
+    let placeholder = VersionedPlaceholder {
+        version,
+        message_type,
+        digest: dummy_prefix(&digest.derivation),
+        data,
+    };
+    Ok(digest.verify_binding(&format.encode(&placeholder)?))
+}
+
+/// Computes digest of `data` serialized in `format`. Data is expected
+/// to already have its digest field filled with `dummy_prefix` of the
+/// same derivation, eg. for types which keep digest field in other
+/// place than `SelfAddressed` does.
+pub fn compute_said<T: Serialize>(
+    data: &T,
+    derivation: &SelfAddressing,
+    format: SerializationFormats,
+) -> Result<SelfAddressingPrefix, Error> {
+    Ok(derivation.derive(&format.encode(data)?))
+}
+
+#[test]
+fn test_self_addressed() -> Result<(), Error> {
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Note {
+        #[serde(rename = "t")]
+        text: String,
+    }
+
+    let note = Note {
+        text: "hello".into(),
+    };
+    for format in [
+        SerializationFormats::JSON,
+        SerializationFormats::CBOR,
+        SerializationFormats::MGPK,
+    ] {
+        let sad = SelfAddressed::new(note.clone(), &SelfAddressing::Blake3_256, format)?;
+        assert!(sad.verify(format)?);
+
+        let tampered = SelfAddressed {
+            data: Note { text: "bye".into() },
+            ..sad.clone()
+        };
+        assert!(!tampered.verify(format)?);
+    }
+
+    // Digest goes first and SAID is the same as computed with
+    // placeholder filled in by hand.
+    let sad = SelfAddressed::new(note, &SelfAddressing::SHA3_256, SerializationFormats::JSON)?;
+    let json = String::from_utf8(sad.serialize(SerializationFormats::JSON)?).unwrap();
+    assert!(json.starts_with(&format!(r#"{{"d":"{}""#, sad.digest)));
+    let placeholder = format!(r#"{{"d":"{}","t":"hello"}}"#, "#".repeat(44));
+    assert_eq!(
+        sad.digest,
+        SelfAddressing::SHA3_256.derive(placeholder.as_bytes())
+    );
+    let parsed: SelfAddressed<Note> = serde_json::from_str(&json)?;
+    assert_eq!(parsed, sad);
+
+    Ok(())
+}
+
+#[test]
+fn test_versioned_said() -> Result<(), Error> {
+    use crate::event_message::serialization_info::SerializationInfo;
+
+    #[derive(Serialize)]
+    struct Note {
+        #[serde(rename = "t")]
+        text: String,
+    }
+
+    let note = Note {
+        text: "hello".into(),
+    };
+    let (version, said) = compute_versioned_said(
+        |size| SerializationInfo::new(SerializationFormats::JSON, size),
+        None,
+        &note,
+        &SelfAddressing::Blake3_256,
+        SerializationFormats::JSON,
+    )?;
+    // Version holds size of the message with SAID in place of placeholder.
+    let json = format!(
+        r#"{{"v":"{}","d":"{}","t":"hello"}}"#,
+        version.to_str(),
+        said
+    );
+    assert_eq!(version.size, json.len());
+    let placeholder = json.replace(&said.to_string(), &"#".repeat(44));
+    assert_eq!(
+        said,
+        SelfAddressing::Blake3_256.derive(placeholder.as_bytes())
+    );
+    assert!(verify_versioned_said(
+        &version,
+        None,
+        &said,
+        &note,
+        SerializationFormats::JSON
+    )?);
+    assert!(!verify_versioned_said(
+        &version,
+        None,
+        &said,
+        &Note { text: "bye".into() },
+        SerializationFormats::JSON
+    )?);
+
+    Ok(())
+}
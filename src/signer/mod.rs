@@ -1,20 +1,52 @@
 use crate::{
+    derivation::{basic::Basic, self_signing::SelfSigning},
     error::Error,
-    keys::{PrivateKey, PublicKey},
+    keys::{sealed_box, PrivateKey, PublicKey},
+    prefix::SeedPrefix,
 };
-use rand::rngs::OsRng;
+use rand::{rngs::OsRng, RngCore};
 
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+#[cfg(feature = "pq")]
+pub mod sphincs;
 #[cfg(feature = "wallet")]
 pub mod wallet;
 
+/// Key Manager
+///
+/// Custodian of current and pre-rotated next keys of a controller.
+/// Event generation only goes through this trait, so keys may be kept
+/// in memory, in a hardware module or by a remote signer.
 pub trait KeyManager {
     fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, Error>;
     fn public_key(&self) -> PublicKey;
     fn next_public_key(&self) -> PublicKey;
+    fn sign_with_next(&self, msg: &[u8]) -> Result<Vec<u8>, Error>;
     fn rotate(&mut self) -> Result<(), Error>;
+
+    /// Derivation code of managed public keys
+    fn key_type(&self) -> Basic {
+        Basic::Ed25519
+    }
+
+    /// Derivation code of signatures made with managed keys
+    fn signature_type(&self) -> SelfSigning {
+        SelfSigning::Ed25519Sha512
+    }
+
+    /// Opens data sealed for current public key.
+    fn decrypt(&self, _data: &[u8]) -> Result<Vec<u8>, Error> {
+        Err(Error::DecryptionError)
+    }
 }
 
+/// Crypto Box
+///
+/// Default, in-memory key manager holding Ed25519 or ECDSA secp256k1
+/// key pairs.
 pub struct CryptoBox {
+    key_type: Basic,
     signer: Signer,
     next_priv_key: PrivateKey,
     pub next_pub_key: PublicKey,
@@ -22,7 +54,7 @@ pub struct CryptoBox {
 
 impl KeyManager for CryptoBox {
     fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
-        self.signer.sign(msg)
+        sign(self.key_type, &self.signer.priv_key, msg)
     }
 
     fn public_key(&self) -> PublicKey {
@@ -33,8 +65,12 @@ impl KeyManager for CryptoBox {
         self.next_pub_key.clone()
     }
 
+    fn sign_with_next(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        sign(self.key_type, &self.next_priv_key, msg)
+    }
+
     fn rotate(&mut self) -> Result<(), Error> {
-        let (next_pub_key, next_priv_key) = generate_key_pair()?;
+        let (next_pub_key, next_priv_key) = generate_key_pair(self.key_type)?;
 
         let new_signer = Signer {
             priv_key: self.next_priv_key.clone(),
@@ -46,18 +82,80 @@ impl KeyManager for CryptoBox {
 
         Ok(())
     }
+
+    fn key_type(&self) -> Basic {
+        self.key_type
+    }
+
+    fn signature_type(&self) -> SelfSigning {
+        match self.key_type {
+            Basic::ECDSAsecp256k1 => SelfSigning::ECDSAsecp256k1Sha256,
+            _ => SelfSigning::Ed25519Sha512,
+        }
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self.key_type {
+            Basic::Ed25519 => sealed_box::decrypt(&self.signer.priv_key, data),
+            _ => Err(Error::DecryptionError),
+        }
+    }
 }
 //#[cfg(feature = "demo")]
 impl CryptoBox {
     pub fn new() -> Result<Self, Error> {
-        let signer = Signer::new();
-        let (next_pub_key, next_priv_key) = generate_key_pair()?;
+        Self::with_key_type(Basic::Ed25519)
+    }
+
+    /// Creates key manager with random ECDSA secp256k1 key pairs.
+    pub fn new_secp256k1() -> Result<Self, Error> {
+        Self::with_key_type(Basic::ECDSAsecp256k1)
+    }
+
+    fn with_key_type(key_type: Basic) -> Result<Self, Error> {
+        let (pub_key, priv_key) = generate_key_pair(key_type)?;
+        let (next_pub_key, next_priv_key) = generate_key_pair(key_type)?;
+        Ok(CryptoBox {
+            key_type,
+            signer: Signer { priv_key, pub_key },
+            next_pub_key,
+            next_priv_key,
+        })
+    }
+
+    /// Creates key manager with current and next key pairs derived
+    /// from given seeds, so it always holds the same keys. Both seeds
+    /// must be of the same type, either Ed25519 or ECDSA secp256k1.
+    pub fn from_seeds(current: &SeedPrefix, next: &SeedPrefix) -> Result<Self, Error> {
+        let key_type = match (current, next) {
+            (SeedPrefix::RandomSeed256Ed25519(_), SeedPrefix::RandomSeed256Ed25519(_)) => {
+                Basic::Ed25519
+            }
+            (
+                SeedPrefix::RandomSeed256ECDSAsecp256k1(_),
+                SeedPrefix::RandomSeed256ECDSAsecp256k1(_),
+            ) => Basic::ECDSAsecp256k1,
+            _ => return Err(Error::ImproperPrefixType),
+        };
+        let (pub_key, priv_key) = current.derive_key_pair()?;
+        let (next_pub_key, next_priv_key) = next.derive_key_pair()?;
         Ok(CryptoBox {
-            signer,
+            key_type,
+            signer: Signer { priv_key, pub_key },
             next_pub_key,
             next_priv_key,
         })
     }
+
+    /// Returns seeds of current and next key pairs, so the same key
+    /// manager can be recreated with `from_seeds`.
+    pub fn seeds(&self) -> (SeedPrefix, SeedPrefix) {
+        let seed = |key: &PrivateKey| match self.key_type {
+            Basic::ECDSAsecp256k1 => SeedPrefix::RandomSeed256ECDSAsecp256k1(key.key()),
+            _ => SeedPrefix::RandomSeed256Ed25519(key.key()),
+        };
+        (seed(&self.signer.priv_key), seed(&self.next_priv_key))
+    }
 }
 
 struct Signer {
@@ -65,24 +163,26 @@ struct Signer {
     pub pub_key: PublicKey,
 }
 
-impl Signer {
-    pub fn new() -> Self {
-        let ed = ed25519_dalek::Keypair::generate(&mut OsRng);
-        let pub_key = PublicKey::new(ed.public.to_bytes().to_vec());
-        let priv_key = PrivateKey::new(ed.secret.to_bytes().to_vec());
-
-        Signer { pub_key, priv_key }
-    }
-
-    pub fn sign(&self, msg: impl AsRef<[u8]>) -> Result<Vec<u8>, Error> {
-        self.priv_key.sign_ed(msg.as_ref())
+fn sign(key_type: Basic, key: &PrivateKey, msg: &[u8]) -> Result<Vec<u8>, Error> {
+    match key_type {
+        Basic::ECDSAsecp256k1 => key.sign_ecdsa(msg),
+        _ => key.sign_ed(msg),
     }
 }
 
-fn generate_key_pair() -> Result<(PublicKey, PrivateKey), Error> {
-    let kp = ed25519_dalek::Keypair::generate(&mut OsRng {});
-    let (vk, sk) = (kp.public, kp.secret);
-    let vk = PublicKey::new(vk.to_bytes().to_vec());
-    let sk = PrivateKey::new(sk.to_bytes().to_vec());
-    Ok((vk, sk))
+fn generate_key_pair(key_type: Basic) -> Result<(PublicKey, PrivateKey), Error> {
+    match key_type {
+        Basic::ECDSAsecp256k1 => {
+            let mut seed = [0u8; 32];
+            OsRng.fill_bytes(&mut seed);
+            SeedPrefix::RandomSeed256ECDSAsecp256k1(seed.to_vec()).derive_key_pair()
+        }
+        _ => {
+            let kp = ed25519_dalek::Keypair::generate(&mut OsRng {});
+            let (vk, sk) = (kp.public, kp.secret);
+            let vk = PublicKey::new(vk.to_bytes().to_vec());
+            let sk = PrivateKey::new(sk.to_bytes().to_vec());
+            Ok((vk, sk))
+        }
+    }
 }
@@ -0,0 +1,297 @@
+//! PKCS#11 backed key manager
+//!
+//! Keys are generated and used inside a hardware token, so private keys
+//! never leave it. Token access is provided by implementation of
+//! `Pkcs11Token`. `CryptokiToken` implements it over a session of a
+//! PKCS#11 module library, loaded with `cryptoki`.
+
+use std::path::Path;
+
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    mechanism::Mechanism,
+    object::{Attribute, AttributeType, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    types::AuthPin,
+};
+
+use super::KeyManager;
+use crate::{error::Error, keys::PublicKey};
+
+/// DER encoded OID of Ed25519 curve (1.3.101.112), used as `CKA_EC_PARAMS`.
+const ED25519_PARAMS: [u8; 5] = [0x06, 0x03, 0x2b, 0x65, 0x70];
+
+/// PKCS#11 Token
+///
+/// Minimal set of token operations needed by `Pkcs11KeyManager`. Key
+/// pairs are addressed by their `CKA_LABEL` attribute.
+pub trait Pkcs11Token {
+    /// Generates Ed25519 key pair labeled with `label` inside the token
+    /// and returns its public key.
+    fn generate_key_pair(&self, label: &str) -> Result<PublicKey, Error>;
+
+    /// Returns public key of key pair labeled with `label`.
+    fn get_public_key(&self, label: &str) -> Result<PublicKey, Error>;
+
+    /// Signs `msg` with private key of key pair labeled with `label`.
+    fn sign(&self, label: &str, msg: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// Cryptoki Token
+///
+/// Read-write session of user logged in to a token, opened with PKCS#11
+/// module library (eg. `libsofthsm2.so`). Ed25519 key pairs are stored
+/// as token objects, so they are kept between sessions.
+pub struct CryptokiToken {
+    session: Session,
+}
+
+impl CryptokiToken {
+    /// Loads PKCS#11 module from `module_path` and logs in with `pin` to
+    /// the token in `slot`-th slot with token present.
+    pub fn open(module_path: impl AsRef<Path>, slot: usize, pin: &str) -> Result<Self, Error> {
+        let pkcs11 = Pkcs11::new(module_path.as_ref())?;
+        pkcs11.initialize(CInitializeArgs::OsThreads)?;
+        let slot = *pkcs11
+            .get_slots_with_token()?
+            .get(slot)
+            .ok_or_else(|| Error::Pkcs11Error(format!("No token in slot {}", slot)))?;
+        let session = pkcs11.open_rw_session(slot)?;
+        session.login(UserType::User, Some(&AuthPin::new(pin.into())))?;
+        Ok(Self { session })
+    }
+
+    fn find_key(&self, class: ObjectClass, label: &str) -> Result<ObjectHandle, Error> {
+        self.session
+            .find_objects(&[Attribute::Class(class), Attribute::Label(label.into())])?
+            .first()
+            .copied()
+            .ok_or_else(|| Error::Pkcs11Error(format!("Missing key: {}", label)))
+    }
+
+    /// Reads `CKA_EC_POINT` of Ed25519 public key. Modules return it
+    /// either as DER encoded octet string or as raw 32 bytes.
+    fn public_key(&self, handle: ObjectHandle) -> Result<PublicKey, Error> {
+        let point = self
+            .session
+            .get_attributes(handle, &[AttributeType::EcPoint])?
+            .into_iter()
+            .find_map(|attribute| match attribute {
+                Attribute::EcPoint(point) => Some(point),
+                _ => None,
+            })
+            .ok_or_else(|| Error::Pkcs11Error("Missing public key point".into()))?;
+        let key = match point.as_slice() {
+            [0x04, 0x20, key @ ..] => key,
+            key => key,
+        };
+        if key.len() != 32 {
+            return Err(Error::Pkcs11Error("Improper Ed25519 public key".into()));
+        }
+        Ok(PublicKey::new(key.to_vec()))
+    }
+}
+
+impl Pkcs11Token for CryptokiToken {
+    fn generate_key_pair(&self, label: &str) -> Result<PublicKey, Error> {
+        let public_template = [
+            Attribute::Token(true),
+            Attribute::Verify(true),
+            Attribute::EcParams(ED25519_PARAMS.to_vec()),
+            Attribute::Label(label.into()),
+        ];
+        let private_template = [
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            Attribute::Sign(true),
+            Attribute::Label(label.into()),
+        ];
+        let (public, _) = self.session.generate_key_pair(
+            &Mechanism::EccEdwardsKeyPairGen,
+            &public_template,
+            &private_template,
+        )?;
+        self.public_key(public)
+    }
+
+    fn get_public_key(&self, label: &str) -> Result<PublicKey, Error> {
+        self.public_key(self.find_key(ObjectClass::PUBLIC_KEY, label)?)
+    }
+
+    fn sign(&self, label: &str, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let key = self.find_key(ObjectClass::PRIVATE_KEY, label)?;
+        Ok(self.session.sign(&Mechanism::Eddsa, key, msg)?)
+    }
+}
+
+/// PKCS#11 Key Manager
+///
+/// Maps keys of a single identifier to token labels `{label}-{index}`,
+/// where index is the number of rotations done. Next key pair is
+/// pre-generated inside the token on every rotation.
+pub struct Pkcs11KeyManager<T: Pkcs11Token> {
+    token: T,
+    label: String,
+    index: u64,
+    current: PublicKey,
+    next: PublicKey,
+}
+
+impl<T: Pkcs11Token> Pkcs11KeyManager<T> {
+    /// Generates current and next key pairs for identifier keys
+    /// labeled with `label`.
+    pub fn new(token: T, label: &str) -> Result<Self, Error> {
+        let current = token.generate_key_pair(&key_label(label, 0))?;
+        let next = token.generate_key_pair(&key_label(label, 1))?;
+        Ok(Self {
+            token,
+            label: label.to_string(),
+            index: 0,
+            current,
+            next,
+        })
+    }
+
+    /// Opens key pairs already stored in the token, after `index`
+    /// rotations of identifier keys labeled with `label`.
+    pub fn open(token: T, label: &str, index: u64) -> Result<Self, Error> {
+        let current = token.get_public_key(&key_label(label, index))?;
+        let next = token.get_public_key(&key_label(label, index + 1))?;
+        Ok(Self {
+            token,
+            label: label.to_string(),
+            index,
+            current,
+            next,
+        })
+    }
+
+    /// Label of current key pair in the token
+    pub fn current_label(&self) -> String {
+        key_label(&self.label, self.index)
+    }
+
+    /// Label of next key pair in the token
+    pub fn next_label(&self) -> String {
+        key_label(&self.label, self.index + 1)
+    }
+}
+
+impl<T: Pkcs11Token> KeyManager for Pkcs11KeyManager<T> {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.token.sign(&self.current_label(), msg)
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.current.clone()
+    }
+
+    fn next_public_key(&self) -> PublicKey {
+        self.next.clone()
+    }
+
+    fn sign_with_next(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.token.sign(&self.next_label(), msg)
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        let next = self
+            .token
+            .generate_key_pair(&key_label(&self.label, self.index + 2))?;
+        self.index += 1;
+        self.current = std::mem::replace(&mut self.next, next);
+        Ok(())
+    }
+}
+
+fn key_label(label: &str, index: u64) -> String {
+    format!("{}-{}", label, index)
+}
+
+#[test]
+fn test_pkcs11_key_manager() -> Result<(), Error> {
+    use crate::keys::PrivateKey;
+    use rand::rngs::OsRng;
+    use std::{cell::RefCell, collections::HashMap};
+
+    // Token keeping key pairs in memory, labeled as in PKCS#11 module.
+    #[derive(Default)]
+    struct MockToken {
+        keys: RefCell<HashMap<String, (PublicKey, PrivateKey)>>,
+    }
+
+    impl Pkcs11Token for &MockToken {
+        fn generate_key_pair(&self, label: &str) -> Result<PublicKey, Error> {
+            let kp = ed25519_dalek::Keypair::generate(&mut OsRng);
+            let pk = PublicKey::new(kp.public.to_bytes().to_vec());
+            let sk = PrivateKey::new(kp.secret.to_bytes().to_vec());
+            self.keys
+                .borrow_mut()
+                .insert(label.to_string(), (pk.clone(), sk));
+            Ok(pk)
+        }
+
+        fn get_public_key(&self, label: &str) -> Result<PublicKey, Error> {
+            self.keys
+                .borrow()
+                .get(label)
+                .map(|(pk, _)| pk.clone())
+                .ok_or_else(|| Error::Pkcs11Error(format!("Missing key: {}", label)))
+        }
+
+        fn sign(&self, label: &str, msg: &[u8]) -> Result<Vec<u8>, Error> {
+            self.keys
+                .borrow()
+                .get(label)
+                .ok_or_else(|| Error::Pkcs11Error(format!("Missing key: {}", label)))?
+                .1
+                .sign_ed(msg)
+        }
+    }
+
+    let token = MockToken::default();
+    let mut km = Pkcs11KeyManager::new(&token, "alice")?;
+    let msg = b"message";
+    assert!(km.public_key().verify_ed(msg, &km.sign(msg)?));
+    assert!(km
+        .next_public_key()
+        .verify_ed(msg, &km.sign_with_next(msg)?));
+
+    let next = km.next_public_key();
+    km.rotate()?;
+    assert_eq!(km.public_key(), next);
+    assert_eq!(km.current_label(), "alice-1");
+    // Next keys are pre-generated inside the token.
+    assert_eq!((&token).get_public_key("alice-2")?, km.next_public_key());
+
+    // Reopened key manager uses the same token keys.
+    let reopened = Pkcs11KeyManager::open(&token, "alice", 1)?;
+    assert_eq!(reopened.public_key(), km.public_key());
+    assert_eq!(reopened.next_public_key(), km.next_public_key());
+    assert!(Pkcs11KeyManager::open(&token, "bob", 0).is_err());
+
+    Ok(())
+}
+
+// Needs initialized token, eg. of SoftHSM, given by `KERI_PKCS11_MODULE`
+// and `KERI_PKCS11_PIN` variables.
+#[test]
+#[ignore]
+fn test_cryptoki_token() -> Result<(), Error> {
+    let module = std::env::var("KERI_PKCS11_MODULE").unwrap();
+    let pin = std::env::var("KERI_PKCS11_PIN").unwrap();
+    let token = CryptokiToken::open(module, 0, &pin)?;
+
+    let label = format!("keriox-test-{}", std::process::id());
+    let mut km = Pkcs11KeyManager::new(token, &label)?;
+    let msg = b"message";
+    assert!(km.public_key().verify_ed(msg, &km.sign(msg)?));
+    let next = km.next_public_key();
+    km.rotate()?;
+    assert_eq!(km.public_key(), next);
+    assert!(km.public_key().verify_ed(msg, &km.sign(msg)?));
+
+    Ok(())
+}
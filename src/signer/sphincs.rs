@@ -0,0 +1,66 @@
+use super::KeyManager;
+use crate::{
+    derivation::{basic::Basic, self_signing::SelfSigning},
+    error::Error,
+    keys::{sphincs, PrivateKey, PublicKey},
+};
+
+/// SPHINCS+ Box
+///
+/// In-memory key manager holding SPHINCS+ key pairs, for identifiers
+/// using post-quantum signatures.
+pub struct SphincsBox {
+    current: (PublicKey, PrivateKey),
+    next: (PublicKey, PrivateKey),
+}
+
+impl SphincsBox {
+    pub fn new() -> Self {
+        Self {
+            current: generate_key_pair(),
+            next: generate_key_pair(),
+        }
+    }
+}
+
+impl Default for SphincsBox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyManager for SphincsBox {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.current.1.sign_sphincs(msg)
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.current.0.clone()
+    }
+
+    fn next_public_key(&self) -> PublicKey {
+        self.next.0.clone()
+    }
+
+    fn sign_with_next(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.next.1.sign_sphincs(msg)
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        self.current = std::mem::replace(&mut self.next, generate_key_pair());
+        Ok(())
+    }
+
+    fn key_type(&self) -> Basic {
+        Basic::SphincsSha2128s
+    }
+
+    fn signature_type(&self) -> SelfSigning {
+        SelfSigning::SphincsSha2128s
+    }
+}
+
+fn generate_key_pair() -> (PublicKey, PrivateKey) {
+    let (pk, sk) = sphincs::generate_key_pair();
+    (PublicKey::new(pk), PrivateKey::new(sk))
+}
@@ -54,6 +54,10 @@ impl KeyManager for UnlockedWallet {
         }
     }
 
+    fn sign_with_next(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(UnlockedWallet::sign_raw(self, NEXT, msg)?)
+    }
+
     fn rotate(&mut self) -> Result<(), Error> {
         if self.next_public_key().key().is_empty() {
             return Err(Error::WalletError(universal_wallet::Error::KeyNotFound));
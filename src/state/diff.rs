@@ -0,0 +1,160 @@
+//! State Diff
+//!
+//! Changes between two states of identifier, eg. the one known before
+//! and after processing its new events, so agents can tell what their
+//! counterparty changed without comparing states field by field.
+
+use super::IdentifierState;
+use crate::{
+    event::sections::{key_config::NextKeysCommitment, threshold::SignatureThreshold},
+    prefix::BasicPrefix,
+};
+use alloc::vec::Vec;
+
+/// Value of field before and after the change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub from: T,
+    pub to: T,
+}
+
+impl<T: PartialEq + Clone> Change<T> {
+    fn of(from: &T, to: &T) -> Option<Self> {
+        (from != to).then(|| Change {
+            from: from.clone(),
+            to: to.clone(),
+        })
+    }
+}
+
+/// State Delta
+///
+/// Changes which turn one state of identifier into the other one.
+/// Fields which didn't change are `None` or empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDelta {
+    pub sn: Option<Change<u64>>,
+    pub added_keys: Vec<BasicPrefix>,
+    pub removed_keys: Vec<BasicPrefix>,
+    pub threshold: Option<Change<SignatureThreshold>>,
+    pub next_keys: Option<Change<Option<NextKeysCommitment>>>,
+    pub added_witnesses: Vec<BasicPrefix>,
+    pub removed_witnesses: Vec<BasicPrefix>,
+    pub witness_threshold: Option<Change<u64>>,
+}
+
+impl StateDelta {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Checks whether signing keys, their threshold or commitment to
+    /// next keys changed, ie. identifier rotated keys.
+    pub fn keys_rotated(&self) -> bool {
+        !self.added_keys.is_empty()
+            || !self.removed_keys.is_empty()
+            || self.threshold.is_some()
+            || self.next_keys.is_some()
+    }
+
+    /// Checks whether witness list or witness threshold changed.
+    pub fn witnesses_rotated(&self) -> bool {
+        !self.added_witnesses.is_empty()
+            || !self.removed_witnesses.is_empty()
+            || self.witness_threshold.is_some()
+    }
+
+    /// Number of events between states, negative if the other state is
+    /// older.
+    pub fn sn_movement(&self) -> i128 {
+        self.sn
+            .as_ref()
+            .map_or(0, |sn| sn.to as i128 - sn.from as i128)
+    }
+}
+
+impl IdentifierState {
+    /// Diff
+    ///
+    /// Returns changes between this and `other` state of the same
+    /// identifier, from this one to the other.
+    pub fn diff(&self, other: &IdentifierState) -> StateDelta {
+        let missing = |list: &[BasicPrefix], from: &[BasicPrefix]| {
+            list.iter()
+                .filter(|prefix| !from.contains(prefix))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let (keys, other_keys) = (&self.current.public_keys, &other.current.public_keys);
+        StateDelta {
+            sn: Change::of(&self.sn, &other.sn),
+            added_keys: missing(other_keys, keys),
+            removed_keys: missing(keys, other_keys),
+            threshold: Change::of(&self.current.threshold, &other.current.threshold),
+            next_keys: Change::of(
+                &self.current.threshold_key_digest,
+                &other.current.threshold_key_digest,
+            ),
+            added_witnesses: missing(&other.witnesses, &self.witnesses),
+            removed_witnesses: missing(&self.witnesses, &other.witnesses),
+            witness_threshold: Change::of(&self.tally, &other.tally),
+        }
+    }
+}
+
+#[test]
+fn test_state_diff() -> Result<(), crate::error::Error> {
+    use crate::{event::sections::KeyConfig, prefix::SelfAddressingPrefix};
+
+    let prefixes: Vec<BasicPrefix> = vec![
+        "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?,
+        "DVcuJOOJF1IE8svqEtrSuyQjGTd2HhfAkt9y2QkUtFJI".parse()?,
+        "BGKVzj4ve0VSd8z_AmvhLg4lqcC_9WYX90k03q-R_Ydo".parse()?,
+        "BuyRFMideczFZoapylLIyCjSdhtqVb31wZkRKvPfNqkw".parse()?,
+    ];
+    let next: SelfAddressingPrefix = "E9izzBkXX76sqt0N-tfLzJeRqj0W56p4pDQ_ZqNCDpyw".parse()?;
+    let state = IdentifierState {
+        sn: 2,
+        current: KeyConfig::new(vec![prefixes[0].clone()], Some(next.clone()), None),
+        witnesses: vec![prefixes[2].clone()],
+        tally: 1,
+        ..IdentifierState::default()
+    };
+    assert!(state.diff(&state).is_empty());
+
+    // Interaction moves sn only.
+    let interacted = IdentifierState {
+        sn: 3,
+        ..state.clone()
+    };
+    let delta = state.diff(&interacted);
+    assert_eq!(delta.sn_movement(), 1);
+    assert!(!delta.keys_rotated() && !delta.witnesses_rotated());
+    assert_eq!(interacted.diff(&state).sn_movement(), -1);
+
+    // Rotation of keys and witnesses.
+    let rotated = IdentifierState {
+        sn: 4,
+        current: KeyConfig::new(vec![prefixes[1].clone()], None, None),
+        witnesses: vec![prefixes[2].clone(), prefixes[3].clone()],
+        tally: 2,
+        ..state.clone()
+    };
+    let delta = state.diff(&rotated);
+    assert!(delta.keys_rotated() && delta.witnesses_rotated());
+    assert_eq!(delta.added_keys, vec![prefixes[1].clone()]);
+    assert_eq!(delta.removed_keys, vec![prefixes[0].clone()]);
+    assert_eq!(delta.threshold, None);
+    assert_eq!(
+        delta.next_keys,
+        Some(Change {
+            from: state.current.threshold_key_digest.clone(),
+            to: None
+        })
+    );
+    assert_eq!(delta.added_witnesses, vec![prefixes[3].clone()]);
+    assert!(delta.removed_witnesses.is_empty());
+    assert_eq!(delta.witness_threshold, Some(Change { from: 1, to: 2 }));
+
+    Ok(())
+}
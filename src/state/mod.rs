@@ -1,15 +1,25 @@
 use crate::{
     error::Error,
-    event::{event_data::EventData, sections::KeyConfig},
+    event::{
+        event_data::EventData,
+        sections::{
+            seal::{BackerSeal, Seal},
+            ConfigTrait, KeyConfig,
+        },
+    },
     event_message::EventTypeTag,
-    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
+    prefix::{AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
 };
-use serde::{Deserialize, Serialize};
-use serde_hex::{Compact, SerHex};
+#[cfg(feature = "query")]
+use crate::{event::SerializationFormats, query::key_state_notice::KeyStateNotice};
+use alloc::{string::String, vec::Vec};
+use serde::{Deserialize, Deserializer, Serialize};
+
+pub mod diff;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct LastEstablishmentData {
-    #[serde(rename = "s", with = "SerHex::<Compact>")]
+    #[serde(rename = "s", with = "crate::hex")]
     pub(crate) sn: u64,
     #[serde(rename = "d")]
     pub(crate) digest: SelfAddressingPrefix,
@@ -25,7 +35,7 @@ pub struct IdentifierState {
     #[serde(rename = "i")]
     pub prefix: IdentifierPrefix,
 
-    #[serde(rename = "s", with = "SerHex::<Compact>")]
+    #[serde(rename = "s", with = "crate::hex")]
     pub sn: u64,
 
     #[serde(rename = "d")]
@@ -40,19 +50,38 @@ pub struct IdentifierState {
     #[serde(flatten)]
     pub current: KeyConfig,
 
-    #[serde(rename = "bt", with = "SerHex::<Compact>")]
+    #[serde(rename = "bt", with = "crate::hex")]
     pub tally: u64,
 
     #[serde(rename = "b")]
     pub witnesses: Vec<BasicPrefix>,
 
-    #[serde(rename = "di")]
+    #[serde(rename = "di", default, deserialize_with = "empty_string_as_none")]
     pub delegator: Option<IdentifierPrefix>,
 
+    #[serde(rename = "c", default)]
+    pub config: Vec<ConfigTrait>,
+
+    /// Current registrar backer, if identifier uses one.
+    #[serde(rename = "rb", default, skip_serializing_if = "Option::is_none")]
+    pub backer: Option<BackerSeal>,
+
     #[serde(rename = "ee")]
     pub last_est: LastEstablishmentData,
 }
 
+/// Key state notices write a missing delegator as an empty string.
+fn empty_string_as_none<'d, D>(de: D) -> Result<Option<IdentifierPrefix>, D::Error>
+where
+    D: Deserializer<'d>,
+{
+    match Option::<String>::deserialize(de)? {
+        None => Ok(None),
+        Some(s) if s.is_empty() => Ok(None),
+        Some(s) => s.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
 impl EventTypeTag {
     pub fn is_establishment_event(&self) -> bool {
         matches!(
@@ -81,6 +110,86 @@ impl IdentifierState {
     pub fn apply<T: EventSemantics>(self, event: &T) -> Result<Self, Error> {
         event.apply_to(self)
     }
+
+    pub fn has_config_trait(&self, config_trait: &ConfigTrait) -> bool {
+        self.config.contains(config_trait)
+    }
+
+    /// Applies backer seal from data of establishment event, which
+    /// designates new registrar backer. Backer seals are allowed only in
+    /// KELs of identifiers incepted with registrar backers trait.
+    pub(crate) fn apply_backer_seals(self, data: &[Seal]) -> Result<Self, Error> {
+        let seals = data
+            .iter()
+            .filter_map(|seal| match seal {
+                Seal::Backer(seal) => Some(seal),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        match seals.as_slice() {
+            [] => Ok(self),
+            _ if !self.has_config_trait(&ConfigTrait::RegistrarBackers) => Err(
+                Error::SemanticError("Backer seal of identifier without registrar backers".into()),
+            ),
+            [seal] => Ok(IdentifierState {
+                backer: Some((*seal).clone()),
+                ..self
+            }),
+            _ => Err(Error::SemanticError(
+                "More than one backer seal in event".into(),
+            )),
+        }
+    }
+
+    /// Verify Signatures
+    ///
+    /// Verifies signatures of the last event of `new` state, which is
+    /// this state with the event applied. Rotation has to be signed to
+    /// both its own threshold and next threshold committed before.
+    pub fn verify_signatures(
+        &self,
+        new: &IdentifierState,
+        message: &[u8],
+        sigs: &[AttachedSignaturePrefix],
+    ) -> Result<bool, Error> {
+        match new.last_event_type {
+            Some(EventTypeTag::Rot) | Some(EventTypeTag::Drt) => {
+                self.current.verify_rotation(&new.current, message, sigs)
+            }
+            _ => new.current.verify(message, sigs),
+        }
+    }
+
+    /// Is Abandoned
+    ///
+    /// Transferable identifier which rotated to null next keys, ie.
+    /// committed to no next keys, can't establish any more events.
+    pub fn is_abandoned(&self) -> bool {
+        self.prefix.is_transferable() && self.current.threshold_key_digest.is_none()
+    }
+
+    /// Witnesses At Sn
+    ///
+    /// Returns witnesses authorized to receipt event of given sn.
+    /// Witness list is established by last establishment event, so
+    /// it can be determined only for events which were not followed
+    /// by witness rotation, that is from last establishment event up
+    /// to the current sn. For earlier events compute state at given
+    /// sn first.
+    pub fn witnesses_at_sn(&self, sn: u64) -> Option<&[BasicPrefix]> {
+        if sn >= self.last_est.sn && sn <= self.sn {
+            Some(&self.witnesses)
+        } else {
+            None
+        }
+    }
+
+    /// Key state notice of the state, to be sent in reply to key state
+    /// query.
+    #[cfg(feature = "query")]
+    pub fn to_ksn(&self, format: SerializationFormats) -> KeyStateNotice {
+        KeyStateNotice::new_ksn(self.clone(), format)
+    }
 }
 
 /// EventSemantics
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use crate::{
+    database::sled::tables::{SledEventTree, SledEventTreeVec},
+    error::Error,
+    prefix::IdentifierPrefix,
+};
+
+use super::event::VerifiableTelEvent;
+
+/// Sled TEL Database
+///
+/// Keeps accepted TEL events, of both registries and credentials,
+/// separately from KELs.
+pub struct SledTelDatabase {
+    // "tids" tree
+    identifiers: SledEventTree<IdentifierPrefix>,
+    // "tels" tree
+    transaction_event_logs: SledEventTreeVec<VerifiableTelEvent>,
+}
+
+impl SledTelDatabase {
+    pub fn new<'a, P>(path: P) -> Result<Self, Error>
+    where
+        P: Into<&'a Path>,
+    {
+        let db = sled::open(path.into())?;
+        Ok(Self {
+            identifiers: SledEventTree::new(db.open_tree(b"tids")?),
+            transaction_event_logs: SledEventTreeVec::new(db.open_tree(b"tels")?),
+        })
+    }
+
+    pub fn add_event(&self, event: VerifiableTelEvent, id: &IdentifierPrefix) -> Result<(), Error> {
+        self.transaction_event_logs
+            .push(self.identifiers.designated_key(id), event)
+    }
+
+    pub fn get_events(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Option<impl DoubleEndedIterator<Item = VerifiableTelEvent>> {
+        self.transaction_event_logs
+            .iter_values(self.identifiers.designated_key(id))
+    }
+}
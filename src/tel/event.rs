@@ -0,0 +1,425 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat, SubsecRound, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{
+    derivation::self_addressing::SelfAddressing,
+    error::Error,
+    event::{
+        sections::seal::{EventSeal, SourceSeal},
+        EventMessage, SerializationFormats,
+    },
+    event_message::{
+        dummy_event::dummy_prefix, serialization_info::SerializationInfo, EventTypeTag, SaidEvent,
+        Typeable,
+    },
+    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
+    sad,
+};
+
+use super::state::{ManagerTelState, VcStatus, VcTelState};
+
+/// Registry configuration trait which disallows backers.
+pub const NO_BACKERS: &str = "NB";
+
+/// Transaction
+///
+/// Content of TEL event. Prefix is the registry identifier for
+/// management events and the credential SAID for credential events.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transaction {
+    #[serde(rename = "i")]
+    pub prefix: IdentifierPrefix,
+
+    #[serde(rename = "s", with = "crate::hex")]
+    pub sn: u64,
+
+    #[serde(flatten)]
+    pub data: TransactionData,
+}
+
+impl Typeable for Transaction {
+    fn get_type(&self) -> EventTypeTag {
+        self.data.get_type()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TransactionData {
+    Vcp(ManagerInception),
+    Vrt(ManagerRotation),
+    Iss(Issuance),
+    Rev(Revocation),
+    Bis(BackerIssuance),
+    Brv(BackerRevocation),
+}
+
+impl<'de> Deserialize<'de> for TransactionData {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Helper struct for adding tag to properly deserialize 't' field
+        #[derive(Deserialize)]
+        struct EventType {
+            t: EventTypeTag,
+        }
+
+        let v = Value::deserialize(deserializer)?;
+        let m = EventType::deserialize(&v).map_err(de::Error::custom)?;
+        match m.t {
+            EventTypeTag::Vcp => Ok(TransactionData::Vcp(
+                ManagerInception::deserialize(&v).map_err(de::Error::custom)?,
+            )),
+            EventTypeTag::Vrt => Ok(TransactionData::Vrt(
+                ManagerRotation::deserialize(&v).map_err(de::Error::custom)?,
+            )),
+            EventTypeTag::Iss => Ok(TransactionData::Iss(
+                Issuance::deserialize(&v).map_err(de::Error::custom)?,
+            )),
+            EventTypeTag::Rev => Ok(TransactionData::Rev(
+                Revocation::deserialize(&v).map_err(de::Error::custom)?,
+            )),
+            EventTypeTag::Bis => Ok(TransactionData::Bis(
+                BackerIssuance::deserialize(&v).map_err(de::Error::custom)?,
+            )),
+            EventTypeTag::Brv => Ok(TransactionData::Brv(
+                BackerRevocation::deserialize(&v).map_err(de::Error::custom)?,
+            )),
+            _ => Err(de::Error::custom(Error::SemanticError(
+                "Not a TEL event".into(),
+            ))),
+        }
+    }
+}
+
+impl Typeable for TransactionData {
+    fn get_type(&self) -> EventTypeTag {
+        match self {
+            TransactionData::Vcp(_) => EventTypeTag::Vcp,
+            TransactionData::Vrt(_) => EventTypeTag::Vrt,
+            TransactionData::Iss(_) => EventTypeTag::Iss,
+            TransactionData::Rev(_) => EventTypeTag::Rev,
+            TransactionData::Bis(_) => EventTypeTag::Bis,
+            TransactionData::Brv(_) => EventTypeTag::Brv,
+        }
+    }
+}
+
+/// Registry inception (vcp) data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManagerInception {
+    #[serde(rename = "ii")]
+    pub issuer: IdentifierPrefix,
+
+    #[serde(rename = "c")]
+    pub config: Vec<String>,
+
+    #[serde(rename = "bt", with = "crate::hex")]
+    pub backer_threshold: u64,
+
+    #[serde(rename = "b")]
+    pub backers: Vec<BasicPrefix>,
+}
+
+/// Registry rotation (vrt) data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManagerRotation {
+    #[serde(rename = "p")]
+    pub previous_event_hash: SelfAddressingPrefix,
+
+    #[serde(rename = "bt", with = "crate::hex")]
+    pub backer_threshold: u64,
+
+    #[serde(rename = "br")]
+    pub backers_to_remove: Vec<BasicPrefix>,
+
+    #[serde(rename = "ba")]
+    pub backers_to_add: Vec<BasicPrefix>,
+}
+
+/// Backerless credential issuance (iss) data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Issuance {
+    #[serde(rename = "ri")]
+    pub registry_id: IdentifierPrefix,
+
+    #[serde(rename = "dt", serialize_with = "serialize_timestamp")]
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+/// Backerless credential revocation (rev) data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revocation {
+    #[serde(rename = "ri")]
+    pub registry_id: IdentifierPrefix,
+
+    #[serde(rename = "p")]
+    pub previous_event_hash: SelfAddressingPrefix,
+
+    #[serde(rename = "dt", serialize_with = "serialize_timestamp")]
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+/// Credential issuance with backers (bis) data
+///
+/// Registry anchor points to the management event which designates
+/// backers in force for the issuance.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackerIssuance {
+    #[serde(rename = "ra")]
+    pub registry_anchor: EventSeal,
+
+    #[serde(rename = "dt", serialize_with = "serialize_timestamp")]
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+/// Credential revocation with backers (brv) data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackerRevocation {
+    #[serde(rename = "p")]
+    pub previous_event_hash: SelfAddressingPrefix,
+
+    #[serde(rename = "ra")]
+    pub registry_anchor: EventSeal,
+
+    #[serde(rename = "dt", serialize_with = "serialize_timestamp")]
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+fn serialize_timestamp<S>(timestamp: &DateTime<FixedOffset>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(&timestamp.to_rfc3339_opts(SecondsFormat::Micros, false))
+}
+
+fn now() -> DateTime<FixedOffset> {
+    Utc::now().trunc_subsecs(6).into()
+}
+
+pub type TelEvent = SaidEvent<Transaction>;
+
+impl TelEvent {
+    /// Registry inception
+    ///
+    /// Registry identifier is self-addressing: it's digest of the vcp
+    /// event, computed with both `i` and `d` fields dummied, the same way
+    /// as for KEL inception events.
+    pub fn new_registry_inception(
+        issuer: &IdentifierPrefix,
+        config: Vec<String>,
+        backer_threshold: u64,
+        backers: Vec<BasicPrefix>,
+        format: SerializationFormats,
+        derivation: &SelfAddressing,
+    ) -> Result<EventMessage<Self>, Error> {
+        let vcp = ManagerInception {
+            issuer: issuer.clone(),
+            config,
+            backer_threshold,
+            backers,
+        };
+        if vcp.config.iter().any(|c| c == NO_BACKERS) && !vcp.backers.is_empty() {
+            return Err(Error::SemanticError(
+                "Backers in registry without backers".into(),
+            ));
+        }
+        let (serialization_info, registry_id) = sad::compute_versioned_said(
+            |size| SerializationInfo::new(format, size),
+            Some(EventTypeTag::Vcp),
+            &DummyRegistryInception::new(&vcp, derivation),
+            derivation,
+            format,
+        )?;
+        Ok(EventMessage {
+            serialization_info,
+            event: SaidEvent::new(
+                registry_id.clone(),
+                Transaction {
+                    prefix: IdentifierPrefix::SelfAddressing(registry_id),
+                    sn: 0,
+                    data: TransactionData::Vcp(vcp),
+                },
+            ),
+        })
+    }
+
+    pub fn new_registry_rotation(
+        registry: &ManagerTelState,
+        backer_threshold: u64,
+        backers_to_remove: Vec<BasicPrefix>,
+        backers_to_add: Vec<BasicPrefix>,
+        format: SerializationFormats,
+        derivation: &SelfAddressing,
+    ) -> Result<EventMessage<Self>, Error> {
+        let vrt = Transaction {
+            prefix: registry.prefix.clone(),
+            sn: registry.sn + 1,
+            data: TransactionData::Vrt(ManagerRotation {
+                previous_event_hash: registry.last.clone(),
+                backer_threshold,
+                backers_to_remove,
+                backers_to_add,
+            }),
+        };
+        SaidEvent::<Transaction>::to_message(vrt, format, derivation)
+    }
+
+    /// Credential issuance
+    ///
+    /// Makes iss event for registry without backers or bis event
+    /// otherwise.
+    pub fn new_issuance(
+        registry: &ManagerTelState,
+        vc_id: &SelfAddressingPrefix,
+        format: SerializationFormats,
+        derivation: &SelfAddressing,
+    ) -> Result<EventMessage<Self>, Error> {
+        let data = match registry.backers {
+            None => TransactionData::Iss(Issuance {
+                registry_id: registry.prefix.clone(),
+                timestamp: now(),
+            }),
+            Some(_) => TransactionData::Bis(BackerIssuance {
+                registry_anchor: registry.to_seal(),
+                timestamp: now(),
+            }),
+        };
+        let iss = Transaction {
+            prefix: IdentifierPrefix::SelfAddressing(vc_id.clone()),
+            sn: 0,
+            data,
+        };
+        SaidEvent::<Transaction>::to_message(iss, format, derivation)
+    }
+
+    /// Credential revocation
+    ///
+    /// Makes rev event for registry without backers or brv event
+    /// otherwise.
+    pub fn new_revocation(
+        registry: &ManagerTelState,
+        vc: &VcTelState,
+        format: SerializationFormats,
+        derivation: &SelfAddressing,
+    ) -> Result<EventMessage<Self>, Error> {
+        if vc.status != VcStatus::Issued {
+            return Err(Error::SemanticError("Credential is not issued".into()));
+        }
+        let data = match registry.backers {
+            None => TransactionData::Rev(Revocation {
+                registry_id: registry.prefix.clone(),
+                previous_event_hash: vc.last.clone(),
+                timestamp: now(),
+            }),
+            Some(_) => TransactionData::Brv(BackerRevocation {
+                previous_event_hash: vc.last.clone(),
+                registry_anchor: registry.to_seal(),
+                timestamp: now(),
+            }),
+        };
+        let rev = Transaction {
+            prefix: vc.prefix.clone(),
+            sn: vc.sn + 1,
+            data,
+        };
+        SaidEvent::<Transaction>::to_message(rev, format, derivation)
+    }
+
+    pub fn get_prefix(&self) -> IdentifierPrefix {
+        self.content.prefix.clone()
+    }
+
+    pub fn get_sn(&self) -> u64 {
+        self.content.sn
+    }
+
+    pub fn get_data(&self) -> TransactionData {
+        self.content.data.clone()
+    }
+
+    pub fn is_management_event(&self) -> bool {
+        matches!(
+            self.content.data,
+            TransactionData::Vcp(_) | TransactionData::Vrt(_)
+        )
+    }
+}
+
+impl EventMessage<TelEvent> {
+    /// Returns seal which should be anchored in issuer KEL.
+    pub fn to_seal(&self) -> EventSeal {
+        EventSeal {
+            prefix: self.event.get_prefix(),
+            sn: self.event.get_sn(),
+            event_digest: self.get_digest(),
+        }
+    }
+
+    pub fn check_digest(&self) -> Result<bool, Error> {
+        let digest = self.get_digest();
+        let (version, message_type, format) = (
+            &self.serialization_info,
+            Some(self.event.get_type()),
+            self.serialization_info.kind,
+        );
+        match self.event.get_data() {
+            TransactionData::Vcp(vcp) => sad::verify_versioned_said(
+                version,
+                message_type,
+                &digest,
+                &DummyRegistryInception::new(&vcp, &digest.derivation),
+                format,
+            ),
+            _ => sad::verify_versioned_said(
+                version,
+                message_type,
+                &digest,
+                &self.event.content,
+                format,
+            ),
+        }
+    }
+}
+
+/// Verifiable TEL Event
+///
+/// TEL event with source seal which points to issuer KEL event
+/// anchoring it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VerifiableTelEvent {
+    pub event: EventMessage<TelEvent>,
+    pub seal: SourceSeal,
+}
+
+impl VerifiableTelEvent {
+    pub fn new(event: EventMessage<TelEvent>, seal: SourceSeal) -> Self {
+        Self { event, seal }
+    }
+}
+
+/// Dummy Registry Inception
+///
+/// Fields of vcp event following its digest, with registry identifier
+/// dummied. Used only to encapsulate the registry identifier derivation
+#[derive(Serialize)]
+struct DummyRegistryInception<'a> {
+    #[serde(rename = "i")]
+    prefix: String,
+    #[serde(rename = "s", with = "crate::hex")]
+    sn: u8,
+    #[serde(flatten)]
+    data: &'a ManagerInception,
+}
+
+impl<'a> DummyRegistryInception<'a> {
+    fn new(vcp: &'a ManagerInception, derivation: &SelfAddressing) -> Self {
+        Self {
+            prefix: dummy_prefix(derivation),
+            sn: 0,
+            data: vcp,
+        }
+    }
+}
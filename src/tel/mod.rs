@@ -0,0 +1,12 @@
+//! Transaction Event Log
+//!
+//! Registry management (vcp, vrt) and credential (iss, rev, bis, brv)
+//! events. Each TEL event is anchored in issuer KEL by event seal
+//! and carries source seal which points to the anchoring KEL event.
+
+pub mod database;
+pub mod event;
+pub mod processor;
+pub mod state;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use crate::{
+    database::sled::SledEventDatabase,
+    error::Error,
+    event::{event_data::EventData, sections::seal::Seal},
+    prefix::IdentifierPrefix,
+    processor::EventProcessor,
+};
+
+use super::{
+    database::SledTelDatabase,
+    event::{TransactionData, VerifiableTelEvent},
+    state::{ManagerTelState, VcTelState},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TelState {
+    Management(ManagerTelState),
+    Vc(VcTelState),
+}
+
+/// TEL Processor
+///
+/// Validates TEL events against issuer KEL and keeps accepted ones
+/// in TEL database. Issuer KEL has to be already processed into KEL
+/// database, otherwise `EventOutOfOrderError` is returned.
+pub struct TelProcessor {
+    kel_processor: EventProcessor,
+    pub db: Arc<SledTelDatabase>,
+}
+
+impl TelProcessor {
+    pub fn new(kel_db: Arc<SledEventDatabase>, tel_db: Arc<SledTelDatabase>) -> Self {
+        Self {
+            kel_processor: EventProcessor::new(kel_db),
+            db: tel_db,
+        }
+    }
+
+    /// Process
+    ///
+    /// Checks event digest, its anchor in issuer KEL and applies
+    /// it to current TEL state. Returns updated state.
+    pub fn process(&self, event: VerifiableTelEvent) -> Result<TelState, Error> {
+        if !event.event.check_digest()? {
+            return Err(Error::IncorrectDigest);
+        }
+        let id = event.event.event.get_prefix();
+        let (issuer, new_state) = match event.event.event.get_data() {
+            TransactionData::Vcp(vcp) => {
+                if self.get_management_tel_state(&id)?.is_some() {
+                    return Err(Error::EventDuplicateError);
+                }
+                (
+                    vcp.issuer,
+                    TelState::Management(ManagerTelState::incept(&event.event)?),
+                )
+            }
+            TransactionData::Vrt(_) => {
+                let state = self
+                    .get_management_tel_state(&id)?
+                    .ok_or(Error::EventOutOfOrderError)?;
+                (
+                    state.issuer.clone(),
+                    TelState::Management(state.apply(&event.event)?),
+                )
+            }
+            TransactionData::Iss(_)
+            | TransactionData::Rev(_)
+            | TransactionData::Bis(_)
+            | TransactionData::Brv(_) => {
+                let registry_id = match event.event.event.get_data() {
+                    TransactionData::Iss(iss) => iss.registry_id,
+                    TransactionData::Rev(rev) => rev.registry_id,
+                    TransactionData::Bis(bis) => bis.registry_anchor.prefix,
+                    TransactionData::Brv(brv) => brv.registry_anchor.prefix,
+                    _ => unreachable!(),
+                };
+                let registry = self
+                    .get_management_tel_state(&registry_id)?
+                    .ok_or(Error::EventOutOfOrderError)?;
+                let state = self.get_vc_state(&id)?;
+                (
+                    registry.issuer.clone(),
+                    TelState::Vc(VcTelState::apply(state.as_ref(), &registry, &event.event)?),
+                )
+            }
+        };
+        self.validate_anchor(&issuer, &event)?;
+        self.db.add_event(event, &id)?;
+        Ok(new_state)
+    }
+
+    /// Checks if issuer KEL event pointed by source seal
+    /// anchors seal of the TEL event.
+    pub(crate) fn validate_anchor(
+        &self,
+        issuer: &IdentifierPrefix,
+        event: &VerifiableTelEvent,
+    ) -> Result<(), Error> {
+        let anchoring = self
+            .kel_processor
+            .get_event_at_sn(issuer, event.seal.sn)?
+            .ok_or(Error::EventOutOfOrderError)?
+            .signed_event_message
+            .event_message;
+        if anchoring.get_digest() != event.seal.digest {
+            return Err(Error::SemanticError(
+                "Source seal doesn't match issuer event".into(),
+            ));
+        }
+        let data = match anchoring.event.get_event_data() {
+            EventData::Rot(rot) | EventData::Drt(rot) => rot.data,
+            EventData::Ixn(ixn) => ixn.data,
+            _ => return Err(Error::SemanticError("Improper event type".into())),
+        };
+        let tel_seal = event.event.to_seal();
+        if data
+            .iter()
+            .any(|seal| matches!(seal, Seal::Event(es) if es == &tel_seal))
+        {
+            Ok(())
+        } else {
+            Err(Error::SemanticError(
+                "Data field doesn't contain TEL event seal.".into(),
+            ))
+        }
+    }
+
+    pub fn get_management_tel_state(
+        &self,
+        id: &IdentifierPrefix,
+    ) -> Result<Option<ManagerTelState>, Error> {
+        let mut events = match self.db.get_events(id) {
+            Some(events) => events.filter(|ev| ev.event.event.is_management_event()),
+            None => return Ok(None),
+        };
+        match events.next() {
+            Some(vcp) => events
+                .try_fold(ManagerTelState::incept(&vcp.event)?, |state, ev| {
+                    state.apply(&ev.event)
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_vc_state(&self, id: &IdentifierPrefix) -> Result<Option<VcTelState>, Error> {
+        let mut events = match self.db.get_events(id) {
+            Some(events) => events.filter(|ev| !ev.event.event.is_management_event()),
+            None => return Ok(None),
+        };
+        events.try_fold(None, |state: Option<VcTelState>, ev| {
+            let registry_id = match ev.event.event.get_data() {
+                TransactionData::Iss(iss) => iss.registry_id,
+                TransactionData::Rev(rev) => rev.registry_id,
+                TransactionData::Bis(bis) => bis.registry_anchor.prefix,
+                TransactionData::Brv(brv) => brv.registry_anchor.prefix,
+                _ => return Err(Error::SemanticError("Not a credential event".into())),
+            };
+            let registry = self
+                .get_management_tel_state(&registry_id)?
+                .ok_or(Error::EventOutOfOrderError)?;
+            VcTelState::apply(state.as_ref(), &registry, &ev.event).map(Some)
+        })
+    }
+
+    /// Returns all accepted TEL events of given registry or credential.
+    pub fn get_tel(&self, id: &IdentifierPrefix) -> Vec<VerifiableTelEvent> {
+        self.db.get_events(id).into_iter().flatten().collect()
+    }
+}
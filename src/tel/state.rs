@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    event::{sections::seal::EventSeal, EventMessage},
+    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
+};
+
+use super::event::{TelEvent, TransactionData, NO_BACKERS};
+
+/// Management TEL State
+///
+/// State of the credential registry. Backers are `None` when
+/// registry was incepted with `NB` configuration trait.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManagerTelState {
+    pub prefix: IdentifierPrefix,
+    pub sn: u64,
+    pub last: SelfAddressingPrefix,
+    pub issuer: IdentifierPrefix,
+    pub backers: Option<Vec<BasicPrefix>>,
+    pub backer_threshold: u64,
+}
+
+impl ManagerTelState {
+    pub fn incept(event: &EventMessage<TelEvent>) -> Result<Self, Error> {
+        match event.event.get_data() {
+            TransactionData::Vcp(vcp) if event.event.get_sn() == 0 => Ok(Self {
+                prefix: event.event.get_prefix(),
+                sn: 0,
+                last: event.get_digest(),
+                issuer: vcp.issuer,
+                backers: if vcp.config.iter().any(|c| c == NO_BACKERS) {
+                    None
+                } else {
+                    Some(vcp.backers)
+                },
+                backer_threshold: vcp.backer_threshold,
+            }),
+            TransactionData::Vcp(_) => Err(Error::SemanticError("Improper vcp sn".into())),
+            _ => Err(Error::EventOutOfOrderError),
+        }
+    }
+
+    pub fn apply(&self, event: &EventMessage<TelEvent>) -> Result<Self, Error> {
+        let vrt = match event.event.get_data() {
+            TransactionData::Vrt(vrt) => vrt,
+            TransactionData::Vcp(_) => return Err(Error::EventDuplicateError),
+            _ => return Err(Error::SemanticError("Not a management event".into())),
+        };
+        if event.event.get_prefix() != self.prefix {
+            return Err(Error::SemanticError("Improper registry identifier".into()));
+        }
+        if event.event.get_sn() <= self.sn {
+            return Err(Error::EventDuplicateError);
+        }
+        if event.event.get_sn() > self.sn + 1 {
+            return Err(Error::EventOutOfOrderError);
+        }
+        if vrt.previous_event_hash != self.last {
+            return Err(Error::SemanticError(
+                "Last event does not match previous event".into(),
+            ));
+        }
+        let backers = self
+            .backers
+            .as_ref()
+            .ok_or_else(|| Error::SemanticError("Registry can't have backers".into()))?;
+        if vrt
+            .backers_to_remove
+            .iter()
+            .any(|backer| !backers.contains(backer))
+        {
+            return Err(Error::SemanticError("Removing unknown backer".into()));
+        }
+        let backers: Vec<BasicPrefix> = backers
+            .iter()
+            .filter(|backer| !vrt.backers_to_remove.contains(backer))
+            .chain(vrt.backers_to_add.iter())
+            .cloned()
+            .collect();
+        if vrt.backer_threshold > backers.len() as u64 {
+            return Err(Error::SemanticError("Improper backer threshold".into()));
+        }
+        Ok(Self {
+            sn: event.event.get_sn(),
+            last: event.get_digest(),
+            backers: Some(backers),
+            backer_threshold: vrt.backer_threshold,
+            ..self.clone()
+        })
+    }
+
+    /// Returns seal of the last management event, used as registry
+    /// anchor in credential events of registry with backers.
+    pub fn to_seal(&self) -> EventSeal {
+        EventSeal {
+            prefix: self.prefix.clone(),
+            sn: self.sn,
+            event_digest: self.last.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum VcStatus {
+    Issued,
+    Revoked,
+}
+
+/// Credential TEL State
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VcTelState {
+    pub prefix: IdentifierPrefix,
+    pub registry_id: IdentifierPrefix,
+    pub sn: u64,
+    pub last: SelfAddressingPrefix,
+    pub status: VcStatus,
+}
+
+impl VcTelState {
+    /// Applies credential event to the state. `registry` is the
+    /// current state of registry which credential belongs to.
+    pub fn apply(
+        state: Option<&Self>,
+        registry: &ManagerTelState,
+        event: &EventMessage<TelEvent>,
+    ) -> Result<Self, Error> {
+        let (registry_id, previous, status) = match (event.event.get_data(), &registry.backers) {
+            (TransactionData::Iss(iss), None) => (iss.registry_id, None, VcStatus::Issued),
+            (TransactionData::Rev(rev), None) => (
+                rev.registry_id,
+                Some(rev.previous_event_hash),
+                VcStatus::Revoked,
+            ),
+            (TransactionData::Bis(bis), Some(_)) => {
+                (bis.registry_anchor.prefix, None, VcStatus::Issued)
+            }
+            (TransactionData::Brv(brv), Some(_)) => (
+                brv.registry_anchor.prefix,
+                Some(brv.previous_event_hash),
+                VcStatus::Revoked,
+            ),
+            (TransactionData::Vcp(_), _) | (TransactionData::Vrt(_), _) => {
+                return Err(Error::SemanticError("Not a credential event".into()))
+            }
+            _ => {
+                return Err(Error::SemanticError(
+                    "Event type doesn't match registry backers configuration".into(),
+                ))
+            }
+        };
+        if registry_id != registry.prefix {
+            return Err(Error::SemanticError("Improper registry identifier".into()));
+        }
+        match (state, previous) {
+            (None, None) if event.event.get_sn() == 0 => Ok(Self {
+                prefix: event.event.get_prefix(),
+                registry_id,
+                sn: 0,
+                last: event.get_digest(),
+                status,
+            }),
+            (None, _) => Err(Error::EventOutOfOrderError),
+            (Some(_), None) => Err(Error::EventDuplicateError),
+            (Some(state), Some(previous)) => {
+                if state.status == VcStatus::Revoked {
+                    Err(Error::SemanticError("Credential already revoked".into()))
+                } else if event.event.get_sn() != state.sn + 1 {
+                    Err(Error::EventOutOfOrderError)
+                } else if previous != state.last || registry_id != state.registry_id {
+                    Err(Error::SemanticError(
+                        "Last event does not match previous event".into(),
+                    ))
+                } else {
+                    Ok(Self {
+                        sn: event.event.get_sn(),
+                        last: event.get_digest(),
+                        status,
+                        ..state.clone()
+                    })
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use tempfile::Builder;
+
+use crate::{
+    database::sled::SledEventDatabase,
+    derivation::{basic::Basic, self_addressing::SelfAddressing, self_signing::SelfSigning},
+    error::Error,
+    event::{
+        sections::seal::{Seal, SourceSeal},
+        EventMessage, SerializationFormats,
+    },
+    event_message::{
+        event_msg_builder::EventMsgBuilder, signed_event_message::Message, EventTypeTag,
+    },
+    prefix::{AttachedSignaturePrefix, IdentifierPrefix},
+    processor::EventProcessor,
+    signer::{CryptoBox, KeyManager},
+};
+
+use super::{
+    database::SledTelDatabase,
+    event::{TelEvent, VerifiableTelEvent, NO_BACKERS},
+    processor::{TelProcessor, TelState},
+    state::VcStatus,
+};
+
+#[test]
+fn test_tel_processing() -> Result<(), Error> {
+    let root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let kel_db = Arc::new(SledEventDatabase::new(root.path()).unwrap());
+    let tel_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let tel_db = Arc::new(SledTelDatabase::new(tel_root.path()).unwrap());
+    let kel_processor = EventProcessor::new(Arc::clone(&kel_db));
+    let tel_processor = TelProcessor::new(Arc::clone(&kel_db), tel_db);
+
+    // Incept issuer.
+    let km = CryptoBox::new()?;
+    let sign = |event: EventMessage<_>| -> Result<Message, Error> {
+        let signature = km.sign(&event.serialize()?)?;
+        Ok(Message::Event(event.sign(
+            vec![AttachedSignaturePrefix::new(
+                SelfSigning::Ed25519Sha512,
+                signature,
+                0,
+            )],
+            None,
+        )))
+    };
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(vec![Basic::Ed25519.derive(km.public_key())])
+        .with_next_keys(vec![Basic::Ed25519.derive(km.next_public_key())])
+        .build()?;
+    let issuer = icp.event.get_prefix();
+    let icp_seal = SourceSeal::new(0, icp.get_digest());
+    kel_processor.process(sign(icp)?)?;
+
+    // Anchors TEL event in issuer's ixn and returns source seal of it.
+    let anchor = |tel_event: &EventMessage<TelEvent>| -> Result<SourceSeal, Error> {
+        let state = kel_processor.compute_state(&issuer)?.unwrap();
+        let ixn = EventMsgBuilder::new(EventTypeTag::Ixn)
+            .with_prefix(&issuer)
+            .with_sn(state.sn + 1)
+            .with_previous_event(&state.last_event_digest)
+            .with_seal(vec![Seal::Event(tel_event.to_seal())])
+            .build()?;
+        let seal = SourceSeal::new(state.sn + 1, ixn.get_digest());
+        kel_processor.process(sign(ixn)?)?;
+        Ok(seal)
+    };
+
+    let vcp = TelEvent::new_registry_inception(
+        &issuer,
+        vec![NO_BACKERS.into()],
+        0,
+        vec![],
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?;
+    let registry_id = vcp.event.get_prefix();
+    assert_eq!(
+        registry_id,
+        IdentifierPrefix::SelfAddressing(vcp.get_digest())
+    );
+    let parsed: EventMessage<TelEvent> = serde_json::from_slice(&vcp.serialize()?).unwrap();
+    assert_eq!(parsed, vcp);
+    assert!(parsed.check_digest()?);
+
+    // Vcp which isn't anchored in issuer KEL is rejected.
+    let not_anchored = VerifiableTelEvent::new(vcp.clone(), icp_seal);
+    assert!(tel_processor.process(not_anchored).is_err());
+    assert_eq!(tel_processor.get_management_tel_state(&registry_id)?, None);
+
+    let seal = anchor(&vcp)?;
+    let registry = match tel_processor.process(VerifiableTelEvent::new(vcp, seal))? {
+        TelState::Management(state) => state,
+        _ => panic!("Expected management TEL state"),
+    };
+    assert_eq!(registry.issuer, issuer);
+    assert_eq!(registry.backers, None);
+    assert_eq!(
+        tel_processor.get_management_tel_state(&registry_id)?,
+        Some(registry.clone())
+    );
+
+    // Registry without backers can't be rotated.
+    let vrt = TelEvent::new_registry_rotation(
+        &registry,
+        0,
+        vec![],
+        vec![],
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?;
+    let seal = anchor(&vrt)?;
+    assert!(tel_processor
+        .process(VerifiableTelEvent::new(vrt, seal))
+        .is_err());
+
+    // Issue credential.
+    let vc_id = SelfAddressing::Blake3_256.derive(br#"{"name":"credential"}"#);
+    let vc_prefix = IdentifierPrefix::SelfAddressing(vc_id.clone());
+    let iss = TelEvent::new_issuance(
+        &registry,
+        &vc_id,
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?;
+    let seal = anchor(&iss)?;
+    tel_processor.process(VerifiableTelEvent::new(iss, seal))?;
+    let vc_state = tel_processor.get_vc_state(&vc_prefix)?.unwrap();
+    assert_eq!(vc_state.status, VcStatus::Issued);
+    assert_eq!(vc_state.registry_id, registry_id);
+
+    // Revoke credential.
+    let rev = TelEvent::new_revocation(
+        &registry,
+        &vc_state,
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )?;
+    let seal = anchor(&rev)?;
+    tel_processor.process(VerifiableTelEvent::new(rev.clone(), seal.clone()))?;
+    let vc_state = tel_processor.get_vc_state(&vc_prefix)?.unwrap();
+    assert_eq!(vc_state.status, VcStatus::Revoked);
+    assert_eq!(vc_state.sn, 1);
+    assert_eq!(tel_processor.get_tel(&vc_prefix).len(), 2);
+
+    // Revoked credential can't be revoked again.
+    assert!(tel_processor
+        .process(VerifiableTelEvent::new(rev, seal))
+        .is_err());
+    assert!(TelEvent::new_revocation(
+        &registry,
+        &vc_state,
+        SerializationFormats::JSON,
+        &SelfAddressing::Blake3_256,
+    )
+    .is_err());
+
+    Ok(())
+}
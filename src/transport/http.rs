@@ -0,0 +1,360 @@
+//! HTTP transport
+//!
+//! Indirect mode exchange over HTTP. CESR streams are sent in request
+//! and response bodies with `application/cesr` content type. Requests
+//! may be signed by sender's identifier in `Signature` header, which
+//! carries indexed signatures of the body, or of the request path when
+//! there is no body.
+
+use std::{collections::HashMap, io::Read, net::SocketAddr, str::FromStr};
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    error::Error,
+    event_parsing::message::signed_event_stream,
+    keri::Keri,
+    mailbox::Mailbox,
+    prefix::{AttachedSignaturePrefix, IdentifierPrefix, Prefix},
+    signer::KeyManager,
+    transport::Transport,
+};
+
+pub const CESR_CONTENT_TYPE: &str = "application/cesr";
+pub const SIGNATURE_HEADER: &str = "Signature";
+
+/// Signature Header
+///
+/// Builds value of `Signature` header, eg.
+/// `indexed="?1";signer="D...";0="AA..."`.
+pub fn signature_header(
+    signer: &IdentifierPrefix,
+    signatures: &[AttachedSignaturePrefix],
+) -> String {
+    let signatures = signatures
+        .iter()
+        .map(|sig| format!(";{}=\"{}\"", sig.index, sig.to_str()));
+    format!("indexed=\"?1\";signer=\"{}\"", signer.to_str()) + &signatures.collect::<String>()
+}
+
+/// Parses value of `Signature` header into signer and its signatures.
+pub fn parse_signature_header(
+    value: &str,
+) -> Result<(IdentifierPrefix, Vec<AttachedSignaturePrefix>), Error> {
+    let mut signer = None;
+    let mut signatures = vec![];
+    for param in value.split(';') {
+        let (key, value) = param
+            .split_once('=')
+            .ok_or_else(|| Error::DeserializeError("Improper signature header".into()))?;
+        let value = value.trim().trim_matches('"');
+        match key.trim() {
+            "indexed" => (),
+            "signer" => signer = Some(IdentifierPrefix::from_str(value)?),
+            _ => signatures.push(AttachedSignaturePrefix::from_str(value)?),
+        }
+    }
+    let signer = signer
+        .ok_or_else(|| Error::DeserializeError("Missing signer in signature header".into()))?;
+    Ok((signer, signatures))
+}
+
+/// HTTP Client
+///
+/// Sends CESR streams to HTTP endpoints of other controllers, witnesses
+/// or mailboxes, signing every request with identifier of `signer`.
+pub struct HttpClient<'a, K: KeyManager + 'static> {
+    agent: ureq::Agent,
+    signer: &'a Keri<K>,
+    // URLs by qb64 of identifier
+    endpoints: HashMap<String, String>,
+}
+
+impl<'a, K: KeyManager> HttpClient<'a, K> {
+    pub fn new(signer: &'a Keri<K>) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            signer,
+            endpoints: HashMap::new(),
+        }
+    }
+
+    /// Sets URL used to reach identifier `id` through `Transport`.
+    pub fn with_endpoint(mut self, id: &IdentifierPrefix, url: &str) -> Self {
+        self.endpoints
+            .insert(id.to_str(), url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Posts event stream to `url` and returns response stream, eg.
+    /// receipts of posted events.
+    pub fn post(&self, url: &str, stream: &[u8]) -> Result<Vec<u8>, Error> {
+        self.send(self.agent.post(url), stream, stream)
+    }
+
+    /// Puts event stream to `url`. Receiver doesn't respond with
+    /// receipts, they may be collected from its mailbox later.
+    pub fn put(&self, url: &str, stream: &[u8]) -> Result<(), Error> {
+        self.send(self.agent.put(url), stream, stream).map(|_| ())
+    }
+
+    /// Gets messages stored for `id` in mailbox served at `url`.
+    pub fn poll_mailbox(
+        &self,
+        url: &str,
+        id: &IdentifierPrefix,
+        topic: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let path = format!("/mailbox/{}{}", id.to_str(), topic);
+        let request = self
+            .agent
+            .get(&format!("{}{}", url.trim_end_matches('/'), path));
+        // There is no body, so the path is signed.
+        self.send(request, path.as_bytes(), &[])
+    }
+
+    fn send(&self, request: ureq::Request, signed: &[u8], body: &[u8]) -> Result<Vec<u8>, Error> {
+        let signatures = self.signer.sign(signed)?;
+        let response = request
+            .set("Content-Type", CESR_CONTENT_TYPE)
+            .set(
+                SIGNATURE_HEADER,
+                &signature_header(self.signer.prefix(), &signatures),
+            )
+            .send_bytes(body)
+            .map_err(|e| Error::TransportError(e.to_string()))?;
+        let mut buf = vec![];
+        response.into_reader().read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<'a, K: KeyManager> Transport for HttpClient<'a, K> {
+    fn send_message(&self, to: &IdentifierPrefix, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let url = self
+            .endpoints
+            .get(&to.to_str())
+            .ok_or_else(|| Error::TransportError(format!("Unknown endpoint of {}", to.to_str())))?;
+        self.post(url, msg)
+    }
+}
+
+/// HTTP Server
+///
+/// Serves event processor of `keri` over HTTP:
+///  * `POST /` processes event stream and responds with receipts,
+///  * `PUT /` processes event stream without responding,
+///  * `GET /mailbox/{id}/{topic}` returns messages stored for `id`,
+///    to `id` only.
+///
+/// Processed messages meant for other identifiers are stored in their
+/// mailboxes, see `Mailbox::forward`.
+///
+/// Signed requests are rejected if signature doesn't verify against
+/// signer's current keys. If signer is not known yet, its KEL is
+/// expected in the body, so signature is checked after processing.
+pub struct HttpServer<'a, K: KeyManager + 'static> {
+    keri: &'a Keri<K>,
+    mailbox: Mailbox,
+    server: Server,
+}
+
+impl<'a, K: KeyManager> HttpServer<'a, K> {
+    pub fn bind(keri: &'a Keri<K>, addr: impl std::net::ToSocketAddrs) -> Result<Self, Error> {
+        let server = Server::http(addr).map_err(|e| Error::TransportError(e.to_string()))?;
+        Ok(Self {
+            keri,
+            mailbox: Mailbox::new(keri.db()),
+            server,
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Serves requests until the server fails.
+    pub fn serve(&self) -> Result<(), Error> {
+        loop {
+            self.handle_next()?;
+        }
+    }
+
+    /// Waits for next request and responds to it.
+    pub fn handle_next(&self) -> Result<(), Error> {
+        let mut request = self.server.recv()?;
+        let signature = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv(SIGNATURE_HEADER))
+            .map(|header| header.value.to_string());
+        let mut body = vec![];
+        request.as_reader().read_to_end(&mut body)?;
+        let (status, response) = match self.respond(&request, signature.as_deref(), &body) {
+            Ok(response) => response,
+            Err(e) => (400, e.to_string().into_bytes()),
+        };
+        let content_type = Header::from_bytes("Content-Type", CESR_CONTENT_TYPE)
+            .map_err(|_| Error::TransportError("Improper header".into()))?;
+        request.respond(
+            Response::from_data(response)
+                .with_status_code(status)
+                .with_header(content_type),
+        )?;
+        Ok(())
+    }
+
+    fn respond(
+        &self,
+        request: &Request,
+        signature: Option<&str>,
+        body: &[u8],
+    ) -> Result<(u16, Vec<u8>), Error> {
+        let signed = if body.is_empty() {
+            request.url().as_bytes()
+        } else {
+            body
+        };
+        let signature = signature.map(parse_signature_header).transpose()?;
+        let verified = |(signer, signatures): &(IdentifierPrefix, Vec<AttachedSignaturePrefix>)| {
+            self.keri
+                .verify(signer, signed, signatures)
+                .unwrap_or(false)
+        };
+        // Signer may be introduced by the body itself.
+        let known_signer = match &signature {
+            Some((signer, _)) => self.keri.get_state_for_prefix(signer)?.is_some(),
+            None => true,
+        };
+        if known_signer && !signature.iter().all(verified) {
+            return Ok((401, vec![]));
+        }
+        let response = match (request.method(), request.url()) {
+            (Method::Post, "/") | (Method::Put, "/") => {
+                let response = self.keri.respond(body)?;
+                let (_, messages) = signed_event_stream(body)
+                    .map_err(|e| Error::DeserializeError(e.to_string()))?;
+                // Messages which can't be processed aren't forwarded.
+                for message in messages {
+                    self.mailbox.forward(message).ok();
+                }
+                response
+            }
+            (Method::Get, path) if path.starts_with("/mailbox/") => {
+                let (id, topic) = path["/mailbox/".len()..]
+                    .split_once('/')
+                    .ok_or_else(|| Error::DeserializeError("Missing mailbox topic".into()))?;
+                let id = IdentifierPrefix::from_str(id)?;
+                return match &signature {
+                    Some((signer, _)) if known_signer && *signer == id => Ok((
+                        200,
+                        self.mailbox
+                            .get(&id, topic.parse()?, 0)
+                            .into_iter()
+                            .flat_map(|msg| msg.message)
+                            .collect(),
+                    )),
+                    _ => Ok((401, vec![])),
+                };
+            }
+            _ => return Ok((404, vec![])),
+        };
+        if !known_signer && !signature.iter().all(verified) {
+            return Ok((401, vec![]));
+        }
+        match request.method() {
+            Method::Put => Ok((204, vec![])),
+            _ => Ok((200, response)),
+        }
+    }
+}
+
+#[test]
+fn test_signature_header() -> Result<(), Error> {
+    let signer: IdentifierPrefix = "DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA".parse()?;
+    let signatures: Vec<AttachedSignaturePrefix> = vec![
+        "AA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw"
+            .parse()?,
+    ];
+    let header = signature_header(&signer, &signatures);
+    assert_eq!(
+        header,
+        "indexed=\"?1\";signer=\"DSuhyBcPZEZLK-fcw5tzHn2N46wRCG_ZOoeKtWTOunRA\";0=\"AA0aSisI4ZZTH_6JCqsvAsEpuf_Jq6bDbvPWj_eCDnAGbSARqYHipNs-9W7MHnwnMfIXwLpcoJkKGrQ-SiaklhAw\""
+    );
+    assert_eq!(parse_signature_header(&header)?, (signer, signatures));
+    assert!(parse_signature_header("indexed=\"?1\"").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_http_transport() -> Result<(), Error> {
+    use crate::{
+        database::sled::SledEventDatabase, event_parsing::SignedEventData, signer::CryptoBox,
+    };
+    use std::sync::{Arc, Mutex};
+    use tempfile::Builder;
+
+    let controller = |root: &std::path::Path| -> Result<Keri<CryptoBox>, Error> {
+        let db = Arc::new(SledEventDatabase::new(root)?);
+        let mut keri = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+        keri.incept(None)?;
+        Ok(keri)
+    };
+    let alice_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice = controller(alice_root.path())?;
+    let bob_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let bob = controller(bob_root.path())?;
+    let eve_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let eve = controller(eve_root.path())?;
+
+    let server = HttpServer::bind(&bob, "127.0.0.1:0")?;
+    let url = format!("http://{}", server.local_addr().unwrap());
+    std::thread::scope(|s| -> Result<(), Error> {
+        // Error is not `Send` with all features, so it goes as string.
+        let bob_side =
+            s.spawn(|| (0..6).try_for_each(|_| server.handle_next().map_err(|e| e.to_string())));
+
+        // Alice introduces herself and gets bob's KEL with receipt.
+        let client = HttpClient::new(&alice).with_endpoint(bob.prefix(), &url);
+        let response = client.send_message(bob.prefix(), &alice.get_kerl()?.unwrap())?;
+        alice.respond(&response)?;
+        assert_eq!(alice.get_state_for_prefix(bob.prefix())?, bob.get_state()?);
+
+        // Known signer can't be impersonated.
+        let eve_signatures = eve.sign(b"fake")?;
+        let forged = ureq::post(&url)
+            .set(
+                SIGNATURE_HEADER,
+                &signature_header(alice.prefix(), &eve_signatures),
+            )
+            .send_bytes(b"fake");
+        assert!(matches!(forged, Err(ureq::Error::Status(401, _))));
+
+        // Unknown path.
+        assert!(client.post(&format!("{}/unknown", url), b"fake").is_err());
+
+        // Alice's receipt waits in bob's mailbox, only for bob.
+        let bob_icp = bob
+            .db()
+            .get_kel_finalized_events(bob.prefix())
+            .unwrap()
+            .next()
+            .unwrap();
+        let rct: SignedEventData = alice
+            .make_rct(bob_icp.signed_event_message.event_message)?
+            .into();
+        client.put(&url, &rct.to_cesr()?)?;
+        let mailbox = HttpClient::new(&bob).poll_mailbox(&url, bob.prefix(), "/receipt")?;
+        assert_eq!(mailbox, rct.to_cesr()?);
+        assert!(client.poll_mailbox(&url, bob.prefix(), "/receipt").is_err());
+
+        bob_side.join().unwrap().map_err(Error::TransportError)
+    })?;
+    assert_eq!(
+        bob.get_state_for_prefix(alice.prefix())?,
+        alice.get_state()?
+    );
+
+    Ok(())
+}
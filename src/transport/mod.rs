@@ -0,0 +1,21 @@
+//! Transport
+//!
+//! Abstraction over the channel used to exchange KERI messages between
+//! instances. Implementations deliver a CESR stream to the instance
+//! controlling the given identifier and hand back its response stream.
+
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "quic")]
+pub mod quic;
+pub mod tcp;
+
+use crate::{error::Error, prefix::IdentifierPrefix};
+
+pub trait Transport {
+    /// Send Message
+    ///
+    /// Delivers serialized `msg` to the controller of `to` and returns
+    /// the bytes it responded with.
+    fn send_message(&self, to: &IdentifierPrefix, msg: &[u8]) -> Result<Vec<u8>, Error>;
+}
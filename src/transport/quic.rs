@@ -0,0 +1,236 @@
+//! QUIC transport
+//!
+//! Low latency exchange between nodes, eg. witness gossip, over QUIC.
+//! Every exchange goes through its own bidirectional stream: CESR stream
+//! is written and finished, and responses are read back. Incoming
+//! streams are framed with `KeriCodec`, so messages are reassembled from
+//! QUIC frames and processed one by one, while stream flow control
+//! gives backpressure to the sender. Messages small enough may also be
+//! sent as unreliable datagrams.
+
+use std::{fmt::Display, future::poll_fn, net::SocketAddr, pin::Pin, sync::Arc};
+
+use futures_core::Stream;
+use quinn::{ClientConfig, Connection, Endpoint, RecvStream, SendStream, ServerConfig};
+use tokio_util::codec::FramedRead;
+
+use crate::{
+    error::Error,
+    event_parsing::{codec::KeriCodec, SignedEventData},
+    keri::Keri,
+    signer::KeyManager,
+};
+
+/// Max size of response read back from the peer.
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+fn quic_error(e: impl Display) -> Error {
+    Error::TransportError(e.to_string())
+}
+
+/// QUIC Node
+///
+/// Endpoint serving event processor of `keri` to connected peers and
+/// sending event streams to peers it connects to. Node identifies itself
+/// with self signed certificate generated on bind, which peers have to
+/// trust to connect.
+pub struct QuicNode<K: KeyManager + 'static> {
+    keri: Arc<Keri<K>>,
+    endpoint: Endpoint,
+    certificate: Vec<u8>,
+}
+
+impl<K: KeyManager + Send + 'static> QuicNode<K> {
+    /// Binds node to `addr` with certificate issued for `server_name`.
+    pub fn bind(keri: Arc<Keri<K>>, addr: SocketAddr, server_name: &str) -> Result<Self, Error> {
+        let cert =
+            rcgen::generate_simple_self_signed(vec![server_name.into()]).map_err(quic_error)?;
+        let certificate = cert.serialize_der().map_err(quic_error)?;
+        let config = ServerConfig::with_single_cert(
+            vec![rustls::Certificate(certificate.clone())],
+            rustls::PrivateKey(cert.serialize_private_key_der()),
+        )
+        .map_err(quic_error)?;
+        Ok(Self {
+            keri,
+            endpoint: Endpoint::server(config, addr)?,
+            certificate,
+        })
+    }
+
+    /// DER encoded certificate of the node.
+    pub fn certificate(&self) -> &[u8] {
+        &self.certificate
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.endpoint.local_addr()?)
+    }
+
+    /// Sets certificates of peers the node can connect to.
+    pub fn trust(&mut self, certificates: &[Vec<u8>]) -> Result<(), Error> {
+        let mut roots = rustls::RootCertStore::empty();
+        for certificate in certificates {
+            roots
+                .add(&rustls::Certificate(certificate.clone()))
+                .map_err(quic_error)?;
+        }
+        self.endpoint
+            .set_default_client_config(ClientConfig::with_root_certificates(roots));
+        Ok(())
+    }
+
+    pub async fn connect(&self, addr: SocketAddr, server_name: &str) -> Result<Connection, Error> {
+        self.endpoint
+            .connect(addr, server_name)
+            .map_err(quic_error)?
+            .await
+            .map_err(quic_error)
+    }
+
+    /// Sends event stream to the peer in new stream and returns its
+    /// response, eg. receipts.
+    pub async fn send(&self, connection: &Connection, stream: &[u8]) -> Result<Vec<u8>, Error> {
+        let (mut send, mut recv) = connection.open_bi().await.map_err(quic_error)?;
+        send.write_all(stream).await.map_err(quic_error)?;
+        send.finish().await.map_err(quic_error)?;
+        recv.read_to_end(MAX_RESPONSE_SIZE)
+            .await
+            .map_err(quic_error)
+    }
+
+    /// Sends message as single datagram, without waiting for response.
+    /// Message may be lost, so it's meant for data which is gossiped
+    /// repeatedly anyway.
+    pub fn send_datagram(
+        &self,
+        connection: &Connection,
+        message: &SignedEventData,
+    ) -> Result<(), Error> {
+        let data = message.to_cesr()?;
+        match connection.max_datagram_size() {
+            Some(max) if data.len() <= max => {
+                connection.send_datagram(data.into()).map_err(quic_error)
+            }
+            _ => Err(Error::TransportError(
+                "Message doesn't fit in datagram".into(),
+            )),
+        }
+    }
+
+    /// Serves incoming connections until the endpoint is closed.
+    pub async fn serve(&self) {
+        while let Some(connecting) = self.endpoint.accept().await {
+            let keri = self.keri.clone();
+            tokio::spawn(async move {
+                if let Ok(connection) = connecting.await {
+                    handle_connection(keri, connection).await;
+                }
+            });
+        }
+    }
+
+    /// Stops accepting connections and closes existing ones.
+    pub fn close(&self) {
+        self.endpoint.close(0u32.into(), b"closed");
+    }
+}
+
+async fn handle_connection<K: KeyManager + Send + 'static>(
+    keri: Arc<Keri<K>>,
+    connection: Connection,
+) {
+    loop {
+        tokio::select! {
+            stream = connection.accept_bi() => match stream {
+                Ok((send, recv)) => {
+                    tokio::spawn(handle_stream(keri.clone(), send, recv));
+                }
+                Err(_) => return,
+            },
+            datagram = connection.read_datagram() => match datagram {
+                // Nobody waits for response of datagram.
+                Ok(datagram) => {
+                    keri.respond(&datagram).ok();
+                }
+                Err(_) => return,
+            },
+        }
+    }
+}
+
+async fn handle_stream<K: KeyManager + Send + 'static>(
+    keri: Arc<Keri<K>>,
+    mut send: SendStream,
+    recv: RecvStream,
+) {
+    let mut framed = FramedRead::new(recv, KeriCodec::new());
+    loop {
+        // Unparsable rest of the stream is dropped by the codec.
+        let message = match poll_fn(|cx| Pin::new(&mut framed).poll_next(cx)).await {
+            Some(Ok(message)) => message,
+            _ => break,
+        };
+        let response = keri.respond_events(vec![message]).unwrap_or_default();
+        if send.write_all(&response).await.is_err() {
+            return;
+        }
+    }
+    send.finish().await.ok();
+}
+
+#[tokio::test]
+async fn test_quic_node() -> Result<(), Error> {
+    use crate::{database::sled::SledEventDatabase, signer::CryptoBox};
+    use std::{sync::Mutex, time::Duration};
+    use tempfile::Builder;
+
+    let controller = |root: &std::path::Path| -> Result<Keri<CryptoBox>, Error> {
+        let db = Arc::new(SledEventDatabase::new(root)?);
+        let mut keri = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+        keri.incept(None)?;
+        Ok(keri)
+    };
+    let alice_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice = Arc::new(controller(alice_root.path())?);
+    let bob_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let bob = Arc::new(controller(bob_root.path())?);
+
+    let localhost = "127.0.0.1:0".parse().unwrap();
+    let bob_node = QuicNode::bind(bob.clone(), localhost, "localhost")?;
+    let bob_addr = bob_node.local_addr()?;
+    let bob_certificate = bob_node.certificate().to_vec();
+    let server = tokio::spawn(async move {
+        bob_node.serve().await;
+    });
+
+    // Alice sends her KEL and gets bob's KEL with receipts.
+    let mut alice_node = QuicNode::bind(alice.clone(), localhost, "localhost")?;
+    alice_node.trust(&[bob_certificate])?;
+    let connection = alice_node.connect(bob_addr, "localhost").await?;
+    let response = alice_node
+        .send(&connection, &alice.get_kerl()?.unwrap())
+        .await?;
+    alice.respond(&response)?;
+    assert_eq!(alice.get_state_for_prefix(bob.prefix())?, bob.get_state()?);
+    assert_eq!(
+        bob.get_state_for_prefix(alice.prefix())?,
+        alice.get_state()?
+    );
+
+    // Datagram arrives eventually.
+    let ixn: SignedEventData = (&alice.anchor(&[])?).into();
+    alice_node.send_datagram(&connection, &ixn)?;
+    let mut state = None;
+    for _ in 0..50 {
+        state = bob.get_state_for_prefix(alice.prefix())?;
+        if state.as_ref().map(|s| s.sn) == Some(1) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(state.map(|s| s.sn), Some(1));
+
+    server.abort();
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! TCP direct mode
+//!
+//! Direct mode exchange between two controllers over TCP, as in keripy
+//! `directing`. Initiator sends its KEL, peer processes it and responds
+//! with own KEL and receipts, which are processed and receipted in turn
+//! until nothing is left to say.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{
+    error::Error,
+    event_parsing::{stream::StreamParser, SignedEventData},
+    keri::Keri,
+    signer::KeyManager,
+};
+
+/// How long peer may stay silent before conversation ends.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Direct Peer
+///
+/// TCP connection to other controller, driving own event processor
+/// with everything the peer sends and responding with receipts.
+pub struct DirectPeer<'a, K: KeyManager + 'static> {
+    keri: &'a Keri<K>,
+    stream: TcpStream,
+    parser: StreamParser,
+}
+
+impl<'a, K: KeyManager> DirectPeer<'a, K> {
+    /// Wraps already established connection, eg. accepted by listener.
+    pub fn new(keri: &'a Keri<K>, stream: TcpStream) -> Result<Self, Error> {
+        stream.set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+        Ok(Self {
+            keri,
+            stream,
+            parser: StreamParser::new(),
+        })
+    }
+
+    /// Connects to the peer listening at `addr`.
+    pub fn connect(keri: &'a Keri<K>, addr: impl ToSocketAddrs) -> Result<Self, Error> {
+        Self::new(keri, TcpStream::connect(addr)?)
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Result<Self, Error> {
+        self.stream.set_read_timeout(Some(timeout))?;
+        Ok(self)
+    }
+
+    /// Converse
+    ///
+    /// Sends own KEL to the peer and exchanges KELs and receipts until
+    /// peer closes connection or stays silent longer than timeout.
+    pub fn converse(&mut self) -> Result<(), Error> {
+        let kel = self
+            .keri
+            .get_kerl()?
+            .ok_or_else(|| Error::SemanticError("KEL is empty".into()))?;
+        self.stream.write_all(&kel)?;
+        self.serve()
+    }
+
+    /// Serve
+    ///
+    /// Processes messages sent by the peer and responds with receipts,
+    /// without sending anything first. Returns when peer closes
+    /// connection or stays silent longer than timeout.
+    pub fn serve(&mut self) -> Result<(), Error> {
+        let mut buf = [0u8; 4096];
+        loop {
+            let read = match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(read) => read,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(())
+                }
+                Err(e) => return Err(e.into()),
+            };
+            self.parser.feed(&buf[..read]);
+            let messages = (&mut self.parser).collect::<Result<Vec<SignedEventData>, _>>()?;
+            let response = self.keri.respond_events(messages)?;
+            if !response.is_empty() {
+                self.stream.write_all(&response)?;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_direct_peer() -> Result<(), Error> {
+    use crate::{database::sled::SledEventDatabase, signer::CryptoBox};
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+    use tempfile::Builder;
+
+    let controller = |root: &std::path::Path| -> Result<Keri<CryptoBox>, Error> {
+        let db = Arc::new(SledEventDatabase::new(root)?);
+        let mut keri = Keri::new(db, Arc::new(Mutex::new(CryptoBox::new()?)))?;
+        keri.incept(None)?;
+        Ok(keri)
+    };
+    let alice_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let alice = controller(alice_root.path())?;
+    let bob_root = Builder::new().prefix("test-db").tempdir().unwrap();
+    let bob = controller(bob_root.path())?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    std::thread::scope(|s| -> Result<(), Error> {
+        // Error is not `Send` with all features, so it goes as string.
+        let bob_side = s.spawn(|| {
+            let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
+            DirectPeer::new(&bob, stream)
+                .and_then(|peer| peer.with_timeout(Duration::from_millis(500)))
+                .and_then(|mut peer| peer.serve())
+                .map_err(|e| e.to_string())
+        });
+        DirectPeer::connect(&alice, addr)?
+            .with_timeout(Duration::from_millis(500))?
+            .converse()?;
+        bob_side.join().unwrap().map_err(Error::TransportError)
+    })?;
+
+    // Both KELs were exchanged and receipted.
+    assert_eq!(alice.get_state_for_prefix(bob.prefix())?, bob.get_state()?);
+    assert_eq!(
+        bob.get_state_for_prefix(alice.prefix())?,
+        alice.get_state()?
+    );
+    assert!(alice.db().get_receipts_t(alice.prefix()).is_some());
+    assert!(bob.db().get_receipts_t(bob.prefix()).is_some());
+
+    Ok(())
+}
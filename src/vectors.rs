@@ -0,0 +1,206 @@
+//! Test Vectors
+//!
+//! Conformance checks against externally generated streams (eg. KELs
+//! produced by keripy), to catch serialization and validation drift
+//! between implementations. Vector is a JSON file with CESR stream and
+//! states of identifiers expected after processing it. Vectors shipped
+//! with the crate are kept in `tests/vectors`.
+//!
+//! Vectors shipped with the crate come from keripy tests: both streams
+//! and expected states were emitted by keripy, as recorded in their
+//! `provenance`. `tests/vectors/keripy_vectors.py` recomputes expected
+//! states of a stream with keripy event processor. Vectors can be
+//! generated by keriox too, so other implementations can check them the
+//! same way.
+
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::memory::MemoryDatabase,
+    error::Error,
+    event_message::signed_event_message::Message,
+    event_parsing::{message::signed_event_stream, EventType},
+    prefix::{BasicPrefix, IdentifierPrefix, SelfAddressingPrefix},
+    processor::EventProcessor,
+    state::IdentifierState,
+};
+
+/// Part of identifier state compared by conformance checks.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpectedState {
+    #[serde(rename = "i")]
+    pub prefix: IdentifierPrefix,
+
+    #[serde(rename = "s", with = "crate::hex")]
+    pub sn: u64,
+
+    #[serde(rename = "d")]
+    pub last_event_digest: SelfAddressingPrefix,
+
+    #[serde(rename = "k")]
+    pub public_keys: Vec<BasicPrefix>,
+
+    #[serde(rename = "b", default)]
+    pub witnesses: Vec<BasicPrefix>,
+}
+
+impl From<&IdentifierState> for ExpectedState {
+    fn from(state: &IdentifierState) -> Self {
+        Self {
+            prefix: state.prefix.clone(),
+            sn: state.sn,
+            last_event_digest: state.last_event_digest.clone(),
+            public_keys: state.current.public_keys.clone(),
+            witnesses: state.witnesses.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TestVector {
+    pub name: String,
+
+    /// Implementation which produced the expected states, eg. `keripy`.
+    pub source: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    /// How the stream and expected states were obtained.
+    #[serde(default)]
+    pub provenance: String,
+
+    /// Messages with attachments, in CESR text domain.
+    pub stream: String,
+
+    pub expected: Vec<ExpectedState>,
+}
+
+impl TestVector {
+    /// Builds vector of `stream`, expecting states which keriox computes
+    /// for all identifiers of key events in the stream.
+    pub fn generate(name: &str, source: &str, stream: &[u8]) -> Result<Self, Error> {
+        let (processor, prefixes) = process(stream)?;
+        let expected = prefixes
+            .iter()
+            .map(|prefix| {
+                processor
+                    .compute_state(prefix)?
+                    .map(|state| ExpectedState::from(&state))
+                    .ok_or_else(|| Error::UnknownIdentifier(prefix.clone()))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            name: name.into(),
+            source: source.into(),
+            description: String::default(),
+            provenance: "Expected states computed by keriox event processor".into(),
+            stream: String::from_utf8(stream.to_vec())
+                .map_err(|e| Error::SerializationError(e.to_string()))?,
+            expected,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Writes vector as `<name>.json` file in `dir`.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, Error> {
+        let path = dir.join(format!("{}.json", self.name));
+        fs::write(&path, serde_json::to_vec_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Processes the stream in fresh in-memory database and compares
+    /// resulting states with the expected ones.
+    pub fn check(&self) -> Result<(), Error> {
+        let (processor, _) = process(self.stream.as_bytes())
+            .map_err(|e| Error::SemanticError(format!("{}: {}", self.name, e)))?;
+        for expected in &self.expected {
+            let state = processor.compute_state(&expected.prefix)?;
+            let actual = state.as_ref().map(ExpectedState::from);
+            if actual.as_ref() != Some(expected) {
+                return Err(Error::SemanticError(format!(
+                    "{}: expected state {:?}, got {:?}",
+                    self.name, expected, actual
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads all vectors from JSON files of `dir`, ordered by file name.
+pub fn load_dir(dir: &Path) -> Result<Vec<TestVector>, Error> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+    paths.iter().map(|path| TestVector::load(path)).collect()
+}
+
+/// Processes all messages of `stream`. Returns the processor and
+/// identifiers of key events in order of their first appearance.
+fn process(
+    stream: &[u8],
+) -> Result<(EventProcessor<MemoryDatabase>, Vec<IdentifierPrefix>), Error> {
+    let messages = match signed_event_stream(stream) {
+        Ok(([], messages)) => messages,
+        _ => return Err(Error::DeserializeError("Can't parse stream".into())),
+    };
+    let processor = EventProcessor::new(Arc::new(MemoryDatabase::new()));
+    let mut prefixes = vec![];
+    for message in messages {
+        if let EventType::KeyEvent(event, _) = &message.deserialized_event {
+            let prefix = event.event.get_prefix();
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+        processor.process(Message::try_from(message)?)?;
+    }
+    Ok((processor, prefixes))
+}
+
+#[test]
+fn test_vectors() -> Result<(), Error> {
+    use tempfile::Builder;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors");
+    let vectors = load_dir(&dir)?;
+    assert!(!vectors.is_empty());
+    for vector in &vectors {
+        vector.check()?;
+    }
+
+    // States computed by keriox match the ones emitted by keripy.
+    for vector in &vectors {
+        assert_eq!(vector.source, "keripy");
+        assert!(!vector.provenance.is_empty());
+        let generated = TestVector::generate(&vector.name, "keriox", vector.stream.as_bytes())?;
+        assert_eq!(generated.expected, vector.expected);
+    }
+
+    // Generated vector survives saving.
+    let kevery = vectors.iter().find(|v| v.name == "kevery").unwrap();
+    let generated = TestVector::generate("kevery", "keriox", kevery.stream.as_bytes())?;
+    let root = Builder::new().prefix("vectors").tempdir().unwrap();
+    let path = generated.save(root.path())?;
+    assert_eq!(TestVector::load(&path)?, generated);
+
+    // Drift is reported.
+    let mut drifted = kevery.clone();
+    drifted.expected[0].sn += 1;
+    assert!(drifted.check().is_err());
+
+    Ok(())
+}
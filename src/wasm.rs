@@ -0,0 +1,213 @@
+//! WASM Bindings
+//!
+//! `wasm-bindgen` wrappers which let javascript code (eg. browser
+//! wallets) build, sign, parse and verify KERI messages. Private keys
+//! are never kept here: events are built from public keys and signed
+//! with seeds injected by the caller. Verification is stateless, every
+//! call processes given KEL in a fresh in-memory database.
+//!
+//! Keys, signatures and identifiers are passed in their qb64
+//! representation, lists of them as JSON arrays. Errors are thrown as
+//! javascript strings.
+
+use std::{convert::TryFrom, str::FromStr, sync::Arc};
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    database::memory::MemoryDatabase,
+    derivation::self_signing::SelfSigning,
+    error::Error,
+    event::sections::seal::LastEstablishmentSeal,
+    event_message::{
+        event_msg_builder::EventMsgBuilder, signed_event_message::Message, EventTypeTag,
+    },
+    event_parsing::{message::signed_event_stream, Attachment, EventType, SignedEventData},
+    prefix::{AttachedSignaturePrefix, BasicPrefix, IdentifierPrefix, Prefix, SeedPrefix},
+    processor::EventProcessor,
+};
+
+fn to_js(e: Error) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+fn parse_list<T: FromStr<Err = Error>>(json: &str) -> Result<Vec<T>, Error> {
+    serde_json::from_str::<Vec<String>>(json)?
+        .iter()
+        .map(|s| s.parse())
+        .collect()
+}
+
+fn parse_stream(stream: &[u8]) -> Result<Vec<SignedEventData>, Error> {
+    match signed_event_stream(stream) {
+        Ok((rest, messages)) if rest.is_empty() => Ok(messages),
+        _ => Err(Error::DeserializeError("Can't parse stream".into())),
+    }
+}
+
+fn event_to_json(event: &EventType) -> Result<Value, Error> {
+    Ok(match event {
+        EventType::KeyEvent(event, _) => serde_json::to_value(event)?,
+        EventType::Receipt(rct) => serde_json::to_value(rct)?,
+        #[cfg(feature = "query")]
+        EventType::Qry(qry) => serde_json::to_value(qry)?,
+        #[cfg(feature = "query")]
+        EventType::Rpy(rpy) => serde_json::to_value(rpy)?,
+        #[cfg(feature = "query")]
+        EventType::Exn(exn) => serde_json::to_value(exn)?,
+    })
+}
+
+/// Processes all messages of `kel` in fresh in-memory processor.
+/// Returns the processor and identifier of the first key event.
+fn load_kel(
+    kel: &[u8],
+) -> Result<(EventProcessor<MemoryDatabase>, Option<IdentifierPrefix>), Error> {
+    let processor = EventProcessor::new(Arc::new(MemoryDatabase::new()));
+    let mut prefix = None;
+    for data in parse_stream(kel)? {
+        if let (None, EventType::KeyEvent(event, _)) = (&prefix, &data.deserialized_event) {
+            prefix = Some(event.event.get_prefix());
+        }
+        processor.process(Message::try_from(data)?)?;
+    }
+    Ok((processor, prefix))
+}
+
+/// Builds inception event of identifier controlled by `keys`,
+/// committing to `next_keys`. Returns the serialized event, which
+/// should be signed and passed to `attach_signatures`.
+#[wasm_bindgen]
+pub fn incept(keys: &str, next_keys: &str) -> Result<String, JsValue> {
+    let icp = EventMsgBuilder::new(EventTypeTag::Icp)
+        .with_keys(parse_list::<BasicPrefix>(keys).map_err(to_js)?)
+        .with_next_keys(parse_list::<BasicPrefix>(next_keys).map_err(to_js)?)
+        .build()
+        .map_err(to_js)?;
+    let serialized = icp.serialize().map_err(to_js)?;
+    String::from_utf8(serialized).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Signs `data` with key derived from qb64 `seed`. Returns indexed
+/// signature, where `index` is the position of the key in the key list.
+#[wasm_bindgen]
+pub fn sign(seed: &str, data: &[u8], index: u16) -> Result<String, JsValue> {
+    let seed = SeedPrefix::from_str(seed).map_err(to_js)?;
+    let (_, private_key) = seed.derive_key_pair().map_err(to_js)?;
+    let (code, signature) = match seed {
+        SeedPrefix::RandomSeed256Ed25519(_) => (
+            SelfSigning::Ed25519Sha512,
+            private_key.sign_ed(data).map_err(to_js)?,
+        ),
+        SeedPrefix::RandomSeed256ECDSAsecp256k1(_) => (
+            SelfSigning::ECDSAsecp256k1Sha256,
+            private_key.sign_ecdsa(data).map_err(to_js)?,
+        ),
+        _ => return Err(to_js(Error::ImproperPrefixType)),
+    };
+    Ok(AttachedSignaturePrefix::new(code, signature, index).to_str())
+}
+
+/// Appends indexed `signatures` to serialized `event`, producing
+/// message ready to be sent or processed.
+#[wasm_bindgen]
+pub fn attach_signatures(event: &[u8], signatures: &str) -> Result<Vec<u8>, JsValue> {
+    let signatures = parse_list::<AttachedSignaturePrefix>(signatures).map_err(to_js)?;
+    let attachment = Attachment::AttachedSignatures(signatures)
+        .to_cesr()
+        .map_err(to_js)?;
+    Ok([event, attachment.as_bytes()].concat())
+}
+
+/// Parses CESR stream. Returns JSON array of parsed messages, each
+/// with its event and list of attachments in CESR.
+#[wasm_bindgen]
+pub fn parse(stream: &[u8]) -> Result<String, JsValue> {
+    let parsed = parse_stream(stream)
+        .and_then(|messages| {
+            messages
+                .iter()
+                .map(|message| {
+                    Ok(json!({
+                        "event": event_to_json(&message.deserialized_event)?,
+                        "attachments": message
+                            .attachments
+                            .iter()
+                            .map(Attachment::to_cesr)
+                            .collect::<Result<Vec<_>, _>>()?,
+                    }))
+                })
+                .collect::<Result<Vec<_>, Error>>()
+        })
+        .map_err(to_js)?;
+    Ok(Value::Array(parsed).to_string())
+}
+
+/// Verifies `kel` of single identifier. Returns JSON of identifier's
+/// state after processing all its events.
+#[wasm_bindgen]
+pub fn verify_kel(kel: &[u8]) -> Result<String, JsValue> {
+    let (processor, prefix) = load_kel(kel).map_err(to_js)?;
+    let prefix = prefix.ok_or_else(|| to_js(Error::SemanticError("Empty KEL".into())))?;
+    let state = processor
+        .compute_state(&prefix)
+        .map_err(to_js)?
+        .ok_or_else(|| to_js(Error::UnknownIdentifier(prefix.clone())))?;
+    serde_json::to_string(&state).map_err(|e| to_js(e.into()))
+}
+
+/// Checks `signatures` of `data` against current keys of `prefix`, as
+/// established by `kel`.
+#[wasm_bindgen]
+pub fn verify_signatures(
+    kel: &[u8],
+    prefix: &str,
+    data: &[u8],
+    signatures: &str,
+) -> Result<bool, JsValue> {
+    let (processor, _) = load_kel(kel).map_err(to_js)?;
+    let seal = LastEstablishmentSeal {
+        prefix: prefix.parse().map_err(to_js)?,
+    };
+    let signatures = parse_list::<AttachedSignaturePrefix>(signatures).map_err(to_js)?;
+    match processor.verify_with_last_establishment(&seal, data, &signatures) {
+        Ok(()) => Ok(true),
+        Err(Error::SignatureVerificationError) => Ok(false),
+        Err(e) => Err(to_js(e)),
+    }
+}
+
+#[test]
+fn test_wasm_bindings() -> Result<(), Error> {
+    use crate::{prefix::derive, state::IdentifierState};
+
+    let seed = "ArwXoACJgOleVZ2PY7kXn7rA0II0mHYDhc6WrBH8fDAc";
+    let next_seed = "A6zz7M08-HQSFq92sJ8KJOT2cZ47x7pXFQLPB0pckB3Q";
+    let key = |seed: &str| -> Result<String, Error> { Ok(derive(&seed.parse()?, true)?.to_str()) };
+    let keys = json!([key(seed)?]).to_string();
+    let next_keys = json!([key(next_seed)?]).to_string();
+
+    let icp = incept(&keys, &next_keys).unwrap();
+    let signature = sign(seed, icp.as_bytes(), 0).unwrap();
+    let kel = attach_signatures(icp.as_bytes(), &json!([signature]).to_string()).unwrap();
+
+    let parsed: Value = serde_json::from_str(&parse(&kel).unwrap())?;
+    assert_eq!(parsed[0]["event"]["t"], "icp");
+    assert_eq!(parsed[0]["attachments"].as_array().unwrap().len(), 1);
+    let prefix = parsed[0]["event"]["i"].as_str().unwrap().to_string();
+
+    let state: IdentifierState = serde_json::from_str(&verify_kel(&kel).unwrap())?;
+    assert_eq!(state.prefix.to_str(), prefix);
+    assert_eq!(state.sn, 0);
+
+    let data = b"some data";
+    let data_signature = json!([sign(seed, data, 0).unwrap()]).to_string();
+    assert!(verify_signatures(&kel, &prefix, data, &data_signature).unwrap());
+    assert!(!verify_signatures(&kel, &prefix, b"other data", &data_signature).unwrap());
+    // Signature made with next keys is not valid before rotation.
+    let next_signature = json!([sign(next_seed, data, 0).unwrap()]).to_string();
+    assert!(!verify_signatures(&kel, &prefix, data, &next_signature).unwrap());
+
+    Ok(())
+}
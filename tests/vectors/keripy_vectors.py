@@ -0,0 +1,52 @@
+"""Recomputes expected states of test vectors with keripy.
+
+Streams of the vectors are processed by keripy `Kevery` in a temporary
+database, and expected states are replaced with the ones of keripy
+kevers. Has to be run with keripy release which reads the serialization
+of the streams (KERI10 JSON events with single next keys digest).
+
+    python keripy_vectors.py tests/vectors/*.json
+"""
+
+import json
+import sys
+
+import keri
+from keri.core import eventing, parsing
+from keri.db import basing
+
+
+def expected_states(stream):
+    with basing.openDB(name="vectors", temp=True) as db:
+        kvy = eventing.Kevery(db=db, lax=True)
+        parsing.Parser().parse(ims=bytearray(stream.encode()), kvy=kvy)
+        states = []
+        # Kevers are kept in order of first appearance of identifiers.
+        for pre, kever in kvy.kevers.items():
+            serder = kever.serder
+            states.append({
+                "i": pre,
+                "s": format(kever.sn, "x"),
+                "d": getattr(serder, "said", None) or serder.dig,
+                "k": [verfer.qb64 for verfer in kever.verfers],
+                "b": list(kever.wits),
+            })
+        return states
+
+
+def main(paths):
+    for path in paths:
+        with open(path) as f:
+            vector = json.load(f)
+        vector["source"] = "keripy"
+        vector["provenance"] = (
+            "Expected states computed by keripy {} Kevery".format(keri.__version__)
+        )
+        vector["expected"] = expected_states(vector["stream"])
+        with open(path, "w") as f:
+            json.dump(vector, f, indent=2)
+            f.write("\n")
+
+
+if __name__ == "__main__":
+    main(sys.argv[1:])